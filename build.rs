@@ -5,8 +5,17 @@ use std::process::Command;
 fn main() {
     // Declare custom cfg for VST3 SDK availability
     println!("cargo::rustc-check-cfg=cfg(vst3_sdk)");
+    println!("cargo::rustc-check-cfg=cfg(vst2_sdk)");
+    println!("cargo::rustc-check-cfg=cfg(link_sdk)");
     let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap();
 
+    // WebAssembly builds only have the simulated plugins, so there's no
+    // native code to build or link
+    if env::var("CARGO_CFG_TARGET_FAMILY").is_ok_and(|f| f.split(',').any(|f| f == "wasm")) {
+        eprintln!("Skipping rack-sys for WebAssembly (simulated plugins only)");
+        return;
+    }
+
     // VST3 is only supported on desktop platforms (macOS, Linux, Windows)
     // Skip VST3 SDK setup on iOS/tvOS/watchOS/visionOS
     // Also skip during cargo publish to avoid modifying source directory
@@ -31,6 +40,22 @@ fn main() {
         None
     };
 
+    // VST2 is opt-in and needs the user's own SDK headers (never downloaded,
+    // as Steinberg no longer licenses the VST2 SDK)
+    let vst2_sdk_path = if env::var("CARGO_FEATURE_VST2").is_ok() && is_desktop {
+        find_vst2_sdk()
+    } else {
+        None
+    };
+
+    // Ableton Link is opt-in and needs the user's own checkout of the SDK
+    // (it's dual-licensed, GPLv2 or proprietary, so the choice is theirs)
+    let link_sdk_path = if env::var("CARGO_FEATURE_LINK").is_ok() && is_desktop {
+        find_link_sdk()
+    } else {
+        None
+    };
+
     // Check if ASAN should be enabled
     let enable_asan = env::var("CARGO_FEATURE_ASAN").is_ok() || env::var("ENABLE_ASAN").is_ok();
 
@@ -69,6 +94,20 @@ fn main() {
         println!("cargo:rustc-cfg=vst3_sdk");
     }
 
+    // Pass VST2 SDK path to CMake if available
+    if let Some(sdk_path) = &vst2_sdk_path {
+        config.define("VST2_SDK_PATH", sdk_path.to_str().unwrap());
+        eprintln!("Configuring CMake with VST2 SDK at: {}", sdk_path.display());
+        println!("cargo:rustc-cfg=vst2_sdk");
+    }
+
+    // Pass Link SDK path to CMake if available
+    if let Some(sdk_path) = &link_sdk_path {
+        config.define("LINK_SDK_PATH", sdk_path.to_str().unwrap());
+        eprintln!("Configuring CMake with Link SDK at: {}", sdk_path.display());
+        println!("cargo:rustc-cfg=link_sdk");
+    }
+
     if enable_asan {
         config.define("ENABLE_ASAN", "ON");
         eprintln!("Building with AddressSanitizer enabled");
@@ -98,6 +137,12 @@ fn main() {
         }
         "windows" => {
             // Windows uses static linking by default, no extra libs needed for C++
+            if link_sdk_path.is_some() {
+                // Link's networking and clock
+                println!("cargo:rustc-link-lib=ws2_32");
+                println!("cargo:rustc-link-lib=iphlpapi");
+                println!("cargo:rustc-link-lib=winmm");
+            }
         }
         _ => {
             eprintln!("Warning: Unsupported target OS: {}", target_os);
@@ -112,11 +157,17 @@ fn main() {
         println!("cargo:rustc-link-arg=-fno-omit-frame-pointer");
     }
 
+    // C header for the capi feature
+    #[cfg(feature = "capi")]
+    generate_c_header();
+
     // Rerun build script if C++ sources, headers, or build config changes
     println!("cargo:rerun-if-changed=rack-sys/src");
     println!("cargo:rerun-if-changed=rack-sys/include");
     println!("cargo:rerun-if-changed=rack-sys/CMakeLists.txt");
     println!("cargo:rerun-if-changed=rack-sys/external/vst3sdk");
+    println!("cargo:rerun-if-env-changed=VST2_SDK_PATH");
+    println!("cargo:rerun-if-env-changed=LINK_SDK_PATH");
 
     // Print target for debugging
     eprintln!(
@@ -126,6 +177,37 @@ fn main() {
     );
 }
 
+/// Write the C API header to target/<profile>/include/rack.h
+#[cfg(feature = "capi")]
+fn generate_c_header() {
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    // OUT_DIR is target/<profile>/build/rack-<hash>/out
+    let include_dir = out_dir.ancestors().nth(3).unwrap().join("include");
+
+    let config = cbindgen::Config {
+        language: cbindgen::Language::C,
+        include_guard: Some("RACK_H".to_string()),
+        header: Some("/* Generated by rack's build script - do not edit */".to_string()),
+        usize_is_size_t: true,
+        cpp_compat: true,
+        ..Default::default()
+    };
+    match cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(crate_dir.join("src/capi.rs"))
+        .generate()
+    {
+        Ok(bindings) => {
+            std::fs::create_dir_all(&include_dir).unwrap();
+            bindings.write_to_file(include_dir.join("rack.h"));
+            eprintln!("C header written to {}", include_dir.join("rack.h").display());
+        }
+        Err(error) => println!("cargo:warning=Couldn't generate the C header: {}", error),
+    }
+    println!("cargo:rerun-if-changed=src/capi.rs");
+}
+
 fn link_apple_frameworks(target_os: &str) {
     // AudioUnit frameworks (macOS and iOS)
     println!("cargo:rustc-link-lib=framework=AudioToolbox");
@@ -144,6 +226,58 @@ fn link_apple_frameworks(target_os: &str) {
     }
 }
 
+/// Find the VST2 SDK headers the user pointed to with `VST2_SDK_PATH`
+/// Returns the absolute SDK path, or None (with a warning) if unusable
+fn find_vst2_sdk() -> Option<PathBuf> {
+    let Some(path) = env::var_os("VST2_SDK_PATH").map(PathBuf::from) else {
+        println!("cargo:warning=The vst2 feature needs VST2_SDK_PATH set to your VST2 SDK; VST2 support is disabled");
+        return None;
+    };
+    let path = if path.is_absolute() {
+        path
+    } else {
+        env::current_dir().unwrap().join(path)
+    };
+
+    if !path.join("pluginterfaces/vst2.x/aeffectx.h").exists() {
+        println!(
+            "cargo:warning=No pluginterfaces/vst2.x/aeffectx.h under VST2_SDK_PATH ({}); VST2 support is disabled",
+            path.display()
+        );
+        return None;
+    }
+    eprintln!("VST2 SDK found at {}", path.display());
+    Some(path)
+}
+
+/// Find the Ableton Link SDK the user pointed to with `LINK_SDK_PATH`
+/// Returns the absolute SDK path, or None (with a warning) if unusable
+fn find_link_sdk() -> Option<PathBuf> {
+    let Some(path) = env::var_os("LINK_SDK_PATH").map(PathBuf::from) else {
+        println!("cargo:warning=The link feature needs LINK_SDK_PATH set to your Ableton Link checkout; Link support is disabled");
+        return None;
+    };
+    let path = if path.is_absolute() {
+        path
+    } else {
+        env::current_dir().unwrap().join(path)
+    };
+
+    // abl_link is Link's C wrapper; its asio dependency is a submodule
+    if !path.join("AbletonLinkConfig.cmake").exists()
+        || !path.join("extensions/abl_link/include/abl_link.h").exists()
+        || !path.join("modules/asio-standalone/asio/include/asio.hpp").exists()
+    {
+        println!(
+            "cargo:warning=No Ableton Link SDK (with submodules) under LINK_SDK_PATH ({}); Link support is disabled",
+            path.display()
+        );
+        return None;
+    }
+    eprintln!("Link SDK found at {}", path.display());
+    Some(path)
+}
+
 /// Ensure VST3 SDK is available, cloning it if necessary
 /// Returns the path to the VST3 SDK, or None if unavailable
 fn ensure_vst3_sdk() -> Option<PathBuf> {
@@ -75,7 +75,7 @@ fn main() -> Result<()> {
             println!("    Range: {:.2} - {:.2}", param.min, param.max);
             println!("    Default: {:.2}", param.default);
             println!("    Current: {:.2} (normalized: {:.4})",
-                     denormalize(value, param.min, param.max),
+                     param.to_plain(Normalized::new(value)).value(),
                      value);
             println!();
         }
@@ -92,7 +92,7 @@ fn main() -> Result<()> {
             // Get original value
             let original_value = plugin.get_parameter(0)?;
             println!("  Original value: {:.4} (normalized)", original_value);
-            println!("  Actual value: {:.2}", denormalize(original_value, param.min, param.max));
+            println!("  Actual value: {:.2}", plugin.get_parameter_plain(0)?.value());
             println!();
 
             // Set to different values
@@ -101,7 +101,7 @@ fn main() -> Result<()> {
             for &normalized_value in &test_values {
                 plugin.set_parameter(0, normalized_value)?;
                 let actual = plugin.get_parameter(0)?;
-                let denorm = denormalize(actual, param.min, param.max);
+                let denorm = param.to_plain(Normalized::new(actual)).value();
 
                 println!("    Set to {:.2} → Read back: {:.4} (actual: {:.2})",
                          normalized_value, actual, denorm);
@@ -122,8 +122,3 @@ fn main() -> Result<()> {
     println!("No effect plugin found to demonstrate parameters");
     Ok(())
 }
-
-/// Helper function to denormalize a 0.0-1.0 value to actual parameter range
-fn denormalize(normalized: f32, min: f32, max: f32) -> f32 {
-    min + (normalized * (max - min))
-}
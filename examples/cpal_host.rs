@@ -4,7 +4,7 @@
 //! - Finding and loading an instrument plugin (synthesizer)
 //! - Setting up CPAL for real-time audio output
 //! - Processing audio through the plugin and playing it through speakers
-//! - Sending MIDI note events with automatic note-off scheduling
+//! - Scheduling MIDI notes with sample-accurate note-offs through an event port
 //! - Opening and displaying the plugin's native GUI
 //! - Loading and switching between plugin presets
 //! - Thread-safe plugin access across audio and UI threads
@@ -26,12 +26,12 @@
 //! - L: List available presets count
 //! - Q: Quit and cleanup
 
+use rack::guard::{catch_audio_panic, PanicPolicy};
+use rack::port::EventPort;
 use rack::prelude::*;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use std::io::{self, Write};
-use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Duration;
+use std::sync::{Arc, Mutex, TryLockError};
 
 // Core Foundation bindings for running the macOS event loop
 #[link(name = "CoreFoundation", kind = "framework")]
@@ -132,6 +132,11 @@ fn main() -> Result<()> {
     let input = Arc::new((left_in, right_in));
     let input_clone = input.clone();
 
+    // MIDI goes through an event port: the audio callback delivers each event
+    // in the block it falls in, so note-offs can be scheduled ahead of time
+    let port = EventPort::new();
+    let midi = port.sender();
+
     // Build the audio stream
     println!("\n🔊 Starting audio stream...");
 
@@ -141,6 +146,7 @@ fn main() -> Result<()> {
             &config.into(),
             plugin_clone,
             input_clone,
+            port,
             channels,
             buffer_frames,
         )?,
@@ -149,6 +155,7 @@ fn main() -> Result<()> {
             &config.into(),
             plugin_clone,
             input_clone,
+            port,
             channels,
             buffer_frames,
         )?,
@@ -157,6 +164,7 @@ fn main() -> Result<()> {
             &config.into(),
             plugin_clone,
             input_clone,
+            port,
             channels,
             buffer_frames,
         )?,
@@ -212,7 +220,7 @@ fn main() -> Result<()> {
                 }
             }
 
-            let mut plugin = plugin.lock().unwrap();
+            let mut plugin = plugin.lock().unwrap_or_else(|e| e.into_inner());
             let plugin_name = plugin.info().name.clone();
             let gui_clone = gui_handle.clone();
 
@@ -268,7 +276,7 @@ fn main() -> Result<()> {
             }
         } else if input == "l" {
             // List presets
-            let plugin = plugin.lock().unwrap();
+            let plugin = plugin.lock().unwrap_or_else(|e| e.into_inner());
             match plugin.preset_count() {
                 Ok(preset_count) if preset_count > 0 => {
                     println!("\nPlugin has {} presets", preset_count);
@@ -281,27 +289,13 @@ fn main() -> Result<()> {
         } else if let Some(ch) = input.chars().next() {
             // Handle keyboard input
             if let Some(note) = key_to_note(ch) {
-                let events = vec![MidiEvent::note_on(note, 100, 0, 0)];
-                {
-                    let mut plugin = plugin.lock().unwrap();
-                    if let Err(e) = plugin.send_midi(&events) {
-                        println!("Error sending MIDI: {}", e);
-                    } else {
-                        println!("♪ Note {} on", note);
-                    }
-                }
-
-                // Schedule note off after 500ms
-                let plugin_clone = Arc::clone(&plugin);
-                thread::spawn(move || {
-                    thread::sleep(Duration::from_millis(500));
-                    let mut plugin = plugin_clone.lock().unwrap();
-                    let events = vec![MidiEvent::note_off(note, 64, 0, 0)];
-                    let _ = plugin.send_midi(&events);
-                });
+                // Play for 500ms; the note-off is delivered by the audio callback
+                let duration = (sample_rate * 0.5) as u32;
+                midi.send_all(MidiEvent::note(note, 100, 0, 0, duration));
+                println!("♪ Note {} on", note);
             } else if let Some(preset_num) = ch.to_digit(10) {
                 if preset_num > 0 {
-                    let mut plugin = plugin.lock().unwrap();
+                    let mut plugin = plugin.lock().unwrap_or_else(|e| e.into_inner());
                     match plugin.load_preset((preset_num - 1) as i32) {
                         Ok(_) => println!("✓ Loaded preset {}", preset_num),
                         Err(e) => println!("Error loading preset: {}", e),
@@ -324,6 +318,7 @@ fn build_stream<T>(
     config: &cpal::StreamConfig,
     plugin: Arc<Mutex<Plugin>>,
     input: Arc<(Vec<f32>, Vec<f32>)>,
+    mut port: EventPort,
     channels: usize,
     buffer_frames: usize,
 ) -> Result<cpal::Stream>
@@ -338,39 +333,56 @@ where
         .build_output_stream(
             config,
             move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
-                let mut plugin = plugin.lock().unwrap();
-
-                // Process audio through the plugin (planar format)
-                if let Err(e) = plugin.process(
-                    &[&input.0, &input.1],
-                    &mut [&mut left_out, &mut right_out],
-                    buffer_frames
-                ) {
-                    eprintln!("Error processing audio: {}", e);
-                    return;
-                }
+                // Catch panics at the audio-thread boundary: a panic unwinding into
+                // cpal would abort the process, so output silence for the block instead
+                let rendered = catch_audio_panic(PanicPolicy::Silence, None, || {
+                    // Never wait for the main thread, and keep going if an
+                    // earlier block panicked while holding the lock
+                    let mut plugin = match plugin.try_lock() {
+                        Ok(plugin) => plugin,
+                        Err(TryLockError::Poisoned(e)) => e.into_inner(),
+                        Err(TryLockError::WouldBlock) => {
+                            data.fill(T::EQUILIBRIUM);
+                            return;
+                        }
+                    };
+
+                    // Deliver due MIDI events and process audio (planar format)
+                    if let Err(e) = port.process(
+                        &mut *plugin,
+                        &[&input.0, &input.1],
+                        &mut [&mut left_out, &mut right_out],
+                        buffer_frames
+                    ) {
+                        eprintln!("Error processing audio: {}", e);
+                        return;
+                    }
 
-                // Copy plugin output (planar) to CPAL buffer (interleaved)
-                let frames = data.len() / channels;
-                for i in 0..frames.min(buffer_frames) {
-                    let left = left_out[i];
-                    let right = right_out[i];
-
-                    // Write to output based on channel count
-                    if channels == 2 {
-                        data[i * 2] = cpal::Sample::from_sample(left);
-                        data[i * 2 + 1] = cpal::Sample::from_sample(right);
-                    } else if channels == 1 {
-                        // Mono: average left and right
-                        let mono = (left + right) * 0.5;
-                        data[i] = cpal::Sample::from_sample(mono);
-                    } else {
-                        // Multi-channel: duplicate stereo to all channels
-                        for ch in 0..channels {
-                            let sample = if ch % 2 == 0 { left } else { right };
-                            data[i * channels + ch] = cpal::Sample::from_sample(sample);
+                    // Copy plugin output (planar) to CPAL buffer (interleaved)
+                    let frames = data.len() / channels;
+                    for i in 0..frames.min(buffer_frames) {
+                        let left = left_out[i];
+                        let right = right_out[i];
+
+                        // Write to output based on channel count
+                        if channels == 2 {
+                            data[i * 2] = cpal::Sample::from_sample(left);
+                            data[i * 2 + 1] = cpal::Sample::from_sample(right);
+                        } else if channels == 1 {
+                            // Mono: average left and right
+                            let mono = (left + right) * 0.5;
+                            data[i] = cpal::Sample::from_sample(mono);
+                        } else {
+                            // Multi-channel: duplicate stereo to all channels
+                            for ch in 0..channels {
+                                let sample = if ch % 2 == 0 { left } else { right };
+                                data[i * channels + ch] = cpal::Sample::from_sample(sample);
+                            }
                         }
                     }
+                });
+                if rendered.is_none() {
+                    data.fill(T::EQUILIBRIUM);
                 }
             },
             move |err| {
@@ -6,6 +6,8 @@
 //! - Processing audio buffers through the plugin
 //! - Analyzing the output
 
+use rack::gain::linear_to_db;
+use rack::generator::{Generator, Signal};
 use rack::prelude::*;
 
 fn main() -> Result<()> {
@@ -59,15 +61,8 @@ fn main() -> Result<()> {
         let mut right_out = vec![0.0f32; frames];
 
         println!("Generating test signal (440 Hz sine wave)...");
-        let frequency = 440.0f32; // A4
-        let sample_rate = 48000.0f32;
-
-        for i in 0..frames {
-            let t = i as f32 / sample_rate;
-            let sample = (2.0 * std::f32::consts::PI * frequency * t).sin() * 0.5;
-            left_in[i] = sample;  // Left channel
-            right_in[i] = sample; // Right channel
-        }
+        let mut sine = Generator::new(Signal::sine(440.0, 0.5), 48000.0); // A4
+        sine.fill(&mut [&mut left_in, &mut right_in], frames);
 
         println!("✓ Test signal generated ({} frames, stereo planar)", frames);
         println!();
@@ -121,7 +116,7 @@ fn main() -> Result<()> {
         println!("Comparison:");
         println!("  Input RMS:  {:.6}", input_rms);
         println!("  Output RMS: {:.6}", rms);
-        println!("  Gain change: {:.2} dB", 20.0 * (rms / input_rms).log10());
+        println!("  Gain change: {:.2} dB", linear_to_db(rms / input_rms));
 
         println!();
         println!("✓ Audio processing demonstration complete!");
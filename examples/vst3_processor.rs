@@ -12,6 +12,7 @@
     not(target_os = "watchos"),
     not(target_os = "visionos")
 ))]
+use rack::generator::{Generator, Signal};
 use rack::vst3::Vst3Scanner;
 #[cfg(all(
     not(target_os = "ios"),
@@ -89,12 +90,8 @@ fn main() -> Result<()> {
     let mut right_out = vec![0.0f32; buffer_size];
 
     // Generate a simple test signal (sine wave at 440 Hz)
-    for i in 0..buffer_size {
-        let t = i as f32 / sample_rate as f32;
-        let sample = (t * 440.0 * 2.0 * std::f32::consts::PI).sin() * 0.5;
-        left_in[i] = sample;
-        right_in[i] = sample;
-    }
+    Generator::new(Signal::sine(440.0, 0.5), sample_rate)
+        .fill(&mut [&mut left_in, &mut right_in], buffer_size);
 
     // Process audio through the plugin
     plugin.process(
@@ -30,6 +30,7 @@ typedef struct {
     char unique_id[64];
     uint32_t version;
     RackAUPluginType plugin_type;
+    uint32_t component_flags;  // AudioComponentFlags
 } RackAUPluginInfo;
 
 // Error codes (0 = success, negative = error)
@@ -38,8 +39,23 @@ typedef struct {
 #define RACK_AU_ERROR_NOT_FOUND -2
 #define RACK_AU_ERROR_INVALID_PARAM -3
 #define RACK_AU_ERROR_NOT_INITIALIZED -4
+#define RACK_AU_ERROR_BUFFER_TOO_SMALL -5  // Required size written to the size argument
 #define RACK_AU_ERROR_AUDIO_UNIT -1000  // Base for AudioUnit OSStatus errors
 
+// Length-negotiated strings
+// Functions taking (char* buffer, size_t* size) never truncate:
+//   1. Call with buffer = NULL: *size receives the required size in bytes,
+//      including the null terminator.
+//   2. Call with a buffer of at least that size (*size = buffer size): the
+//      null-terminated UTF-8 string is copied and *size is set to the bytes used.
+// If the buffer is too small, nothing is copied, *size receives the required
+// size and RACK_AU_ERROR_BUFFER_TOO_SMALL is returned.
+
+// String fields of scanned plugins, for rack_au_scanner_plugin_string()
+#define RACK_AU_FIELD_NAME 0
+#define RACK_AU_FIELD_MANUFACTURER 1
+#define RACK_AU_FIELD_PATH 2
+
 // ============================================================================
 // Scanner API
 // ============================================================================
@@ -68,6 +84,19 @@ void rack_au_scanner_free(RackAUScanner* scanner);
 // max_plugins: size of output array (ignored if plugins is NULL)
 int rack_au_scanner_scan(RackAUScanner* scanner, RackAUPluginInfo* plugins, size_t max_plugins);
 
+// Get a string field of a plugin from the last filling scan, without truncation
+// index: index into the array filled by the last rack_au_scanner_scan() call
+// field: one of RACK_AU_FIELD_*
+// buffer/size: length-negotiated output (see above)
+// Returns 0 on success, negative error code on failure
+int rack_au_scanner_plugin_string(
+    RackAUScanner* scanner,
+    size_t index,
+    int field,
+    char* buffer,
+    size_t* size
+);
+
 // ============================================================================
 // Plugin Instance API
 // ============================================================================
@@ -78,6 +107,13 @@ RackAUPlugin* rack_au_plugin_new(const char* unique_id);
 // Free plugin instance
 void rack_au_plugin_free(RackAUPlugin* plugin);
 
+// Identify the host to the plugin (kAudioUnitProperty_AUHostIdentifier)
+// name: host application name (UTF-8)
+// version: host version as major << 16 | minor << 8 | bugfix
+// Call before initialize. Many AudioUnits don't support the property.
+// Returns 0 on success, negative error code on failure
+int rack_au_plugin_set_host_info(RackAUPlugin* plugin, const char* name, uint32_t version);
+
 // Initialize plugin
 // Returns 0 on success, negative error code on failure
 int rack_au_plugin_initialize(RackAUPlugin* plugin, double sample_rate, uint32_t max_block_size);
@@ -107,6 +143,18 @@ int rack_au_plugin_get_input_channels(RackAUPlugin* plugin);
 // Thread-safety: Should be called after initialize()
 int rack_au_plugin_get_output_channels(RackAUPlugin* plugin);
 
+// Get processing latency in samples (kAudioUnitProperty_Latency)
+// The property is in seconds; it is converted at the initialized sample rate.
+// Returns latency in samples, or 0 if not initialized or the plugin has none
+// Thread-safety: Should be called after initialize()
+int rack_au_plugin_get_latency(RackAUPlugin* plugin);
+
+// Get tail time in seconds (kAudioUnitProperty_TailTime)
+// How long the output keeps sounding after the input goes silent.
+// Returns seconds, or 0 if not initialized or the plugin has no tail
+// Thread-safety: Should be called after initialize()
+double rack_au_plugin_get_tail_time(RackAUPlugin* plugin);
+
 // Process audio (planar format - one buffer per channel)
 // Uses planar (non-interleaved) audio format matching AudioUnit internal format.
 // This enables zero-copy processing in effect chains.
@@ -132,6 +180,66 @@ int rack_au_plugin_process(
     uint32_t frames
 );
 
+// Set the timeline position of the next processed sample
+// process() reports the position to the AudioUnit (AudioTimeStamp::mSampleTime)
+// and advances it by the number of frames processed.
+// position: sample index (must be >= 0)
+// Returns 0 on success, negative error code on failure
+// Thread-safety: Must not be called concurrently with process().
+int rack_au_plugin_set_sample_position(RackAUPlugin* plugin, int64_t position);
+
+// Set the transport reported to the AudioUnit
+// The plugin reads it through its host callbacks (beat and tempo, musical
+// time location, transport state) during the following process() calls,
+// until it is set again. Before the first call the callbacks report no
+// transport.
+// playing: non-zero if the transport is playing
+// tempo: BPM (must be > 0)
+// numerator/denominator: time signature (must be > 0)
+// beat: position of the next processed sample, in quarter notes
+// bar_start_beat: position of the current bar's first beat, in quarter notes
+// cycling: non-zero if the host loops between cycle_start_beat and
+// cycle_end_beat (in quarter notes)
+// Returns 0 on success, negative error code on failure
+// Thread-safety: Must not be called concurrently with process().
+int rack_au_plugin_set_transport(
+    RackAUPlugin* plugin,
+    int playing,
+    double tempo,
+    int32_t numerator,
+    int32_t denominator,
+    double beat,
+    double bar_start_beat,
+    int cycling,
+    double cycle_start_beat,
+    double cycle_end_beat
+);
+
+// Timecode flags (same meaning as the RACK_VST3_FRAME_RATE_* flags)
+#define RACK_AU_FRAME_RATE_PULL_DOWN (1 << 0)
+#define RACK_AU_FRAME_RATE_DROP (1 << 1)
+
+// Set the SMPTE time reported to the AudioUnit
+// process() reports it through the render AudioTimeStamp (mSMPTETime with
+// kAudioTimeStampSMPTETimeValid set) until it is set again.
+// frames_per_second: nominal frame rate (e.g. 30 for 29.97)
+// flags: RACK_AU_FRAME_RATE_* flags
+// hours/minutes/seconds/frames/subframes: timecode of the next processed
+// sample (subframes in 1/80 frames)
+// Returns 0 on success, negative error code on failure (including frame
+// rates SMPTETime cannot describe)
+// Thread-safety: Must not be called concurrently with process().
+int rack_au_plugin_set_timecode(
+    RackAUPlugin* plugin,
+    uint32_t frames_per_second,
+    uint32_t flags,
+    uint32_t hours,
+    uint32_t minutes,
+    uint32_t seconds,
+    uint32_t frames,
+    uint32_t subframes
+);
+
 // Get parameter count
 // Thread-safety: Read-only after initialization. Safe to call from any thread,
 // but plugin instances should not be shared across threads (Send but not Sync).
@@ -151,9 +259,17 @@ int rack_au_plugin_get_parameter(RackAUPlugin* plugin, uint32_t index, float* va
 // Note: Calling during audio processing may cause clicks/pops (AudioUnit internal behavior).
 int rack_au_plugin_set_parameter(RackAUPlugin* plugin, uint32_t index, float value);
 
+// Schedule a parameter change within the next processed block
+// (AudioUnitScheduleParameters), for sample-accurate automation
+// value: normalized 0.0 to 1.0
+// sample_offset: frame of the next block at which the value takes effect
+// Returns 0 on success, negative error code on failure
+// Thread-safety: Must not be called concurrently with process().
+int rack_au_plugin_set_parameter_at(RackAUPlugin* plugin, uint32_t index, float value, uint32_t sample_offset);
+
 // Get parameter info
-// name: output buffer for parameter name (allocated by caller)
-// name_size: size of name buffer
+// name: output buffer for parameter name (allocated by caller, can be NULL)
+// name_size: size of name buffer (ignored if name is NULL)
 // unit: output buffer for parameter unit string (allocated by caller, can be NULL)
 // unit_size: size of unit buffer (ignored if unit is NULL)
 // Returns 0 on success, negative error code on failure
@@ -169,6 +285,23 @@ int rack_au_plugin_parameter_info(
     size_t unit_size
 );
 
+// Get parameter flags
+// flags: output for the raw AudioUnitParameterOptions (kAudioUnitParameterFlag_*)
+//        These carry display hints such as DisplayLogarithmic or DisplaySquared.
+// Returns 0 on success, negative error code on failure
+int rack_au_plugin_parameter_flags(RackAUPlugin* plugin, uint32_t index, uint32_t* flags);
+
+// Get parameter name without truncation
+// buffer/size: length-negotiated output (see above)
+// Returns 0 on success, negative error code on failure
+int rack_au_plugin_parameter_name(RackAUPlugin* plugin, uint32_t index, char* buffer, size_t* size);
+
+// Get parameter unit without truncation
+// Custom units report the plugin's own unit name.
+// buffer/size: length-negotiated output (see above)
+// Returns 0 on success, negative error code on failure
+int rack_au_plugin_parameter_unit(RackAUPlugin* plugin, uint32_t index, char* buffer, size_t* size);
+
 // ============================================================================
 // Preset Management API
 // ============================================================================
@@ -204,6 +337,29 @@ int rack_au_plugin_get_preset_info(
 // Thread-safety: Should be called from the same thread that owns the plugin instance.
 int rack_au_plugin_load_preset(RackAUPlugin* plugin, int32_t preset_number);
 
+// Get factory preset name by index without truncation
+// buffer/size: length-negotiated output (see above)
+// Returns 0 on success, negative error code on failure
+int rack_au_plugin_preset_name(RackAUPlugin* plugin, uint32_t index, char* buffer, size_t* size);
+
+// Get the preset the AudioUnit reports as currently loaded (kAudioUnitProperty_PresentPreset)
+// name: output buffer for preset name (allocated by caller)
+// name_size: size of name buffer
+// preset_number: output parameter for preset number (negative for user presets)
+// Returns 1 if the plugin reports a preset, 0 if it doesn't, negative error code on failure
+// Thread-safety: Should be called from the same thread that owns the plugin instance.
+int rack_au_plugin_get_current_preset(
+    RackAUPlugin* plugin,
+    char* name,
+    size_t name_size,
+    int32_t* preset_number
+);
+
+// Get the name of the preset the AudioUnit reports as currently loaded, without truncation
+// buffer/size: length-negotiated output (see above); *size is 0 if no preset is reported
+// Returns 1 if the plugin reports a preset, 0 if it doesn't, negative error code on failure
+int rack_au_plugin_current_preset_name(RackAUPlugin* plugin, char* buffer, size_t* size);
+
 // Get plugin state size (for allocation)
 // Returns size in bytes needed to store state, or 0 if state cannot be retrieved
 // Thread-safety: Read-only after initialization. Safe to call from any thread.
@@ -224,6 +380,112 @@ int rack_au_plugin_get_state(RackAUPlugin* plugin, uint8_t* data, size_t* size);
 // Thread-safety: Should be called from the same thread that owns the plugin instance.
 int rack_au_plugin_set_state(RackAUPlugin* plugin, const uint8_t* data, size_t size);
 
+// ============================================================================
+// Render Settings API
+// ============================================================================
+
+// Render quality values (kAudioUnitProperty_RenderQuality, 0-127)
+#define RACK_AU_RENDER_QUALITY_MIN 0x00
+#define RACK_AU_RENDER_QUALITY_LOW 0x20
+#define RACK_AU_RENDER_QUALITY_MEDIUM 0x40
+#define RACK_AU_RENDER_QUALITY_HIGH 0x60
+#define RACK_AU_RENDER_QUALITY_MAX 0x7F
+
+// Change the largest block process() accepts (kAudioUnitProperty_MaximumFramesPerSlice)
+// Many AudioUnits only size their buffers for this value when they are
+// initialized, and ignore or reject later changes. If the plugin is initialized
+// it is therefore uninitialized, updated and initialized again; parameter
+// values and state are kept, but audio in flight (tails, delay lines) is lost.
+// Returns 0 on success, negative error code on failure. If re-initialization
+// fails the plugin is left uninitialized.
+// Thread-safety: Should be called from the same thread that owns the plugin instance.
+// Not safe to call concurrently with process().
+int rack_au_plugin_set_max_frames(RackAUPlugin* plugin, uint32_t max_frames);
+
+// Get the render quality (kAudioUnitProperty_RenderQuality)
+// quality: output, 0 (minimum) to 127 (maximum)
+// Returns 0 on success, negative error code on failure (most often because
+// the plugin doesn't support the property)
+int rack_au_plugin_get_render_quality(RackAUPlugin* plugin, uint32_t* quality);
+
+// Set the render quality (kAudioUnitProperty_RenderQuality)
+// quality: 0 (minimum) to 127 (maximum), see RACK_AU_RENDER_QUALITY_*
+// Returns 0 on success, negative error code on failure
+// Thread-safety: Should be called from the same thread that owns the plugin instance.
+int rack_au_plugin_set_render_quality(RackAUPlugin* plugin, uint32_t quality);
+
+// Tell the plugin whether it renders offline (kAudioUnitProperty_OfflineRender)
+// Offline rendering (bounces, exports) lets plugins use more CPU per block
+// than real time allows, e.g. higher oversampling.
+// offline: non-zero for offline rendering, 0 for real time
+// Returns 0 on success, negative error code on failure
+// Thread-safety: Should be called from the same thread that owns the plugin instance.
+// Not safe to call concurrently with process().
+int rack_au_plugin_set_offline_render(RackAUPlugin* plugin, int offline);
+
+// Switch the plugin's metering on or off (kAudioUnitProperty_MeteringMode)
+// While on, the plugin updates its meter parameters
+// (kAudioUnitParameterFlag_MeterReadOnly) as it processes.
+// enabled: non-zero to meter, 0 to stop
+// Returns 0 on success, negative error code on failure (most often because
+// the plugin doesn't support the property)
+// Thread-safety: Should be called from the same thread that owns the plugin instance.
+int rack_au_plugin_set_metering_mode(RackAUPlugin* plugin, int enabled);
+
+// Bypass the plugin's processing (kAudioUnitProperty_BypassEffect)
+// The plugin passes its input through itself, keeping its latency.
+// bypassed: non-zero to bypass, 0 to process
+// Returns 0 on success, negative error code on failure (most often because
+// the plugin doesn't support the property, e.g. instruments)
+// Thread-safety: Should be called from the same thread that owns the plugin instance.
+int rack_au_plugin_set_bypass(RackAUPlugin* plugin, int bypassed);
+
+// ============================================================================
+// Change Notification API
+// ============================================================================
+
+// Runtime changes reported by the AudioUnit (property listeners)
+#define RACK_AU_CHANGE_LATENCY 0         // kAudioUnitProperty_Latency
+#define RACK_AU_CHANGE_TAIL_TIME 1       // kAudioUnitProperty_TailTime
+#define RACK_AU_CHANGE_PARAMETER_LIST 2  // kAudioUnitProperty_ParameterList
+#define RACK_AU_CHANGE_STREAM_FORMAT 3   // kAudioUnitProperty_StreamFormat
+
+// Parameter edits reported by the AudioUnit (parameter event listener), e.g.
+// the user moving a control in the plugin's own view
+#define RACK_AU_CHANGE_BEGIN_EDIT 4  // Gesture started (param_index)
+#define RACK_AU_CHANGE_PARAMETER 5   // Value changed (param_index, value)
+#define RACK_AU_CHANGE_END_EDIT 6    // Gesture ended (param_index)
+
+// The AudioUnit's state changed (kAudioUnitProperty_ClassInfo or
+// kAudioUnitProperty_PresentPreset), i.e. it has unsaved changes
+#define RACK_AU_CHANGE_STATE 7
+
+typedef struct {
+    int32_t kind;         // RACK_AU_CHANGE_*
+    double seconds;       // New latency or tail time (LATENCY, TAIL_TIME)
+    uint32_t samples;     // seconds at the current sample rate (0 if not initialized)
+    int32_t param_index;  // Parameter index (edits), -1 if not in the parameter cache
+    float value;          // New normalized value 0.0-1.0 (PARAMETER)
+} RackAUChangeEvent;
+
+// Callback receiving runtime changes
+// Property changes are invoked on whatever thread changed the property, often
+// the main thread. Parameter edits are delivered on the main thread's run
+// loop, so they only arrive while it runs (as it does while a view is open).
+// The event is only valid for the duration of the call.
+typedef void (*RackAUChangeCallback)(void* user_data, const RackAUChangeEvent* event);
+
+// Set the callback receiving runtime changes (NULL to remove)
+// Changes are watched from the first successful initialize() on.
+// Once this returns, the previous callback is no longer running or invoked.
+// Must not be called from inside the callback.
+// Returns 0 on success, negative error code on failure
+int rack_au_plugin_set_change_callback(
+    RackAUPlugin* plugin,
+    RackAUChangeCallback callback,
+    void* user_data
+);
+
 // ============================================================================
 // MIDI API
 // ============================================================================
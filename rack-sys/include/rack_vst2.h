@@ -0,0 +1,254 @@
+#ifndef RACK_VST2_H
+#define RACK_VST2_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#include <stddef.h>
+#include <stdint.h>
+
+// VST2 hosting, compiled only when the VST2 SDK headers are supplied
+// (VST2_SDK_PATH). Steinberg no longer licenses the VST2 SDK, so it is never
+// bundled or downloaded.
+
+// Opaque types
+typedef struct RackVST2Plugin RackVST2Plugin;
+
+// Plugin type enum
+typedef enum {
+    RACK_VST2_TYPE_EFFECT = 0,
+    RACK_VST2_TYPE_INSTRUMENT = 1,
+    RACK_VST2_TYPE_ANALYZER = 2,
+    RACK_VST2_TYPE_SPATIAL = 3,
+    RACK_VST2_TYPE_OTHER = 4,
+} RackVST2PluginType;
+
+// Plugin info struct (passed to Rust)
+typedef struct {
+    char name[256];          // effGetEffectName, or the shell's name for the plugin
+    char manufacturer[256];  // effGetVendorString
+    int32_t unique_id;       // AEffect::uniqueID (or the shell plugin's ID)
+    int32_t version;         // AEffect::version
+    RackVST2PluginType plugin_type;
+    int32_t num_inputs;
+    int32_t num_outputs;
+} RackVST2PluginInfo;
+
+// Error codes (0 = success, negative = error)
+#define RACK_VST2_OK 0
+#define RACK_VST2_ERROR_GENERIC -1
+#define RACK_VST2_ERROR_NOT_FOUND -2
+#define RACK_VST2_ERROR_INVALID_PARAM -3
+#define RACK_VST2_ERROR_NOT_INITIALIZED -4
+#define RACK_VST2_ERROR_LOAD_FAILED -5
+#define RACK_VST2_ERROR_NOT_SUPPORTED -6  // Feature not supported by this plugin
+#define RACK_VST2_ERROR_BUFFER_TOO_SMALL -7  // Required size written to the size argument
+
+// Length-negotiated output
+// Functions taking (buffer, size_t* size) never truncate:
+//   1. Call with buffer = NULL: *size receives the required size in bytes
+//      (including the null terminator for strings).
+//   2. Call with a buffer of at least that size (*size = buffer size): the
+//      data is copied and *size is set to the bytes used.
+// If the buffer is too small, nothing is copied, *size receives the required
+// size and RACK_VST2_ERROR_BUFFER_TOO_SMALL is returned.
+
+// ============================================================================
+// Scanning API
+// ============================================================================
+
+// Describe the plugins in a VST2 library
+// path: shared library (.dll, .so) or macOS .vst bundle, as raw filesystem
+//       bytes on Unix and UTF-8 on Windows
+// Shell plugins (kPlugCategShell) are asked for every plugin they contain.
+// The library is loaded and each plugin opened briefly.
+//
+// Two-pass usage, like rack_vst3_scanner_scan():
+//   1. count = rack_vst2_describe(path, NULL, 0);
+//   2. rack_vst2_describe(path, array, count);
+// The return value may exceed max_plugins.
+//
+// Returns the number of plugins, or a negative error code
+int rack_vst2_describe(const char* path, RackVST2PluginInfo* plugins, size_t max_plugins);
+
+// ============================================================================
+// Plugin Instance API
+// ============================================================================
+
+// Set the host name reported to plugins (audioMasterGetProductString)
+// name: host application name (UTF-8)
+// Returns 0 on success, negative error code on failure
+// Thread-safety: Safe to call from any thread.
+int rack_vst2_set_host_name(const char* name);
+
+// Set the extra capabilities the host answers yes to in audioMasterCanDo
+// capabilities: array of count canDo strings (UTF-8); replaces the previous set
+// The host's own capabilities (sendVstEvents, ...) are always reported.
+// Returns 0 on success, negative error code on failure
+// Thread-safety: Safe to call from any thread.
+int rack_vst2_set_host_capabilities(const char* const* capabilities, size_t count);
+
+// Create a new plugin instance
+// path: as for rack_vst2_describe()
+// unique_id: the plugin's ID from rack_vst2_describe(); selects the plugin
+//            inside shell libraries
+// Returns plugin instance or NULL on error
+RackVST2Plugin* rack_vst2_plugin_new(const char* path, int32_t unique_id);
+
+// Free plugin instance (closes the plugin and unloads the library)
+void rack_vst2_plugin_free(RackVST2Plugin* plugin);
+
+// Initialize plugin (set sample rate and block size, then resume)
+// Returns 0 on success, negative error code on failure
+int rack_vst2_plugin_initialize(RackVST2Plugin* plugin, double sample_rate, uint32_t max_block_size);
+
+// Check if plugin is initialized
+int rack_vst2_plugin_is_initialized(RackVST2Plugin* plugin);
+
+// Reset plugin state (suspend and resume, which clears tails in most plugins)
+// Returns 0 on success, negative error code on failure
+// Thread-safety: Should be called from a non-realtime thread.
+int rack_vst2_plugin_reset(RackVST2Plugin* plugin);
+
+// Get input/output channel count
+// Returns number of channels, or 0 if not initialized
+int rack_vst2_plugin_get_input_channels(RackVST2Plugin* plugin);
+int rack_vst2_plugin_get_output_channels(RackVST2Plugin* plugin);
+
+// Get the plugin's latency in samples (AEffect::initialDelay)
+// Returns the latency, or 0 if not initialized
+int rack_vst2_plugin_get_latency(RackVST2Plugin* plugin);
+
+// Get the plugin's tail in samples (effGetTailSize)
+// Returns the tail, or 0 if not initialized, the plugin has no tail or
+// doesn't say
+int rack_vst2_plugin_get_tail_size(RackVST2Plugin* plugin);
+
+// Bypass the plugin's processing (effSetBypass), for plugins that can bypass
+// themselves (effCanDo "bypass") and so keep their latency while bypassed
+// bypassed: non-zero to bypass, 0 to process
+// Returns 1 if the plugin handled it, 0 if it can't bypass itself, or a
+// negative error code
+int rack_vst2_plugin_set_bypass(RackVST2Plugin* plugin, int bypassed);
+
+// Process audio (planar format - one buffer per channel)
+// Delivers MIDI sent with rack_vst2_plugin_send_midi() first, then calls
+// processReplacing. Advances the sample position by frames.
+// Returns 0 on success, negative error code on failure
+int rack_vst2_plugin_process(
+    RackVST2Plugin* plugin,
+    const float* const* inputs,
+    uint32_t num_input_channels,
+    float* const* outputs,
+    uint32_t num_output_channels,
+    uint32_t frames
+);
+
+// Set the timeline position of the next processed sample
+// Reported to the plugin as VstTimeInfo::samplePos.
+// position: sample index (must be >= 0)
+// Returns 0 on success, negative error code on failure
+// Thread-safety: Must not be called concurrently with process().
+int rack_vst2_plugin_set_sample_position(RackVST2Plugin* plugin, int64_t position);
+
+// Set the transport state reported through audioMasterGetTime and
+// audioMasterGetCurrentProcessLevel from the next process() call on
+// playing: non-zero while the transport plays
+// tempo: BPM
+// numerator/denominator: time signature
+// ppq_position: musical position of the block start, in quarter notes
+// bar_start_ppq: position of the current bar's first beat, in quarter notes
+// cycling: non-zero if the host loops between cycle_start_ppq and
+// cycle_end_ppq (in quarter notes)
+// offline: non-zero when rendering offline (kVstProcessLevelOffline)
+// Returns 0 on success, negative error code on failure
+// Thread-safety: Must not be called concurrently with process().
+int rack_vst2_plugin_set_transport(
+    RackVST2Plugin* plugin,
+    int playing,
+    double tempo,
+    int32_t numerator,
+    int32_t denominator,
+    double ppq_position,
+    double bar_start_ppq,
+    int cycling,
+    double cycle_start_ppq,
+    double cycle_end_ppq,
+    int offline
+);
+
+// Get parameter count
+int rack_vst2_plugin_parameter_count(RackVST2Plugin* plugin);
+
+// Get/set parameter value (normalized 0.0 to 1.0, as all VST2 parameters are)
+// Returns 0 on success, negative error code on failure
+int rack_vst2_plugin_get_parameter(RackVST2Plugin* plugin, uint32_t index, float* value);
+int rack_vst2_plugin_set_parameter(RackVST2Plugin* plugin, uint32_t index, float value);
+
+// Get parameter name (effGetParamName) / unit (effGetParamLabel)
+// buffer/size: length-negotiated output (see above)
+// Returns 0 on success, negative error code on failure
+int rack_vst2_plugin_parameter_name(RackVST2Plugin* plugin, uint32_t index, char* buffer, size_t* size);
+int rack_vst2_plugin_parameter_unit(RackVST2Plugin* plugin, uint32_t index, char* buffer, size_t* size);
+
+// ============================================================================
+// Program (Preset) API
+// ============================================================================
+
+// Get program count
+int rack_vst2_plugin_get_preset_count(RackVST2Plugin* plugin);
+
+// Get program name by index (effGetProgramNameIndexed)
+// buffer/size: length-negotiated output (see above)
+// Returns 0 on success, negative error code on failure
+int rack_vst2_plugin_preset_name(RackVST2Plugin* plugin, uint32_t index, char* buffer, size_t* size);
+
+// Switch to a program
+// Returns 0 on success, negative error code on failure
+int rack_vst2_plugin_load_preset(RackVST2Plugin* plugin, int32_t preset_number);
+
+// Get the current program number
+// Returns the program number, or a negative error code
+int rack_vst2_plugin_get_current_preset(RackVST2Plugin* plugin);
+
+// ============================================================================
+// State API
+// ============================================================================
+
+// Get plugin state
+// Plugins with effFlagsProgramChunks return their bank chunk; for others the
+// state is every parameter value (little-endian f32) after the program number.
+// data/size: length-negotiated output (see above)
+// Returns 0 on success, negative error code on failure
+int rack_vst2_plugin_get_state(RackVST2Plugin* plugin, uint8_t* data, size_t* size);
+
+// Restore state from rack_vst2_plugin_get_state()
+// Returns 0 on success, negative error code on failure
+int rack_vst2_plugin_set_state(RackVST2Plugin* plugin, const uint8_t* data, size_t size);
+
+// ============================================================================
+// MIDI API
+// ============================================================================
+
+// MIDI event struct
+typedef struct {
+    uint32_t sample_offset;  // Sample offset within buffer
+    uint8_t data[3];         // Raw MIDI message (status, data1, data2)
+} RackVST2MidiEvent;
+
+// Queue MIDI events for the next process() call
+// Events beyond the internal queue (1024 events) are dropped.
+// Returns 0 on success, negative error code on failure
+// Thread-safety: Not safe to call concurrently with process().
+int rack_vst2_plugin_send_midi(
+    RackVST2Plugin* plugin,
+    const RackVST2MidiEvent* events,
+    uint32_t event_count
+);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // RACK_VST2_H
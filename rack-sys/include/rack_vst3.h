@@ -41,6 +41,44 @@ typedef struct {
 #define RACK_VST3_ERROR_NOT_INITIALIZED -4
 #define RACK_VST3_ERROR_LOAD_FAILED -5
 #define RACK_VST3_ERROR_NOT_SUPPORTED -6  // Feature not supported by this plugin
+#define RACK_VST3_ERROR_BUFFER_TOO_SMALL -7  // Required size written to the size argument
+
+// Returned by rack_vst3_plugin_get_tail_samples() for a tail that never ends
+#define RACK_VST3_INFINITE_TAIL 0xFFFFFFFFu
+
+// Length-negotiated strings
+// Functions taking (char* buffer, size_t* size) never truncate:
+//   1. Call with buffer = NULL: *size receives the required size in bytes,
+//      including the null terminator.
+//   2. Call with a buffer of at least that size (*size = buffer size): the
+//      null-terminated UTF-8 string is copied and *size is set to the bytes used.
+// If the buffer is too small, nothing is copied, *size receives the required
+// size and RACK_VST3_ERROR_BUFFER_TOO_SMALL is returned.
+
+// String fields of scanned plugins, for rack_vst3_scanner_plugin_string()
+#define RACK_VST3_FIELD_NAME 0
+#define RACK_VST3_FIELD_MANUFACTURER 1
+#define RACK_VST3_FIELD_PATH 2
+#define RACK_VST3_FIELD_CATEGORY 3
+// Bundle location with symlinks (and Finder aliases on macOS) resolved.
+// RACK_VST3_FIELD_PATH is where the bundle was found; plugins are loaded
+// from this one. Bundles reachable through several links are listed once.
+#define RACK_VST3_FIELD_CANONICAL_PATH 4
+// Factory information (PFactoryInfo), empty if the vendor didn't provide it
+#define RACK_VST3_FIELD_VENDOR_URL 5
+#define RACK_VST3_FIELD_VENDOR_EMAIL 6
+// SDK version the class was built with (PClassInfo2), e.g. "VST 3.7.9"
+#define RACK_VST3_FIELD_SDK_VERSION 7
+
+// PFactoryInfo::FactoryFlags, for rack_vst3_scanner_plugin_flags()
+#define RACK_VST3_FACTORY_CLASSES_DISCARDABLE (1 << 0)
+#define RACK_VST3_FACTORY_LICENSE_CHECK (1 << 1)
+#define RACK_VST3_FACTORY_COMPONENT_NON_DISCARDABLE (1 << 3)
+#define RACK_VST3_FACTORY_UNICODE (1 << 4)
+
+// Vst::ComponentFlags (PClassInfo2::classFlags)
+#define RACK_VST3_CLASS_DISTRIBUTABLE (1 << 0)
+#define RACK_VST3_CLASS_SIMPLE_MODE_SUPPORTED (1 << 1)
 
 // ============================================================================
 // Scanner API
@@ -54,13 +92,40 @@ RackVST3Scanner* rack_vst3_scanner_new(void);
 void rack_vst3_scanner_free(RackVST3Scanner* scanner);
 
 // Add a search path for VST3 plugins
+// path: directory path, as raw filesystem bytes on Unix and UTF-8 on Windows
+//       (see rack_vst3_scanner_add_path_w). On Windows, paths of MAX_PATH
+//       characters or more must use the \\?\ extended-length form; plugins
+//       found under it are reported with the same prefix.
 // Returns 0 on success, negative error code on failure
 int rack_vst3_scanner_add_path(RackVST3Scanner* scanner, const char* path);
 
+#ifdef _WIN32
+// Add a search path for VST3 plugins, given as UTF-16
+// Same as rack_vst3_scanner_add_path(), but takes the path in the native
+// Windows encoding so folders with any characters can be scanned.
+// Returns 0 on success, negative error code on failure
+int rack_vst3_scanner_add_path_w(RackVST3Scanner* scanner, const wchar_t* path);
+#endif
+
 // Add system default VST3 search paths
 // Returns 0 on success, negative error code on failure
 int rack_vst3_scanner_add_default_paths(RackVST3Scanner* scanner);
 
+// Get one of the system default VST3 search paths added by
+// rack_vst3_scanner_add_default_paths()
+// index: 0-based position in the list
+// buffer/size: length-negotiated output (see above), UTF-8
+// Returns 0 on success, RACK_VST3_ERROR_NOT_FOUND past the last path, or
+// another negative error code on failure
+int rack_vst3_default_path(size_t index, char* buffer, size_t* size);
+
+// Set scanning options
+// include_system_paths: non-zero to also scan the platform's default locations
+//                       and the modules the SDK discovers (the default)
+// follow_symlinks: non-zero to follow symbolic links to bundles (the default)
+// Returns 0 on success, negative error code on failure
+int rack_vst3_scanner_set_options(RackVST3Scanner* scanner, int include_system_paths, int follow_symlinks);
+
 // Scan for plugins
 // Returns number of plugins found (or would be found), or negative error code
 //
@@ -78,16 +143,59 @@ int rack_vst3_scanner_add_default_paths(RackVST3Scanner* scanner);
 // max_plugins: size of output array (ignored if plugins is NULL)
 int rack_vst3_scanner_scan(RackVST3Scanner* scanner, RackVST3PluginInfo* plugins, size_t max_plugins);
 
+// Get a string field of a plugin from the last filling scan, without truncation
+// index: index into the array filled by the last rack_vst3_scanner_scan() call
+// field: one of RACK_VST3_FIELD_*
+// buffer/size: length-negotiated output (see above)
+// Returns 0 on success, negative error code on failure
+int rack_vst3_scanner_plugin_string(
+    RackVST3Scanner* scanner,
+    size_t index,
+    int field,
+    char* buffer,
+    size_t* size
+);
+
+// Get the factory and class flags of a plugin from the last filling scan
+// index: index into the array filled by the last rack_vst3_scanner_scan() call
+// factory_flags: receives RACK_VST3_FACTORY_* bits
+// class_flags: receives RACK_VST3_CLASS_* bits (0 if the module only has
+//              version 1 class info)
+// Returns 0 on success, negative error code on failure
+int rack_vst3_scanner_plugin_flags(
+    RackVST3Scanner* scanner,
+    size_t index,
+    uint32_t* factory_flags,
+    uint32_t* class_flags
+);
+
 // ============================================================================
 // Plugin Instance API
 // ============================================================================
 
+// Set the host name reported to plugins through IHostApplication::getName()
+// name: host application name (UTF-8)
+// Applies to plugin instances created afterwards.
+// Returns 0 on success, negative error code on failure
+// Thread-safety: Safe to call from any thread.
+int rack_vst3_set_host_name(const char* name);
+
 // Create a new plugin instance from path and UID
-// path: path to .vst3 bundle/folder
+// path: path to .vst3 bundle/folder, as raw filesystem bytes on Unix and
+//       UTF-8 on Windows (see rack_vst3_plugin_new_w). On Windows, paths of
+//       MAX_PATH characters or more must use the \\?\ extended-length form.
 // uid: plugin UID (from scan result)
 // Returns plugin instance or NULL on error
 RackVST3Plugin* rack_vst3_plugin_new(const char* path, const char* uid);
 
+#ifdef _WIN32
+// Create a new plugin instance from a UTF-16 path and UID
+// Same as rack_vst3_plugin_new(), but takes the path in the native Windows
+// encoding so bundles installed under non-ASCII folders load.
+// Returns plugin instance or NULL on error
+RackVST3Plugin* rack_vst3_plugin_new_w(const wchar_t* path, const char* uid);
+#endif
+
 // Free plugin instance
 void rack_vst3_plugin_free(RackVST3Plugin* plugin);
 
@@ -120,6 +228,17 @@ int rack_vst3_plugin_get_input_channels(RackVST3Plugin* plugin);
 // Thread-safety: Should be called after initialize()
 int rack_vst3_plugin_get_output_channels(RackVST3Plugin* plugin);
 
+// Get processing latency in samples (IAudioProcessor::getLatencySamples)
+// Returns latency in samples, or 0 if not initialized
+// Thread-safety: Should be called after initialize()
+int rack_vst3_plugin_get_latency(RackVST3Plugin* plugin);
+
+// Get tail length in samples (IAudioProcessor::getTailSamples)
+// Returns the tail in samples, RACK_VST3_INFINITE_TAIL for a tail that never
+// ends, or 0 if not initialized or the plugin has no tail
+// Thread-safety: Should be called after initialize()
+uint32_t rack_vst3_plugin_get_tail_samples(RackVST3Plugin* plugin);
+
 // Process audio (planar format - one buffer per channel)
 // Uses planar (non-interleaved) audio format matching VST3 internal format.
 // This enables zero-copy processing in effect chains.
@@ -145,6 +264,59 @@ int rack_vst3_plugin_process(
     uint32_t frames
 );
 
+// Set the timeline position of the next processed sample
+// process() reports the position to the plugin (ProcessContext::projectTimeSamples)
+// and advances it by the number of frames processed.
+// position: sample index (must be >= 0)
+// Returns 0 on success, negative error code on failure
+// Thread-safety: Must not be called concurrently with process().
+int rack_vst3_plugin_set_sample_position(RackVST3Plugin* plugin, int64_t position);
+
+// Timecode flags (match Steinberg::Vst::FrameRate::FrameRateFlags)
+#define RACK_VST3_FRAME_RATE_PULL_DOWN (1 << 0)
+#define RACK_VST3_FRAME_RATE_DROP (1 << 1)
+
+// Set the video frame rate and SMPTE offset reported to the plugin
+// process() reports them through ProcessContext::frameRate and
+// ProcessContext::smpteOffsetSubframes (with kSmpteValid set).
+// frames_per_second: nominal frame rate (e.g. 30 for 29.97)
+// flags: RACK_VST3_FRAME_RATE_* flags
+// smpte_offset_subframes: timecode of the project start, in 1/80 frames
+// Returns 0 on success, negative error code on failure
+// Thread-safety: Must not be called concurrently with process().
+int rack_vst3_plugin_set_timecode(
+    RackVST3Plugin* plugin,
+    uint32_t frames_per_second,
+    uint32_t flags,
+    int32_t smpte_offset_subframes
+);
+
+// Set the transport reported to the plugin
+// process() reports it through ProcessContext (tempo, time signature,
+// projectTimeMusic, barPositionMusic, the cycle and the kPlaying state)
+// until it is set again.
+// playing: non-zero if the transport is playing
+// tempo: BPM (must be > 0)
+// numerator/denominator: time signature (must be > 0)
+// beat: position of the next processed sample, in quarter notes
+// bar_start_beat: position of the current bar's first beat, in quarter notes
+// cycling: non-zero if the host loops between cycle_start_beat and
+// cycle_end_beat (in quarter notes)
+// Returns 0 on success, negative error code on failure
+// Thread-safety: Must not be called concurrently with process().
+int rack_vst3_plugin_set_transport(
+    RackVST3Plugin* plugin,
+    int playing,
+    double tempo,
+    int32_t numerator,
+    int32_t denominator,
+    double beat,
+    double bar_start_beat,
+    int cycling,
+    double cycle_start_beat,
+    double cycle_end_beat
+);
+
 // Get parameter count
 // Thread-safety: Read-only after initialization. Safe to call from any thread.
 int rack_vst3_plugin_parameter_count(RackVST3Plugin* plugin);
@@ -162,6 +334,16 @@ int rack_vst3_plugin_get_parameter(RackVST3Plugin* plugin, uint32_t index, float
 // Note: Calling during audio processing may cause clicks/pops.
 int rack_vst3_plugin_set_parameter(RackVST3Plugin* plugin, uint32_t index, float value);
 
+// Queue a parameter change within the next processed block, for
+// sample-accurate automation
+// The change goes into the block's IParameterChanges queue at sample_offset;
+// changes to the same parameter are kept in offset order.
+// value: normalized 0.0 to 1.0
+// sample_offset: frame of the next block at which the value takes effect
+// Returns 0 on success, negative error code on failure
+// Thread-safety: Must not be called concurrently with process().
+int rack_vst3_plugin_set_parameter_at(RackVST3Plugin* plugin, uint32_t index, float value, uint32_t sample_offset);
+
 // Get parameter info
 // name: output buffer for parameter name (allocated by caller)
 // name_size: size of name buffer
@@ -180,6 +362,22 @@ int rack_vst3_plugin_parameter_info(
     size_t unit_size
 );
 
+// Get parameter flags
+// flags: output for the raw Steinberg::Vst::ParameterInfo::flags
+//        (kCanAutomate, kIsReadOnly, kIsHidden, ...)
+// Returns 0 on success, negative error code on failure
+int rack_vst3_plugin_parameter_flags(RackVST3Plugin* plugin, uint32_t index, uint32_t* flags);
+
+// Get parameter name without truncation
+// buffer/size: length-negotiated output (see above)
+// Returns 0 on success, negative error code on failure
+int rack_vst3_plugin_parameter_name(RackVST3Plugin* plugin, uint32_t index, char* buffer, size_t* size);
+
+// Get parameter unit string without truncation
+// buffer/size: length-negotiated output (see above)
+// Returns 0 on success, negative error code on failure
+int rack_vst3_plugin_parameter_unit(RackVST3Plugin* plugin, uint32_t index, char* buffer, size_t* size);
+
 // ============================================================================
 // Preset Management API
 // ============================================================================
@@ -215,6 +413,31 @@ int rack_vst3_plugin_get_preset_info(
 // Thread-safety: Should be called from the same thread that owns the plugin instance.
 int rack_vst3_plugin_load_preset(RackVST3Plugin* plugin, int32_t preset_number);
 
+// Get preset name by index without truncation
+// buffer/size: length-negotiated output (see above)
+// Returns 0 on success, negative error code on failure
+int rack_vst3_plugin_preset_name(RackVST3Plugin* plugin, uint32_t index, char* buffer, size_t* size);
+
+// Get the currently loaded preset
+// Uses the plugin's program-change parameter when it has one, otherwise the
+// preset last loaded with rack_vst3_plugin_load_preset (cleared by set_state).
+// name: output buffer for preset name (allocated by caller)
+// name_size: size of name buffer
+// preset_number: output parameter for preset number (as used with load_preset)
+// Returns 1 if a current preset is known, 0 if not, negative error code on failure
+// Thread-safety: Should be called from the same thread that owns the plugin instance.
+int rack_vst3_plugin_get_current_preset(
+    RackVST3Plugin* plugin,
+    char* name,
+    size_t name_size,
+    int32_t* preset_number
+);
+
+// Get the name of the currently loaded preset without truncation
+// buffer/size: length-negotiated output (see above); *size is 0 if no preset is known
+// Returns 1 if a current preset is known, 0 if not, negative error code on failure
+int rack_vst3_plugin_current_preset_name(RackVST3Plugin* plugin, char* buffer, size_t* size);
+
 // Get plugin state size (for allocation)
 // Returns actual size in bytes needed to store state, or 0 if state cannot be retrieved
 //
@@ -230,9 +453,32 @@ int rack_vst3_plugin_get_state_size(RackVST3Plugin* plugin);
 // data: output buffer for state data (allocated by caller)
 // size: input/output - buffer size on input, actual size on output
 // Returns 0 on success, negative error code on failure
+// Returns RACK_VST3_ERROR_BUFFER_TOO_SMALL with the required size in *size if the
+// state grew since get_state_size(); prefer rack_vst3_plugin_get_state_stream,
+// which can't race like this
 // Thread-safety: Should be called from the same thread that owns the plugin instance.
 int rack_vst3_plugin_get_state(RackVST3Plugin* plugin, uint8_t* data, size_t* size);
 
+// Callback receiving serialized state, one chunk at a time, in order
+// Return 0 to continue, non-zero to abort (get_state_stream then fails)
+typedef int (*RackVST3StateWriteCallback)(void* user_data, const uint8_t* data, size_t size);
+
+// Callback supplying serialized state as the plugin reads it
+// Fill up to size bytes of data and store the count in *bytes_read (0 at the end)
+// Return 0 on success, non-zero on failure (set_state_stream then fails)
+typedef int (*RackVST3StateReadCallback)(void* user_data, uint8_t* data, size_t size, size_t* bytes_read);
+
+// Get plugin state, passing it to callback in chunks
+// The state is serialized once into chunked storage, so there is no separate size
+// query that can go stale and no single buffer of the whole state.
+// Returns 0 on success, negative error code on failure
+// Thread-safety: Should be called from the same thread that owns the plugin instance.
+int rack_vst3_plugin_get_state_stream(
+    RackVST3Plugin* plugin,
+    RackVST3StateWriteCallback callback,
+    void* user_data
+);
+
 // Set plugin state (restore full state including parameters, preset, etc.)
 // data: state data (from previous get_state call)
 // size: size of state data in bytes
@@ -240,6 +486,17 @@ int rack_vst3_plugin_get_state(RackVST3Plugin* plugin, uint8_t* data, size_t* si
 // Thread-safety: Should be called from the same thread that owns the plugin instance.
 int rack_vst3_plugin_set_state(RackVST3Plugin* plugin, const uint8_t* data, size_t size);
 
+// Set plugin state, pulling it from callback as the plugin reads
+// Accepts the same data as set_state. Data already read is kept so plugins can
+// seek back over it, but the caller never needs to buffer the whole state.
+// Returns 0 on success, negative error code on failure
+// Thread-safety: Should be called from the same thread that owns the plugin instance.
+int rack_vst3_plugin_set_state_stream(
+    RackVST3Plugin* plugin,
+    RackVST3StateReadCallback callback,
+    void* user_data
+);
+
 // ============================================================================
 // MIDI API
 // ============================================================================
@@ -283,6 +540,59 @@ int rack_vst3_plugin_send_midi(
     uint32_t event_count
 );
 
+// ============================================================================
+// Component Handler API
+// ============================================================================
+
+// Notifications from the plugin's edit controller (IComponentHandler)
+#define RACK_VST3_COMPONENT_BEGIN_EDIT 0    // User grabbed a control in the editor
+#define RACK_VST3_COMPONENT_PERFORM_EDIT 1  // Value changed during the gesture
+#define RACK_VST3_COMPONENT_END_EDIT 2      // User released the control
+#define RACK_VST3_COMPONENT_RESTART 3       // restartComponent() was called
+#define RACK_VST3_COMPONENT_DIRTY 4         // setDirty(true): the state has unsaved changes
+
+// Steinberg::Vst::RestartFlags reported with RACK_VST3_COMPONENT_RESTART
+#define RACK_VST3_RESTART_RELOAD_COMPONENT 1
+#define RACK_VST3_RESTART_IO_CHANGED 2
+#define RACK_VST3_RESTART_PARAM_VALUES_CHANGED 4
+#define RACK_VST3_RESTART_LATENCY_CHANGED 8
+#define RACK_VST3_RESTART_PARAM_TITLES_CHANGED 16
+
+typedef struct {
+    int32_t kind;           // RACK_VST3_COMPONENT_*
+    int32_t param_index;    // Index as used by get/set_parameter, -1 if not a known parameter
+    uint32_t param_id;      // VST3 ParamID (edit events)
+    double value;           // Normalized value (PERFORM_EDIT)
+    int32_t restart_flags;  // RACK_VST3_RESTART_* (RESTART)
+    uint32_t latency;       // Latency in samples after the restart (RESTART)
+} RackVST3ComponentEvent;
+
+// Callback receiving component handler notifications
+// Invoked on whatever thread the plugin calls the handler from, usually the
+// editor's UI thread. The event is only valid for the duration of the call.
+typedef void (*RackVST3ComponentCallback)(void* user_data, const RackVST3ComponentEvent* event);
+
+// Set the callback receiving component handler notifications (NULL to remove)
+// Edits performed in the plugin's editor are forwarded to the processor on the
+// next process() call whether or not a callback is set.
+// Once this returns, the previous callback is no longer running or invoked.
+// Must not be called from inside the callback.
+// Returns 0 on success, negative error code on failure
+int rack_vst3_plugin_set_component_callback(
+    RackVST3Plugin* plugin,
+    RackVST3ComponentCallback callback,
+    void* user_data
+);
+
+// Apply the changes the plugin requested with restartComponent() since the last call
+// flags: receives the RACK_VST3_RESTART_* flags that were pending (0 if none)
+// Bus and latency changes deactivate and reactivate the plugin and re-read its
+// channel counts; parameter title changes rebuild the parameter cache.
+// Returns 0 on success, negative error code on failure (the plugin is left
+// uninitialized if it can't be reactivated)
+// Thread-safety: Call from the thread that owns the plugin, never during process().
+int rack_vst3_plugin_apply_restart(RackVST3Plugin* plugin, int32_t* flags);
+
 #ifdef __cplusplus
 }
 #endif
@@ -1,11 +1,13 @@
 #include "rack_au.h"
 #include <AudioToolbox/AudioToolbox.h>
 #include <CoreFoundation/CoreFoundation.h>
+#include <cmath>
 #include <cstring>
 #include <cstdio>  // for sscanf
 #include <climits> // for INT_MAX
 #include <new>     // for std::align_val_t
 #include <mutex>
+#include <string>
 
 // Global mutex to serialize AudioUnit LIFECYCLE operations only
 // Protects: AudioComponentInstanceNew, AudioUnitInitialize, AudioUnitUninitialize, AudioComponentInstanceDispose
@@ -36,13 +38,396 @@ struct RackAUPlugin {
     AudioUnitParameterID* parameter_ids;
     AudioUnitParameterInfo* parameter_info;  // Cached parameter info for performance
     UInt32 parameter_count;
+
+    // Runtime change notifications (see rack_au_plugin_set_change_callback)
+    // The mutex is held while the callback runs so replacing it waits for the call.
+    std::mutex change_mutex;
+    RackAUChangeCallback change_callback = nullptr;
+    void* change_user_data = nullptr;
+    bool listeners_registered = false;
+    // Parameter event listener for edits made outside the host (the plugin's view)
+    AUEventListenerRef parameter_listener = nullptr;
+
+    // Transport reported through the host callbacks (see
+    // rack_au_plugin_set_transport); transport_valid is false until it is set
+    bool transport_valid = false;
+    bool playing = false;
+    bool transport_changed = false;
+    double tempo = 120.0;
+    uint32_t time_sig_numerator = 4;
+    uint32_t time_sig_denominator = 4;
+    double beat = 0.0;
+    double bar_start_beat = 0.0;
+    bool cycling = false;
+    double cycle_start_beat = 0.0;
+    double cycle_end_beat = 0.0;
+
+    // SMPTE time reported in the render timestamp (see
+    // rack_au_plugin_set_timecode); smpte_valid is false until it is set
+    bool smpte_valid = false;
+    SMPTETime smpte = {};
+};
+
+// Properties whose changes are reported through the change callback
+static const AudioUnitPropertyID kWatchedProperties[] = {
+    kAudioUnitProperty_Latency,
+    kAudioUnitProperty_TailTime,
+    kAudioUnitProperty_ParameterList,
+    kAudioUnitProperty_StreamFormat,
+    kAudioUnitProperty_ClassInfo,
+    kAudioUnitProperty_PresentPreset,
 };
 
+// Helper: Read a global Float64 property, 0 if unavailable
+static Float64 get_float64_property(AudioUnit unit, AudioUnitPropertyID property) {
+    Float64 value = 0.0;
+    UInt32 size = sizeof(value);
+    if (AudioUnitGetProperty(unit, property, kAudioUnitScope_Global, 0, &value, &size) != noErr) {
+        return 0.0;
+    }
+    return value;
+}
+
+// Property listener: forwards watched property changes to the change callback
+// Runs on whatever thread changed the property, often the main thread.
+static void property_changed(
+    void* ref_con,
+    AudioUnit unit,
+    AudioUnitPropertyID property,
+    AudioUnitScope scope,
+    AudioUnitElement element)
+{
+    (void)scope;
+    (void)element;
+    RackAUPlugin* plugin = static_cast<RackAUPlugin*>(ref_con);
+
+    RackAUChangeEvent event = {};
+    switch (property) {
+        case kAudioUnitProperty_Latency:
+            event.kind = RACK_AU_CHANGE_LATENCY;
+            event.seconds = get_float64_property(unit, property);
+            break;
+        case kAudioUnitProperty_TailTime:
+            event.kind = RACK_AU_CHANGE_TAIL_TIME;
+            event.seconds = get_float64_property(unit, property);
+            break;
+        case kAudioUnitProperty_ParameterList:
+            event.kind = RACK_AU_CHANGE_PARAMETER_LIST;
+            break;
+        case kAudioUnitProperty_StreamFormat:
+            event.kind = RACK_AU_CHANGE_STREAM_FORMAT;
+            break;
+        case kAudioUnitProperty_ClassInfo:
+        case kAudioUnitProperty_PresentPreset:
+            // AudioUnits announce unsaved changes by notifying ClassInfo
+            event.kind = RACK_AU_CHANGE_STATE;
+            break;
+        default:
+            return;
+    }
+    if (plugin->sample_rate > 0.0 && event.seconds > 0.0) {
+        event.samples = static_cast<uint32_t>(event.seconds * plugin->sample_rate + 0.5);
+    }
+
+    std::lock_guard<std::mutex> lock(plugin->change_mutex);
+    if (plugin->change_callback) {
+        plugin->change_callback(plugin->change_user_data, &event);
+    }
+}
+
+// Helper: Convert a parameter's value in its range to normalized 0.0-1.0
+static float normalize_value(const AudioUnitParameterInfo& info, AudioUnitParameterValue raw_value) {
+    float min_val = info.minValue;
+    float max_val = info.maxValue;
+    float range = max_val - min_val;
+
+    // Validate parameter range (detect malformed AudioUnit parameter info)
+    if (max_val < min_val) {
+        // Invalid range - this shouldn't happen with well-formed AudioUnits
+        // Return mid-range as safe fallback
+        return 0.5f;
+    }
+
+    // Use epsilon comparison for floating-point safety
+    const float epsilon = 1e-7f;
+    if (range <= epsilon) {
+        // Zero or near-zero range - return 0.0 (parameter has single value)
+        return 0.0f;
+    }
+
+    float value = (raw_value - min_val) / range;
+    // Clamp to 0.0-1.0 in case of floating-point rounding errors
+    if (value < 0.0f) value = 0.0f;
+    if (value > 1.0f) value = 1.0f;
+    return value;
+}
+
+// Parameter event listener: forwards edits to the change callback
+// Runs on the main thread's run loop.
+static void parameter_event(
+    void* ref_con,
+    void* object,
+    const AudioUnitEvent* unit_event,
+    UInt64 host_time,
+    AudioUnitParameterValue parameter_value)
+{
+    (void)object;
+    (void)host_time;
+    RackAUPlugin* plugin = static_cast<RackAUPlugin*>(ref_con);
+
+    RackAUChangeEvent event = {};
+    switch (unit_event->mEventType) {
+        case kAudioUnitEvent_BeginParameterChangeGesture:
+            event.kind = RACK_AU_CHANGE_BEGIN_EDIT;
+            break;
+        case kAudioUnitEvent_ParameterValueChange:
+            event.kind = RACK_AU_CHANGE_PARAMETER;
+            break;
+        case kAudioUnitEvent_EndParameterChangeGesture:
+            event.kind = RACK_AU_CHANGE_END_EDIT;
+            break;
+        default:
+            return;
+    }
+
+    // Map the parameter ID back to its index in the cache
+    AudioUnitParameterID param_id = unit_event->mArgument.mParameter.mParameterID;
+    event.param_index = -1;
+    for (UInt32 i = 0; i < plugin->parameter_count; i++) {
+        if (plugin->parameter_ids[i] == param_id) {
+            event.param_index = static_cast<int32_t>(i);
+            if (plugin->parameter_info) {
+                event.value = normalize_value(plugin->parameter_info[i], parameter_value);
+            }
+            break;
+        }
+    }
+
+    std::lock_guard<std::mutex> lock(plugin->change_mutex);
+    if (plugin->change_callback) {
+        plugin->change_callback(plugin->change_user_data, &event);
+    }
+}
+
+// Helper: Listen for edits of every cached parameter
+// The listener is left null if it can't be created.
+static void add_parameter_listener(RackAUPlugin* plugin) {
+    OSStatus status = AUEventListenerCreate(
+        parameter_event,
+        plugin,
+        CFRunLoopGetMain(),
+        kCFRunLoopDefaultMode,
+        0.0,  // No coalescing: every gesture boundary matters
+        0.0,
+        &plugin->parameter_listener
+    );
+    if (status != noErr) {
+        plugin->parameter_listener = nullptr;
+        return;
+    }
+
+    const AudioUnitEventType types[] = {
+        kAudioUnitEvent_BeginParameterChangeGesture,
+        kAudioUnitEvent_ParameterValueChange,
+        kAudioUnitEvent_EndParameterChangeGesture,
+    };
+    for (UInt32 i = 0; i < plugin->parameter_count; i++) {
+        for (AudioUnitEventType type : types) {
+            AudioUnitEvent event = {};
+            event.mEventType = type;
+            event.mArgument.mParameter.mAudioUnit = plugin->audio_unit;
+            event.mArgument.mParameter.mParameterID = plugin->parameter_ids[i];
+            event.mArgument.mParameter.mScope = kAudioUnitScope_Global;
+            event.mArgument.mParameter.mElement = 0;
+            AUEventListenerAddEventType(plugin->parameter_listener, plugin, &event);
+        }
+    }
+}
+
 // ============================================================================
 // Plugin Instance Implementation
 // ============================================================================
 
+// Host callbacks: report the transport set with rack_au_plugin_set_transport()
+// Called by the AudioUnit during AudioUnitRender, so they describe the start
+// of the block being rendered. Any output pointer may be null.
+static OSStatus host_beat_and_tempo(void* user_data, Float64* out_current_beat, Float64* out_current_tempo) {
+    RackAUPlugin* plugin = static_cast<RackAUPlugin*>(user_data);
+    if (!plugin->transport_valid) {
+        return kAudioUnitErr_CannotDoInCurrentContext;
+    }
+    if (out_current_beat) {
+        *out_current_beat = plugin->beat;
+    }
+    if (out_current_tempo) {
+        *out_current_tempo = plugin->tempo;
+    }
+    return noErr;
+}
+
+static OSStatus host_musical_time_location(
+    void* user_data,
+    UInt32* out_delta_sample_offset_to_next_beat,
+    Float32* out_time_sig_numerator,
+    UInt32* out_time_sig_denominator,
+    Float64* out_current_measure_down_beat)
+{
+    RackAUPlugin* plugin = static_cast<RackAUPlugin*>(user_data);
+    if (!plugin->transport_valid) {
+        return kAudioUnitErr_CannotDoInCurrentContext;
+    }
+    if (out_delta_sample_offset_to_next_beat) {
+        double samples_per_beat = plugin->sample_rate * 60.0 / plugin->tempo;
+        double to_next_beat = std::ceil(plugin->beat) - plugin->beat;
+        *out_delta_sample_offset_to_next_beat = static_cast<UInt32>(to_next_beat * samples_per_beat + 0.5);
+    }
+    if (out_time_sig_numerator) {
+        *out_time_sig_numerator = static_cast<Float32>(plugin->time_sig_numerator);
+    }
+    if (out_time_sig_denominator) {
+        *out_time_sig_denominator = plugin->time_sig_denominator;
+    }
+    if (out_current_measure_down_beat) {
+        *out_current_measure_down_beat = plugin->bar_start_beat;
+    }
+    return noErr;
+}
+
+static OSStatus host_transport_state2(
+    void* user_data,
+    Boolean* out_is_playing,
+    Boolean* out_is_recording,
+    Boolean* out_transport_state_changed,
+    Float64* out_current_sample_in_time_line,
+    Boolean* out_is_cycling,
+    Float64* out_cycle_start_beat,
+    Float64* out_cycle_end_beat)
+{
+    RackAUPlugin* plugin = static_cast<RackAUPlugin*>(user_data);
+    if (!plugin->transport_valid) {
+        return kAudioUnitErr_CannotDoInCurrentContext;
+    }
+    if (out_is_playing) {
+        *out_is_playing = plugin->playing;
+    }
+    if (out_is_recording) {
+        *out_is_recording = false;
+    }
+    if (out_transport_state_changed) {
+        *out_transport_state_changed = plugin->transport_changed;
+    }
+    if (out_current_sample_in_time_line) {
+        *out_current_sample_in_time_line = static_cast<Float64>(plugin->sample_position);
+    }
+    if (out_is_cycling) {
+        *out_is_cycling = plugin->cycling;
+    }
+    if (out_cycle_start_beat) {
+        *out_cycle_start_beat = plugin->cycle_start_beat;
+    }
+    if (out_cycle_end_beat) {
+        *out_cycle_end_beat = plugin->cycle_end_beat;
+    }
+    return noErr;
+}
+
+static OSStatus host_transport_state(
+    void* user_data,
+    Boolean* out_is_playing,
+    Boolean* out_transport_state_changed,
+    Float64* out_current_sample_in_time_line,
+    Boolean* out_is_cycling,
+    Float64* out_cycle_start_beat,
+    Float64* out_cycle_end_beat)
+{
+    return host_transport_state2(
+        user_data, out_is_playing, nullptr, out_transport_state_changed,
+        out_current_sample_in_time_line, out_is_cycling, out_cycle_start_beat, out_cycle_end_beat);
+}
+
 // Helper function to convert AudioUnitParameterUnit enum to human-readable string
+// Helper: Copy a CFString into a UTF-8 buffer
+// Unlike CFStringGetCString, which fails outright when the string doesn't fit,
+// this truncates on a character boundary. Returns false if nothing could be
+// converted. Always null-terminates (buffer_size must be > 0).
+static bool cfstring_to_utf8_truncated(CFStringRef str, char* buffer, size_t buffer_size) {
+    buffer[0] = '\0';
+    if (!str) {
+        return false;
+    }
+
+    // CFStringGetBytes only converts whole characters, so a cut never lands
+    // inside a multi-byte sequence
+    CFIndex length = CFStringGetLength(str);
+    CFIndex used = 0;
+    CFIndex converted = CFStringGetBytes(
+        str,
+        CFRangeMake(0, length),
+        kCFStringEncodingUTF8,
+        0,
+        false,
+        reinterpret_cast<UInt8*>(buffer),
+        static_cast<CFIndex>(buffer_size - 1),
+        &used
+    );
+    buffer[used] = '\0';
+
+    return converted > 0 || length == 0;
+}
+
+// Helper: Copy a CFString into a std::string as UTF-8
+// Returns false if the string couldn't be converted.
+static bool cfstring_to_std_string(CFStringRef str, std::string& out) {
+    out.clear();
+    if (!str) {
+        return false;
+    }
+
+    CFIndex length = CFStringGetLength(str);
+    CFIndex required = 0;
+    CFStringGetBytes(str, CFRangeMake(0, length), kCFStringEncodingUTF8, 0, false, nullptr, 0, &required);
+
+    out.resize(static_cast<size_t>(required));
+    CFIndex used = 0;
+    CFIndex converted = CFStringGetBytes(
+        str,
+        CFRangeMake(0, length),
+        kCFStringEncodingUTF8,
+        0,
+        false,
+        reinterpret_cast<UInt8*>(&out[0]),
+        required,
+        &used
+    );
+    out.resize(static_cast<size_t>(used));
+
+    return converted == length;
+}
+
+// Helper: Copy a UTF-8 string into a length-negotiated buffer
+// With a NULL buffer only the required size (including the null terminator)
+// is reported. Never truncates: a buffer that is too small is left untouched.
+static int copy_string_negotiated(const std::string& str, char* buffer, size_t* size) {
+    if (!size) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+
+    size_t required = str.size() + 1;
+    if (!buffer) {
+        *size = required;
+        return RACK_AU_OK;
+    }
+    if (*size < required) {
+        *size = required;
+        return RACK_AU_ERROR_BUFFER_TOO_SMALL;
+    }
+
+    memcpy(buffer, str.data(), str.size());
+    buffer[str.size()] = '\0';
+    *size = required;
+    return RACK_AU_OK;
+}
+
 static const char* parameter_unit_to_string(AudioUnitParameterUnit unit) {
     switch (unit) {
         case kAudioUnitParameterUnit_Generic: return "";
@@ -196,11 +581,59 @@ RackAUPlugin* rack_au_plugin_new(const char* unique_id) {
     return plugin;
 }
 
+int rack_au_plugin_set_host_info(RackAUPlugin* plugin, const char* name, uint32_t version) {
+    if (!plugin || !plugin->audio_unit || !name) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+
+    CFStringRef host_name = CFStringCreateWithCString(kCFAllocatorDefault, name, kCFStringEncodingUTF8);
+    if (!host_name) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+
+    AUHostVersionIdentifier identifier;
+    identifier.hostName = host_name;
+    identifier.hostVersion = version;
+
+    OSStatus status = AudioUnitSetProperty(
+        plugin->audio_unit,
+        kAudioUnitProperty_AUHostIdentifier,
+        kAudioUnitScope_Global,
+        0,
+        &identifier,
+        sizeof(identifier)
+    );
+
+    // The AudioUnit retains the name if it keeps it
+    CFRelease(host_name);
+
+    if (status != noErr) {
+        return RACK_AU_ERROR_AUDIO_UNIT + status;
+    }
+
+    return RACK_AU_OK;
+}
+
 void rack_au_plugin_free(RackAUPlugin* plugin) {
     if (!plugin) {
         return;
     }
 
+    // Stop property notifications before the instance goes away
+    if (plugin->audio_unit && plugin->listeners_registered) {
+        for (AudioUnitPropertyID property : kWatchedProperties) {
+            AudioUnitRemovePropertyListenerWithUserData(plugin->audio_unit, property, property_changed, plugin);
+        }
+    }
+    if (plugin->parameter_listener) {
+        AUListenerDispose(plugin->parameter_listener);
+        plugin->parameter_listener = nullptr;
+    }
+    {
+        std::lock_guard<std::mutex> lock(plugin->change_mutex);
+        plugin->change_callback = nullptr;
+    }
+
     if (plugin->audio_unit) {
         // Serialize AudioUnit cleanup to avoid crashes in Apple's framework
         // when multiple instances are being disposed concurrently
@@ -382,6 +815,23 @@ int rack_au_plugin_initialize(RackAUPlugin* plugin, double sample_rate, uint32_t
     // This may fail for instruments (no input), which is okay
     // We don't return error here
 
+    // Give the AudioUnit the transport (rack_au_plugin_set_transport)
+    HostCallbackInfo host_callbacks = {};
+    host_callbacks.hostUserData = plugin;
+    host_callbacks.beatAndTempoProc = host_beat_and_tempo;
+    host_callbacks.musicalTimeLocationProc = host_musical_time_location;
+    host_callbacks.transportStateProc = host_transport_state;
+    host_callbacks.transportStateProc2 = host_transport_state2;
+    AudioUnitSetProperty(
+        plugin->audio_unit,
+        kAudioUnitProperty_HostCallbacks,
+        kAudioUnitScope_Global,
+        0,
+        &host_callbacks,
+        sizeof(host_callbacks)
+    );
+    // Plugins that don't sync to the host may not support the property
+
     // Initialize the AudioUnit
     // Serialize AudioUnit initialization to avoid crashes in Apple's framework
     {
@@ -470,6 +920,16 @@ int rack_au_plugin_initialize(RackAUPlugin* plugin, double sample_rate, uint32_t
         }
     }
 
+    // Watch for runtime changes. Registered after initialization so the stream
+    // formats set above aren't reported as changes.
+    if (!plugin->listeners_registered) {
+        for (AudioUnitPropertyID property : kWatchedProperties) {
+            AudioUnitAddPropertyListener(plugin->audio_unit, property, property_changed, plugin);
+        }
+        add_parameter_listener(plugin);
+        plugin->listeners_registered = true;
+    }
+
     plugin->initialized = true;
     return RACK_AU_OK;
 }
@@ -538,6 +998,13 @@ int rack_au_plugin_process(
     memset(&timestamp, 0, sizeof(timestamp));
     timestamp.mFlags = kAudioTimeStampSampleTimeValid;
     timestamp.mSampleTime = plugin->sample_position;
+    if (plugin->smpte_valid) {
+        timestamp.mFlags |= kAudioTimeStampSMPTETimeValid;
+        timestamp.mSMPTETime = plugin->smpte;
+        if (plugin->playing) {
+            timestamp.mSMPTETime.mFlags |= kSMPTETimeRunning;
+        }
+    }
 
     // Render audio from the AudioUnit
     AudioUnitRenderActionFlags flags = 0;
@@ -558,7 +1025,120 @@ int rack_au_plugin_process(
 
     // Update sample position for next call
     plugin->sample_position += frames;
+    plugin->transport_changed = false;
+
+    return RACK_AU_OK;
+}
+
+int rack_au_plugin_set_sample_position(RackAUPlugin* plugin, int64_t position) {
+    if (!plugin || position < 0) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+    if (position != plugin->sample_position) {
+        plugin->transport_changed = true;
+    }
+    plugin->sample_position = position;
+    return RACK_AU_OK;
+}
+
+int rack_au_plugin_set_transport(
+    RackAUPlugin* plugin,
+    int playing,
+    double tempo,
+    int32_t numerator,
+    int32_t denominator,
+    double beat,
+    double bar_start_beat,
+    int cycling,
+    double cycle_start_beat,
+    double cycle_end_beat
+) {
+    if (!plugin || tempo <= 0.0 || numerator <= 0 || denominator <= 0) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+
+    if (!plugin->transport_valid || plugin->playing != (playing != 0)) {
+        plugin->transport_changed = true;
+    }
+    plugin->transport_valid = true;
+    plugin->playing = playing != 0;
+    plugin->tempo = tempo;
+    plugin->time_sig_numerator = static_cast<uint32_t>(numerator);
+    plugin->time_sig_denominator = static_cast<uint32_t>(denominator);
+    plugin->beat = beat;
+    plugin->bar_start_beat = bar_start_beat;
+    plugin->cycling = cycling != 0;
+    plugin->cycle_start_beat = cycle_start_beat;
+    plugin->cycle_end_beat = cycle_end_beat;
+    return RACK_AU_OK;
+}
+
+// Map a nominal frame rate and RACK_AU_FRAME_RATE_* flags to an SMPTETimeType
+static bool smpte_time_type(uint32_t fps, uint32_t flags, SMPTETimeType* type) {
+    bool pull_down = (flags & RACK_AU_FRAME_RATE_PULL_DOWN) != 0;
+    bool drop = (flags & RACK_AU_FRAME_RATE_DROP) != 0;
+    switch (fps) {
+        case 24:
+            if (drop) return false;
+            *type = pull_down ? kSMPTETimeType2398 : kSMPTETimeType24;
+            return true;
+        case 25:
+            if (drop || pull_down) return false;
+            *type = kSMPTETimeType25;
+            return true;
+        case 30:
+            if (pull_down) {
+                *type = drop ? kSMPTETimeType2997Drop : kSMPTETimeType2997;
+            } else {
+                *type = drop ? kSMPTETimeType30Drop : kSMPTETimeType30;
+            }
+            return true;
+        case 50:
+            if (drop || pull_down) return false;
+            *type = kSMPTETimeType50;
+            return true;
+        case 60:
+            if (pull_down) {
+                *type = drop ? kSMPTETimeType5994Drop : kSMPTETimeType5994;
+            } else {
+                *type = drop ? kSMPTETimeType60Drop : kSMPTETimeType60;
+            }
+            return true;
+        default:
+            return false;
+    }
+}
+
+int rack_au_plugin_set_timecode(
+    RackAUPlugin* plugin,
+    uint32_t frames_per_second,
+    uint32_t flags,
+    uint32_t hours,
+    uint32_t minutes,
+    uint32_t seconds,
+    uint32_t frames,
+    uint32_t subframes
+) {
+    SMPTETimeType type;
+    if (!plugin || !smpte_time_type(frames_per_second, flags, &type)) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+    if (hours > INT16_MAX || minutes > 59 || seconds > 59 ||
+        frames >= frames_per_second || subframes >= 80) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
 
+    SMPTETime smpte = {};
+    smpte.mSubframes = static_cast<SInt16>(subframes);
+    smpte.mSubframeDivisor = 80;
+    smpte.mType = type;
+    smpte.mFlags = kSMPTETimeValid;
+    smpte.mHours = static_cast<SInt16>(hours);
+    smpte.mMinutes = static_cast<SInt16>(minutes);
+    smpte.mSeconds = static_cast<SInt16>(seconds);
+    smpte.mFrames = static_cast<SInt16>(frames);
+    plugin->smpte = smpte;
+    plugin->smpte_valid = true;
     return RACK_AU_OK;
 }
 
@@ -624,34 +1204,18 @@ int rack_au_plugin_get_parameter(RackAUPlugin* plugin, uint32_t index, float* va
     }
 
     // Normalize to 0.0-1.0 range
-    float min_val = param_info.minValue;
-    float max_val = param_info.maxValue;
-    float range = max_val - min_val;
-
-    // Validate parameter range (detect malformed AudioUnit parameter info)
-    if (max_val < min_val) {
-        // Invalid range - this shouldn't happen with well-formed AudioUnits
-        // Return mid-range as safe fallback
-        *value = 0.5f;
-        return RACK_AU_OK;
-    }
-
-    // Use epsilon comparison for floating-point safety
-    const float epsilon = 1e-7f;
-    if (range > epsilon) {
-        *value = (raw_value - min_val) / range;
-        // Clamp to 0.0-1.0 in case of floating-point rounding errors
-        if (*value < 0.0f) *value = 0.0f;
-        if (*value > 1.0f) *value = 1.0f;
-    } else {
-        // Zero or near-zero range - return 0.0 (parameter has single value)
-        *value = 0.0f;
-    }
-
+    *value = normalize_value(param_info, raw_value);
     return RACK_AU_OK;
 }
 
-int rack_au_plugin_set_parameter(RackAUPlugin* plugin, uint32_t index, float value) {
+// Helper: Look up a parameter's ID and convert a normalized value to its range
+static int denormalize_parameter(
+    RackAUPlugin* plugin,
+    uint32_t index,
+    float value,
+    AudioUnitParameterID* out_id,
+    AudioUnitParameterValue* out_value)
+{
     if (!plugin || !plugin->initialized) {
         return RACK_AU_ERROR_NOT_INITIALIZED;
     }
@@ -695,6 +1259,19 @@ int rack_au_plugin_set_parameter(RackAUPlugin* plugin, uint32_t index, float val
     float max_val = param_info.maxValue;
     float raw_value = min_val + (value * (max_val - min_val));
 
+    *out_id = param_id;
+    *out_value = raw_value;
+    return RACK_AU_OK;
+}
+
+int rack_au_plugin_set_parameter(RackAUPlugin* plugin, uint32_t index, float value) {
+    AudioUnitParameterID param_id;
+    AudioUnitParameterValue raw_value;
+    int result = denormalize_parameter(plugin, index, value, &param_id, &raw_value);
+    if (result != RACK_AU_OK) {
+        return result;
+    }
+
     // Set parameter value (with 0 sample offset for immediate change)
     OSStatus status = AudioUnitSetParameter(
         plugin->audio_unit,
@@ -712,6 +1289,31 @@ int rack_au_plugin_set_parameter(RackAUPlugin* plugin, uint32_t index, float val
     return RACK_AU_OK;
 }
 
+int rack_au_plugin_set_parameter_at(RackAUPlugin* plugin, uint32_t index, float value, uint32_t sample_offset) {
+    AudioUnitParameterID param_id;
+    AudioUnitParameterValue raw_value;
+    int result = denormalize_parameter(plugin, index, value, &param_id, &raw_value);
+    if (result != RACK_AU_OK) {
+        return result;
+    }
+
+    // Applied by the next AudioUnitRender, sample_offset frames into the buffer
+    AudioUnitParameterEvent event = {};
+    event.scope = kAudioUnitScope_Global;
+    event.element = 0;
+    event.parameter = param_id;
+    event.eventType = kParameterEvent_Immediate;
+    event.eventValues.immediate.bufferOffset = sample_offset;
+    event.eventValues.immediate.value = raw_value;
+
+    OSStatus status = AudioUnitScheduleParameters(plugin->audio_unit, &event, 1);
+    if (status != noErr) {
+        return RACK_AU_ERROR_AUDIO_UNIT + status;
+    }
+
+    return RACK_AU_OK;
+}
+
 int rack_au_plugin_parameter_info(
     RackAUPlugin* plugin,
     uint32_t index,
@@ -727,7 +1329,7 @@ int rack_au_plugin_parameter_info(
         return RACK_AU_ERROR_NOT_INITIALIZED;
     }
 
-    if (!name || !min || !max || !default_value) {
+    if (!min || !max || !default_value) {
         return RACK_AU_ERROR_INVALID_PARAM;
     }
 
@@ -765,16 +1367,13 @@ int rack_au_plugin_parameter_info(
     // and don't need to CFRelease it (AudioUnit manages the lifecycle).
     // Reference: Audio Unit Programming Guide, "Getting Parameter Information"
     // https://developer.apple.com/library/archive/documentation/MusicAudio/Conceptual/AudioUnitProgrammingGuide/
-    if (param_info.cfNameString) {
-        CFStringGetCString(
-            param_info.cfNameString,
-            name,
-            name_size,
-            kCFStringEncodingUTF8
-        );
-    } else {
-        // Fallback: use parameter ID as name
-        snprintf(name, name_size, "Parameter %u", param_id);
+    if (name && name_size > 0) {
+        if (param_info.cfNameString) {
+            cfstring_to_utf8_truncated(param_info.cfNameString, name, name_size);
+        } else {
+            // Fallback: use parameter ID as name
+            snprintf(name, name_size, "Parameter %u", param_id);
+        }
     }
 
     // Extract parameter unit string (optional)
@@ -792,60 +1391,181 @@ int rack_au_plugin_parameter_info(
     return RACK_AU_OK;
 }
 
-// ============================================================================
-// Preset Management Implementation
-// ============================================================================
-
-int rack_au_plugin_get_preset_count(RackAUPlugin* plugin) {
+int rack_au_plugin_parameter_flags(RackAUPlugin* plugin, uint32_t index, uint32_t* flags) {
     if (!plugin || !plugin->initialized) {
-        return 0;
-    }
-
-    // Query factory presets
-    CFArrayRef presets = nullptr;
-    UInt32 data_size = sizeof(presets);
-    OSStatus status = AudioUnitGetProperty(
-        plugin->audio_unit,
-        kAudioUnitProperty_FactoryPresets,
-        kAudioUnitScope_Global,
-        0,
-        &presets,
-        &data_size
-    );
-
-    if (status != noErr || !presets) {
-        return 0;  // Plugin has no factory presets
+        return RACK_AU_ERROR_NOT_INITIALIZED;
     }
 
-    CFIndex count = CFArrayGetCount(presets);
-    // Note: presets CFArrayRef is not owned by us - don't CFRelease
-    // Reference: Audio Unit Programming Guide, "Factory Presets"
-    return static_cast<int>(count);
-}
-
-int rack_au_plugin_get_preset_info(
-    RackAUPlugin* plugin,
-    uint32_t index,
-    char* name,
-    size_t name_size,
-    int32_t* preset_number
-) {
-    if (!plugin || !plugin->initialized) {
-        return RACK_AU_ERROR_NOT_INITIALIZED;
+    if (!flags) {
+        return RACK_AU_ERROR_INVALID_PARAM;
     }
 
-    if (!name || !preset_number) {
+    if (index >= plugin->parameter_count) {
         return RACK_AU_ERROR_INVALID_PARAM;
     }
 
-    // Query factory presets
-    CFArrayRef presets = nullptr;
-    UInt32 data_size = sizeof(presets);
-    OSStatus status = AudioUnitGetProperty(
-        plugin->audio_unit,
-        kAudioUnitProperty_FactoryPresets,
-        kAudioUnitScope_Global,
-        0,
+    AudioUnitParameterInfo param_info;
+
+    if (plugin->parameter_info) {
+        // Use cached parameter info (fast path)
+        param_info = plugin->parameter_info[index];
+    } else {
+        // Fall back to querying parameter info (slow path - cache failed to initialize)
+        UInt32 data_size = sizeof(param_info);
+        OSStatus status = AudioUnitGetProperty(
+            plugin->audio_unit,
+            kAudioUnitProperty_ParameterInfo,
+            kAudioUnitScope_Global,
+            plugin->parameter_ids[index],
+            &param_info,
+            &data_size
+        );
+
+        if (status != noErr) {
+            return RACK_AU_ERROR_AUDIO_UNIT + status;
+        }
+    }
+
+    *flags = param_info.flags;
+    return RACK_AU_OK;
+}
+
+int rack_au_plugin_parameter_name(RackAUPlugin* plugin, uint32_t index, char* buffer, size_t* size) {
+    if (!plugin || !plugin->initialized) {
+        return RACK_AU_ERROR_NOT_INITIALIZED;
+    }
+
+    if (index >= plugin->parameter_count) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+
+    AudioUnitParameterID param_id = plugin->parameter_ids[index];
+    AudioUnitParameterInfo param_info;
+
+    if (plugin->parameter_info) {
+        // Use cached parameter info (fast path)
+        param_info = plugin->parameter_info[index];
+    } else {
+        // Fall back to querying parameter info (slow path - cache failed to initialize)
+        UInt32 data_size = sizeof(param_info);
+        OSStatus status = AudioUnitGetProperty(
+            plugin->audio_unit,
+            kAudioUnitProperty_ParameterInfo,
+            kAudioUnitScope_Global,
+            param_id,
+            &param_info,
+            &data_size
+        );
+
+        if (status != noErr) {
+            return RACK_AU_ERROR_AUDIO_UNIT + status;
+        }
+    }
+
+    // Same fallback as rack_au_plugin_parameter_info
+    std::string name;
+    if (!cfstring_to_std_string(param_info.cfNameString, name)) {
+        name = "Parameter " + std::to_string(param_id);
+    }
+
+    return copy_string_negotiated(name, buffer, size);
+}
+
+int rack_au_plugin_parameter_unit(RackAUPlugin* plugin, uint32_t index, char* buffer, size_t* size) {
+    if (!plugin || !plugin->initialized) {
+        return RACK_AU_ERROR_NOT_INITIALIZED;
+    }
+
+    if (index >= plugin->parameter_count) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+
+    AudioUnitParameterInfo param_info;
+
+    if (plugin->parameter_info) {
+        // Use cached parameter info (fast path)
+        param_info = plugin->parameter_info[index];
+    } else {
+        // Fall back to querying parameter info (slow path - cache failed to initialize)
+        UInt32 data_size = sizeof(param_info);
+        OSStatus status = AudioUnitGetProperty(
+            plugin->audio_unit,
+            kAudioUnitProperty_ParameterInfo,
+            kAudioUnitScope_Global,
+            plugin->parameter_ids[index],
+            &param_info,
+            &data_size
+        );
+
+        if (status != noErr) {
+            return RACK_AU_ERROR_AUDIO_UNIT + status;
+        }
+    }
+
+    // Custom units carry their own name (owned by the AudioUnit, like cfNameString)
+    std::string unit;
+    if (param_info.unit != kAudioUnitParameterUnit_CustomUnit ||
+        !cfstring_to_std_string(param_info.unitName, unit)) {
+        unit = parameter_unit_to_string(param_info.unit);
+    }
+
+    return copy_string_negotiated(unit, buffer, size);
+}
+
+// ============================================================================
+// Preset Management Implementation
+// ============================================================================
+
+int rack_au_plugin_get_preset_count(RackAUPlugin* plugin) {
+    if (!plugin || !plugin->initialized) {
+        return 0;
+    }
+
+    // Query factory presets
+    CFArrayRef presets = nullptr;
+    UInt32 data_size = sizeof(presets);
+    OSStatus status = AudioUnitGetProperty(
+        plugin->audio_unit,
+        kAudioUnitProperty_FactoryPresets,
+        kAudioUnitScope_Global,
+        0,
+        &presets,
+        &data_size
+    );
+
+    if (status != noErr || !presets) {
+        return 0;  // Plugin has no factory presets
+    }
+
+    CFIndex count = CFArrayGetCount(presets);
+    // Note: presets CFArrayRef is not owned by us - don't CFRelease
+    // Reference: Audio Unit Programming Guide, "Factory Presets"
+    return static_cast<int>(count);
+}
+
+int rack_au_plugin_get_preset_info(
+    RackAUPlugin* plugin,
+    uint32_t index,
+    char* name,
+    size_t name_size,
+    int32_t* preset_number
+) {
+    if (!plugin || !plugin->initialized) {
+        return RACK_AU_ERROR_NOT_INITIALIZED;
+    }
+
+    if (!name || !preset_number) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+
+    // Query factory presets
+    CFArrayRef presets = nullptr;
+    UInt32 data_size = sizeof(presets);
+    OSStatus status = AudioUnitGetProperty(
+        plugin->audio_unit,
+        kAudioUnitProperty_FactoryPresets,
+        kAudioUnitScope_Global,
+        0,
         &presets,
         &data_size
     );
@@ -874,15 +1594,10 @@ int rack_au_plugin_get_preset_info(
 
     // Extract preset name
     if (preset->presetName) {
-        Boolean success = CFStringGetCString(
-            preset->presetName,
-            name,
-            name_size,
-            kCFStringEncodingUTF8
-        );
+        bool success = cfstring_to_utf8_truncated(preset->presetName, name, name_size);
 
         if (!success) {
-            // Fallback: buffer too small or encoding failed
+            // Fallback: encoding failed
             snprintf(name, name_size, "Preset %d", preset->presetNumber);
         }
     } else {
@@ -893,6 +1608,49 @@ int rack_au_plugin_get_preset_info(
     return RACK_AU_OK;
 }
 
+int rack_au_plugin_preset_name(RackAUPlugin* plugin, uint32_t index, char* buffer, size_t* size) {
+    if (!plugin || !plugin->initialized) {
+        return RACK_AU_ERROR_NOT_INITIALIZED;
+    }
+
+    // Query factory presets
+    CFArrayRef presets = nullptr;
+    UInt32 data_size = sizeof(presets);
+    OSStatus status = AudioUnitGetProperty(
+        plugin->audio_unit,
+        kAudioUnitProperty_FactoryPresets,
+        kAudioUnitScope_Global,
+        0,
+        &presets,
+        &data_size
+    );
+
+    if (status != noErr || !presets) {
+        return RACK_AU_ERROR_NOT_FOUND;
+    }
+
+    CFIndex count = CFArrayGetCount(presets);
+    if (index >= static_cast<uint32_t>(count)) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+
+    const AUPreset* preset = static_cast<const AUPreset*>(
+        CFArrayGetValueAtIndex(presets, index)
+    );
+
+    if (!preset) {
+        return RACK_AU_ERROR_GENERIC;
+    }
+
+    // Same fallback as rack_au_plugin_get_preset_info
+    std::string name;
+    if (!cfstring_to_std_string(preset->presetName, name)) {
+        name = "Preset " + std::to_string(preset->presetNumber);
+    }
+
+    return copy_string_negotiated(name, buffer, size);
+}
+
 int rack_au_plugin_load_preset(RackAUPlugin* plugin, int32_t preset_number) {
     if (!plugin || !plugin->initialized) {
         return RACK_AU_ERROR_NOT_INITIALIZED;
@@ -920,6 +1678,95 @@ int rack_au_plugin_load_preset(RackAUPlugin* plugin, int32_t preset_number) {
     return RACK_AU_OK;
 }
 
+int rack_au_plugin_get_current_preset(
+    RackAUPlugin* plugin,
+    char* name,
+    size_t name_size,
+    int32_t* preset_number
+) {
+    if (!plugin || !plugin->initialized) {
+        return RACK_AU_ERROR_NOT_INITIALIZED;
+    }
+
+    if (!name || name_size == 0 || !preset_number) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+
+    AUPreset preset;
+    memset(&preset, 0, sizeof(preset));
+    UInt32 data_size = sizeof(preset);
+    OSStatus status = AudioUnitGetProperty(
+        plugin->audio_unit,
+        kAudioUnitProperty_PresentPreset,
+        kAudioUnitScope_Global,
+        0,
+        &preset,
+        &data_size
+    );
+
+    if (status != noErr) {
+        // Property not supported: the plugin doesn't track its current preset
+        return 0;
+    }
+
+    *preset_number = preset.presetNumber;
+
+    // Unlike FactoryPresets, PresentPreset returns a name we own
+    if (preset.presetName) {
+        bool success = cfstring_to_utf8_truncated(preset.presetName, name, name_size);
+        CFRelease(preset.presetName);
+
+        if (!success) {
+            snprintf(name, name_size, "Preset %d", preset.presetNumber);
+        }
+    } else {
+        snprintf(name, name_size, "Preset %d", preset.presetNumber);
+    }
+
+    return 1;
+}
+
+int rack_au_plugin_current_preset_name(RackAUPlugin* plugin, char* buffer, size_t* size) {
+    if (!plugin || !plugin->initialized) {
+        return RACK_AU_ERROR_NOT_INITIALIZED;
+    }
+
+    if (!size) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+
+    AUPreset preset;
+    memset(&preset, 0, sizeof(preset));
+    UInt32 data_size = sizeof(preset);
+    OSStatus status = AudioUnitGetProperty(
+        plugin->audio_unit,
+        kAudioUnitProperty_PresentPreset,
+        kAudioUnitScope_Global,
+        0,
+        &preset,
+        &data_size
+    );
+
+    if (status != noErr) {
+        // Property not supported: the plugin doesn't track its current preset
+        *size = 0;
+        return 0;
+    }
+
+    // Unlike FactoryPresets, PresentPreset returns a name we own
+    std::string name;
+    bool converted = cfstring_to_std_string(preset.presetName, name);
+    if (preset.presetName) {
+        CFRelease(preset.presetName);
+    }
+    if (!converted) {
+        name = "Preset " + std::to_string(preset.presetNumber);
+    }
+
+    int result = copy_string_negotiated(name, buffer, size);
+    return result == RACK_AU_OK ? 1 : result;
+}
+
 int rack_au_plugin_get_state_size(RackAUPlugin* plugin) {
     if (!plugin || !plugin->initialized) {
         return 0;
@@ -1147,6 +1994,163 @@ int rack_au_plugin_send_midi(
     return RACK_AU_OK;
 }
 
+// ============================================================================
+// Render Settings
+// ============================================================================
+
+int rack_au_plugin_set_max_frames(RackAUPlugin* plugin, uint32_t max_frames) {
+    if (!plugin || !plugin->audio_unit || max_frames == 0) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+
+    // Plugins size their buffers in AudioUnitInitialize, so an initialized
+    // plugin goes through uninitialize/initialize around the change
+    bool was_initialized = plugin->initialized;
+    if (was_initialized) {
+        std::lock_guard<std::mutex> lock(g_audio_unit_cleanup_mutex);
+        AudioUnitUninitialize(plugin->audio_unit);
+        plugin->initialized = false;
+    }
+
+    UInt32 frames = max_frames;
+    OSStatus status = AudioUnitSetProperty(
+        plugin->audio_unit,
+        kAudioUnitProperty_MaximumFramesPerSlice,
+        kAudioUnitScope_Global,
+        0,
+        &frames,
+        sizeof(frames)
+    );
+
+    // Re-initialize even if the property was refused, so a failed change
+    // leaves the plugin as it was
+    if (was_initialized) {
+        OSStatus init_status;
+        {
+            std::lock_guard<std::mutex> lock(g_audio_unit_cleanup_mutex);
+            init_status = AudioUnitInitialize(plugin->audio_unit);
+        }
+        if (init_status != noErr) {
+            return RACK_AU_ERROR_AUDIO_UNIT + init_status;
+        }
+        plugin->initialized = true;
+    }
+
+    if (status != noErr) {
+        return RACK_AU_ERROR_AUDIO_UNIT + status;
+    }
+
+    plugin->max_block_size = max_frames;
+    return RACK_AU_OK;
+}
+
+int rack_au_plugin_get_render_quality(RackAUPlugin* plugin, uint32_t* quality) {
+    if (!plugin || !plugin->audio_unit || !quality) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+
+    UInt32 value = 0;
+    UInt32 size = sizeof(value);
+    OSStatus status = AudioUnitGetProperty(
+        plugin->audio_unit,
+        kAudioUnitProperty_RenderQuality,
+        kAudioUnitScope_Global,
+        0,
+        &value,
+        &size
+    );
+    if (status != noErr) {
+        return RACK_AU_ERROR_AUDIO_UNIT + status;
+    }
+
+    *quality = value;
+    return RACK_AU_OK;
+}
+
+int rack_au_plugin_set_render_quality(RackAUPlugin* plugin, uint32_t quality) {
+    if (!plugin || !plugin->audio_unit || quality > kRenderQuality_Max) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+
+    UInt32 value = quality;
+    OSStatus status = AudioUnitSetProperty(
+        plugin->audio_unit,
+        kAudioUnitProperty_RenderQuality,
+        kAudioUnitScope_Global,
+        0,
+        &value,
+        sizeof(value)
+    );
+    if (status != noErr) {
+        return RACK_AU_ERROR_AUDIO_UNIT + status;
+    }
+
+    return RACK_AU_OK;
+}
+
+int rack_au_plugin_set_offline_render(RackAUPlugin* plugin, int offline) {
+    if (!plugin || !plugin->audio_unit) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+
+    UInt32 value = offline ? 1 : 0;
+    OSStatus status = AudioUnitSetProperty(
+        plugin->audio_unit,
+        kAudioUnitProperty_OfflineRender,
+        kAudioUnitScope_Global,
+        0,
+        &value,
+        sizeof(value)
+    );
+    if (status != noErr) {
+        return RACK_AU_ERROR_AUDIO_UNIT + status;
+    }
+
+    return RACK_AU_OK;
+}
+
+int rack_au_plugin_set_metering_mode(RackAUPlugin* plugin, int enabled) {
+    if (!plugin || !plugin->audio_unit) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+
+    UInt32 value = enabled ? 1 : 0;
+    OSStatus status = AudioUnitSetProperty(
+        plugin->audio_unit,
+        kAudioUnitProperty_MeteringMode,
+        kAudioUnitScope_Global,
+        0,
+        &value,
+        sizeof(value)
+    );
+    if (status != noErr) {
+        return RACK_AU_ERROR_AUDIO_UNIT + status;
+    }
+
+    return RACK_AU_OK;
+}
+
+int rack_au_plugin_set_bypass(RackAUPlugin* plugin, int bypassed) {
+    if (!plugin || !plugin->audio_unit) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+
+    UInt32 value = bypassed ? 1 : 0;
+    OSStatus status = AudioUnitSetProperty(
+        plugin->audio_unit,
+        kAudioUnitProperty_BypassEffect,
+        kAudioUnitScope_Global,
+        0,
+        &value,
+        sizeof(value)
+    );
+    if (status != noErr) {
+        return RACK_AU_ERROR_AUDIO_UNIT + status;
+    }
+
+    return RACK_AU_OK;
+}
+
 // ============================================================================
 // Channel Count Query
 // ============================================================================
@@ -1165,6 +2169,25 @@ int rack_au_plugin_get_output_channels(RackAUPlugin* plugin) {
     return static_cast<int>(plugin->output_channels);
 }
 
+int rack_au_plugin_get_latency(RackAUPlugin* plugin) {
+    if (!plugin || !plugin->initialized || plugin->sample_rate <= 0.0) {
+        return 0;
+    }
+    Float64 seconds = get_float64_property(plugin->audio_unit, kAudioUnitProperty_Latency);
+    if (seconds <= 0.0) {
+        return 0;
+    }
+    return static_cast<int>(seconds * plugin->sample_rate + 0.5);
+}
+
+double rack_au_plugin_get_tail_time(RackAUPlugin* plugin) {
+    if (!plugin || !plugin->initialized) {
+        return 0.0;
+    }
+    Float64 seconds = get_float64_property(plugin->audio_unit, kAudioUnitProperty_TailTime);
+    return seconds > 0.0 ? seconds : 0.0;
+}
+
 // ============================================================================
 // GUI Helper
 // ============================================================================
@@ -1177,3 +2200,22 @@ extern "C" AudioComponentInstance rack_au_plugin_get_audio_unit(RackAUPlugin* pl
     }
     return plugin->audio_unit;
 }
+
+// ============================================================================
+// Change Notification API
+// ============================================================================
+
+int rack_au_plugin_set_change_callback(
+    RackAUPlugin* plugin,
+    RackAUChangeCallback callback,
+    void* user_data)
+{
+    if (!plugin) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+
+    std::lock_guard<std::mutex> lock(plugin->change_mutex);
+    plugin->change_callback = callback;
+    plugin->change_user_data = user_data;
+    return RACK_AU_OK;
+}
@@ -3,22 +3,103 @@
 #include <CoreFoundation/CoreFoundation.h>
 #include <new>
 #include <cstring>
+#include <string>
+#include <vector>
+
+// Untruncated strings of a scanned plugin
+// RackAUPluginInfo has fixed-size fields; these are served by
+// rack_au_scanner_plugin_string()
+struct ScannedStrings {
+    std::string name;
+    std::string manufacturer;
+    std::string path;
+};
 
 // Internal scanner state
 // Note: AudioComponent handles are transient and obtained via
 // AudioComponentFindNext during each scan. We don't need to store them
 // until we implement plugin loading functionality.
 struct RackAUScanner {
-    // Reserved for future use (e.g., caching component handles)
+    // Strings of the plugins filled in by the last scan, by array index
+    std::vector<ScannedStrings> last_scan;
 };
 
 // Helper: Convert CFString to C string
+// Names that don't fit are truncated on a character boundary rather than
+// rejected, so long names stay valid UTF-8.
 static bool CFStringToCString(CFStringRef cfstr, char* buffer, size_t buffer_size) {
     if (!cfstr || !buffer || buffer_size == 0) {
         return false;
     }
-    
-    return CFStringGetCString(cfstr, buffer, buffer_size, kCFStringEncodingUTF8);
+
+    CFIndex length = CFStringGetLength(cfstr);
+    CFIndex used = 0;
+    CFIndex converted = CFStringGetBytes(
+        cfstr,
+        CFRangeMake(0, length),
+        kCFStringEncodingUTF8,
+        0,
+        false,
+        reinterpret_cast<UInt8*>(buffer),
+        static_cast<CFIndex>(buffer_size - 1),
+        &used
+    );
+    buffer[used] = '\0';
+
+    return converted > 0 || length == 0;
+}
+
+// Helper: Convert CFString to std::string (UTF-8)
+// Returns false if the string couldn't be converted.
+static bool CFStringToStdString(CFStringRef cfstr, std::string& out) {
+    out.clear();
+    if (!cfstr) {
+        return false;
+    }
+
+    CFIndex length = CFStringGetLength(cfstr);
+    CFIndex required = 0;
+    CFStringGetBytes(cfstr, CFRangeMake(0, length), kCFStringEncodingUTF8, 0, false, nullptr, 0, &required);
+
+    out.resize(static_cast<size_t>(required));
+    CFIndex used = 0;
+    CFIndex converted = CFStringGetBytes(
+        cfstr,
+        CFRangeMake(0, length),
+        kCFStringEncodingUTF8,
+        0,
+        false,
+        reinterpret_cast<UInt8*>(&out[0]),
+        required,
+        &used
+    );
+    out.resize(static_cast<size_t>(used));
+
+    return converted == length;
+}
+
+// Helper: Copy a UTF-8 string into a length-negotiated buffer
+// With a NULL buffer only the required size (including the null terminator)
+// is reported. Never truncates: a buffer that is too small is left untouched.
+static int CopyStringNegotiated(const std::string& str, char* buffer, size_t* size) {
+    if (!size) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+
+    size_t required = str.size() + 1;
+    if (!buffer) {
+        *size = required;
+        return RACK_AU_OK;
+    }
+    if (*size < required) {
+        *size = required;
+        return RACK_AU_ERROR_BUFFER_TOO_SMALL;
+    }
+
+    memcpy(buffer, str.data(), str.size());
+    buffer[str.size()] = '\0';
+    *size = required;
+    return RACK_AU_OK;
 }
 
 // Helper: Convert AudioUnit type to our enum
@@ -74,6 +155,9 @@ int rack_au_scanner_scan(RackAUScanner* scanner, RackAUPluginInfo* plugins, size
     // If plugins is NULL, we're just counting
     bool count_only = (plugins == nullptr);
     size_t count = 0;
+    if (!count_only) {
+        scanner->last_scan.clear();
+    }
     
     // Enumerate all AudioUnit components
     AudioComponentDescription desc = {0};
@@ -123,6 +207,10 @@ int rack_au_scanner_scan(RackAUScanner* scanner, RackAUPluginInfo* plugins, size
         if (!CFStringToCString(name, info.name, sizeof(info.name))) {
             snprintf(info.name, sizeof(info.name), "<unknown>");
         }
+        std::string full_name;
+        if (!CFStringToStdString(name, full_name)) {
+            full_name = info.name;
+        }
         CFRelease(name);
         
         // Manufacturer (convert OSType to string)
@@ -163,8 +251,41 @@ int rack_au_scanner_scan(RackAUScanner* scanner, RackAUPluginInfo* plugins, size
         // Type
         info.plugin_type = AudioUnitTypeToPluginType(foundDesc.componentType);
 
+        // Flags (sandbox safety, AUv3, async instantiation, in-process loading)
+        info.component_flags = foundDesc.componentFlags;
+
+        scanner->last_scan.push_back({full_name, info.manufacturer, info.path});
+
         count++;
     }
     
     return static_cast<int>(count);
 }
+
+int rack_au_scanner_plugin_string(
+    RackAUScanner* scanner,
+    size_t index,
+    int field,
+    char* buffer,
+    size_t* size)
+{
+    if (!scanner) {
+        return RACK_AU_ERROR_INVALID_PARAM;
+    }
+
+    if (index >= scanner->last_scan.size()) {
+        return RACK_AU_ERROR_NOT_FOUND;
+    }
+
+    const auto& strings = scanner->last_scan[index];
+    switch (field) {
+        case RACK_AU_FIELD_NAME:
+            return CopyStringNegotiated(strings.name, buffer, size);
+        case RACK_AU_FIELD_MANUFACTURER:
+            return CopyStringNegotiated(strings.manufacturer, buffer, size);
+        case RACK_AU_FIELD_PATH:
+            return CopyStringNegotiated(strings.path, buffer, size);
+        default:
+            return RACK_AU_ERROR_INVALID_PARAM;
+    }
+}
@@ -0,0 +1,891 @@
+#include "rack_vst2.h"
+#include "pluginterfaces/vst2.x/aeffectx.h"
+
+#include <vector>
+#include <string>
+#include <cstring>
+#include <mutex>
+#include <algorithm>
+#include <memory>
+
+#if defined(_WIN32)
+    #include <windows.h>
+#else
+    #include <dlfcn.h>
+    #include <sys/stat.h>
+#endif
+
+// VST2 entry point: VSTPluginMain (or the legacy main / main_macho)
+typedef AEffect* (*VstMainFunction)(audioMasterCallback host);
+
+// Global mutex for VST2 lifecycle operations
+// Loading a library and creating its plugin is not guaranteed to be
+// thread-safe, and shell plugins ask for the ID to create through a callback
+static std::mutex g_vst2_lifecycle_mutex;
+
+// ID a shell plugin should create, answered to audioMasterCurrentId while the
+// entry point runs (0 for the shell itself)
+static VstInt32 g_loading_id = 0;
+
+// Host name reported through audioMasterGetProductString, and the extra
+// capabilities answered in audioMasterCanDo (rack_vst2_set_host_capabilities)
+static std::mutex g_host_name_mutex;
+static std::string g_host_name = "rack";
+static std::vector<std::string> g_host_capabilities;
+
+// Most MIDI events delivered in one process() call
+static const size_t MAX_MIDI_EVENTS = 1024;
+
+struct RackVST2Plugin {
+    void* library = nullptr;
+    AEffect* effect = nullptr;
+
+    double sample_rate = 44100.0;
+    uint32_t max_block_size = 512;
+    bool initialized = false;
+    bool in_process = false;
+    bool offline = false;
+    int64_t sample_position = 0;
+
+    VstTimeInfo time_info;
+
+    // MIDI queued for the next process() call
+    std::vector<VstMidiEvent> midi_events;
+    // VstEvents with room for MAX_MIDI_EVENTS pointers
+    std::vector<char> events_storage;
+
+    // Stand-in channel arrays for plugins without inputs or outputs
+    std::vector<float*> no_channels;
+};
+
+// ============================================================================
+// Helpers
+// ============================================================================
+
+// Helper: Copy a UTF-8 string into a fixed-size buffer
+// Truncates on a character boundary so the result stays valid UTF-8 when the
+// string doesn't fit. Always null-terminates (buffer_size must be > 0).
+static void copy_utf8_truncated(char* buffer, size_t buffer_size, const std::string& str) {
+    size_t length = std::min(str.size(), buffer_size - 1);
+    if (length < str.size()) {
+        // Back up over continuation bytes (10xxxxxx) to the start of the cut character
+        while (length > 0 && (static_cast<unsigned char>(str[length]) & 0xC0) == 0x80) {
+            length--;
+        }
+    }
+    memcpy(buffer, str.data(), length);
+    buffer[length] = '\0';
+}
+
+// Helper: Copy bytes into a length-negotiated buffer
+static int copy_bytes_negotiated(const void* data, size_t data_size, void* buffer, size_t* size) {
+    if (!size) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+    if (!buffer) {
+        *size = data_size;
+        return RACK_VST2_OK;
+    }
+    if (*size < data_size) {
+        *size = data_size;
+        return RACK_VST2_ERROR_BUFFER_TOO_SMALL;
+    }
+
+    if (data_size > 0) {
+        memcpy(buffer, data, data_size);
+    }
+    *size = data_size;
+    return RACK_VST2_OK;
+}
+
+// Helper: Copy a UTF-8 string into a length-negotiated buffer
+// With a NULL buffer only the required size (including the null terminator)
+// is reported. Never truncates: a buffer that is too small is left untouched.
+static int copy_string_negotiated(const std::string& str, char* buffer, size_t* size) {
+    return copy_bytes_negotiated(str.c_str(), str.size() + 1, buffer, size);
+}
+
+// Helper: Call an opcode that writes a string into ptr
+// VST2's documented string limits are small (8 characters for parameter
+// names) and widely ignored, so the plugin gets a generous zeroed buffer.
+static std::string get_string(AEffect* effect, VstInt32 opcode, VstInt32 index = 0, VstIntPtr value = 0) {
+    char buffer[512] = {};
+    effect->dispatcher(effect, opcode, index, value, buffer, 0.0f);
+    buffer[sizeof(buffer) - 1] = '\0';
+    return std::string(buffer);
+}
+
+// Helper: Little-endian encoding for the parameter state
+static void push_u32(std::vector<uint8_t>& out, uint32_t value) {
+    for (int i = 0; i < 4; i++) {
+        out.push_back(static_cast<uint8_t>(value >> (8 * i)));
+    }
+}
+
+static uint32_t read_u32(const uint8_t* data) {
+    return static_cast<uint32_t>(data[0])
+        | (static_cast<uint32_t>(data[1]) << 8)
+        | (static_cast<uint32_t>(data[2]) << 16)
+        | (static_cast<uint32_t>(data[3]) << 24);
+}
+
+// ============================================================================
+// Library loading
+// ============================================================================
+
+#if defined(_WIN32)
+
+static void* open_library(const char* path) {
+    // Paths arrive as UTF-8
+    int length = MultiByteToWideChar(CP_UTF8, MB_ERR_INVALID_CHARS, path, -1, nullptr, 0);
+    if (length <= 0) {
+        return nullptr;
+    }
+    std::wstring wide(static_cast<size_t>(length), L'\0');
+    MultiByteToWideChar(CP_UTF8, MB_ERR_INVALID_CHARS, path, -1, &wide[0], length);
+    return reinterpret_cast<void*>(LoadLibraryW(wide.c_str()));
+}
+
+static void* find_symbol(void* library, const char* name) {
+    return reinterpret_cast<void*>(GetProcAddress(static_cast<HMODULE>(library), name));
+}
+
+static void close_library(void* library) {
+    FreeLibrary(static_cast<HMODULE>(library));
+}
+
+#else
+
+static void* open_library(const char* path) {
+    std::string binary = path;
+
+#if defined(__APPLE__)
+    // A .vst bundle's executable is named after the bundle
+    struct stat info;
+    if (stat(path, &info) == 0 && S_ISDIR(info.st_mode)) {
+        std::string bundle = binary;
+        while (!bundle.empty() && bundle.back() == '/') {
+            bundle.pop_back();
+        }
+        size_t slash = bundle.find_last_of('/');
+        std::string name = slash == std::string::npos ? bundle : bundle.substr(slash + 1);
+        size_t dot = name.find_last_of('.');
+        if (dot != std::string::npos) {
+            name = name.substr(0, dot);
+        }
+        binary = bundle + "/Contents/MacOS/" + name;
+    }
+#endif
+
+    return dlopen(binary.c_str(), RTLD_NOW | RTLD_LOCAL);
+}
+
+static void* find_symbol(void* library, const char* name) {
+    return dlsym(library, name);
+}
+
+static void close_library(void* library) {
+    dlclose(library);
+}
+
+#endif
+
+static VstMainFunction find_main(void* library) {
+    const char* names[] = {
+        "VSTPluginMain",
+#if defined(__APPLE__)
+        "main_macho",
+#endif
+        "main",
+    };
+    for (const char* name : names) {
+        if (void* symbol = find_symbol(library, name)) {
+            return reinterpret_cast<VstMainFunction>(symbol);
+        }
+    }
+    return nullptr;
+}
+
+// ============================================================================
+// Host callback
+// ============================================================================
+
+static VstIntPtr VSTCALLBACK host_callback(
+    AEffect* effect,
+    VstInt32 opcode,
+    VstInt32 index,
+    VstIntPtr value,
+    void* ptr,
+    float opt
+) {
+    // resvd1 is reserved for the host; it holds the owning instance once the
+    // plugin has been created (scanned plugins have none)
+    RackVST2Plugin* plugin = effect ? reinterpret_cast<RackVST2Plugin*>(effect->resvd1) : nullptr;
+
+    switch (opcode) {
+        case audioMasterVersion:
+            return kVstVersion;
+
+        case audioMasterCurrentId:
+            // Only asked while the lifecycle mutex is held
+            return g_loading_id;
+
+        case audioMasterIdle:
+            return 0;
+
+        case audioMasterGetTime:
+            return plugin ? reinterpret_cast<VstIntPtr>(&plugin->time_info) : 0;
+
+        case audioMasterGetSampleRate:
+            return plugin ? static_cast<VstIntPtr>(plugin->sample_rate) : 0;
+
+        case audioMasterGetBlockSize:
+            return plugin ? static_cast<VstIntPtr>(plugin->max_block_size) : 0;
+
+        case audioMasterGetCurrentProcessLevel:
+            if (plugin && plugin->offline) {
+                return kVstProcessLevelOffline;
+            }
+            return plugin && plugin->in_process ? kVstProcessLevelRealtime : kVstProcessLevelUser;
+
+        case audioMasterGetVendorString:
+            if (ptr) {
+                copy_utf8_truncated(static_cast<char*>(ptr), kVstMaxVendorStrLen, "rack");
+            }
+            return 1;
+
+        case audioMasterGetProductString:
+            if (ptr) {
+                std::lock_guard<std::mutex> lock(g_host_name_mutex);
+                copy_utf8_truncated(static_cast<char*>(ptr), kVstMaxProductStrLen, g_host_name);
+            }
+            return 1;
+
+        case audioMasterGetVendorVersion:
+            return 1;
+
+        case audioMasterGetLanguage:
+            return kVstLangEnglish;
+
+        case audioMasterCanDo: {
+            if (!ptr) {
+                return 0;
+            }
+            const char* can_do = static_cast<const char*>(ptr);
+            const char* supported[] = {
+                "sendVstEvents",
+                "sendVstMidiEvent",
+                "sendVstTimeInfo",
+                "shellCategory",
+            };
+            for (const char* name : supported) {
+                if (strcmp(can_do, name) == 0) {
+                    return 1;
+                }
+            }
+            std::lock_guard<std::mutex> lock(g_host_name_mutex);
+            for (const std::string& name : g_host_capabilities) {
+                if (name == can_do) {
+                    return 1;
+                }
+            }
+            return 0;
+        }
+
+        // Editor notifications and requests this host doesn't act on
+        case audioMasterAutomate:
+        case audioMasterBeginEdit:
+        case audioMasterEndEdit:
+        case audioMasterUpdateDisplay:
+        case audioMasterIOChanged:
+        case audioMasterSizeWindow:
+        default:
+            (void)index;
+            (void)value;
+            (void)opt;
+            return 0;
+    }
+}
+
+// Create the plugin with the given ID from a loaded library
+// Call with the lifecycle mutex held. Returns the opened effect or NULL.
+static AEffect* open_effect(void* library, VstInt32 id) {
+    VstMainFunction main = find_main(library);
+    if (!main) {
+        return nullptr;
+    }
+
+    g_loading_id = id;
+    AEffect* effect = main(host_callback);
+    g_loading_id = 0;
+
+    if (!effect || effect->magic != kEffectMagic) {
+        return nullptr;
+    }
+    effect->dispatcher(effect, effOpen, 0, 0, nullptr, 0.0f);
+    return effect;
+}
+
+static void close_effect(AEffect* effect) {
+    effect->dispatcher(effect, effClose, 0, 0, nullptr, 0.0f);
+}
+
+static RackVST2PluginType plugin_type(AEffect* effect) {
+    VstIntPtr category = effect->dispatcher(effect, effGetPlugCategory, 0, 0, nullptr, 0.0f);
+    if ((effect->flags & effFlagsIsSynth) || category == kPlugCategSynth || category == kPlugCategGenerator) {
+        return RACK_VST2_TYPE_INSTRUMENT;
+    }
+    switch (category) {
+        case kPlugCategAnalysis:
+            return RACK_VST2_TYPE_ANALYZER;
+        case kPlugCategSpacializer:
+        case kPlugSurroundFx:
+            return RACK_VST2_TYPE_SPATIAL;
+        case kPlugCategUnknown:
+            return effect->numInputs > 0 ? RACK_VST2_TYPE_EFFECT : RACK_VST2_TYPE_OTHER;
+        default:
+            return RACK_VST2_TYPE_EFFECT;
+    }
+}
+
+static RackVST2PluginInfo describe_effect(AEffect* effect, const std::string& name) {
+    RackVST2PluginInfo info;
+    memset(&info, 0, sizeof(info));
+    copy_utf8_truncated(info.name, sizeof(info.name), name);
+    copy_utf8_truncated(info.manufacturer, sizeof(info.manufacturer), get_string(effect, effGetVendorString));
+    info.unique_id = effect->uniqueID;
+    info.version = effect->version;
+    info.plugin_type = plugin_type(effect);
+    info.num_inputs = effect->numInputs;
+    info.num_outputs = effect->numOutputs;
+    return info;
+}
+
+// ============================================================================
+// Scanning API
+// ============================================================================
+
+int rack_vst2_describe(const char* path, RackVST2PluginInfo* plugins, size_t max_plugins) {
+    if (!path) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+
+    std::lock_guard<std::mutex> lock(g_vst2_lifecycle_mutex);
+
+    void* library = open_library(path);
+    if (!library) {
+        return RACK_VST2_ERROR_LOAD_FAILED;
+    }
+
+    AEffect* effect = open_effect(library, 0);
+    if (!effect) {
+        close_library(library);
+        return RACK_VST2_ERROR_LOAD_FAILED;
+    }
+
+    std::vector<RackVST2PluginInfo> found;
+    VstIntPtr category = effect->dispatcher(effect, effGetPlugCategory, 0, 0, nullptr, 0.0f);
+    if (category == kPlugCategShell) {
+        // List the shell's plugins first: creating one may disturb the enumeration
+        std::vector<std::pair<VstInt32, std::string>> children;
+        for (;;) {
+            char name[kVstMaxProductStrLen + 1] = {};
+            VstInt32 id = static_cast<VstInt32>(
+                effect->dispatcher(effect, effShellGetNextPlugin, 0, 0, name, 0.0f));
+            if (id == 0) {
+                break;
+            }
+            name[kVstMaxProductStrLen] = '\0';
+            children.emplace_back(id, name);
+        }
+
+        for (const auto& child : children) {
+            AEffect* child_effect = open_effect(library, child.first);
+            if (!child_effect) {
+                continue;
+            }
+            RackVST2PluginInfo info = describe_effect(child_effect, child.second);
+            info.unique_id = child.first;
+            found.push_back(info);
+            close_effect(child_effect);
+        }
+    } else {
+        found.push_back(describe_effect(effect, get_string(effect, effGetEffectName)));
+    }
+
+    close_effect(effect);
+    close_library(library);
+
+    if (plugins) {
+        size_t count = std::min(found.size(), max_plugins);
+        std::copy(found.begin(), found.begin() + static_cast<std::ptrdiff_t>(count), plugins);
+    }
+    return static_cast<int>(found.size());
+}
+
+// ============================================================================
+// Plugin Instance API
+// ============================================================================
+
+int rack_vst2_set_host_name(const char* name) {
+    if (!name) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+
+    std::lock_guard<std::mutex> lock(g_host_name_mutex);
+    g_host_name = name;
+    return RACK_VST2_OK;
+}
+
+int rack_vst2_set_host_capabilities(const char* const* capabilities, size_t count) {
+    if (!capabilities && count > 0) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+
+    std::vector<std::string> names;
+    names.reserve(count);
+    for (size_t i = 0; i < count; i++) {
+        if (!capabilities[i]) {
+            return RACK_VST2_ERROR_INVALID_PARAM;
+        }
+        names.emplace_back(capabilities[i]);
+    }
+
+    std::lock_guard<std::mutex> lock(g_host_name_mutex);
+    g_host_capabilities = std::move(names);
+    return RACK_VST2_OK;
+}
+
+RackVST2Plugin* rack_vst2_plugin_new(const char* path, int32_t unique_id) {
+    if (!path) {
+        return nullptr;
+    }
+
+    std::lock_guard<std::mutex> lock(g_vst2_lifecycle_mutex);
+
+    auto plugin = std::unique_ptr<RackVST2Plugin>(new(std::nothrow) RackVST2Plugin());
+    if (!plugin) {
+        return nullptr;
+    }
+
+    plugin->library = open_library(path);
+    if (!plugin->library) {
+        return nullptr;
+    }
+
+    // Shells create the plugin asked for through audioMasterCurrentId; other
+    // plugins ignore the ID
+    plugin->effect = open_effect(plugin->library, unique_id);
+    if (!plugin->effect) {
+        close_library(plugin->library);
+        return nullptr;
+    }
+    plugin->effect->resvd1 = reinterpret_cast<VstIntPtr>(plugin.get());
+
+    memset(&plugin->time_info, 0, sizeof(plugin->time_info));
+    plugin->time_info.tempo = 120.0;
+    plugin->time_info.timeSigNumerator = 4;
+    plugin->time_info.timeSigDenominator = 4;
+
+    plugin->midi_events.reserve(MAX_MIDI_EVENTS);
+    plugin->events_storage.resize(sizeof(VstEvents) + MAX_MIDI_EVENTS * sizeof(VstEvent*));
+    plugin->no_channels.resize(1, nullptr);
+
+    return plugin.release();
+}
+
+static void suspend(RackVST2Plugin* plugin) {
+    AEffect* effect = plugin->effect;
+    effect->dispatcher(effect, effStopProcess, 0, 0, nullptr, 0.0f);
+    effect->dispatcher(effect, effMainsChanged, 0, 0, nullptr, 0.0f);
+}
+
+static void resume(RackVST2Plugin* plugin) {
+    AEffect* effect = plugin->effect;
+    effect->dispatcher(effect, effMainsChanged, 0, 1, nullptr, 0.0f);
+    effect->dispatcher(effect, effStartProcess, 0, 0, nullptr, 0.0f);
+}
+
+void rack_vst2_plugin_free(RackVST2Plugin* plugin) {
+    if (!plugin) {
+        return;
+    }
+
+    std::lock_guard<std::mutex> lock(g_vst2_lifecycle_mutex);
+
+    if (plugin->initialized) {
+        suspend(plugin);
+    }
+    close_effect(plugin->effect);
+    close_library(plugin->library);
+    delete plugin;
+}
+
+int rack_vst2_plugin_initialize(RackVST2Plugin* plugin, double sample_rate, uint32_t max_block_size) {
+    if (!plugin || sample_rate <= 0.0 || max_block_size == 0) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+
+    std::lock_guard<std::mutex> lock(g_vst2_lifecycle_mutex);
+
+    AEffect* effect = plugin->effect;
+    if (plugin->initialized) {
+        suspend(plugin);
+        plugin->initialized = false;
+    }
+
+    plugin->sample_rate = sample_rate;
+    plugin->max_block_size = max_block_size;
+    plugin->time_info.sampleRate = sample_rate;
+
+    effect->dispatcher(effect, effSetSampleRate, 0, 0, nullptr, static_cast<float>(sample_rate));
+    effect->dispatcher(effect, effSetBlockSize, 0, static_cast<VstIntPtr>(max_block_size), nullptr, 0.0f);
+    effect->dispatcher(effect, effSetProcessPrecision, 0, kVstProcessPrecision32, nullptr, 0.0f);
+    resume(plugin);
+
+    plugin->initialized = true;
+    return RACK_VST2_OK;
+}
+
+int rack_vst2_plugin_is_initialized(RackVST2Plugin* plugin) {
+    return plugin && plugin->initialized ? 1 : 0;
+}
+
+int rack_vst2_plugin_reset(RackVST2Plugin* plugin) {
+    if (!plugin) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+    if (!plugin->initialized) {
+        return RACK_VST2_ERROR_NOT_INITIALIZED;
+    }
+
+    suspend(plugin);
+    resume(plugin);
+    plugin->midi_events.clear();
+    return RACK_VST2_OK;
+}
+
+int rack_vst2_plugin_get_input_channels(RackVST2Plugin* plugin) {
+    return plugin && plugin->initialized ? std::max(plugin->effect->numInputs, 0) : 0;
+}
+
+int rack_vst2_plugin_get_output_channels(RackVST2Plugin* plugin) {
+    return plugin && plugin->initialized ? std::max(plugin->effect->numOutputs, 0) : 0;
+}
+
+int rack_vst2_plugin_get_latency(RackVST2Plugin* plugin) {
+    return plugin && plugin->initialized ? std::max(plugin->effect->initialDelay, 0) : 0;
+}
+
+int rack_vst2_plugin_get_tail_size(RackVST2Plugin* plugin) {
+    if (!plugin || !plugin->initialized) {
+        return 0;
+    }
+    // 0 means the plugin doesn't say, 1 means no tail
+    VstIntPtr tail = plugin->effect->dispatcher(plugin->effect, effGetTailSize, 0, 0, nullptr, 0.0f);
+    return tail > 1 ? static_cast<int>(std::min<VstIntPtr>(tail, INT32_MAX)) : 0;
+}
+
+int rack_vst2_plugin_set_bypass(RackVST2Plugin* plugin, int bypassed) {
+    if (!plugin) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+
+    AEffect* effect = plugin->effect;
+    char can_do[] = "bypass";
+    if (effect->dispatcher(effect, effCanDo, 0, 0, can_do, 0.0f) <= 0) {
+        return 0;
+    }
+    effect->dispatcher(effect, effSetBypass, 0, bypassed ? 1 : 0, nullptr, 0.0f);
+    return 1;
+}
+
+int rack_vst2_plugin_process(
+    RackVST2Plugin* plugin,
+    const float* const* inputs,
+    uint32_t num_input_channels,
+    float* const* outputs,
+    uint32_t num_output_channels,
+    uint32_t frames
+) {
+    if (!plugin) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+    if (!plugin->initialized) {
+        return RACK_VST2_ERROR_NOT_INITIALIZED;
+    }
+
+    AEffect* effect = plugin->effect;
+    if (num_input_channels != static_cast<uint32_t>(std::max(effect->numInputs, 0))
+        || num_output_channels != static_cast<uint32_t>(std::max(effect->numOutputs, 0))
+        || frames > plugin->max_block_size) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+    if (!(effect->flags & effFlagsCanReplacing) || !effect->processReplacing) {
+        return RACK_VST2_ERROR_NOT_SUPPORTED;
+    }
+
+    plugin->in_process = true;
+
+    if (!plugin->midi_events.empty()) {
+        auto events = reinterpret_cast<VstEvents*>(plugin->events_storage.data());
+        events->numEvents = static_cast<VstInt32>(plugin->midi_events.size());
+        events->reserved = 0;
+        for (size_t i = 0; i < plugin->midi_events.size(); i++) {
+            VstMidiEvent& event = plugin->midi_events[i];
+            // Events past a short block play on its last frame
+            if (frames > 0 && static_cast<uint32_t>(event.deltaFrames) >= frames) {
+                event.deltaFrames = static_cast<VstInt32>(frames - 1);
+            }
+            events->events[i] = reinterpret_cast<VstEvent*>(&event);
+        }
+        effect->dispatcher(effect, effProcessEvents, 0, 0, events, 0.0f);
+    }
+
+    if (frames > 0) {
+        float** input_ptrs = num_input_channels > 0
+            ? const_cast<float**>(inputs)
+            : plugin->no_channels.data();
+        float** output_ptrs = num_output_channels > 0
+            ? const_cast<float**>(outputs)
+            : plugin->no_channels.data();
+        effect->processReplacing(effect, input_ptrs, output_ptrs, static_cast<VstInt32>(frames));
+    }
+
+    plugin->in_process = false;
+    plugin->midi_events.clear();
+
+    plugin->sample_position += frames;
+    plugin->time_info.samplePos = static_cast<double>(plugin->sample_position);
+    plugin->time_info.flags &= ~kVstTransportChanged;
+    return RACK_VST2_OK;
+}
+
+int rack_vst2_plugin_set_sample_position(RackVST2Plugin* plugin, int64_t position) {
+    if (!plugin || position < 0) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+
+    plugin->sample_position = position;
+    plugin->time_info.samplePos = static_cast<double>(position);
+    plugin->time_info.flags |= kVstTransportChanged;
+    return RACK_VST2_OK;
+}
+
+int rack_vst2_plugin_set_transport(
+    RackVST2Plugin* plugin,
+    int playing,
+    double tempo,
+    int32_t numerator,
+    int32_t denominator,
+    double ppq_position,
+    double bar_start_ppq,
+    int cycling,
+    double cycle_start_ppq,
+    double cycle_end_ppq,
+    int offline
+) {
+    if (!plugin || tempo <= 0.0 || numerator <= 0 || denominator <= 0) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+
+    VstTimeInfo& info = plugin->time_info;
+    bool was_playing = (info.flags & kVstTransportPlaying) != 0;
+    VstInt32 flags = info.flags & kVstTransportChanged;
+    flags |= kVstPpqPosValid | kVstTempoValid | kVstTimeSigValid | kVstBarsValid;
+    if (playing) {
+        flags |= kVstTransportPlaying;
+    }
+    if (cycling) {
+        flags |= kVstTransportCycleActive | kVstCyclePosValid;
+        info.cycleStartPos = cycle_start_ppq;
+        info.cycleEndPos = cycle_end_ppq;
+    }
+    if ((playing != 0) != was_playing) {
+        flags |= kVstTransportChanged;
+    }
+
+    info.samplePos = static_cast<double>(plugin->sample_position);
+    info.sampleRate = plugin->sample_rate;
+    info.ppqPos = ppq_position;
+    info.barStartPos = bar_start_ppq;
+    info.tempo = tempo;
+    info.timeSigNumerator = numerator;
+    info.timeSigDenominator = denominator;
+    info.flags = flags;
+    plugin->offline = offline != 0;
+    return RACK_VST2_OK;
+}
+
+int rack_vst2_plugin_parameter_count(RackVST2Plugin* plugin) {
+    return plugin ? std::max(plugin->effect->numParams, 0) : 0;
+}
+
+int rack_vst2_plugin_get_parameter(RackVST2Plugin* plugin, uint32_t index, float* value) {
+    if (!plugin || !value || index >= static_cast<uint32_t>(rack_vst2_plugin_parameter_count(plugin))) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+
+    *value = plugin->effect->getParameter(plugin->effect, static_cast<VstInt32>(index));
+    return RACK_VST2_OK;
+}
+
+int rack_vst2_plugin_set_parameter(RackVST2Plugin* plugin, uint32_t index, float value) {
+    if (!plugin || index >= static_cast<uint32_t>(rack_vst2_plugin_parameter_count(plugin))) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+
+    plugin->effect->setParameter(plugin->effect, static_cast<VstInt32>(index), std::clamp(value, 0.0f, 1.0f));
+    return RACK_VST2_OK;
+}
+
+int rack_vst2_plugin_parameter_name(RackVST2Plugin* plugin, uint32_t index, char* buffer, size_t* size) {
+    if (!plugin || index >= static_cast<uint32_t>(rack_vst2_plugin_parameter_count(plugin))) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+
+    return copy_string_negotiated(
+        get_string(plugin->effect, effGetParamName, static_cast<VstInt32>(index)), buffer, size);
+}
+
+int rack_vst2_plugin_parameter_unit(RackVST2Plugin* plugin, uint32_t index, char* buffer, size_t* size) {
+    if (!plugin || index >= static_cast<uint32_t>(rack_vst2_plugin_parameter_count(plugin))) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+
+    return copy_string_negotiated(
+        get_string(plugin->effect, effGetParamLabel, static_cast<VstInt32>(index)), buffer, size);
+}
+
+// ============================================================================
+// Program (Preset) API
+// ============================================================================
+
+int rack_vst2_plugin_get_preset_count(RackVST2Plugin* plugin) {
+    return plugin ? std::max(plugin->effect->numPrograms, 0) : 0;
+}
+
+int rack_vst2_plugin_preset_name(RackVST2Plugin* plugin, uint32_t index, char* buffer, size_t* size) {
+    if (!plugin || index >= static_cast<uint32_t>(rack_vst2_plugin_get_preset_count(plugin))) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+
+    // Category -1: any program, not only those of a category
+    return copy_string_negotiated(
+        get_string(plugin->effect, effGetProgramNameIndexed, static_cast<VstInt32>(index), -1), buffer, size);
+}
+
+int rack_vst2_plugin_load_preset(RackVST2Plugin* plugin, int32_t preset_number) {
+    if (!plugin || preset_number < 0 || preset_number >= rack_vst2_plugin_get_preset_count(plugin)) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+
+    AEffect* effect = plugin->effect;
+    effect->dispatcher(effect, effBeginSetProgram, 0, 0, nullptr, 0.0f);
+    effect->dispatcher(effect, effSetProgram, 0, preset_number, nullptr, 0.0f);
+    effect->dispatcher(effect, effEndSetProgram, 0, 0, nullptr, 0.0f);
+    return RACK_VST2_OK;
+}
+
+int rack_vst2_plugin_get_current_preset(RackVST2Plugin* plugin) {
+    if (!plugin) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+
+    return static_cast<int>(plugin->effect->dispatcher(plugin->effect, effGetProgram, 0, 0, nullptr, 0.0f));
+}
+
+// ============================================================================
+// State API
+// ============================================================================
+
+int rack_vst2_plugin_get_state(RackVST2Plugin* plugin, uint8_t* data, size_t* size) {
+    if (!plugin || !size) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+
+    AEffect* effect = plugin->effect;
+    if (effect->flags & effFlagsProgramChunks) {
+        // Index 0: the whole bank rather than the current program
+        void* chunk = nullptr;
+        VstIntPtr chunk_size = effect->dispatcher(effect, effGetChunk, 0, 0, &chunk, 0.0f);
+        if (chunk_size < 0 || (chunk_size > 0 && !chunk)) {
+            return RACK_VST2_ERROR_GENERIC;
+        }
+        return copy_bytes_negotiated(chunk, static_cast<size_t>(chunk_size), data, size);
+    }
+
+    std::vector<uint8_t> state;
+    int32_t count = rack_vst2_plugin_parameter_count(plugin);
+    state.reserve(4 + 4 * static_cast<size_t>(count));
+    push_u32(state, static_cast<uint32_t>(rack_vst2_plugin_get_current_preset(plugin)));
+    for (int32_t i = 0; i < count; i++) {
+        float value = effect->getParameter(effect, i);
+        uint32_t bits;
+        memcpy(&bits, &value, sizeof(bits));
+        push_u32(state, bits);
+    }
+    return copy_bytes_negotiated(state.data(), state.size(), data, size);
+}
+
+int rack_vst2_plugin_set_state(RackVST2Plugin* plugin, const uint8_t* data, size_t size) {
+    if (!plugin || !data || size == 0) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+
+    AEffect* effect = plugin->effect;
+    if (effect->flags & effFlagsProgramChunks) {
+        // Plugins take a mutable pointer; give them a copy
+        std::vector<uint8_t> chunk(data, data + size);
+        effect->dispatcher(effect, effSetChunk, 0, static_cast<VstIntPtr>(size), chunk.data(), 0.0f);
+        return RACK_VST2_OK;
+    }
+
+    int32_t count = rack_vst2_plugin_parameter_count(plugin);
+    if (size != 4 + 4 * static_cast<size_t>(count)) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+
+    int32_t program = static_cast<int32_t>(read_u32(data));
+    if (program >= 0 && program < rack_vst2_plugin_get_preset_count(plugin)) {
+        rack_vst2_plugin_load_preset(plugin, program);
+    }
+    for (int32_t i = 0; i < count; i++) {
+        uint32_t bits = read_u32(data + 4 + 4 * static_cast<size_t>(i));
+        float value;
+        memcpy(&value, &bits, sizeof(value));
+        effect->setParameter(effect, i, value);
+    }
+    return RACK_VST2_OK;
+}
+
+// ============================================================================
+// MIDI API
+// ============================================================================
+
+int rack_vst2_plugin_send_midi(
+    RackVST2Plugin* plugin,
+    const RackVST2MidiEvent* events,
+    uint32_t event_count
+) {
+    if (!plugin || (!events && event_count > 0)) {
+        return RACK_VST2_ERROR_INVALID_PARAM;
+    }
+    if (!plugin->initialized) {
+        return RACK_VST2_ERROR_NOT_INITIALIZED;
+    }
+
+    for (uint32_t i = 0; i < event_count && plugin->midi_events.size() < MAX_MIDI_EVENTS; i++) {
+        VstMidiEvent event;
+        memset(&event, 0, sizeof(event));
+        event.type = kVstMidiType;
+        event.byteSize = sizeof(VstMidiEvent);
+        event.deltaFrames = static_cast<VstInt32>(std::min<uint32_t>(events[i].sample_offset, INT32_MAX));
+        event.flags = kVstMidiEventIsRealtime;
+        event.midiData[0] = static_cast<char>(events[i].data[0]);
+        event.midiData[1] = static_cast<char>(events[i].data[1]);
+        event.midiData[2] = static_cast<char>(events[i].data[2]);
+        plugin->midi_events.push_back(event);
+    }
+    return RACK_VST2_OK;
+}
@@ -5,6 +5,7 @@
 #include "public.sdk/source/vst/hosting/processdata.h"
 #include "public.sdk/source/vst/hosting/parameterchanges.h"
 #include "public.sdk/source/vst/hosting/eventlist.h"
+#include "public.sdk/source/vst/utility/stringconvert.h"
 #include "pluginterfaces/vst/ivstaudioprocessor.h"
 #include "pluginterfaces/vst/ivstcomponent.h"
 #include "pluginterfaces/vst/ivsteditcontroller.h"
@@ -18,6 +19,13 @@
 #include <cstring>
 #include <mutex>
 #include <algorithm>
+#include <atomic>
+#include <limits>
+#include <memory>
+
+#if defined(_WIN32)
+    #include <windows.h>
+#endif
 
 using namespace VST3;
 using namespace Steinberg;
@@ -27,6 +35,60 @@ using namespace Steinberg::Vst;
 // VST3 module loading/unloading is not guaranteed to be thread-safe
 static std::mutex g_vst3_lifecycle_mutex;
 
+// Host name reported through IHostApplication::getName()
+// Separate from the lifecycle mutex: plugins call getName() from initialize(),
+// which runs while the lifecycle mutex is held.
+static std::mutex g_host_name_mutex;
+static std::string g_host_name = "rack";
+
+// Host application that reports the configured host name
+class RackHostApplication : public HostApplication {
+public:
+    tresult PLUGIN_API getName(String128 name) override {
+        std::lock_guard<std::mutex> lock(g_host_name_mutex);
+        return VST3::StringConvert::convert(g_host_name, name) ? kResultTrue : kInternalError;
+    }
+};
+
+// Helper: Copy a UTF-8 string into a fixed-size buffer
+// Truncates on a character boundary so the result stays valid UTF-8 when the
+// string doesn't fit. Always null-terminates (buffer_size must be > 0).
+static void copy_utf8_truncated(char* buffer, size_t buffer_size, const std::string& str) {
+    size_t length = std::min(str.size(), buffer_size - 1);
+    if (length < str.size()) {
+        // Back up over continuation bytes (10xxxxxx) to the start of the cut character
+        while (length > 0 && (static_cast<unsigned char>(str[length]) & 0xC0) == 0x80) {
+            length--;
+        }
+    }
+    memcpy(buffer, str.data(), length);
+    buffer[length] = '\0';
+}
+
+// Helper: Copy a UTF-8 string into a length-negotiated buffer
+// With a NULL buffer only the required size (including the null terminator)
+// is reported. Never truncates: a buffer that is too small is left untouched.
+static int copy_string_negotiated(const std::string& str, char* buffer, size_t* size) {
+    if (!size) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    size_t required = str.size() + 1;
+    if (!buffer) {
+        *size = required;
+        return RACK_VST3_OK;
+    }
+    if (*size < required) {
+        *size = required;
+        return RACK_VST3_ERROR_BUFFER_TOO_SMALL;
+    }
+
+    memcpy(buffer, str.data(), str.size());
+    buffer[str.size()] = '\0';
+    *size = required;
+    return RACK_VST3_OK;
+}
+
 // Helper: Convert UTF-16 to UTF-8
 // VST3 uses char16 (UTF-16) for strings
 // Handles surrogate pairs and malformed input safely
@@ -126,12 +188,19 @@ static bool string_to_uid(const char* str, VST3::UID& uid) {
 }
 
 // Memory stream implementation for state serialization
+//
+// Data is kept in fixed-size chunks rather than one contiguous buffer, so a
+// large state grows without reallocating (and briefly doubling) everything
+// written so far. A stream created with a read callback pulls its contents
+// from the caller as the plugin reads, so restoring a state doesn't need the
+// caller to hold all of it in memory first.
 class MemoryStream : public IBStream {
 public:
-    MemoryStream() : ref_count_(1), position_(0) {}
-    MemoryStream(const uint8_t* data, size_t size) : ref_count_(1), position_(0) {
-        buffer_.assign(data, data + size);
-    }
+    static constexpr size_t kChunkSize = 64 * 1024;
+
+    MemoryStream() : ref_count_(1) {}
+    MemoryStream(RackVST3StateReadCallback source, void* source_user_data)
+        : ref_count_(1), source_(source), source_user_data_(source_user_data) {}
 
     virtual ~MemoryStream() = default;
 
@@ -144,11 +213,12 @@ public:
             return kInvalidArgument;
         }
 
-        int32 available = static_cast<int32>(buffer_.size()) - position_;
-        int32 to_read = std::min(numBytes, available);
+        fill(position_ + numBytes);
+        int64 available = std::max<int64>(size_ - position_, 0);
+        int32 to_read = static_cast<int32>(std::min<int64>(numBytes, available));
 
         if (to_read > 0) {
-            memcpy(buffer, buffer_.data() + position_, to_read);
+            copyOut(position_, static_cast<uint8_t*>(buffer), static_cast<size_t>(to_read));
             position_ += to_read;
         }
 
@@ -164,13 +234,17 @@ public:
             return kInvalidArgument;
         }
 
-        // Resize buffer if needed
-        if (position_ + numBytes > static_cast<int32>(buffer_.size())) {
-            buffer_.resize(position_ + numBytes);
+        // Streams fed by a callback are read-only
+        if (source_) {
+            if (numBytesWritten) {
+                *numBytesWritten = 0;
+            }
+            return kResultFalse;
         }
 
-        memcpy(buffer_.data() + position_, buffer, numBytes);
+        copyIn(position_, static_cast<const uint8_t*>(buffer), static_cast<size_t>(numBytes));
         position_ += numBytes;
+        size_ = std::max(size_, position_);
 
         if (numBytesWritten) {
             *numBytesWritten = numBytes;
@@ -180,7 +254,6 @@ public:
     }
 
     tresult PLUGIN_API seek(int64 pos, int32 mode, int64* result) override {
-        // Calculate new position as int64 to prevent overflow/truncation
         int64 new_position = position_;
 
         switch (mode) {
@@ -188,26 +261,21 @@ public:
                 new_position = pos;
                 break;
             case kIBSeekCur:
-                new_position = static_cast<int64>(position_) + pos;
+                new_position = position_ + pos;
                 break;
             case kIBSeekEnd:
-                new_position = static_cast<int64>(buffer_.size()) + pos;
+                // The end isn't known until the source is drained
+                fill(std::numeric_limits<int64>::max());
+                new_position = size_ + pos;
                 break;
             default:
                 return kInvalidArgument;
         }
 
-        // Clamp to valid range [0, buffer_size]
-        if (new_position < 0) {
-            new_position = 0;
-        }
-        int64 buffer_size = static_cast<int64>(buffer_.size());
-        if (new_position > buffer_size) {
-            new_position = buffer_size;
-        }
-
-        // Safe to cast after clamping
-        position_ = static_cast<int32>(new_position);
+        // Clamp to valid range [0, size]
+        fill(new_position);
+        new_position = std::min(std::max<int64>(new_position, 0), size_);
+        position_ = new_position;
 
         if (result) {
             *result = position_;
@@ -225,14 +293,88 @@ public:
     }
 
     // Accessors
-    const std::vector<uint8_t>& getData() const { return buffer_; }
-    size_t getSize() const { return buffer_.size(); }
-    void clear() { buffer_.clear(); position_ = 0; }
+    size_t getSize() const { return static_cast<size_t>(size_); }
+    bool sourceFailed() const { return source_failed_; }
+
+    // Copy the whole contents to data (at least getSize() bytes)
+    void copyTo(uint8_t* data) const { copyOut(0, data, getSize()); }
+
+    // Hand the contents to a write callback one chunk at a time
+    // Returns false if the callback asked to stop
+    bool writeTo(RackVST3StateWriteCallback callback, void* user_data) const {
+        int64 offset = 0;
+        for (const auto& chunk : chunks_) {
+            if (offset >= size_) {
+                break;
+            }
+            size_t length = static_cast<size_t>(std::min<int64>(kChunkSize, size_ - offset));
+            if (callback(user_data, chunk.get(), length) != 0) {
+                return false;
+            }
+            offset += static_cast<int64>(length);
+        }
+        return true;
+    }
 
 private:
+    // Make sure chunks cover [0, end)
+    void reserve(int64 end) {
+        while (static_cast<int64>(chunks_.size() * kChunkSize) < end) {
+            chunks_.emplace_back(new uint8_t[kChunkSize]());
+        }
+    }
+
+    void copyIn(int64 offset, const uint8_t* data, size_t length) {
+        reserve(offset + static_cast<int64>(length));
+        while (length > 0) {
+            size_t chunk_offset = static_cast<size_t>(offset % kChunkSize);
+            size_t n = std::min(length, kChunkSize - chunk_offset);
+            memcpy(chunks_[static_cast<size_t>(offset / kChunkSize)].get() + chunk_offset, data, n);
+            offset += static_cast<int64>(n);
+            data += n;
+            length -= n;
+        }
+    }
+
+    void copyOut(int64 offset, uint8_t* data, size_t length) const {
+        while (length > 0) {
+            size_t chunk_offset = static_cast<size_t>(offset % kChunkSize);
+            size_t n = std::min(length, kChunkSize - chunk_offset);
+            memcpy(data, chunks_[static_cast<size_t>(offset / kChunkSize)].get() + chunk_offset, n);
+            offset += static_cast<int64>(n);
+            data += n;
+            length -= n;
+        }
+    }
+
+    // Pull from the source until at least `end` bytes are buffered or it runs dry
+    // Everything read is kept so plugins can seek back over it
+    void fill(int64 end) {
+        while (source_ && !source_done_ && size_ < end) {
+            reserve(size_ + 1);
+            size_t chunk_offset = static_cast<size_t>(size_ % kChunkSize);
+            size_t room = kChunkSize - chunk_offset;
+            size_t bytes_read = 0;
+            uint8_t* target = chunks_[static_cast<size_t>(size_ / kChunkSize)].get() + chunk_offset;
+            if (source_(source_user_data_, target, room, &bytes_read) != 0) {
+                source_failed_ = true;
+                source_done_ = true;
+            } else if (bytes_read == 0) {
+                source_done_ = true;
+            } else {
+                size_ += static_cast<int64>(std::min(bytes_read, room));
+            }
+        }
+    }
+
     uint32 ref_count_;  // Non-atomic - IMPLEMENT_REFCOUNT macro handles thread-safety
-    std::vector<uint8_t> buffer_;
-    int32 position_;
+    std::vector<std::unique_ptr<uint8_t[]>> chunks_;
+    int64 size_ = 0;
+    int64 position_ = 0;
+    RackVST3StateReadCallback source_ = nullptr;
+    void* source_user_data_ = nullptr;
+    bool source_done_ = false;
+    bool source_failed_ = false;
 };
 
 IMPLEMENT_REFCOUNT(MemoryStream)
@@ -244,6 +386,146 @@ tresult PLUGIN_API MemoryStream::queryInterface(const TUID _iid, void** obj) {
     return kNoInterface;
 }
 
+// Read-only stream over memory owned by the caller
+// Used by set_state so the state isn't copied before the plugin reads it.
+class ViewStream : public IBStream {
+public:
+    ViewStream(const uint8_t* data, size_t size)
+        : ref_count_(1), data_(data), size_(static_cast<int64>(size)) {}
+
+    virtual ~ViewStream() = default;
+
+    // IUnknown
+    DECLARE_FUNKNOWN_METHODS
+
+    // IBStream
+    tresult PLUGIN_API read(void* buffer, int32 numBytes, int32* numBytesRead) override {
+        if (!buffer || numBytes < 0) {
+            return kInvalidArgument;
+        }
+
+        int32 to_read = static_cast<int32>(std::min<int64>(numBytes, size_ - position_));
+        if (to_read > 0) {
+            memcpy(buffer, data_ + position_, static_cast<size_t>(to_read));
+            position_ += to_read;
+        }
+
+        if (numBytesRead) {
+            *numBytesRead = to_read;
+        }
+
+        return to_read == numBytes ? kResultOk : kResultFalse;
+    }
+
+    tresult PLUGIN_API write(void*, int32, int32* numBytesWritten) override {
+        if (numBytesWritten) {
+            *numBytesWritten = 0;
+        }
+        return kResultFalse;
+    }
+
+    tresult PLUGIN_API seek(int64 pos, int32 mode, int64* result) override {
+        int64 new_position = position_;
+
+        switch (mode) {
+            case kIBSeekSet:
+                new_position = pos;
+                break;
+            case kIBSeekCur:
+                new_position = position_ + pos;
+                break;
+            case kIBSeekEnd:
+                new_position = size_ + pos;
+                break;
+            default:
+                return kInvalidArgument;
+        }
+
+        position_ = std::min(std::max<int64>(new_position, 0), size_);
+
+        if (result) {
+            *result = position_;
+        }
+
+        return kResultOk;
+    }
+
+    tresult PLUGIN_API tell(int64* pos) override {
+        if (!pos) {
+            return kInvalidArgument;
+        }
+        *pos = position_;
+        return kResultOk;
+    }
+
+private:
+    uint32 ref_count_;  // Non-atomic - IMPLEMENT_REFCOUNT macro handles thread-safety
+    const uint8_t* data_;
+    int64 size_;
+    int64 position_ = 0;
+};
+
+IMPLEMENT_REFCOUNT(ViewStream)
+
+tresult PLUGIN_API ViewStream::queryInterface(const TUID _iid, void** obj) {
+    QUERY_INTERFACE(_iid, obj, FUnknown::iid, IBStream)
+    QUERY_INTERFACE(_iid, obj, IBStream::iid, IBStream)
+    *obj = nullptr;
+    return kNoInterface;
+}
+
+// Component handler installed on the edit controller
+// The controller reports edits made in the plugin's own editor (begin/perform/
+// endEdit), runtime changes (restartComponent) and unsaved state (setDirty)
+// through this. Edits are queued for the processor; everything is forwarded to
+// the host's callback.
+class RackComponentHandler : public IComponentHandler, public IComponentHandler2 {
+public:
+    explicit RackComponentHandler(RackVST3Plugin* plugin) : ref_count_(1), plugin_(plugin) {}
+
+    virtual ~RackComponentHandler() = default;
+
+    // IUnknown
+    DECLARE_FUNKNOWN_METHODS
+
+    // IComponentHandler
+    tresult PLUGIN_API beginEdit(ParamID id) override;
+    tresult PLUGIN_API performEdit(ParamID id, ParamValue valueNormalized) override;
+    tresult PLUGIN_API endEdit(ParamID id) override;
+    tresult PLUGIN_API restartComponent(int32 flags) override;
+
+    // IComponentHandler2
+    tresult PLUGIN_API setDirty(TBool state) override;
+    tresult PLUGIN_API requestOpenEditor(FIDString /*name*/) override { return kNotImplemented; }
+    tresult PLUGIN_API startGroupEdit() override { return kNotImplemented; }
+    tresult PLUGIN_API finishGroupEdit() override { return kNotImplemented; }
+
+    void setCallback(RackVST3ComponentCallback callback, void* user_data) {
+        std::lock_guard<std::mutex> lock(mutex_);
+        callback_ = callback;
+        user_data_ = user_data;
+    }
+
+    // Stop forwarding anything (called before the plugin is freed)
+    void detach() {
+        std::lock_guard<std::mutex> lock(mutex_);
+        plugin_ = nullptr;
+        callback_ = nullptr;
+        user_data_ = nullptr;
+    }
+
+private:
+    // Forward an edit notification (caller holds mutex_)
+    void notifyEdit(int32_t kind, ParamID id, ParamValue value);
+
+    uint32 ref_count_;  // Non-atomic - IMPLEMENT_REFCOUNT macro handles thread-safety
+    // Held while the callback runs, so setCallback() waits for it to finish
+    std::mutex mutex_;
+    RackVST3Plugin* plugin_;
+    RackVST3ComponentCallback callback_ = nullptr;
+    void* user_data_ = nullptr;
+};
+
 // Internal plugin state
 struct RackVST3Plugin {
     // Module and factory
@@ -278,6 +560,11 @@ struct RackVST3Plugin {
     EventList input_events;
     EventList output_events;
 
+    // Timeline: position of the next block's first sample, reported to the
+    // plugin through ProcessContext::projectTimeSamples
+    ProcessContext process_context = {};
+    int64_t sample_position = 0;
+
     // Audio buffers (for pointer arrays)
     std::vector<float*> input_ptrs;
     std::vector<float*> output_ptrs;
@@ -290,6 +577,7 @@ struct RackVST3Plugin {
         ParamValue min_value;
         ParamValue max_value;
         ParamValue default_value;
+        int32 flags;
     };
     std::vector<ParameterInfo> parameters;
 
@@ -300,12 +588,146 @@ struct RackVST3Plugin {
         std::string name;
     };
     std::vector<PresetInfo> presets;
+
+    // Index of the preset last loaded with load_preset(), or -1
+    int32_t current_preset = -1;
+
+    // Component handler installed on the controller (null without a controller)
+    IPtr<RackComponentHandler> component_handler;
+
+    // Edits made in the plugin's editor, forwarded to the processor by the
+    // next process() call
+    std::mutex pending_edits_mutex;
+    std::vector<std::pair<ParamID, ParamValue>> pending_edits;
+
+    // RestartFlags requested by the plugin and not yet applied
+    std::atomic<int32> pending_restart{0};
 };
 
+IMPLEMENT_REFCOUNT(RackComponentHandler)
+
+tresult PLUGIN_API RackComponentHandler::queryInterface(const TUID _iid, void** obj) {
+    QUERY_INTERFACE(_iid, obj, FUnknown::iid, IComponentHandler)
+    QUERY_INTERFACE(_iid, obj, IComponentHandler::iid, IComponentHandler)
+    QUERY_INTERFACE(_iid, obj, IComponentHandler2::iid, IComponentHandler2)
+    *obj = nullptr;
+    return kNoInterface;
+}
+
+// Helper: Find a parameter's index in the parameter cache, or -1
+static int32_t parameter_index(RackVST3Plugin* plugin, ParamID id) {
+    for (size_t i = 0; i < plugin->parameters.size(); ++i) {
+        if (plugin->parameters[i].id == id) {
+            return static_cast<int32_t>(i);
+        }
+    }
+    return -1;
+}
+
+void RackComponentHandler::notifyEdit(int32_t kind, ParamID id, ParamValue value) {
+    if (!callback_) {
+        return;
+    }
+
+    RackVST3ComponentEvent event = {};
+    event.kind = kind;
+    event.param_index = parameter_index(plugin_, id);
+    event.param_id = id;
+    event.value = value;
+    callback_(user_data_, &event);
+}
+
+tresult PLUGIN_API RackComponentHandler::beginEdit(ParamID id) {
+    std::lock_guard<std::mutex> lock(mutex_);
+    if (!plugin_) {
+        return kResultFalse;
+    }
+
+    notifyEdit(RACK_VST3_COMPONENT_BEGIN_EDIT, id, 0.0);
+    return kResultOk;
+}
+
+tresult PLUGIN_API RackComponentHandler::performEdit(ParamID id, ParamValue valueNormalized) {
+    std::lock_guard<std::mutex> lock(mutex_);
+    if (!plugin_) {
+        return kResultFalse;
+    }
+
+    // The controller already has the new value; the processor gets it with
+    // the next block
+    {
+        std::lock_guard<std::mutex> edits_lock(plugin_->pending_edits_mutex);
+        plugin_->pending_edits.emplace_back(id, valueNormalized);
+    }
+
+    notifyEdit(RACK_VST3_COMPONENT_PERFORM_EDIT, id, valueNormalized);
+    return kResultOk;
+}
+
+tresult PLUGIN_API RackComponentHandler::endEdit(ParamID id) {
+    std::lock_guard<std::mutex> lock(mutex_);
+    if (!plugin_) {
+        return kResultFalse;
+    }
+
+    notifyEdit(RACK_VST3_COMPONENT_END_EDIT, id, 0.0);
+    return kResultOk;
+}
+
+tresult PLUGIN_API RackComponentHandler::restartComponent(int32 flags) {
+    std::lock_guard<std::mutex> lock(mutex_);
+    if (!plugin_) {
+        return kResultFalse;
+    }
+
+    // Applied by rack_vst3_plugin_apply_restart() on the host's thread; the
+    // plugin may be mid-process() right now
+    plugin_->pending_restart.fetch_or(flags);
+
+    if (callback_) {
+        RackVST3ComponentEvent event = {};
+        event.kind = RACK_VST3_COMPONENT_RESTART;
+        event.param_index = -1;
+        event.restart_flags = flags;
+        if (plugin_->processor) {
+            event.latency = plugin_->processor->getLatencySamples();
+        }
+        callback_(user_data_, &event);
+    }
+    return kResultOk;
+}
+
+tresult PLUGIN_API RackComponentHandler::setDirty(TBool state) {
+    std::lock_guard<std::mutex> lock(mutex_);
+    if (!plugin_) {
+        return kResultFalse;
+    }
+
+    // Only the transition to dirty is interesting; the host decides when the
+    // state counts as saved
+    if (state && callback_) {
+        RackVST3ComponentEvent event = {};
+        event.kind = RACK_VST3_COMPONENT_DIRTY;
+        event.param_index = -1;
+        callback_(user_data_, &event);
+    }
+    return kResultOk;
+}
+
 // ============================================================================
 // Plugin Instance Implementation
 // ============================================================================
 
+int rack_vst3_set_host_name(const char* name) {
+    if (!name) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    std::lock_guard<std::mutex> lock(g_host_name_mutex);
+    g_host_name = name;
+    return RACK_VST3_OK;
+}
+
 RackVST3Plugin* rack_vst3_plugin_new(const char* path, const char* uid) {
     if (!path || !uid) {
         return nullptr;
@@ -350,7 +772,7 @@ RackVST3Plugin* rack_vst3_plugin_new(const char* path, const char* uid) {
     }
 
     // Initialize component
-    if (plugin->component->initialize(FUnknownPtr<IHostApplication>(new HostApplication())) != kResultOk) {
+    if (plugin->component->initialize(FUnknownPtr<IHostApplication>(new RackHostApplication())) != kResultOk) {
         // Component creation succeeded but initialization failed - no need to terminate
         // IPtr will automatically release when plugin is deleted
         plugin->component = nullptr;
@@ -369,7 +791,7 @@ RackVST3Plugin* rack_vst3_plugin_new(const char* path, const char* uid) {
         plugin->controller = factory.createInstance<IEditController>(controllerUID);
         if (plugin->controller) {
             // Initialize controller - if this fails, clean up properly
-            if (plugin->controller->initialize(FUnknownPtr<IHostApplication>(new HostApplication())) != kResultOk) {
+            if (plugin->controller->initialize(FUnknownPtr<IHostApplication>(new RackHostApplication())) != kResultOk) {
                 // Controller init failed - terminate component and clean up
                 plugin->component->terminate();
                 plugin->controller = nullptr;
@@ -385,6 +807,13 @@ RackVST3Plugin* rack_vst3_plugin_new(const char* path, const char* uid) {
         plugin->controller = U::cast<IEditController>(plugin->component);
     }
 
+    // Install the component handler so editor edits and restart requests reach us
+    if (plugin->controller) {
+        IPtr<RackComponentHandler> handler(new RackComponentHandler(plugin), false);
+        plugin->component_handler = handler;
+        plugin->controller->setComponentHandler(handler);
+    }
+
     // Set up connection points if controller is separate
     if (plugin->controller && reinterpret_cast<void*>(plugin->controller.get()) != reinterpret_cast<void*>(plugin->component.get())) {
         plugin->component_cp = U::cast<IConnectionPoint>(plugin->component);
@@ -399,6 +828,25 @@ RackVST3Plugin* rack_vst3_plugin_new(const char* path, const char* uid) {
     return plugin;
 }
 
+#if defined(_WIN32)
+RackVST3Plugin* rack_vst3_plugin_new_w(const wchar_t* path, const char* uid) {
+    if (!path || !uid) {
+        return nullptr;
+    }
+
+    // The SDK's module loader takes UTF-8 and widens it for LoadLibraryW
+    int length = WideCharToMultiByte(CP_UTF8, 0, path, -1, nullptr, 0, nullptr, nullptr);
+    if (length <= 0) {
+        return nullptr;
+    }
+    std::string utf8(static_cast<size_t>(length), '\0');
+    WideCharToMultiByte(CP_UTF8, 0, path, -1, &utf8[0], length, nullptr, nullptr);
+    utf8.resize(static_cast<size_t>(length - 1));
+
+    return rack_vst3_plugin_new(utf8.c_str(), uid);
+}
+#endif
+
 void rack_vst3_plugin_free(RackVST3Plugin* plugin) {
     if (!plugin) {
         return;
@@ -411,6 +859,15 @@ void rack_vst3_plugin_free(RackVST3Plugin* plugin) {
         plugin->component->setActive(false);
     }
 
+    // Detach the component handler before the controller goes away
+    if (plugin->component_handler) {
+        if (plugin->controller) {
+            plugin->controller->setComponentHandler(nullptr);
+        }
+        plugin->component_handler->detach();
+        plugin->component_handler = nullptr;
+    }
+
     // Disconnect connection points
     if (plugin->component_cp && plugin->controller_cp) {
         plugin->component_cp->disconnect(plugin->controller_cp);
@@ -435,32 +892,14 @@ void rack_vst3_plugin_free(RackVST3Plugin* plugin) {
     delete plugin;
 }
 
-int rack_vst3_plugin_initialize(RackVST3Plugin* plugin, double sample_rate, uint32_t max_block_size) {
-    if (!plugin || !plugin->component || !plugin->processor) {
-        return RACK_VST3_ERROR_INVALID_PARAM;
-    }
-
-    std::lock_guard<std::mutex> lock(g_vst3_lifecycle_mutex);
-
-    plugin->sample_rate = sample_rate;
-    plugin->max_block_size = max_block_size;
-
-    // Setup processing with 32-bit float samples in realtime mode
-    ProcessSetup setup;
-    setup.processMode = kRealtime;
-    setup.symbolicSampleSize = kSample32;
-    setup.maxSamplesPerBlock = max_block_size;
-    setup.sampleRate = sample_rate;
-
-    if (plugin->processor->setupProcessing(setup) != kResultOk) {
-        return RACK_VST3_ERROR_GENERIC;
-    }
-
-    // Get bus configuration
+// Helper: Activate the main audio buses and read their channel counts
+static void setup_main_buses(RackVST3Plugin* plugin) {
     int32 numInputBuses = plugin->component->getBusCount(kAudio, kInput);
     int32 numOutputBuses = plugin->component->getBusCount(kAudio, kOutput);
 
-    // Activate main audio buses
+    plugin->num_input_channels = 0;
+    plugin->num_output_channels = 0;
+
     if (numInputBuses > 0) {
         plugin->component->activateBus(kAudio, kInput, 0, true);
         BusInfo busInfo;
@@ -476,22 +915,10 @@ int rack_vst3_plugin_initialize(RackVST3Plugin* plugin, double sample_rate, uint
             plugin->num_output_channels = busInfo.channelCount;
         }
     }
+}
 
-    // Activate component
-    if (plugin->component->setActive(true) != kResultOk) {
-        return RACK_VST3_ERROR_GENERIC;
-    }
-
-    // Start processing
-    if (plugin->processor->setProcessing(true) != kResultOk) {
-        plugin->component->setActive(false);
-        return RACK_VST3_ERROR_GENERIC;
-    }
-
-    // Prepare process_data once during initialization (not in hot path)
-    plugin->process_data.prepare(*plugin->component, max_block_size, kSample32);
-
-    // Build parameter cache
+// Helper: (Re)build the parameter cache from the controller
+static void build_parameter_cache(RackVST3Plugin* plugin) {
     if (plugin->controller) {
         int32 param_count = plugin->controller->getParameterCount();
         plugin->parameters.clear();
@@ -511,11 +938,54 @@ int rack_vst3_plugin_initialize(RackVST3Plugin* plugin, double sample_rate, uint
                 info.min_value = 0.0;
                 info.max_value = 1.0;
                 info.default_value = vst3_param_info.defaultNormalizedValue;
+                info.flags = vst3_param_info.flags;
 
                 plugin->parameters.push_back(info);
             }
         }
     }
+}
+
+int rack_vst3_plugin_initialize(RackVST3Plugin* plugin, double sample_rate, uint32_t max_block_size) {
+    if (!plugin || !plugin->component || !plugin->processor) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    std::lock_guard<std::mutex> lock(g_vst3_lifecycle_mutex);
+
+    plugin->sample_rate = sample_rate;
+    plugin->max_block_size = max_block_size;
+
+    // Setup processing with 32-bit float samples in realtime mode
+    ProcessSetup setup;
+    setup.processMode = kRealtime;
+    setup.symbolicSampleSize = kSample32;
+    setup.maxSamplesPerBlock = max_block_size;
+    setup.sampleRate = sample_rate;
+
+    if (plugin->processor->setupProcessing(setup) != kResultOk) {
+        return RACK_VST3_ERROR_GENERIC;
+    }
+
+    // Activate main audio buses
+    setup_main_buses(plugin);
+
+    // Activate component
+    if (plugin->component->setActive(true) != kResultOk) {
+        return RACK_VST3_ERROR_GENERIC;
+    }
+
+    // Start processing
+    if (plugin->processor->setProcessing(true) != kResultOk) {
+        plugin->component->setActive(false);
+        return RACK_VST3_ERROR_GENERIC;
+    }
+
+    // Prepare process_data once during initialization (not in hot path)
+    plugin->process_data.prepare(*plugin->component, max_block_size, kSample32);
+
+    // Build parameter cache
+    build_parameter_cache(plugin);
 
     // Enumerate factory presets if available
     IPtr<IUnitInfo> unit_info = U::cast<IUnitInfo>(plugin->controller);
@@ -571,18 +1041,77 @@ int rack_vst3_plugin_reset(RackVST3Plugin* plugin) {
     return RACK_VST3_OK;
 }
 
+int rack_vst3_plugin_apply_restart(RackVST3Plugin* plugin, int32_t* flags) {
+    if (!plugin || !flags) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    std::lock_guard<std::mutex> lock(g_vst3_lifecycle_mutex);
+
+    int32 pending = plugin->pending_restart.exchange(0);
+    *flags = pending;
+    if (pending == 0 || !plugin->component) {
+        return RACK_VST3_OK;
+    }
+
+    // Bus and latency changes only take effect across a deactivate/activate
+    // cycle, which also lets us re-read the bus layout safely
+    const int32 reactivate = kReloadComponent | kIoChanged | kLatencyChanged;
+    if ((pending & reactivate) && plugin->initialized && plugin->processor) {
+        plugin->processor->setProcessing(false);
+        plugin->component->setActive(false);
+
+        if (pending & (kReloadComponent | kIoChanged)) {
+            setup_main_buses(plugin);
+            plugin->process_data.prepare(*plugin->component, plugin->max_block_size, kSample32);
+        }
+
+        if (plugin->component->setActive(true) != kResultOk) {
+            plugin->initialized = false;
+            return RACK_VST3_ERROR_GENERIC;
+        }
+        if (plugin->processor->setProcessing(true) != kResultOk) {
+            plugin->component->setActive(false);
+            plugin->initialized = false;
+            return RACK_VST3_ERROR_GENERIC;
+        }
+    }
+
+    if (pending & (kReloadComponent | kParamTitlesChanged)) {
+        build_parameter_cache(plugin);
+    }
+
+    return RACK_VST3_OK;
+}
+
 int rack_vst3_plugin_get_input_channels(RackVST3Plugin* plugin) {
     if (!plugin || !plugin->initialized) {
         return 0;
     }
-    return plugin->num_input_channels;
+    return plugin->num_input_channels;
+}
+
+int rack_vst3_plugin_get_output_channels(RackVST3Plugin* plugin) {
+    if (!plugin || !plugin->initialized) {
+        return 0;
+    }
+    return plugin->num_output_channels;
+}
+
+int rack_vst3_plugin_get_latency(RackVST3Plugin* plugin) {
+    if (!plugin || !plugin->initialized || !plugin->processor) {
+        return 0;
+    }
+    return static_cast<int>(plugin->processor->getLatencySamples());
 }
 
-int rack_vst3_plugin_get_output_channels(RackVST3Plugin* plugin) {
-    if (!plugin || !plugin->initialized) {
+static_assert(RACK_VST3_INFINITE_TAIL == kInfiniteTail, "infinite tail must match the SDK");
+
+uint32_t rack_vst3_plugin_get_tail_samples(RackVST3Plugin* plugin) {
+    if (!plugin || !plugin->initialized || !plugin->processor) {
         return 0;
     }
-    return plugin->num_output_channels;
+    return plugin->processor->getTailSamples();
 }
 
 int rack_vst3_plugin_process(
@@ -619,8 +1148,21 @@ int rack_vst3_plugin_process(
     }
 
     // Update dynamic fields only (prepare() was called during initialization)
+    // frames == 0 is a valid VST3 "flush" call: the plugin receives pending
+    // parameter changes and events without producing audio
     plugin->process_data.numSamples = frames;
 
+    // Events queued by send_midi() may have offsets beyond this block (e.g. when
+    // the device delivers very small blocks). Clamp them into the block so they
+    // are delivered now instead of being dropped by the plugin.
+    int32 last_offset = frames > 0 ? static_cast<int32>(frames) - 1 : 0;
+    for (int32 i = 0; i < plugin->input_events.getEventCount(); ++i) {
+        Event* event = plugin->input_events.getEventByIndex(i);
+        if (event && event->sampleOffset > last_offset) {
+            event->sampleOffset = last_offset;
+        }
+    }
+
     // Set input buffers
     if (num_input_channels > 0) {
         AudioBusBuffers& bus = plugin->process_data.inputs[0];
@@ -635,12 +1177,37 @@ int rack_vst3_plugin_process(
         bus.channelBuffers32 = const_cast<float**>(outputs);
     }
 
+    // Forward edits made in the plugin's editor. Never block the audio
+    // thread on the editor: if it holds the lock, they go with the next block.
+    {
+        std::unique_lock<std::mutex> edits_lock(plugin->pending_edits_mutex, std::try_to_lock);
+        if (edits_lock.owns_lock()) {
+            for (const auto& edit : plugin->pending_edits) {
+                int32 queue_index = 0;
+                IParamValueQueue* queue = plugin->input_param_changes.addParameterData(edit.first, queue_index);
+                if (queue) {
+                    int32 point_index = 0;
+                    queue->addPoint(0, edit.second, point_index);
+                }
+            }
+            plugin->pending_edits.clear();
+        }
+    }
+
     // Set parameter and event interfaces
     plugin->process_data.inputParameterChanges = &plugin->input_param_changes;
     plugin->process_data.outputParameterChanges = &plugin->output_param_changes;
     plugin->process_data.inputEvents = &plugin->input_events;
     plugin->process_data.outputEvents = &plugin->output_events;
 
+    // Set the timeline position (transport fields are kept from
+    // rack_vst3_plugin_set_transport, and stay zero - not playing - until it
+    // is called; timecode fields from rack_vst3_plugin_set_timecode)
+    plugin->process_context.sampleRate = plugin->sample_rate;
+    plugin->process_context.projectTimeSamples = plugin->sample_position;
+    plugin->process_context.continousTimeSamples = plugin->sample_position;
+    plugin->process_data.processContext = &plugin->process_context;
+
     // Process
     tresult result = plugin->processor->process(plugin->process_data);
 
@@ -650,7 +1217,76 @@ int rack_vst3_plugin_process(
     plugin->output_events.clear();
     plugin->output_param_changes.clearQueue();
 
-    return (result == kResultOk) ? RACK_VST3_OK : RACK_VST3_ERROR_GENERIC;
+    if (result != kResultOk) {
+        return RACK_VST3_ERROR_GENERIC;
+    }
+
+    // Update sample position for next call
+    plugin->sample_position += frames;
+
+    return RACK_VST3_OK;
+}
+
+int rack_vst3_plugin_set_sample_position(RackVST3Plugin* plugin, int64_t position) {
+    if (!plugin || position < 0) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+    plugin->sample_position = position;
+    return RACK_VST3_OK;
+}
+
+int rack_vst3_plugin_set_timecode(
+    RackVST3Plugin* plugin,
+    uint32_t frames_per_second,
+    uint32_t flags,
+    int32_t smpte_offset_subframes
+) {
+    if (!plugin || frames_per_second == 0) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+    plugin->process_context.frameRate.framesPerSecond = frames_per_second;
+    plugin->process_context.frameRate.flags = flags;
+    plugin->process_context.smpteOffsetSubframes = smpte_offset_subframes;
+    plugin->process_context.state |= ProcessContext::kSmpteValid;
+    return RACK_VST3_OK;
+}
+
+int rack_vst3_plugin_set_transport(
+    RackVST3Plugin* plugin,
+    int playing,
+    double tempo,
+    int32_t numerator,
+    int32_t denominator,
+    double beat,
+    double bar_start_beat,
+    int cycling,
+    double cycle_start_beat,
+    double cycle_end_beat
+) {
+    if (!plugin || tempo <= 0.0 || numerator <= 0 || denominator <= 0) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    ProcessContext& context = plugin->process_context;
+    uint32 state = context.state & ProcessContext::kSmpteValid;
+    state |= ProcessContext::kTempoValid | ProcessContext::kTimeSigValid
+        | ProcessContext::kProjectTimeMusicValid | ProcessContext::kBarPositionValid;
+    if (playing) {
+        state |= ProcessContext::kPlaying;
+    }
+    if (cycling) {
+        state |= ProcessContext::kCycleActive | ProcessContext::kCycleValid;
+        context.cycleStartMusic = cycle_start_beat;
+        context.cycleEndMusic = cycle_end_beat;
+    }
+
+    context.tempo = tempo;
+    context.timeSigNumerator = numerator;
+    context.timeSigDenominator = denominator;
+    context.projectTimeMusic = beat;
+    context.barPositionMusic = bar_start_beat;
+    context.state = state;
+    return RACK_VST3_OK;
 }
 
 // ============================================================================
@@ -681,6 +1317,11 @@ int rack_vst3_plugin_get_parameter(RackVST3Plugin* plugin, uint32_t index, float
 }
 
 int rack_vst3_plugin_set_parameter(RackVST3Plugin* plugin, uint32_t index, float value) {
+    // Add parameter change at sample offset 0 (beginning of next buffer)
+    return rack_vst3_plugin_set_parameter_at(plugin, index, value, 0);
+}
+
+int rack_vst3_plugin_set_parameter_at(RackVST3Plugin* plugin, uint32_t index, float value, uint32_t sample_offset) {
     if (!plugin || !plugin->controller) {
         return RACK_VST3_ERROR_INVALID_PARAM;
     }
@@ -705,9 +1346,8 @@ int rack_vst3_plugin_set_parameter(RackVST3Plugin* plugin, uint32_t index, float
     int32 queue_index = 0;
     IParamValueQueue* queue = plugin->input_param_changes.addParameterData(param_id, queue_index);
     if (queue) {
-        // Add parameter change at sample offset 0 (beginning of next buffer)
         int32 point_index = 0;
-        queue->addPoint(0, value, point_index);
+        queue->addPoint(static_cast<int32>(sample_offset), value, point_index);
     }
 
     return RACK_VST3_OK;
@@ -735,8 +1375,7 @@ int rack_vst3_plugin_parameter_info(
     const auto& param_info = plugin->parameters[index];
 
     // Name
-    strncpy(name, param_info.title.c_str(), name_size - 1);
-    name[name_size - 1] = '\0';
+    copy_utf8_truncated(name, name_size, param_info.title);
 
     // Min/max/default
     if (min) *min = static_cast<float>(param_info.min_value);
@@ -745,13 +1384,49 @@ int rack_vst3_plugin_parameter_info(
 
     // Unit
     if (unit && unit_size > 0) {
-        strncpy(unit, param_info.units.c_str(), unit_size - 1);
-        unit[unit_size - 1] = '\0';
+        copy_utf8_truncated(unit, unit_size, param_info.units);
+    }
+
+    return RACK_VST3_OK;
+}
+
+int rack_vst3_plugin_parameter_flags(RackVST3Plugin* plugin, uint32_t index, uint32_t* flags) {
+    if (!plugin || !plugin->controller || !flags) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    if (index >= plugin->parameters.size()) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
     }
 
+    *flags = static_cast<uint32_t>(plugin->parameters[index].flags);
     return RACK_VST3_OK;
 }
 
+int rack_vst3_plugin_parameter_name(RackVST3Plugin* plugin, uint32_t index, char* buffer, size_t* size) {
+    if (!plugin || !plugin->controller) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    if (index >= plugin->parameters.size()) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    return copy_string_negotiated(plugin->parameters[index].title, buffer, size);
+}
+
+int rack_vst3_plugin_parameter_unit(RackVST3Plugin* plugin, uint32_t index, char* buffer, size_t* size) {
+    if (!plugin || !plugin->controller) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    if (index >= plugin->parameters.size()) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    return copy_string_negotiated(plugin->parameters[index].units, buffer, size);
+}
+
 // ============================================================================
 // Preset Management (Stub - TODO: Implement)
 // ============================================================================
@@ -781,8 +1456,7 @@ int rack_vst3_plugin_get_preset_info(
     const auto& preset = plugin->presets[index];
 
     // Copy name
-    strncpy(name, preset.name.c_str(), name_size - 1);
-    name[name_size - 1] = '\0';
+    copy_utf8_truncated(name, name_size, preset.name);
 
     // Preset number is just the index
     if (preset_number) {
@@ -792,7 +1466,19 @@ int rack_vst3_plugin_get_preset_info(
     return RACK_VST3_OK;
 }
 
-int rack_vst3_plugin_load_preset(RackVST3Plugin* plugin, int32_t preset_number) {
+int rack_vst3_plugin_preset_name(RackVST3Plugin* plugin, uint32_t index, char* buffer, size_t* size) {
+    if (!plugin || !plugin->initialized) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    if (index >= plugin->presets.size()) {
+        return RACK_VST3_ERROR_NOT_FOUND;
+    }
+
+    return copy_string_negotiated(plugin->presets[index].name, buffer, size);
+}
+
+static int load_preset_impl(RackVST3Plugin* plugin, int32_t preset_number) {
     if (!plugin || !plugin->initialized || !plugin->controller) {
         return RACK_VST3_ERROR_NOT_INITIALIZED;
     }
@@ -950,64 +1636,119 @@ int rack_vst3_plugin_load_preset(RackVST3Plugin* plugin, int32_t preset_number)
     return RACK_VST3_ERROR_NOT_SUPPORTED;
 }
 
-int rack_vst3_plugin_get_state_size(RackVST3Plugin* plugin) {
-    if (!plugin || !plugin->component) {
-        return 0;
+int rack_vst3_plugin_load_preset(RackVST3Plugin* plugin, int32_t preset_number) {
+    int result = load_preset_impl(plugin, preset_number);
+    if (result == RACK_VST3_OK) {
+        plugin->current_preset = preset_number;
     }
+    return result;
+}
 
-    // VST3 doesn't provide a query method for state size
-    // We need to actually serialize the state to determine the size
-    // This is done once for buffer allocation - worth the cost for accuracy
+// Find the preset selected by the plugin's program-change parameter, or -1
+static int32_t find_program_change_preset(RackVST3Plugin* plugin) {
+    IPtr<IUnitInfo> unit_info = U::cast<IUnitInfo>(plugin->controller);
+    if (!unit_info) {
+        return -1;
+    }
 
-    // Create temporary memory stream for size calculation
-    IPtr<MemoryStream> stream(new MemoryStream(), false);
+    int32 param_count = plugin->controller->getParameterCount();
+    for (int32 i = 0; i < param_count; i++) {
+        Vst::ParameterInfo param_info;
+        if (plugin->controller->getParameterInfo(i, param_info) != kResultOk) {
+            continue;
+        }
+        if ((param_info.flags & ParameterInfo::kIsProgramChange) == 0 || param_info.stepCount <= 0) {
+            continue;
+        }
 
-    // Reserve space for component state size marker
-    uint32_t size_marker_placeholder = 0;
-    stream->write(&size_marker_placeholder, sizeof(size_marker_placeholder), nullptr);
+        // The parameter selects a program in its unit's program list
+        ProgramListID list_id = kNoProgramListId;
+        int32 unit_count = unit_info->getUnitCount();
+        for (int32 u = 0; u < unit_count; u++) {
+            UnitInfo unit;
+            if (unit_info->getUnitInfo(u, unit) == kResultOk && unit.id == param_info.unitId) {
+                list_id = unit.programListId;
+                break;
+            }
+        }
+        if (list_id == kNoProgramListId) {
+            continue;
+        }
 
-    // Record position before component state
-    int64 component_start_pos = 0;
-    stream->tell(&component_start_pos);
+        ParamValue value = plugin->controller->getParamNormalized(param_info.id);
+        int32 program_index = static_cast<int32>(value * param_info.stepCount + 0.5);
 
-    // Get component state
-    tresult result = plugin->component->getState(stream);
-    if (result != kResultOk) {
-        // State serialization failed - return a safe default
-        return 1024 * 1024;  // 1MB fallback
+        for (size_t p = 0; p < plugin->presets.size(); p++) {
+            const auto& preset = plugin->presets[p];
+            if (preset.program_list_id == list_id && preset.program_index == program_index) {
+                return static_cast<int32_t>(p);
+            }
+        }
     }
 
-    // Record position after component state
-    int64 component_end_pos = 0;
-    stream->tell(&component_end_pos);
+    return -1;
+}
 
-    // Get controller state if separate controller
-    if (plugin->controller && reinterpret_cast<void*>(plugin->controller.get()) != reinterpret_cast<void*>(plugin->component.get())) {
-        result = plugin->controller->getState(stream);
-        if (result != kResultOk) {
-            // Controller state failed - return component state size only
-            return static_cast<int>(stream->getSize());
-        }
+// Helper: Index of the currently loaded preset, or -1 if unknown
+// Prefers the plugin's own program-change parameter, which follows program
+// changes made from its GUI or by MIDI; falls back to the last preset we loaded
+static int32_t current_preset_index(RackVST3Plugin* plugin) {
+    int32_t index = find_program_change_preset(plugin);
+    if (index < 0) {
+        index = plugin->current_preset;
     }
-
-    // Return actual state size
-    // This avoids the retry pattern - user gets correct size on first call
-    return static_cast<int>(stream->getSize());
+    if (index < 0 || index >= static_cast<int32_t>(plugin->presets.size())) {
+        return -1;
+    }
+    return index;
 }
 
-int rack_vst3_plugin_get_state(RackVST3Plugin* plugin, uint8_t* data, size_t* size) {
-    if (!plugin || !data || !size || !plugin->component) {
+int rack_vst3_plugin_get_current_preset(
+    RackVST3Plugin* plugin,
+    char* name,
+    size_t name_size,
+    int32_t* preset_number)
+{
+    if (!plugin || !plugin->initialized || !plugin->controller) {
+        return RACK_VST3_ERROR_NOT_INITIALIZED;
+    }
+
+    if (!name || name_size == 0 || !preset_number) {
         return RACK_VST3_ERROR_INVALID_PARAM;
     }
 
-    if (*size == 0) {
+    int32_t index = current_preset_index(plugin);
+    if (index < 0) {
+        return 0;
+    }
+
+    *preset_number = index;
+    copy_utf8_truncated(name, name_size, plugin->presets[index].name);
+    return 1;
+}
+
+int rack_vst3_plugin_current_preset_name(RackVST3Plugin* plugin, char* buffer, size_t* size) {
+    if (!plugin || !plugin->initialized || !plugin->controller) {
+        return RACK_VST3_ERROR_NOT_INITIALIZED;
+    }
+
+    if (!size) {
         return RACK_VST3_ERROR_INVALID_PARAM;
     }
 
-    // Create memory stream for state serialization
-    // IPtr ensures automatic cleanup on ALL code paths (success, error, buffer size check failure)
-    IPtr<MemoryStream> stream(new MemoryStream(), false);
+    int32_t index = current_preset_index(plugin);
+    if (index < 0) {
+        *size = 0;
+        return 0;
+    }
+
+    int result = copy_string_negotiated(plugin->presets[index].name, buffer, size);
+    return result == RACK_VST3_OK ? 1 : result;
+}
 
+// Serialize the component and controller state into stream
+// Layout: u32 size of the component state, component state, controller state
+static int write_plugin_state(RackVST3Plugin* plugin, MemoryStream* stream) {
     // Reserve space for component state size marker (write it later)
     uint32_t size_marker_placeholder = 0;
     stream->write(&size_marker_placeholder, sizeof(size_marker_placeholder), nullptr);
@@ -1042,31 +1783,11 @@ int rack_vst3_plugin_get_state(RackVST3Plugin* plugin, uint8_t* data, size_t* si
         }
     }
 
-    // Copy to output buffer
-    // Note: MemoryStream grows dynamically - no fixed limit, can't overflow
-    // get_state_size() returns the actual size, so this should match
-    // However, we still check in case the state changed between calls
-    size_t state_size = stream->getSize();
-    if (state_size > *size) {
-        *size = state_size;  // Return required size for caller to retry
-        return RACK_VST3_ERROR_INVALID_PARAM;
-    }
-
-    memcpy(data, stream->getData().data(), state_size);
-    *size = state_size;
-    // IPtr automatically releases stream on scope exit
-
     return RACK_VST3_OK;
 }
 
-int rack_vst3_plugin_set_state(RackVST3Plugin* plugin, const uint8_t* data, size_t size) {
-    if (!plugin || !data || size == 0 || !plugin->component) {
-        return RACK_VST3_ERROR_INVALID_PARAM;
-    }
-
-    // Create memory stream from data (IPtr provides RAII cleanup)
-    IPtr<MemoryStream> stream(new MemoryStream(data, size), false);
-
+// Restore state written by write_plugin_state from stream
+static int read_plugin_state(RackVST3Plugin* plugin, IBStream* stream) {
     // Read component state size marker first (written at position 0 during serialization)
     uint32_t component_state_size = 0;
     int32 bytes_read = 0;
@@ -1092,10 +1813,109 @@ int rack_vst3_plugin_set_state(RackVST3Plugin* plugin, const uint8_t* data, size
         }
     }
 
-    // IPtr automatically releases stream on scope exit
+    // The restored state may come from any preset
+    plugin->current_preset = -1;
+
+    return RACK_VST3_OK;
+}
+
+int rack_vst3_plugin_get_state_size(RackVST3Plugin* plugin) {
+    if (!plugin || !plugin->component) {
+        return 0;
+    }
+
+    // VST3 doesn't provide a query method for state size
+    // We need to actually serialize the state to determine the size
+    IPtr<MemoryStream> stream(new MemoryStream(), false);
+    if (write_plugin_state(plugin, stream) != RACK_VST3_OK) {
+        return 0;
+    }
+
+    size_t state_size = stream->getSize();
+    if (state_size > static_cast<size_t>(std::numeric_limits<int>::max())) {
+        return 0;
+    }
+    return static_cast<int>(state_size);
+}
+
+int rack_vst3_plugin_get_state(RackVST3Plugin* plugin, uint8_t* data, size_t* size) {
+    if (!plugin || !data || !size || !plugin->component) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    if (*size == 0) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    // IPtr ensures automatic cleanup on ALL code paths (success, error, buffer size check failure)
+    IPtr<MemoryStream> stream(new MemoryStream(), false);
+    int result = write_plugin_state(plugin, stream);
+    if (result != RACK_VST3_OK) {
+        return result;
+    }
+
+    // The state may have changed since get_state_size()
+    size_t state_size = stream->getSize();
+    if (state_size > *size) {
+        *size = state_size;  // Return required size for caller to retry
+        return RACK_VST3_ERROR_BUFFER_TOO_SMALL;
+    }
+
+    stream->copyTo(data);
+    *size = state_size;
+
     return RACK_VST3_OK;
 }
 
+int rack_vst3_plugin_get_state_stream(
+    RackVST3Plugin* plugin,
+    RackVST3StateWriteCallback callback,
+    void* user_data)
+{
+    if (!plugin || !callback || !plugin->component) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    // Serialized once, so the size and contents always agree
+    IPtr<MemoryStream> stream(new MemoryStream(), false);
+    int result = write_plugin_state(plugin, stream);
+    if (result != RACK_VST3_OK) {
+        return result;
+    }
+
+    return stream->writeTo(callback, user_data) ? RACK_VST3_OK : RACK_VST3_ERROR_GENERIC;
+}
+
+int rack_vst3_plugin_set_state(RackVST3Plugin* plugin, const uint8_t* data, size_t size) {
+    if (!plugin || !data || size == 0 || !plugin->component) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    // Read straight from the caller's buffer (IPtr provides RAII cleanup)
+    IPtr<ViewStream> stream(new ViewStream(data, size), false);
+    return read_plugin_state(plugin, stream);
+}
+
+int rack_vst3_plugin_set_state_stream(
+    RackVST3Plugin* plugin,
+    RackVST3StateReadCallback callback,
+    void* user_data)
+{
+    if (!plugin || !callback || !plugin->component) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    IPtr<MemoryStream> stream(new MemoryStream(callback, user_data), false);
+    int result = read_plugin_state(plugin, stream);
+
+    // A failed read looks like a truncated state to the plugin; report the
+    // callback's failure instead of whatever the plugin made of it
+    if (stream->sourceFailed()) {
+        return RACK_VST3_ERROR_GENERIC;
+    }
+    return result;
+}
+
 // ============================================================================
 // MIDI API
 // ============================================================================
@@ -1214,3 +2034,25 @@ int rack_vst3_plugin_send_midi(
 
     return RACK_VST3_OK;
 }
+
+// ============================================================================
+// Component Handler API
+// ============================================================================
+
+int rack_vst3_plugin_set_component_callback(
+    RackVST3Plugin* plugin,
+    RackVST3ComponentCallback callback,
+    void* user_data)
+{
+    if (!plugin) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    // Without an edit controller there is nothing to report
+    if (!plugin->component_handler) {
+        return RACK_VST3_ERROR_NOT_SUPPORTED;
+    }
+
+    plugin->component_handler->setCallback(callback, user_data);
+    return RACK_VST3_OK;
+}
@@ -1,18 +1,25 @@
 #include "rack_vst3.h"
 #include "public.sdk/source/vst/hosting/module.h"
 #include "public.sdk/source/vst/hosting/plugprovider.h"
+#include "public.sdk/source/vst/moduleinfo/moduleinfoparser.h"
 #include "pluginterfaces/vst/ivstaudioprocessor.h"
 #include "pluginterfaces/vst/ivstcomponent.h"
 
 #include <vector>
 #include <string>
 #include <algorithm>
+#include <cstdlib>
 #include <cstring>
+#include <fstream>
+#include <optional>
+#include <set>
+#include <sstream>
 
 #if defined(__APPLE__)
     #include <CoreFoundation/CoreFoundation.h>
     #include <dirent.h>
     #include <sys/stat.h>
+    #include <limits.h>
 #elif defined(_WIN32)
     #include <windows.h>
     #include <shlobj.h>
@@ -27,29 +34,206 @@ using namespace VST3;
 using namespace Steinberg;
 using namespace Steinberg::Vst;
 
+// Factory and class information beyond what RackVST3PluginInfo holds
+struct ScannedFactory {
+    std::string url;
+    std::string email;
+    std::string sdk_version;
+    uint32_t factory_flags = 0;
+    uint32_t class_flags = 0;
+};
+
+// Untruncated strings of a scanned plugin
+// RackVST3PluginInfo has fixed-size fields; these are served by
+// rack_vst3_scanner_plugin_string() and rack_vst3_scanner_plugin_flags()
+struct ScannedStrings {
+    std::string name;
+    std::string manufacturer;
+    std::string path;
+    std::string category;
+    std::string canonical_path;
+    ScannedFactory factory;
+};
+
+// A bundle found while scanning
+// `path` is where it was found (possibly through a symlink or alias);
+// `canonical_path` is where it actually lives, and is what gets loaded.
+struct DiscoveredBundle {
+    std::string path;
+    std::string canonical_path;
+};
+
 // Internal scanner state
 struct RackVST3Scanner {
     std::vector<std::string> search_paths;
+    bool include_system_paths = true;
+    bool follow_symlinks = true;
+
+    // Strings of the plugins filled in by the last scan, by array index
+    std::vector<ScannedStrings> last_scan;
 };
 
+// Helper: Copy a UTF-8 string into a fixed-size buffer
+// Truncates on a character boundary so the result stays valid UTF-8 when the
+// string doesn't fit. Always null-terminates (buffer_size must be > 0).
+static void copy_utf8_truncated(char* buffer, size_t buffer_size, const std::string& str) {
+    size_t length = std::min(str.size(), buffer_size - 1);
+    if (length < str.size()) {
+        // Back up over continuation bytes (10xxxxxx) to the start of the cut character
+        while (length > 0 && (static_cast<unsigned char>(str[length]) & 0xC0) == 0x80) {
+            length--;
+        }
+    }
+    memcpy(buffer, str.data(), length);
+    buffer[length] = '\0';
+}
+
+// Helper: Copy a UTF-8 string into a length-negotiated buffer
+// With a NULL buffer only the required size (including the null terminator)
+// is reported. Never truncates: a buffer that is too small is left untouched.
+static int copy_string_negotiated(const std::string& str, char* buffer, size_t* size) {
+    if (!size) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    size_t required = str.size() + 1;
+    if (!buffer) {
+        *size = required;
+        return RACK_VST3_OK;
+    }
+    if (*size < required) {
+        *size = required;
+        return RACK_VST3_ERROR_BUFFER_TOO_SMALL;
+    }
+
+    memcpy(buffer, str.data(), str.size());
+    buffer[str.size()] = '\0';
+    *size = required;
+    return RACK_VST3_OK;
+}
+
+#if defined(_WIN32)
+// Helper: Convert UTF-8 to UTF-16 for the wide Win32 APIs
+static std::wstring utf8_to_wide(const std::string& str) {
+    if (str.empty()) {
+        return std::wstring();
+    }
+    int length = MultiByteToWideChar(CP_UTF8, 0, str.data(), static_cast<int>(str.size()), nullptr, 0);
+    std::wstring wide(static_cast<size_t>(length), L'\0');
+    MultiByteToWideChar(CP_UTF8, 0, str.data(), static_cast<int>(str.size()), &wide[0], length);
+    return wide;
+}
+
+// Helper: Convert UTF-16 from the wide Win32 APIs to UTF-8
+static std::string wide_to_utf8(const std::wstring& wide) {
+    if (wide.empty()) {
+        return std::string();
+    }
+    int length = WideCharToMultiByte(CP_UTF8, 0, wide.data(), static_cast<int>(wide.size()), nullptr, 0, nullptr, nullptr);
+    std::string str(static_cast<size_t>(length), '\0');
+    WideCharToMultiByte(CP_UTF8, 0, wide.data(), static_cast<int>(wide.size()), &str[0], length, nullptr, nullptr);
+    return str;
+}
+#endif
+
+#if defined(__APPLE__)
+// Helper: Resolve a Finder alias file to the path it points to
+// Returns an empty Optional if the file isn't an alias or its target is gone.
+// Never shows UI or mounts volumes.
+static std::optional<std::string> resolve_alias(const std::string& path) {
+    CFURLRef url = CFURLCreateFromFileSystemRepresentation(
+        kCFAllocatorDefault, reinterpret_cast<const UInt8*>(path.c_str()),
+        static_cast<CFIndex>(path.size()), false);
+    if (!url) {
+        return {};
+    }
+
+    std::optional<std::string> resolved;
+    CFDataRef bookmark = CFURLCreateBookmarkDataFromAliasFile(kCFAllocatorDefault, url, nullptr);
+    if (bookmark) {
+        Boolean is_stale = false;
+        CFURLRef target = CFURLCreateByResolvingBookmarkData(
+            kCFAllocatorDefault, bookmark,
+            kCFBookmarkResolutionWithoutUIMask | kCFBookmarkResolutionWithoutMountingMask,
+            nullptr, nullptr, &is_stale, nullptr);
+        if (target) {
+            char buffer[PATH_MAX];
+            if (CFURLGetFileSystemRepresentation(target, true, reinterpret_cast<UInt8*>(buffer), sizeof(buffer))) {
+                resolved = std::string(buffer);
+            }
+            CFRelease(target);
+        }
+        CFRelease(bookmark);
+    }
+    CFRelease(url);
+    return resolved;
+}
+#endif
+
+// Helper: Resolve a bundle path to its canonical location
+// Follows every symlink (junctions and symlinks on Windows) and removes . and
+// .. components. Returns the path unchanged if it can't be resolved.
+// On Windows the result is in \\?\ extended-length form.
+static std::string canonical_bundle_path(const std::string& path) {
+#if defined(_WIN32)
+    HANDLE handle = CreateFileW(
+        utf8_to_wide(path).c_str(), 0,
+        FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+        nullptr, OPEN_EXISTING, FILE_FLAG_BACKUP_SEMANTICS, nullptr);
+    if (handle == INVALID_HANDLE_VALUE) {
+        return path;
+    }
+
+    std::string resolved = path;
+    DWORD length = GetFinalPathNameByHandleW(handle, nullptr, 0, FILE_NAME_NORMALIZED);
+    if (length > 0) {
+        std::wstring wide(length, L'\0');
+        DWORD written = GetFinalPathNameByHandleW(handle, &wide[0], length, FILE_NAME_NORMALIZED);
+        if (written > 0 && written < length) {
+            wide.resize(written);
+            resolved = wide_to_utf8(wide);
+        }
+    }
+    CloseHandle(handle);
+    return resolved;
+#else
+    char* resolved = realpath(path.c_str(), nullptr);
+    if (!resolved) {
+        return path;
+    }
+    std::string result(resolved);
+    free(resolved);
+    return result;
+#endif
+}
+
 // Helper: Scan a directory for .vst3 bundles/folders
 // Returns list of full paths to .vst3 bundles found
-static std::vector<std::string> scan_directory_for_vst3(const std::string& dir_path) {
-    std::vector<std::string> vst3_paths;
+// On Windows, dir_path may be in \\?\ extended-length form; the bundles found
+// are returned in the same form, so paths past MAX_PATH keep working.
+// With follow_symlinks, symlinked bundles (and Finder aliases to bundles on
+// macOS) are included; otherwise only real bundle directories are.
+static std::vector<DiscoveredBundle> scan_directory_for_vst3(const std::string& dir_path, bool follow_symlinks) {
+    std::vector<DiscoveredBundle> vst3_paths;
 
 #if defined(_WIN32)
     // Windows implementation
-    std::string search_path = dir_path + "\\*.vst3";
-    WIN32_FIND_DATAA find_data;
-    HANDLE find_handle = FindFirstFileA(search_path.c_str(), &find_data);
+    // The wide API is required: the ANSI one is limited to MAX_PATH even with
+    // the \\?\ prefix, and can't represent names outside the code page
+    std::wstring search_path = utf8_to_wide(dir_path) + L"\\*.vst3";
+    WIN32_FIND_DATAW find_data;
+    HANDLE find_handle = FindFirstFileW(search_path.c_str(), &find_data);
 
     if (find_handle != INVALID_HANDLE_VALUE) {
         do {
+            if (!follow_symlinks && (find_data.dwFileAttributes & FILE_ATTRIBUTE_REPARSE_POINT)) {
+                continue;
+            }
             if (find_data.dwFileAttributes & FILE_ATTRIBUTE_DIRECTORY) {
-                std::string full_path = dir_path + "\\" + find_data.cFileName;
-                vst3_paths.push_back(full_path);
+                std::string full_path = dir_path + "\\" + wide_to_utf8(find_data.cFileName);
+                vst3_paths.push_back({full_path, canonical_bundle_path(full_path)});
             }
-        } while (FindNextFileA(find_handle, &find_data));
+        } while (FindNextFileW(find_handle, &find_data));
         FindClose(find_handle);
     }
 #else
@@ -73,10 +257,22 @@ static std::vector<std::string> scan_directory_for_vst3(const std::string& dir_p
             std::string full_path = dir_path + "/" + name;
 
             // Verify it's a directory (VST3 bundles are folders on macOS/Linux)
+            // lstat doesn't follow links, so a symlinked bundle isn't a directory
             struct stat st;
-            if (stat(full_path.c_str(), &st) == 0 && S_ISDIR(st.st_mode)) {
-                vst3_paths.push_back(full_path);
+            int stat_result = follow_symlinks ? stat(full_path.c_str(), &st) : lstat(full_path.c_str(), &st);
+            if (stat_result == 0 && S_ISDIR(st.st_mode)) {
+                vst3_paths.push_back({full_path, canonical_bundle_path(full_path)});
             }
+#if defined(__APPLE__)
+            // A Finder alias is a regular file that points at the bundle
+            else if (follow_symlinks && stat_result == 0 && S_ISREG(st.st_mode)) {
+                auto target = resolve_alias(full_path);
+                struct stat target_st;
+                if (target && stat(target->c_str(), &target_st) == 0 && S_ISDIR(target_st.st_mode)) {
+                    vst3_paths.push_back({full_path, canonical_bundle_path(*target)});
+                }
+            }
+#endif
         }
     }
 
@@ -103,9 +299,10 @@ static std::vector<std::string> get_default_vst3_paths() {
 
 #elif defined(_WIN32)
     // Windows paths
-    char common_files[MAX_PATH];
-    if (SUCCEEDED(SHGetFolderPathA(NULL, CSIDL_PROGRAM_FILES_COMMON, NULL, 0, common_files))) {
-        std::string path = std::string(common_files) + "\\VST3";
+    // Wide API: the ANSI one mangles folders outside the system code page
+    wchar_t common_files[MAX_PATH];
+    if (SUCCEEDED(SHGetFolderPathW(NULL, CSIDL_PROGRAM_FILES_COMMON, NULL, 0, common_files))) {
+        std::string path = wide_to_utf8(common_files) + "\\VST3";
         paths.push_back(path);
     }
 
@@ -144,6 +341,113 @@ static RackVST3PluginType determine_plugin_type(const std::string& subcategories
     return RACK_VST3_TYPE_OTHER;
 }
 
+// Helper: Parse a VST3 version string (e.g., "1.0.0" or "1.2.3.4") to uint32_t
+// Format: major.minor.patch.build -> pack into uint32_t
+static uint32_t parse_version(const std::string& version_str) {
+    uint32_t version = 0;
+    if (!version_str.empty()) {
+        int major = 0, minor = 0, patch = 0, build = 0;
+        // Try to parse up to 4 components
+        int parsed = sscanf(version_str.c_str(), "%d.%d.%d.%d", &major, &minor, &patch, &build);
+        if (parsed >= 1) {
+            // Clamp each component to valid byte range [0, 255]
+            // This prevents integer overflow and handles negative/oversized values
+            auto clamp_byte = [](int val) -> uint8_t {
+                return static_cast<uint8_t>(std::max(0, std::min(255, val)));
+            };
+
+            uint8_t major_byte = clamp_byte(major);
+            uint8_t minor_byte = clamp_byte(minor);
+            uint8_t patch_byte = clamp_byte(patch);
+            uint8_t build_byte = clamp_byte(build);
+
+            // Pack into uint32_t: major(8) | minor(8) | patch(8) | build(8)
+            version = (static_cast<uint32_t>(major_byte) << 24) |
+                     (static_cast<uint32_t>(minor_byte) << 16) |
+                     (static_cast<uint32_t>(patch_byte) << 8) |
+                     static_cast<uint32_t>(build_byte);
+        }
+    }
+    return version;
+}
+
+// Helper: Fill a RackVST3PluginInfo from class metadata
+// Shared by the moduleinfo.json and factory enumeration paths
+// The untruncated strings are appended to full_strings.
+static void fill_plugin_info(
+    RackVST3PluginInfo& info,
+    std::vector<ScannedStrings>& full_strings,
+    const std::string& name,
+    const std::string& vendor,
+    const DiscoveredBundle& bundle,
+    const std::string& uid_str,
+    const std::string& version_str,
+    const std::string& subcategories,
+    const ScannedFactory& factory)
+{
+    // Name
+    copy_utf8_truncated(info.name, sizeof(info.name), name);
+
+    // Manufacturer
+    copy_utf8_truncated(info.manufacturer, sizeof(info.manufacturer), vendor);
+
+    // Path (full path to the .vst3 bundle/folder)
+    strncpy(info.path, bundle.path.c_str(), sizeof(info.path) - 1);
+    info.path[sizeof(info.path) - 1] = '\0';
+
+    // Unique ID (UID as hex string)
+    strncpy(info.unique_id, uid_str.c_str(), sizeof(info.unique_id) - 1);
+    info.unique_id[sizeof(info.unique_id) - 1] = '\0';
+
+    // Version
+    info.version = parse_version(version_str);
+
+    // Type (from subcategories)
+    info.plugin_type = determine_plugin_type(subcategories);
+
+    // Category (subcategories string)
+    copy_utf8_truncated(info.category, sizeof(info.category), subcategories);
+
+    full_strings.push_back({name, vendor, bundle.path, subcategories, bundle.canonical_path, factory});
+}
+
+// Helper: Read and parse a bundle's moduleinfo.json, if it has one
+// Newer bundles ship this file so hosts can list their classes without
+// loading the binary. Returns an empty Optional if the file is missing or
+// can't be parsed, in which case the caller falls back to the factory.
+static std::optional<ModuleInfo> read_module_info(const std::string& module_path) {
+    auto info_path = Hosting::Module::getModuleInfoPath(module_path);
+    if (!info_path) {
+        return {};
+    }
+
+#if defined(_WIN32)
+    // The narrow ifstream constructor uses the ANSI code page on Windows
+    std::ifstream file(utf8_to_wide(*info_path), std::ios::in | std::ios::binary);
+#else
+    std::ifstream file(*info_path, std::ios::in | std::ios::binary);
+#endif
+    if (!file) {
+        return {};
+    }
+    std::stringstream contents;
+    contents << file.rdbuf();
+
+    return ModuleInfoLib::parseJson(contents.str(), nullptr);
+}
+
+// Helper: Join moduleinfo.json subcategories into the factory's "Fx|Reverb" form
+static std::string join_subcategories(const std::vector<std::string>& sub_categories) {
+    std::string joined;
+    for (const auto& sub_category : sub_categories) {
+        if (!joined.empty()) {
+            joined += "|";
+        }
+        joined += sub_category;
+    }
+    return joined;
+}
+
 // ============================================================================
 // Scanner Implementation
 // ============================================================================
@@ -165,6 +469,17 @@ int rack_vst3_scanner_add_path(RackVST3Scanner* scanner, const char* path) {
     return RACK_VST3_OK;
 }
 
+#if defined(_WIN32)
+int rack_vst3_scanner_add_path_w(RackVST3Scanner* scanner, const wchar_t* path) {
+    if (!scanner || !path) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    // Search paths are kept as UTF-8 and widened again for every Win32 call
+    return rack_vst3_scanner_add_path(scanner, wide_to_utf8(path).c_str());
+}
+#endif
+
 int rack_vst3_scanner_add_default_paths(RackVST3Scanner* scanner) {
     if (!scanner) {
         return RACK_VST3_ERROR_INVALID_PARAM;
@@ -175,6 +490,28 @@ int rack_vst3_scanner_add_default_paths(RackVST3Scanner* scanner) {
     return RACK_VST3_OK;
 }
 
+int rack_vst3_default_path(size_t index, char* buffer, size_t* size) {
+    if (!size) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    auto paths = get_default_vst3_paths();
+    if (index >= paths.size()) {
+        return RACK_VST3_ERROR_NOT_FOUND;
+    }
+    return copy_string_negotiated(paths[index], buffer, size);
+}
+
+int rack_vst3_scanner_set_options(RackVST3Scanner* scanner, int include_system_paths, int follow_symlinks) {
+    if (!scanner) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    scanner->include_system_paths = include_system_paths != 0;
+    scanner->follow_symlinks = follow_symlinks != 0;
+    return RACK_VST3_OK;
+}
+
 int rack_vst3_scanner_scan(RackVST3Scanner* scanner, RackVST3PluginInfo* plugins, size_t max_plugins) {
     if (!scanner) {
         return RACK_VST3_ERROR_INVALID_PARAM;
@@ -182,33 +519,95 @@ int rack_vst3_scanner_scan(RackVST3Scanner* scanner, RackVST3PluginInfo* plugins
 
     bool count_only = (plugins == nullptr);
     size_t count = 0;
+    if (!count_only) {
+        scanner->last_scan.clear();
+    }
 
     // Determine which paths to scan
     std::vector<std::string> paths_to_scan = scanner->search_paths;
-    if (paths_to_scan.empty()) {
+    if (paths_to_scan.empty() && scanner->include_system_paths) {
         // No custom paths - use system defaults
         paths_to_scan = get_default_vst3_paths();
     }
 
-    // Collect all module paths by scanning directories for .vst3 bundles
-    std::vector<std::string> module_paths;
+    // Collect all bundles by scanning directories for .vst3 bundles
+    std::vector<DiscoveredBundle> bundles;
 
     for (const auto& search_path : paths_to_scan) {
-        auto found_bundles = scan_directory_for_vst3(search_path);
-        module_paths.insert(module_paths.end(), found_bundles.begin(), found_bundles.end());
+        auto found_bundles = scan_directory_for_vst3(search_path, scanner->follow_symlinks);
+        bundles.insert(bundles.end(), found_bundles.begin(), found_bundles.end());
     }
 
     // Also include system-discovered modules (from getModulePaths)
     // This ensures we find all plugins even if custom paths are specified
-    auto system_modules = Hosting::Module::getModulePaths();
-    module_paths.insert(module_paths.end(), system_modules.begin(), system_modules.end());
+    if (scanner->include_system_paths) {
+        for (const auto& system_module : Hosting::Module::getModulePaths()) {
+            bundles.push_back({system_module, canonical_bundle_path(system_module)});
+        }
+    }
 
-    // Remove duplicates (in case same plugin is in both custom and system paths)
-    std::sort(module_paths.begin(), module_paths.end());
-    module_paths.erase(std::unique(module_paths.begin(), module_paths.end()), module_paths.end());
+    // Remove duplicates: the same bundle in both custom and system paths, or
+    // reachable through several symlinks (e.g. plugin managers that link a
+    // central store into each folder). The first path in sorted order wins.
+    std::sort(bundles.begin(), bundles.end(), [](const DiscoveredBundle& a, const DiscoveredBundle& b) {
+        return a.path < b.path;
+    });
+    std::set<std::string> seen_bundles;
+    bundles.erase(
+        std::remove_if(bundles.begin(), bundles.end(), [&](const DiscoveredBundle& bundle) {
+            return !seen_bundles.insert(bundle.canonical_path).second;
+        }),
+        bundles.end());
 
     // Scan all found modules
-    for (const auto& module_path : module_paths) {
+    for (const auto& bundle : bundles) {
+        const std::string& module_path = bundle.canonical_path;
+        // Prefer moduleinfo.json: listing classes from it avoids loading (and
+        // running static initializers of) the plugin binary at all
+        if (auto module_info = read_module_info(module_path)) {
+            for (const auto& class_info : module_info->classes) {
+                // Only process audio effect classes
+                if (class_info.category != kVstAudioEffectClass) {
+                    continue;
+                }
+
+                // Normalize the class ID to the same format the factory path produces
+                auto uid = VST3::UID::fromString(class_info.cid);
+                if (!uid) {
+                    continue;
+                }
+
+                if (count_only || count >= max_plugins) {
+                    count++;
+                    continue;
+                }
+
+                std::string vendor = class_info.vendor;
+                if (vendor.empty()) {
+                    vendor = module_info->factoryInfo.vendor;
+                }
+
+                fill_plugin_info(
+                    plugins[count],
+                    scanner->last_scan,
+                    class_info.name,
+                    vendor,
+                    bundle,
+                    uid_to_string(*uid),
+                    class_info.version,
+                    join_subcategories(class_info.subCategories),
+                    ScannedFactory{
+                        module_info->factoryInfo.url,
+                        module_info->factoryInfo.email,
+                        class_info.sdkVersion,
+                        static_cast<uint32_t>(module_info->factoryInfo.flags),
+                        class_info.flags});
+
+                count++;
+            }
+            continue;
+        }
+
         std::string error_description;
         auto module = Hosting::Module::create(module_path, error_description);
 
@@ -239,67 +638,25 @@ int rack_vst3_scanner_scan(RackVST3Scanner* scanner, RackVST3PluginInfo* plugins
                 continue;
             }
 
-            // Fill in plugin info
-            RackVST3PluginInfo& info = plugins[count];
-
-            // Name
-            std::string name = class_info.name();
-            strncpy(info.name, name.c_str(), sizeof(info.name) - 1);
-            info.name[sizeof(info.name) - 1] = '\0';
-
-            // Manufacturer
             std::string vendor = class_info.vendor();
             if (vendor.empty()) {
                 vendor = factory.info().vendor();
             }
-            strncpy(info.manufacturer, vendor.c_str(), sizeof(info.manufacturer) - 1);
-            info.manufacturer[sizeof(info.manufacturer) - 1] = '\0';
-
-            // Path (full path to the .vst3 bundle/folder)
-            strncpy(info.path, module_path.c_str(), sizeof(info.path) - 1);
-            info.path[sizeof(info.path) - 1] = '\0';
-
-            // Unique ID (UID as hex string)
-            std::string uid_str = uid_to_string(class_info.ID());
-            strncpy(info.unique_id, uid_str.c_str(), sizeof(info.unique_id) - 1);
-            info.unique_id[sizeof(info.unique_id) - 1] = '\0';
-
-            // Version - parse version string (e.g., "1.0.0" or "1.2.3.4") to uint32_t
-            // Format: major.minor.patch.build -> pack into uint32_t
-            std::string version_str = class_info.version();
-            uint32_t version = 0;
-            if (!version_str.empty()) {
-                int major = 0, minor = 0, patch = 0, build = 0;
-                // Try to parse up to 4 components
-                int parsed = sscanf(version_str.c_str(), "%d.%d.%d.%d", &major, &minor, &patch, &build);
-                if (parsed >= 1) {
-                    // Clamp each component to valid byte range [0, 255]
-                    // This prevents integer overflow and handles negative/oversized values
-                    auto clamp_byte = [](int val) -> uint8_t {
-                        return static_cast<uint8_t>(std::max(0, std::min(255, val)));
-                    };
-
-                    uint8_t major_byte = clamp_byte(major);
-                    uint8_t minor_byte = clamp_byte(minor);
-                    uint8_t patch_byte = clamp_byte(patch);
-                    uint8_t build_byte = clamp_byte(build);
-
-                    // Pack into uint32_t: major(8) | minor(8) | patch(8) | build(8)
-                    version = (static_cast<uint32_t>(major_byte) << 24) |
-                             (static_cast<uint32_t>(minor_byte) << 16) |
-                             (static_cast<uint32_t>(patch_byte) << 8) |
-                             static_cast<uint32_t>(build_byte);
-                }
-            }
-            info.version = version;
-
-            // Type (from subcategories)
-            std::string subcategories = class_info.subCategoriesString();
-            info.plugin_type = determine_plugin_type(subcategories);
-
-            // Category (subcategories string)
-            strncpy(info.category, subcategories.c_str(), sizeof(info.category) - 1);
-            info.category[sizeof(info.category) - 1] = '\0';
+            fill_plugin_info(
+                plugins[count],
+                scanner->last_scan,
+                class_info.name(),
+                vendor,
+                bundle,
+                uid_to_string(class_info.ID()),
+                class_info.version(),
+                class_info.subCategoriesString(),
+                ScannedFactory{
+                    factory.info().url(),
+                    factory.info().email(),
+                    class_info.sdkVersion(),
+                    static_cast<uint32_t>(factory.info().flags()),
+                    class_info.classFlags()});
 
             count++;
         }
@@ -307,3 +664,61 @@ int rack_vst3_scanner_scan(RackVST3Scanner* scanner, RackVST3PluginInfo* plugins
 
     return static_cast<int>(count);
 }
+
+int rack_vst3_scanner_plugin_string(
+    RackVST3Scanner* scanner,
+    size_t index,
+    int field,
+    char* buffer,
+    size_t* size)
+{
+    if (!scanner) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    if (index >= scanner->last_scan.size()) {
+        return RACK_VST3_ERROR_NOT_FOUND;
+    }
+
+    const auto& strings = scanner->last_scan[index];
+    switch (field) {
+        case RACK_VST3_FIELD_NAME:
+            return copy_string_negotiated(strings.name, buffer, size);
+        case RACK_VST3_FIELD_MANUFACTURER:
+            return copy_string_negotiated(strings.manufacturer, buffer, size);
+        case RACK_VST3_FIELD_PATH:
+            return copy_string_negotiated(strings.path, buffer, size);
+        case RACK_VST3_FIELD_CATEGORY:
+            return copy_string_negotiated(strings.category, buffer, size);
+        case RACK_VST3_FIELD_CANONICAL_PATH:
+            return copy_string_negotiated(strings.canonical_path, buffer, size);
+        case RACK_VST3_FIELD_VENDOR_URL:
+            return copy_string_negotiated(strings.factory.url, buffer, size);
+        case RACK_VST3_FIELD_VENDOR_EMAIL:
+            return copy_string_negotiated(strings.factory.email, buffer, size);
+        case RACK_VST3_FIELD_SDK_VERSION:
+            return copy_string_negotiated(strings.factory.sdk_version, buffer, size);
+        default:
+            return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+}
+
+int rack_vst3_scanner_plugin_flags(
+    RackVST3Scanner* scanner,
+    size_t index,
+    uint32_t* factory_flags,
+    uint32_t* class_flags)
+{
+    if (!scanner || !factory_flags || !class_flags) {
+        return RACK_VST3_ERROR_INVALID_PARAM;
+    }
+
+    if (index >= scanner->last_scan.size()) {
+        return RACK_VST3_ERROR_NOT_FOUND;
+    }
+
+    const auto& factory = scanner->last_scan[index].factory;
+    *factory_flags = factory.factory_flags;
+    *class_flags = factory.class_flags;
+    return RACK_VST3_OK;
+}
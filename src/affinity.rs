@@ -0,0 +1,324 @@
+//! Pinning threads to CPU cores
+//!
+//! On many-core machines, heavy instruments run better when their threads
+//! stay on the same cores (and NUMA node) as the memory they touch, instead
+//! of migrating and thrashing other cores' caches. A [`CpuSet`] names cores,
+//! by index or by NUMA node, and [`pin_current_thread()`] keeps the calling
+//! thread on them.
+//!
+//! Rack runs a [`Graph`](crate::graph::Graph) or [`Chain`](crate::graph::Chain)
+//! on whichever thread processes it, so hosts pin their own audio and worker
+//! threads. Helper processes are pinned with
+//! [`IsolationHost::affinity()`](crate::isolation::IsolationHost::affinity)
+//! and [`IsolationHost::group_affinity()`](crate::isolation::IsolationHost::group_affinity).
+//!
+//! Pinning is supported on Linux and Windows. macOS has no hard affinity
+//! (only scheduling hints), so pinning fails there, as does NUMA lookup
+//! anywhere but Linux.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rack::affinity::{self, CpuSet};
+//!
+//! # fn main() -> rack::Result<()> {
+//! // Keep this worker on the first NUMA node
+//! affinity::pin_current_thread(&CpuSet::numa_node(0)?)?;
+//!
+//! // Or on cores 4 to 7
+//! affinity::pin_current_thread(&"4-7".parse::<CpuSet>()?)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{Error, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// A set of CPU cores, by index
+///
+/// Written and parsed in the Linux `cpulist` format, e.g. `0-3,8,10-11`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct CpuSet {
+    /// Sorted, without duplicates
+    cores: Vec<usize>,
+}
+
+impl CpuSet {
+    /// The given cores
+    pub fn new(cores: impl IntoIterator<Item = usize>) -> Self {
+        let mut cores: Vec<usize> = cores.into_iter().collect();
+        cores.sort_unstable();
+        cores.dedup();
+        Self { cores }
+    }
+
+    /// The cores of a NUMA node
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the node doesn't exist, or the platform doesn't
+    /// expose its NUMA topology (everywhere but Linux)
+    pub fn numa_node(node: usize) -> Result<Self> {
+        sys::numa_node(node)
+    }
+
+    /// How many NUMA nodes the machine has (1 if unknown)
+    pub fn numa_node_count() -> usize {
+        sys::numa_node_count().unwrap_or(1).max(1)
+    }
+
+    /// The cores the calling thread may currently run on
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the platform doesn't support thread affinity
+    pub fn current() -> Result<Self> {
+        sys::current()
+    }
+
+    /// The cores, in ascending order
+    pub fn cores(&self) -> &[usize] {
+        &self.cores
+    }
+
+    /// Whether the set has no cores
+    pub fn is_empty(&self) -> bool {
+        self.cores.is_empty()
+    }
+
+    /// Whether the set includes `core`
+    pub fn contains(&self, core: usize) -> bool {
+        self.cores.binary_search(&core).is_ok()
+    }
+}
+
+impl FromStr for CpuSet {
+    type Err = Error;
+
+    fn from_str(list: &str) -> Result<Self> {
+        let invalid = || Error::InvalidFormat(format!("Invalid CPU list: {:?}", list));
+        let mut cores = Vec::new();
+        for part in list.trim().split(',').filter(|p| !p.trim().is_empty()) {
+            let (first, last) = match part.split_once('-') {
+                Some((first, last)) => (first, last),
+                None => (part, part),
+            };
+            let first: usize = first.trim().parse().map_err(|_| invalid())?;
+            let last: usize = last.trim().parse().map_err(|_| invalid())?;
+            if first > last {
+                return Err(invalid());
+            }
+            cores.extend(first..=last);
+        }
+        Ok(Self::new(cores))
+    }
+}
+
+impl fmt::Display for CpuSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        let mut cores = self.cores.iter().copied().peekable();
+        while let Some(start) = cores.next() {
+            let mut end = start;
+            while cores.next_if_eq(&(end + 1)).is_some() {
+                end += 1;
+            }
+            if !first {
+                f.write_str(",")?;
+            }
+            first = false;
+            if start == end {
+                write!(f, "{}", start)?;
+            } else {
+                write!(f, "{}-{}", start, end)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Keep the calling thread on the cores of `set`
+///
+/// Threads the calling thread starts afterwards inherit the affinity on
+/// Linux, but not on Windows.
+///
+/// # Errors
+///
+/// Returns an error if `set` is empty or names cores the platform can't
+/// address, or if the platform doesn't support thread affinity
+pub fn pin_current_thread(set: &CpuSet) -> Result<()> {
+    if set.is_empty() {
+        return Err(Error::Other("Can't pin a thread to no cores".to_string()));
+    }
+    sys::pin_current_thread(set)
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use super::CpuSet;
+    use crate::{Error, Result};
+    use std::ffi::c_int;
+
+    /// Cores addressable by a `cpu_set_t`
+    const MAX_CORES: usize = 1024;
+    const WORDS: usize = MAX_CORES / 64;
+
+    extern "C" {
+        fn sched_setaffinity(pid: c_int, size: usize, mask: *const u64) -> c_int;
+        fn sched_getaffinity(pid: c_int, size: usize, mask: *mut u64) -> c_int;
+    }
+
+    pub(super) fn pin_current_thread(set: &CpuSet) -> Result<()> {
+        let mut mask = [0u64; WORDS];
+        for &core in set.cores() {
+            if core >= MAX_CORES {
+                return Err(Error::Other(format!("Core {} out of range", core)));
+            }
+            mask[core / 64] |= 1 << (core % 64);
+        }
+        // Safety: mask is a cpu_set_t-sized bitmask; pid 0 is the calling thread
+        if unsafe { sched_setaffinity(0, std::mem::size_of_val(&mask), mask.as_ptr()) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+
+    pub(super) fn current() -> Result<CpuSet> {
+        let mut mask = [0u64; WORDS];
+        // Safety: as above
+        if unsafe { sched_getaffinity(0, std::mem::size_of_val(&mask), mask.as_mut_ptr()) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(CpuSet::new(
+            (0..MAX_CORES).filter(|&core| mask[core / 64] & (1 << (core % 64)) != 0),
+        ))
+    }
+
+    pub(super) fn numa_node(node: usize) -> Result<CpuSet> {
+        let path = format!("/sys/devices/system/node/node{}/cpulist", node);
+        let list = std::fs::read_to_string(path)
+            .map_err(|_| Error::Other(format!("No NUMA node {}", node)))?;
+        list.parse()
+    }
+
+    pub(super) fn numa_node_count() -> Option<usize> {
+        let online = std::fs::read_to_string("/sys/devices/system/node/online").ok()?;
+        online
+            .parse::<CpuSet>()
+            .ok()
+            .map(|nodes| nodes.cores().len())
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use super::CpuSet;
+    use crate::{Error, Result};
+    use std::ffi::c_void;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentThread() -> *mut c_void;
+        fn SetThreadAffinityMask(thread: *mut c_void, mask: usize) -> usize;
+    }
+
+    fn mask(set: &CpuSet) -> Result<usize> {
+        let mut mask = 0usize;
+        for &core in set.cores() {
+            if core >= usize::BITS as usize {
+                // Cores beyond the first processor group need group affinity
+                return Err(Error::Other(format!("Core {} out of range", core)));
+            }
+            mask |= 1 << core;
+        }
+        Ok(mask)
+    }
+
+    pub(super) fn pin_current_thread(set: &CpuSet) -> Result<()> {
+        let mask = mask(set)?;
+        // Safety: GetCurrentThread() returns a pseudo-handle valid on this thread
+        if unsafe { SetThreadAffinityMask(GetCurrentThread(), mask) } == 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+
+    pub(super) fn current() -> Result<CpuSet> {
+        // Windows only reports a thread's mask when replacing it
+        Err(Error::Other(
+            "Reading thread affinity isn't supported on Windows".to_string(),
+        ))
+    }
+
+    pub(super) fn numa_node(node: usize) -> Result<CpuSet> {
+        Err(Error::Other(format!(
+            "NUMA node {} can't be looked up on this platform",
+            node
+        )))
+    }
+
+    pub(super) fn numa_node_count() -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod sys {
+    use super::CpuSet;
+    use crate::{Error, Result};
+
+    pub(super) fn pin_current_thread(_set: &CpuSet) -> Result<()> {
+        Err(Error::Other(
+            "Thread affinity isn't supported on this platform".to_string(),
+        ))
+    }
+
+    pub(super) fn current() -> Result<CpuSet> {
+        Err(Error::Other(
+            "Thread affinity isn't supported on this platform".to_string(),
+        ))
+    }
+
+    pub(super) fn numa_node(node: usize) -> Result<CpuSet> {
+        Err(Error::Other(format!(
+            "NUMA node {} can't be looked up on this platform",
+            node
+        )))
+    }
+
+    pub(super) fn numa_node_count() -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_list_format() {
+        let set: CpuSet = "8, 0-3,10-11,2".parse().unwrap();
+        assert_eq!(set.cores(), &[0, 1, 2, 3, 8, 10, 11]);
+        assert_eq!(set.to_string(), "0-3,8,10-11");
+        assert!(set.contains(10) && !set.contains(9));
+        assert_eq!("".parse::<CpuSet>().unwrap(), CpuSet::default());
+        assert!("3-1".parse::<CpuSet>().is_err());
+        assert!("a".parse::<CpuSet>().is_err());
+        assert!(pin_current_thread(&CpuSet::default()).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_pin_current_thread() {
+        std::thread::spawn(|| {
+            let allowed = CpuSet::current().unwrap();
+            let core = CpuSet::new([allowed.cores()[0]]);
+            pin_current_thread(&core).unwrap();
+            assert_eq!(CpuSet::current().unwrap(), core);
+        })
+        .join()
+        .unwrap();
+        assert!(CpuSet::numa_node_count() >= 1);
+    }
+}
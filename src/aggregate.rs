@@ -0,0 +1,448 @@
+//! Playing one engine through two output devices
+//!
+//! A DJ or live rig sends the main mix to the PA and a cue mix to
+//! headphones on a second interface. The two devices run on independent
+//! clocks, so even at the same nominal sample rate one consumes audio
+//! slightly faster than the other and a plain FIFO between them eventually
+//! under- or overflows.
+//!
+//! [`DriftQueue`] carries audio from one clock domain to another, resampling
+//! at the nominal rate ratio nudged by a fraction of a percent to hold its
+//! fill level at a target latency. With the `cpal` feature,
+//! [`AggregateOutput`] uses it to run a renderer on a main device and route
+//! extra output channels (the cue bus) to a second device; each device follows
+//! unplugs and default changes like a single
+//! [`OutputStream`](crate::output::OutputStream).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[cfg(feature = "cpal")]
+//! # mod example {
+//! # use rack::prelude::*;
+//! # use rack::output::OutputRenderer;
+//! use rack::aggregate::{AggregateConfig, AggregateOutput};
+//!
+//! # fn example(mixer: impl OutputRenderer) -> Result<()> {
+//! // The mixer renders the main device's channels, then two cue channels
+//! let output = AggregateOutput::start(AggregateConfig::new("USB Headphones"), mixer)?;
+//! # Ok(())
+//! # }
+//! # }
+//! ```
+
+use crate::events::{self, HostEvent};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[cfg(feature = "cpal")]
+use crate::output::{OutputConfig, OutputRenderer, OutputStream};
+#[cfg(feature = "cpal")]
+use crate::Result;
+#[cfg(feature = "cpal")]
+use smallvec::SmallVec;
+#[cfg(feature = "cpal")]
+use std::sync::Arc;
+
+/// Smoothing applied to the fill level per [`pull()`](DriftQueue::pull)
+const FILL_SMOOTHING: f64 = 0.05;
+
+/// Rate correction per unit of relative fill error
+const CORRECTION_GAIN: f64 = 0.05;
+
+/// Largest rate correction (0.5%, under 9 cents of pitch)
+const MAX_CORRECTION: f64 = 0.005;
+
+/// FIFO between two audio clocks with drift-compensating resampling
+///
+/// The producer calls [`push()`](Self::push) at the input rate and the
+/// consumer [`pull()`](Self::pull) at the output rate, each from its own
+/// audio thread. Output is silent until the target latency has been
+/// buffered, and again after an underrun (reported as [`HostEvent::Xrun`]).
+/// If the consumer stalls, old audio is discarded to keep the latency at the
+/// target.
+#[derive(Debug)]
+pub struct DriftQueue {
+    channels: usize,
+    latency: Duration,
+    state: Mutex<DriftState>,
+}
+
+#[derive(Debug)]
+struct DriftState {
+    queues: Vec<VecDeque<f32>>,
+    input_rate: f64,
+    output_rate: f64,
+    /// Target fill in input frames
+    target: f64,
+    /// Fractional read position in the queues
+    position: f64,
+    /// Smoothed fill level in input frames
+    average: f64,
+    /// Waiting for the target fill before producing output
+    priming: bool,
+}
+
+impl DriftState {
+    fn reset(&mut self) {
+        for queue in &mut self.queues {
+            queue.clear();
+        }
+        self.position = 0.0;
+        self.average = self.target;
+        self.priming = true;
+    }
+
+    /// Frames left to read
+    fn fill(&self) -> f64 {
+        self.queues.first().map_or(0, VecDeque::len) as f64 - self.position
+    }
+}
+
+impl DriftQueue {
+    /// Queue for `channels` channels holding about `latency` of audio
+    ///
+    /// Both rates start at 48 kHz.
+    pub fn new(channels: usize, latency: Duration) -> Self {
+        let mut state = DriftState {
+            queues: vec![VecDeque::new(); channels],
+            input_rate: 48000.0,
+            output_rate: 48000.0,
+            target: 0.0,
+            position: 0.0,
+            average: 0.0,
+            priming: true,
+        };
+        state.target = (latency.as_secs_f64() * state.input_rate).max(1.0);
+        state.reset();
+        Self {
+            channels,
+            latency,
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Number of channels carried
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Target latency
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    /// Set the producer's sample rate, discarding buffered audio
+    pub fn set_input_rate(&self, rate: f64) {
+        let mut state = self.lock();
+        state.input_rate = rate;
+        state.target = (self.latency.as_secs_f64() * rate).max(1.0);
+        state.reset();
+    }
+
+    /// Set the consumer's sample rate, discarding buffered audio
+    pub fn set_output_rate(&self, rate: f64) {
+        let mut state = self.lock();
+        state.output_rate = rate;
+        state.reset();
+    }
+
+    /// Frames buffered, at the input rate
+    pub fn fill(&self) -> usize {
+        self.lock().fill().max(0.0) as usize
+    }
+
+    /// Discard buffered audio and wait for the target latency again
+    pub fn reset(&self) {
+        self.lock().reset();
+    }
+
+    /// Append `frames` frames, one slice per channel
+    ///
+    /// Missing channels are filled with silence; extra ones are ignored.
+    pub fn push(&self, input: &[&[f32]], frames: usize) {
+        let mut state = self.lock();
+        for (ch, queue) in state.queues.iter_mut().enumerate() {
+            match input.get(ch) {
+                Some(samples) => queue.extend(&samples[..frames]),
+                None => queue.resize(queue.len() + frames, 0.0),
+            }
+        }
+
+        // The consumer stalled (e.g. its device is gone): drop the oldest
+        // audio so it resumes at the target latency rather than far behind
+        let limit = (state.target * 4.0) as usize + frames;
+        let len = state.queues.first().map_or(0, VecDeque::len);
+        if len > limit {
+            let excess = len - state.target as usize;
+            for queue in &mut state.queues {
+                queue.drain(..excess);
+            }
+            state.position = 0.0;
+            state.average = state.target;
+        }
+    }
+
+    /// Fill `frames` frames of each output slice
+    ///
+    /// Channels beyond those carried are silenced.
+    pub fn pull(&self, outputs: &mut [&mut [f32]], frames: usize) {
+        let mut underrun = 0;
+        {
+            let mut state = self.lock();
+            let mut done = 0;
+            if state.priming && state.fill() >= state.target {
+                state.priming = false;
+                state.average = state.fill();
+            }
+
+            if !state.priming {
+                let fill = state.fill();
+                state.average += (fill - state.average) * FILL_SMOOTHING;
+                let error = (state.average - state.target) / state.target;
+                let correction = (error * CORRECTION_GAIN).clamp(-MAX_CORRECTION, MAX_CORRECTION);
+                let step = state.input_rate / state.output_rate * (1.0 + correction);
+
+                let len = state.queues.first().map_or(0, VecDeque::len);
+                let mut position = state.position;
+                while done < frames {
+                    let index = position as usize;
+                    if index + 1 >= len {
+                        break;
+                    }
+                    let t = (position - index as f64) as f32;
+                    for (out, queue) in outputs.iter_mut().zip(&state.queues) {
+                        let a = queue[index];
+                        out[done] = a + (queue[index + 1] - a) * t;
+                    }
+                    position += step;
+                    done += 1;
+                }
+
+                let consumed = (position as usize).min(len);
+                for queue in &mut state.queues {
+                    queue.drain(..consumed);
+                }
+                state.position = position - consumed as f64;
+                if done < frames {
+                    underrun = frames - done;
+                    state.priming = true;
+                }
+            }
+
+            for (ch, out) in outputs.iter_mut().enumerate() {
+                let start = if ch < self.channels { done } else { 0 };
+                out[start..frames].fill(0.0);
+            }
+        }
+
+        if underrun > 0 {
+            events::emit(HostEvent::Xrun { frames: underrun });
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, DriftState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Devices and routing for an [`AggregateOutput`]
+#[cfg(feature = "cpal")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AggregateConfig {
+    /// The main device, which drives rendering
+    pub main: OutputConfig,
+
+    /// The cue device
+    pub cue: OutputConfig,
+
+    /// Number of cue bus channels the renderer produces after the main
+    /// device's channels
+    pub cue_channels: usize,
+
+    /// Audio buffered between the devices; must cover both devices' buffer
+    /// sizes
+    pub cue_latency: Duration,
+}
+
+#[cfg(feature = "cpal")]
+impl AggregateConfig {
+    /// Main mix on the default output, a stereo cue bus on the named device
+    ///
+    /// The cue device doesn't fall back to the default output when missing,
+    /// so cue audio never ends up on the main speakers.
+    pub fn new(cue_device: impl Into<String>) -> Self {
+        Self {
+            main: OutputConfig::default(),
+            cue: OutputConfig {
+                device: Some(cue_device.into()),
+                fallback_to_default: false,
+                ..OutputConfig::default()
+            },
+            cue_channels: 2,
+            cue_latency: Duration::from_millis(30),
+        }
+    }
+}
+
+/// A renderer playing on a main and a cue device
+///
+/// The renderer runs on the main device's audio thread. It is configured
+/// with the main device's channel count plus
+/// [`cue_channels`](AggregateConfig::cue_channels) and renders the main
+/// channels first, then the cue bus, which is carried to the cue device
+/// through a [`DriftQueue`]. Dropping it stops both devices like
+/// [`stop()`](Self::stop).
+#[cfg(feature = "cpal")]
+pub struct AggregateOutput {
+    main: OutputStream,
+    cue: OutputStream,
+    queue: Arc<DriftQueue>,
+}
+
+#[cfg(feature = "cpal")]
+impl AggregateOutput {
+    /// Open both devices and start playing `renderer`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either device can't be opened, or `configure()`
+    /// fails
+    pub fn start<R: OutputRenderer>(config: AggregateConfig, renderer: R) -> Result<Self> {
+        let queue = Arc::new(DriftQueue::new(config.cue_channels, config.cue_latency));
+        // The cue side starts first and plays silence until audio arrives
+        let cue = OutputStream::start(
+            config.cue,
+            CueReader {
+                queue: Arc::clone(&queue),
+            },
+        )?;
+        let main = OutputStream::start(
+            config.main,
+            MainTap {
+                inner: renderer,
+                queue: Arc::clone(&queue),
+                cue: Vec::new(),
+            },
+        )?;
+        Ok(Self { main, cue, queue })
+    }
+
+    /// The main device's stream
+    pub fn main(&self) -> &OutputStream {
+        &self.main
+    }
+
+    /// The cue device's stream
+    pub fn cue(&self) -> &OutputStream {
+        &self.cue
+    }
+
+    /// Cue audio currently buffered between the devices
+    pub fn cue_buffered(&self) -> Duration {
+        let rate = self.main.device().map_or(48000.0, |d| d.sample_rate);
+        Duration::from_secs_f64(self.queue.fill() as f64 / rate)
+    }
+
+    /// Stop both devices
+    pub fn stop(self) {
+        let Self { main, cue, .. } = self;
+        main.stop();
+        cue.stop();
+    }
+}
+
+/// Main-device renderer: renders both buses and queues the cue bus
+#[cfg(feature = "cpal")]
+struct MainTap<R> {
+    inner: R,
+    queue: Arc<DriftQueue>,
+    /// Cue bus buffers, one per cue channel
+    cue: Vec<Vec<f32>>,
+}
+
+#[cfg(feature = "cpal")]
+impl<R: OutputRenderer> OutputRenderer for MainTap<R> {
+    fn configure(
+        &mut self,
+        sample_rate: f64,
+        max_block_size: usize,
+        channels: usize,
+    ) -> Result<()> {
+        let cue_channels = self.queue.channels();
+        self.inner
+            .configure(sample_rate, max_block_size, channels + cue_channels)?;
+        self.cue = vec![vec![0.0; max_block_size]; cue_channels];
+        self.queue.set_input_rate(sample_rate);
+        Ok(())
+    }
+
+    fn render(&mut self, outputs: &mut [&mut [f32]], num_frames: usize) {
+        let mut buses: SmallVec<[&mut [f32]; 8]> = outputs.iter_mut().map(|o| &mut **o).collect();
+        buses.extend(self.cue.iter_mut().map(|b| &mut b[..num_frames]));
+        self.inner.render(&mut buses, num_frames);
+        drop(buses);
+
+        let cue: SmallVec<[&[f32]; 8]> = self.cue.iter().map(|b| &b[..num_frames]).collect();
+        self.queue.push(&cue, num_frames);
+    }
+}
+
+/// Cue-device renderer: plays the queued cue bus
+#[cfg(feature = "cpal")]
+struct CueReader {
+    queue: Arc<DriftQueue>,
+}
+
+#[cfg(feature = "cpal")]
+impl OutputRenderer for CueReader {
+    fn configure(
+        &mut self,
+        sample_rate: f64,
+        _max_block_size: usize,
+        _channels: usize,
+    ) -> Result<()> {
+        self.queue.set_output_rate(sample_rate);
+        Ok(())
+    }
+
+    fn render(&mut self, outputs: &mut [&mut [f32]], num_frames: usize) {
+        self.queue.pull(outputs, num_frames);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drift_queue_holds_latency() {
+        // 10 ms at 48 kHz
+        let queue = DriftQueue::new(1, Duration::from_millis(10));
+        let target = 480;
+        let input: Vec<f32> = (0..256).map(|i| i as f32).collect();
+        let mut output = vec![1.0f32; 255];
+
+        // Silent until the target latency is buffered
+        queue.push(&[&input], 256);
+        queue.pull(&mut [&mut output], 255);
+        assert!(output.iter().all(|&s| s == 0.0));
+
+        // The producer's clock runs 0.4% fast; without correction the queue
+        // would grow by a frame per block
+        let mut fills = Vec::new();
+        for _ in 0..4000 {
+            queue.push(&[&input], 256);
+            fills.push(queue.fill());
+            queue.pull(&mut [&mut output], 255);
+        }
+        let settled = &fills[3000..];
+        let max = *settled.iter().max().unwrap();
+        let min = *settled.iter().min().unwrap();
+        assert!(min >= target && max <= target * 5 / 4, "{}..{}", min, max);
+
+        // Output follows the input ramp
+        assert!(output.windows(2).filter(|w| w[1] < w[0]).count() <= 2);
+    }
+}
@@ -0,0 +1,513 @@
+//! Offline measurement utilities
+//!
+//! These helpers drive an initialized plugin with test signals outside of any
+//! realtime context and analyze what comes out, e.g. to draw an EQ curve for a
+//! plugin that doesn't expose one, or to find out how much latency a plugin
+//! really introduces.
+//!
+//! All measurements call [`reset()`](PluginInstance::reset) before and after
+//! running, so earlier processing doesn't leak into the result and the plugin
+//! is left without a ringing tail. Audio is processed in blocks of
+//! [`MEASUREMENT_BLOCK_SIZE`] frames, so the plugin must have been initialized
+//! with a `max_block_size` of at least that.
+
+use crate::gain::linear_to_db;
+use crate::generator::{Generator, Signal};
+use crate::render::RenderJob;
+use crate::{Error, PluginInstance, Result};
+use std::f64::consts::PI;
+
+/// Block size used when rendering test signals through a plugin
+pub const MEASUREMENT_BLOCK_SIZE: usize = 64;
+
+/// Lowest frequency reported by [`measure_frequency_response()`]
+const LOWEST_FREQUENCY_HZ: f64 = 20.0;
+
+/// Magnitude and phase response of a plugin
+#[derive(Debug, Clone)]
+pub struct FrequencyResponse {
+    /// Frequencies of each point in Hz (logarithmically spaced, ascending)
+    pub frequencies: Vec<f64>,
+
+    /// Linear magnitude at each frequency, relative to the input level (1.0 = unity)
+    pub magnitude: Vec<f32>,
+
+    /// Phase at each frequency in radians, wrapped to -π..π
+    pub phase: Vec<f32>,
+}
+
+impl FrequencyResponse {
+    /// Magnitude at each frequency in decibels
+    pub fn magnitude_db(&self) -> Vec<f32> {
+        self.magnitude
+            .iter()
+            .map(|&m| linear_to_db(m))
+            .collect()
+    }
+}
+
+/// Measure the frequency response of an effect plugin
+///
+/// Sends an impulse of amplitude `level` into every input channel and evaluates
+/// the first output channel's impulse response at `n_points` logarithmically
+/// spaced frequencies between 20 Hz and Nyquist.
+///
+/// The impulse response is captured for half a second, which is plenty for EQs
+/// and filters; reverbs and long delays will be truncated.
+///
+/// # Arguments
+///
+/// * `plugin` - An initialized effect plugin
+/// * `sample_rate` - The sample rate the plugin was initialized with
+/// * `n_points` - Number of frequency points to evaluate
+/// * `level` - Impulse amplitude (linear, e.g. 0.5). Use a low level for
+///   plugins with level-dependent behavior such as compressors.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The plugin is not initialized
+/// - The plugin has no audio inputs or outputs
+/// - `level` is not a positive finite number
+/// - Processing fails
+///
+/// # Examples
+///
+/// ```no_run
+/// # use rack::prelude::*;
+/// # fn example(mut plugin: impl PluginInstance) -> Result<()> {
+/// plugin.initialize(48000.0, 512)?;
+/// let response = rack::analysis::measure_frequency_response(&mut plugin, 48000.0, 256, 0.25)?;
+/// for (freq, db) in response.frequencies.iter().zip(response.magnitude_db()) {
+///     println!("{:>8.1} Hz: {:+.2} dB", freq, db);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn measure_frequency_response<P: PluginInstance + ?Sized>(
+    plugin: &mut P,
+    sample_rate: f64,
+    n_points: usize,
+    level: f32,
+) -> Result<FrequencyResponse> {
+    if !(sample_rate.is_finite() && sample_rate > 0.0) {
+        return Err(Error::Other(format!(
+            "Invalid sample rate: {}",
+            sample_rate
+        )));
+    }
+
+    let length = (sample_rate / 2.0).ceil() as usize;
+    let response = render_impulse(plugin, length, level)?;
+    let ir = &response[0];
+
+    let nyquist = sample_rate / 2.0;
+    let low = LOWEST_FREQUENCY_HZ.min(nyquist / 2.0);
+    let high = nyquist * 0.999;
+
+    let mut frequencies = Vec::with_capacity(n_points);
+    let mut magnitude = Vec::with_capacity(n_points);
+    let mut phase = Vec::with_capacity(n_points);
+
+    for k in 0..n_points {
+        let t = if n_points > 1 {
+            k as f64 / (n_points - 1) as f64
+        } else {
+            0.5
+        };
+        let freq = low * (high / low).powf(t);
+        let (re, im) = dft_bin(ir, 2.0 * PI * freq / sample_rate);
+
+        frequencies.push(freq);
+        magnitude.push(((re * re + im * im).sqrt() / level as f64) as f32);
+        phase.push(im.atan2(re) as f32);
+    }
+
+    Ok(FrequencyResponse {
+        frequencies,
+        magnitude,
+        phase,
+    })
+}
+
+/// Captured impulse response of a plugin
+#[derive(Debug, Clone)]
+pub struct ImpulseResponse {
+    /// Impulse response for each output channel, normalized to the input level
+    pub channels: Vec<Vec<f32>>,
+
+    /// Measured latency in samples (position of the strongest response peak)
+    pub latency_samples: usize,
+}
+
+/// Capture the impulse response of an effect plugin
+///
+/// Sends an impulse of amplitude `level` into every input channel and records
+/// `length` frames from every output channel. The recorded samples are divided
+/// by `level`, so a unity-gain plugin produces a peak of 1.0.
+///
+/// Useful for convolution export, for plugin validation, and for checking the
+/// measured latency against what the plugin reports.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The plugin is not initialized
+/// - The plugin has no audio inputs or outputs
+/// - `level` is not a positive finite number
+/// - Processing fails
+pub fn capture_impulse_response<P: PluginInstance + ?Sized>(
+    plugin: &mut P,
+    length: usize,
+    level: f32,
+) -> Result<ImpulseResponse> {
+    let mut channels = render_impulse(plugin, length, level)?;
+    for channel in channels.iter_mut() {
+        channel.iter_mut().for_each(|s| *s /= level);
+    }
+    let latency_samples = peak_position(&channels);
+
+    Ok(ImpulseResponse {
+        channels,
+        latency_samples,
+    })
+}
+
+/// Measure a plugin's processing latency in samples
+///
+/// Locates the peak of the plugin's impulse response within `max_latency`
+/// samples. This works well for plugins whose impulse response has a clear main
+/// peak (delay-compensated linear-phase EQs, lookahead limiters, etc.). A plugin
+/// that outputs silence reports zero latency.
+///
+/// # Errors
+///
+/// Same as [`capture_impulse_response()`].
+pub fn detect_latency<P: PluginInstance + ?Sized>(
+    plugin: &mut P,
+    max_latency: usize,
+) -> Result<usize> {
+    let response = capture_impulse_response(plugin, max_latency + 1, 0.5)?;
+    Ok(response.latency_samples)
+}
+
+/// Largest latency [`null_test()`] will look for when aligning the two paths
+pub const NULL_TEST_MAX_LATENCY: usize = 16384;
+
+/// Result of comparing two processing paths with [`null_test()`]
+#[derive(Debug, Clone)]
+pub struct NullReport {
+    /// Measured latency of the first path in samples
+    pub latency_a: usize,
+
+    /// Measured latency of the second path in samples
+    pub latency_b: usize,
+
+    /// Number of frames compared per channel (after alignment)
+    pub frames_compared: usize,
+
+    /// RMS of the difference signal across all channels (linear)
+    pub residual_rms: f32,
+
+    /// Peak absolute difference across all channels (linear)
+    pub residual_peak: f32,
+}
+
+impl NullReport {
+    /// RMS of the residual in dBFS
+    pub fn residual_rms_db(&self) -> f32 {
+        linear_to_db(self.residual_rms)
+    }
+
+    /// Peak of the residual in dBFS
+    pub fn residual_peak_db(&self) -> f32 {
+        linear_to_db(self.residual_peak)
+    }
+
+    /// Whether both paths produced identical output
+    pub fn is_bit_exact(&self) -> bool {
+        self.residual_peak == 0.0
+    }
+
+    /// Whether the residual peak stays below `threshold_db` (e.g. -120.0)
+    pub fn nulls_below(&self, threshold_db: f32) -> bool {
+        self.is_bit_exact() || self.residual_peak_db() < threshold_db
+    }
+}
+
+/// Render the same input through two plugins and compare the results
+///
+/// Both paths are time-aligned using their measured latency (see
+/// [`detect_latency()`], searched up to [`NULL_TEST_MAX_LATENCY`] samples),
+/// then the difference of every output channel is measured. Handy for
+/// verifying that a wrapper (oversampling, a sandboxed path, a different
+/// format of the same product) is transparent.
+///
+/// # Arguments
+///
+/// * `a`, `b` - Initialized plugins with the same channel configuration
+/// * `input` - Planar input signal, one slice per input channel
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - Either plugin is not initialized
+/// - The channel configurations differ, or don't match `input`
+/// - Processing fails
+///
+/// # Examples
+///
+/// ```no_run
+/// # use rack::prelude::*;
+/// # fn example(mut a: impl PluginInstance, mut b: impl PluginInstance) -> Result<()> {
+/// let noise: Vec<f32> = (0..48000).map(|i| ((i * 7919) % 200) as f32 / 100.0 - 1.0).collect();
+/// let report = rack::analysis::null_test(&mut a, &mut b, &[&noise, &noise])?;
+/// println!("residual peak: {:.1} dBFS", report.residual_peak_db());
+/// # Ok(())
+/// # }
+/// ```
+pub fn null_test<A, B>(a: &mut A, b: &mut B, input: &[&[f32]]) -> Result<NullReport>
+where
+    A: PluginInstance + ?Sized,
+    B: PluginInstance + ?Sized,
+{
+    if a.input_channels() != b.input_channels() || a.output_channels() != b.output_channels() {
+        return Err(Error::Other(format!(
+            "Channel configurations differ: {}in/{}out vs {}in/{}out",
+            a.input_channels(),
+            a.output_channels(),
+            b.input_channels(),
+            b.output_channels()
+        )));
+    }
+
+    let latency_a = detect_latency(a, NULL_TEST_MAX_LATENCY)?;
+    let latency_b = detect_latency(b, NULL_TEST_MAX_LATENCY)?;
+
+    let frames = input.iter().map(|c| c.len()).max().unwrap_or(0);
+    let output_a = render(a, input, frames + latency_a)?;
+    let output_b = render(b, input, frames + latency_b)?;
+
+    let mut sum_squares = 0.0f64;
+    let mut peak = 0.0f32;
+    for (channel_a, channel_b) in output_a.iter().zip(&output_b) {
+        let aligned_a = &channel_a[latency_a..];
+        let aligned_b = &channel_b[latency_b..];
+        for (&x, &y) in aligned_a.iter().zip(aligned_b) {
+            let diff = x - y;
+            sum_squares += diff as f64 * diff as f64;
+            peak = peak.max(diff.abs());
+        }
+    }
+
+    let count = frames * output_a.len();
+    let residual_rms = if count > 0 {
+        (sum_squares / count as f64).sqrt() as f32
+    } else {
+        0.0
+    };
+
+    Ok(NullReport {
+        latency_a,
+        latency_b,
+        frames_compared: frames,
+        residual_rms,
+        residual_peak: peak,
+    })
+}
+
+/// Position of the largest absolute sample across all channels (earliest wins)
+fn peak_position(channels: &[Vec<f32>]) -> usize {
+    let mut peak = 0.0f32;
+    let mut position = 0;
+    for channel in channels {
+        for (i, &sample) in channel.iter().enumerate() {
+            let magnitude = sample.abs();
+            if magnitude > peak || (magnitude == peak && peak > 0.0 && i < position) {
+                peak = magnitude;
+                position = i;
+            }
+        }
+    }
+    position
+}
+
+/// Evaluate the discrete-time Fourier transform of `signal` at angular frequency `omega`
+fn dft_bin(signal: &[f32], omega: f64) -> (f64, f64) {
+    // Rotate a unit phasor sample by sample instead of calling sin/cos per sample
+    let (step_im, step_re) = (-omega).sin_cos();
+    let (mut rot_re, mut rot_im) = (1.0f64, 0.0f64);
+    let (mut re, mut im) = (0.0f64, 0.0f64);
+
+    for &x in signal {
+        re += x as f64 * rot_re;
+        im += x as f64 * rot_im;
+        let next_re = rot_re * step_re - rot_im * step_im;
+        rot_im = rot_re * step_im + rot_im * step_re;
+        rot_re = next_re;
+    }
+
+    (re, im)
+}
+
+/// Render an impulse of amplitude `level` through the plugin
+///
+/// Returns `length` frames for every output channel.
+pub(crate) fn render_impulse<P: PluginInstance + ?Sized>(
+    plugin: &mut P,
+    length: usize,
+    level: f32,
+) -> Result<Vec<Vec<f32>>> {
+    if !(level.is_finite() && level > 0.0) {
+        return Err(Error::Other(format!("Invalid impulse level: {}", level)));
+    }
+    let impulse = Generator::new(Signal::Impulse { level }, 1.0).render(1);
+    let input: Vec<&[f32]> = vec![&impulse; plugin.input_channels()];
+    render(plugin, &input, length)
+}
+
+/// Render `input` through the plugin, zero-padded to `length` frames
+///
+/// Returns `length` frames for every output channel.
+fn render<P: PluginInstance + ?Sized>(
+    plugin: &mut P,
+    input: &[&[f32]],
+    length: usize,
+) -> Result<Vec<Vec<f32>>> {
+    if !plugin.is_initialized() {
+        return Err(Error::NotInitialized);
+    }
+    if plugin.input_channels() == 0 || plugin.output_channels() == 0 {
+        return Err(Error::Other(
+            "Measurement requires a plugin with audio inputs and outputs".to_string(),
+        ));
+    }
+
+    plugin.reset()?;
+    let output = RenderJob::new(MEASUREMENT_BLOCK_SIZE).run(plugin, input, length)?;
+    plugin.reset()?;
+
+    Ok(output.channels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockPlugin;
+    use crate::Plain;
+
+    #[test]
+    fn test_flat_response_of_gain_plugin() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+        plugin.set_parameter_plain(0, Plain(-6.0)).unwrap();
+
+        let response = measure_frequency_response(&mut plugin, 48000.0, 32, 0.5).unwrap();
+
+        assert_eq!(response.frequencies.len(), 32);
+        assert!((response.frequencies[0] - 20.0).abs() < 1e-9);
+        assert!(response.frequencies[31] < 24000.0);
+        for (db, phase) in response.magnitude_db().iter().zip(&response.phase) {
+            assert!((db + 6.0).abs() < 0.01, "magnitude {} dB", db);
+            assert!(phase.abs() < 1e-3, "phase {}", phase);
+        }
+    }
+
+    #[test]
+    fn test_dft_bin_of_delayed_impulse() {
+        let mut signal = vec![0.0f32; 16];
+        signal[1] = 1.0;
+        // A one-sample delay has unit magnitude and phase of -omega
+        let (re, im) = dft_bin(&signal, 0.5);
+        assert!(((re * re + im * im).sqrt() - 1.0).abs() < 1e-9);
+        assert!((im.atan2(re) + 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_capture_impulse_response() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+        plugin.set_parameter_plain(0, Plain(-6.0)).unwrap();
+
+        let ir = capture_impulse_response(&mut plugin, 100, 0.5).unwrap();
+        assert_eq!(ir.channels.len(), 2);
+        assert_eq!(ir.channels[0].len(), 100);
+        assert!((ir.channels[0][0] - 0.501).abs() < 1e-3);
+        assert!(ir.channels[0][1..].iter().all(|&s| s == 0.0));
+        assert_eq!(ir.latency_samples, 0);
+    }
+
+    #[test]
+    fn test_detect_latency() {
+        let mut plugin = MockPlugin::new().with_latency(173);
+        plugin.initialize(48000.0, 512).unwrap();
+
+        assert_eq!(detect_latency(&mut plugin, 1000).unwrap(), 173);
+    }
+
+    fn test_signal(frames: usize) -> Vec<f32> {
+        Generator::new(Signal::sine(48000.0 * 0.05 / (2.0 * PI), 0.5), 48000.0).render(frames)
+    }
+
+    #[test]
+    fn test_null_test_identical_paths() {
+        let mut a = MockPlugin::new();
+        let mut b = MockPlugin::new();
+        a.initialize(48000.0, 512).unwrap();
+        b.initialize(48000.0, 512).unwrap();
+
+        let signal = test_signal(1000);
+        let report = null_test(&mut a, &mut b, &[&signal, &signal]).unwrap();
+
+        assert_eq!(report.frames_compared, 1000);
+        assert!(report.is_bit_exact());
+        assert!(report.nulls_below(-120.0));
+    }
+
+    #[test]
+    fn test_null_test_aligns_latency() {
+        let mut a = MockPlugin::new();
+        let mut b = MockPlugin::new().with_latency(100);
+        a.initialize(48000.0, 512).unwrap();
+        b.initialize(48000.0, 512).unwrap();
+
+        let signal = test_signal(1000);
+        let report = null_test(&mut a, &mut b, &[&signal, &signal]).unwrap();
+
+        assert_eq!(report.latency_a, 0);
+        assert_eq!(report.latency_b, 100);
+        assert!(report.is_bit_exact());
+    }
+
+    #[test]
+    fn test_null_test_reports_difference() {
+        let mut a = MockPlugin::new();
+        let mut b = MockPlugin::new();
+        a.initialize(48000.0, 512).unwrap();
+        b.initialize(48000.0, 512).unwrap();
+        b.set_parameter_plain(0, Plain(-6.0)).unwrap();
+
+        let signal = test_signal(1000);
+        let report = null_test(&mut a, &mut b, &[&signal, &signal]).unwrap();
+
+        assert!(!report.is_bit_exact());
+        assert!(!report.nulls_below(-60.0));
+        // Half the signal remains: peak residual is about 0.5 * 0.5
+        assert!((report.residual_peak - 0.25).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_requires_initialization() {
+        let mut plugin = MockPlugin::new();
+        let result = measure_frequency_response(&mut plugin, 48000.0, 8, 0.5);
+        assert!(matches!(result, Err(Error::NotInitialized)));
+    }
+
+    #[test]
+    fn test_rejects_invalid_level() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+        assert!(measure_frequency_response(&mut plugin, 48000.0, 8, 0.0).is_err());
+        assert!(measure_frequency_response(&mut plugin, 48000.0, 8, f32::NAN).is_err());
+    }
+}
@@ -0,0 +1,325 @@
+//! Async scanning and loading (the `async` feature)
+//!
+//! Scanning and loading call into plugin binaries and can block for seconds.
+//! [`AsyncScanner`] runs those calls on a [`BlockingPool`] of dedicated
+//! threads and hands back a [`Task`] to `.await`, so a GUI built on an async
+//! runtime doesn't have to wrap every call in its own `spawn_blocking`.
+//!
+//! Tasks are plain [`Future`]s and work with any executor; the crate doesn't
+//! depend on one.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rack::async_scan::{AsyncScanner, BlockingPool};
+//! use rack::unified::UnifiedScanner;
+//! use rack::prelude::*;
+//!
+//! # async fn example() -> Result<()> {
+//! let scanner = AsyncScanner::new(rack::clap::ClapScanner::new()?);
+//! let plugins = scanner.scan().await?;
+//! let mut plugin = scanner.load(&plugins[0]).await?;
+//! plugin.initialize(48000.0, 512)?;
+//!
+//! // Anything else, e.g. a scanner that isn't Sync, through `run()`
+//! let all = BlockingPool::shared().run(|| UnifiedScanner::new()?.scan()).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::guard::panic_message;
+use crate::scan::ScanFilter;
+use crate::{BoxedPlugin, Error, PluginInfo, PluginScanner, Result};
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::task::{Context, Poll, Waker};
+use std::thread::JoinHandle;
+
+/// Most threads the shared pool starts
+const SHARED_POOL_THREADS: usize = 4;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Threads that run blocking calls for [`Task`]s
+///
+/// Jobs run in the order they are submitted, on whichever thread is free.
+/// Dropping the pool lets the queued jobs finish, then stops the threads.
+pub struct BlockingPool {
+    queue: Option<mpsc::Sender<Job>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl BlockingPool {
+    /// Start a pool with `threads` threads (at least one)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a thread can't be started
+    pub fn new(threads: usize) -> Result<Self> {
+        let (queue, jobs) = mpsc::channel::<Job>();
+        let jobs = Arc::new(Mutex::new(jobs));
+        let threads = (0..threads.max(1))
+            .map(|index| {
+                let jobs = Arc::clone(&jobs);
+                std::thread::Builder::new()
+                    .name(format!("rack-blocking-{}", index))
+                    .spawn(move || loop {
+                        // The lock is released before the job runs
+                        let job = jobs.lock().unwrap_or_else(|e| e.into_inner()).recv();
+                        match job {
+                            Ok(job) => job(),
+                            Err(_) => break,
+                        }
+                    })
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        Ok(Self {
+            queue: Some(queue),
+            threads,
+        })
+    }
+
+    /// The pool shared by every [`AsyncScanner`] created with
+    /// [`AsyncScanner::new()`]
+    ///
+    /// Started on first use, with one thread per core up to four.
+    ///
+    /// # Panics
+    ///
+    /// Panics if its threads can't be started
+    pub fn shared() -> Arc<Self> {
+        static SHARED: OnceLock<Arc<BlockingPool>> = OnceLock::new();
+        Arc::clone(SHARED.get_or_init(|| {
+            let threads = std::thread::available_parallelism()
+                .map_or(1, |n| n.get())
+                .min(SHARED_POOL_THREADS);
+            Arc::new(Self::new(threads).expect("failed to start the blocking pool"))
+        }))
+    }
+
+    /// Run `job` on the pool
+    ///
+    /// A panic in `job` is caught and returned as [`Error::Panic`]. Dropping
+    /// the task doesn't cancel the job; its result is discarded.
+    pub fn run<T, F>(&self, job: F) -> Task<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Result<T> + Send + 'static,
+    {
+        let shared = Arc::new(Mutex::new(Shared {
+            result: None,
+            waker: None,
+        }));
+        let task = Task {
+            shared: Arc::clone(&shared),
+        };
+
+        let run = Box::new(move || {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(job))
+                .unwrap_or_else(|payload| Err(Error::Panic(panic_message(payload.as_ref()))));
+            let mut shared = shared.lock().unwrap_or_else(|e| e.into_inner());
+            shared.result = Some(result);
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        });
+        if let Some(Err(mpsc::SendError(run))) = self.queue.as_ref().map(|q| q.send(run)) {
+            // Only possible if every thread has died; run it here instead
+            run();
+        }
+        task
+    }
+
+    /// Number of threads in the pool
+    pub fn threads(&self) -> usize {
+        self.threads.len()
+    }
+}
+
+impl Drop for BlockingPool {
+    fn drop(&mut self) {
+        // Closing the queue stops each thread once it is empty
+        self.queue.take();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+struct Shared<T> {
+    result: Option<Result<T>>,
+    waker: Option<Waker>,
+}
+
+/// The result of a call running on a [`BlockingPool`]
+///
+/// Resolves when the call returns.
+#[must_use = "tasks do nothing useful unless awaited"]
+pub struct Task<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+impl<T> Future for Task<T> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        match shared.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// A scanner whose calls run on a [`BlockingPool`]
+///
+/// The scanner is shared with the pool, so it must be `Send + Sync`. Loaded
+/// plugins move to the task's owner; formats whose plugins must be created on
+/// a particular thread (e.g. the main thread) should be loaded with the
+/// blocking API instead.
+pub struct AsyncScanner<S> {
+    scanner: Arc<S>,
+    pool: Arc<BlockingPool>,
+}
+
+impl<S> AsyncScanner<S>
+where
+    S: PluginScanner + Send + Sync + 'static,
+    S::Plugin: 'static,
+{
+    /// Wrap `scanner`, running its calls on the [shared pool](BlockingPool::shared)
+    pub fn new(scanner: S) -> Self {
+        Self::with_pool(scanner, BlockingPool::shared())
+    }
+
+    /// Wrap `scanner`, running its calls on `pool`
+    pub fn with_pool(scanner: S, pool: Arc<BlockingPool>) -> Self {
+        Self {
+            scanner: Arc::new(scanner),
+            pool,
+        }
+    }
+
+    /// The wrapped scanner, for calls that don't need to be async
+    pub fn scanner(&self) -> &S {
+        &self.scanner
+    }
+
+    /// Async [`scan()`](PluginScanner::scan)
+    pub fn scan(&self) -> Task<Vec<PluginInfo>> {
+        self.run(|scanner| scanner.scan())
+    }
+
+    /// Async [`scan_filtered()`](PluginScanner::scan_filtered)
+    pub fn scan_filtered(&self, filter: &ScanFilter) -> Task<Vec<PluginInfo>> {
+        let filter = filter.clone();
+        self.run(move |scanner| scanner.scan_filtered(&filter))
+    }
+
+    /// Async [`scan_path()`](PluginScanner::scan_path)
+    pub fn scan_path(&self, path: impl Into<PathBuf>) -> Task<Vec<PluginInfo>> {
+        let path = path.into();
+        self.run(move |scanner| scanner.scan_path(&path))
+    }
+
+    /// Async [`load()`](PluginScanner::load)
+    pub fn load(&self, info: &PluginInfo) -> Task<S::Plugin> {
+        let info = info.clone();
+        self.run(move |scanner| scanner.load(&info))
+    }
+
+    /// Async [`load_boxed()`](PluginScanner::load_boxed)
+    pub fn load_boxed(&self, info: &PluginInfo) -> Task<BoxedPlugin> {
+        let info = info.clone();
+        self.run(move |scanner| scanner.load_boxed(&info))
+    }
+
+    /// Run any call on the scanner in the pool
+    pub fn run<T, F>(&self, call: F) -> Task<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&S) -> Result<T> + Send + 'static,
+    {
+        let scanner = Arc::clone(&self.scanner);
+        self.pool.run(move || call(&scanner))
+    }
+}
+
+impl<S> Clone for AsyncScanner<S> {
+    fn clone(&self) -> Self {
+        Self {
+            scanner: Arc::clone(&self.scanner),
+            pool: Arc::clone(&self.pool),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clap::{test_plugin, ClapScanner};
+    use crate::scan::ScannerConfig;
+    use crate::PluginInstance;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::task::Wake;
+
+    /// Minimal executor: parks the thread until woken
+    fn block_on<F: Future>(future: F) -> F::Output {
+        struct Unpark(std::thread::Thread, AtomicBool);
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.1.store(true, Ordering::SeqCst);
+                self.0.unpark();
+            }
+        }
+
+        let unpark = Arc::new(Unpark(std::thread::current(), AtomicBool::new(false)));
+        let waker = Waker::from(Arc::clone(&unpark));
+        let mut context = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+            while !unpark.1.swap(false, Ordering::SeqCst) {
+                std::thread::park();
+            }
+        }
+    }
+
+    #[test]
+    fn test_pool_runs_jobs_and_catches_panics() {
+        let pool = BlockingPool::new(2).unwrap();
+        assert_eq!(pool.threads(), 2);
+        let on = block_on(pool.run(|| Ok(std::thread::current().name().map(str::to_string))))
+            .unwrap()
+            .unwrap();
+        assert!(on.starts_with("rack-blocking-"));
+
+        let panicked = block_on(pool.run::<(), _>(|| panic!("scan blew up")));
+        assert!(matches!(panicked, Err(Error::Panic(message)) if message.contains("scan blew up")));
+    }
+
+    #[test]
+    fn test_async_scan_and_load() {
+        let dir = std::env::temp_dir().join(format!("rack-async-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let _gain = test_plugin::install(&dir.join("gain.clap"));
+
+        let config = ScannerConfig::new().path(&dir).skip_default_paths(true);
+        let scanner = AsyncScanner::new(ClapScanner::with_config(config).unwrap());
+        let plugins = block_on(scanner.scan()).unwrap();
+        assert_eq!(plugins.len(), 1);
+
+        let mut plugin = block_on(scanner.load_boxed(&plugins[0])).unwrap();
+        plugin.initialize(48000.0, 32).unwrap();
+        assert_eq!(plugin.output_channels(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -44,6 +44,7 @@ pub struct RackAUPluginInfo {
     pub unique_id: [c_char; 64],
     pub version: u32,
     pub plugin_type: RackAUPluginType,
+    pub component_flags: u32,
 }
 
 // Error codes
@@ -52,8 +53,65 @@ pub const RACK_AU_ERROR_GENERIC: c_int = -1;
 pub const RACK_AU_ERROR_NOT_FOUND: c_int = -2;
 pub const RACK_AU_ERROR_INVALID_PARAM: c_int = -3;
 pub const RACK_AU_ERROR_NOT_INITIALIZED: c_int = -4;
+pub const RACK_AU_ERROR_BUFFER_TOO_SMALL: c_int = -5;
 pub const RACK_AU_ERROR_AUDIO_UNIT: c_int = -1000;
 
+// String fields for rack_au_scanner_plugin_string
+pub const RACK_AU_FIELD_NAME: c_int = 0;
+pub const RACK_AU_FIELD_MANUFACTURER: c_int = 1;
+pub const RACK_AU_FIELD_PATH: c_int = 2;
+
+// Timecode flags for rack_au_plugin_set_timecode
+pub const RACK_AU_FRAME_RATE_PULL_DOWN: u32 = 1 << 0;
+pub const RACK_AU_FRAME_RATE_DROP: u32 = 1 << 1;
+
+// AudioComponentFlags (from AudioComponent.h)
+pub const AU_COMPONENT_FLAG_SANDBOX_SAFE: u32 = 2;
+pub const AU_COMPONENT_FLAG_IS_V3_AUDIO_UNIT: u32 = 4;
+pub const AU_COMPONENT_FLAG_REQUIRES_ASYNC_INSTANTIATION: u32 = 8;
+pub const AU_COMPONENT_FLAG_CAN_LOAD_IN_PROCESS: u32 = 0x10;
+
+// AudioUnitParameterOptions flags (from AudioUnitProperties.h)
+pub const AU_PARAMETER_FLAG_DISPLAY_SQUARE_ROOT: u32 = 1 << 16;
+pub const AU_PARAMETER_FLAG_DISPLAY_SQUARED: u32 = 2 << 16;
+pub const AU_PARAMETER_FLAG_DISPLAY_CUBED: u32 = 3 << 16;
+pub const AU_PARAMETER_FLAG_DISPLAY_CUBE_ROOT: u32 = 4 << 16;
+pub const AU_PARAMETER_FLAG_DISPLAY_EXPONENTIAL: u32 = 5 << 16;
+pub const AU_PARAMETER_FLAG_DISPLAY_LOGARITHMIC: u32 = 1 << 22;
+pub const AU_PARAMETER_FLAG_DISPLAY_MASK: u32 = (7 << 16) | (1 << 22);
+pub const AU_PARAMETER_FLAG_METER_READ_ONLY: u32 = 1 << 15;
+pub const AU_PARAMETER_FLAG_EXPERT_MODE: u32 = 1 << 26;
+
+// Runtime changes (RackAUChangeEvent::kind)
+pub const RACK_AU_CHANGE_LATENCY: i32 = 0;
+pub const RACK_AU_CHANGE_TAIL_TIME: i32 = 1;
+pub const RACK_AU_CHANGE_PARAMETER_LIST: i32 = 2;
+pub const RACK_AU_CHANGE_STREAM_FORMAT: i32 = 3;
+pub const RACK_AU_CHANGE_BEGIN_EDIT: i32 = 4;
+pub const RACK_AU_CHANGE_PARAMETER: i32 = 5;
+pub const RACK_AU_CHANGE_END_EDIT: i32 = 6;
+pub const RACK_AU_CHANGE_STATE: i32 = 7;
+
+/// A runtime change reported by an AudioUnit property or parameter listener
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RackAUChangeEvent {
+    pub kind: i32,
+    pub seconds: f64,
+    pub samples: u32,
+    /// Parameter index for edits, -1 if not in the parameter cache
+    pub param_index: i32,
+    /// New normalized value (`RACK_AU_CHANGE_PARAMETER`)
+    pub value: f32,
+}
+
+/// Callback receiving runtime changes
+///
+/// Property changes are invoked on whatever thread changed the property,
+/// often the main thread; parameter edits on the main thread's run loop.
+pub type RackAUChangeCallback =
+    extern "C" fn(user_data: *mut std::ffi::c_void, event: *const RackAUChangeEvent);
+
 extern "C" {
     // ============================================================================
     // Scanner API
@@ -112,6 +170,34 @@ extern "C" {
         max_plugins: usize,
     ) -> c_int;
 
+    /// Get a string field of a plugin from the last filling scan, without truncation
+    ///
+    /// Length-negotiated: with a NULL `buffer`, `*size` receives the required
+    /// size including the null terminator. If the buffer is too small, nothing
+    /// is copied and `RACK_AU_ERROR_BUFFER_TOO_SMALL` is returned with the required
+    /// size in `*size`.
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `scanner` must be a valid pointer returned by `rack_au_scanner_new`
+    /// - `index` must be less than the number of plugins filled in by the last scan
+    /// - `field` must be one of the `RACK_AU_FIELD_*` constants
+    /// - `size` must be a valid pointer to a usize
+    /// - `buffer` can be NULL to query the required size, or must point to a
+    ///   buffer of at least `*size` bytes
+    pub fn rack_au_scanner_plugin_string(
+        scanner: *mut RackAUScanner,
+        index: usize,
+        field: c_int,
+        buffer: *mut c_char,
+        size: *mut usize,
+    ) -> c_int;
+
     // ============================================================================
     // Plugin Instance API (for future phases)
     // ============================================================================
@@ -126,6 +212,23 @@ extern "C" {
     /// - Returned pointer must be freed with `rack_au_plugin_free`
     pub fn rack_au_plugin_new(unique_id: *const c_char) -> *mut RackAUPlugin;
 
+    /// Identify the host to the plugin (`kAudioUnitProperty_AUHostIdentifier`)
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code if the plugin doesn't support the property
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_au_plugin_new`
+    /// - `name` must be a valid null-terminated UTF-8 C string
+    pub fn rack_au_plugin_set_host_info(
+        plugin: *mut RackAUPlugin,
+        name: *const c_char,
+        version: u32,
+    ) -> c_int;
+
     /// Free plugin instance
     ///
     /// # Safety
@@ -215,6 +318,35 @@ extern "C" {
     /// - Should be called after `rack_au_plugin_initialize`
     pub fn rack_au_plugin_get_output_channels(plugin: *mut RackAUPlugin) -> c_int;
 
+    /// Get processing latency in samples
+    ///
+    /// Reads `kAudioUnitProperty_Latency` (seconds) and converts it at the
+    /// initialized sample rate.
+    ///
+    /// # Returns
+    ///
+    /// - Latency in samples (>= 0)
+    /// - 0 if not initialized or the plugin reports none
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_au_plugin_new`
+    /// - Should be called after `rack_au_plugin_initialize`
+    pub fn rack_au_plugin_get_latency(plugin: *mut RackAUPlugin) -> c_int;
+
+    /// Get tail time in seconds (`kAudioUnitProperty_TailTime`)
+    ///
+    /// # Returns
+    ///
+    /// - Tail time in seconds (>= 0)
+    /// - 0 if not initialized or the plugin has no tail
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_au_plugin_new`
+    /// - Should be called after `rack_au_plugin_initialize`
+    pub fn rack_au_plugin_get_tail_time(plugin: *mut RackAUPlugin) -> f64;
+
     /// Process audio through the plugin (planar format)
     ///
     /// Uses planar (non-interleaved) audio format - one buffer per channel.
@@ -249,6 +381,75 @@ extern "C" {
         frames: u32,
     ) -> c_int;
 
+    /// Set the timeline position of the next processed sample
+    ///
+    /// `process` reports the position to the plugin and advances it by the
+    /// number of frames processed.
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code if `position` is negative
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer
+    /// - Must not be called concurrently with `rack_au_plugin_process`
+    pub fn rack_au_plugin_set_sample_position(plugin: *mut RackAUPlugin, position: i64) -> c_int;
+
+    /// Set the transport reported to the plugin through its host callbacks
+    ///
+    /// Reported during `process` until it is set again. Positions are in
+    /// quarter notes; the cycle is only reported if `cycling` is non-zero.
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code if the tempo or time signature is invalid
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer
+    /// - Must not be called concurrently with `rack_au_plugin_process`
+    pub fn rack_au_plugin_set_transport(
+        plugin: *mut RackAUPlugin,
+        playing: c_int,
+        tempo: f64,
+        numerator: i32,
+        denominator: i32,
+        beat: f64,
+        bar_start_beat: f64,
+        cycling: c_int,
+        cycle_start_beat: f64,
+        cycle_end_beat: f64,
+    ) -> c_int;
+
+    /// Set the SMPTE time reported to the plugin in the render timestamp
+    ///
+    /// Reported during `process` until it is set again. `flags` is a
+    /// combination of the `RACK_AU_FRAME_RATE_*` flags; `subframes` are in
+    /// 1/80 frames.
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code if the frame rate or timecode is invalid
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer
+    /// - Must not be called concurrently with `rack_au_plugin_process`
+    pub fn rack_au_plugin_set_timecode(
+        plugin: *mut RackAUPlugin,
+        frames_per_second: u32,
+        flags: u32,
+        hours: u32,
+        minutes: u32,
+        seconds: u32,
+        frames: u32,
+        subframes: u32,
+    ) -> c_int;
+
     /// Get parameter count
     ///
     /// # Returns
@@ -297,6 +498,28 @@ extern "C" {
         value: f32,
     ) -> c_int;
 
+    /// Schedule a parameter change at a sample offset within the next
+    /// processed block
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_au_plugin_new`
+    /// - `index` must be less than parameter count
+    /// - `value` should be in range 0.0-1.0 (values outside may be clamped)
+    /// - `sample_offset` should be less than the next block's frame count
+    /// - Must not be called concurrently with process()
+    pub fn rack_au_plugin_set_parameter_at(
+        plugin: *mut RackAUPlugin,
+        index: u32,
+        value: f32,
+        sample_offset: u32,
+    ) -> c_int;
+
     /// Get parameter info (name, min, max, default, unit)
     ///
     /// # Returns
@@ -308,12 +531,12 @@ extern "C" {
     ///
     /// - `plugin` must be a valid pointer returned by `rack_au_plugin_new`
     /// - `index` must be less than parameter count
-    /// - `name` must point to a buffer with at least `name_size` bytes
     /// - `min`, `max`, `default_value` must be valid pointers to f32
-    /// - `unit` can be NULL, or must point to a buffer with at least `unit_size` bytes
-    /// - `name` and `unit` (if not NULL) will be null-terminated
-    /// - `name_size` should be at least 256 bytes for typical parameter names
-    /// - `unit_size` should be at least 32 bytes for typical unit strings
+    /// - `name` and `unit` can be NULL, or must point to buffers with at least
+    ///   `name_size` and `unit_size` bytes
+    /// - `name` and `unit` (if not NULL) will be null-terminated and may be
+    ///   truncated; use [`rack_au_plugin_parameter_name`] and
+    ///   [`rack_au_plugin_parameter_unit`] to read them in full
     pub fn rack_au_plugin_parameter_info(
         plugin: *mut RackAUPlugin,
         index: u32,
@@ -326,6 +549,71 @@ extern "C" {
         unit_size: usize,
     ) -> c_int;
 
+    /// Get parameter flags (raw `AudioUnitParameterOptions`)
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success (flags written to `flags` pointer)
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_au_plugin_new`
+    /// - `index` must be less than parameter count
+    /// - `flags` must be a valid pointer to a u32
+    pub fn rack_au_plugin_parameter_flags(
+        plugin: *mut RackAUPlugin,
+        index: u32,
+        flags: *mut u32,
+    ) -> c_int;
+
+    /// Get a parameter name without truncation
+    ///
+    /// Length-negotiated, like [`rack_au_scanner_plugin_string`].
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_au_plugin_new`
+    /// - `index` must be less than parameter count
+    /// - `size` must be a valid pointer to a usize
+    /// - `buffer` can be NULL to query the required size, or must point to a
+    ///   buffer of at least `*size` bytes
+    pub fn rack_au_plugin_parameter_name(
+        plugin: *mut RackAUPlugin,
+        index: u32,
+        buffer: *mut c_char,
+        size: *mut usize,
+    ) -> c_int;
+
+    /// Get a parameter unit without truncation
+    ///
+    /// Length-negotiated, like [`rack_au_scanner_plugin_string`]. Custom
+    /// units report the plugin's own unit name.
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_au_plugin_new`
+    /// - `index` must be less than parameter count
+    /// - `size` must be a valid pointer to a usize
+    /// - `buffer` can be NULL to query the required size, or must point to a
+    ///   buffer of at least `*size` bytes
+    pub fn rack_au_plugin_parameter_unit(
+        plugin: *mut RackAUPlugin,
+        index: u32,
+        buffer: *mut c_char,
+        size: *mut usize,
+    ) -> c_int;
+
     // ============================================================================
     // Preset Management API
     // ============================================================================
@@ -381,6 +669,74 @@ extern "C" {
     /// - `preset_number` should be a valid preset number from get_preset_info
     pub fn rack_au_plugin_load_preset(plugin: *mut RackAUPlugin, preset_number: i32) -> c_int;
 
+    /// Get a factory preset name without truncation
+    ///
+    /// Length-negotiated, like [`rack_au_scanner_plugin_string`].
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_au_plugin_new`
+    /// - `index` must be less than preset count
+    /// - `size` must be a valid pointer to a usize
+    /// - `buffer` can be NULL to query the required size, or must point to a
+    ///   buffer of at least `*size` bytes
+    pub fn rack_au_plugin_preset_name(
+        plugin: *mut RackAUPlugin,
+        index: u32,
+        buffer: *mut c_char,
+        size: *mut usize,
+    ) -> c_int;
+
+    /// Get the currently loaded preset
+    ///
+    /// # Returns
+    ///
+    /// - 1 if the plugin reports a current preset (`name` and `preset_number` are filled in)
+    /// - 0 if it doesn't
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_au_plugin_new`
+    /// - Plugin must be initialized
+    /// - `name` must point to a buffer of at least `name_size` bytes
+    /// - `preset_number` must be a valid pointer
+    pub fn rack_au_plugin_get_current_preset(
+        plugin: *mut RackAUPlugin,
+        name: *mut c_char,
+        name_size: usize,
+        preset_number: *mut i32,
+    ) -> c_int;
+
+    /// Get the name of the current preset without truncation
+    ///
+    /// Length-negotiated, like [`rack_au_scanner_plugin_string`]. `*size` is
+    /// set to 0 if there is no current preset.
+    ///
+    /// # Returns
+    ///
+    /// - 1 if the plugin reports a current preset
+    /// - 0 if it doesn't
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_au_plugin_new`
+    /// - Plugin must be initialized
+    /// - `size` must be a valid pointer to a usize
+    /// - `buffer` can be NULL to query the required size, or must point to a
+    ///   buffer of at least `*size` bytes
+    pub fn rack_au_plugin_current_preset_name(
+        plugin: *mut RackAUPlugin,
+        buffer: *mut c_char,
+        size: *mut usize,
+    ) -> c_int;
+
     /// Get plugin state size (for allocation)
     ///
     /// # Returns
@@ -436,6 +792,114 @@ extern "C" {
         size: usize,
     ) -> c_int;
 
+    // ============================================================================
+    // Render Settings API
+    // ============================================================================
+
+    /// Change the largest block process() accepts (MaximumFramesPerSlice)
+    ///
+    /// Uninitializes and re-initializes the plugin if it is initialized.
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure (the plugin is left uninitialized if
+    ///   re-initialization failed)
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_au_plugin_new`
+    /// - Must not be called concurrently with process()
+    pub fn rack_au_plugin_set_max_frames(plugin: *mut RackAUPlugin, max_frames: u32) -> c_int;
+
+    /// Get the render quality (0-127)
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_au_plugin_new`
+    /// - `quality` must be a valid pointer
+    pub fn rack_au_plugin_get_render_quality(plugin: *mut RackAUPlugin, quality: *mut u32) -> c_int;
+
+    /// Set the render quality (0-127)
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_au_plugin_new`
+    pub fn rack_au_plugin_set_render_quality(plugin: *mut RackAUPlugin, quality: u32) -> c_int;
+
+    /// Tell the plugin whether it renders offline
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_au_plugin_new`
+    /// - Must not be called concurrently with process()
+    pub fn rack_au_plugin_set_offline_render(plugin: *mut RackAUPlugin, offline: c_int) -> c_int;
+
+    /// Switch the plugin's metering on or off (kAudioUnitProperty_MeteringMode)
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_au_plugin_new`
+    pub fn rack_au_plugin_set_metering_mode(plugin: *mut RackAUPlugin, enabled: c_int) -> c_int;
+
+    /// Bypass the plugin's processing (kAudioUnitProperty_BypassEffect)
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure (e.g. the plugin doesn't support it)
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_au_plugin_new`
+    pub fn rack_au_plugin_set_bypass(plugin: *mut RackAUPlugin, bypassed: c_int) -> c_int;
+
+    // ============================================================================
+    // Change Notification API
+    // ============================================================================
+
+    /// Set the callback receiving runtime changes
+    ///
+    /// Pass `None` to remove it. Once this returns, the previous callback is
+    /// no longer running or invoked.
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_au_plugin_new`
+    /// - `user_data` must stay valid until the callback is replaced or the
+    ///   plugin is freed
+    /// - Must not be called from inside the callback
+    pub fn rack_au_plugin_set_change_callback(
+        plugin: *mut RackAUPlugin,
+        callback: Option<RackAUChangeCallback>,
+        user_data: *mut std::ffi::c_void,
+    ) -> c_int;
+
     // ============================================================================
     // MIDI API
     // ============================================================================
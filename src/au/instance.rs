@@ -1,11 +1,19 @@
-use crate::{Error, MidiEvent, MidiEventKind, ParameterInfo, PluginInfo, PluginInstance, PresetInfo, Result};
+use crate::bypass::HostBypass;
+use crate::events::{self, HostEvent};
+use crate::host;
+use crate::meter::MeterReading;
+use crate::node::BlockContext;
+use crate::quirks::{self, Quirk, Quirks};
+use crate::{CurrentPreset, Error, MidiEvent, MidiEventKind, Normalized, ParameterChange, ParameterEdit, ParameterInfo, PluginInfo, PluginInstance, PresetInfo, Result};
 use smallvec::SmallVec;
 use std::ffi::CString;
 use std::marker::PhantomData;
 use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
 
 use super::ffi;
-use super::util::map_error;
+use super::util::{map_error, negotiated_name, parameter_curve_from_flags, parameter_visibility_from_flags};
 
 /// An instantiated AudioUnit plugin
 ///
@@ -24,6 +32,26 @@ pub struct AudioUnitPlugin {
     // Channel configuration (queried from AudioUnit during initialize)
     input_channels: usize,
     output_channels: usize,
+    // Max frames per process() call (set during initialize)
+    max_block_size: usize,
+    // Sample rate (set during initialize)
+    sample_rate: f64,
+    // Timeline position of the next processed sample (mirrors the C++ side)
+    sample_position: u64,
+    // Quirks looked up when the plugin was loaded
+    quirks: Quirks,
+    // Target of the change callback (boxed for a stable address), shared
+    // with C++ through the pointer handed to it
+    changes: Box<ChangeContext>,
+    // Whether kAudioUnitProperty_BypassEffect is set; plugins that don't
+    // support it are bypassed by the host instead
+    native_bypassed: bool,
+    bypass: HostBypass,
+    // Whether create_gui() has been called
+    gui_requested: bool,
+    // State restored before the GUI existed, applied again once it does
+    // (Quirk::GuiBeforeStateRestore)
+    pending_gui_state: Option<Vec<u8>>,
     // PhantomData<*const ()> makes this type !Sync while keeping it Send
     _not_sync: PhantomData<*const ()>,
 }
@@ -53,6 +81,25 @@ impl AudioUnitPlugin {
                 )));
             }
 
+            // Optional for AudioUnits; most ignore it
+            let host = host::host_info();
+            if let Ok(name) = CString::new(host.name.as_str()) {
+                ffi::rack_au_plugin_set_host_info(ptr, name.as_ptr(), host.version_number());
+            }
+
+            // Report runtime changes (latency, tail, parameters, formats)
+            // through the host event sink like the VST3 backend does
+            let changes = Box::new(ChangeContext {
+                info: info.clone(),
+                listener: RwLock::new(None),
+                state_dirty: AtomicBool::new(false),
+            });
+            ffi::rack_au_plugin_set_change_callback(
+                ptr,
+                Some(property_changed),
+                &*changes as *const ChangeContext as *mut std::ffi::c_void,
+            );
+
             Ok(Self {
                 inner: NonNull::new_unchecked(ptr),
                 info: info.clone(),
@@ -60,10 +107,121 @@ impl AudioUnitPlugin {
                 output_ptrs: Vec::new(),
                 input_channels: 0,
                 output_channels: 0,
+                max_block_size: 0,
+                sample_rate: 0.0,
+                sample_position: 0,
+                quirks: quirks::lookup(info),
+                changes,
+                native_bypassed: false,
+                bypass: HostBypass::new(),
+                gui_requested: false,
+                pending_gui_state: None,
                 _not_sync: PhantomData,
             })
         }
     }
+
+    /// Receive the edits the user makes in the plugin's own view
+    ///
+    /// The listener is called for every [`ParameterEdit`] the AudioUnit
+    /// reports through its parameter listeners, on the main thread (edits
+    /// only arrive while its run loop runs, as it does while a view is open),
+    /// so it should hand edits off rather than block. Edits made with
+    /// [`set_parameter()`](PluginInstance::set_parameter) aren't reported.
+    /// Replaces any previous listener.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use rack::au::AudioUnitPlugin;
+    /// use rack::ParameterEdit;
+    ///
+    /// # fn example(plugin: &mut AudioUnitPlugin) {
+    /// plugin.set_parameter_listener(|edit| match edit {
+    ///     ParameterEdit::Begin { index } => println!("touch {}", index),
+    ///     ParameterEdit::Change { index, value } => println!("{} = {}", index, value.value()),
+    ///     ParameterEdit::End { index } => println!("release {}", index),
+    /// });
+    /// # }
+    /// ```
+    pub fn set_parameter_listener<F>(&mut self, listener: F)
+    where
+        F: Fn(ParameterEdit) + Send + Sync + 'static,
+    {
+        *self.changes.listener.write().unwrap_or_else(|e| e.into_inner()) =
+            Some(Box::new(listener));
+    }
+
+    /// Stop receiving view edits
+    pub fn clear_parameter_listener(&mut self) {
+        *self.changes.listener.write().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+/// Listener installed with [`AudioUnitPlugin::set_parameter_listener()`]
+type ParameterListener = Box<dyn Fn(ParameterEdit) + Send + Sync>;
+
+/// What the change callback needs, shared with the C++ side
+struct ChangeContext {
+    info: PluginInfo,
+    listener: RwLock<Option<ParameterListener>>,
+    // Set by ClassInfo/PresentPreset notifications, cleared by take_state_dirty()
+    state_dirty: AtomicBool,
+}
+
+impl ChangeContext {
+    fn handle(&self, event: &ffi::RackAUChangeEvent) {
+        let info = &self.info;
+        match event.kind {
+            ffi::RACK_AU_CHANGE_BEGIN_EDIT
+            | ffi::RACK_AU_CHANGE_PARAMETER
+            | ffi::RACK_AU_CHANGE_END_EDIT => self.edit(event),
+            ffi::RACK_AU_CHANGE_LATENCY => events::emit(HostEvent::LatencyChanged {
+                info,
+                samples: event.samples as usize,
+            }),
+            ffi::RACK_AU_CHANGE_TAIL_TIME => events::emit(HostEvent::TailChanged {
+                info,
+                seconds: event.seconds,
+            }),
+            ffi::RACK_AU_CHANGE_PARAMETER_LIST => {
+                events::emit(HostEvent::ParametersChanged { info })
+            }
+            ffi::RACK_AU_CHANGE_STREAM_FORMAT => events::emit(HostEvent::IoChanged { info }),
+            ffi::RACK_AU_CHANGE_STATE => self.state_dirty.store(true, Ordering::Release),
+            _ => {}
+        }
+    }
+
+    fn edit(&self, event: &ffi::RackAUChangeEvent) {
+        // Parameters missing from the cache can't be addressed by index
+        let Ok(index) = usize::try_from(event.param_index) else {
+            return;
+        };
+        let edit = match event.kind {
+            ffi::RACK_AU_CHANGE_BEGIN_EDIT => ParameterEdit::Begin { index },
+            ffi::RACK_AU_CHANGE_PARAMETER => ParameterEdit::Change {
+                index,
+                value: Normalized::new(event.value),
+            },
+            _ => ParameterEdit::End { index },
+        };
+
+        let listener = self.listener.read().unwrap_or_else(|e| e.into_inner());
+        if let Some(listener) = listener.as_ref() {
+            listener(edit);
+        }
+    }
+}
+
+/// Change callback for `rack_au_plugin_set_change_callback`
+extern "C" fn property_changed(user_data: *mut std::ffi::c_void, event: *const ffi::RackAUChangeEvent) {
+    // Safety: user_data is the plugin's boxed ChangeContext, which outlives
+    // the callback registration, and event is valid for the call
+    let (context, event) = unsafe { (&*(user_data as *const ChangeContext), &*event) };
+
+    // Don't unwind into the AudioUnit (an event sink may panic)
+    let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| context.handle(event)));
 }
 
 impl PluginInstance for AudioUnitPlugin {
@@ -89,6 +247,8 @@ impl PluginInstance for AudioUnitPlugin {
 
             self.input_channels = input_channels as usize;
             self.output_channels = output_channels as usize;
+            self.max_block_size = max_block_size;
+            self.sample_rate = sample_rate;
 
             // Pre-allocate pointer arrays for zero-allocation process() calls
             // Reserve capacity to avoid reallocation even if channel counts are unusual
@@ -99,8 +259,16 @@ impl PluginInstance for AudioUnitPlugin {
             self.input_ptrs.resize(self.input_channels, std::ptr::null());
             self.output_ptrs.resize(self.output_channels, std::ptr::null_mut());
 
-            Ok(())
+            // Plugins only update their meters while metering is on. Not all
+            // of them support the property; their meters update regardless.
+            if !self.meter_parameters().is_empty() {
+                ffi::rack_au_plugin_set_metering_mode(self.inner.as_ptr(), 1);
+            }
         }
+
+        self.bypass
+            .prepare(sample_rate, self.output_channels, self.latency_samples());
+        Ok(())
     }
 
     fn reset(&mut self) -> Result<()> {
@@ -125,6 +293,14 @@ impl PluginInstance for AudioUnitPlugin {
             return Err(Error::NotInitialized);
         }
 
+        // Reject oversized blocks here rather than passing them to the plugin
+        if num_frames > self.max_block_size {
+            return Err(Error::BlockTooLarge {
+                max: self.max_block_size,
+                got: num_frames,
+            });
+        }
+
         // Validate channel counts match plugin configuration
         if inputs.len() != self.input_channels {
             return Err(Error::Other(format!(
@@ -139,6 +315,13 @@ impl PluginInstance for AudioUnitPlugin {
             )));
         }
 
+        // Zero-frame blocks have nothing to render. Parameter changes and MIDI
+        // events are delivered to the AudioUnit as they are made, so there is
+        // nothing to flush either; queued MIDI plays in the next non-empty block.
+        if num_frames == 0 {
+            return Ok(());
+        }
+
         // Validate inputs (channel counts are now guaranteed to be correct)
         if inputs.is_empty() || outputs.is_empty() {
             return Err(Error::Other("Empty input or output channels".to_string()));
@@ -173,6 +356,9 @@ impl PluginInstance for AudioUnitPlugin {
             self.input_ptrs[i] = input_ch.as_ptr();
         }
         for (i, output_ch) in outputs.iter_mut().enumerate() {
+            if self.quirks.contains(Quirk::ClearOutputsBeforeProcess) {
+                output_ch[..num_frames].fill(0.0);
+            }
             self.output_ptrs[i] = output_ch.as_mut_ptr();
         }
 
@@ -189,9 +375,124 @@ impl PluginInstance for AudioUnitPlugin {
             if result != ffi::RACK_AU_OK {
                 return Err(map_error(result));
             }
+        }
+        self.bypass.process(inputs, outputs, num_frames);
 
-            Ok(())
+        self.sample_position += num_frames as u64;
+        Ok(())
+    }
+
+    /// Process a block, reporting the context's transport to the plugin
+    /// through its host callbacks and its timecode in the render timestamp
+    fn process_with_context(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        context: &BlockContext,
+    ) -> Result<()> {
+        if context.sample_position != self.sample_position {
+            self.set_sample_position(context.sample_position)?;
+        }
+
+        let signature = context.time_signature;
+        let cycle = context.loop_range;
+        unsafe {
+            let result = ffi::rack_au_plugin_set_transport(
+                self.inner.as_ptr(),
+                context.playing as i32,
+                context.tempo,
+                signature.numerator as i32,
+                signature.denominator as i32,
+                context.beat,
+                context.bar_start,
+                cycle.is_some() as i32,
+                cycle.map_or(0.0, |range| range.start),
+                cycle.map_or(0.0, |range| range.end),
+            );
+            if result != ffi::RACK_AU_OK {
+                return Err(map_error(result));
+            }
+        }
+
+        let frame_rate = context.frame_rate;
+        let timecode = context.timecode();
+        let mut flags = 0;
+        if frame_rate.is_pull_down() {
+            flags |= ffi::RACK_AU_FRAME_RATE_PULL_DOWN;
+        }
+        if frame_rate.is_drop_frame() {
+            flags |= ffi::RACK_AU_FRAME_RATE_DROP;
+        }
+        unsafe {
+            let result = ffi::rack_au_plugin_set_timecode(
+                self.inner.as_ptr(),
+                frame_rate.nominal(),
+                flags,
+                timecode.hours,
+                timecode.minutes,
+                timecode.seconds,
+                timecode.frames,
+                timecode.subframes,
+            );
+            if result != ffi::RACK_AU_OK {
+                return Err(map_error(result));
+            }
+        }
+
+        self.process(inputs, outputs, context.num_frames)
+    }
+
+    fn process_with_events(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        changes: &[ParameterChange],
+        midi: &[MidiEvent],
+    ) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        // Scheduled within the next block, at offsets the plugin handles itself
+        let last = num_frames.saturating_sub(1) as u32;
+        for change in changes {
+            unsafe {
+                let result = ffi::rack_au_plugin_set_parameter_at(
+                    self.inner.as_ptr(),
+                    change.index as u32,
+                    change.value,
+                    change.sample_offset.min(last),
+                );
+                if result != ffi::RACK_AU_OK {
+                    return Err(map_error(result));
+                }
+            }
+        }
+        if !midi.is_empty() {
+            self.send_midi(midi)?;
+        }
+
+        self.process(inputs, outputs, num_frames)
+    }
+
+    fn sample_position(&self) -> u64 {
+        self.sample_position
+    }
+
+    fn set_sample_position(&mut self, position: u64) -> Result<()> {
+        let raw = i64::try_from(position)
+            .map_err(|_| Error::Other(format!("Sample position {} out of range", position)))?;
+
+        unsafe {
+            let result = ffi::rack_au_plugin_set_sample_position(self.inner.as_ptr(), raw);
+            if result != ffi::RACK_AU_OK {
+                return Err(map_error(result));
+            }
         }
+
+        self.sample_position = position;
+        Ok(())
     }
 
     fn parameter_count(&self) -> usize {
@@ -211,8 +512,7 @@ impl PluginInstance for AudioUnitPlugin {
         }
 
         unsafe {
-            let mut name = vec![0i8; 256];
-            let mut unit = vec![0i8; 32];
+            // The name and unit are read below without truncation
             let mut min = 0.0f32;
             let mut max = 0.0f32;
             let mut default_value = 0.0f32;
@@ -220,32 +520,34 @@ impl PluginInstance for AudioUnitPlugin {
             let result = ffi::rack_au_plugin_parameter_info(
                 self.inner.as_ptr(),
                 index as u32,
-                name.as_mut_ptr(),
-                name.len(),
+                std::ptr::null_mut(),
+                0,
                 &mut min,
                 &mut max,
                 &mut default_value,
-                unit.as_mut_ptr(),
-                unit.len(),
+                std::ptr::null_mut(),
+                0,
             );
 
             if result != ffi::RACK_AU_OK {
                 return Err(map_error(result));
             }
 
-            // Convert name to String
-            let name_cstr = std::ffi::CStr::from_ptr(name.as_ptr());
-            let name_str = name_cstr
-                .to_str()
-                .map_err(|e| Error::Other(format!("Invalid UTF-8 in parameter name: {}", e)))?
-                .to_string();
+            let plugin = self.inner.as_ptr();
+            let (_, name_str) = negotiated_name("parameter name", |buffer, size| {
+                ffi::rack_au_plugin_parameter_name(plugin, index as u32, buffer, size)
+            })?;
 
-            // Convert unit to String
-            let unit_cstr = std::ffi::CStr::from_ptr(unit.as_ptr());
-            let unit_str = unit_cstr
-                .to_str()
-                .map_err(|e| Error::Other(format!("Invalid UTF-8 in parameter unit: {}", e)))?
-                .to_string();
+            let (_, unit_str) = negotiated_name("parameter unit", |buffer, size| {
+                ffi::rack_au_plugin_parameter_unit(plugin, index as u32, buffer, size)
+            })?;
+
+            let mut flags = 0u32;
+            let result =
+                ffi::rack_au_plugin_parameter_flags(self.inner.as_ptr(), index as u32, &mut flags);
+            if result != ffi::RACK_AU_OK {
+                return Err(map_error(result));
+            }
 
             Ok(ParameterInfo {
                 index,
@@ -254,6 +556,8 @@ impl PluginInstance for AudioUnitPlugin {
                 max,
                 default: default_value,
                 unit: unit_str,
+                curve: parameter_curve_from_flags(flags),
+                visibility: parameter_visibility_from_flags(flags),
             })
         }
     }
@@ -409,7 +713,8 @@ impl PluginInstance for AudioUnitPlugin {
         }
 
         unsafe {
-            let mut name = vec![0i8; 256];
+            // The name is read below without truncation
+            let mut name = [0i8; 1];
             let mut preset_number: i32 = 0;
 
             let result = ffi::rack_au_plugin_get_preset_info(
@@ -424,12 +729,10 @@ impl PluginInstance for AudioUnitPlugin {
                 return Err(map_error(result));
             }
 
-            // Convert name to String
-            let name_cstr = std::ffi::CStr::from_ptr(name.as_ptr());
-            let name_str = name_cstr
-                .to_str()
-                .map_err(|e| Error::Other(format!("Invalid UTF-8 in preset name: {}", e)))?
-                .to_string();
+            let plugin = self.inner.as_ptr();
+            let (_, name_str) = negotiated_name("preset name", |buffer, size| {
+                ffi::rack_au_plugin_preset_name(plugin, index as u32, buffer, size)
+            })?;
 
             Ok(PresetInfo {
                 index,
@@ -455,6 +758,46 @@ impl PluginInstance for AudioUnitPlugin {
         }
     }
 
+    fn current_preset(&self) -> Result<Option<CurrentPreset>> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        unsafe {
+            // The name is read below without truncation
+            let mut name = [0i8; 1];
+            let mut preset_number: i32 = 0;
+
+            let result = ffi::rack_au_plugin_get_current_preset(
+                self.inner.as_ptr(),
+                name.as_mut_ptr(),
+                name.len(),
+                &mut preset_number,
+            );
+
+            if result < 0 {
+                return Err(map_error(result));
+            }
+            if result == 0 {
+                return Ok(None);
+            }
+
+            let plugin = self.inner.as_ptr();
+            let (result, name) = negotiated_name("preset name", |buffer, size| {
+                ffi::rack_au_plugin_current_preset_name(plugin, buffer, size)
+            })?;
+            if result == 0 {
+                // The preset went away in between
+                return Ok(None);
+            }
+
+            Ok(Some(CurrentPreset {
+                preset_number,
+                name,
+            }))
+        }
+    }
+
     fn get_state(&self) -> Result<Vec<u8>> {
         if !self.is_initialized() {
             return Err(Error::NotInitialized);
@@ -508,9 +851,30 @@ impl PluginInstance for AudioUnitPlugin {
             if result != ffi::RACK_AU_OK {
                 return Err(map_error(result));
             }
+        }
 
-            Ok(())
+        // Setting ClassInfo notifies its listeners; a restored state isn't a
+        // change the user made
+        self.changes.state_dirty.store(false, Ordering::Release);
+
+        if self.quirks.contains(Quirk::ResetAfterStateRestore) {
+            self.reset()?;
         }
+
+        if self.quirks.contains(Quirk::GuiBeforeStateRestore) && !self.gui_requested {
+            self.pending_gui_state = Some(data.to_vec());
+        }
+
+        events::emit(HostEvent::StateRestored {
+            info: &self.info,
+            bytes: data.len(),
+        });
+
+        Ok(())
+    }
+
+    fn take_state_dirty(&self) -> bool {
+        self.changes.state_dirty.swap(false, Ordering::AcqRel)
     }
 
     fn info(&self) -> &PluginInfo {
@@ -523,76 +887,74 @@ impl PluginInstance for AudioUnitPlugin {
             result != 0
         }
     }
-}
 
-// Additional methods not in PluginInstance trait
-impl AudioUnitPlugin {
-    /// Get the number of input channels
-    ///
-    /// Returns the actual number of input channels the plugin was configured with
-    /// after initialization. This may differ from what was requested if the plugin
-    /// doesn't support the requested configuration.
-    ///
-    /// # Returns
-    ///
-    /// - Number of input channels (e.g., 1 for mono, 2 for stereo)
-    /// - 0 if not initialized
-    ///
-    /// # Example
-    ///
-    /// ```no_run
-    /// # use rack::prelude::*;
-    /// # fn example() -> Result<()> {
-    /// # let scanner = Scanner::new()?;
-    /// # let plugins = scanner.scan()?;
-    /// # let mut plugin = scanner.load(&plugins[0])?;
-    /// plugin.initialize(48000.0, 512)?;
-    /// let channels = plugin.input_channels();
-    /// println!("Plugin has {} input channels", channels);
-    /// # Ok(())
-    /// # }
-    /// ```
-    pub fn input_channels(&self) -> usize {
+    fn input_channels(&self) -> usize {
         self.input_channels
     }
 
-    /// Get the number of output channels
-    ///
-    /// Returns the actual number of output channels the plugin was configured with
-    /// after initialization. This may differ from what was requested if the plugin
-    /// doesn't support the requested configuration.
-    ///
-    /// # Returns
-    ///
-    /// - Number of output channels (e.g., 1 for mono, 2 for stereo)
-    /// - 0 if not initialized
-    ///
-    /// # Example
-    ///
-    /// ```no_run
-    /// # use rack::prelude::*;
-    /// # fn example() -> Result<()> {
-    /// # let scanner = Scanner::new()?;
-    /// # let plugins = scanner.scan()?;
-    /// # let mut plugin = scanner.load(&plugins[0])?;
-    /// plugin.initialize(48000.0, 512)?;
-    /// let channels = plugin.output_channels();
-    /// println!("Plugin has {} output channels", channels);
-    ///
-    /// // Allocate buffers with correct channel count
-    /// let mut inputs: Vec<Vec<f32>> = (0..plugin.input_channels())
-    ///     .map(|_| vec![0.0f32; 512])
-    ///     .collect();
-    /// let mut outputs: Vec<Vec<f32>> = (0..plugin.output_channels())
-    ///     .map(|_| vec![0.0f32; 512])
-    ///     .collect();
-    /// # Ok(())
-    /// # }
-    /// ```
-    pub fn output_channels(&self) -> usize {
+    fn output_channels(&self) -> usize {
         self.output_channels
     }
 
+    fn latency_samples(&self) -> usize {
+        let latency = unsafe { ffi::rack_au_plugin_get_latency(self.inner.as_ptr()) };
+        latency.max(0) as usize
+    }
+
+    fn tail_samples(&self) -> usize {
+        crate::traits::tail_samples(self.tail_seconds(), self.sample_rate)
+    }
+
+    fn tail_seconds(&self) -> f64 {
+        unsafe { ffi::rack_au_plugin_get_tail_time(self.inner.as_ptr()) }
+    }
+
+    fn set_bypass(&mut self, bypassed: bool) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        let result = unsafe {
+            ffi::rack_au_plugin_set_bypass(self.inner.as_ptr(), bypassed as std::os::raw::c_int)
+        };
+        if result == ffi::RACK_AU_OK {
+            self.native_bypassed = bypassed;
+        } else {
+            self.bypass.set_bypassed(bypassed);
+        }
+        Ok(())
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.native_bypassed || self.bypass.is_bypassed()
+    }
+
+    fn quirks(&self) -> Quirks {
+        self.quirks.clone()
+    }
+
+    fn plugin_meter(&self) -> Option<Vec<MeterReading>> {
+        let indices = self.meter_parameters();
+        if indices.is_empty() {
+            return None;
+        }
+        indices
+            .into_iter()
+            .map(|index| {
+                let info = self.parameter_info(index).ok()?;
+                let value = self.get_parameter(index).ok()?;
+                Some(MeterReading::new(&info, info.to_plain(Normalized::new(value)).value()))
+            })
+            .collect()
+    }
+}
+
+/// What `create_gui()` hands to its trampoline: the plugin's info (for event
+/// reporting), the plugin, the state to apply again and the user's callback
+type GuiRequest<F> = (PluginInfo, *mut ffi::RackAUPlugin, Option<Vec<u8>>, F);
+
+// Additional methods not in PluginInstance trait
+impl AudioUnitPlugin {
     /// Create GUI asynchronously
     ///
     /// Creates the plugin's graphical user interface. This function tries multiple
@@ -603,6 +965,9 @@ impl AudioUnitPlugin {
     ///
     /// The callback is invoked on the main thread when the GUI is ready.
     ///
+    /// Plugins with [`Quirk::GuiBeforeStateRestore`] get the state last
+    /// restored before this call again once their GUI exists.
+    ///
     /// # Parameters
     ///
     /// - `callback`: Closure invoked when GUI creation completes. Receives
@@ -654,8 +1019,17 @@ impl AudioUnitPlugin {
             return;
         }
 
-        // Box the callback so we can pass it through C
-        let boxed_callback = Box::new(callback);
+        self.gui_requested = true;
+        let pending_state = self.pending_gui_state.take();
+
+        // Box the callback (with the plugin info for event reporting and the
+        // state to apply again) so we can pass it through C
+        let boxed_callback: Box<GuiRequest<F>> = Box::new((
+            self.info.clone(),
+            self.inner.as_ptr(),
+            pending_state,
+            callback,
+        ));
         let user_data = Box::into_raw(boxed_callback) as *mut std::ffi::c_void;
 
         // Define the C callback trampoline
@@ -667,9 +1041,10 @@ impl AudioUnitPlugin {
             F: FnOnce(Result<super::gui::AudioUnitGui>) -> Result<()> + Send + 'static,
         {
             // Safety: user_data is the boxed callback we created above
-            let callback = unsafe {
-                Box::from_raw(user_data as *mut F)
+            let boxed = unsafe {
+                Box::from_raw(user_data as *mut GuiRequest<F>)
             };
+            let (info, plugin, pending_state, callback) = *boxed;
 
             let result = if gui.is_null() {
                 Err(map_error(error_code))
@@ -678,6 +1053,27 @@ impl AudioUnitPlugin {
                 Ok(unsafe { super::gui::AudioUnitGui::from_raw(gui) })
             };
 
+            if let (Ok(_), Some(state)) = (&result, pending_state) {
+                // Safety: the GUI belongs to the plugin, so the plugin is alive
+                let code = unsafe {
+                    ffi::rack_au_plugin_set_state(plugin, state.as_ptr(), state.len())
+                };
+                if code != ffi::RACK_AU_OK {
+                    events::emit(HostEvent::Error {
+                        info: Some(&info),
+                        error: &map_error(code),
+                    });
+                }
+            }
+
+            match &result {
+                Ok(_) => events::emit(HostEvent::GuiOpened { info: &info }),
+                Err(error) => events::emit(HostEvent::Error {
+                    info: Some(&info),
+                    error,
+                }),
+            }
+
             // Invoke the user's callback
             let _ = callback(result);
         }
@@ -693,6 +1089,155 @@ impl AudioUnitPlugin {
     }
 }
 
+/// Render quality of an AudioUnit (`kAudioUnitProperty_RenderQuality`)
+///
+/// AudioUnits take any value from 0 to 127; the constants are Apple's named
+/// levels. Plugins that support the property typically trade CPU for
+/// quality in resampling, oversampling or interpolation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RenderQuality(pub u8);
+
+impl RenderQuality {
+    /// Lowest quality (`kRenderQuality_Min`)
+    pub const MIN: Self = Self(0x00);
+
+    /// `kRenderQuality_Low`
+    pub const LOW: Self = Self(0x20);
+
+    /// `kRenderQuality_Medium`
+    pub const MEDIUM: Self = Self(0x40);
+
+    /// `kRenderQuality_High`
+    pub const HIGH: Self = Self(0x60);
+
+    /// Highest quality (`kRenderQuality_Max`)
+    pub const MAX: Self = Self(0x7F);
+}
+
+// Render settings (AudioUnit only)
+impl AudioUnitPlugin {
+    /// Change the largest block `process()` accepts after initialization
+    ///
+    /// Many instruments only allocate buffers for the
+    /// `kAudioUnitProperty_MaximumFramesPerSlice` they see at initialization
+    /// and crash when a host later renders larger slices. This sets the
+    /// property with the plugin uninitialized and initializes it again, so
+    /// the plugin reallocates. Parameters and state are kept; audio still
+    /// ringing out (reverb tails, delay lines) is lost.
+    ///
+    /// Before `initialize()` this just sets the property; `initialize()`
+    /// overrides it with its own `max_block_size`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `max_block_size` is 0, or the plugin refuses the
+    /// new size (the previous size stays in effect). If the plugin fails to
+    /// initialize again it is left uninitialized.
+    pub fn set_max_block_size(&mut self, max_block_size: usize) -> Result<()> {
+        let frames = u32::try_from(max_block_size)
+            .map_err(|_| Error::Other(format!("Invalid block size {}", max_block_size)))?;
+        unsafe {
+            let result = ffi::rack_au_plugin_set_max_frames(self.inner.as_ptr(), frames);
+            if result != ffi::RACK_AU_OK {
+                return Err(map_error(result));
+            }
+        }
+        if self.is_initialized() {
+            self.max_block_size = max_block_size;
+        }
+        Ok(())
+    }
+
+    /// Get the plugin's render quality
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin doesn't support
+    /// `kAudioUnitProperty_RenderQuality`
+    pub fn render_quality(&self) -> Result<RenderQuality> {
+        let mut quality = 0u32;
+        unsafe {
+            let result = ffi::rack_au_plugin_get_render_quality(self.inner.as_ptr(), &mut quality);
+            if result != ffi::RACK_AU_OK {
+                return Err(map_error(result));
+            }
+        }
+        Ok(RenderQuality(quality.min(0x7F) as u8))
+    }
+
+    /// Set the plugin's render quality
+    ///
+    /// Plugins that don't support the property return an error; ignoring it
+    /// is usually fine.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the quality is above [`RenderQuality::MAX`] or the
+    /// plugin rejects it
+    pub fn set_render_quality(&mut self, quality: RenderQuality) -> Result<()> {
+        unsafe {
+            let result = ffi::rack_au_plugin_set_render_quality(self.inner.as_ptr(), u32::from(quality.0));
+            if result != ffi::RACK_AU_OK {
+                return Err(map_error(result));
+            }
+        }
+        Ok(())
+    }
+
+    /// Tell the plugin whether it is rendering offline
+    ///
+    /// Set this around bounces and exports: plugins may then use more CPU
+    /// per block than real time allows (higher oversampling, longer
+    /// lookahead). Switch it back off before rendering in real time again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin doesn't support
+    /// `kAudioUnitProperty_OfflineRender`
+    pub fn set_offline_render(&mut self, offline: bool) -> Result<()> {
+        unsafe {
+            let result = ffi::rack_au_plugin_set_offline_render(self.inner.as_ptr(), offline as std::os::raw::c_int);
+            if result != ffi::RACK_AU_OK {
+                return Err(map_error(result));
+            }
+        }
+        Ok(())
+    }
+
+    /// Switch the plugin's metering on or off
+    ///
+    /// Metering is switched on by `initialize()` for plugins that publish
+    /// meters (see [`plugin_meter()`](PluginInstance::plugin_meter)); switch
+    /// it off to save the CPU it costs while no meters are shown.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin doesn't support
+    /// `kAudioUnitProperty_MeteringMode`
+    pub fn set_metering(&mut self, enabled: bool) -> Result<()> {
+        unsafe {
+            let result = ffi::rack_au_plugin_set_metering_mode(self.inner.as_ptr(), enabled as std::os::raw::c_int);
+            if result != ffi::RACK_AU_OK {
+                return Err(map_error(result));
+            }
+        }
+        Ok(())
+    }
+
+    /// Indices of the parameters the plugin flags as meters
+    fn meter_parameters(&self) -> Vec<usize> {
+        (0..self.parameter_count())
+            .filter(|&index| {
+                let mut flags = 0u32;
+                let result = unsafe {
+                    ffi::rack_au_plugin_parameter_flags(self.inner.as_ptr(), index as u32, &mut flags)
+                };
+                result == ffi::RACK_AU_OK && flags & ffi::AU_PARAMETER_FLAG_METER_READ_ONLY != 0
+            })
+            .collect()
+    }
+}
+
 impl Drop for AudioUnitPlugin {
     fn drop(&mut self) {
         unsafe {
@@ -706,6 +1251,57 @@ mod tests {
     use super::*;
     use crate::{PluginScanner, PluginType};
 
+    #[test]
+    fn test_parameter_events_reach_listener() {
+        let context = ChangeContext {
+            info: PluginInfo::new(
+                "Edits".to_string(),
+                "Test".to_string(),
+                1,
+                PluginType::Effect,
+                std::path::PathBuf::new(),
+                "edits".to_string(),
+            ),
+            listener: RwLock::new(None),
+            state_dirty: AtomicBool::new(false),
+        };
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let listener_seen = std::sync::Arc::clone(&seen);
+        *context.listener.write().unwrap() =
+            Some(Box::new(move |edit| listener_seen.lock().unwrap().push(edit)));
+
+        let event = |kind, param_index, value| ffi::RackAUChangeEvent {
+            kind,
+            seconds: 0.0,
+            samples: 0,
+            param_index,
+            value,
+        };
+        let user_data = &context as *const ChangeContext as *mut std::ffi::c_void;
+        property_changed(user_data, &event(ffi::RACK_AU_CHANGE_BEGIN_EDIT, 2, 0.0));
+        property_changed(user_data, &event(ffi::RACK_AU_CHANGE_PARAMETER, 2, 0.75));
+        // Unknown parameters are skipped
+        property_changed(user_data, &event(ffi::RACK_AU_CHANGE_PARAMETER, -1, 0.5));
+        property_changed(user_data, &event(ffi::RACK_AU_CHANGE_END_EDIT, 2, 0.0));
+        assert!(!context.state_dirty.load(Ordering::Acquire));
+
+        // State changes are recorded, not passed to the listener
+        property_changed(user_data, &event(ffi::RACK_AU_CHANGE_STATE, -1, 0.0));
+        assert!(context.state_dirty.load(Ordering::Acquire));
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                ParameterEdit::Begin { index: 2 },
+                ParameterEdit::Change {
+                    index: 2,
+                    value: Normalized::new(0.75)
+                },
+                ParameterEdit::End { index: 2 },
+            ]
+        );
+    }
+
     // Helper to get a real plugin for testing
     fn get_test_plugin() -> Option<PluginInfo> {
         use super::super::scanner::AudioUnitScanner;
@@ -904,6 +1500,30 @@ mod tests {
         assert!(param_info.index == 0);
     }
 
+    #[test]
+    fn test_parameter_visibility() {
+        let Some(info) = get_test_plugin() else {
+            println!("No test plugins available, skipping test");
+            return;
+        };
+
+        let mut plugin = AudioUnitPlugin::new(&info).expect("Failed to create plugin");
+        plugin
+            .initialize(48000.0, 512)
+            .expect("Failed to initialize plugin");
+
+        let all = plugin.all_parameters().expect("Failed to list parameters");
+        let visible = plugin.parameters().expect("Failed to list parameters");
+        println!(
+            "  {} parameters, {} expert-only",
+            all.len(),
+            all.len() - visible.len()
+        );
+
+        assert_eq!(all.len(), plugin.parameter_count());
+        assert!(visible.iter().all(|p| all[p.index].name == p.name));
+    }
+
     #[test]
     fn test_get_set_parameter() {
         let Some(info) = get_test_plugin() else {
@@ -1489,6 +2109,36 @@ mod tests {
         println!("  ✓ Loaded preset: {}", preset.name);
     }
 
+    #[test]
+    fn test_current_preset() {
+        let Some(info) = get_test_plugin() else {
+            println!("No test plugins available, skipping test");
+            return;
+        };
+
+        let mut plugin = AudioUnitPlugin::new(&info).expect("Failed to create plugin");
+        assert!(matches!(plugin.current_preset(), Err(Error::NotInitialized)));
+
+        plugin
+            .initialize(48000.0, 512)
+            .expect("Failed to initialize plugin");
+
+        if plugin.preset_count().unwrap_or(0) == 0 {
+            println!("  Plugin has no presets, skipping test");
+            return;
+        }
+
+        let preset = plugin.preset_info(0).expect("Failed to get preset info");
+        plugin
+            .load_preset(preset.preset_number)
+            .expect("Failed to load preset");
+
+        if let Some(current) = plugin.current_preset().expect("Failed to get current preset") {
+            assert_eq!(current.preset_number, preset.preset_number);
+            assert_eq!(current.name, preset.name);
+        }
+    }
+
     #[test]
     fn test_state_round_trip() {
         let Some(info) = get_test_plugin() else {
@@ -1752,6 +2402,110 @@ mod tests {
         println!("✓ Successfully processed with {}/{} channels", input_ch, output_ch);
     }
 
+    #[test]
+    fn test_process_zero_and_small_blocks() {
+        let Some(info) = get_test_plugin() else {
+            println!("No test plugins available, skipping test");
+            return;
+        };
+
+        let mut plugin = AudioUnitPlugin::new(&info).expect("Failed to create plugin");
+        plugin
+            .initialize(48000.0, 512)
+            .expect("Failed to initialize plugin");
+
+        let inputs: Vec<Vec<f32>> = (0..plugin.input_channels()).map(|_| vec![0.0f32; 16]).collect();
+        let mut outputs: Vec<Vec<f32>> = (0..plugin.output_channels()).map(|_| vec![0.0f32; 16]).collect();
+
+        for frames in [0, 1, 2, 7, 16] {
+            let input_refs: Vec<&[f32]> = inputs.iter().map(|v| v.as_slice()).collect();
+            let mut output_refs: Vec<&mut [f32]> = outputs.iter_mut().map(|v| v.as_mut_slice()).collect();
+            let result = plugin.process(&input_refs, &mut output_refs, frames);
+            assert!(result.is_ok(), "process() should accept {}-frame blocks: {:?}", frames, result);
+        }
+    }
+
+    #[test]
+    fn test_sample_position_advances() {
+        let Some(info) = get_test_plugin() else {
+            println!("No test plugins available, skipping test");
+            return;
+        };
+
+        let mut plugin = AudioUnitPlugin::new(&info).expect("Failed to create plugin");
+        plugin
+            .initialize(48000.0, 512)
+            .expect("Failed to initialize plugin");
+        assert_eq!(plugin.sample_position(), 0);
+
+        let inputs: Vec<Vec<f32>> = (0..plugin.input_channels()).map(|_| vec![0.0f32; 512]).collect();
+        let mut outputs: Vec<Vec<f32>> = (0..plugin.output_channels()).map(|_| vec![0.0f32; 512]).collect();
+
+        for frames in [512, 100, 0] {
+            let input_refs: Vec<&[f32]> = inputs.iter().map(|v| v.as_slice()).collect();
+            let mut output_refs: Vec<&mut [f32]> = outputs.iter_mut().map(|v| v.as_mut_slice()).collect();
+            plugin.process(&input_refs, &mut output_refs, frames).expect("process() failed");
+        }
+        assert_eq!(plugin.sample_position(), 612);
+
+        plugin.set_sample_position(48000).expect("Failed to seek");
+        plugin.reset().expect("Failed to reset plugin");
+        assert_eq!(plugin.sample_position(), 48000);
+        assert!(plugin.set_sample_position(u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_process_block_too_large() {
+        let Some(info) = get_test_plugin() else {
+            println!("No test plugins available, skipping test");
+            return;
+        };
+
+        let mut plugin = AudioUnitPlugin::new(&info).expect("Failed to create plugin");
+        plugin
+            .initialize(48000.0, 256)
+            .expect("Failed to initialize plugin");
+
+        let inputs: Vec<Vec<f32>> = (0..plugin.input_channels()).map(|_| vec![0.0f32; 512]).collect();
+        let mut outputs: Vec<Vec<f32>> = (0..plugin.output_channels()).map(|_| vec![0.0f32; 512]).collect();
+        let input_refs: Vec<&[f32]> = inputs.iter().map(|v| v.as_slice()).collect();
+        let mut output_refs: Vec<&mut [f32]> = outputs.iter_mut().map(|v| v.as_mut_slice()).collect();
+
+        let result = plugin.process(&input_refs, &mut output_refs, 512);
+        assert!(
+            matches!(result, Err(Error::BlockTooLarge { max: 256, got: 512 })),
+            "process() should reject blocks larger than max_block_size, got {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_set_max_block_size_after_initialize() {
+        let Some(info) = get_test_plugin() else {
+            println!("No test plugins available, skipping test");
+            return;
+        };
+
+        let mut plugin = AudioUnitPlugin::new(&info).expect("Failed to create plugin");
+        plugin
+            .initialize(48000.0, 256)
+            .expect("Failed to initialize plugin");
+        plugin
+            .set_max_block_size(1024)
+            .expect("Failed to raise the maximum block size");
+        assert!(plugin.is_initialized());
+
+        let inputs: Vec<Vec<f32>> = (0..plugin.input_channels()).map(|_| vec![0.0f32; 1024]).collect();
+        let mut outputs: Vec<Vec<f32>> = (0..plugin.output_channels()).map(|_| vec![0.0f32; 1024]).collect();
+        let input_refs: Vec<&[f32]> = inputs.iter().map(|v| v.as_slice()).collect();
+        let mut output_refs: Vec<&mut [f32]> = outputs.iter_mut().map(|v| v.as_mut_slice()).collect();
+        plugin
+            .process(&input_refs, &mut output_refs, 1024)
+            .expect("process() should accept the new maximum block size");
+
+        assert!(plugin.set_max_block_size(0).is_err());
+    }
+
     #[test]
     fn test_process_with_too_many_channels() {
         let Some(info) = get_test_plugin() else {
@@ -1,12 +1,16 @@
-use crate::{Error, PluginInfo, PluginScanner, PluginType, Result};
+use crate::events::{self, HostEvent};
+use crate::metadata::{self, SharedMetadataStore};
+use crate::scan::{find_bundles, ScanFilter, ScannerConfig};
+use crate::{Error, PluginFormat, PluginInfo, PluginScanner, PluginType, Result};
+use std::ffi::c_char;
 use std::marker::PhantomData;
 use std::mem::MaybeUninit;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::ptr::NonNull;
 
 use super::ffi;
 use super::instance::AudioUnitPlugin;
-use super::util::{c_array_to_string, map_error};
+use super::util::{audio_unit_flags_from_raw, c_array_to_string, map_error, negotiated_name, negotiated_path};
 
 /// Scanner for AudioUnit plugins on macOS
 ///
@@ -22,6 +26,8 @@ pub struct AudioUnitScanner {
     // PhantomData<*const ()> makes this type !Sync while keeping it Send
     // This prevents concurrent access without Arc<Mutex<>>
     _not_sync: PhantomData<*const ()>,
+    usage: Option<SharedMetadataStore>,
+    config: ScannerConfig,
 }
 
 // Safety: AudioUnitScanner can be sent between threads because:
@@ -40,20 +46,43 @@ impl AudioUnitScanner {
     ///
     /// Returns an error if scanner allocation fails
     pub fn new() -> Result<Self> {
-        unsafe {
-            let ptr = ffi::rack_au_scanner_new();
-            if ptr.is_null() {
-                return Err(Error::Other("Failed to allocate scanner".to_string()));
-            }
-            Ok(Self {
-                inner: NonNull::new_unchecked(ptr),
-                _not_sync: PhantomData,
-            })
-        }
+        Self::with_config(ScannerConfig::from_env())
+    }
+
+    /// Record every successful [`load()`](PluginScanner::load) in `store`
+    ///
+    /// Enables [`most_used()`](PluginScanner::most_used). Usage tracking is off
+    /// until this is called.
+    pub fn track_usage(&mut self, store: SharedMetadataStore) {
+        self.usage = Some(store);
     }
 
     /// Scan for AudioUnit components
-    fn scan_components(&self) -> Result<Vec<PluginInfo>> {
+    ///
+    /// The system registry is the only place AudioUnits come from, so with
+    /// `skip_default_paths` set only the registered components installed
+    /// under `extra_paths` are reported.
+    fn scan_components(&self, filter: Option<&ScanFilter>) -> Result<Vec<PluginInfo>> {
+        if !self.config.skip_default_paths {
+            return self.scan_registry(filter);
+        }
+        if self.config.extra_paths.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let dirs: Vec<PathBuf> = self
+            .config
+            .extra_paths
+            .iter()
+            .map(|dir| std::fs::canonicalize(dir).unwrap_or_else(|_| dir.clone()))
+            .collect();
+        let mut plugins = self.scan_registry(filter)?;
+        plugins.retain(|p| dirs.iter().any(|dir| installed_under(p, dir)));
+        Ok(plugins)
+    }
+
+    /// Scan every component in the system registry
+    fn scan_registry(&self, filter: Option<&ScanFilter>) -> Result<Vec<PluginInfo>> {
         unsafe {
             // First pass: get count
             let count = ffi::rack_au_scanner_scan(self.inner.as_ptr(), std::ptr::null_mut(), 0);
@@ -99,11 +128,13 @@ impl AudioUnitScanner {
             let plugins = plugins_c
                 .into_iter()
                 .take(valid_count)
-                .map(|p| {
+                .enumerate()
+                .map(|(index, p)| {
                     // Safety: C++ has written valid data to these elements
                     let plugin_info = p.assume_init();
-                    convert_plugin_info(&plugin_info)
+                    convert_plugin_info(self.inner.as_ptr(), index, &plugin_info, filter)
                 })
+                .filter_map(|r| r.transpose())
                 .collect::<Result<Vec<_>>>()?;
 
             Ok(plugins)
@@ -111,16 +142,37 @@ impl AudioUnitScanner {
     }
 }
 
+/// Whether a component's bundle is at or below `dir`, or `dir` is inside its
+/// bundle (AUv3s may be reported at their containing app)
+fn installed_under(plugin: &PluginInfo, dir: &Path) -> bool {
+    plugin.canonical_path.starts_with(dir)
+        || (!plugin.canonical_path.as_os_str().is_empty()
+            && dir.starts_with(&plugin.canonical_path))
+        || plugin.path.starts_with(dir)
+}
+
 /// Convert C plugin info to Rust PluginInfo
-fn convert_plugin_info(c_info: &ffi::RackAUPluginInfo) -> Result<PluginInfo> {
+///
+/// Returns `Ok(None)` if the plugin is rejected by `filter`. The filter is checked
+/// before the path and unique ID are converted.
+///
+/// Name, manufacturer and path are read from the scanner at full length rather
+/// than from the fixed-size fields of `c_info`, which may be truncated.
+fn convert_plugin_info(
+    scanner: *mut ffi::RackAUScanner,
+    index: usize,
+    c_info: &ffi::RackAUPluginInfo,
+    filter: Option<&ScanFilter>,
+) -> Result<Option<PluginInfo>> {
     unsafe {
-        // Use bounded string conversion for safety
-        // This protects against C++ bugs (missing null-termination)
-        // by only searching for null within the fixed array bounds
-        let name = c_array_to_string(&c_info.name, "plugin name")?;
-        let manufacturer = c_array_to_string(&c_info.manufacturer, "manufacturer")?;
-        let path_str = c_array_to_string(&c_info.path, "path")?;
-        let unique_id = c_array_to_string(&c_info.unique_id, "unique_id")?;
+        let field = |field: std::ffi::c_int| {
+            move |buffer: *mut c_char, size: *mut usize| {
+                ffi::rack_au_scanner_plugin_string(scanner, index, field, buffer, size)
+            }
+        };
+        let (_, name) = negotiated_name("plugin name", field(ffi::RACK_AU_FIELD_NAME))?;
+        let (_, manufacturer) =
+            negotiated_name("manufacturer", field(ffi::RACK_AU_FIELD_MANUFACTURER))?;
 
         // Convert plugin type
         let plugin_type = match c_info.plugin_type {
@@ -131,14 +183,33 @@ fn convert_plugin_info(c_info: &ffi::RackAUPluginInfo) -> Result<PluginInfo> {
             ffi::RackAUPluginType::Other => PluginType::Other,
         };
 
-        Ok(PluginInfo::new(
+        if let Some(filter) = filter {
+            if !filter.matches_fields(&name, &manufacturer, plugin_type) {
+                return Ok(None);
+            }
+        }
+
+        let path = negotiated_path(field(ffi::RACK_AU_FIELD_PATH))?;
+        // AudioUnits are registered by the system, so there's nothing to
+        // deduplicate, but a symlinked .component still has a real location
+        let canonical_path = std::fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+
+        // Use bounded string conversion for safety
+        // This protects against C++ bugs (missing null-termination)
+        // by only searching for null within the fixed array bounds
+        let unique_id = c_array_to_string(&c_info.unique_id, "unique_id")?;
+
+        Ok(Some(PluginInfo::new(
             name,
             manufacturer,
             c_info.version,
             plugin_type,
-            PathBuf::from(path_str),
+            path,
             unique_id,
-        ))
+        )
+        .with_format(PluginFormat::AudioUnit)
+        .with_canonical_path(canonical_path)
+        .with_au_flags(audio_unit_flags_from_raw(c_info.component_flags))))
     }
 }
 
@@ -153,18 +224,91 @@ impl Drop for AudioUnitScanner {
 impl PluginScanner for AudioUnitScanner {
     type Plugin = AudioUnitPlugin;
 
+    fn with_config(config: ScannerConfig) -> Result<Self> {
+        unsafe {
+            let ptr = ffi::rack_au_scanner_new();
+            if ptr.is_null() {
+                return Err(Error::Other("Failed to allocate scanner".to_string()));
+            }
+            Ok(Self {
+                inner: NonNull::new_unchecked(ptr),
+                _not_sync: PhantomData,
+                usage: None,
+                config,
+            })
+        }
+    }
+
+    fn config(&self) -> &ScannerConfig {
+        &self.config
+    }
+
+    /// Where macOS looks for AudioUnit v2 components
+    ///
+    /// For display only: the system registry decides what is found, and
+    /// AUv3 extensions are found wherever their apps are installed.
+    fn default_paths() -> Vec<std::path::PathBuf> {
+        let mut paths = Vec::new();
+        if cfg!(target_os = "macos") {
+            paths.push(std::path::PathBuf::from("/Library/Audio/Plug-Ins/Components"));
+            if let Some(home) = std::env::var_os("HOME") {
+                paths.push(std::path::Path::new(&home).join("Library/Audio/Plug-Ins/Components"));
+            }
+        }
+        paths
+    }
+
+    fn add_path(&mut self, path: &std::path::Path) -> Result<()> {
+        // AudioUnits are registered with the system rather than found in
+        // directories; the path only narrows scans that skip the default ones
+        self.config.extra_paths.push(path.to_path_buf());
+        Ok(())
+    }
+
     fn scan(&self) -> Result<Vec<PluginInfo>> {
-        self.scan_components()
+        self.scan_components(None)
     }
 
-    fn scan_path(&self, _path: &std::path::Path) -> Result<Vec<PluginInfo>> {
-        // AudioUnits are registered with the system, not scanned from paths
-        // So we just return the same result as scan()
-        self.scan()
+    fn scan_filtered(&self, filter: &ScanFilter) -> Result<Vec<PluginInfo>> {
+        self.scan_components(Some(filter))
+    }
+
+    fn scan_path(&self, path: &std::path::Path) -> Result<Vec<PluginInfo>> {
+        // AudioUnits are registered with the system, not scanned from paths,
+        // so by default this is the same result as scan()
+        let plugins = self.scan()?;
+        if self.config.scan_depth == 0 {
+            return Ok(plugins);
+        }
+
+        // A deep scan narrows the registry to the bundles found under the path
+        // (so it only finds components the system has registered). AUv3s may
+        // be reported at their containing app.
+        let bundles = find_bundles(
+            path,
+            self.config.scan_depth,
+            self.config.follow_symlinks,
+            self.config.io_timeout,
+        );
+        Ok(plugins
+            .into_iter()
+            .filter(|p| {
+                bundles.iter().any(|b| {
+                    b.kind.format() == PluginFormat::AudioUnit && installed_under(p, &b.path)
+                })
+            })
+            .collect())
     }
 
     fn load(&self, info: &PluginInfo) -> Result<Self::Plugin> {
-        AudioUnitPlugin::new(info)
+        let plugin = AudioUnitPlugin::new(info)?;
+        metadata::record_load(self.usage.as_ref(), &info.unique_id);
+        events::emit(HostEvent::PluginLoaded { info });
+        Ok(plugin)
+    }
+
+    fn usage_store(&self) -> Option<&SharedMetadataStore> {
+        self.usage.as_ref()
     }
 }
 
@@ -195,6 +339,32 @@ mod tests {
         assert!(result.is_ok(), "Scan should succeed");
     }
 
+    #[test]
+    fn test_scan_filtered() {
+        let scanner = AudioUnitScanner::new().expect("Scanner creation should succeed");
+        let filter = ScanFilter::new().plugin_type(PluginType::Effect).manufacturer("Apple");
+        let plugins = scanner.scan_filtered(&filter).expect("Scan should succeed");
+        assert!(plugins
+            .iter()
+            .all(|p| p.plugin_type == PluginType::Effect && p.manufacturer == "Apple"));
+    }
+
+    #[test]
+    fn test_usage_tracking() {
+        let mut scanner = AudioUnitScanner::new().expect("Scanner creation should succeed");
+        assert!(scanner.most_used(5).is_empty());
+
+        let store = std::sync::Arc::new(std::sync::Mutex::new(metadata::MetadataStore::new()));
+        scanner.track_usage(store.clone());
+
+        let plugins = scanner.scan().expect("Scan should succeed");
+        if let Some(info) = plugins.iter().find(|p| p.plugin_type == PluginType::Effect) {
+            let _plugin = scanner.load(info).expect("Load should succeed");
+            assert_eq!(scanner.most_used(5), vec![info.unique_id.clone()]);
+            assert_eq!(store.lock().unwrap().get(&info.unique_id).unwrap().load_count, 1);
+        }
+    }
+
     #[test]
     fn test_scan_returns_plugins() {
         let scanner = AudioUnitScanner::new().expect("Scanner creation should succeed");
@@ -255,6 +425,7 @@ mod tests {
             // Version can be 0, so we don't assert it
             // path may be "<system>" for system plugins, so we just check it's not empty
             assert!(plugin.path.as_os_str().len() > 0, "Path should not be empty");
+            assert!(plugin.au_flags.is_some(), "AudioUnits should report component flags");
         }
     }
 
@@ -267,4 +438,11 @@ mod tests {
         let result = scanner.scan_path(path);
         assert!(result.is_ok(), "scan_path should succeed");
     }
+
+    #[test]
+    fn test_skip_default_paths() {
+        let config = ScannerConfig::new().skip_default_paths(true);
+        let scanner = AudioUnitScanner::with_config(config).expect("Scanner creation should succeed");
+        assert!(scanner.scan().expect("Scan should succeed").is_empty());
+    }
 }
@@ -1,9 +1,11 @@
 //! Shared utilities for AudioUnit FFI interop
 
-use crate::{Error, Result};
-use std::ffi::CStr;
+use crate::{AudioUnitFlags, Error, ParameterCurve, ParameterVisibility, Result};
+use std::ffi::{c_char, CStr};
+use std::path::PathBuf;
 
 use super::ffi;
+use crate::{paths, text};
 
 /// Convert C API error code to Rust Error
 ///
@@ -16,11 +18,48 @@ pub(crate) fn map_error(code: i32) -> Error {
         ffi::RACK_AU_ERROR_NOT_FOUND => Error::PluginNotFound("AudioUnit not found".to_string()),
         ffi::RACK_AU_ERROR_INVALID_PARAM => Error::Other("Invalid parameter".to_string()),
         ffi::RACK_AU_ERROR_NOT_INITIALIZED => Error::NotInitialized,
+        ffi::RACK_AU_ERROR_BUFFER_TOO_SMALL => Error::Other("String kept changing size".to_string()),
         // AudioUnit OSStatus errors (< -1000) or unknown negative codes
         _ => Error::from_os_status(code),
     }
 }
 
+/// Map AudioUnit parameter display flags to a ParameterCurve
+pub(crate) fn parameter_curve_from_flags(flags: u32) -> ParameterCurve {
+    match flags & ffi::AU_PARAMETER_FLAG_DISPLAY_MASK {
+        ffi::AU_PARAMETER_FLAG_DISPLAY_LOGARITHMIC => ParameterCurve::Logarithmic,
+        ffi::AU_PARAMETER_FLAG_DISPLAY_EXPONENTIAL => ParameterCurve::Exponential,
+        ffi::AU_PARAMETER_FLAG_DISPLAY_SQUARED => ParameterCurve::Squared,
+        ffi::AU_PARAMETER_FLAG_DISPLAY_SQUARE_ROOT => ParameterCurve::SquareRoot,
+        ffi::AU_PARAMETER_FLAG_DISPLAY_CUBED => ParameterCurve::Cubed,
+        ffi::AU_PARAMETER_FLAG_DISPLAY_CUBE_ROOT => ParameterCurve::CubeRoot,
+        _ => ParameterCurve::Linear,
+    }
+}
+
+/// Map AudioUnit parameter flags to a ParameterVisibility
+///
+/// AudioUnits have no "hidden" flag; expert-mode parameters are the ones
+/// hosts leave out of their normal lists.
+pub(crate) fn parameter_visibility_from_flags(flags: u32) -> ParameterVisibility {
+    if flags & ffi::AU_PARAMETER_FLAG_EXPERT_MODE != 0 {
+        ParameterVisibility::Expert
+    } else {
+        ParameterVisibility::Visible
+    }
+}
+
+/// Convert raw AudioComponentFlags to [`AudioUnitFlags`]
+pub(crate) fn audio_unit_flags_from_raw(flags: u32) -> AudioUnitFlags {
+    AudioUnitFlags {
+        sandbox_safe: flags & ffi::AU_COMPONENT_FLAG_SANDBOX_SAFE != 0,
+        is_v3: flags & ffi::AU_COMPONENT_FLAG_IS_V3_AUDIO_UNIT != 0,
+        requires_async_instantiation: flags & ffi::AU_COMPONENT_FLAG_REQUIRES_ASYNC_INSTANTIATION
+            != 0,
+        can_load_in_process: flags & ffi::AU_COMPONENT_FLAG_CAN_LOAD_IN_PROCESS != 0,
+    }
+}
+
 /// Safely convert a fixed-size C char array to a Rust String
 ///
 /// This uses bounded string conversion to prevent UB even if the C++ code
@@ -46,3 +85,30 @@ pub(crate) unsafe fn c_array_to_string(arr: &[i8], field_name: &str) -> Result<S
         .map_err(|e| Error::Other(format!("Invalid UTF-8 in {}: {}", field_name, e)))
         .map(|s| s.to_string())
 }
+
+/// Read a display name through a length-negotiating FFI call
+///
+/// Never truncates. Invalid UTF-8 is handled according to the configured
+/// [`StringDecoding`](crate::text::StringDecoding).
+/// Returns the call's non-negative result code along with the name.
+pub(crate) fn negotiated_name<F>(field_name: &str, call: F) -> Result<(i32, String)>
+where
+    F: FnMut(*mut c_char, *mut usize) -> i32,
+{
+    let (code, bytes) =
+        text::read_negotiated(ffi::RACK_AU_ERROR_BUFFER_TOO_SMALL, call).map_err(map_error)?;
+    Ok((code, text::decode_name(&bytes, field_name)?))
+}
+
+/// Read a path through a length-negotiating FFI call
+///
+/// Never truncates. The bytes are converted with the platform's path rules
+/// (see [`paths`](crate::paths)), so non-UTF-8 paths survive on Unix.
+pub(crate) fn negotiated_path<F>(call: F) -> Result<PathBuf>
+where
+    F: FnMut(*mut c_char, *mut usize) -> i32,
+{
+    let (_, bytes) =
+        text::read_negotiated(ffi::RACK_AU_ERROR_BUFFER_TOO_SMALL, call).map_err(map_error)?;
+    paths::from_ffi_bytes(bytes)
+}
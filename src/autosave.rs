@@ -0,0 +1,336 @@
+//! Periodic background snapshots with crash recovery
+//!
+//! [`Autosave`] runs a background thread that asks the host for a snapshot at a
+//! fixed interval and writes it to a rotating set of files in a directory.
+//! What goes into a snapshot is up to the host: the states of the plugins that
+//! changed since the last save, or a whole session document.
+//!
+//! While autosave is running a marker file sits in the directory; a clean
+//! [`stop()`](Autosave::stop) removes it. If the marker is still there on the
+//! next start, the previous run crashed and [`recover()`] returns its newest
+//! snapshot.
+//!
+//! Snapshots are taken on the autosave thread, never on the audio thread.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::autosave::{self, Autosave, AutosaveConfig};
+//! # use rack::dirty::DirtyTracker;
+//! # use std::sync::{Arc, Mutex};
+//! # fn restore(_: &[u8]) {}
+//! # fn example<P: PluginInstance + Send + 'static>(plugin: Arc<Mutex<P>>) -> Result<()> {
+//! let config = AutosaveConfig::new("/tmp/my-host/autosave");
+//!
+//! if let Some(recovered) = autosave::recover(&config.dir)? {
+//!     restore(&recovered.data);
+//! }
+//!
+//! let mut tracker = DirtyTracker::new();
+//! let autosave = Autosave::start(config, move || {
+//!     let plugin = plugin.lock().unwrap();
+//!     if !tracker.is_state_dirty(&*plugin)? {
+//!         return Ok(None); // Nothing changed
+//!     }
+//!     let state = plugin.get_state()?;
+//!     tracker.mark_saved(&*plugin)?;
+//!     Ok(Some(state))
+//! })?;
+//!
+//! // ... on clean shutdown ...
+//! autosave.stop();
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::events::{self, HostEvent};
+use crate::Result;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+/// Marker file present while autosave is running
+const RUNNING_MARKER: &str = "autosave.running";
+
+/// Prefix and extension of snapshot files
+const SNAPSHOT_PREFIX: &str = "autosave-";
+const SNAPSHOT_EXTENSION: &str = "snapshot";
+
+/// Where and how often to autosave
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AutosaveConfig {
+    /// Directory for snapshots (created if missing)
+    pub dir: PathBuf,
+
+    /// Time between snapshots
+    pub interval: Duration,
+
+    /// Number of snapshots to keep; older ones are deleted
+    pub keep: usize,
+}
+
+impl AutosaveConfig {
+    /// Snapshot into `dir` every minute, keeping the last five
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            interval: Duration::from_secs(60),
+            keep: 5,
+        }
+    }
+}
+
+/// A snapshot left behind by a run that didn't shut down cleanly
+#[derive(Debug, Clone)]
+pub struct Recovered {
+    /// The snapshot file
+    pub path: PathBuf,
+
+    /// When the snapshot was written
+    pub saved_at: Option<SystemTime>,
+
+    /// The snapshot contents
+    pub data: Vec<u8>,
+}
+
+/// A running autosave thread
+///
+/// Dropping it stops the thread like [`stop()`](Self::stop).
+pub struct Autosave {
+    dir: PathBuf,
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Autosave {
+    /// Start autosaving
+    ///
+    /// `snapshot` is called on the autosave thread every `config.interval`. It
+    /// returns the bytes to save, or `None` to skip this round (e.g. when
+    /// nothing changed). Errors from `snapshot` or from writing the file are
+    /// reported as [`HostEvent::Error`]s and autosaving carries on.
+    ///
+    /// Call [`recover()`] first: starting marks the directory as in use, so a
+    /// previous crash can no longer be detected.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory or marker file can't be created, or
+    /// the thread can't be started
+    pub fn start<F>(config: AutosaveConfig, mut snapshot: F) -> Result<Self>
+    where
+        F: FnMut() -> Result<Option<Vec<u8>>> + Send + 'static,
+    {
+        std::fs::create_dir_all(&config.dir)?;
+        std::fs::write(config.dir.join(RUNNING_MARKER), b"")?;
+
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        let dir = config.dir.clone();
+        let thread = std::thread::Builder::new()
+            .name("rack-autosave".to_string())
+            .spawn(move || {
+                let mut sequence = snapshots(&config.dir)
+                    .ok()
+                    .and_then(|s| s.last().map(|&(n, _)| n + 1))
+                    .unwrap_or(0);
+
+                // Wakes up every interval until told to stop
+                while let Err(mpsc::RecvTimeoutError::Timeout) =
+                    stop_rx.recv_timeout(config.interval)
+                {
+                    let result = snapshot().and_then(|data| match data {
+                        Some(data) => {
+                            write_snapshot(&config.dir, sequence, &data)?;
+                            sequence += 1;
+                            prune(&config.dir, config.keep)
+                        }
+                        None => Ok(()),
+                    });
+                    if let Err(error) = result {
+                        events::emit(HostEvent::Error {
+                            info: None,
+                            error: &error,
+                        });
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            dir,
+            stop: Some(stop_tx),
+            thread: Some(thread),
+        })
+    }
+
+    /// Stop autosaving and mark the shutdown as clean
+    ///
+    /// Waits for a snapshot in progress to finish. Snapshots are kept.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        // Dropping the sender wakes the thread
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        let _ = std::fs::remove_file(self.dir.join(RUNNING_MARKER));
+    }
+}
+
+impl Drop for Autosave {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Load the newest snapshot if the previous run didn't stop cleanly
+///
+/// Returns `None` if the last run shut down cleanly or left no snapshot.
+///
+/// # Errors
+///
+/// Returns an error if the directory or snapshot can't be read
+pub fn recover(dir: impl AsRef<Path>) -> Result<Option<Recovered>> {
+    let dir = dir.as_ref();
+    if !dir.join(RUNNING_MARKER).exists() {
+        return Ok(None);
+    }
+    let Some(path) = latest(dir)? else {
+        return Ok(None);
+    };
+
+    let data = std::fs::read(&path)?;
+    let saved_at = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+    Ok(Some(Recovered {
+        path,
+        saved_at,
+        data,
+    }))
+}
+
+/// Path of the newest snapshot in `dir`, regardless of how the last run ended
+///
+/// # Errors
+///
+/// Returns an error if the directory can't be read
+pub fn latest(dir: impl AsRef<Path>) -> Result<Option<PathBuf>> {
+    let dir = dir.as_ref();
+    if !dir.exists() {
+        return Ok(None);
+    }
+    Ok(snapshots(dir)?.pop().map(|(_, path)| path))
+}
+
+/// Snapshot files in `dir`, oldest first
+fn snapshots(dir: &Path) -> Result<Vec<(u64, PathBuf)>> {
+    let mut found = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some(SNAPSHOT_EXTENSION) {
+            continue;
+        }
+        let sequence = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.strip_prefix(SNAPSHOT_PREFIX))
+            .and_then(|n| n.parse::<u64>().ok());
+        if let Some(sequence) = sequence {
+            found.push((sequence, path));
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+fn write_snapshot(dir: &Path, sequence: u64, data: &[u8]) -> Result<()> {
+    let path = dir.join(format!(
+        "{}{:08}.{}",
+        SNAPSHOT_PREFIX, sequence, SNAPSHOT_EXTENSION
+    ));
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn prune(dir: &Path, keep: usize) -> Result<()> {
+    let found = snapshots(dir)?;
+    let excess = found.len().saturating_sub(keep.max(1));
+    for (_, path) in &found[..excess] {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rack-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_rotation_and_clean_stop() {
+        let dir = temp_dir("autosave-rotation");
+        let config = AutosaveConfig {
+            dir: dir.clone(),
+            interval: Duration::from_millis(5),
+            keep: 3,
+        };
+
+        let (taken, snapshots_taken) = std::sync::mpsc::channel();
+        let mut counter = 0u8;
+        let autosave = Autosave::start(config, move || {
+            counter = counter.wrapping_add(1);
+            let _ = taken.send(counter);
+            Ok(Some(vec![counter]))
+        })
+        .unwrap();
+
+        // Wait until enough snapshots were written to rotate (each is written
+        // before the next one is taken)
+        let deadline = std::time::Instant::now() + Duration::from_secs(30);
+        loop {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            let taken = snapshots_taken
+                .recv_timeout(remaining)
+                .expect("autosave took no snapshots");
+            if taken > 5 {
+                break;
+            }
+        }
+
+        // Crashed runs leave the marker behind
+        assert!(recover(&dir).unwrap().is_some());
+
+        autosave.stop();
+        assert!(recover(&dir).unwrap().is_none());
+        assert!(snapshots(&dir).unwrap().len() <= 3);
+        assert!(latest(&dir).unwrap().is_some());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_recover_newest_snapshot() {
+        let dir = temp_dir("autosave-recover");
+        std::fs::create_dir_all(&dir).unwrap();
+        write_snapshot(&dir, 9, b"old").unwrap();
+        write_snapshot(&dir, 10, b"new").unwrap();
+        assert!(recover(&dir).unwrap().is_none());
+
+        std::fs::write(dir.join(RUNNING_MARKER), b"").unwrap();
+        let recovered = recover(&dir).unwrap().unwrap();
+        assert_eq!(recovered.data, b"new");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
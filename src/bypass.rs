@@ -0,0 +1,187 @@
+//! Click-free bypass for plugins without one of their own
+//!
+//! [`PluginInstance::set_bypass()`] uses a plugin's own bypass where the
+//! format has one, so the plugin can ramp its processing down and keep its
+//! latency. Plugins without one are bypassed by the host with a
+//! [`HostBypass`]: the plugin keeps processing, and its output crossfades to
+//! its input (delayed by the plugin's latency, so the two line up) over a few
+//! milliseconds instead of switching abruptly.
+//!
+//! Plugin types outside the crate can use `HostBypass` to implement
+//! `set_bypass()` the same way.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # fn example(mut plugin: impl PluginInstance) -> Result<()> {
+//! plugin.initialize(48000.0, 512)?;
+//! plugin.set_bypass(true)?;
+//! assert!(plugin.is_bypassed());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+/// How long the host-side crossfade takes
+pub const FADE_TIME: Duration = Duration::from_millis(10);
+
+/// Host-side bypass: crossfades a plugin's output with its input
+///
+/// Call [`prepare()`](Self::prepare) when the plugin is initialized, and
+/// [`process()`](Self::process) after each block the plugin processes. Not
+/// bypassed, and once fully faded back in, the output is left as the plugin
+/// wrote it.
+#[derive(Debug, Clone)]
+pub struct HostBypass {
+    bypassed: bool,
+    /// How much of the plugin's output is heard, from 0 (bypassed) to 1
+    wet: f32,
+    /// Change of `wet` per sample while fading
+    step: f32,
+    /// The input delayed by the plugin's latency, per output channel
+    lines: Vec<Vec<f32>>,
+    position: usize,
+}
+
+impl Default for HostBypass {
+    fn default() -> Self {
+        Self {
+            bypassed: false,
+            wet: 1.0,
+            step: 1.0,
+            lines: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl HostBypass {
+    /// Create a bypass that isn't engaged
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Size the crossfade and latency compensation for a plugin's
+    /// configuration
+    ///
+    /// Allocates; call it from `initialize()`, not the audio thread.
+    pub fn prepare(&mut self, sample_rate: f64, channels: usize, latency: usize) {
+        let fade = (FADE_TIME.as_secs_f64() * sample_rate).max(1.0);
+        self.step = (1.0 / fade) as f32;
+        self.lines = if latency > 0 {
+            vec![vec![0.0; latency]; channels]
+        } else {
+            Vec::new()
+        };
+        self.position = 0;
+        self.wet = if self.bypassed { 0.0 } else { 1.0 };
+    }
+
+    /// Engage or release the bypass; the change fades in over the next
+    /// blocks
+    pub fn set_bypassed(&mut self, bypassed: bool) {
+        self.bypassed = bypassed;
+    }
+
+    /// Whether the bypass is engaged (it may still be fading)
+    pub fn is_bypassed(&self) -> bool {
+        self.bypassed
+    }
+
+    /// Mix the input into the plugin's output, as far as the bypass has
+    /// faded
+    ///
+    /// A mono input is heard on every output channel; outputs without a
+    /// matching input (e.g. an instrument's) fade to silence.
+    pub fn process(&mut self, inputs: &[&[f32]], outputs: &mut [&mut [f32]], num_frames: usize) {
+        let target = if self.bypassed { 0.0 } else { 1.0 };
+        if num_frames == 0 || (self.wet == target && target == 1.0 && self.lines.is_empty()) {
+            return;
+        }
+
+        let start = self.wet;
+        let step = if target > start {
+            self.step
+        } else {
+            -self.step
+        };
+        let wet = |i: usize| {
+            let wet = start + step * (i + 1) as f32;
+            if step > 0.0 {
+                wet.min(target)
+            } else {
+                wet.max(target)
+            }
+        };
+
+        for (ch, output) in outputs.iter_mut().enumerate() {
+            let input = match inputs.len() {
+                0 => None,
+                1 => Some(inputs[0]),
+                _ => inputs.get(ch).copied(),
+            };
+            let mut line = self.lines.get_mut(ch);
+            let mut position = self.position;
+            for (i, sample) in output[..num_frames].iter_mut().enumerate() {
+                let mut dry = input.map_or(0.0, |input| input[i]);
+                if let Some(line) = line.as_deref_mut() {
+                    std::mem::swap(&mut line[position], &mut dry);
+                    position = (position + 1) % line.len();
+                }
+                let wet = wet(i);
+                *sample = *sample * wet + dry * (1.0 - wet);
+            }
+        }
+
+        if let Some(line) = self.lines.first() {
+            self.position = (self.position + num_frames) % line.len();
+        }
+        self.wet = wet(num_frames - 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_bypass_crossfades() {
+        // 10 samples of fade at 1 kHz
+        let mut bypass = HostBypass::new();
+        bypass.prepare(1000.0, 1, 0);
+        let input = [1.0f32; 20];
+        let mut output = [0.0f32; 20];
+        bypass.process(&[&input], &mut [&mut output], 20);
+        assert_eq!(output, [0.0; 20]);
+
+        bypass.set_bypassed(true);
+        bypass.process(&[&input], &mut [&mut output], 20);
+        assert!((output[0] - 0.1).abs() < 1e-6);
+        assert!(output.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(output[9..], [1.0; 11]);
+
+        bypass.set_bypassed(false);
+        let mut output = [0.0f32; 20];
+        bypass.process(&[&input], &mut [&mut output], 20);
+        assert!((output[0] - 0.9).abs() < 1e-6);
+        assert_eq!(output[9..], [0.0; 11]);
+    }
+
+    #[test]
+    fn test_host_bypass_compensates_latency() {
+        let mut bypass = HostBypass::new();
+        bypass.set_bypassed(true);
+        bypass.prepare(1000.0, 2, 3);
+        let input = [1.0f32, 2.0, 3.0, 4.0, 5.0];
+        let mut left = [9.0f32; 5];
+        let mut right = [9.0f32; 5];
+        bypass.process(&[&input], &mut [&mut left, &mut right], 5);
+        assert_eq!(left, [0.0, 0.0, 0.0, 1.0, 2.0]);
+        assert_eq!(left, right);
+
+        bypass.process(&[&input], &mut [&mut left, &mut right], 5);
+        assert_eq!(left, [3.0, 4.0, 5.0, 1.0, 2.0]);
+    }
+}
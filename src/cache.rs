@@ -0,0 +1,677 @@
+//! Scan results remembered between sessions
+//!
+//! A [`ScanCache`] records what a scan found: each plugin's version and a hash
+//! of its bundle's files. Comparing a new scan against it tells a host what
+//! changed while it wasn't running, so it can tell users "3 plugins updated
+//! since last session" or re-validate only the plugins that changed.
+//!
+//! A plugin is [`Updated`](ScanChange::Updated) when the same unique ID (in the
+//! same format) comes back with a different version or different bundle
+//! contents, which also catches vendors that ship fixes without bumping the
+//! version.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::cache::ScanCache;
+//! # fn example(scanner: &impl PluginScanner) -> rack::Result<()> {
+//! let mut cache = ScanCache::open("scan-cache.txt")?;
+//! let diff = scanner.diff_with_cache(&mut cache)?;
+//!
+//! if !diff.updated().is_empty() {
+//!     println!("{} plugins updated since last session", diff.updated().len());
+//! }
+//! for plugin in diff.added() {
+//!     println!("new: {}", plugin.name);
+//! }
+//!
+//! cache.save()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::identity::PluginIdentity;
+use crate::metadata::{escape, unescape};
+use crate::{Error, PluginFormat, PluginInfo, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Header written at the top of saved cache files
+const FILE_HEADER: &str = "# rack scan cache v1";
+
+/// Deepest directory level inside a bundle included in its hash
+const MAX_BUNDLE_DEPTH: usize = 8;
+
+/// A plugin as recorded by an earlier scan
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedPlugin {
+    /// Plugin name
+    pub name: String,
+
+    /// Manufacturer/vendor name
+    pub manufacturer: String,
+
+    /// Plugin format
+    pub format: PluginFormat,
+
+    /// Unique identifier for the plugin
+    pub unique_id: String,
+
+    /// Plugin version
+    pub version: u32,
+
+    /// Path to the plugin bundle
+    pub path: PathBuf,
+
+    /// Hash of the bundle's file names, sizes and modification times
+    ///
+    /// `None` if the bundle couldn't be read.
+    pub bundle_hash: Option<u64>,
+}
+
+impl CachedPlugin {
+    /// Record a plugin, hashing its bundle
+    pub fn from_info(info: &PluginInfo) -> Self {
+        Self::with_hash(info, bundle_hash(&info.canonical_path))
+    }
+
+    fn with_hash(info: &PluginInfo, bundle_hash: Option<u64>) -> Self {
+        Self {
+            name: info.name.clone(),
+            manufacturer: info.manufacturer.clone(),
+            format: info.format,
+            unique_id: info.unique_id.clone(),
+            version: info.version,
+            path: info.path.clone(),
+            bundle_hash,
+        }
+    }
+
+    /// The plugin's identity
+    ///
+    /// The bundle hash isn't a content hash, so the identity has none.
+    pub fn identity(&self) -> PluginIdentity {
+        PluginIdentity::new(&self.manufacturer, &self.name).with_id(self.format, &self.unique_id)
+    }
+
+    /// Whether `other` is a different build of the same plugin
+    fn is_updated_by(&self, other: &CachedPlugin) -> bool {
+        let hash_changed = match (self.bundle_hash, other.bundle_hash) {
+            (Some(a), Some(b)) => a != b,
+            _ => false,
+        };
+        self.version != other.version || hash_changed
+    }
+}
+
+/// A difference between a scan and the cache
+#[derive(Debug, Clone)]
+pub enum ScanChange {
+    /// A plugin that wasn't in the cache
+    Added(PluginInfo),
+
+    /// A cached plugin the scan didn't find
+    Removed(CachedPlugin),
+
+    /// A plugin whose version or bundle contents changed
+    ///
+    /// Downgrades are reported here too; compare the versions to tell.
+    Updated {
+        /// The plugin as cached
+        previous: CachedPlugin,
+
+        /// The plugin as found now
+        current: PluginInfo,
+    },
+}
+
+/// Everything that changed between the cache and a scan
+#[derive(Debug, Clone, Default)]
+pub struct ScanDiff {
+    /// Changes in scan order, followed by removals in cache order
+    pub changes: Vec<ScanChange>,
+}
+
+impl ScanDiff {
+    /// Check whether nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Plugins that weren't in the cache
+    pub fn added(&self) -> Vec<&PluginInfo> {
+        self.changes
+            .iter()
+            .filter_map(|c| match c {
+                ScanChange::Added(info) => Some(info),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Cached plugins that are gone
+    pub fn removed(&self) -> Vec<&CachedPlugin> {
+        self.changes
+            .iter()
+            .filter_map(|c| match c {
+                ScanChange::Removed(cached) => Some(cached),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Plugins that changed, as `(previous, current)`
+    pub fn updated(&self) -> Vec<(&CachedPlugin, &PluginInfo)> {
+        self.changes
+            .iter()
+            .filter_map(|c| match c {
+                ScanChange::Updated { previous, current } => Some((previous, current)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Persistent record of the plugins found by the last scan
+#[derive(Debug, Clone, Default)]
+pub struct ScanCache {
+    entries: BTreeMap<(String, String), CachedPlugin>,
+    path: Option<PathBuf>,
+}
+
+impl ScanCache {
+    /// Create an empty in-memory cache
+    ///
+    /// Every plugin of the first scan compared against it is
+    /// [`Added`](ScanChange::Added).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open a cache backed by a file
+    ///
+    /// Loads the file if it exists; otherwise starts empty. [`save()`](Self::save)
+    /// writes back to the same file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or parsed
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut cache = match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::new(),
+            Err(e) => return Err(e.into()),
+        };
+        cache.path = Some(path.to_path_buf());
+        Ok(cache)
+    }
+
+    /// Save the cache to the file it was opened from
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache was not opened from a file, or writing fails
+    pub fn save(&self) -> Result<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| Error::Other("Scan cache has no backing file".to_string()))?;
+        self.save_to(path)
+    }
+
+    /// Save the cache to a specific file
+    ///
+    /// Written to a temporary sibling first and renamed into place, like
+    /// [`MetadataStore::save_to()`](crate::metadata::MetadataStore::save_to).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails
+    pub fn save_to(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, self.serialize())?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Get a cached plugin
+    pub fn get(&self, format: PluginFormat, unique_id: &str) -> Option<&CachedPlugin> {
+        self.entries
+            .get(&(format.to_string(), unique_id.to_string()))
+    }
+
+    /// Find a cached plugin by identity
+    ///
+    /// Prefers an entry with the identity's unique ID for its format, then
+    /// one with the same vendor and product name in any format.
+    pub fn find(&self, identity: &PluginIdentity) -> Option<&CachedPlugin> {
+        identity
+            .ids
+            .iter()
+            .find_map(|(format, id)| self.get(*format, id))
+            .or_else(|| {
+                self.plugins()
+                    .find(|p| identity.matches_name(&p.manufacturer, &p.name))
+            })
+    }
+
+    /// Number of cached plugins
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// All cached plugins, ordered by format and unique ID
+    pub fn plugins(&self) -> impl Iterator<Item = &CachedPlugin> {
+        self.entries.values()
+    }
+
+    /// Compare a scan against the cache without changing it
+    ///
+    /// Hashes the bundles of all scanned plugins.
+    pub fn diff(&self, plugins: &[PluginInfo]) -> ScanDiff {
+        self.diff_snapshot(plugins, &snapshot(plugins))
+    }
+
+    /// Replace the cached plugins with the results of a scan
+    pub fn update(&mut self, plugins: &[PluginInfo]) {
+        self.replace(snapshot(plugins));
+    }
+
+    /// Compare a scan against the cache, then update the cache to it
+    ///
+    /// Same as [`diff()`](Self::diff) followed by [`update()`](Self::update),
+    /// but hashes each bundle only once.
+    pub fn refresh(&mut self, plugins: &[PluginInfo]) -> ScanDiff {
+        let current = snapshot(plugins);
+        let diff = self.diff_snapshot(plugins, &current);
+        self.replace(current);
+        diff
+    }
+
+    fn diff_snapshot(&self, plugins: &[PluginInfo], current: &[CachedPlugin]) -> ScanDiff {
+        let mut changes = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+
+        for (info, now) in plugins.iter().zip(current) {
+            let key = key(now);
+            match self.entries.get(&key) {
+                None => changes.push(ScanChange::Added(info.clone())),
+                Some(previous) if previous.is_updated_by(now) => {
+                    changes.push(ScanChange::Updated {
+                        previous: previous.clone(),
+                        current: info.clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+            seen.insert(key);
+        }
+
+        for (key, cached) in &self.entries {
+            if !seen.contains(key) {
+                changes.push(ScanChange::Removed(cached.clone()));
+            }
+        }
+
+        ScanDiff { changes }
+    }
+
+    fn replace(&mut self, plugins: Vec<CachedPlugin>) {
+        self.entries = plugins.into_iter().map(|p| (key(&p), p)).collect();
+    }
+
+    /// Serialize to the text file format
+    ///
+    /// One section per plugin, keyed by format and unique ID:
+    ///
+    /// ```text
+    /// [VST3 58E595CC2DB24EFFAD2B3D8B1B4B5C2A]
+    /// name = Synth
+    /// manufacturer = Acme
+    /// version = 65536
+    /// path = /usr/lib/vst3/Synth.vst3
+    /// bundle_hash = 9f86d081884c7d65
+    /// ```
+    fn serialize(&self) -> String {
+        let mut out = String::from(FILE_HEADER);
+        out.push('\n');
+
+        for ((format, id), plugin) in &self.entries {
+            out.push_str(&format!("\n[{} {}]\n", format, escape(id)));
+            out.push_str(&format!("name = {}\n", escape(&plugin.name)));
+            out.push_str(&format!(
+                "manufacturer = {}\n",
+                escape(&plugin.manufacturer)
+            ));
+            out.push_str(&format!("version = {}\n", plugin.version));
+            out.push_str(&format!(
+                "path = {}\n",
+                escape(&plugin.path.to_string_lossy())
+            ));
+            if let Some(hash) = plugin.bundle_hash {
+                out.push_str(&format!("bundle_hash = {:016x}\n", hash));
+            }
+        }
+
+        out
+    }
+
+    /// Parse the text file format
+    fn parse(text: &str) -> Result<Self> {
+        let mut cache = Self::new();
+        let mut current: Option<(String, String)> = None;
+
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || {
+                Error::InvalidFormat(format!(
+                    "Scan cache line {}: unexpected '{}'",
+                    line_number + 1,
+                    line
+                ))
+            };
+
+            if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let (format, id) = section.split_once(' ').ok_or_else(invalid)?;
+                let format = parse_format(format).ok_or_else(invalid)?;
+                let plugin = CachedPlugin {
+                    name: String::new(),
+                    manufacturer: String::new(),
+                    format,
+                    unique_id: unescape(id),
+                    version: 0,
+                    path: PathBuf::new(),
+                    bundle_hash: None,
+                };
+                let key = key(&plugin);
+                cache.entries.insert(key.clone(), plugin);
+                current = Some(key);
+                continue;
+            }
+
+            let key = current.as_ref().ok_or_else(invalid)?;
+            let (name, value) = line.split_once('=').ok_or_else(invalid)?;
+            let value = value.trim();
+            let plugin = cache.entries.get_mut(key).ok_or_else(invalid)?;
+
+            match name.trim() {
+                "name" => plugin.name = unescape(value),
+                "manufacturer" => plugin.manufacturer = unescape(value),
+                "version" => plugin.version = value.parse().map_err(|_| invalid())?,
+                "path" => plugin.path = PathBuf::from(unescape(value)),
+                "bundle_hash" => {
+                    plugin.bundle_hash =
+                        Some(u64::from_str_radix(value, 16).map_err(|_| invalid())?)
+                }
+                // Unknown keys come from newer versions; keep going
+                _ => {}
+            }
+        }
+
+        Ok(cache)
+    }
+}
+
+/// Cache key of a plugin: format and unique ID
+fn key(plugin: &CachedPlugin) -> (String, String) {
+    (plugin.format.to_string(), plugin.unique_id.clone())
+}
+
+/// Inverse of `PluginFormat`'s `Display`
+pub(crate) fn parse_format(s: &str) -> Option<PluginFormat> {
+    match s {
+        "AU" => Some(PluginFormat::AudioUnit),
+        "VST3" => Some(PluginFormat::Vst3),
+        "CLAP" => Some(PluginFormat::Clap),
+        "LV2" => Some(PluginFormat::Lv2),
+        "VST2" => Some(PluginFormat::Vst2),
+        "Unknown" => Some(PluginFormat::Unknown),
+        _ => None,
+    }
+}
+
+/// Record a scan, hashing each bundle once even if it holds several plugins
+fn snapshot(plugins: &[PluginInfo]) -> Vec<CachedPlugin> {
+    let mut hashes: HashMap<&Path, Option<u64>> = HashMap::new();
+    plugins
+        .iter()
+        .map(|info| {
+            let hash = *hashes
+                .entry(&info.canonical_path)
+                .or_insert_with(|| bundle_hash(&info.canonical_path));
+            CachedPlugin::with_hash(info, hash)
+        })
+        .collect()
+}
+
+/// Hash the names, sizes and modification times of the files in a bundle
+///
+/// Uses FNV-1a so hashes stay comparable across Rust versions. Symlinks inside
+/// the bundle aren't followed. Returns `None` if the bundle can't be read.
+pub fn bundle_hash(path: &Path) -> Option<u64> {
+    hash_bundle(path, false)
+}
+
+/// Hash the names and contents of the files in a bundle
+///
+/// Unlike [`bundle_hash()`], the result doesn't depend on where or when the
+/// bundle was installed, so it identifies the same build after a reinstall or
+/// on another machine (see
+/// [`PluginIdentity::content_hash`](crate::identity::PluginIdentity::content_hash)).
+/// Reads every file, so it is much slower. Returns `None` if the bundle can't
+/// be read.
+pub fn content_hash(path: &Path) -> Option<u64> {
+    hash_bundle(path, true)
+}
+
+fn hash_bundle(path: &Path, contents: bool) -> Option<u64> {
+    let mut hash = Fnv1a::new();
+    let metadata = std::fs::metadata(path).ok()?;
+    if metadata.is_dir() {
+        hash_dir(&mut hash, path, Path::new(""), 0, contents).ok()?;
+    } else {
+        hash_file(&mut hash, path, Path::new(""), &metadata, contents).ok()?;
+    }
+    Some(hash.finish())
+}
+
+fn hash_dir(
+    hash: &mut Fnv1a,
+    root: &Path,
+    relative: &Path,
+    depth: usize,
+    contents: bool,
+) -> std::io::Result<()> {
+    let mut entries =
+        std::fs::read_dir(root.join(relative))?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let relative = relative.join(entry.file_name());
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            hash.write(relative.to_string_lossy().as_bytes());
+            if depth < MAX_BUNDLE_DEPTH {
+                hash_dir(hash, root, &relative, depth + 1, contents)?;
+            }
+        } else {
+            hash_file(hash, &entry.path(), &relative, &metadata, contents)?;
+        }
+    }
+    Ok(())
+}
+
+fn hash_file(
+    hash: &mut Fnv1a,
+    path: &Path,
+    relative: &Path,
+    metadata: &std::fs::Metadata,
+    contents: bool,
+) -> std::io::Result<()> {
+    hash.write(relative.to_string_lossy().as_bytes());
+    hash.write(&metadata.len().to_le_bytes());
+    if contents {
+        if metadata.file_type().is_file() {
+            hash.write(&std::fs::read(path)?);
+        }
+    } else {
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        hash.write(&modified.as_nanos().to_le_bytes());
+    }
+    Ok(())
+}
+
+/// 64-bit FNV-1a
+pub(crate) struct Fnv1a(u64);
+
+impl Fnv1a {
+    pub(crate) fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        // Separator, so ("ab", "c") and ("a", "bc") differ
+        self.0 ^= 0xff;
+        self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PluginType;
+
+    fn plugin(name: &str, version: u32, path: &Path) -> PluginInfo {
+        PluginInfo::new(
+            name.to_string(),
+            "Acme".to_string(),
+            version,
+            PluginType::Effect,
+            path.to_path_buf(),
+            format!("acme-{}", name.to_lowercase()),
+        )
+        .with_format(PluginFormat::Vst3)
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rack-cache-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_diff_reports_changes() {
+        let dir = temp_dir("diff");
+        let bundle = dir.join("Delay.vst3");
+        std::fs::create_dir_all(bundle.join("Contents")).unwrap();
+        std::fs::write(bundle.join("Contents/Delay.so"), b"v1").unwrap();
+
+        let mut cache = ScanCache::new();
+        let first = vec![
+            plugin("Delay", 1, &bundle),
+            plugin("Reverb", 1, &dir.join("Reverb.vst3")),
+        ];
+        let diff = cache.refresh(&first);
+        assert_eq!(diff.added().len(), 2);
+        assert!(cache.refresh(&first).is_empty());
+
+        // Same version, new binary: still an update
+        std::fs::write(bundle.join("Contents/Delay.so"), b"v1.0.1").unwrap();
+        let second = vec![
+            plugin("Delay", 1, &bundle),
+            plugin("Chorus", 1, &dir.join("Chorus.vst3")),
+        ];
+        let diff = cache.refresh(&second);
+        let updated = diff.updated();
+        assert_eq!(updated.len(), 1);
+        assert_eq!(updated[0].1.name, "Delay");
+        assert_eq!(diff.added()[0].name, "Chorus");
+        assert_eq!(diff.removed()[0].name, "Reverb");
+
+        // A version bump alone is an update, even without a readable bundle
+        let third = vec![
+            plugin("Delay", 1, &bundle),
+            plugin("Chorus", 2, &dir.join("Chorus.vst3")),
+        ];
+        let diff = cache.diff(&third);
+        assert_eq!(diff.updated().len(), 1);
+        assert_eq!(diff.updated()[0].0.version, 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = temp_dir("round-trip");
+        let bundle = dir.join("Tape [Echo].vst3");
+        std::fs::create_dir_all(&bundle).unwrap();
+
+        let mut cache = ScanCache::new();
+        cache.update(&[plugin("Tape = Echo", 3, &bundle)]);
+        let file = dir.join("cache.txt");
+        cache.save_to(&file).unwrap();
+
+        let loaded = ScanCache::open(&file).unwrap();
+        assert_eq!(loaded.len(), 1);
+        let cached = loaded.get(PluginFormat::Vst3, "acme-tape = echo").unwrap();
+        assert_eq!(cached, cache.plugins().next().unwrap());
+        assert!(cached.bundle_hash.is_some());
+
+        assert!(ScanCache::parse("version = 1").is_err());
+        assert!(ScanCache::parse("[AAX foo]").is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_content_hash_and_find() {
+        let dir = temp_dir("content");
+        let first = dir.join("a/Delay.vst3");
+        let second = dir.join("b/Delay.vst3");
+        for bundle in [&first, &second] {
+            std::fs::create_dir_all(bundle.join("Contents")).unwrap();
+            std::fs::write(bundle.join("Contents/Delay.so"), b"v1").unwrap();
+        }
+        // Installed elsewhere, same contents
+        assert_eq!(content_hash(&first), content_hash(&second));
+        std::fs::write(second.join("Contents/Delay.so"), b"v2").unwrap();
+        assert_ne!(content_hash(&first), content_hash(&second));
+
+        let mut cache = ScanCache::new();
+        cache.update(&[plugin("Delay", 1, &first)]);
+        let cached = cache.plugins().next().unwrap();
+        assert_eq!(cache.find(&cached.identity()), Some(cached));
+        let renamed = PluginIdentity::new("ACME", "delay").with_id(PluginFormat::AudioUnit, "x");
+        assert_eq!(cache.find(&renamed), Some(cached));
+        assert_eq!(cache.find(&PluginIdentity::new("Acme", "Reverb")), None);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,778 @@
+//! C API (the `capi` feature)
+//!
+//! Exports rack to C, C++, Swift, C# and anything else that can call C:
+//! scanning every available format, loading plugins, processing, parameters,
+//! MIDI and state. The build writes the matching header to
+//! `target/<profile>/include/rack.h`; build the library itself with
+//! `cargo rustc --release --features capi --crate-type cdylib` (or
+//! `staticlib`).
+//!
+//! # Conventions
+//!
+//! - Functions returning `int32_t` return [`RACK_OK`] or a negative
+//!   `RACK_ERROR_*` code. [`rack_last_error()`] then describes the failure.
+//! - Objects are created by `rack_*_new()` or `rack_*_load()` and freed with
+//!   the matching `rack_*_free()`. Passing a null object is an error, never a
+//!   crash.
+//! - Strings are UTF-8 and nul-terminated.
+//! - A plugin may be used from any thread, but not from two at once. Audio
+//!   calls ([`rack_plugin_process()`], [`rack_plugin_set_parameter()`],
+//!   [`rack_plugin_send_midi()`]) don't allocate beyond what the plugin does.
+//! - Panics never cross into the caller; they return [`RACK_ERROR_PANIC`].
+//!
+//! # Example
+//!
+//! ```c
+//! RackScanner *scanner = rack_scanner_new();
+//! int32_t count = rack_scanner_scan(scanner);
+//! RackPlugin *plugin = count > 0 ? rack_scanner_load(scanner, 0) : NULL;
+//! if (!plugin || rack_plugin_initialize(plugin, 48000.0, 512) != RACK_OK) {
+//!     fprintf(stderr, "%s\n", rack_last_error());
+//! }
+//! ```
+
+use crate::guard::panic_message;
+use crate::midi::MidiEvent;
+use crate::unified::UnifiedScanner;
+use crate::{BoxedPlugin, Error, PluginInfo, PluginType, Result};
+use smallvec::SmallVec;
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::AssertUnwindSafe;
+use std::path::Path;
+
+/// Success
+pub const RACK_OK: i32 = 0;
+/// An AudioUnit call failed
+pub const RACK_ERROR_AUDIO_UNIT: i32 = -1;
+/// The plugin couldn't be found
+pub const RACK_ERROR_PLUGIN_NOT_FOUND: i32 = -2;
+/// The parameter index is out of range
+pub const RACK_ERROR_INVALID_PARAMETER: i32 = -3;
+/// The plugin isn't initialized
+pub const RACK_ERROR_NOT_INITIALIZED: i32 = -4;
+/// A plugin, bundle or state is malformed
+pub const RACK_ERROR_INVALID_FORMAT: i32 = -5;
+/// A file or device couldn't be accessed
+pub const RACK_ERROR_IO: i32 = -6;
+/// More frames than the plugin was initialized for
+pub const RACK_ERROR_BLOCK_TOO_LARGE: i32 = -7;
+/// Rust code panicked
+pub const RACK_ERROR_PANIC: i32 = -8;
+/// Any other failure, including invalid arguments
+pub const RACK_ERROR_OTHER: i32 = -9;
+
+/// Audio effect ([`RackPluginInfo::plugin_type`])
+pub const RACK_PLUGIN_TYPE_EFFECT: i32 = 0;
+/// Instrument or generator
+pub const RACK_PLUGIN_TYPE_INSTRUMENT: i32 = 1;
+/// Mixer
+pub const RACK_PLUGIN_TYPE_MIXER: i32 = 2;
+/// Format converter
+pub const RACK_PLUGIN_TYPE_FORMAT_CONVERTER: i32 = 3;
+/// Analyzer
+pub const RACK_PLUGIN_TYPE_ANALYZER: i32 = 4;
+/// Spatial audio processor
+pub const RACK_PLUGIN_TYPE_SPATIAL: i32 = 5;
+/// Anything else
+pub const RACK_PLUGIN_TYPE_OTHER: i32 = 6;
+
+/// A scanner for every plugin format available on the platform
+pub struct RackScanner {
+    scanner: UnifiedScanner,
+    /// Plugins found by the last scan, with their strings for C
+    plugins: Vec<(PluginInfo, InfoStrings)>,
+}
+
+/// A loaded plugin
+pub struct RackPlugin {
+    plugin: BoxedPlugin,
+}
+
+/// A plugin found by a scan
+///
+/// The strings belong to the scanner and stay valid until its next scan or
+/// until it is freed.
+#[repr(C)]
+pub struct RackPluginInfo {
+    pub name: *const c_char,
+    pub manufacturer: *const c_char,
+    /// Identifier within the format (AU component, VST3 class ID, ...)
+    pub unique_id: *const c_char,
+    pub path: *const c_char,
+    /// Format name: "AU", "VST3", "CLAP", "LV2" or "VST2"
+    pub format: *const c_char,
+    pub version: u32,
+    /// One of the `RACK_PLUGIN_TYPE_*` constants
+    pub plugin_type: i32,
+}
+
+/// A plugin parameter
+///
+/// Names and units too long for their fields are cut at a character boundary.
+#[repr(C)]
+pub struct RackParameterInfo {
+    pub name: [c_char; 256],
+    pub unit: [c_char; 32],
+    pub min: f32,
+    pub max: f32,
+    pub default_value: f32,
+}
+
+/// A MIDI message for [`rack_plugin_send_midi()`]
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct RackMidiEvent {
+    /// Frame within the next processed block
+    pub sample_offset: u32,
+    /// Status byte and up to two data bytes
+    pub data: [u8; 3],
+}
+
+struct InfoStrings {
+    name: CString,
+    manufacturer: CString,
+    unique_id: CString,
+    path: CString,
+    format: CString,
+}
+
+impl InfoStrings {
+    fn new(info: &PluginInfo) -> Self {
+        Self {
+            name: c_string(&info.name),
+            manufacturer: c_string(&info.manufacturer),
+            unique_id: c_string(&info.unique_id),
+            path: c_string(&info.path.to_string_lossy()),
+            format: c_string(&info.format.to_string()),
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// The version of rack, e.g. "0.4.8"
+#[no_mangle]
+pub extern "C" fn rack_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
+
+/// Describe the last failure on this thread
+///
+/// Returns null if nothing has failed. The message stays valid until the
+/// next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn rack_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Create a scanner for every available format, searching the default
+/// locations plus any in `RACK_PLUGIN_PATH`
+///
+/// Returns null on failure.
+#[no_mangle]
+pub extern "C" fn rack_scanner_new() -> *mut RackScanner {
+    create(|| {
+        Ok(RackScanner {
+            scanner: UnifiedScanner::new()?,
+            plugins: Vec::new(),
+        })
+    })
+}
+
+/// Free a scanner
+///
+/// # Safety
+///
+/// `scanner` must come from [`rack_scanner_new()`] and not be used again.
+/// Null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn rack_scanner_free(scanner: *mut RackScanner) {
+    if !scanner.is_null() {
+        drop(Box::from_raw(scanner));
+    }
+}
+
+/// Add a folder for every format to search
+///
+/// # Safety
+///
+/// `scanner` must be a live scanner and `path` a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rack_scanner_add_path(
+    scanner: *mut RackScanner,
+    path: *const c_char,
+) -> i32 {
+    call(|| {
+        let scanner = object(scanner)?;
+        scanner.scanner.add_path(Path::new(string(path)?))?;
+        Ok(RACK_OK)
+    })
+}
+
+/// Scan for plugins
+///
+/// Returns the number of plugins found, or an error code. The plugins are
+/// then available by index from [`rack_scanner_plugin_info()`] and
+/// [`rack_scanner_load()`].
+///
+/// # Safety
+///
+/// `scanner` must be a live scanner.
+#[no_mangle]
+pub unsafe extern "C" fn rack_scanner_scan(scanner: *mut RackScanner) -> i32 {
+    call(|| {
+        let scanner = object(scanner)?;
+        scanner.plugins = scanner
+            .scanner
+            .scan()?
+            .into_iter()
+            .map(|info| {
+                let strings = InfoStrings::new(&info);
+                (info, strings)
+            })
+            .collect();
+        Ok(scanner.plugins.len().min(i32::MAX as usize) as i32)
+    })
+}
+
+/// Describe a plugin found by the last scan
+///
+/// # Safety
+///
+/// `scanner` must be a live scanner and `info` point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn rack_scanner_plugin_info(
+    scanner: *const RackScanner,
+    index: usize,
+    info: *mut RackPluginInfo,
+) -> i32 {
+    call(|| {
+        let scanner = shared(scanner)?;
+        let out = object(info)?;
+        let (plugin, strings) = scanned(scanner, index)?;
+        *out = RackPluginInfo {
+            name: strings.name.as_ptr(),
+            manufacturer: strings.manufacturer.as_ptr(),
+            unique_id: strings.unique_id.as_ptr(),
+            path: strings.path.as_ptr(),
+            format: strings.format.as_ptr(),
+            version: plugin.version,
+            plugin_type: plugin_type(plugin.plugin_type),
+        };
+        Ok(RACK_OK)
+    })
+}
+
+/// Load a plugin found by the last scan
+///
+/// Returns null on failure. The plugin outlives the scanner.
+///
+/// # Safety
+///
+/// `scanner` must be a live scanner.
+#[no_mangle]
+pub unsafe extern "C" fn rack_scanner_load(
+    scanner: *const RackScanner,
+    index: usize,
+) -> *mut RackPlugin {
+    create(|| {
+        let scanner = shared(scanner)?;
+        let (info, _) = scanned(scanner, index)?;
+        Ok(RackPlugin {
+            plugin: scanner.scanner.load(info)?,
+        })
+    })
+}
+
+/// Free a plugin
+///
+/// # Safety
+///
+/// `plugin` must come from [`rack_scanner_load()`] and not be used again.
+/// Null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn rack_plugin_free(plugin: *mut RackPlugin) {
+    if !plugin.is_null() {
+        drop(Box::from_raw(plugin));
+    }
+}
+
+/// Prepare the plugin to process at `sample_rate` in blocks of up to
+/// `max_block_size` frames
+///
+/// # Safety
+///
+/// `plugin` must be a live plugin.
+#[no_mangle]
+pub unsafe extern "C" fn rack_plugin_initialize(
+    plugin: *mut RackPlugin,
+    sample_rate: f64,
+    max_block_size: usize,
+) -> i32 {
+    call(|| {
+        object(plugin)?
+            .plugin
+            .initialize(sample_rate, max_block_size)?;
+        Ok(RACK_OK)
+    })
+}
+
+/// Clear the plugin's internal state (delay lines, envelopes, ...)
+///
+/// # Safety
+///
+/// `plugin` must be a live plugin.
+#[no_mangle]
+pub unsafe extern "C" fn rack_plugin_reset(plugin: *mut RackPlugin) -> i32 {
+    call(|| {
+        object(plugin)?.plugin.reset()?;
+        Ok(RACK_OK)
+    })
+}
+
+/// Number of input channels, or 0 before initialization
+///
+/// # Safety
+///
+/// `plugin` must be a live plugin or null.
+#[no_mangle]
+pub unsafe extern "C" fn rack_plugin_input_channels(plugin: *const RackPlugin) -> usize {
+    plugin.as_ref().map_or(0, |p| p.plugin.input_channels())
+}
+
+/// Number of output channels, or 0 before initialization
+///
+/// # Safety
+///
+/// `plugin` must be a live plugin or null.
+#[no_mangle]
+pub unsafe extern "C" fn rack_plugin_output_channels(plugin: *const RackPlugin) -> usize {
+    plugin.as_ref().map_or(0, |p| p.plugin.output_channels())
+}
+
+/// The plugin's processing latency in samples
+///
+/// # Safety
+///
+/// `plugin` must be a live plugin or null.
+#[no_mangle]
+pub unsafe extern "C" fn rack_plugin_latency_samples(plugin: *const RackPlugin) -> usize {
+    plugin.as_ref().map_or(0, |p| p.plugin.latency_samples())
+}
+
+/// Process a block of `num_frames` frames
+///
+/// `inputs` and `outputs` are arrays of channel pointers (planar audio), one
+/// per channel the plugin reports.
+///
+/// # Safety
+///
+/// `plugin` must be a live plugin. Each channel pointer must be valid for
+/// `num_frames` floats, readable for inputs and writable for outputs, and
+/// outputs must not overlap inputs or each other.
+#[no_mangle]
+pub unsafe extern "C" fn rack_plugin_process(
+    plugin: *mut RackPlugin,
+    inputs: *const *const f32,
+    num_inputs: usize,
+    outputs: *const *mut f32,
+    num_outputs: usize,
+    num_frames: usize,
+) -> i32 {
+    call(|| {
+        let plugin = object(plugin)?;
+        let inputs: SmallVec<[&[f32]; 8]> = raw_slice(inputs, num_inputs)?
+            .iter()
+            .map(|&channel| raw_slice(channel, num_frames))
+            .collect::<Result<_>>()?;
+        let mut outputs: SmallVec<[&mut [f32]; 8]> = raw_slice(outputs, num_outputs)?
+            .iter()
+            .map(|&channel| slice_mut(channel, num_frames))
+            .collect::<Result<_>>()?;
+        plugin.plugin.process(&inputs, &mut outputs, num_frames)?;
+        Ok(RACK_OK)
+    })
+}
+
+/// Number of parameters
+///
+/// # Safety
+///
+/// `plugin` must be a live plugin or null.
+#[no_mangle]
+pub unsafe extern "C" fn rack_plugin_parameter_count(plugin: *const RackPlugin) -> usize {
+    plugin.as_ref().map_or(0, |p| p.plugin.parameter_count())
+}
+
+/// Describe a parameter
+///
+/// # Safety
+///
+/// `plugin` must be a live plugin and `info` point to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn rack_plugin_parameter_info(
+    plugin: *const RackPlugin,
+    index: usize,
+    info: *mut RackParameterInfo,
+) -> i32 {
+    call(|| {
+        let parameter = shared(plugin)?.plugin.parameter_info(index)?;
+        let out = object(info)?;
+        copy_str(&mut out.name, &parameter.name);
+        copy_str(&mut out.unit, &parameter.unit);
+        out.min = parameter.min;
+        out.max = parameter.max;
+        out.default_value = parameter.default;
+        Ok(RACK_OK)
+    })
+}
+
+/// Read a parameter's normalized value (0.0 to 1.0) into `value`
+///
+/// # Safety
+///
+/// `plugin` must be a live plugin and `value` point to a writable float.
+#[no_mangle]
+pub unsafe extern "C" fn rack_plugin_get_parameter(
+    plugin: *const RackPlugin,
+    index: usize,
+    value: *mut f32,
+) -> i32 {
+    call(|| {
+        let current = shared(plugin)?.plugin.get_parameter(index)?;
+        *object(value)? = current;
+        Ok(RACK_OK)
+    })
+}
+
+/// Set a parameter's normalized value (0.0 to 1.0)
+///
+/// # Safety
+///
+/// `plugin` must be a live plugin.
+#[no_mangle]
+pub unsafe extern "C" fn rack_plugin_set_parameter(
+    plugin: *mut RackPlugin,
+    index: usize,
+    value: f32,
+) -> i32 {
+    call(|| {
+        object(plugin)?.plugin.set_parameter(index, value)?;
+        Ok(RACK_OK)
+    })
+}
+
+/// Queue MIDI messages for the next processed block
+///
+/// Messages rack doesn't support (e.g. system exclusive) are an error; none
+/// of the batch is sent then.
+///
+/// # Safety
+///
+/// `plugin` must be a live plugin and `events` point to `count` events.
+#[no_mangle]
+pub unsafe extern "C" fn rack_plugin_send_midi(
+    plugin: *mut RackPlugin,
+    events: *const RackMidiEvent,
+    count: usize,
+) -> i32 {
+    call(|| {
+        let plugin = object(plugin)?;
+        let events: SmallVec<[MidiEvent; 16]> = raw_slice(events, count)?
+            .iter()
+            .map(|event| {
+                MidiEvent::from_bytes(&event.data, event.sample_offset).ok_or_else(|| {
+                    Error::Other(format!("Unsupported MIDI message {:02X?}", event.data))
+                })
+            })
+            .collect::<Result<_>>()?;
+        plugin.plugin.send_midi(&events)?;
+        Ok(RACK_OK)
+    })
+}
+
+/// Save the plugin's state
+///
+/// Writes the state's size to `size`, then the state to `buffer` if it's
+/// large enough. Call with a null `buffer` to get the size first.
+///
+/// # Safety
+///
+/// `plugin` must be a live plugin, `size` point to a writable `size_t`, and
+/// `buffer` be null or writable for `capacity` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rack_plugin_get_state(
+    plugin: *const RackPlugin,
+    buffer: *mut u8,
+    capacity: usize,
+    size: *mut usize,
+) -> i32 {
+    call(|| {
+        let state = shared(plugin)?.plugin.get_state()?;
+        *object(size)? = state.len();
+        if buffer.is_null() {
+            return Ok(RACK_OK);
+        }
+        if capacity < state.len() {
+            return Err(Error::Other(format!(
+                "State needs {} bytes, buffer holds {}",
+                state.len(),
+                capacity
+            )));
+        }
+        std::ptr::copy_nonoverlapping(state.as_ptr(), buffer, state.len());
+        Ok(RACK_OK)
+    })
+}
+
+/// Restore a state saved by [`rack_plugin_get_state()`]
+///
+/// # Safety
+///
+/// `plugin` must be a live plugin and `data` readable for `size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rack_plugin_set_state(
+    plugin: *mut RackPlugin,
+    data: *const u8,
+    size: usize,
+) -> i32 {
+    call(|| {
+        let plugin = object(plugin)?;
+        plugin.plugin.set_state(raw_slice(data, size)?)?;
+        Ok(RACK_OK)
+    })
+}
+
+/// The C code for an error
+fn code(error: &Error) -> i32 {
+    match error {
+        Error::AudioUnit(_) => RACK_ERROR_AUDIO_UNIT,
+        Error::PluginNotFound(_) => RACK_ERROR_PLUGIN_NOT_FOUND,
+        Error::InvalidParameter(_) => RACK_ERROR_INVALID_PARAMETER,
+        Error::NotInitialized => RACK_ERROR_NOT_INITIALIZED,
+        Error::InvalidFormat(_) => RACK_ERROR_INVALID_FORMAT,
+        Error::Io(_) => RACK_ERROR_IO,
+        Error::BlockTooLarge { .. } => RACK_ERROR_BLOCK_TOO_LARGE,
+        Error::Panic(_) => RACK_ERROR_PANIC,
+        Error::Other(_) => RACK_ERROR_OTHER,
+    }
+}
+
+fn plugin_type(plugin_type: PluginType) -> i32 {
+    match plugin_type {
+        PluginType::Effect => RACK_PLUGIN_TYPE_EFFECT,
+        PluginType::Instrument => RACK_PLUGIN_TYPE_INSTRUMENT,
+        PluginType::Mixer => RACK_PLUGIN_TYPE_MIXER,
+        PluginType::FormatConverter => RACK_PLUGIN_TYPE_FORMAT_CONVERTER,
+        PluginType::Analyzer => RACK_PLUGIN_TYPE_ANALYZER,
+        PluginType::Spatial => RACK_PLUGIN_TYPE_SPATIAL,
+        PluginType::Other => RACK_PLUGIN_TYPE_OTHER,
+    }
+}
+
+fn set_last_error(error: &Error) {
+    let message = c_string(&error.to_string());
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Run `f`, turning errors and panics into error codes
+fn call(f: impl FnOnce() -> Result<i32>) -> i32 {
+    let result = std::panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|payload| Err(Error::Panic(panic_message(payload.as_ref()))));
+    result.unwrap_or_else(|error| {
+        set_last_error(&error);
+        code(&error)
+    })
+}
+
+/// Run `f` and box its object for C, or return null on errors and panics
+fn create<T>(f: impl FnOnce() -> Result<T>) -> *mut T {
+    let mut created = None;
+    call(|| {
+        created = Some(f()?);
+        Ok(RACK_OK)
+    });
+    created.map_or(std::ptr::null_mut(), |object| {
+        Box::into_raw(Box::new(object))
+    })
+}
+
+/// Borrow an object passed from C
+unsafe fn object<'a, T>(pointer: *mut T) -> Result<&'a mut T> {
+    pointer
+        .as_mut()
+        .ok_or_else(|| Error::Other("Null pointer passed to rack".to_string()))
+}
+
+/// Borrow an object passed from C for reading
+unsafe fn shared<'a, T>(pointer: *const T) -> Result<&'a T> {
+    pointer
+        .as_ref()
+        .ok_or_else(|| Error::Other("Null pointer passed to rack".to_string()))
+}
+
+/// Borrow a string passed from C
+unsafe fn string<'a>(pointer: *const c_char) -> Result<&'a str> {
+    if pointer.is_null() {
+        return Err(Error::Other("Null string passed to rack".to_string()));
+    }
+    CStr::from_ptr(pointer)
+        .to_str()
+        .map_err(|_| Error::Other("String passed to rack isn't UTF-8".to_string()))
+}
+
+/// Borrow an array passed from C; null is fine when empty
+unsafe fn raw_slice<'a, T>(pointer: *const T, len: usize) -> Result<&'a [T]> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+    if pointer.is_null() {
+        return Err(Error::Other("Null array passed to rack".to_string()));
+    }
+    Ok(std::slice::from_raw_parts(pointer, len))
+}
+
+/// Borrow an output channel passed from C; null is fine when empty
+unsafe fn slice_mut<'a>(channel: *mut f32, num_frames: usize) -> Result<&'a mut [f32]> {
+    if num_frames == 0 {
+        return Ok(&mut []);
+    }
+    if channel.is_null() {
+        return Err(Error::Other("Null channel passed to rack".to_string()));
+    }
+    Ok(std::slice::from_raw_parts_mut(channel, num_frames))
+}
+
+fn scanned(scanner: &RackScanner, index: usize) -> Result<&(PluginInfo, InfoStrings)> {
+    scanner.plugins.get(index).ok_or_else(|| {
+        Error::PluginNotFound(format!(
+            "No plugin {} in the last scan ({} found)",
+            index,
+            scanner.plugins.len()
+        ))
+    })
+}
+
+/// A C copy of `text`, without any interior nul
+fn c_string(text: &str) -> CString {
+    CString::new(text.replace('\0', "")).unwrap_or_default()
+}
+
+/// Copy `text` into a fixed C buffer, cut at a character boundary
+fn copy_str(buffer: &mut [c_char], text: &str) {
+    let mut len = text.len().min(buffer.len().saturating_sub(1));
+    while !text.is_char_boundary(len) {
+        len -= 1;
+    }
+    for (out, byte) in buffer.iter_mut().zip(&text.as_bytes()[..len]) {
+        *out = *byte as c_char;
+    }
+    if let Some(end) = buffer.get_mut(len) {
+        *end = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan::ScannerConfig;
+    use crate::PluginFormat;
+
+    #[test]
+    fn test_c_api_round_trip() {
+        let dir = std::env::temp_dir().join(format!("rack-capi-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let _gain = crate::clap::test_plugin::install(&dir.join("gain.clap"));
+
+        let config = ScannerConfig::new().path(&dir).skip_default_paths(true);
+        let scanner = Box::into_raw(Box::new(RackScanner {
+            scanner: UnifiedScanner::with_config(config).unwrap(),
+            plugins: Vec::new(),
+        }));
+
+        unsafe {
+            let count = rack_scanner_scan(scanner);
+            assert!(count >= 1);
+            let mut info = std::mem::zeroed::<RackPluginInfo>();
+            let index = (0..count as usize)
+                .find(|&index| {
+                    assert_eq!(rack_scanner_plugin_info(scanner, index, &mut info), RACK_OK);
+                    CStr::from_ptr(info.format).to_str() == Ok(&PluginFormat::Clap.to_string())
+                })
+                .unwrap();
+
+            let plugin = rack_scanner_load(scanner, index);
+            assert!(!plugin.is_null());
+            rack_scanner_free(scanner);
+
+            // Errors come back as codes with a message
+            let input = [1.0f32; 16];
+            let mut output = [[0.0f32; 16]; 2];
+            let inputs = [input.as_ptr(), input.as_ptr()];
+            let outputs = [output[0].as_mut_ptr(), output[1].as_mut_ptr()];
+            assert_eq!(
+                rack_plugin_process(plugin, inputs.as_ptr(), 2, outputs.as_ptr(), 2, 16),
+                RACK_ERROR_NOT_INITIALIZED
+            );
+            assert!(!rack_last_error().is_null());
+            assert_eq!(
+                rack_plugin_set_parameter(std::ptr::null_mut(), 0, 0.0),
+                RACK_ERROR_OTHER
+            );
+
+            assert_eq!(rack_plugin_initialize(plugin, 48000.0, 16), RACK_OK);
+            assert_eq!(rack_plugin_output_channels(plugin), 2);
+            assert_eq!(
+                rack_plugin_process(plugin, inputs.as_ptr(), 2, outputs.as_ptr(), 2, 16),
+                RACK_OK
+            );
+
+            assert!(rack_plugin_parameter_count(plugin) > 0);
+            let mut parameter = std::mem::zeroed::<RackParameterInfo>();
+            assert_eq!(
+                rack_plugin_parameter_info(plugin, 0, &mut parameter),
+                RACK_OK
+            );
+            assert!(parameter.name[0] != 0);
+            assert_eq!(rack_plugin_set_parameter(plugin, 0, 0.25), RACK_OK);
+            let mut value = 0.0;
+            assert_eq!(rack_plugin_get_parameter(plugin, 0, &mut value), RACK_OK);
+
+            let note_on = RackMidiEvent {
+                sample_offset: 0,
+                data: [0x90, 60, 100],
+            };
+            assert_eq!(rack_plugin_send_midi(plugin, &note_on, 1), RACK_OK);
+
+            let mut size = 0;
+            let null = std::ptr::null_mut();
+            assert_eq!(rack_plugin_get_state(plugin, null, 0, &mut size), RACK_OK);
+            let mut state = vec![0u8; size];
+            assert_eq!(
+                rack_plugin_get_state(plugin, state.as_mut_ptr(), size, &mut size),
+                RACK_OK
+            );
+            assert_eq!(rack_plugin_set_state(plugin, state.as_ptr(), size), RACK_OK);
+
+            rack_plugin_free(plugin);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_copy_str_cuts_at_char_boundary() {
+        let mut buffer = [1 as c_char; 4];
+        copy_str(&mut buffer, "aé€");
+        assert_eq!(
+            buffer[..4],
+            [b'a' as c_char, 0xC3u8 as c_char, 0xA9u8 as c_char, 0]
+        );
+    }
+}
@@ -0,0 +1,1439 @@
+use crate::bypass::HostBypass;
+use crate::events::{self, HostEvent};
+use crate::host;
+use crate::node::BlockContext;
+use crate::quirks::{self, Quirk, Quirks};
+use crate::text::decode_name;
+use crate::{
+    Error, MidiEvent, ParameterChange, ParameterInfo, ParameterVisibility, PluginInfo,
+    PluginInstance, PresetInfo, Result,
+};
+use smallvec::SmallVec;
+use std::any::Any;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::io::{ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+
+use super::ffi;
+use super::library::Library;
+
+/// An instantiated CLAP plugin
+///
+/// The plugin is activated by [`initialize()`](PluginInstance::initialize)
+/// and starts processing on the first [`process()`](PluginInstance::process)
+/// call. Parameter changes made while it is processing are delivered with
+/// the next block; before that they are applied straight away.
+///
+/// CLAP plugins ask the host to call them back on the main thread (to apply
+/// a restart, rescan parameters, and so on). Call [`idle()`](Self::idle)
+/// regularly from the thread that manages the plugin to let that happen.
+///
+/// CLAP has no preset list of its own; presets are restored with
+/// [`set_state()`](PluginInstance::set_state).
+///
+/// # Thread Safety
+///
+/// This type is `Send` but not `Sync`:
+/// - `Send`: The plugin can be moved between threads safely
+/// - NOT `Sync`: Multiple threads should not access the plugin simultaneously
+///   without synchronization. Wrap in `Arc<Mutex<>>` if shared access is needed.
+pub struct ClapPlugin {
+    plugin: NonNull<ffi::clap_plugin>,
+    info: PluginInfo,
+    // Callbacks and requests from the plugin (boxed for a stable address)
+    host: Box<HostContext>,
+    // Plugin extensions (null if unsupported)
+    audio_ports: *const ffi::clap_plugin_audio_ports,
+    params: *const ffi::clap_plugin_params,
+    state: *const ffi::clap_plugin_state,
+    latency: *const ffi::clap_plugin_latency,
+    tail: *const ffi::clap_plugin_tail,
+    // Channels per audio port (queried during initialize)
+    input_ports: Vec<u32>,
+    output_ports: Vec<u32>,
+    input_channels: usize,
+    output_channels: usize,
+    // Pre-allocated pointer and buffer arrays for zero-allocation process() calls
+    input_ptrs: Vec<*mut f32>,
+    output_ptrs: Vec<*mut f32>,
+    input_buffers: Vec<ffi::clap_audio_buffer>,
+    output_buffers: Vec<ffi::clap_audio_buffer>,
+    // Events for the next process() call, in time order
+    events: Vec<InputEvent>,
+    // Index of the plugin's bypass parameter (found during initialize), or
+    // the host's bypass if it has none
+    bypass_param: Option<usize>,
+    bypass: HostBypass,
+    sample_rate: f64,
+    max_block_size: usize,
+    active: bool,
+    processing: bool,
+    // Timeline position of the next processed sample
+    sample_position: u64,
+    // Samples processed since activation (CLAP's steady time)
+    steady_time: i64,
+    // Quirks looked up when the plugin was loaded
+    quirks: Quirks,
+    // Dropped after the plugin is destroyed
+    _library: Arc<Library>,
+    // PhantomData<*const ()> makes this type !Sync while keeping it Send
+    _not_sync: PhantomData<*const ()>,
+}
+
+// Safety: ClapPlugin can be sent between threads because:
+// 1. Each plugin instance owns its plugin exclusively
+// 2. CLAP plugins may be used from any thread as long as calls don't overlap
+//    (the host decides which threads are "main" and "audio")
+unsafe impl Send for ClapPlugin {}
+
+// Note: ClapPlugin is NOT Sync due to PhantomData<*const ()>
+
+impl ClapPlugin {
+    /// Create a new CLAP plugin instance
+    pub(crate) fn new(info: &PluginInfo) -> Result<Self> {
+        let library = Library::open(&info.path)?;
+        let factory = library.plugin_factory()?;
+        let create = factory
+            .create_plugin
+            .ok_or_else(|| Error::InvalidFormat("CLAP factory can't create plugins".to_string()))?;
+
+        let unique_id = CString::new(info.unique_id.as_str())
+            .map_err(|_| Error::Other("Invalid unique_id (contains null byte)".to_string()))?;
+        let host = HostContext::new(info)?;
+
+        unsafe {
+            let ptr = create(factory, &host.host, unique_id.as_ptr());
+            let Some(plugin) = NonNull::new(ptr as *mut ffi::clap_plugin) else {
+                return Err(Error::PluginNotFound(format!(
+                    "Failed to create CLAP instance for {}",
+                    info.name
+                )));
+            };
+
+            let init = (*ptr).init;
+            if !init.is_some_and(|init| init(ptr)) {
+                if let Some(destroy) = (*ptr).destroy {
+                    destroy(ptr);
+                }
+                return Err(Error::Other(format!(
+                    "CLAP plugin {} failed to initialize",
+                    info.name
+                )));
+            }
+
+            let extension = |id: &[u8]| match (*ptr).get_extension {
+                Some(get_extension) => get_extension(ptr, id.as_ptr() as *const c_char),
+                None => std::ptr::null(),
+            };
+            Ok(Self {
+                plugin,
+                info: info.clone(),
+                host,
+                audio_ports: extension(ffi::CLAP_EXT_AUDIO_PORTS) as *const _,
+                params: extension(ffi::CLAP_EXT_PARAMS) as *const _,
+                state: extension(ffi::CLAP_EXT_STATE) as *const _,
+                latency: extension(ffi::CLAP_EXT_LATENCY) as *const _,
+                tail: extension(ffi::CLAP_EXT_TAIL) as *const _,
+                input_ports: Vec::new(),
+                output_ports: Vec::new(),
+                input_channels: 0,
+                output_channels: 0,
+                input_ptrs: Vec::new(),
+                output_ptrs: Vec::new(),
+                input_buffers: Vec::new(),
+                output_buffers: Vec::new(),
+                events: Vec::new(),
+                bypass_param: None,
+                bypass: HostBypass::new(),
+                sample_rate: 0.0,
+                max_block_size: 0,
+                active: false,
+                processing: false,
+                sample_position: 0,
+                steady_time: 0,
+                quirks: quirks::lookup(info),
+                _library: library,
+                _not_sync: PhantomData,
+            })
+        }
+    }
+
+    /// Run the work the plugin asked the host to do on the main thread
+    ///
+    /// Calls the plugin's `on_main_thread()` when it requested a callback,
+    /// reactivates it when it requested a restart, flushes parameter changes
+    /// and reports parameter, latency and tail changes as [`HostEvent`]s. Call this
+    /// regularly (e.g. from a UI timer) from the thread that manages the
+    /// plugin, never while another thread is processing it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a requested restart fails to reactivate the plugin
+    pub fn idle(&mut self) -> Result<()> {
+        let host = &self.host;
+        if host.callback_requested.swap(false, Ordering::AcqRel) {
+            unsafe {
+                if let Some(on_main_thread) = self.plugin.as_ref().on_main_thread {
+                    on_main_thread(self.plugin.as_ptr());
+                }
+            }
+        }
+
+        if host.params_flush_requested.swap(false, Ordering::AcqRel) && !self.processing {
+            self.flush_parameters();
+        }
+
+        let rescan = self.host.params_rescan.swap(0, Ordering::AcqRel);
+        if rescan & (ffi::CLAP_PARAM_RESCAN_INFO | ffi::CLAP_PARAM_RESCAN_ALL) != 0 {
+            events::emit(HostEvent::ParametersChanged { info: &self.info });
+        }
+
+        if self.host.latency_changed.swap(false, Ordering::AcqRel) {
+            events::emit(HostEvent::LatencyChanged {
+                info: &self.info,
+                samples: self.latency_samples(),
+            });
+        }
+
+        if self.host.tail_changed.swap(false, Ordering::AcqRel) {
+            events::emit(HostEvent::TailChanged {
+                info: &self.info,
+                seconds: self.tail_seconds(),
+            });
+        }
+
+        if self.host.restart_requested.swap(false, Ordering::AcqRel) && self.active {
+            let (inputs, outputs) = (self.input_channels, self.output_channels);
+            self.initialize(self.sample_rate, self.max_block_size)?;
+            if (inputs, outputs) != (self.input_channels, self.output_channels) {
+                events::emit(HostEvent::IoChanged { info: &self.info });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stop processing and deactivate, if active
+    fn deactivate(&mut self) {
+        unsafe {
+            let plugin = self.plugin.as_ref();
+            if self.processing {
+                if let Some(stop_processing) = plugin.stop_processing {
+                    stop_processing(self.plugin.as_ptr());
+                }
+                self.processing = false;
+            }
+            if self.active {
+                if let Some(deactivate) = plugin.deactivate {
+                    deactivate(self.plugin.as_ptr());
+                }
+                self.active = false;
+            }
+        }
+    }
+
+    /// Channel counts of the plugin's input or output ports
+    fn query_ports(&self, is_input: bool) -> Vec<u32> {
+        unsafe {
+            let Some(ports) = self.audio_ports.as_ref() else {
+                return Vec::new();
+            };
+            let (Some(count), Some(get)) = (ports.count, ports.get) else {
+                return Vec::new();
+            };
+            let plugin = self.plugin.as_ptr();
+            (0..count(plugin, is_input))
+                .map(|index| {
+                    let mut info: ffi::clap_audio_port_info = std::mem::zeroed();
+                    if get(plugin, index, is_input, &mut info) {
+                        info.channel_count
+                    } else {
+                        0
+                    }
+                })
+                .collect()
+        }
+    }
+
+    /// Parameter info from the plugin, by index
+    fn param_info(&self, index: usize) -> Result<ffi::clap_param_info> {
+        unsafe {
+            let get_info = self
+                .params
+                .as_ref()
+                .and_then(|params| params.get_info)
+                .ok_or(Error::InvalidParameter(index))?;
+            let index = u32::try_from(index).map_err(|_| Error::InvalidParameter(index))?;
+            let mut info: ffi::clap_param_info = std::mem::zeroed();
+            if !get_info(self.plugin.as_ptr(), index, &mut info) {
+                return Err(Error::InvalidParameter(index as usize));
+            }
+            Ok(info)
+        }
+    }
+
+    /// Hand queued parameter changes to the plugin outside of processing
+    fn flush_parameters(&mut self) {
+        let Some(flush) = (unsafe { self.params.as_ref() }).and_then(|params| params.flush) else {
+            return;
+        };
+        let changes: SmallVec<[InputEvent; 16]> = self
+            .events
+            .iter()
+            .filter(|event| matches!(event, InputEvent::Param(_)))
+            .copied()
+            .collect();
+        self.events
+            .retain(|event| !matches!(event, InputEvent::Param(_)));
+
+        let input = InputEvents::new(&changes);
+        let output = OutputEvents::new();
+        unsafe { flush(self.plugin.as_ptr(), input.as_raw(), output.as_raw()) };
+    }
+
+    /// Queue a change of parameter `index` to normalized `value`, `time`
+    /// frames into the next block
+    fn queue_parameter(&mut self, index: usize, value: f32, time: u32) -> Result<()> {
+        // CLAP parameters take plain values
+        let info = self.param_info(index)?;
+        let normalized = (value as f64).clamp(0.0, 1.0);
+        let mut plain = info.min_value + normalized * (info.max_value - info.min_value);
+        if info.flags & ffi::CLAP_PARAM_IS_STEPPED != 0 {
+            plain = plain.round();
+        }
+
+        self.queue(InputEvent::Param(ffi::clap_event_param_value {
+            header: event_header::<ffi::clap_event_param_value>(time, ffi::CLAP_EVENT_PARAM_VALUE),
+            param_id: info.id,
+            cookie: info.cookie,
+            note_id: -1,
+            port_index: -1,
+            channel: -1,
+            key: -1,
+            value: plain,
+        }));
+        Ok(())
+    }
+
+    /// Process a block, with `transport` if the host has one
+    fn process_block(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        transport: Option<&ffi::clap_event_transport>,
+    ) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        // Reject oversized blocks here rather than passing them to the plugin
+        if num_frames > self.max_block_size {
+            return Err(Error::BlockTooLarge {
+                max: self.max_block_size,
+                got: num_frames,
+            });
+        }
+
+        // Validate channel counts match plugin configuration
+        if inputs.len() != self.input_channels {
+            return Err(Error::Other(format!(
+                "Input channel count mismatch: plugin expects {}, got {}",
+                self.input_channels,
+                inputs.len()
+            )));
+        }
+        if outputs.len() != self.output_channels {
+            return Err(Error::Other(format!(
+                "Output channel count mismatch: plugin expects {}, got {}",
+                self.output_channels,
+                outputs.len()
+            )));
+        }
+
+        for (i, input) in inputs.iter().enumerate() {
+            if input.len() < num_frames {
+                return Err(Error::Other(format!(
+                    "Input channel {} has {} samples, need at least {}",
+                    i,
+                    input.len(),
+                    num_frames
+                )));
+            }
+        }
+        for (i, output) in outputs.iter().enumerate() {
+            if output.len() < num_frames {
+                return Err(Error::Other(format!(
+                    "Output channel {} has {} samples, need at least {}",
+                    i,
+                    output.len(),
+                    num_frames
+                )));
+            }
+        }
+
+        if !self.processing {
+            let started = unsafe {
+                match self.plugin.as_ref().start_processing {
+                    Some(start_processing) => start_processing(self.plugin.as_ptr()),
+                    None => true,
+                }
+            };
+            if !started {
+                return Err(Error::Other(format!(
+                    "CLAP plugin {} failed to start processing",
+                    self.info.name
+                )));
+            }
+            self.processing = true;
+        }
+
+        // The plugin only reads the inputs; CLAP buffers are mutable in
+        // general because they may be processed in place
+        for (ptr, input) in self.input_ptrs.iter_mut().zip(inputs) {
+            *ptr = input.as_ptr() as *mut f32;
+        }
+        for (ptr, output) in self.output_ptrs.iter_mut().zip(outputs.iter_mut()) {
+            if self.quirks.contains(Quirk::ClearOutputsBeforeProcess) {
+                output[..num_frames].fill(0.0);
+            }
+            *ptr = output.as_mut_ptr();
+        }
+
+        // MIDI sent for later blocks lands at the end of this one
+        let last_frame = num_frames.saturating_sub(1) as u32;
+        for event in &mut self.events {
+            let header = event.header_mut();
+            header.time = header.time.min(last_frame);
+        }
+        let input_events = InputEvents::new(&self.events);
+        let output_events = OutputEvents::new();
+
+        let process = ffi::clap_process {
+            steady_time: self.steady_time,
+            frames_count: num_frames as u32,
+            transport: transport.map_or(std::ptr::null(), |transport| transport as *const _),
+            audio_inputs: self.input_buffers.as_ptr(),
+            audio_outputs: self.output_buffers.as_mut_ptr(),
+            audio_inputs_count: self.input_buffers.len() as u32,
+            audio_outputs_count: self.output_buffers.len() as u32,
+            in_events: input_events.as_raw(),
+            out_events: output_events.as_raw(),
+        };
+        let status = unsafe {
+            match self.plugin.as_ref().process {
+                Some(process_fn) => process_fn(self.plugin.as_ptr(), &process),
+                None => ffi::CLAP_PROCESS_ERROR,
+            }
+        };
+        self.events.clear();
+
+        if status == ffi::CLAP_PROCESS_ERROR {
+            return Err(Error::Other(format!(
+                "CLAP plugin {} failed to process",
+                self.info.name
+            )));
+        }
+        self.bypass.process(inputs, outputs, num_frames);
+
+        self.sample_position += num_frames as u64;
+        self.steady_time += num_frames as i64;
+        Ok(())
+    }
+
+    /// Queue an event for the next block, after the events at the same time
+    fn queue(&mut self, event: InputEvent) {
+        let time = event.header().time;
+        let at = self.events.partition_point(|e| e.header().time <= time);
+        self.events.insert(at, event);
+    }
+
+    /// Bookkeeping after the plugin accepted a restored state of `bytes` bytes
+    fn state_restored(&mut self, bytes: usize) -> Result<()> {
+        if self.quirks.contains(Quirk::ResetAfterStateRestore) {
+            self.reset()?;
+        }
+
+        events::emit(HostEvent::StateRestored {
+            info: &self.info,
+            bytes,
+        });
+
+        Ok(())
+    }
+}
+
+/// The host as the plugin sees it
+///
+/// The plugin may call the host from any thread, so requests are recorded
+/// in atomics and acted on in [`ClapPlugin::idle()`].
+struct HostContext {
+    host: ffi::clap_host,
+    info: PluginInfo,
+    callback_requested: AtomicBool,
+    restart_requested: AtomicBool,
+    params_flush_requested: AtomicBool,
+    params_rescan: AtomicU32,
+    latency_changed: AtomicBool,
+    tail_changed: AtomicBool,
+    state_dirty: AtomicBool,
+    // Strings the clap_host points to
+    _name: CString,
+    _version: CString,
+}
+
+// Safety: the raw pointers in clap_host point to the context itself, to its
+// immutable strings or to static data
+unsafe impl Send for HostContext {}
+unsafe impl Sync for HostContext {}
+
+impl HostContext {
+    fn new(info: &PluginInfo) -> Result<Box<Self>> {
+        let host_info = host::host_info();
+        let name = CString::new(host_info.name)
+            .map_err(|_| Error::Other("Host name contains null byte".to_string()))?;
+        let version = CString::new(host_info.version)
+            .map_err(|_| Error::Other("Host version contains null byte".to_string()))?;
+
+        let mut context = Box::new(Self {
+            host: ffi::clap_host {
+                clap_version: ffi::CLAP_VERSION,
+                host_data: std::ptr::null_mut(),
+                name: name.as_ptr(),
+                vendor: EMPTY.as_ptr() as *const c_char,
+                url: EMPTY.as_ptr() as *const c_char,
+                version: version.as_ptr(),
+                get_extension: Some(host_get_extension),
+                request_restart: Some(host_request_restart),
+                request_process: Some(host_request_process),
+                request_callback: Some(host_request_callback),
+            },
+            info: info.clone(),
+            callback_requested: AtomicBool::new(false),
+            restart_requested: AtomicBool::new(false),
+            params_flush_requested: AtomicBool::new(false),
+            params_rescan: AtomicU32::new(0),
+            latency_changed: AtomicBool::new(false),
+            tail_changed: AtomicBool::new(false),
+            state_dirty: AtomicBool::new(false),
+            _name: name,
+            _version: version,
+        });
+        context.host.host_data = &mut *context as *mut Self as *mut c_void;
+        Ok(context)
+    }
+
+    /// The context behind a `clap_host` pointer passed to a callback
+    ///
+    /// # Safety
+    ///
+    /// `host` must be the `clap_host` of a live context
+    unsafe fn from_raw<'a>(host: *const ffi::clap_host) -> &'a Self {
+        &*((*host).host_data as *const Self)
+    }
+}
+
+const EMPTY: &[u8] = b"\0";
+
+static HOST_LOG: ffi::clap_host_log = ffi::clap_host_log {
+    log: Some(host_log),
+};
+
+static HOST_PARAMS: ffi::clap_host_params = ffi::clap_host_params {
+    rescan: Some(host_params_rescan),
+    clear: Some(host_params_clear),
+    request_flush: Some(host_params_request_flush),
+};
+
+static HOST_LATENCY: ffi::clap_host_latency = ffi::clap_host_latency {
+    changed: Some(host_latency_changed),
+};
+
+static HOST_TAIL: ffi::clap_host_tail = ffi::clap_host_tail {
+    changed: Some(host_tail_changed),
+};
+
+static HOST_STATE: ffi::clap_host_state = ffi::clap_host_state {
+    mark_dirty: Some(host_state_mark_dirty),
+};
+
+unsafe extern "C" fn host_get_extension(
+    _host: *const ffi::clap_host,
+    id: *const c_char,
+) -> *const c_void {
+    if id.is_null() {
+        return std::ptr::null();
+    }
+    match CStr::from_ptr(id).to_bytes_with_nul() {
+        id if id == ffi::CLAP_EXT_LOG => &HOST_LOG as *const _ as *const c_void,
+        id if id == ffi::CLAP_EXT_PARAMS => &HOST_PARAMS as *const _ as *const c_void,
+        id if id == ffi::CLAP_EXT_LATENCY => &HOST_LATENCY as *const _ as *const c_void,
+        id if id == ffi::CLAP_EXT_TAIL => &HOST_TAIL as *const _ as *const c_void,
+        id if id == ffi::CLAP_EXT_STATE => &HOST_STATE as *const _ as *const c_void,
+        _ => std::ptr::null(),
+    }
+}
+
+unsafe extern "C" fn host_request_restart(host: *const ffi::clap_host) {
+    HostContext::from_raw(host)
+        .restart_requested
+        .store(true, Ordering::Release);
+}
+
+unsafe extern "C" fn host_request_process(_host: *const ffi::clap_host) {
+    // Plugins are processed whenever the host's audio callback runs
+}
+
+unsafe extern "C" fn host_request_callback(host: *const ffi::clap_host) {
+    HostContext::from_raw(host)
+        .callback_requested
+        .store(true, Ordering::Release);
+}
+
+/// Report errors the plugin logs; other messages are dropped
+unsafe extern "C" fn host_log(host: *const ffi::clap_host, severity: i32, message: *const c_char) {
+    if !matches!(
+        severity,
+        ffi::CLAP_LOG_ERROR
+            | ffi::CLAP_LOG_FATAL
+            | ffi::CLAP_LOG_HOST_MISBEHAVING
+            | ffi::CLAP_LOG_PLUGIN_MISBEHAVING
+    ) || message.is_null()
+    {
+        return;
+    }
+    let message = CStr::from_ptr(message).to_string_lossy();
+    let context = HostContext::from_raw(host);
+    let error = Error::Other(format!("CLAP plugin logged: {}", message));
+    events::emit(HostEvent::Error {
+        info: Some(&context.info),
+        error: &error,
+    });
+}
+
+unsafe extern "C" fn host_params_rescan(host: *const ffi::clap_host, flags: u32) {
+    HostContext::from_raw(host)
+        .params_rescan
+        .fetch_or(flags, Ordering::AcqRel);
+}
+
+unsafe extern "C" fn host_params_clear(
+    _host: *const ffi::clap_host,
+    _id: ffi::clap_id,
+    _flags: u32,
+) {
+    // The host holds no automation or modulation to clear
+}
+
+unsafe extern "C" fn host_params_request_flush(host: *const ffi::clap_host) {
+    HostContext::from_raw(host)
+        .params_flush_requested
+        .store(true, Ordering::Release);
+}
+
+unsafe extern "C" fn host_latency_changed(host: *const ffi::clap_host) {
+    HostContext::from_raw(host)
+        .latency_changed
+        .store(true, Ordering::Release);
+}
+
+unsafe extern "C" fn host_tail_changed(host: *const ffi::clap_host) {
+    HostContext::from_raw(host)
+        .tail_changed
+        .store(true, Ordering::Release);
+}
+
+unsafe extern "C" fn host_state_mark_dirty(host: *const ffi::clap_host) {
+    // Picked up by take_state_dirty()
+    HostContext::from_raw(host)
+        .state_dirty
+        .store(true, Ordering::Release);
+}
+
+/// An event for the plugin
+#[derive(Clone, Copy)]
+enum InputEvent {
+    Param(ffi::clap_event_param_value),
+    Midi(ffi::clap_event_midi),
+}
+
+impl InputEvent {
+    fn header(&self) -> &ffi::clap_event_header {
+        match self {
+            Self::Param(event) => &event.header,
+            Self::Midi(event) => &event.header,
+        }
+    }
+
+    fn header_mut(&mut self) -> &mut ffi::clap_event_header {
+        match self {
+            Self::Param(event) => &mut event.header,
+            Self::Midi(event) => &mut event.header,
+        }
+    }
+}
+
+/// A `clap_input_events` list over a slice of events
+///
+/// The callbacks find the slice through the list pointer, so the list must
+/// stay where it is while the plugin holds it.
+#[repr(C)]
+struct InputEvents<'a> {
+    list: ffi::clap_input_events,
+    events: &'a [InputEvent],
+}
+
+impl<'a> InputEvents<'a> {
+    fn new(events: &'a [InputEvent]) -> Self {
+        Self {
+            list: ffi::clap_input_events {
+                ctx: std::ptr::null_mut(),
+                size: Some(input_events_size),
+                get: Some(input_events_get),
+            },
+            events,
+        }
+    }
+
+    fn as_raw(&self) -> *const ffi::clap_input_events {
+        &self.list
+    }
+
+    /// The list behind a pointer from [`as_raw()`](Self::as_raw)
+    ///
+    /// # Safety
+    ///
+    /// `list` must come from `as_raw()` on a list that is still alive
+    unsafe fn from_raw<'b>(list: *const ffi::clap_input_events) -> &'b Self {
+        &*(list as *const Self)
+    }
+}
+
+unsafe extern "C" fn input_events_size(list: *const ffi::clap_input_events) -> u32 {
+    InputEvents::from_raw(list).events.len() as u32
+}
+
+unsafe extern "C" fn input_events_get(
+    list: *const ffi::clap_input_events,
+    index: u32,
+) -> *const ffi::clap_event_header {
+    match InputEvents::from_raw(list).events.get(index as usize) {
+        Some(event) => event.header(),
+        None => std::ptr::null(),
+    }
+}
+
+/// A `clap_output_events` list that accepts and drops everything
+///
+/// Parameter values the plugin reports are read back with `get_value()`.
+struct OutputEvents {
+    list: ffi::clap_output_events,
+}
+
+impl OutputEvents {
+    fn new() -> Self {
+        Self {
+            list: ffi::clap_output_events {
+                ctx: std::ptr::null_mut(),
+                try_push: Some(output_events_try_push),
+            },
+        }
+    }
+
+    fn as_raw(&self) -> *const ffi::clap_output_events {
+        &self.list
+    }
+}
+
+unsafe extern "C" fn output_events_try_push(
+    _list: *const ffi::clap_output_events,
+    _event: *const ffi::clap_event_header,
+) -> bool {
+    true
+}
+
+/// A state save or load in progress, shared with its stream callbacks
+///
+/// Errors and panics from the caller's reader or writer are parked here so
+/// they can be reported (or resumed) once control is back on the Rust side.
+struct StateStream<S> {
+    io: S,
+    bytes: usize,
+    error: Option<std::io::Error>,
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+impl<S> StateStream<S> {
+    fn new(io: S) -> Self {
+        Self {
+            io,
+            bytes: 0,
+            error: None,
+            panic: None,
+        }
+    }
+
+    fn as_ctx(&mut self) -> *mut c_void {
+        self as *mut Self as *mut c_void
+    }
+
+    /// Result of the plugin call, preferring the callback's own failure
+    fn finish(self, ok: bool, action: &str) -> Result<usize> {
+        if let Some(payload) = self.panic {
+            panic::resume_unwind(payload);
+        }
+        if let Some(error) = self.error {
+            return Err(error.into());
+        }
+        if !ok {
+            return Err(Error::Other(format!(
+                "Plugin failed to {} its state",
+                action
+            )));
+        }
+        Ok(self.bytes)
+    }
+}
+
+/// `clap_ostream.write` over a [`StateStream`] of a writer
+unsafe extern "C" fn write_state(
+    stream: *const ffi::clap_ostream,
+    buffer: *const c_void,
+    size: u64,
+) -> i64 {
+    let state = &mut *((*stream).ctx as *mut StateStream<&mut dyn Write>);
+    if size == 0 {
+        return 0;
+    }
+    let chunk = std::slice::from_raw_parts(buffer as *const u8, size as usize);
+
+    match panic::catch_unwind(AssertUnwindSafe(|| state.io.write_all(chunk))) {
+        Ok(Ok(())) => {
+            state.bytes += chunk.len();
+            size as i64
+        }
+        Ok(Err(error)) => {
+            state.error = Some(error);
+            -1
+        }
+        Err(payload) => {
+            state.panic = Some(payload);
+            -1
+        }
+    }
+}
+
+/// `clap_istream.read` over a [`StateStream`] of a reader
+unsafe extern "C" fn read_state(
+    stream: *const ffi::clap_istream,
+    buffer: *mut c_void,
+    size: u64,
+) -> i64 {
+    let state = &mut *((*stream).ctx as *mut StateStream<&mut dyn Read>);
+    if size == 0 {
+        return 0;
+    }
+    let buffer = std::slice::from_raw_parts_mut(buffer as *mut u8, size as usize);
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| loop {
+        match state.io.read(buffer) {
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            result => break result,
+        }
+    }));
+    match result {
+        Ok(Ok(n)) => {
+            state.bytes += n;
+            n as i64
+        }
+        Ok(Err(error)) => {
+            state.error = Some(error);
+            -1
+        }
+        Err(payload) => {
+            state.panic = Some(payload);
+            -1
+        }
+    }
+}
+
+impl Drop for ClapPlugin {
+    fn drop(&mut self) {
+        self.deactivate();
+        unsafe {
+            if let Some(destroy) = self.plugin.as_ref().destroy {
+                destroy(self.plugin.as_ptr());
+            }
+        }
+    }
+}
+
+impl PluginInstance for ClapPlugin {
+    fn initialize(&mut self, sample_rate: f64, max_block_size: usize) -> Result<()> {
+        let max_frames = u32::try_from(max_block_size)
+            .map_err(|_| Error::Other(format!("Block size {} out of range", max_block_size)))?;
+        self.deactivate();
+
+        // Ports can only change while the plugin is inactive
+        self.input_ports = self.query_ports(true);
+        self.output_ports = self.query_ports(false);
+        self.input_channels = self.input_ports.iter().map(|&n| n as usize).sum();
+        self.output_channels = self.output_ports.iter().map(|&n| n as usize).sum();
+
+        let activated = unsafe {
+            match self.plugin.as_ref().activate {
+                Some(activate) => activate(self.plugin.as_ptr(), sample_rate, 1, max_frames),
+                None => false,
+            }
+        };
+        if !activated {
+            return Err(Error::Other(format!(
+                "CLAP plugin {} failed to activate",
+                self.info.name
+            )));
+        }
+
+        self.active = true;
+        self.sample_rate = sample_rate;
+        self.max_block_size = max_block_size;
+        self.steady_time = 0;
+
+        // Pre-allocate everything process() needs; buffers point into the
+        // pointer arrays, which are never reallocated until the next
+        // initialize()
+        self.input_ptrs = vec![std::ptr::null_mut(); self.input_channels];
+        self.output_ptrs = vec![std::ptr::null_mut(); self.output_channels];
+        self.input_buffers = port_buffers(&self.input_ports, &mut self.input_ptrs);
+        self.output_buffers = port_buffers(&self.output_ports, &mut self.output_ptrs);
+        self.events.reserve(256);
+
+        self.bypass_param = (0..self.parameter_count()).find(|&index| {
+            self.param_info(index)
+                .is_ok_and(|info| info.flags & ffi::CLAP_PARAM_IS_BYPASS != 0)
+        });
+        self.bypass.prepare(
+            sample_rate,
+            self.output_channels,
+            self.latency_samples(),
+        );
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        unsafe {
+            if let Some(reset) = self.plugin.as_ref().reset {
+                reset(self.plugin.as_ptr());
+            }
+        }
+        Ok(())
+    }
+
+    fn process(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        self.process_block(inputs, outputs, num_frames, None)
+    }
+
+    /// Process with `context` reported to the plugin as its transport
+    fn process_with_context(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        context: &BlockContext,
+    ) -> Result<()> {
+        let transport = transport(context);
+        self.process_block(inputs, outputs, context.num_frames, Some(&transport))?;
+        self.sample_position = context.sample_position + context.num_frames as u64;
+        Ok(())
+    }
+
+    fn process_with_events(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        changes: &[ParameterChange],
+        midi: &[MidiEvent],
+    ) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        // Timed events in the next block, not flushed ahead of it
+        let last = num_frames.saturating_sub(1) as u32;
+        for change in changes {
+            self.queue_parameter(change.index, change.value, change.sample_offset.min(last))?;
+        }
+        self.send_midi(midi)?;
+
+        self.process(inputs, outputs, num_frames)
+    }
+
+    fn sample_position(&self) -> u64 {
+        self.sample_position
+    }
+
+    fn set_sample_position(&mut self, position: u64) -> Result<()> {
+        // Plugins only see the position through the transport; steady time
+        // keeps counting
+        self.sample_position = position;
+        Ok(())
+    }
+
+    fn flush_events(&mut self) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        // Parameters can only be flushed outside of processing; processing
+        // restarts with the next block
+        if self.processing {
+            unsafe {
+                if let Some(stop_processing) = self.plugin.as_ref().stop_processing {
+                    stop_processing(self.plugin.as_ptr());
+                }
+            }
+            self.processing = false;
+        }
+        self.flush_parameters();
+        Ok(())
+    }
+
+    fn parameter_count(&self) -> usize {
+        unsafe {
+            match self.params.as_ref().and_then(|params| params.count) {
+                Some(count) => count(self.plugin.as_ptr()) as usize,
+                None => 0,
+            }
+        }
+    }
+
+    fn parameter_info(&self, index: usize) -> Result<ParameterInfo> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        let info = self.param_info(index)?;
+        let name = decode_name(c_array_bytes(&info.name), "parameter name")?;
+        let visibility = if info.flags & ffi::CLAP_PARAM_IS_HIDDEN != 0 {
+            ParameterVisibility::Hidden
+        } else {
+            ParameterVisibility::Visible
+        };
+
+        // CLAP has no unit field; units are part of the value text
+        Ok(ParameterInfo::new(
+            index,
+            name,
+            info.min_value as f32,
+            info.max_value as f32,
+            info.default_value as f32,
+            String::new(),
+        )
+        .with_visibility(visibility))
+    }
+
+    fn get_parameter(&self, index: usize) -> Result<f32> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        let info = self.param_info(index)?;
+        let mut value = 0.0f64;
+        let read = unsafe {
+            match self.params.as_ref().and_then(|params| params.get_value) {
+                Some(get_value) => get_value(self.plugin.as_ptr(), info.id, &mut value),
+                None => false,
+            }
+        };
+        if !read {
+            return Err(Error::InvalidParameter(index));
+        }
+
+        let range = info.max_value - info.min_value;
+        if range <= 0.0 {
+            return Ok(0.0);
+        }
+        Ok(((value - info.min_value) / range).clamp(0.0, 1.0) as f32)
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        self.queue_parameter(index, value, 0)?;
+        if !self.processing {
+            self.flush_parameters();
+        }
+        Ok(())
+    }
+
+    fn send_midi(&mut self, events: &[MidiEvent]) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        for event in events {
+            self.queue(InputEvent::Midi(ffi::clap_event_midi {
+                header: event_header::<ffi::clap_event_midi>(
+                    event.sample_offset,
+                    ffi::CLAP_EVENT_MIDI,
+                ),
+                port_index: 0,
+                data: event.to_bytes(),
+            }));
+        }
+        Ok(())
+    }
+
+    fn preset_count(&self) -> Result<usize> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        // Preset discovery is a separate factory, not supported yet
+        Ok(0)
+    }
+
+    fn preset_info(&self, index: usize) -> Result<PresetInfo> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        Err(Error::Other(format!("Preset index {} out of range", index)))
+    }
+
+    fn load_preset(&mut self, preset_number: i32) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        Err(Error::Other(format!(
+            "CLAP plugins have no preset {}; restore presets with set_state()",
+            preset_number
+        )))
+    }
+
+    fn get_state(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.get_state_to_writer(&mut data)?;
+        Ok(data)
+    }
+
+    fn set_state(&mut self, data: &[u8]) -> Result<()> {
+        if data.is_empty() {
+            return Err(Error::Other("State data is empty".to_string()));
+        }
+
+        let mut reader = data;
+        self.set_state_from_reader(&mut reader)
+    }
+
+    fn get_state_to_writer(&self, writer: &mut dyn Write) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+        let save = unsafe { self.state.as_ref() }
+            .and_then(|state| state.save)
+            .ok_or_else(|| Error::Other("Plugin has no state".to_string()))?;
+
+        let mut state = StateStream::new(writer);
+        let stream = ffi::clap_ostream {
+            ctx: state.as_ctx(),
+            write: Some(write_state),
+        };
+        let ok = unsafe { save(self.plugin.as_ptr(), &stream) };
+        state.finish(ok, "save")?;
+        Ok(())
+    }
+
+    fn set_state_from_reader(&mut self, reader: &mut dyn Read) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+        let load = unsafe { self.state.as_ref() }
+            .and_then(|state| state.load)
+            .ok_or_else(|| Error::Other("Plugin has no state".to_string()))?;
+
+        let mut state = StateStream::new(reader);
+        let stream = ffi::clap_istream {
+            ctx: state.as_ctx(),
+            read: Some(read_state),
+        };
+        let ok = unsafe { load(self.plugin.as_ptr(), &stream) };
+        let bytes = state.finish(ok, "load")?;
+
+        self.state_restored(bytes)
+    }
+
+    fn take_state_dirty(&self) -> bool {
+        self.host.state_dirty.swap(false, Ordering::AcqRel)
+    }
+
+    fn info(&self) -> &PluginInfo {
+        &self.info
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.active
+    }
+
+    fn input_channels(&self) -> usize {
+        self.input_channels
+    }
+
+    fn output_channels(&self) -> usize {
+        self.output_channels
+    }
+
+    fn latency_samples(&self) -> usize {
+        unsafe {
+            match self.latency.as_ref().and_then(|latency| latency.get) {
+                Some(get) if self.active => get(self.plugin.as_ptr()) as usize,
+                _ => 0,
+            }
+        }
+    }
+
+    fn tail_samples(&self) -> usize {
+        let tail = unsafe {
+            match self.tail.as_ref().and_then(|tail| tail.get) {
+                Some(get) if self.active => get(self.plugin.as_ptr()),
+                _ => 0,
+            }
+        };
+        // CLAP counts INT32_MAX and up as infinite
+        if tail >= i32::MAX as u32 {
+            usize::MAX
+        } else {
+            tail as usize
+        }
+    }
+
+    fn tail_seconds(&self) -> f64 {
+        crate::traits::tail_seconds(self.tail_samples(), self.sample_rate)
+    }
+
+    fn set_bypass(&mut self, bypassed: bool) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        match self.bypass_param {
+            Some(index) => self.set_parameter(index, if bypassed { 1.0 } else { 0.0 }),
+            None => {
+                self.bypass.set_bypassed(bypassed);
+                Ok(())
+            }
+        }
+    }
+
+    fn is_bypassed(&self) -> bool {
+        match self.bypass_param {
+            Some(index) => self.get_parameter(index).is_ok_and(|value| value >= 0.5),
+            None => self.bypass.is_bypassed(),
+        }
+    }
+
+    fn quirks(&self) -> Quirks {
+        self.quirks.clone()
+    }
+}
+
+/// One `clap_audio_buffer` per port, pointing into `ptrs`
+fn port_buffers(ports: &[u32], ptrs: &mut [*mut f32]) -> Vec<ffi::clap_audio_buffer> {
+    let mut offset = 0;
+    ports
+        .iter()
+        .map(|&channels| {
+            let buffer = ffi::clap_audio_buffer {
+                data32: ptrs[offset..].as_mut_ptr(),
+                data64: std::ptr::null_mut(),
+                channel_count: channels,
+                latency: 0,
+                constant_mask: 0,
+            };
+            offset += channels as usize;
+            buffer
+        })
+        .collect()
+}
+
+/// The transport event describing the block `context` is for
+///
+/// Loop positions in seconds assume the tempo stays at the block's.
+fn transport(context: &BlockContext) -> ffi::clap_event_transport {
+    let beats = |beat: f64| (beat * ffi::CLAP_BEATTIME_FACTOR as f64).round() as i64;
+    let seconds = |seconds: f64| (seconds * ffi::CLAP_SECTIME_FACTOR as f64).round() as i64;
+    let song_seconds = if context.sample_rate > 0.0 {
+        context.sample_position as f64 / context.sample_rate
+    } else {
+        0.0
+    };
+    let seconds_at = |beat: f64| {
+        if context.tempo > 0.0 {
+            song_seconds + (beat - context.beat) * 60.0 / context.tempo
+        } else {
+            song_seconds
+        }
+    };
+
+    let mut flags = ffi::CLAP_TRANSPORT_HAS_TEMPO
+        | ffi::CLAP_TRANSPORT_HAS_BEATS_TIMELINE
+        | ffi::CLAP_TRANSPORT_HAS_SECONDS_TIMELINE
+        | ffi::CLAP_TRANSPORT_HAS_TIME_SIGNATURE;
+    if context.playing {
+        flags |= ffi::CLAP_TRANSPORT_IS_PLAYING;
+    }
+    let (loop_start, loop_end) = match context.loop_range {
+        Some(range) => {
+            flags |= ffi::CLAP_TRANSPORT_IS_LOOP_ACTIVE;
+            (range.start, range.end)
+        }
+        None => (0.0, 0.0),
+    };
+
+    ffi::clap_event_transport {
+        header: event_header::<ffi::clap_event_transport>(0, ffi::CLAP_EVENT_TRANSPORT),
+        flags,
+        song_pos_beats: beats(context.beat),
+        song_pos_seconds: seconds(song_seconds),
+        tempo: context.tempo,
+        tempo_inc: 0.0,
+        loop_start_beats: beats(loop_start),
+        loop_end_beats: beats(loop_end),
+        loop_start_seconds: seconds(seconds_at(loop_start)),
+        loop_end_seconds: seconds(seconds_at(loop_end)),
+        bar_start: beats(context.bar_start),
+        bar_number: context.bar.clamp(i32::MIN as i64, i32::MAX as i64) as i32,
+        tsig_num: context.time_signature.numerator.min(u16::MAX as u32) as u16,
+        tsig_denom: context.time_signature.denominator.min(u16::MAX as u32) as u16,
+    }
+}
+
+/// A core event header for an event of type `T`
+fn event_header<T>(time: u32, type_: u16) -> ffi::clap_event_header {
+    ffi::clap_event_header {
+        size: std::mem::size_of::<T>() as u32,
+        time,
+        space_id: ffi::CLAP_CORE_EVENT_SPACE_ID,
+        type_,
+        flags: 0,
+    }
+}
+
+/// The bytes of a null-terminated fixed-size C string
+fn c_array_bytes(array: &[c_char]) -> &[u8] {
+    // Safety: c_char and u8 have the same layout
+    let bytes = unsafe { std::slice::from_raw_parts(array.as_ptr() as *const u8, array.len()) };
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    &bytes[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clap::test_plugin;
+    use crate::clap::ClapScanner;
+    use crate::scan::ScannerConfig;
+    use crate::PluginScanner;
+
+    #[test]
+    fn test_gain_plugin() {
+        let dir = std::env::temp_dir().join(format!("rack-clap-instance-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let _library = test_plugin::install(&dir.join("gain.clap"));
+
+        let scanner =
+            ClapScanner::with_config(ScannerConfig::new().path(&dir).skip_default_paths(true))
+                .unwrap();
+        let info = scanner.scan().unwrap().remove(0);
+        let mut plugin = scanner.load(&info).unwrap();
+        assert!(
+            plugin.process(&[], &mut [], 0).is_err(),
+            "Not initialized yet"
+        );
+
+        assert_eq!(plugin.tail_samples(), 0, "Only reported while active");
+        plugin.initialize(48000.0, 64).unwrap();
+        assert_eq!((plugin.input_channels(), plugin.output_channels()), (2, 2));
+        assert_eq!(plugin.tail_samples(), test_plugin::TAIL as usize);
+        assert_eq!(plugin.tail_seconds(), 0.1);
+        assert_eq!(plugin.parameter_count(), 1);
+        let param = plugin.parameter_info(0).unwrap();
+        assert_eq!(param.name, "Gain");
+        assert_eq!((param.min, param.max, param.default), (0.0, 2.0, 1.0));
+        assert_eq!(plugin.get_parameter(0).unwrap(), 0.5);
+
+        // Applied straight away before processing starts
+        plugin.set_parameter(0, 1.0).unwrap();
+        assert_eq!(plugin.get_parameter(0).unwrap(), 1.0);
+
+        let input = vec![0.25f32; 64];
+        let mut left = vec![0.0f32; 64];
+        let mut right = vec![0.0f32; 64];
+        plugin
+            .process(&[&input, &input], &mut [&mut left, &mut right], 64)
+            .unwrap();
+        assert!(left.iter().chain(&right).all(|&s| s == 0.5));
+        assert_eq!(plugin.sample_position(), 64);
+
+        // Delivered with the next block once processing
+        plugin.set_parameter(0, 0.25).unwrap();
+        plugin
+            .send_midi(&[MidiEvent::note_on(60, 100, 0, 200)])
+            .unwrap();
+        plugin
+            .process(&[&input, &input], &mut [&mut left, &mut right], 64)
+            .unwrap();
+        assert!(left.iter().all(|&s| s == 0.125));
+
+        let state = plugin.get_state().unwrap();
+        assert_eq!(state, 0.5f64.to_le_bytes());
+        plugin.set_parameter(0, 0.0).unwrap();
+        plugin.flush_events().unwrap();
+        assert_eq!(plugin.get_parameter(0).unwrap(), 0.0);
+        plugin.set_state(&state).unwrap();
+        assert_eq!(plugin.get_parameter(0).unwrap(), 0.25);
+
+        assert_eq!(plugin.preset_count().unwrap(), 0);
+        drop(plugin);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_transport_from_context() {
+        use crate::tempo::{LoopRange, TempoMap, TimeSignature, Transport};
+
+        let mut map = TempoMap::new(120.0);
+        map.set_time_signature(0.0, TimeSignature::new(3, 4));
+        let mut transport = Transport::new(48000.0, map);
+        transport.playing = true;
+        transport.loop_range = Some(LoopRange::new(4.0, 8.0));
+        transport.seek(96000); // 4 beats in: the second bar of 3/4
+
+        let event = super::transport(&BlockContext::from_transport(&transport, 64));
+        let factor = ffi::CLAP_BEATTIME_FACTOR;
+        assert_eq!(event.header.type_, ffi::CLAP_EVENT_TRANSPORT);
+        assert_ne!(event.flags & ffi::CLAP_TRANSPORT_IS_PLAYING, 0);
+        assert_ne!(event.flags & ffi::CLAP_TRANSPORT_IS_LOOP_ACTIVE, 0);
+        assert_eq!(event.song_pos_beats, 4 * factor);
+        assert_eq!(event.song_pos_seconds, 2 * ffi::CLAP_SECTIME_FACTOR);
+        assert_eq!((event.bar_start, event.bar_number), (3 * factor, 1));
+        assert_eq!((event.loop_start_beats, event.loop_end_beats), (4 * factor, 8 * factor));
+        assert_eq!(event.loop_end_seconds, 4 * ffi::CLAP_SECTIME_FACTOR);
+        assert_eq!((event.tsig_num, event.tsig_denom), (3, 4));
+    }
+}
@@ -0,0 +1,186 @@
+//! Loading CLAP libraries
+//!
+//! A CLAP library is initialized once (`clap_entry->init()`) and shared by
+//! every plugin created from it. Open libraries are kept in a process-wide
+//! table so scanning and loading the same file reuse one initialized copy;
+//! the library is deinitialized and unloaded when the last user drops it.
+
+use crate::{Error, Result};
+use std::ffi::{c_char, c_void, CString};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+
+use super::ffi;
+use crate::dylib as sys;
+
+/// Libraries currently open, by the path they were opened from
+static LIBRARIES: Mutex<Vec<(PathBuf, Weak<Library>)>> = Mutex::new(Vec::new());
+
+/// An initialized CLAP library
+pub(super) struct Library {
+    path: PathBuf,
+    // Null for entries compiled into the process (tests)
+    handle: *mut c_void,
+    entry: *const ffi::clap_plugin_entry,
+}
+
+// Safety: the entry is immutable once initialized, and CLAP requires its
+// functions to be callable from any thread
+unsafe impl Send for Library {}
+unsafe impl Sync for Library {}
+
+impl Library {
+    /// Open and initialize the library at `path`, or share the copy that is
+    /// already open
+    ///
+    /// `path` is a `.clap` file, or on macOS a `.clap` bundle.
+    pub fn open(path: &Path) -> Result<Arc<Self>> {
+        let mut libraries = LIBRARIES.lock().unwrap_or_else(|e| e.into_inner());
+        libraries.retain(|(_, library)| library.strong_count() > 0);
+        if let Some(library) = libraries
+            .iter()
+            .find(|(open, _)| open == path)
+            .and_then(|(_, library)| library.upgrade())
+        {
+            return Ok(library);
+        }
+
+        let binary = binary_path(path);
+        let handle = sys::open(&binary)?;
+        // Safety: handle is a library that was just opened
+        let entry = unsafe { sys::symbol(handle, ffi::CLAP_ENTRY_SYMBOL) };
+        if entry.is_null() {
+            unsafe { sys::close(handle) };
+            return Err(Error::InvalidFormat(format!(
+                "{} doesn't export clap_entry",
+                path.display()
+            )));
+        }
+
+        let library = Self::init(path, handle, entry as *const ffi::clap_plugin_entry)?;
+        libraries.push((path.to_path_buf(), Arc::downgrade(&library)));
+        Ok(library)
+    }
+
+    /// Register an entry compiled into the process as the library at `path`
+    #[cfg(test)]
+    pub fn from_entry(path: &Path, entry: &'static ffi::clap_plugin_entry) -> Result<Arc<Self>> {
+        let library = Self::init(path, std::ptr::null_mut(), entry)?;
+        let mut libraries = LIBRARIES.lock().unwrap_or_else(|e| e.into_inner());
+        libraries.push((path.to_path_buf(), Arc::downgrade(&library)));
+        Ok(library)
+    }
+
+    /// Check the entry's version and call its `init()`
+    ///
+    /// Takes ownership of `handle`, closing it on failure.
+    fn init(
+        path: &Path,
+        handle: *mut c_void,
+        entry: *const ffi::clap_plugin_entry,
+    ) -> Result<Arc<Self>> {
+        // Constructed first so that failures below close the handle; deinit()
+        // is only called for libraries whose init() succeeded
+        let mut library = Self {
+            path: path.to_path_buf(),
+            handle,
+            entry: std::ptr::null(),
+        };
+
+        // Safety: clap_entry points to a clap_plugin_entry for as long as the
+        // library is loaded
+        let entry_ref = unsafe { &*entry };
+        if !entry_ref.clap_version.is_compatible() {
+            return Err(Error::InvalidFormat(format!(
+                "{} was built for CLAP {}.{}.{}",
+                path.display(),
+                entry_ref.clap_version.major,
+                entry_ref.clap_version.minor,
+                entry_ref.clap_version.revision
+            )));
+        }
+
+        let plugin_path = path_to_c_string(path)?;
+        let init = entry_ref
+            .init
+            .ok_or_else(|| Error::InvalidFormat(format!("{} has no init()", path.display())))?;
+        if !unsafe { init(plugin_path.as_ptr()) } {
+            return Err(Error::Other(format!(
+                "CLAP library {} failed to initialize",
+                path.display()
+            )));
+        }
+
+        library.entry = entry;
+        Ok(Arc::new(library))
+    }
+
+    /// The library's plugin factory
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the library has no plugin factory
+    pub fn plugin_factory(&self) -> Result<&ffi::clap_plugin_factory> {
+        // Safety: entry is valid while the library is loaded
+        let factory = unsafe {
+            match (*self.entry).get_factory {
+                Some(get_factory) => {
+                    get_factory(ffi::CLAP_PLUGIN_FACTORY_ID.as_ptr() as *const c_char)
+                }
+                None => std::ptr::null(),
+            }
+        };
+        if factory.is_null() {
+            return Err(Error::InvalidFormat(format!(
+                "{} has no plugin factory",
+                self.path.display()
+            )));
+        }
+        // Safety: the factory lives as long as the library
+        Ok(unsafe { &*(factory as *const ffi::clap_plugin_factory) })
+    }
+}
+
+impl Drop for Library {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.entry.is_null() {
+                if let Some(deinit) = (*self.entry).deinit {
+                    deinit();
+                }
+            }
+            if !self.handle.is_null() {
+                sys::close(self.handle);
+            }
+        }
+    }
+}
+
+/// The file to load for a `.clap` path
+///
+/// On macOS a `.clap` is a bundle, whose executable is named after it.
+fn binary_path(path: &Path) -> PathBuf {
+    if cfg!(target_vendor = "apple") && path.is_dir() {
+        if let Some(stem) = path.file_stem() {
+            return path.join("Contents").join("MacOS").join(stem);
+        }
+    }
+    path.to_path_buf()
+}
+
+/// The path passed to `clap_entry->init()`, which takes UTF-8 on Windows
+fn path_to_c_string(path: &Path) -> Result<CString> {
+    #[cfg(windows)]
+    let bytes = path
+        .to_str()
+        .ok_or_else(|| Error::Other(format!("Path isn't valid Unicode: {}", path.display())))?
+        .as_bytes()
+        .to_vec();
+    #[cfg(not(windows))]
+    let bytes = {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes().to_vec()
+    };
+    CString::new(bytes)
+        .map_err(|_| Error::Other(format!("Path contains null byte: {}", path.display())))
+}
@@ -0,0 +1,9 @@
+mod ffi;
+mod instance;
+mod library;
+mod scanner;
+#[cfg(test)]
+pub(crate) mod test_plugin;
+
+pub use instance::ClapPlugin;
+pub use scanner::{ClapScanner, CLAP_PATH_ENV};
@@ -0,0 +1,348 @@
+use crate::events::{self, HostEvent};
+use crate::metadata::{self, SharedMetadataStore};
+use crate::scan::{find_bundles, BundleKind, ScanFilter, ScannerConfig};
+use crate::text::decode_name;
+use crate::{Error, PluginFormat, PluginInfo, PluginScanner, PluginType, Result};
+use std::ffi::{c_char, CStr};
+use std::path::{Path, PathBuf};
+
+use super::ffi;
+use super::instance::ClapPlugin;
+use super::library::Library;
+
+/// Environment variable with extra CLAP search paths, defined by the CLAP
+/// specification
+///
+/// Uses the platform's path list separator (`:` on Unix, `;` on Windows).
+pub const CLAP_PATH_ENV: &str = "CLAP_PATH";
+
+/// How deep [`scan()`](PluginScanner::scan) looks below each search path
+///
+/// CLAP asks hosts to search its folders recursively, so vendors can install
+/// into subfolders.
+const SEARCH_DEPTH: usize = 8;
+
+/// Scanner for CLAP plugins
+///
+/// Every `.clap` library found is loaded and initialized to list the
+/// plugins its factory offers. Libraries that fail to load are skipped and
+/// reported as [`HostEvent::Error`].
+pub struct ClapScanner {
+    usage: Option<SharedMetadataStore>,
+    config: ScannerConfig,
+}
+
+impl ClapScanner {
+    /// Create a new CLAP scanner
+    ///
+    /// Searches the default system paths plus any listed in `RACK_PLUGIN_PATH`
+    /// (see [`ScannerConfig::from_env()`]).
+    ///
+    /// # Errors
+    ///
+    /// Never fails; returns a `Result` like the other scanners
+    pub fn new() -> Result<Self> {
+        Self::with_config(ScannerConfig::from_env())
+    }
+
+    /// Record every successful [`load()`](PluginScanner::load) in `store`
+    ///
+    /// Enables [`most_used()`](PluginScanner::most_used). Usage tracking is off
+    /// until this is called.
+    pub fn track_usage(&mut self, store: SharedMetadataStore) {
+        self.usage = Some(store);
+    }
+
+    /// Describe the plugins in every CLAP library under `roots`
+    fn scan_roots(
+        &self,
+        roots: &[PathBuf],
+        depth: usize,
+        filter: Option<&ScanFilter>,
+    ) -> Result<Vec<PluginInfo>> {
+        let mut libraries: Vec<PathBuf> = roots
+            .iter()
+            .flat_map(|root| {
+                find_bundles(
+                    root,
+                    depth,
+                    self.config.follow_symlinks,
+                    self.config.io_timeout,
+                )
+            })
+            .filter(|bundle| bundle.kind == BundleKind::Clap)
+            .map(|bundle| bundle.path)
+            .collect();
+        libraries.sort();
+        libraries.dedup();
+
+        let mut plugins = Vec::new();
+        for path in libraries {
+            match describe_library(&path, filter) {
+                Ok(found) => plugins.extend(found),
+                Err(error) => events::emit(HostEvent::Error {
+                    info: None,
+                    error: &error,
+                }),
+            }
+        }
+        Ok(plugins)
+    }
+
+    /// Every path [`scan()`](PluginScanner::scan) searches
+    fn search_paths(&self) -> Vec<PathBuf> {
+        let mut paths = if self.config.skip_default_paths {
+            Vec::new()
+        } else {
+            Self::default_paths()
+        };
+        paths.extend(self.config.extra_paths.iter().cloned());
+        paths
+    }
+}
+
+/// List the plugins in the library at `path`
+fn describe_library(path: &Path, filter: Option<&ScanFilter>) -> Result<Vec<PluginInfo>> {
+    let library = Library::open(path)?;
+    let factory = library.plugin_factory()?;
+    let canonical_path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+    let count = match factory.get_plugin_count {
+        // Safety: the factory belongs to the open library
+        Some(count) => unsafe { count(factory) },
+        None => 0,
+    };
+    let mut plugins = Vec::new();
+    for index in 0..count {
+        // Safety: as above; descriptors live as long as the library
+        let descriptor = unsafe {
+            match factory.get_plugin_descriptor {
+                Some(get) => get(factory, index).as_ref(),
+                None => None,
+            }
+        };
+        let Some(descriptor) = descriptor else {
+            continue;
+        };
+        if let Some(info) = convert_descriptor(path, descriptor, filter)? {
+            plugins.push(info.with_canonical_path(canonical_path.clone()));
+        }
+    }
+    Ok(plugins)
+}
+
+/// Convert a plugin descriptor to a PluginInfo
+///
+/// Returns `Ok(None)` if the plugin is rejected by `filter`.
+fn convert_descriptor(
+    path: &Path,
+    descriptor: &ffi::clap_plugin_descriptor,
+    filter: Option<&ScanFilter>,
+) -> Result<Option<PluginInfo>> {
+    // Safety: descriptor strings are null or null-terminated
+    let string = |ptr: *const c_char, field: &str| {
+        if ptr.is_null() {
+            Ok(String::new())
+        } else {
+            decode_name(unsafe { CStr::from_ptr(ptr) }.to_bytes(), field)
+        }
+    };
+
+    let unique_id = string(descriptor.id, "plugin ID")?;
+    if unique_id.is_empty() {
+        return Err(Error::InvalidFormat(format!(
+            "{} lists a plugin without an ID",
+            path.display()
+        )));
+    }
+    let name = string(descriptor.name, "plugin name")?;
+    let manufacturer = string(descriptor.vendor, "manufacturer")?;
+
+    let mut features = Vec::new();
+    if !descriptor.features.is_null() {
+        for index in 0.. {
+            // Safety: features is a null-terminated array of C strings
+            let feature = unsafe { *descriptor.features.add(index) };
+            if feature.is_null() {
+                break;
+            }
+            features.push(string(feature, "plugin feature")?);
+        }
+    }
+    let plugin_type = plugin_type_from_features(&features);
+
+    if let Some(filter) = filter {
+        if !filter.matches_fields(&name, &manufacturer, plugin_type) {
+            return Ok(None);
+        }
+    }
+
+    let version = parse_version(&string(descriptor.version, "plugin version")?);
+    Ok(Some(
+        PluginInfo::new(
+            name,
+            manufacturer,
+            version,
+            plugin_type,
+            path.to_path_buf(),
+            unique_id,
+        )
+        .with_format(PluginFormat::Clap),
+    ))
+}
+
+/// The plugin type for a descriptor's feature list
+fn plugin_type_from_features(features: &[String]) -> PluginType {
+    let has = |feature: &str| features.iter().any(|f| f == feature);
+    if has(ffi::CLAP_PLUGIN_FEATURE_INSTRUMENT) {
+        PluginType::Instrument
+    } else if has(ffi::CLAP_PLUGIN_FEATURE_ANALYZER) {
+        PluginType::Analyzer
+    } else if has(ffi::CLAP_PLUGIN_FEATURE_AUDIO_EFFECT) {
+        PluginType::Effect
+    } else {
+        PluginType::Other
+    }
+}
+
+/// Pack a "major.minor.bugfix" version like AudioUnit versions
+/// (`0xMMMMmmbb`)
+///
+/// Missing or non-numeric parts count as 0; anything after a part's digits
+/// (e.g. "2-beta") is ignored.
+fn parse_version(version: &str) -> u32 {
+    let mut parts = version.trim().split('.').map(|part| {
+        let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+        digits.parse::<u32>().unwrap_or(0)
+    });
+    let major = parts.next().unwrap_or(0).min(0xFFFF);
+    let minor = parts.next().unwrap_or(0).min(0xFF);
+    let bugfix = parts.next().unwrap_or(0).min(0xFF);
+    (major << 16) | (minor << 8) | bugfix
+}
+
+impl PluginScanner for ClapScanner {
+    type Plugin = ClapPlugin;
+
+    fn with_config(config: ScannerConfig) -> Result<Self> {
+        Ok(Self {
+            usage: None,
+            config,
+        })
+    }
+
+    fn config(&self) -> &ScannerConfig {
+        &self.config
+    }
+
+    /// The locations from the CLAP specification, plus any listed in
+    /// [`CLAP_PATH_ENV`]
+    fn default_paths() -> Vec<PathBuf> {
+        let var = |name: &str| {
+            std::env::var_os(name)
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from)
+        };
+
+        let mut paths = Vec::new();
+        if cfg!(windows) {
+            paths.extend(var("COMMONPROGRAMFILES").map(|dir| dir.join("CLAP")));
+            paths.extend(var("LOCALAPPDATA").map(|dir| dir.join(r"Programs\Common\CLAP")));
+        } else if cfg!(target_vendor = "apple") {
+            paths.push(PathBuf::from("/Library/Audio/Plug-Ins/CLAP"));
+            paths.extend(var("HOME").map(|home| home.join("Library/Audio/Plug-Ins/CLAP")));
+        } else {
+            paths.extend(var("HOME").map(|home| home.join(".clap")));
+            paths.push(PathBuf::from("/usr/lib/clap"));
+        }
+
+        if let Some(list) = std::env::var_os(CLAP_PATH_ENV) {
+            paths.extend(std::env::split_paths(&list).filter(|p| p.is_absolute()));
+        }
+        paths
+    }
+
+    fn add_path(&mut self, path: &Path) -> Result<()> {
+        self.config.extra_paths.push(path.to_path_buf());
+        Ok(())
+    }
+
+    fn scan(&self) -> Result<Vec<PluginInfo>> {
+        let depth = self.config.scan_depth.max(SEARCH_DEPTH);
+        self.scan_roots(&self.search_paths(), depth, None)
+    }
+
+    fn scan_filtered(&self, filter: &ScanFilter) -> Result<Vec<PluginInfo>> {
+        let depth = self.config.scan_depth.max(SEARCH_DEPTH);
+        self.scan_roots(&self.search_paths(), depth, Some(filter))
+    }
+
+    fn scan_path(&self, path: &Path) -> Result<Vec<PluginInfo>> {
+        self.scan_roots(&[path.to_path_buf()], self.config.scan_depth, None)
+    }
+
+    fn scan_path_filtered(&self, path: &Path, filter: &ScanFilter) -> Result<Vec<PluginInfo>> {
+        self.scan_roots(&[path.to_path_buf()], self.config.scan_depth, Some(filter))
+    }
+
+    fn load(&self, info: &PluginInfo) -> Result<Self::Plugin> {
+        let plugin = ClapPlugin::new(info)?;
+        metadata::record_load(self.usage.as_ref(), &info.unique_id);
+        events::emit(HostEvent::PluginLoaded { info });
+        Ok(plugin)
+    }
+
+    fn usage_store(&self) -> Option<&SharedMetadataStore> {
+        self.usage.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clap::test_plugin;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("1.2.3"), 0x0001_0203);
+        assert_eq!(parse_version("10.0"), 0x000A_0000);
+        assert_eq!(parse_version("2.1.0-beta"), 0x0002_0100);
+        assert_eq!(parse_version(""), 0);
+    }
+
+    #[test]
+    fn test_default_paths() {
+        let paths = ClapScanner::default_paths();
+        assert!(
+            !paths.is_empty(),
+            "Every desktop platform has default CLAP paths"
+        );
+        assert!(paths.iter().all(|p| p.is_absolute()));
+    }
+
+    #[test]
+    fn test_scan_describes_plugins() {
+        let dir = std::env::temp_dir().join(format!("rack-clap-scan-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("Vendor")).unwrap();
+        let _library = test_plugin::install(&dir.join("Vendor/gain.clap"));
+
+        let config = ScannerConfig::new().path(&dir).skip_default_paths(true);
+        let scanner = ClapScanner::with_config(config).unwrap();
+        let plugins = scanner.scan().unwrap();
+        assert_eq!(plugins.len(), 1);
+        let plugin = &plugins[0];
+        assert_eq!(plugin.name, "Test Gain");
+        assert_eq!(plugin.manufacturer, "Example");
+        assert_eq!(plugin.unique_id, "com.example.gain");
+        assert_eq!(plugin.version, 0x0001_0203);
+        assert_eq!(plugin.plugin_type, PluginType::Effect);
+        assert_eq!(plugin.format, PluginFormat::Clap);
+
+        // A plain scan_path() only looks in the folder itself
+        assert!(scanner.scan_path(&dir).unwrap().is_empty());
+        let filter = ScanFilter::new().plugin_type(PluginType::Instrument);
+        assert!(scanner.scan_filtered(&filter).unwrap().is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
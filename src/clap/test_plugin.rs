@@ -0,0 +1,357 @@
+//! A CLAP gain plugin compiled into the test binary
+//!
+//! Stereo in and out, one "Gain" parameter (0 to 2, default 1), a fixed tail
+//! and the gain as its state. Registered with [`Library::from_entry()`] under a file name
+//! of the caller's choosing, so scanners and instances find it like a real
+//! library.
+
+use std::ffi::{c_char, c_void, CStr};
+use std::path::Path;
+use std::sync::Arc;
+
+use super::ffi;
+use super::library::Library;
+
+pub const GAIN_PARAM_ID: ffi::clap_id = 7;
+
+/// What the plugin reports as its tail, in samples
+pub const TAIL: u32 = 4800;
+
+/// Keeps the gain plugin registered while alive
+pub(crate) struct Installed {
+    _library: Arc<Library>,
+}
+
+/// Register the gain plugin as a library at `path`, creating the file
+///
+/// The registration lasts as long as the returned value is kept.
+pub(crate) fn install(path: &Path) -> Installed {
+    std::fs::write(path, b"").unwrap();
+    Installed {
+        _library: Library::from_entry(path, &ENTRY).unwrap(),
+    }
+}
+
+struct Gain {
+    plugin: ffi::clap_plugin,
+    gain: f64,
+}
+
+struct Static<T>(T);
+
+// Safety: only holds pointers to static strings
+unsafe impl<T> Sync for Static<T> {}
+
+const fn c(bytes: &'static [u8]) -> *const c_char {
+    bytes.as_ptr() as *const c_char
+}
+
+static FEATURES: Static<[*const c_char; 3]> =
+    Static([c(b"audio-effect\0"), c(b"stereo\0"), std::ptr::null()]);
+
+static DESCRIPTOR: Static<ffi::clap_plugin_descriptor> = Static(ffi::clap_plugin_descriptor {
+    clap_version: ffi::CLAP_VERSION,
+    id: c(b"com.example.gain\0"),
+    name: c(b"Test Gain\0"),
+    vendor: c(b"Example\0"),
+    url: std::ptr::null(),
+    manual_url: std::ptr::null(),
+    support_url: std::ptr::null(),
+    version: c(b"1.2.3\0"),
+    description: c(b"\0"),
+    features: &FEATURES.0 as *const [*const c_char; 3] as *const *const c_char,
+});
+
+static ENTRY: ffi::clap_plugin_entry = ffi::clap_plugin_entry {
+    clap_version: ffi::CLAP_VERSION,
+    init: Some(entry_init),
+    deinit: Some(entry_deinit),
+    get_factory: Some(get_factory),
+};
+
+static FACTORY: ffi::clap_plugin_factory = ffi::clap_plugin_factory {
+    get_plugin_count: Some(plugin_count),
+    get_plugin_descriptor: Some(plugin_descriptor),
+    create_plugin: Some(create_plugin),
+};
+
+static AUDIO_PORTS: ffi::clap_plugin_audio_ports = ffi::clap_plugin_audio_ports {
+    count: Some(ports_count),
+    get: Some(ports_get),
+};
+
+static PARAMS: ffi::clap_plugin_params = ffi::clap_plugin_params {
+    count: Some(params_count),
+    get_info: Some(params_get_info),
+    get_value: Some(params_get_value),
+    value_to_text: None,
+    text_to_value: None,
+    flush: Some(params_flush),
+};
+
+static STATE: ffi::clap_plugin_state = ffi::clap_plugin_state {
+    save: Some(state_save),
+    load: Some(state_load),
+};
+
+static TAIL_EXT: ffi::clap_plugin_tail = ffi::clap_plugin_tail {
+    get: Some(tail_get),
+};
+
+unsafe extern "C" fn entry_init(_path: *const c_char) -> bool {
+    true
+}
+
+unsafe extern "C" fn entry_deinit() {}
+
+unsafe extern "C" fn get_factory(id: *const c_char) -> *const c_void {
+    if CStr::from_ptr(id).to_bytes_with_nul() == ffi::CLAP_PLUGIN_FACTORY_ID {
+        &FACTORY as *const ffi::clap_plugin_factory as *const c_void
+    } else {
+        std::ptr::null()
+    }
+}
+
+unsafe extern "C" fn plugin_count(_factory: *const ffi::clap_plugin_factory) -> u32 {
+    1
+}
+
+unsafe extern "C" fn plugin_descriptor(
+    _factory: *const ffi::clap_plugin_factory,
+    index: u32,
+) -> *const ffi::clap_plugin_descriptor {
+    if index == 0 {
+        &DESCRIPTOR.0
+    } else {
+        std::ptr::null()
+    }
+}
+
+unsafe extern "C" fn create_plugin(
+    _factory: *const ffi::clap_plugin_factory,
+    _host: *const ffi::clap_host,
+    id: *const c_char,
+) -> *const ffi::clap_plugin {
+    if CStr::from_ptr(id) != CStr::from_ptr(DESCRIPTOR.0.id) {
+        return std::ptr::null();
+    }
+    let gain = Box::into_raw(Box::new(Gain {
+        plugin: ffi::clap_plugin {
+            desc: &DESCRIPTOR.0,
+            plugin_data: std::ptr::null_mut(),
+            init: Some(init),
+            destroy: Some(destroy),
+            activate: Some(activate),
+            deactivate: Some(deactivate),
+            start_processing: Some(start_processing),
+            stop_processing: Some(stop_processing),
+            reset: Some(reset),
+            process: Some(process),
+            get_extension: Some(get_extension),
+            on_main_thread: Some(on_main_thread),
+        },
+        gain: 1.0,
+    }));
+    (*gain).plugin.plugin_data = gain as *mut c_void;
+    &(*gain).plugin
+}
+
+unsafe fn gain<'a>(plugin: *const ffi::clap_plugin) -> &'a mut Gain {
+    &mut *((*plugin).plugin_data as *mut Gain)
+}
+
+unsafe extern "C" fn init(_plugin: *const ffi::clap_plugin) -> bool {
+    true
+}
+
+unsafe extern "C" fn destroy(plugin: *const ffi::clap_plugin) {
+    drop(Box::from_raw((*plugin).plugin_data as *mut Gain));
+}
+
+unsafe extern "C" fn activate(
+    _plugin: *const ffi::clap_plugin,
+    _rate: f64,
+    _min: u32,
+    _max: u32,
+) -> bool {
+    true
+}
+
+unsafe extern "C" fn deactivate(_plugin: *const ffi::clap_plugin) {}
+
+unsafe extern "C" fn start_processing(_plugin: *const ffi::clap_plugin) -> bool {
+    true
+}
+
+unsafe extern "C" fn stop_processing(_plugin: *const ffi::clap_plugin) {}
+
+unsafe extern "C" fn reset(_plugin: *const ffi::clap_plugin) {}
+
+unsafe extern "C" fn on_main_thread(_plugin: *const ffi::clap_plugin) {}
+
+/// Apply the parameter changes in `events`
+unsafe fn apply_events(gain: &mut Gain, events: *const ffi::clap_input_events) {
+    let events = &*events;
+    for index in 0..events.size.unwrap()(events) {
+        let header = &*events.get.unwrap()(events, index);
+        if header.space_id == ffi::CLAP_CORE_EVENT_SPACE_ID
+            && header.type_ == ffi::CLAP_EVENT_PARAM_VALUE
+        {
+            let event =
+                &*(header as *const ffi::clap_event_header as *const ffi::clap_event_param_value);
+            if event.param_id == GAIN_PARAM_ID {
+                gain.gain = event.value;
+            }
+        }
+    }
+}
+
+unsafe extern "C" fn process(
+    plugin: *const ffi::clap_plugin,
+    process: *const ffi::clap_process,
+) -> i32 {
+    let gain = gain(plugin);
+    let process = &*process;
+    apply_events(gain, process.in_events);
+
+    let input = &*process.audio_inputs;
+    let output = &*process.audio_outputs;
+    let frames = process.frames_count as usize;
+    for channel in 0..output.channel_count as usize {
+        let from = std::slice::from_raw_parts(*input.data32.add(channel), frames);
+        let to = std::slice::from_raw_parts_mut(*output.data32.add(channel), frames);
+        for (out, sample) in to.iter_mut().zip(from) {
+            *out = (*sample as f64 * gain.gain) as f32;
+        }
+    }
+    ffi::CLAP_PROCESS_CONTINUE
+}
+
+unsafe extern "C" fn get_extension(
+    _plugin: *const ffi::clap_plugin,
+    id: *const c_char,
+) -> *const c_void {
+    match CStr::from_ptr(id).to_bytes_with_nul() {
+        id if id == ffi::CLAP_EXT_AUDIO_PORTS => &AUDIO_PORTS as *const _ as *const c_void,
+        id if id == ffi::CLAP_EXT_PARAMS => &PARAMS as *const _ as *const c_void,
+        id if id == ffi::CLAP_EXT_STATE => &STATE as *const _ as *const c_void,
+        id if id == ffi::CLAP_EXT_TAIL => &TAIL_EXT as *const _ as *const c_void,
+        _ => std::ptr::null(),
+    }
+}
+
+unsafe extern "C" fn tail_get(_plugin: *const ffi::clap_plugin) -> u32 {
+    TAIL
+}
+
+unsafe extern "C" fn ports_count(_plugin: *const ffi::clap_plugin, _is_input: bool) -> u32 {
+    1
+}
+
+unsafe extern "C" fn ports_get(
+    _plugin: *const ffi::clap_plugin,
+    index: u32,
+    _is_input: bool,
+    info: *mut ffi::clap_audio_port_info,
+) -> bool {
+    if index != 0 {
+        return false;
+    }
+    let info = &mut *info;
+    info.id = 0;
+    info.channel_count = 2;
+    info.flags = 1;
+    info.port_type = std::ptr::null();
+    info.in_place_pair = ffi::CLAP_INVALID_ID;
+    true
+}
+
+unsafe extern "C" fn params_count(_plugin: *const ffi::clap_plugin) -> u32 {
+    1
+}
+
+unsafe extern "C" fn params_get_info(
+    _plugin: *const ffi::clap_plugin,
+    index: u32,
+    info: *mut ffi::clap_param_info,
+) -> bool {
+    if index != 0 {
+        return false;
+    }
+    let info = &mut *info;
+    info.id = GAIN_PARAM_ID;
+    info.flags = 0;
+    info.cookie = std::ptr::null_mut();
+    info.name = [0; ffi::CLAP_NAME_SIZE];
+    for (to, from) in info.name.iter_mut().zip(b"Gain") {
+        *to = *from as c_char;
+    }
+    info.module = [0; ffi::CLAP_PATH_SIZE];
+    info.min_value = 0.0;
+    info.max_value = 2.0;
+    info.default_value = 1.0;
+    true
+}
+
+unsafe extern "C" fn params_get_value(
+    plugin: *const ffi::clap_plugin,
+    id: ffi::clap_id,
+    value: *mut f64,
+) -> bool {
+    if id != GAIN_PARAM_ID {
+        return false;
+    }
+    *value = gain(plugin).gain;
+    true
+}
+
+unsafe extern "C" fn params_flush(
+    plugin: *const ffi::clap_plugin,
+    events: *const ffi::clap_input_events,
+    _out: *const ffi::clap_output_events,
+) {
+    apply_events(gain(plugin), events);
+}
+
+unsafe extern "C" fn state_save(
+    plugin: *const ffi::clap_plugin,
+    stream: *const ffi::clap_ostream,
+) -> bool {
+    let bytes = gain(plugin).gain.to_le_bytes();
+    let stream = &*stream;
+    let mut written = 0;
+    while written < bytes.len() {
+        let n = stream.write.unwrap()(
+            stream,
+            bytes[written..].as_ptr() as *const c_void,
+            (bytes.len() - written) as u64,
+        );
+        if n <= 0 {
+            return false;
+        }
+        written += n as usize;
+    }
+    true
+}
+
+unsafe extern "C" fn state_load(
+    plugin: *const ffi::clap_plugin,
+    stream: *const ffi::clap_istream,
+) -> bool {
+    let mut bytes = [0u8; 8];
+    let stream = &*stream;
+    let mut read = 0;
+    while read < bytes.len() {
+        let n = stream.read.unwrap()(
+            stream,
+            bytes[read..].as_mut_ptr() as *mut c_void,
+            (bytes.len() - read) as u64,
+        );
+        if n <= 0 {
+            return false;
+        }
+        read += n as usize;
+    }
+    gain(plugin).gain = f64::from_le_bytes(bytes);
+    true
+}
@@ -0,0 +1,275 @@
+//! Copying settings between instances of the same plugin
+//!
+//! [`ClipboardState`] captures a plugin's state together with which plugin
+//! (and which version of it) produced it, so "copy settings to the instance
+//! on another track" can refuse to paste a reverb's state into a compressor,
+//! or a newer plugin version's state into an older one that can't read it.
+//!
+//! [`to_bytes()`](ClipboardState::to_bytes) gives a self-describing byte
+//! string for hosts that put it on the system clipboard.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::clipboard::ClipboardState;
+//! # fn example(source: &impl PluginInstance, target: &mut impl PluginInstance) -> Result<()> {
+//! let copied = ClipboardState::copy(source)?;
+//!
+//! // Grey out "Paste" for instances that can't take it
+//! if copied.compatibility(target.info()).is_compatible() {
+//!     copied.paste_into(target)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::cache::parse_format;
+use crate::session::{write_bytes, Reader};
+use crate::{Error, PluginFormat, PluginInfo, PluginInstance, Result};
+
+/// Magic bytes at the start of serialized clipboard contents
+const MAGIC: &[u8; 8] = b"RACKCLIP";
+
+/// Current serialization version
+const CLIPBOARD_VERSION: u32 = 1;
+
+/// Whether copied state can be pasted into a plugin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compatibility {
+    /// Same plugin and version
+    Compatible,
+
+    /// Same plugin, copied from an older version
+    ///
+    /// Plugins are expected to read state from their older versions, so this
+    /// can be pasted.
+    OlderVersion {
+        /// Version the state was copied from
+        copied: u32,
+
+        /// Version of the target plugin
+        target: u32,
+    },
+
+    /// Same plugin, copied from a newer version the target may not understand
+    NewerVersion {
+        /// Version the state was copied from
+        copied: u32,
+
+        /// Version of the target plugin
+        target: u32,
+    },
+
+    /// A different plugin (or the same plugin in another format)
+    DifferentPlugin,
+}
+
+impl Compatibility {
+    /// Whether the state can be pasted
+    pub fn is_compatible(self) -> bool {
+        matches!(
+            self,
+            Compatibility::Compatible | Compatibility::OlderVersion { .. }
+        )
+    }
+}
+
+/// A plugin's state tagged with the plugin it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClipboardState {
+    /// Name of the source plugin, for messages
+    pub name: String,
+
+    /// Format of the source plugin
+    pub format: PluginFormat,
+
+    /// Unique ID of the source plugin
+    pub unique_id: String,
+
+    /// Version of the source plugin
+    pub version: u32,
+
+    /// The state blob from [`get_state()`](PluginInstance::get_state)
+    pub state: Vec<u8>,
+}
+
+impl ClipboardState {
+    /// Copy a plugin's current state
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin's state can't be read
+    pub fn copy<P: PluginInstance + ?Sized>(plugin: &P) -> Result<Self> {
+        let info = plugin.info();
+        Ok(Self {
+            name: info.name.clone(),
+            format: info.format,
+            unique_id: info.unique_id.clone(),
+            version: info.version,
+            state: plugin.get_state()?,
+        })
+    }
+
+    /// Check whether this state can be pasted into the plugin described by `info`
+    pub fn compatibility(&self, info: &PluginInfo) -> Compatibility {
+        if self.format != info.format || self.unique_id != info.unique_id {
+            Compatibility::DifferentPlugin
+        } else if self.version < info.version {
+            Compatibility::OlderVersion {
+                copied: self.version,
+                target: info.version,
+            }
+        } else if self.version > info.version {
+            Compatibility::NewerVersion {
+                copied: self.version,
+                target: info.version,
+            }
+        } else {
+            Compatibility::Compatible
+        }
+    }
+
+    /// Apply the copied state to another instance
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the state isn't [compatible](Compatibility::is_compatible)
+    /// with the target, or the target rejects it
+    pub fn paste_into<P: PluginInstance + ?Sized>(&self, plugin: &mut P) -> Result<()> {
+        match self.compatibility(plugin.info()) {
+            Compatibility::DifferentPlugin => Err(Error::Other(format!(
+                "Can't paste settings of {} into {}",
+                self.name,
+                plugin.info().name
+            ))),
+            Compatibility::NewerVersion { copied, target } => Err(Error::Other(format!(
+                "Settings were copied from a newer version of {} ({:#x}) than this one ({:#x})",
+                self.name, copied, target
+            ))),
+            _ => plugin.set_state(&self.state),
+        }
+    }
+
+    /// Serialize for the system clipboard
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the state is 4 GiB or larger
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&CLIPBOARD_VERSION.to_le_bytes());
+        write_bytes(&mut out, self.name.as_bytes())?;
+        write_bytes(&mut out, self.format.to_string().as_bytes())?;
+        write_bytes(&mut out, self.unique_id.as_bytes())?;
+        out.extend_from_slice(&self.version.to_le_bytes());
+        write_bytes(&mut out, &self.state)?;
+        Ok(out)
+    }
+
+    /// Parse data produced by [`to_bytes()`](Self::to_bytes)
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidFormat`] if the data isn't copied plugin state
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(data);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(Error::InvalidFormat(
+                "Not copied plugin settings".to_string(),
+            ));
+        }
+        let version = reader.u32()?;
+        if version > CLIPBOARD_VERSION {
+            return Err(Error::InvalidFormat(format!(
+                "Copied settings version {} is newer than supported ({})",
+                version, CLIPBOARD_VERSION
+            )));
+        }
+
+        let name = reader.string()?;
+        let format = reader.string()?;
+        let format = parse_format(&format)
+            .ok_or_else(|| Error::InvalidFormat(format!("Unknown plugin format '{}'", format)))?;
+        let state = Self {
+            name,
+            format,
+            unique_id: reader.string()?,
+            version: reader.u32()?,
+            state: reader.bytes()?.to_vec(),
+        };
+        if !reader.is_empty() {
+            return Err(Error::InvalidFormat(
+                "Trailing data after copied settings".to_string(),
+            ));
+        }
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockPlugin;
+    use crate::PluginType;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_copy_paste_between_instances() {
+        let mut source = MockPlugin::new();
+        source.set_parameter(1, 0.5).unwrap();
+        let mut target = MockPlugin::new();
+
+        let copied = ClipboardState::copy(&source).unwrap();
+        let copied = ClipboardState::from_bytes(&copied.to_bytes().unwrap()).unwrap();
+        assert_eq!(
+            copied.compatibility(target.info()),
+            Compatibility::Compatible
+        );
+        copied.paste_into(&mut target).unwrap();
+        assert_eq!(target.get_parameter(1).unwrap(), 0.5);
+
+        assert!(ClipboardState::from_bytes(b"RACKSESS").is_err());
+    }
+
+    #[test]
+    fn test_compatibility_checks() {
+        let info = |id: &str, version: u32| {
+            PluginInfo::new(
+                "Comp".to_string(),
+                "Acme".to_string(),
+                version,
+                PluginType::Effect,
+                PathBuf::new(),
+                id.to_string(),
+            )
+        };
+        let copied = ClipboardState {
+            name: "Comp".to_string(),
+            format: PluginFormat::Unknown,
+            unique_id: "comp".to_string(),
+            version: 2,
+            state: Vec::new(),
+        };
+
+        assert!(copied.compatibility(&info("comp", 3)).is_compatible());
+        assert_eq!(
+            copied.compatibility(&info("comp", 1)),
+            Compatibility::NewerVersion {
+                copied: 2,
+                target: 1
+            }
+        );
+        assert_eq!(
+            copied.compatibility(&info("reverb", 2)),
+            Compatibility::DifferentPlugin
+        );
+        assert!(!copied
+            .compatibility(&info("comp", 2).with_format(PluginFormat::Vst3))
+            .is_compatible());
+
+        // The mock reports "mock-gain", so pasting is refused
+        assert!(copied.paste_into(&mut MockPlugin::new()).is_err());
+    }
+}
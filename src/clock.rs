@@ -0,0 +1,214 @@
+//! MIDI clock generated from the transport
+//!
+//! [`MidiClock`] turns a [`Transport`] into MIDI Timing Clock (24 per quarter
+//! note), Start, Continue and Stop events with sample offsets, one block at a
+//! time. Tempo changes in the tempo map are followed exactly. The events can
+//! be queued into an [`EventPort`](crate::port::EventPort) to sync hosted
+//! plugins (arpeggiators, step sequencers), or, with the `midir` feature,
+//! sent to external hardware with [`send_to_output()`].
+//!
+//! Song Position Pointer isn't part of the event model, so a transport that
+//! starts away from the beginning of the song sends Continue, and jumps while
+//! playing aren't signalled to the receiver.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::clock::MidiClock;
+//! # use rack::port::EventPort;
+//! # use rack::tempo::Transport;
+//! # fn example(mut arp: impl PluginInstance, mut transport: Transport) -> Result<()> {
+//! let mut clock = MidiClock::new();
+//! let mut port = EventPort::new();
+//!
+//! let mut left = vec![0.0f32; 512];
+//! let mut right = vec![0.0f32; 512];
+//! loop {
+//!     clock.process(&transport, 512, &mut port);
+//!     port.process(&mut arp, &[], &mut [&mut left, &mut right], 512)?;
+//!     transport.advance(512);
+//! }
+//! # }
+//! ```
+
+use crate::tempo::Transport;
+use crate::MidiEvent;
+
+/// Timing Clock pulses per quarter note
+pub const PULSES_PER_QUARTER_NOTE: f64 = 24.0;
+
+/// Generator of MIDI clock events from a transport
+#[derive(Debug, Clone, Default)]
+pub struct MidiClock {
+    /// Whether the transport was playing in the last block
+    playing: bool,
+
+    /// Keep sending Timing Clock at the current tempo while stopped
+    free_running: bool,
+
+    /// Pulses elapsed while stopped, for free-running clock
+    free_phase: f64,
+}
+
+impl MidiClock {
+    /// Create a clock that is silent while the transport is stopped
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep sending Timing Clock while the transport is stopped
+    ///
+    /// Many devices show and follow the incoming tempo from the clock alone,
+    /// and expect it to run continuously.
+    pub fn with_free_running(mut self, free_running: bool) -> Self {
+        self.free_running = free_running;
+        self
+    }
+
+    /// Forget the last transport state, so the next playing block sends Start
+    /// or Continue again
+    pub fn reset(&mut self) {
+        self.playing = false;
+        self.free_phase = 0.0;
+    }
+
+    /// Generate the events of a block of `num_frames` starting at the
+    /// transport's position
+    ///
+    /// Start (at the beginning of the song) or Continue is sent at offset 0 of
+    /// the first block the transport plays, Stop at offset 0 of the first
+    /// block it doesn't. Events are added in time order.
+    pub fn process(
+        &mut self,
+        transport: &Transport,
+        num_frames: usize,
+        out: &mut impl Extend<MidiEvent>,
+    ) {
+        if transport.playing != self.playing {
+            self.playing = transport.playing;
+            let event = if !transport.playing {
+                self.free_phase = 0.0;
+                MidiEvent::stop(0)
+            } else if transport.position == 0 {
+                MidiEvent::start(0)
+            } else {
+                MidiEvent::continue_playback(0)
+            };
+            out.extend(std::iter::once(event));
+        }
+
+        if transport.playing {
+            let start = transport.position;
+            let end = start + num_frames as u64;
+            // Tolerate rounding when the block starts on a pulse
+            let mut pulse =
+                (transport.beat_at_sample(start) * PULSES_PER_QUARTER_NOTE - 1e-6).ceil();
+            loop {
+                let position = transport.sample_at_beat(pulse / PULSES_PER_QUARTER_NOTE);
+                if position >= end {
+                    break;
+                }
+                let offset = position.saturating_sub(start) as u32;
+                out.extend(std::iter::once(MidiEvent::timing_clock(offset)));
+                pulse += 1.0;
+            }
+        } else if self.free_running && transport.sample_rate > 0.0 {
+            let pulses_per_frame =
+                transport.tempo() / 60.0 * PULSES_PER_QUARTER_NOTE / transport.sample_rate;
+            let mut next = (self.free_phase - 1e-6).ceil();
+            let block_end = self.free_phase + pulses_per_frame * num_frames as f64;
+            while next < block_end {
+                let offset = ((next - self.free_phase) / pulses_per_frame).round() as u32;
+                out.extend(std::iter::once(MidiEvent::timing_clock(offset)));
+                next += 1.0;
+            }
+            self.free_phase = block_end;
+        }
+    }
+}
+
+/// Send clock events to a hardware MIDI output
+///
+/// The events are sent immediately, in order, so they are only as precise as
+/// the block size: call this right after [`MidiClock::process()`] with small
+/// blocks. Events other than system real-time messages are skipped.
+///
+/// # Errors
+///
+/// Returns [`Error::Other`](crate::Error::Other) if the output refuses a
+/// message
+#[cfg(feature = "midir")]
+pub fn send_to_output(
+    output: &mut midir::MidiOutputConnection,
+    events: &[MidiEvent],
+) -> crate::Result<()> {
+    use crate::MidiEventKind;
+
+    for event in events {
+        let status = match event.kind {
+            MidiEventKind::TimingClock => 0xF8,
+            MidiEventKind::Start => 0xFA,
+            MidiEventKind::Continue => 0xFB,
+            MidiEventKind::Stop => 0xFC,
+            _ => continue,
+        };
+        output
+            .send(&[status])
+            .map_err(|e| crate::Error::Other(format!("Failed to send MIDI clock: {}", e)))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tempo::TempoMap;
+    use crate::MidiEventKind;
+
+    #[test]
+    fn test_clock_follows_transport() {
+        // 125 BPM at 1 kHz: a pulse every 20 samples
+        let mut transport = Transport::new(1000.0, TempoMap::new(125.0));
+        let mut clock = MidiClock::new();
+        let mut events = Vec::new();
+
+        clock.process(&transport, 50, &mut events);
+        assert!(events.is_empty());
+
+        transport.playing = true;
+        clock.process(&transport, 50, &mut events);
+        assert_eq!(events[0].kind, MidiEventKind::Start);
+        let offsets: Vec<u32> = events[1..].iter().map(|e| e.sample_offset).collect();
+        assert_eq!(offsets, vec![0, 20, 40]);
+
+        events.clear();
+        transport.advance(50);
+        clock.process(&transport, 50, &mut events);
+        let offsets: Vec<u32> = events.iter().map(|e| e.sample_offset).collect();
+        assert_eq!(offsets, vec![10, 30]);
+
+        events.clear();
+        transport.playing = false;
+        clock.process(&transport, 50, &mut events);
+        assert_eq!(events, vec![MidiEvent::stop(0)]);
+
+        // Resuming mid-song continues rather than starting over
+        events.clear();
+        transport.playing = true;
+        clock.process(&transport, 1, &mut events);
+        assert_eq!(events[0].kind, MidiEventKind::Continue);
+    }
+
+    #[test]
+    fn test_free_running_clock() {
+        let transport = Transport::new(1000.0, TempoMap::new(125.0));
+        let mut clock = MidiClock::new().with_free_running(true);
+        let mut events = Vec::new();
+        clock.process(&transport, 30, &mut events);
+        clock.process(&transport, 30, &mut events);
+
+        let offsets: Vec<u32> = events.iter().map(|e| e.sample_offset).collect();
+        assert_eq!(offsets, vec![0, 20, 10]);
+    }
+}
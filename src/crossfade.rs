@@ -0,0 +1,452 @@
+//! Glitch-free preset changes on a playing instrument
+//!
+//! Calling [`load_preset()`](PluginInstance::load_preset) while a note is
+//! sounding usually cuts it off or jumps abruptly to the new sound. A
+//! [`PresetCrossfade`] hides this by running two instances of the same plugin:
+//! the preset is loaded into the idle instance, and for a short time both are
+//! rendered while the output crossfades from the old sound to the new one.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::crossfade::PresetCrossfade;
+//! # fn example<S: PluginScanner>(scanner: &S, info: &PluginInfo) -> Result<()> {
+//! let mut synth = PresetCrossfade::new(scanner.load(info)?, scanner.load(info)?);
+//! synth.initialize(48000.0, 512)?;
+//!
+//! // ... while audio is running ...
+//! synth.load_preset_crossfade(5, 50.0)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::meter::MeterReading;
+use crate::node::BlockContext;
+use crate::quirks::Quirks;
+use crate::{CurrentPreset, Error, MidiEvent, ParameterChange, ParameterInfo, PluginInfo, PluginInstance, PresetInfo, Result};
+use smallvec::SmallVec;
+use std::f32::consts::FRAC_PI_2;
+
+/// A crossfade in progress
+#[derive(Debug, Clone, Copy)]
+struct Fade {
+    position: usize,
+    length: usize,
+}
+
+/// Two instances of one plugin that crossfade between presets
+///
+/// All [`PluginInstance`] methods act on the active instance. During a
+/// crossfade the previous instance keeps rendering (and keeps receiving MIDI,
+/// so held notes are released) while it fades out.
+pub struct PresetCrossfade<P> {
+    active: P,
+    fading: P,
+    fade: Option<Fade>,
+    sample_rate: f64,
+    /// Output buffers for the fading instance, one per output channel
+    scratch: Vec<Vec<f32>>,
+}
+
+impl<P: PluginInstance> PresetCrossfade<P> {
+    /// Combine two freshly loaded instances of the same plugin
+    pub fn new(active: P, standby: P) -> Self {
+        Self {
+            active,
+            fading: standby,
+            fade: None,
+            sample_rate: 0.0,
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Load a preset, crossfading to it over `ms` milliseconds
+    ///
+    /// The idle instance is given the active instance's state, then loads the
+    /// preset and becomes active. A crossfade already in progress is cut short.
+    pub fn load_preset_crossfade(&mut self, preset_number: i32, ms: f32) -> Result<()> {
+        self.crossfade_to(ms, |plugin| plugin.load_preset(preset_number))
+    }
+
+    /// Restore a state, crossfading to it over `ms` milliseconds
+    pub fn set_state_crossfade(&mut self, data: &[u8], ms: f32) -> Result<()> {
+        self.crossfade_to(ms, |plugin| plugin.set_state(data))
+    }
+
+    /// Apply an arbitrary change to the idle instance and crossfade to it
+    ///
+    /// `change` receives the idle instance after it has been reset and given
+    /// the active instance's state, and is moved to the active instance's
+    /// sample position before taking over. If it fails, nothing changes.
+    pub fn crossfade_to(
+        &mut self,
+        ms: f32,
+        change: impl FnOnce(&mut P) -> Result<()>,
+    ) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        self.fade = None;
+        let state = self.active.get_state()?;
+        self.fading.reset()?;
+        self.fading.set_state(&state)?;
+        change(&mut self.fading)?;
+        // The idle instance has been silent; pick up where the active one is
+        self.fading.set_sample_position(self.active.sample_position())?;
+
+        std::mem::swap(&mut self.active, &mut self.fading);
+        let length = (ms.max(0.0) as f64 * self.sample_rate / 1000.0) as usize;
+        self.fade = (length > 0).then_some(Fade {
+            position: 0,
+            length,
+        });
+        Ok(())
+    }
+
+    /// Check whether a crossfade is in progress
+    pub fn is_crossfading(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    /// The active instance
+    pub fn active(&self) -> &P {
+        &self.active
+    }
+
+    /// The active instance, mutably
+    pub fn active_mut(&mut self) -> &mut P {
+        &mut self.active
+    }
+
+    /// Render a block of `num_frames` with `active` running the active
+    /// instance and `fading` the fading one, crossfading between them
+    fn render(
+        &mut self,
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        active: impl FnOnce(&mut P, &mut [&mut [f32]]) -> Result<()>,
+        fading: impl FnOnce(&mut P, &mut [&mut [f32]]) -> Result<()>,
+    ) -> Result<()> {
+        active(&mut self.active, outputs)?;
+
+        let Some(mut fade) = self.fade else {
+            return Ok(());
+        };
+
+        let mut scratch: SmallVec<[&mut [f32]; 8]> = self
+            .scratch
+            .iter_mut()
+            .map(|b| {
+                let frames = num_frames.min(b.len());
+                &mut b[..frames]
+            })
+            .collect();
+        if fading(&mut self.fading, &mut scratch).is_err() {
+            // The old sound can't be rendered; cut straight to the new one
+            self.fade = None;
+            return Ok(());
+        }
+
+        // Equal-power crossfade from the fading instance to the active one
+        let start = fade.position;
+        for (output, old) in outputs.iter_mut().zip(scratch.iter()) {
+            for (i, (sample, &old)) in output[..num_frames].iter_mut().zip(old.iter()).enumerate() {
+                let t = ((start + i) as f32 / fade.length as f32).min(1.0) * FRAC_PI_2;
+                *sample = *sample * t.sin() + old * t.cos();
+            }
+        }
+
+        fade.position += num_frames;
+        self.fade = (fade.position < fade.length).then_some(fade);
+        Ok(())
+    }
+}
+
+impl<P: PluginInstance> PluginInstance for PresetCrossfade<P> {
+    fn initialize(&mut self, sample_rate: f64, max_block_size: usize) -> Result<()> {
+        self.active.initialize(sample_rate, max_block_size)?;
+        self.fading.initialize(sample_rate, max_block_size)?;
+        self.sample_rate = sample_rate;
+        self.scratch = vec![vec![0.0; max_block_size]; self.active.output_channels()];
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.fade = None;
+        self.active.reset()?;
+        self.fading.reset()
+    }
+
+    fn process(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        self.render(
+            outputs,
+            num_frames,
+            |plugin, outputs| plugin.process(inputs, outputs, num_frames),
+            |plugin, outputs| plugin.process(inputs, outputs, num_frames),
+        )
+    }
+
+    fn process_with_context(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        context: &BlockContext,
+    ) -> Result<()> {
+        self.render(
+            outputs,
+            context.num_frames,
+            |plugin, outputs| plugin.process_with_context(inputs, outputs, context),
+            |plugin, outputs| plugin.process_with_context(inputs, outputs, context),
+        )
+    }
+
+    /// Process the active instance with `changes` and `midi`; the fading one
+    /// only receives the MIDI, to release its notes
+    fn process_with_events(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        changes: &[ParameterChange],
+        midi: &[MidiEvent],
+    ) -> Result<()> {
+        self.render(
+            outputs,
+            num_frames,
+            |plugin, outputs| {
+                plugin.process_with_events(inputs, outputs, num_frames, changes, midi)
+            },
+            |plugin, outputs| plugin.process_with_events(inputs, outputs, num_frames, &[], midi),
+        )
+    }
+
+    fn sample_position(&self) -> u64 {
+        self.active.sample_position()
+    }
+
+    fn set_sample_position(&mut self, position: u64) -> Result<()> {
+        self.active.set_sample_position(position)?;
+        self.fading.set_sample_position(position)
+    }
+
+    fn flush_events(&mut self) -> Result<()> {
+        if self.fade.is_some() {
+            self.fading.flush_events()?;
+        }
+        self.active.flush_events()
+    }
+
+    fn parameter_count(&self) -> usize {
+        self.active.parameter_count()
+    }
+
+    fn parameter_info(&self, index: usize) -> Result<ParameterInfo> {
+        self.active.parameter_info(index)
+    }
+
+    fn get_parameter(&self, index: usize) -> Result<f32> {
+        self.active.get_parameter(index)
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) -> Result<()> {
+        self.active.set_parameter(index, value)
+    }
+
+    fn send_midi(&mut self, events: &[MidiEvent]) -> Result<()> {
+        if self.fade.is_some() {
+            // Release notes that are still sounding in the old instance
+            self.fading.send_midi(events)?;
+        }
+        self.active.send_midi(events)
+    }
+
+    fn preset_count(&self) -> Result<usize> {
+        self.active.preset_count()
+    }
+
+    fn preset_info(&self, index: usize) -> Result<PresetInfo> {
+        self.active.preset_info(index)
+    }
+
+    fn load_preset(&mut self, preset_number: i32) -> Result<()> {
+        self.fade = None;
+        self.active.load_preset(preset_number)
+    }
+
+    fn current_preset(&self) -> Result<Option<CurrentPreset>> {
+        self.active.current_preset()
+    }
+
+    fn get_state(&self) -> Result<Vec<u8>> {
+        self.active.get_state()
+    }
+
+    fn set_state(&mut self, data: &[u8]) -> Result<()> {
+        self.fade = None;
+        self.active.set_state(data)
+    }
+
+    fn get_state_to_writer(&self, writer: &mut dyn std::io::Write) -> Result<()> {
+        self.active.get_state_to_writer(writer)
+    }
+
+    fn set_state_from_reader(&mut self, reader: &mut dyn std::io::Read) -> Result<()> {
+        self.fade = None;
+        self.active.set_state_from_reader(reader)
+    }
+
+    fn set_state_from_file(&mut self, path: &std::path::Path) -> Result<()> {
+        self.fade = None;
+        self.active.set_state_from_file(path)
+    }
+
+    fn take_state_dirty(&self) -> bool {
+        self.active.take_state_dirty()
+    }
+
+    fn info(&self) -> &PluginInfo {
+        self.active.info()
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.active.is_initialized() && self.fading.is_initialized()
+    }
+
+    fn input_channels(&self) -> usize {
+        self.active.input_channels()
+    }
+
+    fn output_channels(&self) -> usize {
+        self.active.output_channels()
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.active.latency_samples()
+    }
+
+    fn tail_samples(&self) -> usize {
+        self.active.tail_samples()
+    }
+
+    fn tail_seconds(&self) -> f64 {
+        self.active.tail_seconds()
+    }
+
+    fn set_bypass(&mut self, bypassed: bool) -> Result<()> {
+        self.active.set_bypass(bypassed)?;
+        self.fading.set_bypass(bypassed)
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.active.is_bypassed()
+    }
+
+    fn quirks(&self) -> Quirks {
+        self.active.quirks()
+    }
+
+    fn plugin_meter(&self) -> Option<Vec<MeterReading>> {
+        self.active.plugin_meter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockPlugin;
+
+    #[test]
+    fn test_crossfade_between_gains() {
+        let mut plugin = PresetCrossfade::new(MockPlugin::new(), MockPlugin::new());
+        plugin.initialize(1000.0, 64).unwrap();
+
+        // Gain parameter: 0 dB at the default, -60 dB at 0.0
+        let input = vec![1.0f32; 64];
+        let mut left = vec![0.0f32; 64];
+        let mut right = vec![0.0f32; 64];
+        plugin
+            .crossfade_to(64.0, |p| p.set_parameter(0, 0.0))
+            .unwrap();
+        assert!(plugin.is_crossfading());
+
+        plugin
+            .process(&[&input, &input], &mut [&mut left, &mut right], 64)
+            .unwrap();
+        assert!(!plugin.is_crossfading());
+
+        // Starts at the old sound and moves monotonically towards the new one
+        assert!((left[0] - 1.0).abs() < 1e-6);
+        assert!(left.windows(2).all(|w| w[1] <= w[0]));
+        assert!(left[63] < 0.1);
+
+        left.fill(0.0);
+        plugin
+            .process(&[&input, &input], &mut [&mut left, &mut right], 64)
+            .unwrap();
+        assert!(left.iter().all(|&s| (s - 0.001).abs() < 1e-4));
+    }
+
+    #[test]
+    fn test_failed_change_keeps_active_instance() {
+        let mut plugin = PresetCrossfade::new(MockPlugin::new(), MockPlugin::new());
+        plugin.initialize(48000.0, 64).unwrap();
+        plugin.set_parameter(0, 0.25).unwrap();
+
+        // The mock has no presets
+        assert!(plugin.load_preset_crossfade(1, 20.0).is_err());
+        assert!(!plugin.is_crossfading());
+        assert_eq!(plugin.get_parameter(0).unwrap(), 0.25);
+    }
+
+    #[test]
+    fn test_context_reaches_both_instances() {
+        use crate::node::FnNode;
+        use std::sync::{Arc, Mutex};
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let tap = || {
+            let seen = Arc::clone(&seen);
+            FnNode::new("Tap", 1, 1, move |_, _, context: &BlockContext| {
+                seen.lock().unwrap().push(context.sample_position)
+            })
+        };
+        let mut plugin = PresetCrossfade::new(tap(), tap());
+        plugin.initialize(1000.0, 64).unwrap();
+        plugin.crossfade_to(128.0, |_| Ok(())).unwrap();
+
+        let input = vec![1.0f32; 64];
+        let mut output = vec![0.0f32; 64];
+        let context = BlockContext::new(1000.0, 5000, 64);
+        plugin
+            .process_with_context(&[&input], &mut [&mut output], &context)
+            .unwrap();
+        assert_eq!(*seen.lock().unwrap(), vec![5000, 5000]);
+        assert_eq!(plugin.sample_position(), 5064);
+    }
+
+    #[test]
+    fn test_new_instance_continues_sample_position() {
+        let mut plugin = PresetCrossfade::new(MockPlugin::new(), MockPlugin::new());
+        plugin.initialize(1000.0, 64).unwrap();
+
+        let input = vec![1.0f32; 64];
+        let mut left = vec![0.0f32; 64];
+        let mut right = vec![0.0f32; 64];
+        plugin
+            .process(&[&input, &input], &mut [&mut left, &mut right], 64)
+            .unwrap();
+        assert_eq!(plugin.sample_position(), 64);
+
+        plugin.crossfade_to(32.0, |_| Ok(())).unwrap();
+        assert_eq!(plugin.sample_position(), 64);
+        plugin.flush_events().unwrap();
+        assert_eq!(plugin.sample_position(), 64);
+    }
+}
@@ -0,0 +1,340 @@
+//! Non-realtime work scheduled around the audio clock
+//!
+//! Loading a preset or saving a plugin's state can take milliseconds, and it
+//! can't overlap `process()`: the plugin is only ever used from one thread at
+//! a time. Done at a random moment, it delays the next block and the device
+//! drops out. [`DeferredWork`] runs such work cooperatively instead, on the
+//! thread that processes the plugin, right after a block completes, when the
+//! whole period until the next callback is still ahead.
+//!
+//! The controller queues work through a [`WorkQueue`] from any thread. After
+//! each block, at most one job runs, and only while the plugin's measured
+//! load leaves enough headroom; a busy stretch (a dense passage, another
+//! plugin's spike) postpones the work until the load falls. Work that has
+//! waited longer than [`DeferConfig::max_delay`] runs regardless, so it is
+//! never starved. Jobs can also be scheduled ahead for a timeline position,
+//! e.g. a preset change that lands on the next bar.
+//!
+//! Load and delays follow the audio clock: the load is the time spent
+//! processing a block relative to its duration at the sample rate.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::deferred::DeferredWork;
+//! # fn example<P: PluginInstance + 'static>(mut plugin: P) -> Result<()> {
+//! plugin.initialize(48000.0, 512)?;
+//! let mut deferred = DeferredWork::new(48000.0);
+//!
+//! // On the controller thread
+//! let queue = deferred.queue();
+//! queue.defer(|plugin: &mut P| plugin.load_preset(3));
+//!
+//! // On the audio thread
+//! let input = vec![0.0f32; 512];
+//! let mut output = vec![0.0f32; 512];
+//! deferred.process(&mut plugin, &[&input], &mut [&mut output], 512)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::events::{self, HostEvent};
+use crate::{PluginInstance, Result};
+use std::collections::VecDeque;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// How much of a drop in load is followed per block; rises are followed
+/// immediately
+const LOAD_DECAY: f64 = 0.1;
+
+/// Work for the plugin, returning an error to report
+type Work<P> = Box<dyn FnOnce(&mut P) -> Result<()> + Send>;
+
+/// When deferred work may run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeferConfig {
+    /// Share of each block's period that must be left idle for work to run
+    /// (0.5: the plugin took at most half of the block's duration)
+    pub min_headroom: f64,
+
+    /// Longest a due job is postponed for lack of headroom
+    pub max_delay: Duration,
+}
+
+impl Default for DeferConfig {
+    fn default() -> Self {
+        Self {
+            min_headroom: 0.5,
+            max_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+/// A queued job
+struct Job<P: ?Sized> {
+    work: Work<P>,
+    /// Timeline position the job waits for, if any
+    not_before: Option<u64>,
+    /// Frames processed when the job arrived, set on the audio side
+    arrived: Option<u64>,
+}
+
+/// Queues work for a [`DeferredWork`] from any thread
+pub struct WorkQueue<P: ?Sized> {
+    sender: mpsc::Sender<Job<P>>,
+}
+
+impl<P: ?Sized> Clone for WorkQueue<P> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<P: ?Sized> WorkQueue<P> {
+    /// Run `work` after an upcoming block, once there is headroom
+    ///
+    /// Jobs run in the order they were queued (among those that are due).
+    /// Work queued after the scheduler is dropped is discarded.
+    pub fn defer<F>(&self, work: F)
+    where
+        F: FnOnce(&mut P) -> Result<()> + Send + 'static,
+    {
+        self.send(None, work);
+    }
+
+    /// Run `work` after the block that reaches timeline position `position`
+    /// (the plugin's [`sample_position()`](PluginInstance::sample_position)),
+    /// or as soon after as there is headroom
+    ///
+    /// Queue it a little ahead, so it's waiting when the position comes.
+    pub fn defer_at<F>(&self, position: u64, work: F)
+    where
+        F: FnOnce(&mut P) -> Result<()> + Send + 'static,
+    {
+        self.send(Some(position), work);
+    }
+
+    fn send<F>(&self, not_before: Option<u64>, work: F)
+    where
+        F: FnOnce(&mut P) -> Result<()> + Send + 'static,
+    {
+        let _ = self.sender.send(Job {
+            work: Box::new(work),
+            not_before,
+            arrived: None,
+        });
+    }
+}
+
+/// Runs queued non-realtime work between a plugin's blocks
+pub struct DeferredWork<P: ?Sized> {
+    config: DeferConfig,
+    sample_rate: f64,
+    sender: mpsc::Sender<Job<P>>,
+    receiver: mpsc::Receiver<Job<P>>,
+    pending: VecDeque<Job<P>>,
+    /// Frames processed so far
+    clock: u64,
+    /// Peak-following average of the per-block load
+    load: f64,
+}
+
+impl<P: PluginInstance + ?Sized> DeferredWork<P> {
+    /// Create a scheduler for a plugin running at `sample_rate`, with the
+    /// default [`DeferConfig`]
+    pub fn new(sample_rate: f64) -> Self {
+        Self::with_config(sample_rate, DeferConfig::default())
+    }
+
+    /// Create a scheduler for a plugin running at `sample_rate`
+    pub fn with_config(sample_rate: f64, config: DeferConfig) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            config,
+            sample_rate,
+            sender,
+            receiver,
+            pending: VecDeque::with_capacity(32),
+            clock: 0,
+            load: 0.0,
+        }
+    }
+
+    /// A handle for queuing work, for the controller thread
+    pub fn queue(&self) -> WorkQueue<P> {
+        WorkQueue {
+            sender: self.sender.clone(),
+        }
+    }
+
+    /// Process a block, then run deferred work if there is headroom
+    ///
+    /// # Errors
+    ///
+    /// Returns the error from `process()`; no work runs after a failed
+    /// block. Errors from the work itself are reported as
+    /// [`HostEvent::Error`].
+    pub fn process(
+        &mut self,
+        plugin: &mut P,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        let start = Instant::now();
+        plugin.process(inputs, outputs, num_frames)?;
+        self.block_done(plugin, num_frames, start.elapsed());
+        Ok(())
+    }
+
+    /// Record a block the host processed itself, taking `elapsed`, then run
+    /// deferred work if there is headroom
+    ///
+    /// For hosts that process the plugin as part of a larger block (a
+    /// [`Graph`](crate::graph::Graph), say) and time the whole of it.
+    pub fn block_done(&mut self, plugin: &mut P, num_frames: usize, elapsed: Duration) {
+        let period = num_frames as f64 / self.sample_rate;
+        if period > 0.0 {
+            let load = elapsed.as_secs_f64() / period;
+            self.load = if load > self.load {
+                load
+            } else {
+                self.load + (load - self.load) * LOAD_DECAY
+            };
+        }
+        self.clock += num_frames as u64;
+
+        while let Ok(mut job) = self.receiver.try_recv() {
+            job.arrived = Some(self.clock);
+            self.pending.push_back(job);
+        }
+
+        let position = plugin.sample_position();
+        let Some(index) = self
+            .pending
+            .iter()
+            .position(|job| job.not_before.is_none_or(|at| at <= position))
+        else {
+            return;
+        };
+
+        let waited = self.clock - self.pending[index].arrived.unwrap_or(self.clock);
+        let max_delay = (self.config.max_delay.as_secs_f64() * self.sample_rate) as u64;
+        if self.headroom() < self.config.min_headroom && waited < max_delay {
+            return;
+        }
+
+        if let Some(job) = self.pending.remove(index) {
+            if let Err(error) = (job.work)(plugin) {
+                events::emit(HostEvent::Error {
+                    info: Some(plugin.info()),
+                    error: &error,
+                });
+            }
+        }
+    }
+
+    /// Share of the block period the plugin has been leaving idle, from 1
+    /// (no load) down to 0 (or below, when blocks take longer than real
+    /// time)
+    pub fn headroom(&self) -> f64 {
+        1.0 - self.load
+    }
+
+    /// Jobs waiting to run
+    ///
+    /// Work queued since the last block isn't counted until the next one.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockPlugin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Duration of a 480-frame block at 48 kHz
+    const BLOCK: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn test_deferred_work_waits_for_headroom() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 480).unwrap();
+        let config = DeferConfig {
+            min_headroom: 0.5,
+            max_delay: BLOCK * 10,
+        };
+        let mut deferred = DeferredWork::with_config(48000.0, config);
+        let queue = deferred.queue();
+        let ran = Arc::new(AtomicUsize::new(0));
+        let job = |ran: &Arc<AtomicUsize>| {
+            let ran = Arc::clone(ran);
+            move |_: &mut MockPlugin| {
+                ran.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        };
+
+        // Busy blocks postpone the work...
+        queue.defer(job(&ran));
+        for _ in 0..10 {
+            deferred.block_done(&mut plugin, 480, BLOCK * 9 / 10);
+        }
+        assert_eq!(ran.load(Ordering::SeqCst), 0);
+        assert_eq!(deferred.pending(), 1);
+
+        // ...until it has waited too long
+        deferred.block_done(&mut plugin, 480, BLOCK * 9 / 10);
+        assert_eq!(ran.load(Ordering::SeqCst), 1);
+
+        // Once the load falls, work runs without waiting
+        queue.defer(job(&ran));
+        for _ in 0..5 {
+            deferred.block_done(&mut plugin, 480, Duration::ZERO);
+        }
+        assert_eq!(ran.load(Ordering::SeqCst), 1);
+        assert!(deferred.headroom() < 0.5);
+        deferred.block_done(&mut plugin, 480, Duration::ZERO);
+        assert_eq!(ran.load(Ordering::SeqCst), 2);
+        assert_eq!(deferred.pending(), 0);
+    }
+
+    #[test]
+    fn test_deferred_work_at_position() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 480).unwrap();
+        let mut deferred = DeferredWork::new(48000.0);
+        deferred
+            .queue()
+            .defer_at(960, |plugin: &mut MockPlugin| plugin.set_parameter(1, 0.25));
+
+        let input = vec![0.0f32; 480];
+        let mut left = vec![0.0f32; 480];
+        let mut right = vec![0.0f32; 480];
+        deferred
+            .process(
+                &mut plugin,
+                &[&input, &input],
+                &mut [&mut left, &mut right],
+                480,
+            )
+            .unwrap();
+        assert_eq!(plugin.get_parameter(1).unwrap(), 1.0);
+        deferred
+            .process(
+                &mut plugin,
+                &[&input, &input],
+                &mut [&mut left, &mut right],
+                480,
+            )
+            .unwrap();
+        assert_eq!(plugin.get_parameter(1).unwrap(), 0.25);
+    }
+}
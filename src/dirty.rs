@@ -0,0 +1,213 @@
+//! Unsaved-changes tracking
+//!
+//! Hosts mark a plugin whose state has changed since it was last saved, the
+//! classic asterisk next to its name. Plugins that report unsaved changes
+//! themselves (see [`PluginInstance::take_state_dirty()`]) are taken at their
+//! word; for the rest, and for changes they don't report, a [`DirtyTracker`]
+//! compares a hash of the plugin's current state with the one taken when it
+//! was saved. Serializing state isn't free, so the comparison is rate-limited
+//! by a configurable poll interval.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::dirty::DirtyTracker;
+//! # fn example(plugin: impl PluginInstance) -> Result<()> {
+//! let mut tracker = DirtyTracker::new();
+//! tracker.mark_saved(&plugin)?;
+//!
+//! // In the UI update loop
+//! let title = if tracker.is_state_dirty(&plugin)? {
+//!     format!("{} *", plugin.info().name)
+//! } else {
+//!     plugin.info().name.clone()
+//! };
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{PluginInstance, Result};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+/// Default time between state comparisons
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Tracks whether a plugin's state has changed since it was last saved
+#[derive(Debug, Clone)]
+pub struct DirtyTracker {
+    /// Minimum time between state comparisons; calls in between return the
+    /// previous answer
+    pub poll_interval: Duration,
+
+    /// Hash of the state when it was last saved
+    saved: Option<u64>,
+
+    /// Set by [`mark_dirty()`](Self::mark_dirty) until the next save
+    forced: bool,
+
+    /// Time and result of the last comparison
+    last_poll: Option<(Instant, bool)>,
+}
+
+impl DirtyTracker {
+    /// Create a tracker with the [`DEFAULT_POLL_INTERVAL`]
+    pub fn new() -> Self {
+        Self::with_poll_interval(DEFAULT_POLL_INTERVAL)
+    }
+
+    /// Create a tracker that compares state at most once per `poll_interval`
+    pub fn with_poll_interval(poll_interval: Duration) -> Self {
+        Self {
+            poll_interval,
+            saved: None,
+            forced: false,
+            last_poll: None,
+        }
+    }
+
+    /// Record the plugin's current state as saved
+    ///
+    /// Call after saving (and after loading, so a freshly loaded plugin starts
+    /// clean).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin's state can't be read
+    pub fn mark_saved<P: PluginInstance + ?Sized>(&mut self, plugin: &P) -> Result<()> {
+        // Changes reported before the save are part of the saved state
+        plugin.take_state_dirty();
+        self.saved = Some(state_hash(plugin)?);
+        self.forced = false;
+        self.last_poll = None;
+        Ok(())
+    }
+
+    /// Flag the state as changed without comparing it
+    ///
+    /// For hosts that learn about changes some other way, e.g. from their own
+    /// undo history. Plugins reporting that their state is dirty are picked up
+    /// by [`is_state_dirty()`](Self::is_state_dirty) without this. Cleared by
+    /// [`mark_saved()`](Self::mark_saved).
+    pub fn mark_dirty(&mut self) {
+        self.forced = true;
+    }
+
+    /// Check whether the state differs from the saved one
+    ///
+    /// A change the plugin reported itself marks the state dirty until the
+    /// next save, like [`mark_dirty()`](Self::mark_dirty). Otherwise, if
+    /// nothing has been saved yet, the current state becomes the baseline and
+    /// the plugin is reported clean.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin's state can't be read
+    pub fn is_state_dirty<P: PluginInstance + ?Sized>(&mut self, plugin: &P) -> Result<bool> {
+        if plugin.take_state_dirty() {
+            self.mark_dirty();
+        }
+        if self.forced {
+            return Ok(true);
+        }
+        if let Some((at, dirty)) = self.last_poll {
+            if at.elapsed() < self.poll_interval {
+                return Ok(dirty);
+            }
+        }
+
+        let current = state_hash(plugin)?;
+        let dirty = match self.saved {
+            Some(saved) => saved != current,
+            None => {
+                self.saved = Some(current);
+                false
+            }
+        };
+        self.last_poll = Some((Instant::now(), dirty));
+        Ok(dirty)
+    }
+}
+
+impl Default for DirtyTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn state_hash<P: PluginInstance + ?Sized>(plugin: &P) -> Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    plugin.get_state()?.hash(&mut hasher);
+    Ok(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockPlugin;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn test_dirty_after_change() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+
+        let mut tracker = DirtyTracker::with_poll_interval(Duration::ZERO);
+        tracker.mark_saved(&plugin).unwrap();
+        assert!(!tracker.is_state_dirty(&plugin).unwrap());
+
+        let original = plugin.get_parameter(0).unwrap();
+        plugin.set_parameter(0, 0.1).unwrap();
+        assert!(tracker.is_state_dirty(&plugin).unwrap());
+
+        // Undoing the edit makes it clean again
+        plugin.set_parameter(0, original).unwrap();
+        assert!(!tracker.is_state_dirty(&plugin).unwrap());
+
+        tracker.mark_dirty();
+        assert!(tracker.is_state_dirty(&plugin).unwrap());
+        tracker.mark_saved(&plugin).unwrap();
+        assert!(!tracker.is_state_dirty(&plugin).unwrap());
+    }
+
+    #[test]
+    fn test_plugin_reported_changes() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+
+        // The poll interval doesn't delay changes the plugin reports itself
+        let mut tracker = DirtyTracker::with_poll_interval(Duration::from_secs(3600));
+        tracker.mark_saved(&plugin).unwrap();
+        assert!(!tracker.is_state_dirty(&plugin).unwrap());
+
+        plugin.state_dirty.store(true, Ordering::Release);
+        assert!(tracker.is_state_dirty(&plugin).unwrap());
+        // Stays dirty after the plugin's flag was consumed
+        assert!(tracker.is_state_dirty(&plugin).unwrap());
+
+        tracker.mark_saved(&plugin).unwrap();
+        assert!(!tracker.is_state_dirty(&plugin).unwrap());
+
+        // Reported before saving, so part of the saved state
+        plugin.state_dirty.store(true, Ordering::Release);
+        tracker.mark_saved(&plugin).unwrap();
+        assert!(!tracker.is_state_dirty(&plugin).unwrap());
+    }
+
+    #[test]
+    fn test_poll_interval_caches_result() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+
+        let mut tracker = DirtyTracker::with_poll_interval(Duration::from_secs(3600));
+        assert!(!tracker.is_state_dirty(&plugin).unwrap());
+
+        plugin.set_parameter(0, 0.1).unwrap();
+        assert!(!tracker.is_state_dirty(&plugin).unwrap());
+
+        tracker.poll_interval = Duration::ZERO;
+        assert!(tracker.is_state_dirty(&plugin).unwrap());
+    }
+}
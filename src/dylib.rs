@@ -0,0 +1,109 @@
+//! Loading shared libraries at runtime
+//!
+//! Thin wrappers over `dlopen`/`LoadLibraryW` for the backends that load
+//! plugin binaries themselves (CLAP, LV2).
+
+#[cfg(unix)]
+mod sys {
+    use crate::paths;
+    use crate::{Error, Result};
+    use std::ffi::{c_char, c_int, c_void, CStr};
+    use std::path::Path;
+
+    #[cfg_attr(all(target_os = "linux", target_env = "gnu"), link(name = "dl"))]
+    extern "C" {
+        fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+        fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+        fn dlclose(handle: *mut c_void) -> c_int;
+        fn dlerror() -> *mut c_char;
+    }
+
+    const RTLD_NOW: c_int = 2;
+    #[cfg(target_vendor = "apple")]
+    const RTLD_LOCAL: c_int = 4;
+    #[cfg(not(target_vendor = "apple"))]
+    const RTLD_LOCAL: c_int = 0;
+
+    pub fn open(path: &Path) -> Result<*mut c_void> {
+        let name = paths::to_ffi_bytes(path)?;
+        // Safety: name is a valid C string
+        let handle = unsafe { dlopen(name.as_ptr(), RTLD_NOW | RTLD_LOCAL) };
+        if handle.is_null() {
+            // Safety: dlerror returns null or a C string valid until the next
+            // dl* call on this thread
+            let error = unsafe { dlerror() };
+            let message = if error.is_null() {
+                "unknown error".to_string()
+            } else {
+                unsafe { CStr::from_ptr(error) }
+                    .to_string_lossy()
+                    .into_owned()
+            };
+            return Err(Error::PluginNotFound(format!(
+                "Failed to load {}: {}",
+                path.display(),
+                message
+            )));
+        }
+        Ok(handle)
+    }
+
+    /// # Safety
+    ///
+    /// `handle` must be an open library and `name` null-terminated
+    pub unsafe fn symbol(handle: *mut c_void, name: &[u8]) -> *mut c_void {
+        dlsym(handle, name.as_ptr() as *const c_char)
+    }
+
+    /// # Safety
+    ///
+    /// `handle` must be an open library that is no longer used
+    pub unsafe fn close(handle: *mut c_void) {
+        dlclose(handle);
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use crate::paths;
+    use crate::{Error, Result};
+    use std::ffi::{c_char, c_void};
+    use std::path::Path;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn LoadLibraryW(name: *const u16) -> *mut c_void;
+        fn GetProcAddress(module: *mut c_void, name: *const c_char) -> *mut c_void;
+        fn FreeLibrary(module: *mut c_void) -> i32;
+    }
+
+    pub fn open(path: &Path) -> Result<*mut c_void> {
+        let name = paths::to_ffi_wide(path)?;
+        // Safety: name is a null-terminated UTF-16 string
+        let handle = unsafe { LoadLibraryW(name.as_ptr()) };
+        if handle.is_null() {
+            return Err(Error::PluginNotFound(format!(
+                "Failed to load {}: {}",
+                path.display(),
+                std::io::Error::last_os_error()
+            )));
+        }
+        Ok(handle)
+    }
+
+    /// # Safety
+    ///
+    /// `handle` must be an open library and `name` null-terminated
+    pub unsafe fn symbol(handle: *mut c_void, name: &[u8]) -> *mut c_void {
+        GetProcAddress(handle, name.as_ptr() as *const c_char)
+    }
+
+    /// # Safety
+    ///
+    /// `handle` must be an open library that is no longer used
+    pub unsafe fn close(handle: *mut c_void) {
+        FreeLibrary(handle);
+    }
+}
+
+pub(crate) use sys::{close, open, symbol};
@@ -28,6 +28,19 @@ pub enum Error {
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
 
+    /// `process()` was called with more frames than the plugin was initialized for
+    #[error("Block too large: {got} frames, max block size is {max}")]
+    BlockTooLarge {
+        /// Max block size passed to `initialize()`
+        max: usize,
+        /// Frames requested
+        got: usize,
+    },
+
+    /// A panic was caught on the audio thread
+    #[error("Panic on audio thread: {0}")]
+    Panic(String),
+
     /// Generic error
     #[error("{0}")]
     Other(String),
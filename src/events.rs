@@ -0,0 +1,221 @@
+//! Structured host events
+//!
+//! Rack reports notable things that happen inside it — a plugin was loaded,
+//! state was restored, a render block failed — as [`HostEvent`]s delivered to a
+//! process-wide [`HostEventSink`]. Applications install a sink to feed their own
+//! logging or analytics; with no sink installed, events are dropped.
+//!
+//! # Examples
+//!
+//! ```
+//! use rack::events::{self, HostEvent};
+//!
+//! events::set_sink(|event: &HostEvent<'_>| match event {
+//!     HostEvent::PluginLoaded { info } => println!("loaded {}", info.name),
+//!     HostEvent::Error { error, .. } => eprintln!("rack error: {}", error),
+//!     _ => {}
+//! });
+//! ```
+
+use crate::isolation::CrashReport;
+use crate::{Error, PluginInfo};
+use arc_swap::ArcSwapOption;
+use std::sync::Arc;
+
+/// Something that happened inside rack
+#[derive(Debug, Clone, Copy)]
+pub enum HostEvent<'a> {
+    /// A plugin instance was created
+    PluginLoaded {
+        /// The loaded plugin
+        info: &'a PluginInfo,
+    },
+
+    /// A plugin's state was restored with `set_state()`
+    StateRestored {
+        /// The plugin
+        info: &'a PluginInfo,
+        /// Size of the restored state in bytes
+        bytes: usize,
+    },
+
+    /// A plugin's GUI was created
+    ///
+    /// Only sent for AudioUnits (`AudioUnitPlugin::create_gui()`), the one
+    /// format rack can open plugin GUIs for so far. Hosts that embed VST3,
+    /// CLAP, LV2 or VST2 editors themselves won't see it.
+    GuiOpened {
+        /// The plugin
+        info: &'a PluginInfo,
+    },
+
+    /// A plugin reported a new processing latency
+    LatencyChanged {
+        /// The plugin
+        info: &'a PluginInfo,
+        /// New latency in samples
+        samples: usize,
+    },
+
+    /// A plugin reported a new tail time
+    TailChanged {
+        /// The plugin
+        info: &'a PluginInfo,
+        /// New tail time in seconds
+        seconds: f64,
+    },
+
+    /// A plugin's parameter list or parameter names changed at runtime
+    ///
+    /// Parameter counts and [`ParameterInfo`](crate::ParameterInfo) read
+    /// earlier may be stale.
+    ParametersChanged {
+        /// The plugin
+        info: &'a PluginInfo,
+    },
+
+    /// All of a plugin's parameters were reset to their defaults with
+    /// [`reset_all_parameters()`](crate::PluginInstance::reset_all_parameters)
+    ///
+    /// Sent once for the whole batch, so UIs can refresh every control at once.
+    ParametersReset {
+        /// The plugin
+        info: &'a PluginInfo,
+    },
+
+    /// A plugin's bus or channel configuration changed at runtime
+    IoChanged {
+        /// The plugin
+        info: &'a PluginInfo,
+    },
+
+    /// A helper process running isolated plugins died
+    PluginCrashed {
+        /// What is known about the crash
+        report: &'a CrashReport,
+    },
+
+    /// The audio callback missed its deadline or dropped a block
+    Xrun {
+        /// Number of frames affected (0 if unknown)
+        frames: usize,
+    },
+
+    /// An audio output device stopped being used, because it was removed or
+    /// another device replaced it
+    DeviceLost {
+        /// Device name
+        name: &'a str,
+    },
+
+    /// An audio output device was opened and started playing
+    DeviceChanged {
+        /// Device name
+        name: &'a str,
+        /// Sample rate in Hz
+        sample_rate: f64,
+        /// Number of output channels
+        channels: usize,
+    },
+
+    /// An error that rack recovered from or could not report to a caller
+    Error {
+        /// The plugin involved, if any
+        info: Option<&'a PluginInfo>,
+        /// The error
+        error: &'a Error,
+    },
+}
+
+/// Receiver for [`HostEvent`]s
+///
+/// Events can be emitted from any thread, including the audio thread (e.g.
+/// [`HostEvent::Xrun`]), so implementations should return quickly and avoid
+/// blocking; hand events off to a queue if they need slow processing.
+///
+/// Implemented for closures taking `&HostEvent`.
+pub trait HostEventSink: Send + Sync {
+    /// Handle an event
+    fn on_event(&self, event: &HostEvent<'_>);
+}
+
+impl<F> HostEventSink for F
+where
+    F: Fn(&HostEvent<'_>) + Send + Sync,
+{
+    fn on_event(&self, event: &HostEvent<'_>) {
+        self(event)
+    }
+}
+
+/// The installed sink
+///
+/// Lock-free so that [`emit()`] can be called on the audio thread: loading it
+/// neither blocks nor touches the reference count.
+static SINK: ArcSwapOption<Box<dyn HostEventSink>> = ArcSwapOption::const_empty();
+
+/// Install the process-wide event sink, replacing any previous one
+pub fn set_sink(sink: impl HostEventSink + 'static) {
+    SINK.store(Some(Arc::new(Box::new(sink))));
+}
+
+/// Remove the installed event sink
+pub fn clear_sink() {
+    SINK.store(None);
+}
+
+/// Deliver an event to the installed sink
+///
+/// Public so that engines built on rack can report their own events (such as
+/// xruns) through the same sink. Doesn't lock or allocate, so it is safe to
+/// call on the audio thread as long as the sink is.
+pub fn emit(event: HostEvent<'_>) {
+    if let Some(sink) = SINK.load().as_ref() {
+        sink.on_event(&event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::SINK_LOCK;
+    use crate::PluginType;
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_emit_reaches_sink() {
+        let info = PluginInfo::new(
+            "Events Test".to_string(),
+            "Test".to_string(),
+            1,
+            PluginType::Effect,
+            PathBuf::new(),
+            "events-test".to_string(),
+        );
+
+        let _lock = SINK_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink_seen = Arc::clone(&seen);
+        // The sink is global; only record events for our plugin
+        set_sink(move |event: &HostEvent<'_>| {
+            if let HostEvent::StateRestored { info, bytes } = event {
+                if info.unique_id == "events-test" {
+                    sink_seen.lock().unwrap().push(*bytes);
+                }
+            }
+        });
+
+        emit(HostEvent::StateRestored {
+            info: &info,
+            bytes: 42,
+        });
+        clear_sink();
+        emit(HostEvent::StateRestored {
+            info: &info,
+            bytes: 7,
+        });
+
+        assert_eq!(*seen.lock().unwrap(), vec![42]);
+    }
+}
@@ -0,0 +1,1178 @@
+//! Flight recorder for plugin bug reports
+//!
+//! Plugin misbehavior is often impossible to reproduce from a description:
+//! the glitch depends on the exact notes, automation and block sizes that led
+//! up to it. [`FlightRecorder`] wraps a plugin and keeps the last few seconds
+//! of what it was sent (MIDI, parameter changes, preset and state loads) and
+//! of every `process()` call (block size, duration, error) in a ring buffer.
+//! When something goes wrong, the host saves a [`FlightRecording`] and
+//! attaches it to the bug report.
+//!
+//! A recording also holds what it takes to start from the same place: the
+//! plugin's state when the recording window begins (the state captured at
+//! `initialize()`, with everything that scrolled out of the window since
+//! applied on top). Its [`Display`](std::fmt::Display) form is a readable
+//! listing; the saved form is binary.
+//!
+//! [`FlightRecording::replay()`] feeds a recording into a fresh instance of
+//! the plugin offline: the same setup, events, automation and block sizes, at
+//! the same sample positions. Audio isn't recorded, so the input is silence or
+//! a test [`Signal`]. This reproduces most glitches that depend on what the
+//! plugin was sent rather than on the user's audio hardware.
+//!
+//! Recording is opt-in and costs a little on every call: a clock read per
+//! block and an entry per event. `initialize()` allocates room for
+//! [`MAX_ENTRIES`] entries, and errors are kept as fixed-size
+//! [`FlightError`]s, so recording doesn't allocate on the audio thread.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::flight::FlightRecorder;
+//! # use std::time::Duration;
+//! # fn example(synth: impl PluginInstance) -> Result<()> {
+//! let mut synth = FlightRecorder::new(synth, Duration::from_secs(30))
+//!     .with_dump_on_error("synth-crash.rackflight");
+//! synth.initialize(48000.0, 512)?;
+//!
+//! // Off the audio thread, after a block failed
+//! synth.save_pending_dump()?;
+//!
+//! // ... later, when the user reports a glitch:
+//! synth.recording().save("glitch.rackflight")?;
+//! println!("{}", synth.recording());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Replaying a recording attached to a bug report:
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::flight::FlightRecording;
+//! # fn example(mut synth: impl PluginInstance) -> Result<()> {
+//! let recording = FlightRecording::load("glitch.rackflight")?;
+//! let report = recording.replay(&mut synth, None)?;
+//! if report.reproduced() {
+//!     println!("Reproduced: {:?}", report.failures().next());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::generator::{Generator, Signal};
+use crate::isolation::protocol;
+use crate::meter::MeterReading;
+use crate::node::BlockContext;
+use crate::quirks::Quirks;
+use crate::session::{write_bytes, write_len, Reader};
+use crate::{
+    CurrentPreset, Error, MidiEvent, ParameterChange, ParameterInfo, PluginInfo, PluginInstance,
+    PresetInfo, Result,
+};
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Most entries a recorder keeps, however short the window
+pub const MAX_ENTRIES: usize = 1 << 16;
+
+const MAGIC: &[u8; 8] = b"RACKFLIT";
+const VERSION: u32 = 1;
+
+/// Something that happened to the recorded plugin
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlightEvent {
+    /// The plugin was initialized
+    Initialize {
+        /// Sample rate in Hz
+        sample_rate: f64,
+        /// Largest block size
+        max_block_size: usize,
+    },
+
+    /// The plugin was reset
+    Reset,
+
+    /// The host moved the plugin's timeline
+    SetSamplePosition(u64),
+
+    /// A MIDI event was sent, to be played in the next block
+    Midi(MidiEvent),
+
+    /// A parameter was set (normalized value)
+    Parameter {
+        /// Parameter index
+        index: usize,
+        /// Normalized value
+        value: f32,
+    },
+
+    /// A preset was loaded
+    Preset(i32),
+
+    /// A state was restored
+    State(Vec<u8>),
+
+    /// A block was processed
+    Process {
+        /// Frames in the block
+        frames: usize,
+        /// How long `process()` took
+        duration: Duration,
+        /// The error `process()` returned, if it failed
+        error: Option<FlightError>,
+    },
+}
+
+/// The message of an error a recorded block failed with
+///
+/// Kept inline, truncated to [`CAPACITY`](Self::CAPACITY) bytes, so that
+/// recording a failure doesn't allocate on the audio thread.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct FlightError {
+    len: u8,
+    bytes: [u8; Self::CAPACITY],
+}
+
+impl FlightError {
+    /// Longest message kept, in bytes
+    pub const CAPACITY: usize = 120;
+
+    /// Keep `message`, cut at a character boundary if it is too long
+    pub fn new(message: &str) -> Self {
+        let mut error = Self {
+            len: 0,
+            bytes: [0; Self::CAPACITY],
+        };
+        let _ = fmt::Write::write_str(&mut error, message);
+        error
+    }
+
+    /// The message
+    pub fn as_str(&self) -> &str {
+        // Only whole characters are ever copied in
+        std::str::from_utf8(&self.bytes[..self.len as usize]).unwrap_or_default()
+    }
+}
+
+impl From<&Error> for FlightError {
+    fn from(error: &Error) -> Self {
+        let mut message = Self::new("");
+        let _ = fmt::Write::write_fmt(&mut message, format_args!("{}", error));
+        message
+    }
+}
+
+impl fmt::Write for FlightError {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let len = self.len as usize;
+        let mut end = s.len().min(Self::CAPACITY - len);
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.bytes[len..len + end].copy_from_slice(&s.as_bytes()[..end]);
+        self.len += end as u8;
+        Ok(())
+    }
+}
+
+impl fmt::Display for FlightError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for FlightError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+/// One recorded event
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlightEntry {
+    /// The plugin's sample position at the time
+    pub position: u64,
+
+    /// Time since the recorder was created
+    pub elapsed: Duration,
+
+    /// What happened
+    pub event: FlightEvent,
+}
+
+/// What a [`FlightRecorder`] captured
+#[derive(Debug, Clone)]
+pub struct FlightRecording {
+    /// The recorded plugin
+    pub plugin: PluginInfo,
+
+    /// Events that bring a freshly initialized plugin to where the recording
+    /// starts: the last state (if any), then presets and parameter changes
+    /// made since
+    pub setup: Vec<FlightEvent>,
+
+    /// Everything within the window, oldest first
+    pub entries: Vec<FlightEntry>,
+}
+
+impl FlightRecording {
+    /// The first `initialize()` in the recording or its setup, if any
+    pub fn initialization(&self) -> Option<(f64, usize)> {
+        self.setup
+            .iter()
+            .chain(self.entries.iter().map(|entry| &entry.event))
+            .find_map(|event| match *event {
+                FlightEvent::Initialize {
+                    sample_rate,
+                    max_block_size,
+                } => Some((sample_rate, max_block_size)),
+                _ => None,
+            })
+    }
+
+    /// The entries of blocks that failed
+    pub fn failures(&self) -> impl Iterator<Item = &FlightEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| matches!(entry.event, FlightEvent::Process { error: Some(_), .. }))
+    }
+
+    /// Serialize for saving or attaching to a bug report
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a recorded state is 4 GiB or larger
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        protocol::write_plugin_info(&mut out, &self.plugin);
+        write_len(&mut out, self.setup.len())?;
+        for event in &self.setup {
+            write_event(&mut out, event)?;
+        }
+        write_len(&mut out, self.entries.len())?;
+        for entry in &self.entries {
+            protocol::write_u64(&mut out, entry.position);
+            protocol::write_u64(&mut out, entry.elapsed.as_nanos() as u64);
+            write_event(&mut out, &entry.event)?;
+        }
+        Ok(out)
+    }
+
+    /// Parse a recording produced by [`to_bytes()`](Self::to_bytes)
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidFormat`] if the data isn't a flight recording,
+    /// is truncated, or comes from a newer format version
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(data);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(Error::InvalidFormat("Not a flight recording".to_string()));
+        }
+        let version = reader.u32()?;
+        if version > VERSION {
+            return Err(Error::InvalidFormat(format!(
+                "Flight recording version {} is newer than supported version {}",
+                version, VERSION
+            )));
+        }
+        let plugin = protocol::read_plugin_info(&mut reader)?;
+        let mut setup = Vec::new();
+        for _ in 0..reader.u32()? {
+            setup.push(read_event(&mut reader)?);
+        }
+        let mut entries = Vec::new();
+        for _ in 0..reader.u32()? {
+            let position = reader.u64()?;
+            let elapsed = Duration::from_nanos(reader.u64()?);
+            let event = read_event(&mut reader)?;
+            entries.push(FlightEntry {
+                position,
+                elapsed,
+                event,
+            });
+        }
+        Ok(Self {
+            plugin,
+            setup,
+            entries,
+        })
+    }
+
+    /// Write the recording to a file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+
+    /// Read a recording from a file
+    ///
+    /// # Errors
+    ///
+    /// As [`from_bytes()`](Self::from_bytes), plus I/O errors
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Replay the recording into a fresh (uninitialized) plugin instance
+    ///
+    /// The plugin is initialized as recorded, brought to the recording's
+    /// starting point with the setup events, moved to the first entry's sample
+    /// position, then sent every recorded event in order. Each recorded block
+    /// is processed with the same number of frames, with `input` (or silence)
+    /// on every input channel.
+    ///
+    /// Only `process()` outcomes are compared with the recording; errors from
+    /// the other calls are ignored, as the recorded host carried on after
+    /// them too.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the recording holds no `initialize()`, or if the
+    /// plugin can't be initialized or brought to the starting point
+    pub fn replay<P: PluginInstance + ?Sized>(
+        &self,
+        plugin: &mut P,
+        input: Option<Signal>,
+    ) -> Result<ReplayReport> {
+        let (sample_rate, max_block_size) = self.initialization().ok_or_else(|| {
+            Error::InvalidFormat("Flight recording has no initialization".to_string())
+        })?;
+        plugin.initialize(sample_rate, max_block_size)?;
+        for event in &self.setup {
+            match event {
+                FlightEvent::State(state) => plugin.set_state(state)?,
+                FlightEvent::Preset(number) => plugin.load_preset(*number)?,
+                FlightEvent::Parameter { index, value } => plugin.set_parameter(*index, *value)?,
+                _ => {}
+            }
+        }
+        if let Some(first) = self.entries.first() {
+            if first.position != plugin.sample_position() {
+                let _ = plugin.set_sample_position(first.position);
+            }
+        }
+
+        let mut generator = input.map(|signal| Generator::new(signal, sample_rate));
+        let mut inputs = vec![vec![0.0f32; max_block_size]; plugin.input_channels()];
+        let mut outputs = vec![vec![0.0f32; max_block_size]; plugin.output_channels()];
+        let mut report = ReplayReport {
+            blocks: Vec::new(),
+            output: vec![Vec::new(); outputs.len()],
+        };
+        let mut midi = Vec::new();
+
+        for entry in &self.entries {
+            match &entry.event {
+                FlightEvent::Initialize {
+                    sample_rate,
+                    max_block_size,
+                } => {
+                    let _ = plugin.initialize(*sample_rate, *max_block_size);
+                }
+                FlightEvent::Reset => {
+                    let _ = plugin.reset();
+                }
+                FlightEvent::SetSamplePosition(position) => {
+                    let _ = plugin.set_sample_position(*position);
+                }
+                FlightEvent::Midi(event) => midi.push(*event),
+                FlightEvent::Parameter { index, value } => {
+                    let _ = plugin.set_parameter(*index, *value);
+                }
+                FlightEvent::Preset(number) => {
+                    let _ = plugin.load_preset(*number);
+                }
+                FlightEvent::State(state) => {
+                    let _ = plugin.set_state(state);
+                }
+                FlightEvent::Process { frames, error, .. } => {
+                    let frames = *frames;
+                    if frames > inputs.first().map_or(max_block_size, Vec::len) {
+                        inputs.iter_mut().for_each(|c| c.resize(frames, 0.0));
+                        outputs.iter_mut().for_each(|c| c.resize(frames, 0.0));
+                    }
+                    // Events are sent just before the block they were sent for
+                    if !midi.is_empty() {
+                        let _ = plugin.send_midi(&midi);
+                        midi.clear();
+                    }
+                    if let Some(generator) = generator.as_mut() {
+                        let mut channels: Vec<&mut [f32]> =
+                            inputs.iter_mut().map(|c| c.as_mut_slice()).collect();
+                        generator.fill(&mut channels, frames);
+                    }
+
+                    let position = plugin.sample_position();
+                    let input_refs: Vec<&[f32]> = inputs.iter().map(|c| c.as_slice()).collect();
+                    let mut output_refs: Vec<&mut [f32]> =
+                        outputs.iter_mut().map(|c| c.as_mut_slice()).collect();
+                    let start = Instant::now();
+                    let result = plugin.process(&input_refs, &mut output_refs, frames);
+                    let duration = start.elapsed();
+
+                    for (rendered, output) in report.output.iter_mut().zip(&outputs) {
+                        match result {
+                            Ok(()) => rendered.extend_from_slice(&output[..frames]),
+                            Err(_) => rendered.resize(rendered.len() + frames, 0.0),
+                        }
+                    }
+                    report.blocks.push(ReplayedBlock {
+                        position,
+                        frames,
+                        duration,
+                        recorded_error: error.map(|e| e.to_string()),
+                        error: result.err().map(|e| e.to_string()),
+                    });
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// One block processed by [`FlightRecording::replay()`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayedBlock {
+    /// The plugin's sample position at the start of the block
+    pub position: u64,
+
+    /// Frames in the block
+    pub frames: usize,
+
+    /// How long `process()` took in the replay
+    pub duration: Duration,
+
+    /// The error the block failed with in the recording, if it did
+    pub recorded_error: Option<String>,
+
+    /// The error the block failed with in the replay, if it did
+    pub error: Option<String>,
+}
+
+/// Outcome of [`FlightRecording::replay()`]
+#[derive(Debug, Clone)]
+pub struct ReplayReport {
+    /// Every replayed block, in order
+    pub blocks: Vec<ReplayedBlock>,
+
+    /// Rendered audio per output channel (silence for failed blocks)
+    pub output: Vec<Vec<f32>>,
+}
+
+impl ReplayReport {
+    /// The blocks that failed in the replay
+    pub fn failures(&self) -> impl Iterator<Item = &ReplayedBlock> {
+        self.blocks.iter().filter(|block| block.error.is_some())
+    }
+
+    /// Whether the recording had failures and every one of them failed again
+    pub fn reproduced(&self) -> bool {
+        let mut recorded = self
+            .blocks
+            .iter()
+            .filter(|block| block.recorded_error.is_some())
+            .peekable();
+        recorded.peek().is_some() && recorded.all(|block| block.error.is_some())
+    }
+
+    /// The slowest block of the replay
+    pub fn slowest_block(&self) -> Option<&ReplayedBlock> {
+        self.blocks.iter().max_by_key(|block| block.duration)
+    }
+}
+
+impl fmt::Display for FlightEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlightEvent::Initialize {
+                sample_rate,
+                max_block_size,
+            } => write!(
+                f,
+                "initialize {} Hz, {} frames",
+                sample_rate, max_block_size
+            ),
+            FlightEvent::Reset => write!(f, "reset"),
+            FlightEvent::SetSamplePosition(position) => {
+                write!(f, "set sample position {}", position)
+            }
+            FlightEvent::Midi(event) => {
+                write!(f, "midi +{} {:?}", event.sample_offset, event.kind)
+            }
+            FlightEvent::Parameter { index, value } => {
+                write!(f, "parameter {} = {}", index, value)
+            }
+            FlightEvent::Preset(number) => write!(f, "load preset {}", number),
+            FlightEvent::State(state) => write!(f, "set state ({} bytes)", state.len()),
+            FlightEvent::Process {
+                frames,
+                duration,
+                error,
+            } => {
+                write!(f, "process {} frames in {:?}", frames, duration)?;
+                match error {
+                    Some(error) => write!(f, ": FAILED: {}", error),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for FlightRecording {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Flight recording of {} ({})",
+            self.plugin.name, self.plugin.unique_id
+        )?;
+        for event in &self.setup {
+            writeln!(f, "  setup: {}", event)?;
+        }
+        for entry in &self.entries {
+            writeln!(
+                f,
+                "  {:>12} {:>10.3}s  {}",
+                entry.position,
+                entry.elapsed.as_secs_f64(),
+                entry.event
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn write_event(out: &mut Vec<u8>, event: &FlightEvent) -> Result<()> {
+    match event {
+        FlightEvent::Initialize {
+            sample_rate,
+            max_block_size,
+        } => {
+            out.push(0);
+            protocol::write_f64(out, *sample_rate);
+            protocol::write_u32(out, *max_block_size as u32);
+        }
+        FlightEvent::Reset => out.push(1),
+        FlightEvent::SetSamplePosition(position) => {
+            out.push(2);
+            protocol::write_u64(out, *position);
+        }
+        FlightEvent::Midi(event) => {
+            out.push(3);
+            protocol::write_midi(out, std::slice::from_ref(event));
+        }
+        FlightEvent::Parameter { index, value } => {
+            out.push(4);
+            protocol::write_u32(out, *index as u32);
+            protocol::write_f32(out, *value);
+        }
+        FlightEvent::Preset(number) => {
+            out.push(5);
+            protocol::write_u32(out, *number as u32);
+        }
+        FlightEvent::State(state) => {
+            out.push(6);
+            write_bytes(out, state)?;
+        }
+        FlightEvent::Process {
+            frames,
+            duration,
+            error,
+        } => {
+            out.push(7);
+            protocol::write_u32(out, *frames as u32);
+            protocol::write_u64(out, duration.as_nanos() as u64);
+            match error {
+                Some(error) => {
+                    out.push(1);
+                    protocol::write_str(out, error.as_str());
+                }
+                None => out.push(0),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_event(reader: &mut Reader<'_>) -> Result<FlightEvent> {
+    Ok(match reader.u8()? {
+        0 => FlightEvent::Initialize {
+            sample_rate: reader.f64()?,
+            max_block_size: reader.u32()? as usize,
+        },
+        1 => FlightEvent::Reset,
+        2 => FlightEvent::SetSamplePosition(reader.u64()?),
+        3 => {
+            let mut events = Vec::with_capacity(1);
+            protocol::read_midi(reader, &mut events)?;
+            match events.as_slice() {
+                [event] => FlightEvent::Midi(*event),
+                _ => {
+                    return Err(Error::InvalidFormat(
+                        "Flight recording MIDI entry must hold one event".to_string(),
+                    ))
+                }
+            }
+        }
+        4 => FlightEvent::Parameter {
+            index: reader.u32()? as usize,
+            value: reader.f32()?,
+        },
+        5 => FlightEvent::Preset(reader.u32()? as i32),
+        6 => FlightEvent::State(reader.bytes()?.to_vec()),
+        7 => FlightEvent::Process {
+            frames: reader.u32()? as usize,
+            duration: Duration::from_nanos(reader.u64()?),
+            error: match reader.u8()? {
+                0 => None,
+                _ => Some(FlightError::new(&reader.string()?)),
+            },
+        },
+        kind => {
+            return Err(Error::InvalidFormat(format!(
+                "Unknown flight recording event {}",
+                kind
+            )))
+        }
+    })
+}
+
+/// Plugin wrapper that records what the plugin is sent
+///
+/// All methods are forwarded to the wrapped plugin and recorded on the way.
+/// See the [module documentation](self).
+pub struct FlightRecorder<P> {
+    plugin: P,
+    window: Duration,
+    /// Window length in frames (0 until initialized)
+    window_frames: u64,
+    started: Instant,
+    entries: VecDeque<FlightEntry>,
+    setup: Vec<FlightEvent>,
+    dump_path: Option<PathBuf>,
+    /// A call that may run on the audio thread failed since the last dump
+    dump_pending: bool,
+}
+
+impl<P: PluginInstance> FlightRecorder<P> {
+    /// Wrap a plugin, keeping the last `window` of audio time
+    pub fn new(plugin: P, window: Duration) -> Self {
+        Self {
+            plugin,
+            window,
+            window_frames: 0,
+            started: Instant::now(),
+            entries: VecDeque::new(),
+            setup: Vec::new(),
+            dump_path: None,
+            dump_pending: false,
+        }
+    }
+
+    /// Save the recording to `path` whenever a call fails
+    ///
+    /// Failures of the calls made from the control thread (`initialize()`,
+    /// `reset()`, presets and states) are saved right away. The others may run
+    /// on the audio thread, which mustn't write files, so they only leave a
+    /// pending dump for [`save_pending_dump()`](Self::save_pending_dump).
+    /// Each dump overwrites the previous one.
+    pub fn with_dump_on_error(mut self, path: impl Into<PathBuf>) -> Self {
+        self.dump_path = Some(path.into());
+        self
+    }
+
+    /// Whether a failure is waiting for [`save_pending_dump()`](Self::save_pending_dump)
+    pub fn has_pending_dump(&self) -> bool {
+        self.dump_pending
+    }
+
+    /// Save the recording if a failure left a dump pending
+    ///
+    /// Call it off the audio thread, e.g. as a
+    /// [`DeferredWork`](crate::deferred::DeferredWork) job queued after a
+    /// failed block (`queue.defer(FlightRecorder::save_pending_dump)`). The
+    /// recording includes whatever was recorded since the failure.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written; the dump stays pending
+    pub fn save_pending_dump(&mut self) -> Result<()> {
+        if let (true, Some(path)) = (self.dump_pending, &self.dump_path) {
+            self.recording().save(path)?;
+        }
+        self.dump_pending = false;
+        Ok(())
+    }
+
+    /// Snapshot of what has been recorded
+    pub fn recording(&self) -> FlightRecording {
+        FlightRecording {
+            plugin: self.plugin.info().clone(),
+            setup: self.setup.clone(),
+            entries: self.entries.iter().cloned().collect(),
+        }
+    }
+
+    /// Forget everything recorded so far
+    ///
+    /// The plugin's current state becomes the start of the recording.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.setup.clear();
+        self.capture_state();
+    }
+
+    /// The wrapped plugin
+    pub fn inner(&self) -> &P {
+        &self.plugin
+    }
+
+    /// The wrapped plugin, mutably
+    ///
+    /// Calls made directly on the inner plugin aren't recorded.
+    pub fn inner_mut(&mut self) -> &mut P {
+        &mut self.plugin
+    }
+
+    /// Unwrap the plugin
+    pub fn into_inner(self) -> P {
+        self.plugin
+    }
+
+    fn record(&mut self, event: FlightEvent) {
+        self.record_at(self.plugin.sample_position(), event);
+    }
+
+    fn record_at(&mut self, position: u64, event: FlightEvent) {
+        while let Some(oldest) = self.entries.front() {
+            let expired = self.window_frames > 0
+                && oldest.position.saturating_add(self.window_frames) < position;
+            if !expired && self.entries.len() < MAX_ENTRIES {
+                break;
+            }
+            if let Some(oldest) = self.entries.pop_front() {
+                self.retire(oldest.event);
+            }
+        }
+        self.entries.push_back(FlightEntry {
+            position,
+            elapsed: self.started.elapsed(),
+            event,
+        });
+    }
+
+    /// Fold an event leaving the window into the setup
+    fn retire(&mut self, event: FlightEvent) {
+        match event {
+            FlightEvent::Initialize { .. } => {
+                self.setup
+                    .retain(|e| !matches!(e, FlightEvent::Initialize { .. }));
+                self.setup.insert(0, event);
+            }
+            FlightEvent::State(_) => {
+                self.setup
+                    .retain(|e| matches!(e, FlightEvent::Initialize { .. }));
+                self.setup.push(event);
+            }
+            FlightEvent::Preset(_) => {
+                self.setup.retain(|e| {
+                    !matches!(e, FlightEvent::Preset(_) | FlightEvent::Parameter { .. })
+                });
+                self.setup.push(event);
+            }
+            FlightEvent::Parameter { index, .. } => {
+                self.setup.retain(
+                    |e| !matches!(*e, FlightEvent::Parameter { index: i, .. } if i == index),
+                );
+                self.setup.push(event);
+            }
+            _ => {}
+        }
+    }
+
+    /// Start the setup from the plugin's current state, where it has one
+    fn capture_state(&mut self) {
+        if let Ok(state) = self.plugin.get_state() {
+            self.retire(FlightEvent::State(state));
+        }
+    }
+
+    /// Record the outcome of a call that may run on the audio thread, leaving
+    /// a dump pending if it failed
+    fn checked(&mut self, result: Result<()>) -> Result<()> {
+        if result.is_err() && self.dump_path.is_some() {
+            self.dump_pending = true;
+        }
+        result
+    }
+
+    /// Record the outcome of a control thread call, dumping the recording if
+    /// it failed
+    fn checked_now(&mut self, result: Result<()>) -> Result<()> {
+        if result.is_err() {
+            self.dump();
+        }
+        result
+    }
+
+    /// Run and record a block of `num_frames` processed by `process`
+    fn recorded_process(
+        &mut self,
+        num_frames: usize,
+        process: impl FnOnce(&mut P) -> Result<()>,
+    ) -> Result<()> {
+        let position = self.plugin.sample_position();
+        let start = Instant::now();
+        let result = process(&mut self.plugin);
+        let duration = start.elapsed();
+
+        // Recorded at the block's start, after the events sent for it
+        self.record_at(
+            position,
+            FlightEvent::Process {
+                frames: num_frames,
+                duration,
+                error: result.as_ref().err().map(FlightError::from),
+            },
+        );
+        self.checked(result)
+    }
+
+    fn dump(&mut self) {
+        if let Some(path) = &self.dump_path {
+            let _ = self.recording().save(path);
+            self.dump_pending = false;
+        }
+    }
+}
+
+impl<P: PluginInstance> PluginInstance for FlightRecorder<P> {
+    fn initialize(&mut self, sample_rate: f64, max_block_size: usize) -> Result<()> {
+        let result = self.plugin.initialize(sample_rate, max_block_size);
+        if result.is_ok() {
+            self.window_frames = (self.window.as_secs_f64() * sample_rate).ceil() as u64;
+            self.entries.clear();
+            self.entries.reserve(MAX_ENTRIES);
+            self.setup.clear();
+            // Initialize, state, preset and one change per parameter
+            self.setup.reserve(self.plugin.parameter_count() + 3);
+            self.retire(FlightEvent::Initialize {
+                sample_rate,
+                max_block_size,
+            });
+            self.capture_state();
+        }
+        self.checked_now(result)
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.record(FlightEvent::Reset);
+        let result = self.plugin.reset();
+        self.checked_now(result)
+    }
+
+    fn process(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        self.recorded_process(num_frames, |plugin| {
+            plugin.process(inputs, outputs, num_frames)
+        })
+    }
+
+    fn process_with_context(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        context: &BlockContext,
+    ) -> Result<()> {
+        self.recorded_process(context.num_frames, |plugin| {
+            plugin.process_with_context(inputs, outputs, context)
+        })
+    }
+
+    /// Record the changes and MIDI, then process the block
+    ///
+    /// Changes are recorded as parameter events before the block, so a replay
+    /// applies them at its start.
+    fn process_with_events(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        changes: &[ParameterChange],
+        midi: &[MidiEvent],
+    ) -> Result<()> {
+        for change in changes {
+            self.record(FlightEvent::Parameter {
+                index: change.index,
+                value: change.value,
+            });
+        }
+        for event in midi {
+            self.record(FlightEvent::Midi(*event));
+        }
+        self.recorded_process(num_frames, |plugin| {
+            plugin.process_with_events(inputs, outputs, num_frames, changes, midi)
+        })
+    }
+
+    fn sample_position(&self) -> u64 {
+        self.plugin.sample_position()
+    }
+
+    fn set_sample_position(&mut self, position: u64) -> Result<()> {
+        self.record(FlightEvent::SetSamplePosition(position));
+        let result = self.plugin.set_sample_position(position);
+        self.checked(result)
+    }
+
+    fn flush_events(&mut self) -> Result<()> {
+        self.plugin.flush_events()
+    }
+
+    fn parameter_count(&self) -> usize {
+        self.plugin.parameter_count()
+    }
+
+    fn parameter_info(&self, index: usize) -> Result<ParameterInfo> {
+        self.plugin.parameter_info(index)
+    }
+
+    fn get_parameter(&self, index: usize) -> Result<f32> {
+        self.plugin.get_parameter(index)
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) -> Result<()> {
+        self.record(FlightEvent::Parameter { index, value });
+        let result = self.plugin.set_parameter(index, value);
+        self.checked(result)
+    }
+
+    fn send_midi(&mut self, events: &[MidiEvent]) -> Result<()> {
+        for event in events {
+            self.record(FlightEvent::Midi(*event));
+        }
+        let result = self.plugin.send_midi(events);
+        self.checked(result)
+    }
+
+    fn preset_count(&self) -> Result<usize> {
+        self.plugin.preset_count()
+    }
+
+    fn preset_info(&self, index: usize) -> Result<PresetInfo> {
+        self.plugin.preset_info(index)
+    }
+
+    fn load_preset(&mut self, preset_number: i32) -> Result<()> {
+        self.record(FlightEvent::Preset(preset_number));
+        let result = self.plugin.load_preset(preset_number);
+        self.checked_now(result)
+    }
+
+    fn current_preset(&self) -> Result<Option<CurrentPreset>> {
+        self.plugin.current_preset()
+    }
+
+    fn get_state(&self) -> Result<Vec<u8>> {
+        self.plugin.get_state()
+    }
+
+    fn set_state(&mut self, data: &[u8]) -> Result<()> {
+        self.record(FlightEvent::State(data.to_vec()));
+        let result = self.plugin.set_state(data);
+        self.checked_now(result)
+    }
+
+    fn get_state_to_writer(&self, writer: &mut dyn std::io::Write) -> Result<()> {
+        self.plugin.get_state_to_writer(writer)
+    }
+
+    fn set_state_from_reader(&mut self, reader: &mut dyn std::io::Read) -> Result<()> {
+        let result = self.plugin.set_state_from_reader(reader);
+        // The stream is gone; record the state it produced instead
+        if let (Ok(()), Ok(state)) = (&result, self.plugin.get_state()) {
+            self.record(FlightEvent::State(state));
+        }
+        self.checked_now(result)
+    }
+
+    fn set_state_from_file(&mut self, path: &Path) -> Result<()> {
+        let result = self.plugin.set_state_from_file(path);
+        if let (Ok(()), Ok(state)) = (&result, self.plugin.get_state()) {
+            self.record(FlightEvent::State(state));
+        }
+        self.checked_now(result)
+    }
+
+    fn take_state_dirty(&self) -> bool {
+        self.plugin.take_state_dirty()
+    }
+
+    fn info(&self) -> &PluginInfo {
+        self.plugin.info()
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.plugin.is_initialized()
+    }
+
+    fn input_channels(&self) -> usize {
+        self.plugin.input_channels()
+    }
+
+    fn output_channels(&self) -> usize {
+        self.plugin.output_channels()
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.plugin.latency_samples()
+    }
+
+    fn tail_samples(&self) -> usize {
+        self.plugin.tail_samples()
+    }
+
+    fn tail_seconds(&self) -> f64 {
+        self.plugin.tail_seconds()
+    }
+
+    fn set_bypass(&mut self, bypassed: bool) -> Result<()> {
+        self.plugin.set_bypass(bypassed)
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.plugin.is_bypassed()
+    }
+
+    fn quirks(&self) -> Quirks {
+        self.plugin.quirks()
+    }
+
+    fn plugin_meter(&self) -> Option<Vec<MeterReading>> {
+        self.plugin.plugin_meter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockPlugin;
+
+    fn block(plugin: &mut impl PluginInstance) -> Result<()> {
+        let input = [0.5f32; 100];
+        let mut left = [0.0f32; 100];
+        let mut right = [0.0f32; 100];
+        plugin.process(&[&input, &input], &mut [&mut left, &mut right], 100)
+    }
+
+    #[test]
+    fn test_recorder_keeps_window_and_dumps_failures() {
+        let path = std::env::temp_dir().join(format!("rack-flight-{}", std::process::id()));
+        let mut mock = MockPlugin::new();
+        mock.fail_blocks = vec![4];
+        // 250 frames at 1 kHz
+        let mut plugin =
+            FlightRecorder::new(mock, Duration::from_millis(250)).with_dump_on_error(&path);
+        plugin.initialize(1000.0, 100).unwrap();
+
+        plugin.set_parameter(0, 0.25).unwrap();
+        block(&mut plugin).unwrap();
+        plugin
+            .send_midi(&[MidiEvent::note_on(60, 100, 0, 10)])
+            .unwrap();
+        for _ in 0..3 {
+            block(&mut plugin).unwrap();
+        }
+        plugin.set_parameter(1, 0.5).unwrap();
+        assert!(block(&mut plugin).is_err());
+
+        // The audio thread only marks the dump; it is written later
+        assert!(plugin.has_pending_dump());
+        assert!(!path.exists());
+        plugin.save_pending_dump().unwrap();
+        assert!(!plugin.has_pending_dump());
+        let recording = FlightRecording::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            recording.to_bytes().unwrap(),
+            plugin.recording().to_bytes().unwrap()
+        );
+        assert_eq!(recording.initialization(), Some((1000.0, 100)));
+        assert_eq!(recording.failures().count(), 1);
+
+        // The first parameter change scrolled out into the setup
+        assert!(matches!(recording.setup[0], FlightEvent::Initialize { .. }));
+        assert!(matches!(recording.setup[1], FlightEvent::State(_)));
+        assert_eq!(
+            recording.setup[2],
+            FlightEvent::Parameter {
+                index: 0,
+                value: 0.25
+            }
+        );
+        let positions: Vec<u64> = recording.entries.iter().map(|e| e.position).collect();
+        assert_eq!(positions, vec![200, 300, 400, 400]);
+        assert!(recording
+            .to_string()
+            .contains("FAILED: Mock processing failure"));
+    }
+
+    #[test]
+    fn test_flight_error_is_truncated_on_char_boundary() {
+        let error = FlightError::from(&Error::Other("é".repeat(100)));
+        assert_eq!(error.as_str(), "é".repeat(FlightError::CAPACITY / 2));
+        assert_eq!(FlightError::new("short").to_string(), "short");
+    }
+
+    #[test]
+    fn test_replay_reproduces_recording() {
+        let signal = Signal::sine(100.0, 0.5);
+        let mut mock = MockPlugin::new();
+        mock.fail_blocks = vec![3];
+        let mut plugin = FlightRecorder::new(mock, Duration::from_secs(10));
+        plugin.initialize(1000.0, 100).unwrap();
+        plugin.set_parameter(0, 0.25).unwrap();
+
+        // Record a run fed with the same signal the replay will use
+        let mut generator = Generator::new(signal, 1000.0);
+        let mut expected = Vec::new();
+        for block in 0..5 {
+            if block == 2 {
+                plugin.set_parameter(0, 0.75).unwrap();
+            }
+            let input = generator.render(100);
+            let mut left = [0.0f32; 100];
+            let mut right = [0.0f32; 100];
+            match plugin.process(&[&input, &input], &mut [&mut left, &mut right], 100) {
+                Ok(()) => expected.extend_from_slice(&left),
+                Err(_) => expected.extend_from_slice(&[0.0; 100]),
+            }
+        }
+        let recording = plugin.recording();
+
+        let mut fresh = MockPlugin::new();
+        fresh.fail_blocks = vec![3];
+        let report = recording.replay(&mut fresh, Some(signal)).unwrap();
+        assert_eq!(report.blocks.len(), 5);
+        assert_eq!(report.blocks[3].position, 300);
+        assert_eq!(report.output[0], expected);
+        assert_eq!(report.failures().count(), 1);
+        assert!(report.reproduced());
+
+        // A fixed plugin doesn't reproduce it
+        let report = recording.replay(&mut MockPlugin::new(), None).unwrap();
+        assert!(!report.reproduced());
+        assert!(report.output[0].iter().all(|&s| s == 0.0));
+    }
+}
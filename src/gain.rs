@@ -0,0 +1,240 @@
+//! Decibel conversions and gain staging
+//!
+//! [`db_to_linear()`] and [`linear_to_db()`] convert between decibels and
+//! linear amplitude. [`GainStaging`] keeps the levels of a chain of hosted
+//! plugins consistent in one place:
+//!
+//! - The *headroom* attenuates the signal entering the chain, so plugins
+//!   modelled on analog gear (which expect a nominal level around -18 dBFS)
+//!   aren't driven into saturation, and boosts in the middle of the chain
+//!   don't clip plugins that clip internally. The same amount is made up at
+//!   the output, so changing the headroom doesn't change the overall level.
+//! - The *master gain* is applied at the output on top of that.
+//!
+//! Settings are shared through atomics, so a UI can change them while the
+//! audio thread applies them through a [`GainStage`]; changes are ramped over
+//! a block to avoid zipper noise.
+//!
+//! # Examples
+//!
+//! ```
+//! use rack::gain::{db_to_linear, GainStaging};
+//!
+//! let staging = GainStaging::new().with_headroom_db(18.0);
+//! let mut input = staging.input_stage();
+//! let mut output = staging.output_stage();
+//!
+//! let mut left = vec![0.5f32; 64];
+//! let mut right = vec![0.5f32; 64];
+//! input.process(&mut [&mut left, &mut right], 64);
+//! assert!((left[63] - 0.5 * db_to_linear(-18.0)).abs() < 1e-6);
+//! // ... process the plugin chain ...
+//! output.process(&mut [&mut left, &mut right], 64);
+//! assert!((left[63] - 0.5).abs() < 1e-6);
+//!
+//! staging.set_master_db(-6.0); // From any thread
+//! ```
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+/// Level reported by [`linear_to_db()`] for silence, in dB
+pub const SILENCE_DB: f32 = -200.0;
+
+/// Convert decibels to linear amplitude
+pub fn db_to_linear(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// Convert linear amplitude to decibels
+///
+/// Silence (and anything below [`SILENCE_DB`]) is reported as [`SILENCE_DB`]
+/// rather than negative infinity.
+pub fn linear_to_db(linear: f32) -> f32 {
+    let linear = linear.abs();
+    if linear <= 1e-10 {
+        SILENCE_DB
+    } else {
+        20.0 * linear.log10()
+    }
+}
+
+#[derive(Debug)]
+struct Settings {
+    /// f32 bit patterns, in dB
+    headroom_db: AtomicU32,
+    master_db: AtomicU32,
+}
+
+/// Headroom and master gain shared by the input and output of a chain
+///
+/// Cloning gives another handle to the same settings.
+#[derive(Debug, Clone)]
+pub struct GainStaging {
+    settings: Arc<Settings>,
+}
+
+impl Default for GainStaging {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GainStaging {
+    /// Create settings with no headroom and unity master gain
+    pub fn new() -> Self {
+        Self {
+            settings: Arc::new(Settings {
+                headroom_db: AtomicU32::new(0f32.to_bits()),
+                master_db: AtomicU32::new(0f32.to_bits()),
+            }),
+        }
+    }
+
+    /// Set the headroom
+    pub fn with_headroom_db(self, db: f32) -> Self {
+        self.set_headroom_db(db);
+        self
+    }
+
+    /// Set the master gain
+    pub fn with_master_db(self, db: f32) -> Self {
+        self.set_master_db(db);
+        self
+    }
+
+    /// Attenuation applied to the chain's input and made up at its output, in
+    /// dB
+    pub fn headroom_db(&self) -> f32 {
+        f32::from_bits(self.settings.headroom_db.load(Ordering::Relaxed))
+    }
+
+    /// Change the headroom (negative values are treated as 0 dB)
+    pub fn set_headroom_db(&self, db: f32) {
+        self.settings
+            .headroom_db
+            .store(db.max(0.0).to_bits(), Ordering::Relaxed);
+    }
+
+    /// Gain applied to the chain's output, in dB
+    pub fn master_db(&self) -> f32 {
+        f32::from_bits(self.settings.master_db.load(Ordering::Relaxed))
+    }
+
+    /// Change the master gain
+    pub fn set_master_db(&self, db: f32) {
+        self.settings
+            .master_db
+            .store(db.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Linear gain applied to the chain's input
+    pub fn input_gain(&self) -> f32 {
+        db_to_linear(-self.headroom_db())
+    }
+
+    /// Linear gain applied to the chain's output
+    pub fn output_gain(&self) -> f32 {
+        db_to_linear(self.headroom_db() + self.master_db())
+    }
+
+    /// A stage applying [`input_gain()`](Self::input_gain), for the audio
+    /// thread
+    pub fn input_stage(&self) -> GainStage {
+        GainStage::new(self.clone(), Point::Input)
+    }
+
+    /// A stage applying [`output_gain()`](Self::output_gain), for the audio
+    /// thread
+    pub fn output_stage(&self) -> GainStage {
+        GainStage::new(self.clone(), Point::Output)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Point {
+    Input,
+    Output,
+}
+
+/// Applies one point of a [`GainStaging`] to audio
+///
+/// Gain changes are ramped linearly over the next block.
+#[derive(Debug)]
+pub struct GainStage {
+    staging: GainStaging,
+    point: Point,
+    /// Gain at the end of the last block
+    current: f32,
+}
+
+impl GainStage {
+    fn new(staging: GainStaging, point: Point) -> Self {
+        let mut stage = Self {
+            staging,
+            point,
+            current: 1.0,
+        };
+        stage.current = stage.target();
+        stage
+    }
+
+    fn target(&self) -> f32 {
+        match self.point {
+            Point::Input => self.staging.input_gain(),
+            Point::Output => self.staging.output_gain(),
+        }
+    }
+
+    /// Apply the gain to `num_frames` frames of every channel, in place
+    pub fn process(&mut self, channels: &mut [&mut [f32]], num_frames: usize) {
+        let start = self.current;
+        let target = self.target();
+        self.current = target;
+        if start == target {
+            if target != 1.0 {
+                for channel in channels.iter_mut() {
+                    channel[..num_frames].iter_mut().for_each(|s| *s *= target);
+                }
+            }
+            return;
+        }
+        let step = (target - start) / num_frames.max(1) as f32;
+        for channel in channels.iter_mut() {
+            for (i, sample) in channel[..num_frames].iter_mut().enumerate() {
+                *sample *= start + step * (i + 1) as f32;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_db_conversions() {
+        assert!((db_to_linear(-6.0) - 0.501_187).abs() < 1e-6);
+        assert!((linear_to_db(db_to_linear(-18.0)) + 18.0).abs() < 1e-4);
+        assert_eq!(linear_to_db(0.0), SILENCE_DB);
+        assert_eq!(linear_to_db(-1.0), 0.0);
+    }
+
+    #[test]
+    fn test_staging_ramps_changes() {
+        let staging = GainStaging::new().with_headroom_db(12.0);
+        let mut output = staging.output_stage();
+        staging.set_headroom_db(-3.0);
+        staging.set_master_db(-6.0);
+        assert_eq!(staging.headroom_db(), 0.0);
+
+        let mut samples = [1.0f32; 4];
+        output.process(&mut [&mut samples], 4);
+        assert!(samples[0] < db_to_linear(12.0) && samples[0] > samples[3]);
+        assert!((samples[3] - db_to_linear(-6.0)).abs() < 1e-6);
+
+        let mut samples = [1.0f32; 4];
+        output.process(&mut [&mut samples], 4);
+        assert_eq!(samples, [db_to_linear(-6.0); 4]);
+    }
+}
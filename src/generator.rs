@@ -0,0 +1,480 @@
+//! Test-signal generators
+//!
+//! A [`Generator`] produces one of the standard test signals ([`Signal`]):
+//! sine, white or pink noise, a logarithmic sweep, or an impulse. Use it
+//! directly to fill buffers, or as a node: `Generator` implements
+//! [`PluginInstance`] as a source with no inputs, so it fits anywhere a plugin
+//! does (e.g. feeding a chain for calibration, or rendered with
+//! [`RenderJob`](crate::render::RenderJob)).
+//!
+//! Noise is generated from a fixed seed, so renders are reproducible; change it
+//! with [`Generator::with_seed()`].
+//!
+//! # Examples
+//!
+//! ```
+//! use rack::generator::{Generator, Signal};
+//!
+//! let mut sine = Generator::new(Signal::sine(440.0, 0.5), 48000.0);
+//! let mut left = vec![0.0f32; 512];
+//! let mut right = vec![0.0f32; 512];
+//! sine.fill(&mut [&mut left, &mut right], 512);
+//! assert_eq!(left, right);
+//!
+//! // One second of pink noise at -20 dBFS
+//! let noise = Generator::new(Signal::PinkNoise { level: 0.1 }, 48000.0).render(48000);
+//! # assert_eq!(noise.len(), 48000);
+//! ```
+
+use crate::{
+    Error, MidiEvent, ParameterInfo, PluginInfo, PluginInstance, PluginType, PresetInfo, Result,
+};
+use std::f64::consts::PI;
+use std::path::PathBuf;
+
+/// Seed of the noise generator unless set with [`Generator::with_seed()`]
+const DEFAULT_SEED: u32 = 0x2545_F491;
+
+/// A test signal
+///
+/// Levels are linear peak amplitudes (noise stays within `-level..level`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Signal {
+    /// Sine wave
+    Sine {
+        /// Frequency in Hz
+        frequency: f64,
+        /// Peak amplitude
+        level: f32,
+    },
+
+    /// White noise (equal energy per Hz)
+    WhiteNoise {
+        /// Peak amplitude
+        level: f32,
+    },
+
+    /// Pink noise (equal energy per octave)
+    PinkNoise {
+        /// Peak amplitude (approximate)
+        level: f32,
+    },
+
+    /// Exponential sine sweep from `start` to `end`, followed by silence
+    Sweep {
+        /// Start frequency in Hz
+        start: f64,
+        /// End frequency in Hz
+        end: f64,
+        /// Length of the sweep in seconds
+        duration: f64,
+        /// Peak amplitude
+        level: f32,
+    },
+
+    /// A single sample of `level`, followed by silence
+    Impulse {
+        /// Amplitude of the impulse
+        level: f32,
+    },
+}
+
+impl Signal {
+    /// Sine wave of `frequency` Hz
+    pub fn sine(frequency: f64, level: f32) -> Self {
+        Signal::Sine { frequency, level }
+    }
+
+    /// Sweep over the audible range (20 Hz to 20 kHz) in `duration` seconds
+    pub fn sweep(duration: f64, level: f32) -> Self {
+        Signal::Sweep {
+            start: 20.0,
+            end: 20000.0,
+            duration,
+            level,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Signal::Sine { .. } => "Sine",
+            Signal::WhiteNoise { .. } => "White Noise",
+            Signal::PinkNoise { .. } => "Pink Noise",
+            Signal::Sweep { .. } => "Sweep",
+            Signal::Impulse { .. } => "Impulse",
+        }
+    }
+
+    fn id(&self) -> &'static str {
+        match self {
+            Signal::Sine { .. } => "sine",
+            Signal::WhiteNoise { .. } => "white-noise",
+            Signal::PinkNoise { .. } => "pink-noise",
+            Signal::Sweep { .. } => "sweep",
+            Signal::Impulse { .. } => "impulse",
+        }
+    }
+}
+
+/// Generates a [`Signal`]
+///
+/// As a [`PluginInstance`], the generator has no inputs, no parameters and no
+/// presets, writes the same signal to every output channel (two unless set
+/// with [`with_channels()`](Self::with_channels)), and ignores MIDI.
+/// `reset()` restarts the signal.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    signal: Signal,
+    sample_rate: f64,
+    seed: u32,
+    /// Frames generated since the start of the signal
+    frame: u64,
+    /// Sine phase in radians
+    phase: f64,
+    rng: u32,
+    /// Pink noise filter state
+    pink: [f32; 7],
+    info: PluginInfo,
+    channels: usize,
+    initialized: bool,
+    max_block_size: usize,
+    sample_position: u64,
+}
+
+impl Generator {
+    /// Create a generator of `signal` at `sample_rate`
+    ///
+    /// As a node, the sample rate is replaced by the one passed to
+    /// `initialize()`.
+    pub fn new(signal: Signal, sample_rate: f64) -> Self {
+        let info = PluginInfo::new(
+            format!("{} Generator", signal.name()),
+            "Rack".to_string(),
+            1,
+            PluginType::Instrument,
+            PathBuf::new(),
+            format!("rack.generator.{}", signal.id()),
+        );
+        Self {
+            signal,
+            sample_rate,
+            seed: DEFAULT_SEED,
+            frame: 0,
+            phase: 0.0,
+            rng: DEFAULT_SEED,
+            pink: [0.0; 7],
+            info,
+            channels: 2,
+            initialized: false,
+            max_block_size: 0,
+            sample_position: 0,
+        }
+    }
+
+    /// Use `seed` for noise (0 is replaced with the default seed)
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.seed = if seed == 0 { DEFAULT_SEED } else { seed };
+        self.restart();
+        self
+    }
+
+    /// Output `channels` channels as a node
+    pub fn with_channels(mut self, channels: usize) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    /// The signal being generated
+    pub fn signal(&self) -> Signal {
+        self.signal
+    }
+
+    /// Switch to another signal, restarting it
+    pub fn set_signal(&mut self, signal: Signal) {
+        self.signal = signal;
+        self.restart();
+    }
+
+    /// The sample rate the signal is generated at
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// Start the signal over (sweeps and impulses play again, noise repeats)
+    pub fn restart(&mut self) {
+        self.frame = 0;
+        self.phase = 0.0;
+        self.rng = self.seed;
+        self.pink = [0.0; 7];
+    }
+
+    /// Whether a one-shot signal (sweep, impulse) has finished
+    pub fn is_finished(&self) -> bool {
+        match self.signal {
+            Signal::Sweep { duration, .. } => self.frame as f64 >= duration * self.sample_rate,
+            Signal::Impulse { .. } => self.frame > 0,
+            _ => false,
+        }
+    }
+
+    /// Generate the next sample
+    pub fn next_sample(&mut self) -> f32 {
+        let frame = self.frame;
+        self.frame += 1;
+        match self.signal {
+            Signal::Sine { frequency, level } => {
+                let sample = self.phase.sin() as f32 * level;
+                self.phase = (self.phase + 2.0 * PI * frequency / self.sample_rate) % (2.0 * PI);
+                sample
+            }
+            Signal::WhiteNoise { level } => self.white() * level,
+            Signal::PinkNoise { level } => self.pink() * level,
+            Signal::Sweep {
+                start,
+                end,
+                duration,
+                level,
+            } => {
+                let t = frame as f64 / self.sample_rate;
+                if t >= duration || start <= 0.0 || end <= 0.0 || duration <= 0.0 {
+                    return 0.0;
+                }
+                let ratio = (end / start).ln();
+                let phase = if ratio.abs() < 1e-12 {
+                    2.0 * PI * start * t
+                } else {
+                    2.0 * PI * start * duration / ratio * ((t / duration * ratio).exp() - 1.0)
+                };
+                phase.sin() as f32 * level
+            }
+            Signal::Impulse { level } => {
+                if frame == 0 {
+                    level
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    /// Write the next `num_frames` samples to every channel
+    pub fn fill(&mut self, channels: &mut [&mut [f32]], num_frames: usize) {
+        let Some((first, rest)) = channels.split_first_mut() else {
+            // Keep the signal moving even with nowhere to write it
+            for _ in 0..num_frames {
+                self.next_sample();
+            }
+            return;
+        };
+        for sample in first[..num_frames].iter_mut() {
+            *sample = self.next_sample();
+        }
+        for channel in rest {
+            channel[..num_frames].copy_from_slice(&first[..num_frames]);
+        }
+    }
+
+    /// Generate the next `frames` samples
+    pub fn render(&mut self, frames: usize) -> Vec<f32> {
+        (0..frames).map(|_| self.next_sample()).collect()
+    }
+
+    /// Uniform noise in -1.0..1.0 (xorshift32)
+    fn white(&mut self) -> f32 {
+        let mut x = self.rng;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.rng = x;
+        (x as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+
+    /// Pink noise in about -1.0..1.0 (Paul Kellet's refined filter)
+    fn pink(&mut self) -> f32 {
+        let white = self.white();
+        let b = &mut self.pink;
+        b[0] = 0.99886 * b[0] + white * 0.0555179;
+        b[1] = 0.99332 * b[1] + white * 0.0750759;
+        b[2] = 0.96900 * b[2] + white * 0.153_852;
+        b[3] = 0.86650 * b[3] + white * 0.3104856;
+        b[4] = 0.55000 * b[4] + white * 0.5329522;
+        b[5] = -0.7616 * b[5] - white * 0.0168980;
+        let pink = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+        b[6] = white * 0.115926;
+        (pink * 0.2).clamp(-1.0, 1.0)
+    }
+}
+
+impl PluginInstance for Generator {
+    fn initialize(&mut self, sample_rate: f64, max_block_size: usize) -> Result<()> {
+        if !(sample_rate.is_finite() && sample_rate > 0.0) {
+            return Err(Error::Other(format!(
+                "Invalid sample rate: {}",
+                sample_rate
+            )));
+        }
+        self.sample_rate = sample_rate;
+        self.max_block_size = max_block_size;
+        self.initialized = true;
+        self.restart();
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.restart();
+        Ok(())
+    }
+
+    fn process(
+        &mut self,
+        _inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        if !self.initialized {
+            return Err(Error::NotInitialized);
+        }
+        if num_frames > self.max_block_size {
+            return Err(Error::BlockTooLarge {
+                max: self.max_block_size,
+                got: num_frames,
+            });
+        }
+        self.fill(outputs, num_frames);
+        self.sample_position += num_frames as u64;
+        Ok(())
+    }
+
+    fn sample_position(&self) -> u64 {
+        self.sample_position
+    }
+
+    fn set_sample_position(&mut self, position: u64) -> Result<()> {
+        self.sample_position = position;
+        Ok(())
+    }
+
+    /// Check the generator is initialized; it has no parameters and ignores
+    /// MIDI, so there is nothing to deliver
+    fn flush_events(&mut self) -> Result<()> {
+        if !self.initialized {
+            return Err(Error::NotInitialized);
+        }
+        Ok(())
+    }
+
+    fn parameter_count(&self) -> usize {
+        0
+    }
+
+    fn parameter_info(&self, index: usize) -> Result<ParameterInfo> {
+        Err(Error::InvalidParameter(index))
+    }
+
+    fn get_parameter(&self, index: usize) -> Result<f32> {
+        Err(Error::InvalidParameter(index))
+    }
+
+    fn set_parameter(&mut self, index: usize, _value: f32) -> Result<()> {
+        Err(Error::InvalidParameter(index))
+    }
+
+    fn send_midi(&mut self, _events: &[MidiEvent]) -> Result<()> {
+        Ok(())
+    }
+
+    fn preset_count(&self) -> Result<usize> {
+        Ok(0)
+    }
+
+    fn preset_info(&self, index: usize) -> Result<PresetInfo> {
+        Err(Error::Other(format!("Invalid preset index: {}", index)))
+    }
+
+    fn load_preset(&mut self, preset_number: i32) -> Result<()> {
+        Err(Error::Other(format!(
+            "Invalid preset number: {}",
+            preset_number
+        )))
+    }
+
+    fn get_state(&self) -> Result<Vec<u8>> {
+        Ok(Vec::new())
+    }
+
+    fn set_state(&mut self, _data: &[u8]) -> Result<()> {
+        Ok(())
+    }
+
+    fn info(&self) -> &PluginInfo {
+        &self.info
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    fn input_channels(&self) -> usize {
+        0
+    }
+
+    fn output_channels(&self) -> usize {
+        if self.initialized {
+            self.channels
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::RenderJob;
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn test_signals() {
+        let sine = Generator::new(Signal::sine(1000.0, 0.5), 48000.0).render(480);
+        assert!((sine[12] - 0.5).abs() < 1e-6); // Quarter period
+        assert!((rms(&sine) - 0.5 / 2f32.sqrt()).abs() < 1e-3);
+
+        let white = Generator::new(Signal::WhiteNoise { level: 1.0 }, 48000.0).render(48000);
+        assert!(white.iter().all(|s| s.abs() <= 1.0));
+        assert!((rms(&white) - 1.0 / 3f32.sqrt()).abs() < 0.02);
+
+        // Pink noise loses energy towards the highs: neighbours correlate
+        let pink = Generator::new(Signal::PinkNoise { level: 1.0 }, 48000.0).render(48000);
+        let correlation: f32 = pink.windows(2).map(|w| w[0] * w[1]).sum::<f32>()
+            / pink.iter().map(|s| s * s).sum::<f32>();
+        assert!(correlation > 0.5);
+
+        let mut sweep = Generator::new(Signal::sweep(0.5, 1.0), 48000.0);
+        assert!(sweep.render(24000).iter().any(|s| s.abs() > 0.99));
+        assert!(sweep.is_finished());
+        assert_eq!(sweep.render(10), vec![0.0; 10]);
+
+        let impulse = Generator::new(Signal::Impulse { level: 0.25 }, 48000.0).render(3);
+        assert_eq!(impulse, vec![0.25, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_generator_as_node() {
+        let mut node = Generator::new(Signal::WhiteNoise { level: 0.5 }, 44100.0).with_seed(7);
+        node.initialize(48000.0, 64).unwrap();
+        assert_eq!((node.input_channels(), node.output_channels()), (0, 2));
+
+        let first = RenderJob::new(64).run(&mut node, &[], 100).unwrap();
+        node.reset().unwrap();
+        let second = RenderJob::new(64).run(&mut node, &[], 100).unwrap();
+        assert_eq!(first.channels, second.channels);
+        assert_eq!(first.channels[0], first.channels[1]);
+        assert_eq!(node.sample_position(), 200);
+
+        node.flush_events().unwrap();
+        assert_eq!(node.sample_position(), 200);
+    }
+}
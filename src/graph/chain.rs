@@ -0,0 +1,399 @@
+use crate::node::BlockContext;
+use crate::{BoxedPlugin, Error, PluginInstance, Result};
+use smallvec::SmallVec;
+
+use super::adapt_channels;
+
+/// A plugin in a chain, with the channel counts it was initialized with
+struct Slot {
+    plugin: BoxedPlugin,
+    inputs: usize,
+    outputs: usize,
+}
+
+impl Slot {
+    fn new(plugin: BoxedPlugin) -> Self {
+        let inputs = plugin.input_channels();
+        let outputs = plugin.output_channels();
+        Self {
+            plugin,
+            inputs,
+            outputs,
+        }
+    }
+}
+
+/// Where the signal is between two plugins
+#[derive(Clone, Copy)]
+enum Signal {
+    /// Still the chain's inputs
+    Inputs,
+    /// In the source buffer, with this many channels
+    Buffer(usize),
+}
+
+/// Plugins processing audio in series
+///
+/// Each plugin's output is the next one's input. Where the channel counts of
+/// neighbouring plugins differ, the signal is adapted: mono is copied to every
+/// channel, anything is averaged down to mono, and other mismatches repeat or
+/// drop channels. The same applies to the chain's own inputs and outputs. An
+/// empty chain passes its input through.
+///
+/// Plugins are initialized with the chain, and when added to an initialized
+/// chain. Signal passes between plugins through two buffers allocated then,
+/// so [`process()`](Self::process) doesn't allocate unless a plugin has more
+/// than eight channels.
+#[derive(Default)]
+pub struct Chain {
+    slots: Vec<Slot>,
+    /// Ping-pong buffers, one per channel of the widest plugin
+    buffers: [Vec<Vec<f32>>; 2],
+    sample_rate: f64,
+    max_block_size: usize,
+    initialized: bool,
+    sample_position: u64,
+}
+
+impl Chain {
+    /// Create an empty chain
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of plugins in the chain
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Whether the chain has no plugins
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Add a plugin at the end of the chain
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the chain is initialized and the plugin fails to
+    /// initialize; the plugin isn't added
+    pub fn push(&mut self, plugin: BoxedPlugin) -> Result<()> {
+        self.insert(self.slots.len(), plugin)
+    }
+
+    /// Add a plugin at position `index`, before the plugin there
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `index` is past the end, or if the chain is
+    /// initialized and the plugin fails to initialize; the plugin isn't added
+    pub fn insert(&mut self, index: usize, mut plugin: BoxedPlugin) -> Result<()> {
+        if index > self.slots.len() {
+            return Err(Error::Other(format!(
+                "Chain position {} out of range (length {})",
+                index,
+                self.slots.len()
+            )));
+        }
+        if self.initialized {
+            plugin.initialize(self.sample_rate, self.max_block_size)?;
+        }
+        self.slots.insert(index, Slot::new(plugin));
+        if self.initialized {
+            self.allocate();
+        }
+        Ok(())
+    }
+
+    /// Take the plugin at position `index` out of the chain
+    pub fn remove(&mut self, index: usize) -> Option<BoxedPlugin> {
+        (index < self.slots.len()).then(|| self.slots.remove(index).plugin)
+    }
+
+    /// The plugin at position `index`
+    pub fn plugin(&self, index: usize) -> Option<&dyn PluginInstance> {
+        self.slots.get(index).map(|slot| slot.plugin.as_ref())
+    }
+
+    /// The plugin at position `index`, e.g. to change its parameters
+    pub fn plugin_mut(&mut self, index: usize) -> Option<&mut dyn PluginInstance> {
+        Some(self.slots.get_mut(index)?.plugin.as_mut())
+    }
+
+    /// The plugins in processing order
+    pub fn plugins(&self) -> impl Iterator<Item = &dyn PluginInstance> + '_ {
+        self.slots.iter().map(|slot| slot.plugin.as_ref())
+    }
+
+    /// Initialize every plugin and allocate the buffers between them
+    ///
+    /// Plugins report their channel counts once initialized, so the chain's
+    /// adaptation follows whatever configuration each plugin settled on.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first plugin's error; plugins before it stay initialized,
+    /// but the chain doesn't process until initialized successfully
+    pub fn initialize(&mut self, sample_rate: f64, max_block_size: usize) -> Result<()> {
+        self.initialized = false;
+        for slot in &mut self.slots {
+            slot.plugin.initialize(sample_rate, max_block_size)?;
+            slot.inputs = slot.plugin.input_channels();
+            slot.outputs = slot.plugin.output_channels();
+        }
+        self.sample_rate = sample_rate;
+        self.max_block_size = max_block_size;
+        self.allocate();
+        self.initialized = true;
+        Ok(())
+    }
+
+    /// Whether [`initialize()`](Self::initialize) has succeeded
+    pub fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    /// Reset every plugin's internal state (delay lines, envelopes, ...)
+    ///
+    /// # Errors
+    ///
+    /// Returns the first plugin's error; later plugins aren't reset
+    pub fn reset(&mut self) -> Result<()> {
+        for slot in &mut self.slots {
+            slot.plugin.reset()?;
+        }
+        Ok(())
+    }
+
+    /// Total latency of the chain in samples, the sum of each plugin's
+    /// [`latency_samples()`](PluginInstance::latency_samples)
+    pub fn latency_samples(&self) -> usize {
+        self.slots
+            .iter()
+            .map(|slot| slot.plugin.latency_samples())
+            .sum()
+    }
+
+    /// Timeline position of the next block [`process()`](Self::process) runs
+    pub fn sample_position(&self) -> u64 {
+        self.sample_position
+    }
+
+    /// Move the timeline position of the next block, e.g. after a seek
+    pub fn set_sample_position(&mut self, position: u64) {
+        self.sample_position = position;
+    }
+
+    /// Process a block through every plugin at the chain's timeline position
+    ///
+    /// Like [`process_with_context()`](Self::process_with_context), with a
+    /// context for a stopped transport at [`sample_position()`](Self::sample_position).
+    ///
+    /// # Errors
+    ///
+    /// See [`process_with_context()`](Self::process_with_context)
+    pub fn process(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        let context = BlockContext::new(self.sample_rate, self.sample_position, num_frames);
+        self.process_with_context(inputs, outputs, &context)
+    }
+
+    /// Process a block through every plugin, each seeing `context`
+    ///
+    /// Inputs and outputs may have any number of channels; they are adapted to
+    /// the first and last plugins. The chain's sample position moves to the
+    /// end of the block.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the chain isn't initialized, the block is larger
+    /// than the initialized maximum, a buffer is shorter than the block, or a
+    /// plugin fails; the outputs are then undefined
+    pub fn process_with_context(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        context: &BlockContext,
+    ) -> Result<()> {
+        let num_frames = context.num_frames;
+        if !self.initialized {
+            return Err(Error::NotInitialized);
+        }
+        if num_frames > self.max_block_size {
+            return Err(Error::BlockTooLarge {
+                max: self.max_block_size,
+                got: num_frames,
+            });
+        }
+        if inputs.iter().any(|input| input.len() < num_frames)
+            || outputs.iter().any(|output| output.len() < num_frames)
+        {
+            return Err(Error::Other(format!(
+                "Buffers must hold at least {} samples",
+                num_frames
+            )));
+        }
+
+        let [first, second] = &mut self.buffers;
+        let (mut source, mut target) = (first, second);
+        let mut signal = Signal::Inputs;
+
+        for slot in &mut self.slots {
+            // Bring the signal to the plugin's input count in `source`
+            match signal {
+                Signal::Inputs if inputs.len() == slot.inputs => {}
+                Signal::Inputs => {
+                    adapt_channels(
+                        inputs,
+                        &mut channels_mut(source, slot.inputs, num_frames),
+                        num_frames,
+                    );
+                    signal = Signal::Buffer(slot.inputs);
+                }
+                Signal::Buffer(channels) if channels == slot.inputs => {}
+                Signal::Buffer(channels) => {
+                    adapt_channels(
+                        &channels_ref(source, channels, num_frames),
+                        &mut channels_mut(target, slot.inputs, num_frames),
+                        num_frames,
+                    );
+                    std::mem::swap(&mut source, &mut target);
+                    signal = Signal::Buffer(slot.inputs);
+                }
+            }
+
+            let mut plugin_outputs = channels_mut(target, slot.outputs, num_frames);
+            match signal {
+                Signal::Inputs => {
+                    slot.plugin
+                        .process_with_context(inputs, &mut plugin_outputs, context)?;
+                }
+                Signal::Buffer(channels) => {
+                    slot.plugin.process_with_context(
+                        &channels_ref(source, channels, num_frames),
+                        &mut plugin_outputs,
+                        context,
+                    )?;
+                }
+            }
+            drop(plugin_outputs);
+            std::mem::swap(&mut source, &mut target);
+            signal = Signal::Buffer(slot.outputs);
+        }
+
+        match signal {
+            Signal::Inputs => adapt_channels(inputs, outputs, num_frames),
+            Signal::Buffer(channels) => adapt_channels(
+                &channels_ref(source, channels, num_frames),
+                outputs,
+                num_frames,
+            ),
+        }
+        self.sample_position = context.sample_position + num_frames as u64;
+        Ok(())
+    }
+
+    /// Size the ping-pong buffers for the widest plugin
+    fn allocate(&mut self) {
+        let channels = self
+            .slots
+            .iter()
+            .map(|slot| slot.inputs.max(slot.outputs))
+            .max()
+            .unwrap_or(0);
+        for buffer in &mut self.buffers {
+            buffer.resize_with(channels, Vec::new);
+            for channel in buffer.iter_mut() {
+                channel.resize(self.max_block_size, 0.0);
+            }
+        }
+    }
+}
+
+/// The first `channels` channels of `buffer`, trimmed to `num_frames`
+fn channels_ref(buffer: &[Vec<f32>], channels: usize, num_frames: usize) -> SmallVec<[&[f32]; 8]> {
+    buffer[..channels]
+        .iter()
+        .map(|channel| &channel[..num_frames])
+        .collect()
+}
+
+/// The first `channels` channels of `buffer`, trimmed to `num_frames`
+fn channels_mut(
+    buffer: &mut [Vec<f32>],
+    channels: usize,
+    num_frames: usize,
+) -> SmallVec<[&mut [f32]; 8]> {
+    buffer[..channels]
+        .iter_mut()
+        .map(|channel| &mut channel[..num_frames])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::FnNode;
+    use crate::test_util::MockPlugin;
+
+    fn mono_to_stereo() -> BoxedPlugin {
+        Box::new(FnNode::new("Spread", 1, 2, |inputs, outputs, _| {
+            outputs[0].copy_from_slice(inputs[0]);
+            for (o, i) in outputs[1].iter_mut().zip(inputs[0]) {
+                *o = -i;
+            }
+        }))
+    }
+
+    #[test]
+    fn test_chain_processes_in_series() {
+        let mut chain = Chain::new();
+        chain.push(mono_to_stereo()).unwrap();
+        chain
+            .push(Box::new(MockPlugin::new().with_latency(3)))
+            .unwrap();
+        chain.initialize(48000.0, 16).unwrap();
+        chain
+            .insert(0, Box::new(MockPlugin::new().with_latency(2)))
+            .unwrap();
+        assert_eq!(chain.len(), 3);
+        assert_eq!(chain.latency_samples(), 5);
+
+        // Stereo in, downmixed for the spreader, stereo out, delayed by 5
+        let mut input = vec![0.0f32; 16];
+        input[0] = 1.0;
+        let mut out_left = vec![0.0f32; 16];
+        let mut out_right = vec![0.0f32; 16];
+        chain
+            .process(&[&input, &input], &mut [&mut out_left, &mut out_right], 16)
+            .unwrap();
+        assert_eq!(out_left[5], 1.0);
+        assert_eq!(out_right[5], -1.0);
+        assert_eq!(out_left.iter().sum::<f32>(), 1.0);
+        assert_eq!(chain.sample_position(), 16);
+
+        assert!(matches!(
+            chain.process(&[&input], &mut [&mut out_left], 32),
+            Err(Error::BlockTooLarge { max: 16, got: 32 })
+        ));
+        assert_eq!(chain.remove(1).unwrap().info().name, "Spread");
+    }
+
+    #[test]
+    fn test_empty_chain_passes_through() {
+        let mut chain = Chain::new();
+        assert!(matches!(
+            chain.process(&[], &mut [], 0),
+            Err(Error::NotInitialized)
+        ));
+        chain.initialize(48000.0, 4).unwrap();
+        let input = [1.0f32, 2.0, 3.0, 4.0];
+        let mut output = [0.0f32; 4];
+        chain.process(&[&input], &mut [&mut output], 4).unwrap();
+        assert_eq!(output, input);
+    }
+}
@@ -0,0 +1,132 @@
+//! Running several plugins together
+//!
+//! [`Chain`] runs plugins in series, like an insert rack: each plugin's output
+//! feeds the next one's input. [`Graph`] routes freely: parallel branches,
+//! sends to shared buses and back, with a gain on every connection. Both own
+//! the buffers between their plugins, so processing a block doesn't allocate,
+//! and adapt channel counts where connected plugins disagree.
+//!
+//! # Examples
+//!
+//! ```
+//! use rack::graph::Chain;
+//! use rack::node::FnNode;
+//! use rack::prelude::*;
+//!
+//! # fn main() -> rack::Result<()> {
+//! let mut chain = Chain::new();
+//! chain.push(Box::new(FnNode::new("Half", 2, 2, |inputs, outputs, _| {
+//!     for (input, output) in inputs.iter().zip(outputs.iter_mut()) {
+//!         for (i, o) in input.iter().zip(output.iter_mut()) {
+//!             *o = i * 0.5;
+//!         }
+//!     }
+//! })))?;
+//! chain.push(Box::new(FnNode::new("Mono", 1, 1, |inputs, outputs, _| {
+//!     outputs[0].copy_from_slice(inputs[0]);
+//! })))?;
+//! chain.initialize(48000.0, 512)?;
+//!
+//! let left = vec![1.0f32; 512];
+//! let right = vec![0.0f32; 512];
+//! let mut out_left = vec![0.0f32; 512];
+//! let mut out_right = vec![0.0f32; 512];
+//! chain.process(&[&left, &right], &mut [&mut out_left, &mut out_right], 512)?;
+//! assert_eq!(out_left[0], 0.25); // Halved, then downmixed to mono
+//! # Ok(())
+//! # }
+//! ```
+
+mod chain;
+mod routing;
+
+pub use chain::Chain;
+pub use routing::{Graph, NodeId};
+
+/// Copy `from` to `to`, adapting the channel count
+///
+/// Equal counts copy channel by channel. Mono is copied to every output, and
+/// everything is averaged into a mono output. Otherwise the input channels
+/// repeat across the outputs (stereo to quad is L R L R) and extra inputs are
+/// dropped. No inputs means silence.
+pub(crate) fn adapt_channels(from: &[&[f32]], to: &mut [&mut [f32]], num_frames: usize) {
+    if from.is_empty() {
+        for output in to.iter_mut() {
+            output[..num_frames].fill(0.0);
+        }
+        return;
+    }
+
+    if to.len() == 1 && from.len() > 1 {
+        let scale = 1.0 / from.len() as f32;
+        let output = &mut to[0][..num_frames];
+        output.copy_from_slice(&from[0][..num_frames]);
+        for input in &from[1..] {
+            for (o, i) in output.iter_mut().zip(&input[..num_frames]) {
+                *o += i;
+            }
+        }
+        for o in output.iter_mut() {
+            *o *= scale;
+        }
+        return;
+    }
+
+    for (index, output) in to.iter_mut().enumerate() {
+        output[..num_frames].copy_from_slice(&from[index % from.len()][..num_frames]);
+    }
+}
+
+/// Add `from`, scaled by `gain`, to `to`, adapting the channel count like
+/// [`adapt_channels()`]
+pub(crate) fn mix_channels(from: &[&[f32]], to: &mut [&mut [f32]], num_frames: usize, gain: f32) {
+    if from.is_empty() {
+        return;
+    }
+
+    if to.len() == 1 && from.len() > 1 {
+        let gain = gain / from.len() as f32;
+        for input in from {
+            for (o, i) in to[0][..num_frames].iter_mut().zip(&input[..num_frames]) {
+                *o += i * gain;
+            }
+        }
+        return;
+    }
+
+    for (index, output) in to.iter_mut().enumerate() {
+        let input = &from[index % from.len()][..num_frames];
+        for (o, i) in output[..num_frames].iter_mut().zip(input) {
+            *o += i * gain;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adapt_channels() {
+        let left = [1.0f32, 1.0];
+        let right = [3.0f32, 3.0];
+        let mut a = [9.0f32; 2];
+        let mut b = [9.0f32; 2];
+        let mut c = [9.0f32; 2];
+
+        adapt_channels(&[&left, &right], &mut [&mut a], 2);
+        assert_eq!(a, [2.0, 2.0]);
+
+        adapt_channels(&[&left], &mut [&mut a, &mut b], 2);
+        assert_eq!((a, b), ([1.0, 1.0], [1.0, 1.0]));
+
+        adapt_channels(&[&left, &right], &mut [&mut a, &mut b, &mut c], 2);
+        assert_eq!((a, b, c), ([1.0, 1.0], [3.0, 3.0], [1.0, 1.0]));
+
+        adapt_channels(&[], &mut [&mut a], 1);
+        assert_eq!(a, [0.0, 1.0]);
+
+        mix_channels(&[&left, &right], &mut [&mut a], 2, 0.5);
+        assert_eq!(a, [1.0, 2.0]);
+    }
+}
@@ -0,0 +1,1077 @@
+use crate::listen::{ListenTap, SidechainListen};
+use crate::node::BlockContext;
+use crate::session::{NodeState, SessionDocument, INPUT_NODE, OUTPUT_NODE};
+use crate::{BoxedPlugin, Error, PluginInstance, Result};
+use smallvec::SmallVec;
+use std::collections::{BTreeMap, VecDeque};
+
+use super::{adapt_channels, mix_channels};
+
+/// Index of the graph's input node
+const INPUT: usize = 0;
+
+/// Index of the graph's output node
+const OUTPUT: usize = 1;
+
+/// Identifies a node in a [`Graph`]
+///
+/// IDs aren't reused: once a node is removed, its ID refers to nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+enum Kind {
+    /// Produces the graph's inputs
+    Input,
+    /// Collects the graph's outputs
+    Output,
+    /// Sums its connections and passes them on
+    Bus,
+    Plugin(BoxedPlugin),
+}
+
+struct Node {
+    kind: Kind,
+    inputs: usize,
+    outputs: usize,
+    /// Sum of the incoming connections
+    input: Vec<Vec<f32>>,
+    /// What a plugin produced; unused by the other kinds
+    output: Vec<Vec<f32>>,
+}
+
+impl Node {
+    fn new(kind: Kind, inputs: usize, outputs: usize) -> Self {
+        Self {
+            kind,
+            inputs,
+            outputs,
+            input: Vec::new(),
+            output: Vec::new(),
+        }
+    }
+
+    /// What the node passes on to its connections
+    fn signal(&self) -> &[Vec<f32>] {
+        match self.kind {
+            Kind::Bus => &self.input[..self.outputs],
+            _ => &self.output[..self.outputs],
+        }
+    }
+
+    fn allocate(&mut self, max_block_size: usize) {
+        let output_channels = match self.kind {
+            Kind::Input | Kind::Plugin(_) => self.outputs,
+            Kind::Output | Kind::Bus => 0,
+        };
+        for (buffer, channels) in [
+            (&mut self.input, self.inputs),
+            (&mut self.output, output_channels),
+        ] {
+            buffer.resize_with(channels, Vec::new);
+            for channel in buffer.iter_mut() {
+                channel.resize(max_block_size, 0.0);
+            }
+        }
+    }
+}
+
+/// A connection from one node's output to another's input
+struct Edge {
+    from: usize,
+    to: usize,
+    gain: f32,
+}
+
+/// Plugins processing audio with arbitrary routing
+///
+/// A graph has an [input](Self::input) node producing the audio passed to
+/// [`process()`](Self::process) and an [output](Self::output) node collecting
+/// what it returns. Plugins and summing buses are added as nodes and
+/// [connected](Self::connect) freely, each connection with its own gain: a
+/// node's input is the sum of everything connected to it, so parallel
+/// branches, sends to a shared reverb bus and its return are all just
+/// connections. Connections that would form a loop are refused.
+///
+/// Every block, nodes are processed in dependency order, so each one runs
+/// after everything feeding it. Nodes that nothing feeds process silence.
+/// Channel counts are adapted per connection, like in a [`Chain`](super::Chain).
+///
+/// Latency isn't compensated yet: branches with different latencies arrive
+/// at a node out of alignment. [`latency_samples()`](Self::latency_samples)
+/// reports the longest path.
+///
+/// [`get_state()`](Self::get_state) saves the whole graph, every plugin's
+/// state included, in one [`SessionDocument`]; [`set_state()`](Self::set_state)
+/// restores it.
+///
+/// # Examples
+///
+/// ```
+/// use rack::graph::Graph;
+/// use rack::node::FnNode;
+/// use rack::prelude::*;
+///
+/// # fn main() -> rack::Result<()> {
+/// let mut graph = Graph::new(2, 2);
+/// let reverb = graph.add_plugin(Box::new(FnNode::new("Reverb", 2, 2, |inputs, outputs, _| {
+///     outputs[0].copy_from_slice(inputs[0]);
+///     outputs[1].copy_from_slice(inputs[1]);
+/// })))?;
+/// let send = graph.add_bus(2);
+///
+/// // Dry signal straight through, plus a quarter of it through the reverb
+/// graph.connect(graph.input(), graph.output(), 1.0)?;
+/// graph.connect(graph.input(), send, 0.25)?;
+/// graph.connect(send, reverb, 1.0)?;
+/// graph.connect(reverb, graph.output(), 1.0)?;
+/// graph.initialize(48000.0, 512)?;
+///
+/// let left = vec![1.0f32; 512];
+/// let right = vec![1.0f32; 512];
+/// let mut out_left = vec![0.0f32; 512];
+/// let mut out_right = vec![0.0f32; 512];
+/// graph.process(&[&left, &right], &mut [&mut out_left, &mut out_right], 512)?;
+/// assert_eq!(out_left[0], 1.25);
+/// # Ok(())
+/// # }
+/// ```
+pub struct Graph {
+    /// Nodes by ID; `None` once removed
+    nodes: Vec<Option<Node>>,
+    edges: Vec<Edge>,
+    /// Live nodes, each after everything feeding it
+    order: Vec<usize>,
+    /// Edges into each node, by node index
+    incoming: Vec<Vec<usize>>,
+    sample_rate: f64,
+    max_block_size: usize,
+    initialized: bool,
+    sample_position: u64,
+    /// Node whose input can replace the output, and the tap switching to it
+    listen: Option<(usize, ListenTap)>,
+}
+
+impl Graph {
+    /// Create a graph taking `inputs` channels and returning `outputs`
+    /// channels, with nothing connected
+    ///
+    /// [`process()`](Self::process) adapts buffers with other channel counts.
+    pub fn new(inputs: usize, outputs: usize) -> Self {
+        let mut graph = Self {
+            nodes: vec![
+                Some(Node::new(Kind::Input, 0, inputs)),
+                Some(Node::new(Kind::Output, outputs, 0)),
+            ],
+            edges: Vec::new(),
+            order: Vec::new(),
+            incoming: Vec::new(),
+            sample_rate: 0.0,
+            max_block_size: 0,
+            initialized: false,
+            sample_position: 0,
+            listen: None,
+        };
+        graph.schedule();
+        graph
+    }
+
+    /// The node producing the graph's inputs
+    pub fn input(&self) -> NodeId {
+        NodeId(INPUT)
+    }
+
+    /// The node collecting the graph's outputs
+    pub fn output(&self) -> NodeId {
+        NodeId(OUTPUT)
+    }
+
+    /// Add a plugin as a node
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the graph is initialized and the plugin fails to
+    /// initialize; the plugin isn't added
+    pub fn add_plugin(&mut self, mut plugin: BoxedPlugin) -> Result<NodeId> {
+        if self.initialized {
+            plugin.initialize(self.sample_rate, self.max_block_size)?;
+        }
+        let (inputs, outputs) = (plugin.input_channels(), plugin.output_channels());
+        Ok(self.add_node(Node::new(Kind::Plugin(plugin), inputs, outputs)))
+    }
+
+    /// Add a bus with `channels` channels, which sums its connections and
+    /// passes them on
+    pub fn add_bus(&mut self, channels: usize) -> NodeId {
+        self.add_node(Node::new(Kind::Bus, channels, channels))
+    }
+
+    /// Remove a node and its connections
+    ///
+    /// Returns the node's plugin, or `None` for a bus.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for the input and output nodes, which can't be
+    /// removed, and for unknown nodes
+    pub fn remove(&mut self, node: NodeId) -> Result<Option<BoxedPlugin>> {
+        if node.0 == INPUT || node.0 == OUTPUT {
+            return Err(Error::Other(
+                "The graph's input and output nodes can't be removed".to_string(),
+            ));
+        }
+        let removed = self
+            .nodes
+            .get_mut(node.0)
+            .and_then(Option::take)
+            .ok_or_else(|| unknown(node))?;
+        self.edges
+            .retain(|edge| edge.from != node.0 && edge.to != node.0);
+        if self
+            .listen
+            .as_ref()
+            .is_some_and(|(index, _)| *index == node.0)
+        {
+            self.listen = None;
+        }
+        self.schedule();
+        Ok(match removed.kind {
+            Kind::Plugin(plugin) => Some(plugin),
+            _ => None,
+        })
+    }
+
+    /// Connect `from`'s output to `to`'s input with `gain` (linear)
+    ///
+    /// Connecting two nodes that are already connected changes the gain.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a node is unknown, `from` is the output node, `to`
+    /// is the input node, or the connection would form a loop
+    pub fn connect(&mut self, from: NodeId, to: NodeId, gain: f32) -> Result<()> {
+        self.node(from)?;
+        self.node(to)?;
+        if from.0 == OUTPUT || to.0 == INPUT {
+            return Err(Error::Other(
+                "Connections go from the input node and into the output node".to_string(),
+            ));
+        }
+        if let Some(edge) = self.edge_mut(from, to) {
+            edge.gain = gain;
+            return Ok(());
+        }
+        if from == to || self.reaches(to.0, from.0) {
+            return Err(Error::Other(format!(
+                "Connecting node {} to node {} would form a loop",
+                from.0, to.0
+            )));
+        }
+        self.edges.push(Edge {
+            from: from.0,
+            to: to.0,
+            gain,
+        });
+        self.schedule();
+        Ok(())
+    }
+
+    /// Remove the connection from `from` to `to`
+    ///
+    /// Returns whether they were connected.
+    pub fn disconnect(&mut self, from: NodeId, to: NodeId) -> bool {
+        let before = self.edges.len();
+        self.edges
+            .retain(|edge| (edge.from, edge.to) != (from.0, to.0));
+        let removed = self.edges.len() != before;
+        if removed {
+            self.schedule();
+        }
+        removed
+    }
+
+    /// Change the gain of the connection from `from` to `to`
+    ///
+    /// Safe to call between blocks; takes effect on the next one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the nodes aren't connected
+    pub fn set_gain(&mut self, from: NodeId, to: NodeId, gain: f32) -> Result<()> {
+        let edge = self.edge_mut(from, to).ok_or_else(|| {
+            Error::Other(format!("Node {} isn't connected to node {}", from.0, to.0))
+        })?;
+        edge.gain = gain;
+        Ok(())
+    }
+
+    /// Every connection, as `(from, to, gain)`
+    pub fn connections(&self) -> impl Iterator<Item = (NodeId, NodeId, f32)> + '_ {
+        self.edges
+            .iter()
+            .map(|edge| (NodeId(edge.from), NodeId(edge.to), edge.gain))
+    }
+
+    /// The plugin of a node, or `None` if the node isn't a plugin
+    pub fn plugin(&self, node: NodeId) -> Option<&dyn PluginInstance> {
+        match &self.node(node).ok()?.kind {
+            Kind::Plugin(plugin) => Some(plugin.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// The plugin of a node, e.g. to change its parameters
+    pub fn plugin_mut(&mut self, node: NodeId) -> Option<&mut dyn PluginInstance> {
+        match &mut self.nodes.get_mut(node.0)?.as_mut()?.kind {
+            Kind::Plugin(plugin) => Some(plugin.as_mut()),
+            _ => None,
+        }
+    }
+
+    /// Let the user listen to what feeds `node` instead of the graph's output
+    ///
+    /// Typically `node` is a compressor or gate, or the bus feeding its key:
+    /// while the returned switch is on, the graph's outputs play the sum of
+    /// `node`'s incoming connections, so the user hears what it is keyed
+    /// from. The switch can be flipped from any thread and crossfades over one
+    /// block (see [`SidechainListen`]). Replaces an earlier listen on another
+    /// node; removing the node ends it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the node is unknown
+    pub fn listen(&mut self, node: NodeId) -> Result<SidechainListen> {
+        self.node(node)?;
+        let listen = SidechainListen::new();
+        self.listen = Some((node.0, listen.tap()));
+        Ok(listen)
+    }
+
+    /// Stop offering the listen set up with [`listen()`](Self::listen)
+    pub fn clear_listen(&mut self) {
+        self.listen = None;
+    }
+
+    /// Save the graph's topology and every plugin's state in one document
+    ///
+    /// The bytes of [`to_session()`](Self::to_session)'s document; restore
+    /// them with [`set_state()`](Self::set_state).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a plugin's state can't be read or is 4 GiB or
+    /// larger
+    pub fn get_state(&self) -> Result<Vec<u8>> {
+        self.to_session()?.to_bytes()
+    }
+
+    /// Restore a graph saved with [`get_state()`](Self::get_state)
+    ///
+    /// See [`restore_session()`](Self::restore_session).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `data` isn't a session document, or restoring it
+    /// fails
+    pub fn set_state<F>(&mut self, data: &[u8], load: F) -> Result<()>
+    where
+        F: FnMut(&NodeState) -> Result<BoxedPlugin>,
+    {
+        self.restore_session(&SessionDocument::from_bytes(data)?, load)
+    }
+
+    /// Describe the graph as a [`SessionDocument`]
+    ///
+    /// Every plugin becomes a node with its current state, every bus a
+    /// [`BusState`](crate::session::BusState) and every connection keeps its
+    /// gain. IDs are the graph's [`NodeId`]s as strings, with [`INPUT_NODE`]
+    /// and [`OUTPUT_NODE`] for the graph's input and output. Use this rather
+    /// than [`get_state()`](Self::get_state) to serialize with a
+    /// [`StateTransform`](crate::session::StateTransform).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a plugin's state can't be read
+    pub fn to_session(&self) -> Result<SessionDocument> {
+        let mut session = SessionDocument::new();
+        if self.initialized {
+            session.sample_rate = Some(self.sample_rate.round() as u32);
+        }
+        for (index, node) in self.nodes.iter().enumerate() {
+            let Some(node) = node else {
+                continue;
+            };
+            match &node.kind {
+                Kind::Plugin(plugin) => session.add_node(&node_key(index), plugin.as_ref())?,
+                Kind::Bus => session.add_bus(&node_key(index), node.inputs),
+                Kind::Input | Kind::Output => {}
+            }
+        }
+        for edge in &self.edges {
+            session.connect_with_gain(&node_key(edge.from), &node_key(edge.to), edge.gain);
+        }
+        Ok(session)
+    }
+
+    /// Replace the graph's nodes and connections with those of `session`
+    ///
+    /// Node IDs are kept, so [`NodeId`]s from before the save refer to the
+    /// same nodes afterwards. A plugin already at a node's ID is reused if it
+    /// is the saved plugin; otherwise `load` creates it (typically by finding
+    /// the node's plugin in a scan and loading it) and, if the graph is
+    /// initialized, it is initialized. Either way it then gets its saved
+    /// state, so plugins that only accept states once initialized need an
+    /// initialized graph or a `load` that initializes them. The graph's own
+    /// input and output keep their channel counts.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the document doesn't describe a graph (unknown or
+    /// duplicate IDs, sidechain connections, loops), `load` fails, or a plugin
+    /// fails to initialize or rejects its state. The graph's topology is then
+    /// unchanged, though reused plugins may already have their saved states.
+    pub fn restore_session<F>(&mut self, session: &SessionDocument, mut load: F) -> Result<()>
+    where
+        F: FnMut(&NodeState) -> Result<BoxedPlugin>,
+    {
+        // Check the whole document before touching anything
+        let mut saved = BTreeMap::new();
+        let entries = session
+            .nodes
+            .iter()
+            .map(|node| (&node.id, Saved::Plugin(node)))
+            .chain(
+                session
+                    .buses
+                    .iter()
+                    .map(|bus| (&bus.id, Saved::Bus(bus.channels))),
+            );
+        for (id, entry) in entries {
+            let index = node_index(id)?;
+            if index == INPUT || index == OUTPUT {
+                return Err(Error::Other(format!(
+                    "Session node '{}' clashes with the graph's {}",
+                    id, id
+                )));
+            }
+            if saved.insert(index, entry).is_some() {
+                return Err(Error::Other(format!("Session has two nodes '{}'", id)));
+            }
+        }
+
+        let mut edges: Vec<Edge> = Vec::new();
+        for connection in &session.connections {
+            if connection.sidechain {
+                return Err(Error::Other(format!(
+                    "Graphs have no sidechain inputs (connection from '{}' to '{}')",
+                    connection.from, connection.to
+                )));
+            }
+            let (from, to) = (node_index(&connection.from)?, node_index(&connection.to)?);
+            for (id, index) in [(&connection.from, from), (&connection.to, to)] {
+                if index != INPUT && index != OUTPUT && !saved.contains_key(&index) {
+                    return Err(Error::Other(format!(
+                        "Session connection refers to unknown node '{}'",
+                        id
+                    )));
+                }
+            }
+            if from == OUTPUT || to == INPUT {
+                return Err(Error::Other(
+                    "Connections go from the input node and into the output node".to_string(),
+                ));
+            }
+            match edges
+                .iter_mut()
+                .find(|edge| (edge.from, edge.to) == (from, to))
+            {
+                Some(edge) => edge.gain = connection.gain,
+                None => edges.push(Edge {
+                    from,
+                    to,
+                    gain: connection.gain,
+                }),
+            }
+        }
+
+        // Removed nodes' IDs stay retired
+        let size = saved
+            .keys()
+            .next_back()
+            .map_or(0, |index| index + 1)
+            .max(self.nodes.len());
+        if has_loop(size, &edges) {
+            return Err(Error::Other("Session connections form a loop".to_string()));
+        }
+
+        // Load the plugins that aren't in the graph yet and give every plugin
+        // its state
+        let mut loaded = BTreeMap::new();
+        for (&index, entry) in &saved {
+            let Saved::Plugin(node) = entry else {
+                continue;
+            };
+            match self.plugin_mut(NodeId(index)) {
+                Some(plugin) if node.matches(plugin.info()) => plugin.set_state(&node.state)?,
+                _ => {
+                    let mut plugin = load(node)?;
+                    if self.initialized {
+                        plugin.initialize(self.sample_rate, self.max_block_size)?;
+                    }
+                    plugin.set_state(&node.state)?;
+                    loaded.insert(index, plugin);
+                }
+            }
+        }
+
+        let mut previous = std::mem::take(&mut self.nodes);
+        self.nodes = (0..size).map(|_| None).collect();
+        self.nodes[INPUT] = previous[INPUT].take();
+        self.nodes[OUTPUT] = previous[OUTPUT].take();
+        for (index, entry) in saved {
+            let mut node = match (entry, loaded.remove(&index)) {
+                (Saved::Bus(channels), _) => Node::new(Kind::Bus, channels, channels),
+                (Saved::Plugin(_), Some(plugin)) => {
+                    let (inputs, outputs) = (plugin.input_channels(), plugin.output_channels());
+                    Node::new(Kind::Plugin(plugin), inputs, outputs)
+                }
+                (Saved::Plugin(_), None) => previous[index].take().expect("reused nodes exist"),
+            };
+            if self.initialized {
+                node.allocate(self.max_block_size);
+            }
+            self.nodes[index] = Some(node);
+        }
+        self.edges = edges;
+        if let Some((index, _)) = &self.listen {
+            if self.nodes[*index].is_none() {
+                self.listen = None;
+            }
+        }
+        self.schedule();
+        Ok(())
+    }
+
+    /// Initialize every plugin and allocate the buffers between nodes
+    ///
+    /// # Errors
+    ///
+    /// Returns the first plugin's error; the graph doesn't process until
+    /// initialized successfully
+    pub fn initialize(&mut self, sample_rate: f64, max_block_size: usize) -> Result<()> {
+        self.initialized = false;
+        for node in self.nodes.iter_mut().flatten() {
+            if let Kind::Plugin(plugin) = &mut node.kind {
+                plugin.initialize(sample_rate, max_block_size)?;
+                node.inputs = plugin.input_channels();
+                node.outputs = plugin.output_channels();
+            }
+            node.allocate(max_block_size);
+        }
+        self.sample_rate = sample_rate;
+        self.max_block_size = max_block_size;
+        self.initialized = true;
+        Ok(())
+    }
+
+    /// Whether [`initialize()`](Self::initialize) has succeeded
+    pub fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    /// Reset every plugin's internal state (delay lines, envelopes, ...)
+    ///
+    /// # Errors
+    ///
+    /// Returns the first plugin's error; later plugins aren't reset
+    pub fn reset(&mut self) -> Result<()> {
+        for node in self.nodes.iter_mut().flatten() {
+            if let Kind::Plugin(plugin) = &mut node.kind {
+                plugin.reset()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Latency of the slowest path from the input to the output node, in
+    /// samples
+    ///
+    /// A path's latency is the sum of its plugins'
+    /// [`latency_samples()`](PluginInstance::latency_samples).
+    pub fn latency_samples(&self) -> usize {
+        let mut latency = vec![0; self.nodes.len()];
+        for &index in &self.order {
+            let own = match &self.nodes[index].as_ref().map(|node| &node.kind) {
+                Some(Kind::Plugin(plugin)) => plugin.latency_samples(),
+                _ => 0,
+            };
+            let fed = self.incoming[index]
+                .iter()
+                .map(|&edge| latency[self.edges[edge].from])
+                .max()
+                .unwrap_or(0);
+            latency[index] = fed + own;
+        }
+        latency[OUTPUT]
+    }
+
+    /// Timeline position of the next block [`process()`](Self::process) runs
+    pub fn sample_position(&self) -> u64 {
+        self.sample_position
+    }
+
+    /// Move the timeline position of the next block, e.g. after a seek
+    pub fn set_sample_position(&mut self, position: u64) {
+        self.sample_position = position;
+    }
+
+    /// Process a block through the graph at its timeline position
+    ///
+    /// Like [`process_with_context()`](Self::process_with_context), with a
+    /// context for a stopped transport at [`sample_position()`](Self::sample_position).
+    ///
+    /// # Errors
+    ///
+    /// See [`process_with_context()`](Self::process_with_context)
+    pub fn process(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        let context = BlockContext::new(self.sample_rate, self.sample_position, num_frames);
+        self.process_with_context(inputs, outputs, &context)
+    }
+
+    /// Process a block through the graph, each plugin seeing `context`
+    ///
+    /// Inputs and outputs may have any number of channels; they are adapted to
+    /// the graph's. The graph's sample position moves to the end of the
+    /// block.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the graph isn't initialized, the block is larger
+    /// than the initialized maximum, a buffer is shorter than the block, or a
+    /// plugin fails; the outputs are then undefined
+    pub fn process_with_context(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        context: &BlockContext,
+    ) -> Result<()> {
+        let num_frames = context.num_frames;
+        if !self.initialized {
+            return Err(Error::NotInitialized);
+        }
+        if num_frames > self.max_block_size {
+            return Err(Error::BlockTooLarge {
+                max: self.max_block_size,
+                got: num_frames,
+            });
+        }
+        if inputs.iter().any(|input| input.len() < num_frames)
+            || outputs.iter().any(|output| output.len() < num_frames)
+        {
+            return Err(Error::Other(format!(
+                "Buffers must hold at least {} samples",
+                num_frames
+            )));
+        }
+
+        for &index in &self.order {
+            if index == INPUT {
+                let node = self.nodes[INPUT].as_mut().expect("input node exists");
+                adapt_channels(
+                    inputs,
+                    &mut channels_mut(&mut node.output, num_frames),
+                    num_frames,
+                );
+                continue;
+            }
+
+            // Sum the connections into the node's input
+            {
+                let node = self.nodes[index].as_mut().expect("scheduled nodes exist");
+                for channel in &mut node.input {
+                    channel[..num_frames].fill(0.0);
+                }
+            }
+            for &edge in &self.incoming[index] {
+                let Edge { from, gain, .. } = self.edges[edge];
+                let (source, node) = pair(&mut self.nodes, from, index);
+                mix_channels(
+                    &channels_ref(source.signal(), num_frames),
+                    &mut channels_mut(&mut node.input, num_frames),
+                    num_frames,
+                    gain,
+                );
+            }
+
+            let node = self.nodes[index].as_mut().expect("scheduled nodes exist");
+            match &mut node.kind {
+                Kind::Plugin(plugin) => plugin.process_with_context(
+                    &channels_ref(&node.input, num_frames),
+                    &mut channels_mut(&mut node.output, num_frames),
+                    context,
+                )?,
+                Kind::Output => {
+                    adapt_channels(&channels_ref(&node.input, num_frames), outputs, num_frames)
+                }
+                Kind::Input | Kind::Bus => {}
+            }
+        }
+
+        if let Some((index, tap)) = &mut self.listen {
+            let node = self.nodes[*index].as_ref().expect("listened nodes exist");
+            tap.process(&channels_ref(&node.input, num_frames), outputs, num_frames);
+        }
+
+        self.sample_position = context.sample_position + num_frames as u64;
+        Ok(())
+    }
+
+    fn add_node(&mut self, mut node: Node) -> NodeId {
+        if self.initialized {
+            node.allocate(self.max_block_size);
+        }
+        self.nodes.push(Some(node));
+        self.schedule();
+        NodeId(self.nodes.len() - 1)
+    }
+
+    fn node(&self, node: NodeId) -> Result<&Node> {
+        self.nodes
+            .get(node.0)
+            .and_then(Option::as_ref)
+            .ok_or_else(|| unknown(node))
+    }
+
+    fn edge_mut(&mut self, from: NodeId, to: NodeId) -> Option<&mut Edge> {
+        self.edges
+            .iter_mut()
+            .find(|edge| (edge.from, edge.to) == (from.0, to.0))
+    }
+
+    /// Whether a path of connections leads from `from` to `to`
+    fn reaches(&self, from: usize, to: usize) -> bool {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut pending = vec![from];
+        while let Some(index) = pending.pop() {
+            if index == to {
+                return true;
+            }
+            if !std::mem::replace(&mut visited[index], true) {
+                pending.extend(
+                    self.edges
+                        .iter()
+                        .filter(|edge| edge.from == index)
+                        .map(|edge| edge.to),
+                );
+            }
+        }
+        false
+    }
+
+    /// Work out the processing order after the topology changed
+    fn schedule(&mut self) {
+        self.incoming = vec![Vec::new(); self.nodes.len()];
+        for (index, edge) in self.edges.iter().enumerate() {
+            self.incoming[edge.to].push(index);
+        }
+
+        // Kahn's algorithm; connect() keeps the graph free of loops
+        let mut waiting: Vec<usize> = self.incoming.iter().map(Vec::len).collect();
+        let mut ready: VecDeque<usize> = (0..self.nodes.len())
+            .filter(|&index| self.nodes[index].is_some() && waiting[index] == 0)
+            .collect();
+        self.order.clear();
+        while let Some(index) = ready.pop_front() {
+            self.order.push(index);
+            for edge in self.edges.iter().filter(|edge| edge.from == index) {
+                waiting[edge.to] -= 1;
+                if waiting[edge.to] == 0 {
+                    ready.push_back(edge.to);
+                }
+            }
+        }
+    }
+}
+
+fn unknown(node: NodeId) -> Error {
+    Error::Other(format!("Unknown graph node {}", node.0))
+}
+
+/// A node described by a session, before it is added
+enum Saved<'a> {
+    Plugin(&'a NodeState),
+    Bus(usize),
+}
+
+/// A node's ID in session documents
+fn node_key(index: usize) -> String {
+    match index {
+        INPUT => INPUT_NODE.to_string(),
+        OUTPUT => OUTPUT_NODE.to_string(),
+        _ => index.to_string(),
+    }
+}
+
+/// The node index a session document's ID stands for
+fn node_index(id: &str) -> Result<usize> {
+    match id {
+        INPUT_NODE => Ok(INPUT),
+        OUTPUT_NODE => Ok(OUTPUT),
+        _ => id
+            .parse()
+            .map_err(|_| Error::Other(format!("Session node '{}' isn't a graph node", id))),
+    }
+}
+
+/// Whether `edges` between `size` nodes form a loop
+fn has_loop(size: usize, edges: &[Edge]) -> bool {
+    let mut waiting = vec![0usize; size];
+    for edge in edges {
+        waiting[edge.to] += 1;
+    }
+    let mut ready: Vec<usize> = (0..size).filter(|&index| waiting[index] == 0).collect();
+    let mut visited = 0;
+    while let Some(index) = ready.pop() {
+        visited += 1;
+        for edge in edges.iter().filter(|edge| edge.from == index) {
+            waiting[edge.to] -= 1;
+            if waiting[edge.to] == 0 {
+                ready.push(edge.to);
+            }
+        }
+    }
+    visited < size
+}
+
+/// The node at `from`, shared, and the one at `to`, exclusive
+fn pair(nodes: &mut [Option<Node>], from: usize, to: usize) -> (&Node, &mut Node) {
+    let (source, target) = if from < to {
+        let (head, tail) = nodes.split_at_mut(to);
+        (&head[from], &mut tail[0])
+    } else {
+        let (head, tail) = nodes.split_at_mut(from);
+        (&tail[0], &mut head[to])
+    };
+    (
+        source.as_ref().expect("connected nodes exist"),
+        target.as_mut().expect("connected nodes exist"),
+    )
+}
+
+/// Every channel of `buffer`, trimmed to `num_frames`
+fn channels_ref(buffer: &[Vec<f32>], num_frames: usize) -> SmallVec<[&[f32]; 8]> {
+    buffer
+        .iter()
+        .map(|channel| &channel[..num_frames])
+        .collect()
+}
+
+/// Every channel of `buffer`, trimmed to `num_frames`
+fn channels_mut(buffer: &mut [Vec<f32>], num_frames: usize) -> SmallVec<[&mut [f32]; 8]> {
+    buffer
+        .iter_mut()
+        .map(|channel| &mut channel[..num_frames])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::FnNode;
+    use crate::test_util::MockPlugin;
+
+    fn invert() -> BoxedPlugin {
+        Box::new(FnNode::new("Invert", 1, 1, |inputs, outputs, _| {
+            for (o, i) in outputs[0].iter_mut().zip(inputs[0]) {
+                *o = -i;
+            }
+        }))
+    }
+
+    #[test]
+    fn test_parallel_branches_and_buses() {
+        let mut graph = Graph::new(2, 2);
+        let mock = graph
+            .add_plugin(Box::new(MockPlugin::new().with_latency(4)))
+            .unwrap();
+        let inverted = graph.add_plugin(invert()).unwrap();
+        let bus = graph.add_bus(2);
+
+        // Input -> mock -> bus, input -> invert (mono) -> bus at half gain
+        let (input, output) = (graph.input(), graph.output());
+        graph.connect(input, mock, 1.0).unwrap();
+        graph.connect(input, inverted, 1.0).unwrap();
+        graph.connect(mock, bus, 1.0).unwrap();
+        graph.connect(inverted, bus, 0.5).unwrap();
+        graph.connect(bus, output, 1.0).unwrap();
+        graph.initialize(48000.0, 8).unwrap();
+        assert_eq!(graph.latency_samples(), 4);
+
+        let left = [1.0f32; 8];
+        let right = [3.0f32; 8];
+        let mut out_left = [0.0f32; 8];
+        let mut out_right = [0.0f32; 8];
+        graph
+            .process(&[&left, &right], &mut [&mut out_left, &mut out_right], 8)
+            .unwrap();
+        // Before the mock's latency, only the inverted downmix (-2 * 0.5)
+        assert_eq!((out_left[0], out_right[0]), (-1.0, -1.0));
+        assert_eq!((out_left[4], out_right[4]), (0.0, 2.0));
+        assert_eq!(graph.sample_position(), 8);
+
+        graph.set_gain(inverted, bus, 0.0).unwrap();
+        graph
+            .process(&[&left, &right], &mut [&mut out_left, &mut out_right], 8)
+            .unwrap();
+        assert_eq!((out_left[0], out_right[0]), (1.0, 3.0));
+
+        assert_eq!(
+            graph.remove(inverted).unwrap().unwrap().info().name,
+            "Invert"
+        );
+        assert_eq!(graph.connections().count(), 3);
+        assert!(graph.plugin(inverted).is_none());
+    }
+
+    #[test]
+    fn test_loops_are_refused() {
+        let mut graph = Graph::new(1, 1);
+        let a = graph.add_bus(1);
+        let b = graph.add_bus(1);
+        graph.connect(a, b, 1.0).unwrap();
+        assert!(graph.connect(b, a, 1.0).is_err());
+        assert!(graph.connect(a, a, 1.0).is_err());
+        assert!(graph.connect(graph.output(), a, 1.0).is_err());
+        assert!(graph.remove(graph.input()).is_err());
+
+        // Reconnecting changes the gain
+        graph.connect(a, b, 0.5).unwrap();
+        assert_eq!(graph.connections().collect::<Vec<_>>(), [(a, b, 0.5)]);
+        assert!(graph.disconnect(a, b));
+        graph.connect(b, a, 1.0).unwrap();
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut graph = Graph::new(2, 2);
+        let first = graph.add_plugin(Box::new(MockPlugin::new())).unwrap();
+        let removed = graph.add_bus(2);
+        let second = graph.add_plugin(Box::new(MockPlugin::new())).unwrap();
+        let bus = graph.add_bus(2);
+        graph.remove(removed).unwrap();
+        let (input, output) = (graph.input(), graph.output());
+        graph.connect(input, first, 1.0).unwrap();
+        graph.connect(first, bus, 0.5).unwrap();
+        graph.connect(input, second, 1.0).unwrap();
+        graph.connect(second, bus, 0.25).unwrap();
+        graph.connect(bus, output, 1.0).unwrap();
+        graph.initialize(48000.0, 8).unwrap();
+        graph
+            .plugin_mut(second)
+            .unwrap()
+            .set_parameter(0, 0.2)
+            .unwrap();
+        let state = graph.get_state().unwrap();
+
+        // A fresh graph loads every plugin and keeps the node IDs
+        let mut restored = Graph::new(2, 2);
+        restored.initialize(48000.0, 8).unwrap();
+        let mut loads = 0;
+        restored
+            .set_state(&state, |node| {
+                assert_eq!(node.unique_id, "mock-gain");
+                loads += 1;
+                Ok(Box::new(MockPlugin::new()))
+            })
+            .unwrap();
+        assert_eq!(loads, 2);
+        assert_eq!(
+            restored.connections().collect::<Vec<_>>(),
+            graph.connections().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            restored.plugin(second).unwrap().get_parameter(0).unwrap(),
+            0.2
+        );
+        assert!(restored.plugin(removed).is_none());
+        assert!(restored.add_bus(1) > bus);
+
+        let signal = [1.0f32; 8];
+        let (mut expected, mut actual) = ([0.0f32; 8], [0.0f32; 8]);
+        graph.process(&[&signal], &mut [&mut expected], 8).unwrap();
+        restored.process(&[&signal], &mut [&mut actual], 8).unwrap();
+        assert_eq!(actual, expected);
+
+        // Plugins already in place are reused
+        graph
+            .plugin_mut(second)
+            .unwrap()
+            .set_parameter(0, 0.9)
+            .unwrap();
+        graph
+            .set_state(&state, |_| Err(Error::Other("nothing to load".to_string())))
+            .unwrap();
+        assert_eq!(graph.plugin(second).unwrap().get_parameter(0).unwrap(), 0.2);
+    }
+
+    #[test]
+    fn test_restore_rejects_invalid_sessions() {
+        let mut graph = Graph::new(1, 1);
+        let load = |_: &NodeState| -> Result<BoxedPlugin> { Ok(Box::new(MockPlugin::new())) };
+
+        let mut looped = SessionDocument::new();
+        looped.add_bus("2", 1);
+        looped.add_bus("3", 1);
+        looped.connect("2", "3");
+        looped.connect("3", "2");
+        assert!(graph.restore_session(&looped, load).is_err());
+
+        let mut sidechain = SessionDocument::new();
+        sidechain.add_node("2", &MockPlugin::new()).unwrap();
+        sidechain.connect_sidechain(INPUT_NODE, "2");
+        assert!(graph.restore_session(&sidechain, load).is_err());
+
+        let mut unknown = SessionDocument::new();
+        unknown.connect("synth", OUTPUT_NODE);
+        assert!(graph.restore_session(&unknown, load).is_err());
+
+        // Nothing was changed by the failed attempts
+        assert_eq!(graph.nodes.len(), 2);
+        assert_eq!(graph.connections().count(), 0);
+    }
+
+    #[test]
+    fn test_listen_to_a_sidechain_feed() {
+        let mut graph = Graph::new(1, 1);
+        let compressor = graph.add_plugin(invert()).unwrap();
+        let key = graph.add_bus(1);
+
+        // The compressor's output is the mix; the key bus only feeds it
+        let (input, output) = (graph.input(), graph.output());
+        graph.connect(input, key, 0.5).unwrap();
+        graph.connect(key, compressor, 1.0).unwrap();
+        graph.connect(compressor, output, 1.0).unwrap();
+        graph.initialize(48000.0, 4).unwrap();
+        let listen = graph.listen(key).unwrap();
+
+        let signal = [1.0f32; 4];
+        let mut out = [0.0f32; 4];
+        graph.process(&[&signal], &mut [&mut out], 4).unwrap();
+        assert_eq!(out, [-0.5; 4]);
+
+        listen.set_listening(true);
+        graph.process(&[&signal], &mut [&mut out], 4).unwrap();
+        assert_eq!(out[3], 0.5);
+        graph.process(&[&signal], &mut [&mut out], 4).unwrap();
+        assert_eq!(out, [0.5; 4]);
+
+        graph.clear_listen();
+        graph.process(&[&signal], &mut [&mut out], 4).unwrap();
+        assert_eq!(out, [-0.5; 4]);
+        assert!(graph.listen(NodeId(99)).is_err());
+    }
+}
@@ -0,0 +1,370 @@
+//! Audio-thread panic handling
+//!
+//! A panic that unwinds out of an audio callback (cpal, JACK, CoreAudio) crosses
+//! an FFI boundary and aborts the process. The helpers here catch panics at that
+//! boundary instead: the block is replaced with silence and a
+//! [`HostEvent::Error`] is emitted, so one bad block costs a click rather than
+//! the whole session. [`PanicPolicy::Propagate`] opts out for those who prefer
+//! to fail fast.
+//!
+//! # Examples
+//!
+//! Wrap a plugin so every `process()` call is guarded:
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::guard::PanicSafe;
+//! # fn example(plugin: impl PluginInstance) -> Result<()> {
+//! let mut plugin = PanicSafe::new(plugin);
+//! plugin.initialize(48000.0, 512)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Or guard a whole callback, including host code around the plugin:
+//!
+//! ```no_run
+//! # use rack::guard::{catch_audio_panic, PanicPolicy};
+//! # fn render(data: &mut [f32]) {}
+//! let callback = move |data: &mut [f32]| {
+//!     if catch_audio_panic(PanicPolicy::Silence, None, || render(data)).is_none() {
+//!         data.fill(0.0);
+//!     }
+//! };
+//! ```
+
+use crate::events::{self, HostEvent};
+use crate::meter::MeterReading;
+use crate::node::BlockContext;
+use crate::quirks::Quirks;
+use crate::{CurrentPreset, Error, MidiEvent, ParameterChange, ParameterInfo, PluginInfo, PluginInstance, PresetInfo, Result};
+use std::panic::{self, AssertUnwindSafe};
+
+/// What to do when a panic is caught on the audio thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PanicPolicy {
+    /// Output silence for the block and emit an error event
+    #[default]
+    Silence,
+
+    /// Let the panic continue unwinding (fail fast)
+    Propagate,
+}
+
+/// Run `f`, catching any panic according to `policy`
+///
+/// Returns `None` if `f` panicked and the panic was caught; the caller should
+/// output silence for the block. A [`HostEvent::Error`] carrying
+/// [`Error::Panic`] is emitted for every caught panic.
+///
+/// # Arguments
+///
+/// * `policy` - Whether to catch the panic or let it propagate
+/// * `info` - The plugin being processed, for the error event
+/// * `f` - The code to run
+pub fn catch_audio_panic<R>(
+    policy: PanicPolicy,
+    info: Option<&PluginInfo>,
+    f: impl FnOnce() -> R,
+) -> Option<R> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(value) => Some(value),
+        Err(payload) => {
+            handle_panic(policy, info, payload);
+            None
+        }
+    }
+}
+
+/// Re-raise or report a caught panic
+fn handle_panic(
+    policy: PanicPolicy,
+    info: Option<&PluginInfo>,
+    payload: Box<dyn std::any::Any + Send>,
+) {
+    if policy == PanicPolicy::Propagate {
+        panic::resume_unwind(payload);
+    }
+    let error = Error::Panic(panic_message(payload.as_ref()));
+    events::emit(HostEvent::Error {
+        info,
+        error: &error,
+    });
+}
+
+/// Extract the message from a panic payload
+pub(crate) fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Plugin wrapper that guards `process()` against panics
+///
+/// When the wrapped plugin's `process()` panics, the outputs are silenced, an
+/// error event is emitted and `process()` returns `Ok(())`, so the audio
+/// callback carries on. All other methods are forwarded unchanged.
+pub struct PanicSafe<P> {
+    plugin: P,
+
+    /// How panics in `process()` are handled
+    pub policy: PanicPolicy,
+}
+
+impl<P: PluginInstance> PanicSafe<P> {
+    /// Wrap a plugin with the default [`PanicPolicy::Silence`] policy
+    pub fn new(plugin: P) -> Self {
+        Self {
+            plugin,
+            policy: PanicPolicy::default(),
+        }
+    }
+
+    /// The wrapped plugin
+    pub fn inner(&self) -> &P {
+        &self.plugin
+    }
+
+    /// The wrapped plugin, mutably
+    pub fn inner_mut(&mut self) -> &mut P {
+        &mut self.plugin
+    }
+
+    /// Unwrap the plugin
+    pub fn into_inner(self) -> P {
+        self.plugin
+    }
+
+    /// Run `process`, silencing the block if it panics
+    fn guarded(
+        &mut self,
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        process: impl FnOnce(&mut P, &mut [&mut [f32]]) -> Result<()>,
+    ) -> Result<()> {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| process(&mut self.plugin, outputs)));
+        match result {
+            Ok(result) => result,
+            Err(payload) => {
+                handle_panic(self.policy, Some(self.plugin.info()), payload);
+                for output in outputs.iter_mut() {
+                    let frames = num_frames.min(output.len());
+                    output[..frames].fill(0.0);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<P: PluginInstance> PluginInstance for PanicSafe<P> {
+    fn initialize(&mut self, sample_rate: f64, max_block_size: usize) -> Result<()> {
+        self.plugin.initialize(sample_rate, max_block_size)
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.plugin.reset()
+    }
+
+    fn process(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        self.guarded(outputs, num_frames, |plugin, outputs| {
+            plugin.process(inputs, outputs, num_frames)
+        })
+    }
+
+    fn process_with_context(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        context: &BlockContext,
+    ) -> Result<()> {
+        self.guarded(outputs, context.num_frames, |plugin, outputs| {
+            plugin.process_with_context(inputs, outputs, context)
+        })
+    }
+
+    fn process_with_events(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        changes: &[ParameterChange],
+        midi: &[MidiEvent],
+    ) -> Result<()> {
+        self.guarded(outputs, num_frames, |plugin, outputs| {
+            plugin.process_with_events(inputs, outputs, num_frames, changes, midi)
+        })
+    }
+
+    fn sample_position(&self) -> u64 {
+        self.plugin.sample_position()
+    }
+
+    fn set_sample_position(&mut self, position: u64) -> Result<()> {
+        self.plugin.set_sample_position(position)
+    }
+
+    fn flush_events(&mut self) -> Result<()> {
+        self.plugin.flush_events()
+    }
+
+    fn parameter_count(&self) -> usize {
+        self.plugin.parameter_count()
+    }
+
+    fn parameter_info(&self, index: usize) -> Result<ParameterInfo> {
+        self.plugin.parameter_info(index)
+    }
+
+    fn get_parameter(&self, index: usize) -> Result<f32> {
+        self.plugin.get_parameter(index)
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) -> Result<()> {
+        self.plugin.set_parameter(index, value)
+    }
+
+    fn send_midi(&mut self, events: &[MidiEvent]) -> Result<()> {
+        self.plugin.send_midi(events)
+    }
+
+    fn preset_count(&self) -> Result<usize> {
+        self.plugin.preset_count()
+    }
+
+    fn preset_info(&self, index: usize) -> Result<PresetInfo> {
+        self.plugin.preset_info(index)
+    }
+
+    fn load_preset(&mut self, preset_number: i32) -> Result<()> {
+        self.plugin.load_preset(preset_number)
+    }
+
+    fn current_preset(&self) -> Result<Option<CurrentPreset>> {
+        self.plugin.current_preset()
+    }
+
+    fn get_state(&self) -> Result<Vec<u8>> {
+        self.plugin.get_state()
+    }
+
+    fn set_state(&mut self, data: &[u8]) -> Result<()> {
+        self.plugin.set_state(data)
+    }
+
+    fn get_state_to_writer(&self, writer: &mut dyn std::io::Write) -> Result<()> {
+        self.plugin.get_state_to_writer(writer)
+    }
+
+    fn set_state_from_reader(&mut self, reader: &mut dyn std::io::Read) -> Result<()> {
+        self.plugin.set_state_from_reader(reader)
+    }
+
+    fn set_state_from_file(&mut self, path: &std::path::Path) -> Result<()> {
+        self.plugin.set_state_from_file(path)
+    }
+
+    fn take_state_dirty(&self) -> bool {
+        self.plugin.take_state_dirty()
+    }
+
+    fn info(&self) -> &PluginInfo {
+        self.plugin.info()
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.plugin.is_initialized()
+    }
+
+    fn input_channels(&self) -> usize {
+        self.plugin.input_channels()
+    }
+
+    fn output_channels(&self) -> usize {
+        self.plugin.output_channels()
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.plugin.latency_samples()
+    }
+
+    fn tail_samples(&self) -> usize {
+        self.plugin.tail_samples()
+    }
+
+    fn tail_seconds(&self) -> f64 {
+        self.plugin.tail_seconds()
+    }
+
+    fn set_bypass(&mut self, bypassed: bool) -> Result<()> {
+        self.plugin.set_bypass(bypassed)
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.plugin.is_bypassed()
+    }
+
+    fn quirks(&self) -> Quirks {
+        self.plugin.quirks()
+    }
+
+    fn plugin_meter(&self) -> Option<Vec<MeterReading>> {
+        self.plugin.plugin_meter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockPlugin;
+
+    #[test]
+    fn test_catch_audio_panic() {
+        assert_eq!(
+            catch_audio_panic(PanicPolicy::Silence, None, || 42),
+            Some(42)
+        );
+        assert_eq!(
+            catch_audio_panic(PanicPolicy::Silence, None, || -> i32 { panic!("boom") }),
+            None
+        );
+    }
+
+    #[test]
+    fn test_propagate_policy() {
+        let result = panic::catch_unwind(|| {
+            catch_audio_panic(PanicPolicy::Propagate, None, || -> i32 { panic!("boom") })
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_panic_safe_silences_block() {
+        let mut mock = MockPlugin::new();
+        mock.panic_blocks = vec![1];
+        let mut plugin = PanicSafe::new(mock);
+        plugin.initialize(48000.0, 64).unwrap();
+
+        let input = vec![1.0f32; 64];
+        let mut left = vec![0.0f32; 64];
+        let mut right = vec![0.0f32; 64];
+
+        for block in 0..3 {
+            left.fill(0.5);
+            plugin
+                .process(&[&input, &input], &mut [&mut left, &mut right], 64)
+                .unwrap();
+            let expected = if block == 1 { 0.0 } else { 1.0 };
+            assert!(left.iter().all(|&s| s == expected), "block {}", block);
+        }
+    }
+}
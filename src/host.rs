@@ -0,0 +1,141 @@
+//! Host identity reported to plugins
+//!
+//! Plugins can ask who is hosting them: VST3 plugins through
+//! `IHostApplication::getName()`, AudioUnits through
+//! `kAudioUnitProperty_AUHostIdentifier`. Some change their behavior based on
+//! the answer (enabling workarounds for a particular DAW, or refusing to run in
+//! unknown hosts), so the embedding application should identify itself rather
+//! than appear as "rack".
+//!
+//! The [`HostInfo`] set with [`set_host_info()`] is reported to every plugin
+//! instance created afterwards. Its capabilities reach plugins in the formats
+//! that ask hosts about features: VST2 plugins get a yes to
+//! `audioMasterCanDo` for each of them, and LV2 plugins receive the ones that
+//! are URIs as features without data (the form flag features such as
+//! `lv2core#isLive` take). VST3, AudioUnit and CLAP plugins have no such
+//! query; engines and wrappers can still check the list with
+//! [`HostInfo::has_capability()`].
+//!
+//! # Examples
+//!
+//! ```
+//! use rack::host::{self, HostInfo};
+//!
+//! host::set_host_info(
+//!     HostInfo::new("My DAW", "2.1.0")
+//!         .with_capability("offline-render")
+//!         .with_capability("sample-accurate-automation"),
+//! );
+//!
+//! assert!(host::host_info().has_capability("offline-render"));
+//! # host::reset_host_info();
+//! ```
+
+use std::sync::RwLock;
+
+/// Name, version and capabilities of the hosting application
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostInfo {
+    /// Application name reported to plugins
+    pub name: String,
+
+    /// Application version, as "major.minor.bugfix"
+    ///
+    /// Only AudioUnits receive it; `IHostApplication` has no version.
+    pub version: String,
+
+    /// Features the host supports (free-form identifiers)
+    ///
+    /// VST2 `canDo` strings and LV2 feature URIs are reported to plugins of
+    /// those formats.
+    pub capabilities: Vec<String>,
+}
+
+impl HostInfo {
+    /// Create host info with no capabilities
+    pub fn new(name: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: version.into(),
+            capabilities: Vec::new(),
+        }
+    }
+
+    /// Add a capability
+    pub fn with_capability(mut self, capability: impl Into<String>) -> Self {
+        self.capabilities.push(capability.into());
+        self
+    }
+
+    /// Check whether the host declared a capability
+    pub fn has_capability(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+
+    /// The version packed the way AudioUnits expect it
+    ///
+    /// `major << 16 | minor << 8 | bugfix`, with missing or unparsable
+    /// components treated as 0 and minor/bugfix capped at 255.
+    pub fn version_number(&self) -> u32 {
+        let mut parts = self
+            .version
+            .split('.')
+            .map(|p| p.trim().parse::<u32>().unwrap_or(0));
+        let major = parts.next().unwrap_or(0).min(0xFFFF);
+        let minor = parts.next().unwrap_or(0).min(0xFF);
+        let bugfix = parts.next().unwrap_or(0).min(0xFF);
+        (major << 16) | (minor << 8) | bugfix
+    }
+}
+
+impl Default for HostInfo {
+    /// Identifies the host as rack itself
+    fn default() -> Self {
+        Self::new("rack", env!("CARGO_PKG_VERSION"))
+    }
+}
+
+/// The configured host info (`None` until set)
+static HOST_INFO: RwLock<Option<HostInfo>> = RwLock::new(None);
+
+/// Set the host info reported to plugins created from now on
+pub fn set_host_info(info: HostInfo) {
+    *HOST_INFO.write().unwrap_or_else(|e| e.into_inner()) = Some(info);
+}
+
+/// Go back to reporting the default host info
+pub fn reset_host_info() {
+    *HOST_INFO.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// The host info currently reported to plugins
+pub fn host_info() -> HostInfo {
+    HOST_INFO
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_number() {
+        assert_eq!(HostInfo::new("Host", "2.1.3").version_number(), 0x0002_0103);
+        assert_eq!(HostInfo::new("Host", "10").version_number(), 0x000A_0000);
+        assert_eq!(
+            HostInfo::new("Host", "1.x.700").version_number(),
+            0x0001_00FF
+        );
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let info = HostInfo::default().with_capability("offline-render");
+        assert_eq!(info.name, "rack");
+        assert!(info.has_capability("offline-render"));
+        assert!(!info.has_capability("surround"));
+    }
+}
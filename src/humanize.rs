@@ -0,0 +1,237 @@
+//! Velocity curves and humanization for generated MIDI
+//!
+//! Notes produced by code (sequencers, arpeggiators, preset auditioning) all
+//! land exactly on the grid with the same velocity, which sounds mechanical
+//! and often doesn't match how a patch was voiced. An
+//! [`EventPort`](crate::port::EventPort) can reshape note velocities with a
+//! [`VelocityCurve`] and add small random timing and velocity variations with
+//! [`Humanize`] as events are queued.
+//!
+//! # Examples
+//!
+//! ```
+//! use rack::humanize::{Humanize, VelocityCurve};
+//! use rack::port::EventPort;
+//! use rack::MidiEvent;
+//!
+//! let mut port = EventPort::new();
+//! port.velocity_curve = VelocityCurve::Power(0.5); // Soft notes come out louder
+//! port.humanize = Some(Humanize::with_seed(96, 8, 42)); // ±2ms at 48kHz, ±8 velocity
+//!
+//! port.extend(MidiEvent::note(60, 40, 0, 0, 24000));
+//! ```
+
+use crate::{Error, MidiEvent, MidiEventKind, Result};
+
+/// Mapping applied to Note On velocities
+///
+/// Velocity 0 (a Note Off in disguise) is never changed, and every curve maps
+/// sounding notes into `1..=127`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum VelocityCurve {
+    /// Velocities pass through unchanged
+    #[default]
+    Linear,
+
+    /// `127 * (v / 127) ^ exponent`: below 1.0 boosts soft notes, above 1.0
+    /// makes them softer
+    ///
+    /// Negative exponents act as 0. A NaN or infinite exponent leaves
+    /// velocities unchanged; [`VelocityCurve::power()`] rejects them up front.
+    Power(f32),
+
+    /// Every note gets this velocity
+    Fixed(u8),
+
+    /// Velocities are scaled linearly into `min..=max`
+    Range {
+        /// Velocity for input velocity 1
+        min: u8,
+        /// Velocity for input velocity 127
+        max: u8,
+    },
+}
+
+impl VelocityCurve {
+    /// Create a [`Power`](Self::Power) curve
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `exponent` is NaN or infinite
+    pub fn power(exponent: f32) -> Result<Self> {
+        if !exponent.is_finite() {
+            return Err(Error::Other(format!(
+                "Invalid velocity curve exponent: {}",
+                exponent
+            )));
+        }
+        Ok(VelocityCurve::Power(exponent))
+    }
+
+    /// Map a velocity through the curve
+    pub fn apply(&self, velocity: u8) -> u8 {
+        if velocity == 0 {
+            return 0;
+        }
+        let velocity = velocity.min(127);
+        let mapped = match *self {
+            VelocityCurve::Linear => velocity as f32,
+            VelocityCurve::Power(exponent) if !exponent.is_finite() => velocity as f32,
+            VelocityCurve::Power(exponent) => {
+                127.0 * (velocity as f32 / 127.0).powf(exponent.max(0.0))
+            }
+            VelocityCurve::Fixed(fixed) => fixed as f32,
+            VelocityCurve::Range { min, max } => {
+                let t = (velocity - 1) as f32 / 126.0;
+                min as f32 + t * (max as f32 - min as f32)
+            }
+        };
+        mapped.round().clamp(1.0, 127.0) as u8
+    }
+}
+
+/// Random timing and velocity variation for Note On events
+///
+/// Each Note On is moved by up to `timing` samples either way (never before
+/// offset 0) and its velocity changed by up to `velocity` either way. Note Offs
+/// are not moved, so keep `timing` well below the shortest note length.
+///
+/// The generator is seedable so generated material can be reproduced.
+#[derive(Debug, Clone)]
+pub struct Humanize {
+    /// Maximum timing offset in samples
+    pub timing: u32,
+
+    /// Maximum velocity change
+    pub velocity: u8,
+
+    rng: SplitMix64,
+}
+
+impl Humanize {
+    /// Create with a seed taken from the system clock
+    pub fn new(timing: u32, velocity: u8) -> Self {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Self::with_seed(timing, velocity, seed)
+    }
+
+    /// Create with a fixed seed, for reproducible results
+    pub fn with_seed(timing: u32, velocity: u8, seed: u64) -> Self {
+        Self {
+            timing,
+            velocity,
+            rng: SplitMix64(seed),
+        }
+    }
+
+    /// Apply random variation to an event
+    ///
+    /// Only Note On events with a non-zero velocity are changed.
+    pub fn apply(&mut self, event: &mut MidiEvent) {
+        let MidiEventKind::NoteOn { velocity, .. } = &mut event.kind else {
+            return;
+        };
+        if *velocity == 0 {
+            return;
+        }
+
+        let shift = self.rng.jitter(self.timing as i64);
+        event.sample_offset = (event.sample_offset as i64 + shift).clamp(0, u32::MAX as i64) as u32;
+
+        let change = self.rng.jitter(self.velocity as i64);
+        *velocity = (*velocity as i64 + change).clamp(1, 127) as u8;
+    }
+}
+
+/// Small, fast, seedable PRNG (SplitMix64)
+#[derive(Debug, Clone)]
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform integer in `-max..=max`
+    fn jitter(&mut self, max: i64) -> i64 {
+        if max <= 0 {
+            return 0;
+        }
+        let span = (2 * max + 1) as u64;
+        (self.next_u64() % span) as i64 - max
+    }
+}
+
+/// Apply a velocity curve to a Note On event
+pub(crate) fn apply_curve(curve: &VelocityCurve, event: &mut MidiEvent) {
+    if let MidiEventKind::NoteOn { velocity, .. } = &mut event.kind {
+        *velocity = curve.apply(*velocity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_velocity_curves() {
+        assert_eq!(VelocityCurve::Linear.apply(64), 64);
+        assert_eq!(VelocityCurve::Power(0.5).apply(32), 64);
+        assert_eq!(VelocityCurve::Power(2.0).apply(127), 127);
+        assert_eq!(VelocityCurve::Power(8.0).apply(1), 1);
+        assert_eq!(VelocityCurve::Power(-1.0).apply(1), 127);
+        assert_eq!(VelocityCurve::Power(0.5).apply(200), 127);
+        assert_eq!(VelocityCurve::Fixed(100).apply(5), 100);
+        assert_eq!(VelocityCurve::Fixed(0).apply(5), 1);
+        assert_eq!(VelocityCurve::Range { min: 40, max: 100 }.apply(1), 40);
+        assert_eq!(VelocityCurve::Range { min: 40, max: 100 }.apply(127), 100);
+
+        // Velocity 0 means Note Off and is left alone
+        assert_eq!(VelocityCurve::Fixed(100).apply(0), 0);
+    }
+
+    #[test]
+    fn test_non_finite_power() {
+        assert_eq!(
+            VelocityCurve::power(0.5).unwrap(),
+            VelocityCurve::Power(0.5)
+        );
+        for exponent in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            assert!(VelocityCurve::power(exponent).is_err());
+            for velocity in [1, 64, 127] {
+                assert_eq!(VelocityCurve::Power(exponent).apply(velocity), velocity);
+            }
+        }
+    }
+
+    #[test]
+    fn test_humanize_is_bounded_and_reproducible() {
+        let mut a = Humanize::with_seed(10, 5, 7);
+        let mut b = Humanize::with_seed(10, 5, 7);
+
+        for _ in 0..1000 {
+            let mut event_a = MidiEvent::note_on(60, 100, 0, 100);
+            let mut event_b = event_a;
+            a.apply(&mut event_a);
+            b.apply(&mut event_b);
+            assert_eq!(event_a, event_b);
+
+            assert!((90..=110).contains(&event_a.sample_offset));
+            let MidiEventKind::NoteOn { velocity, .. } = event_a.kind else {
+                unreachable!()
+            };
+            assert!((95..=105).contains(&velocity));
+        }
+
+        let mut off = MidiEvent::note_off(60, 64, 0, 100);
+        a.apply(&mut off);
+        assert_eq!(off, MidiEvent::note_off(60, 64, 0, 100));
+    }
+}
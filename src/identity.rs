@@ -0,0 +1,267 @@
+//! Format-independent plugin identity
+//!
+//! A [`PluginIdentity`] names a plugin product by vendor and product name,
+//! together with the unique ID it has in each format it was seen in and,
+//! optionally, a hash of its bundle's contents. Session documents and caches
+//! that store an identity rather than one format's ID keep working when the
+//! product turns up in another format (an AU session opened on Windows finds
+//! the VST3) or is reinstalled somewhere else:
+//! [`load_best()`](crate::scan::load_best) finds every installed entry matching
+//! the identity and loads the one in the most preferred format.
+//!
+//! Identities have a one-line text form for storing in documents and
+//! settings:
+//!
+//! ```text
+//! FabFilter/Pro-Q 3;AU=aufx:FQ3p:FabF;VST3=72C4DB717A4D459AB97E51745D84B39D;hash=5e1b08c3a2f4d617
+//! ```
+//!
+//! Vendor and product come first; `/`, `;`, `=` and `%` in them are
+//! percent-encoded.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::identity::PluginIdentity;
+//! # fn example(scanner: &impl PluginScanner) -> Result<()> {
+//! let identity: PluginIdentity = "FabFilter/Pro-Q 3;VST3=72C4DB717A4D459AB97E51745D84B39D".parse()?;
+//! let mut eq = scanner.load_best(&identity)?;
+//! eq.initialize(48000.0, 512)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::cache::parse_format;
+use crate::scan::{normalize_manufacturer, normalize_name};
+use crate::{Error, PluginFormat, PluginInfo};
+use std::fmt;
+use std::str::FromStr;
+
+/// Identifies a plugin product across formats
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PluginIdentity {
+    /// Manufacturer name
+    pub vendor: String,
+
+    /// Product name
+    pub product: String,
+
+    /// Unique ID in each format the product was seen in, one per format
+    pub ids: Vec<(PluginFormat, String)>,
+
+    /// Hash of the bundle's file contents
+    /// (see [`content_hash()`](crate::cache::content_hash)), identifying the
+    /// exact build wherever it is installed
+    pub content_hash: Option<u64>,
+}
+
+impl PluginIdentity {
+    /// Identify a product by vendor and product name
+    pub fn new(vendor: impl Into<String>, product: impl Into<String>) -> Self {
+        Self {
+            vendor: vendor.into(),
+            product: product.into(),
+            ids: Vec::new(),
+            content_hash: None,
+        }
+    }
+
+    /// Also match entries with this unique ID in `format`
+    ///
+    /// Replaces any ID already known for the format.
+    pub fn with_id(mut self, format: PluginFormat, unique_id: impl Into<String>) -> Self {
+        self.set_id(format, unique_id);
+        self
+    }
+
+    /// Set the bundle content hash
+    pub fn with_content_hash(mut self, hash: u64) -> Self {
+        self.content_hash = Some(hash);
+        self
+    }
+
+    /// The identity of a scanned plugin
+    ///
+    /// The content hash isn't computed, as it reads the whole bundle.
+    pub fn from_info(info: &PluginInfo) -> Self {
+        Self::new(&info.manufacturer, &info.name).with_id(info.format, &info.unique_id)
+    }
+
+    /// The unique ID in `format`, if known
+    pub fn id(&self, format: PluginFormat) -> Option<&str> {
+        self.ids
+            .iter()
+            .find(|(f, _)| *f == format)
+            .map(|(_, id)| id.as_str())
+    }
+
+    /// Record the unique ID in `format`
+    pub fn set_id(&mut self, format: PluginFormat, unique_id: impl Into<String>) {
+        let unique_id = unique_id.into();
+        match self.ids.iter_mut().find(|(f, _)| *f == format) {
+            Some(entry) => entry.1 = unique_id,
+            None => self.ids.push((format, unique_id)),
+        }
+    }
+
+    /// Add the IDs `other` knows and this identity doesn't
+    ///
+    /// Used to accumulate what is known about a product as it is seen in
+    /// more formats.
+    pub fn merge(&mut self, other: &PluginIdentity) {
+        for (format, id) in &other.ids {
+            if self.id(*format).is_none() {
+                self.ids.push((*format, id.clone()));
+            }
+        }
+        if self.content_hash.is_none() {
+            self.content_hash = other.content_hash;
+        }
+    }
+
+    /// Whether a scanned plugin has this identity's unique ID for its format
+    pub fn matches_id(&self, info: &PluginInfo) -> bool {
+        self.id(info.format) == Some(info.unique_id.as_str())
+    }
+
+    /// Whether a scanned plugin is this product, in any format
+    ///
+    /// Entries match by their format's unique ID, or by vendor and product
+    /// compared the way [`group_by_product()`](crate::scan::group_by_product)
+    /// does (ignoring case, punctuation and company suffixes).
+    pub fn matches(&self, info: &PluginInfo) -> bool {
+        self.matches_id(info) || self.matches_name(&info.manufacturer, &info.name)
+    }
+
+    /// Whether a vendor and product name are this product's
+    pub fn matches_name(&self, vendor: &str, product: &str) -> bool {
+        normalize_manufacturer(&self.vendor) == normalize_manufacturer(vendor)
+            && normalize_name(&self.product) == normalize_name(product)
+    }
+}
+
+impl From<&PluginInfo> for PluginIdentity {
+    fn from(info: &PluginInfo) -> Self {
+        Self::from_info(info)
+    }
+}
+
+impl fmt::Display for PluginIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", escape(&self.vendor), escape(&self.product))?;
+        for (format, id) in &self.ids {
+            write!(f, ";{}={}", format, escape(id))?;
+        }
+        if let Some(hash) = self.content_hash {
+            write!(f, ";hash={:016x}", hash)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PluginIdentity {
+    type Err = Error;
+
+    /// Parse the form written by `Display`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidFormat(format!("Invalid plugin identity '{}'", s));
+        let mut fields = s.split(';');
+        let (vendor, product) = fields
+            .next()
+            .and_then(|names| names.split_once('/'))
+            .ok_or_else(invalid)?;
+        let mut identity = Self::new(unescape(vendor), unescape(product));
+        for field in fields {
+            let (key, value) = field.split_once('=').ok_or_else(invalid)?;
+            if key == "hash" {
+                let hash = u64::from_str_radix(value, 16).map_err(|_| invalid())?;
+                identity.content_hash = Some(hash);
+            } else {
+                let format = parse_format(key).ok_or_else(invalid)?;
+                identity.set_id(format, unescape(value));
+            }
+        }
+        Ok(identity)
+    }
+}
+
+/// Percent-encode the characters that separate fields
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '%' | '/' | ';' | '=' => out.push_str(&format!("%{:02X}", c as u32)),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Reverse [`escape()`]
+fn unescape(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok());
+        match hex.map(|h| u8::from_str_radix(h, 16)) {
+            Some(Ok(byte)) if bytes[i] == b'%' => {
+                out.push(byte);
+                i += 3;
+            }
+            _ => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_round_trip() {
+        let identity = PluginIdentity::new("AC/DC Audio", "Gain=1;2 100%")
+            .with_id(PluginFormat::AudioUnit, "aufx:Gain:ACDC")
+            .with_id(PluginFormat::Vst3, "0123456789ABCDEF0123456789ABCDEF")
+            .with_content_hash(0x5e1b_08c3_a2f4_d617);
+        let text = identity.to_string();
+        assert!(text.starts_with("AC%2FDC Audio/Gain%3D1%3B2 100%25;AU=aufx:Gain:ACDC;"));
+        assert_eq!(text.parse::<PluginIdentity>().unwrap(), identity);
+
+        assert!("no product".parse::<PluginIdentity>().is_err());
+        assert!("A/B;CLAP9=x".parse::<PluginIdentity>().is_err());
+    }
+
+    #[test]
+    fn test_identity_matches_across_formats() {
+        let au = PluginInfo::new(
+            "Pro-Q 3".to_string(),
+            "FabFilter".to_string(),
+            1,
+            crate::PluginType::Effect,
+            Default::default(),
+            "aufx:FQ3p:FabF".to_string(),
+        )
+        .with_format(PluginFormat::AudioUnit);
+        let mut identity = PluginIdentity::from_info(&au);
+
+        // Renamed in the VST3 build, but known by its ID
+        let vst3 = PluginInfo {
+            name: "FabFilter Pro-Q 3".to_string(),
+            unique_id: "72C4DB71".to_string(),
+            format: PluginFormat::Vst3,
+            ..au.clone()
+        };
+        assert!(!identity.matches(&vst3));
+        identity.merge(&PluginIdentity::from_info(&vst3));
+        assert!(identity.matches(&vst3));
+        assert_eq!(identity.id(PluginFormat::AudioUnit), Some("aufx:FQ3p:FabF"));
+    }
+}
@@ -0,0 +1,133 @@
+//! Reports on helper processes that died
+
+use crate::PluginInfo;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+
+/// Environment variable telling a helper where crash dumps go
+///
+/// Set when the host configured a directory with
+/// [`IsolationHost::minidump_dir()`](super::IsolationHost::minidump_dir).
+/// Rack doesn't write dumps itself: a crash handler installed in the helper
+/// (Crashpad, Breakpad, ...) should write `<pid>.dmp` into the directory,
+/// where [`CrashReport::minidump`] picks it up.
+pub const MINIDUMP_DIR_ENV: &str = "RACK_MINIDUMP_DIR";
+
+/// What is known about a helper process that died
+///
+/// Emitted as [`HostEvent::PluginCrashed`](crate::events::HostEvent::PluginCrashed)
+/// and kept by the plugins that ran in the helper (see
+/// [`IsolatedPlugin::crash_report()`](super::IsolatedPlugin::crash_report)),
+/// e.g. to offer to disable the plugin that crashed.
+#[derive(Debug, Clone)]
+pub struct CrashReport {
+    /// Group the helper served
+    pub group: String,
+
+    /// Process ID of the helper, if it was a separate process
+    pub pid: Option<u32>,
+
+    /// Signal that terminated the helper (Unix)
+    pub signal: Option<i32>,
+
+    /// Exit code, if the helper exited rather than being killed by a signal;
+    /// on Windows, the exception code of a crash
+    pub exit_code: Option<i32>,
+
+    /// The plugin whose call was in flight, if any
+    ///
+    /// Most likely the one that crashed, though a plugin sharing the helper
+    /// may have corrupted memory earlier.
+    pub plugin: Option<PluginInfo>,
+
+    /// The call in flight (`"process"`, `"set_state"`, ...), if any
+    pub operation: Option<&'static str>,
+
+    /// Every plugin that was loaded in the helper, all of which are gone
+    pub plugins: Vec<PluginInfo>,
+
+    /// Crash dump written by the helper's crash handler, if any
+    pub minidump: Option<PathBuf>,
+}
+
+impl CrashReport {
+    pub(crate) fn new(group: &str, pid: Option<u32>, status: Option<ExitStatus>) -> Self {
+        let (signal, exit_code) = status.map_or((None, None), exit_details);
+        Self {
+            group: group.to_string(),
+            pid,
+            signal,
+            exit_code,
+            plugin: None,
+            operation: None,
+            plugins: Vec::new(),
+            minidump: None,
+        }
+    }
+
+    /// Look for the dump the helper's crash handler wrote into `dir`
+    pub(crate) fn find_minidump(&mut self, dir: &Path) {
+        if let Some(pid) = self.pid {
+            let path = dir.join(format!("{}.dmp", pid));
+            if path.is_file() {
+                self.minidump = Some(path);
+            }
+        }
+    }
+
+    /// Conventional name of [`signal`](Self::signal) (e.g. `"SIGSEGV"`)
+    pub fn signal_name(&self) -> Option<&'static str> {
+        self.signal.and_then(signal_name)
+    }
+}
+
+impl fmt::Display for CrashReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.plugin {
+            Some(plugin) => write!(f, "{} crashed", plugin.name)?,
+            None => write!(f, "Isolation helper '{}' crashed", self.group)?,
+        }
+        if let Some(operation) = self.operation {
+            write!(f, " during {}", operation)?;
+        }
+        match (self.signal, self.signal_name(), self.exit_code) {
+            (Some(_), Some(name), _) => write!(f, " ({})", name),
+            (Some(signal), None, _) => write!(f, " (signal {})", signal),
+            (None, _, Some(code)) if cfg!(windows) && code < 0 => {
+                write!(f, " (exception {:#010x})", code)
+            }
+            (None, _, Some(code)) => write!(f, " (exit code {})", code),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn exit_details(status: ExitStatus) -> (Option<i32>, Option<i32>) {
+    use std::os::unix::process::ExitStatusExt;
+    (status.signal(), status.code())
+}
+
+#[cfg(not(unix))]
+fn exit_details(status: ExitStatus) -> (Option<i32>, Option<i32>) {
+    (None, status.code())
+}
+
+fn signal_name(signal: i32) -> Option<&'static str> {
+    Some(match signal {
+        4 => "SIGILL",
+        5 => "SIGTRAP",
+        6 => "SIGABRT",
+        8 => "SIGFPE",
+        9 => "SIGKILL",
+        11 => "SIGSEGV",
+        13 => "SIGPIPE",
+        15 => "SIGTERM",
+        #[cfg(target_os = "linux")]
+        7 => "SIGBUS",
+        #[cfg(not(target_os = "linux"))]
+        10 => "SIGBUS",
+        _ => return None,
+    })
+}
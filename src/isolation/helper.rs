@@ -0,0 +1,387 @@
+//! The helper side: loading plugins and answering the host's requests
+
+use super::protocol::{self, Op};
+use super::shm::{self, Ring};
+use crate::node::BlockContext;
+use crate::session::Reader;
+use crate::{Error, MidiEvent, ParameterChange, PluginInstance, PluginScanner, Result};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+
+/// Serve requests from a host until it disconnects
+///
+/// Loads plugins with `scanner` as the host asks for them. Panics in a plugin
+/// are caught and reported to the host as [`Error::Panic`]; crashes end the
+/// helper, which the host sees as the connection closing.
+///
+/// [`run_helper_if_requested()`](super::run_helper_if_requested) calls this
+/// over the process's standard input and output. Hosts with their own helper
+/// executable or transport can call it directly.
+///
+/// Plugins whose host attaches a [shared audio ring](super::shm) are
+/// processed on a worker thread of their own.
+///
+/// # Errors
+///
+/// Returns an error if the connection fails or the host sends a malformed
+/// message. A clean disconnect returns `Ok(())`.
+pub fn serve<S>(scanner: &S, reader: &mut dyn Read, writer: &mut dyn Write) -> Result<()>
+where
+    S: PluginScanner,
+    S::Plugin: 'static,
+{
+    protocol::write_hello(writer)?;
+
+    let mut server = Server {
+        scanner,
+        plugins: HashMap::new(),
+        workers: HashMap::new(),
+        next_id: 1,
+        inputs: Vec::new(),
+        outputs: Vec::new(),
+        midi: Vec::new(),
+        changes: Vec::new(),
+    };
+    let mut request = Vec::new();
+    let mut response = Vec::new();
+
+    while protocol::read_message(reader, &mut request)? {
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            server.handle(&request, &mut response)
+        }))
+        .unwrap_or_else(|panic| Err(Error::Panic(crate::guard::panic_message(&*panic))));
+        if let Err(error) = result {
+            protocol::err(&mut response, &error);
+        }
+        protocol::write_message(writer, &response)?;
+    }
+    Ok(())
+}
+
+struct Server<'a, S: PluginScanner> {
+    scanner: &'a S,
+    /// Shared with the plugins' audio workers
+    plugins: HashMap<u32, Arc<Mutex<S::Plugin>>>,
+    workers: HashMap<u32, Worker>,
+    next_id: u32,
+    /// Scratch buffers for process()
+    inputs: Vec<Vec<f32>>,
+    outputs: Vec<Vec<f32>>,
+    midi: Vec<MidiEvent>,
+    changes: Vec<ParameterChange>,
+}
+
+/// Thread processing a plugin's blocks from a shared audio ring
+struct Worker {
+    ring: Arc<Ring>,
+    thread: JoinHandle<()>,
+}
+
+impl Worker {
+    fn stop(self) {
+        self.ring.shut_down();
+        let _ = self.thread.join();
+    }
+}
+
+impl<S: PluginScanner> Drop for Server<'_, S> {
+    fn drop(&mut self) {
+        for (_, worker) in self.workers.drain() {
+            worker.stop();
+        }
+    }
+}
+
+impl<S> Server<'_, S>
+where
+    S: PluginScanner,
+    S::Plugin: 'static,
+{
+    fn shared(&self, id: u32) -> Result<&Arc<Mutex<S::Plugin>>> {
+        self.plugins
+            .get(&id)
+            .ok_or_else(|| Error::Other(format!("Helper has no plugin instance {}", id)))
+    }
+
+    fn plugin(&self, id: u32) -> Result<MutexGuard<'_, S::Plugin>> {
+        Ok(lock(self.shared(id)?))
+    }
+
+    fn stop_worker(&mut self, id: u32) {
+        if let Some(worker) = self.workers.remove(&id) {
+            worker.stop();
+        }
+    }
+
+    fn attach_ring(&mut self, id: u32, path: &Path) -> Result<()> {
+        self.stop_worker(id);
+        let plugin = Arc::clone(self.shared(id)?);
+        let ring = Arc::new(Ring::open(path)?);
+        let thread = {
+            let ring = Arc::clone(&ring);
+            std::thread::Builder::new()
+                .name(format!("rack-audio-{}", id))
+                .spawn(move || {
+                    super::pin_to_requested_cores();
+                    run_worker(&ring, &plugin)
+                })?
+        };
+        self.workers.insert(id, Worker { ring, thread });
+        Ok(())
+    }
+
+    fn handle(&mut self, request: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        let mut reader = Reader::new(request);
+        let op = reader.u8()?;
+        let op = Op::from_u8(op)
+            .ok_or_else(|| Error::InvalidFormat(format!("Unknown helper request {}", op)))?;
+        let id = reader.u32()?;
+
+        protocol::ok(out);
+        match op {
+            Op::Load => {
+                let info = protocol::read_plugin_info(&mut reader)?;
+                let plugin = self.scanner.load(&info)?;
+                let id = self.next_id;
+                self.next_id += 1;
+                protocol::write_u32(out, id);
+                protocol::write_u32(out, plugin.parameter_count() as u32);
+                self.plugins.insert(id, Arc::new(Mutex::new(plugin)));
+            }
+            Op::Unload => {
+                self.stop_worker(id);
+                self.plugins.remove(&id);
+            }
+            Op::Initialize => {
+                let sample_rate = reader.f64()?;
+                let max_block_size = reader.u32()? as usize;
+                // The ring is sized for the old block size
+                self.stop_worker(id);
+                let mut plugin = self.plugin(id)?;
+                plugin.initialize(sample_rate, max_block_size)?;
+                protocol::write_u32(out, plugin.input_channels() as u32);
+                protocol::write_u32(out, plugin.output_channels() as u32);
+                protocol::write_u32(out, plugin.parameter_count() as u32);
+            }
+            Op::Reset => self.plugin(id)?.reset()?,
+            Op::Process => self.process(id, &mut reader, out)?,
+            Op::SetSamplePosition => {
+                let position = reader.u64()?;
+                self.plugin(id)?.set_sample_position(position)?;
+            }
+            Op::FlushEvents => self.plugin(id)?.flush_events()?,
+            Op::ParameterInfo => {
+                let index = reader.u32()? as usize;
+                let info = self.plugin(id)?.parameter_info(index)?;
+                protocol::write_parameter_info(out, &info);
+            }
+            Op::GetParameter => {
+                let index = reader.u32()? as usize;
+                let value = self.plugin(id)?.get_parameter(index)?;
+                protocol::write_f32(out, value);
+            }
+            Op::SetParameter => {
+                let index = reader.u32()? as usize;
+                let value = reader.f32()?;
+                self.plugin(id)?.set_parameter(index, value)?;
+            }
+            Op::SendMidi => {
+                let mut midi = std::mem::take(&mut self.midi);
+                let result = protocol::read_midi(&mut reader, &mut midi)
+                    .and_then(|()| self.plugin(id)?.send_midi(&midi));
+                self.midi = midi;
+                result?;
+            }
+            Op::PresetCount => {
+                let count = self.plugin(id)?.preset_count()?;
+                protocol::write_u32(out, count as u32);
+            }
+            Op::PresetInfo => {
+                let index = reader.u32()? as usize;
+                let info = self.plugin(id)?.preset_info(index)?;
+                protocol::write_preset_info(out, &info);
+            }
+            Op::LoadPreset => {
+                let preset_number = reader.u32()? as i32;
+                self.plugin(id)?.load_preset(preset_number)?;
+            }
+            Op::CurrentPreset => {
+                let preset = self.plugin(id)?.current_preset()?;
+                protocol::write_current_preset(out, preset.as_ref());
+            }
+            Op::PluginMeter => {
+                let meter = self.plugin(id)?.plugin_meter();
+                protocol::write_meter(out, meter.as_deref());
+            }
+            Op::Latency => {
+                let latency = self.plugin(id)?.latency_samples();
+                protocol::write_u32(out, latency as u32);
+            }
+            Op::Tail => {
+                let plugin = self.plugin(id)?;
+                protocol::write_u64(out, plugin.tail_samples() as u64);
+                protocol::write_f64(out, plugin.tail_seconds());
+            }
+            Op::SetBypass => {
+                let bypassed = reader.u8()? != 0;
+                self.plugin(id)?.set_bypass(bypassed)?;
+            }
+            Op::Bypassed => out.push(self.plugin(id)?.is_bypassed() as u8),
+            Op::StateDirty => out.push(self.plugin(id)?.take_state_dirty() as u8),
+            Op::GetState => {
+                let state = self.plugin(id)?.get_state()?;
+                protocol::write_field(out, &state);
+            }
+            Op::SetState => {
+                let state = reader.bytes()?;
+                self.plugin(id)?.set_state(state)?;
+            }
+            Op::AttachRing => {
+                let path = reader.string()?;
+                self.attach_ring(id, Path::new(&path))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn process(&mut self, id: u32, reader: &mut Reader<'_>, out: &mut Vec<u8>) -> Result<()> {
+        let channels = reader.u32()? as usize;
+        let frames = reader.u32()? as usize;
+        let plugin = Arc::clone(self.shared(id)?);
+        let mut plugin = lock(&plugin);
+        let output_channels = plugin.output_channels();
+
+        self.inputs.resize_with(channels, Vec::new);
+        for input in &mut self.inputs {
+            let samples = reader.take(frames * 4)?;
+            input.clear();
+            input.extend(
+                samples
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            );
+        }
+        self.outputs.resize_with(output_channels, Vec::new);
+        for output in &mut self.outputs {
+            output.clear();
+            output.resize(frames, 0.0);
+        }
+
+        let context = protocol::read_block(reader, frames, &mut self.changes, &mut self.midi)?;
+
+        let inputs: Vec<&[f32]> = self.inputs.iter().map(|b| b.as_slice()).collect();
+        let mut outputs: Vec<&mut [f32]> =
+            self.outputs.iter_mut().map(|b| b.as_mut_slice()).collect();
+        process(
+            &mut *plugin,
+            &inputs,
+            &mut outputs,
+            frames,
+            context.as_ref(),
+            &self.changes,
+            &self.midi,
+        )?;
+
+        let outputs: Vec<&[f32]> = outputs.iter().map(|b| &**b).collect();
+        protocol::write_audio(out, &outputs, frames);
+        Ok(())
+    }
+}
+
+fn lock<P>(plugin: &Mutex<P>) -> MutexGuard<'_, P> {
+    // A panic while processing leaves the plugin usable as far as we can tell
+    plugin.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// What a ring worker reuses from block to block
+#[derive(Default)]
+struct WorkerBuffers {
+    inputs: Vec<Vec<f32>>,
+    outputs: Vec<Vec<f32>>,
+    block: Vec<u8>,
+    changes: Vec<ParameterChange>,
+    midi: Vec<MidiEvent>,
+}
+
+/// Process blocks from `ring` in order until it is shut down
+fn run_worker<P: PluginInstance>(ring: &Ring, plugin: &Mutex<P>) {
+    let mut buffers = WorkerBuffers {
+        block: Vec::with_capacity(shm::BLOCK_SIZE),
+        ..Default::default()
+    };
+    let mut seq = ring.completed().load(Ordering::Acquire) + 1;
+
+    while shm::wait_for(ring.requested(), seq, None, || ring.is_shut_down()) {
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            process_block(ring, seq, &mut *lock(plugin), &mut buffers)
+        }))
+        .unwrap_or_else(|panic| Err(Error::Panic(crate::guard::panic_message(&*panic))));
+        if let Err(error) = result {
+            ring.write_error(seq, &error);
+        }
+        ring.completed().store(seq, Ordering::Release);
+        seq += 1;
+    }
+}
+
+fn process_block<P: PluginInstance>(
+    ring: &Ring,
+    seq: u64,
+    plugin: &mut P,
+    buffers: &mut WorkerBuffers,
+) -> Result<()> {
+    let WorkerBuffers {
+        inputs,
+        outputs,
+        block,
+        changes,
+        midi,
+    } = buffers;
+    let frames = ring
+        .read_input(seq, inputs, block)
+        .ok_or_else(|| Error::InvalidFormat("Corrupt audio ring slot".to_string()))?;
+    let context = protocol::read_block(&mut Reader::new(block), frames, changes, midi)?;
+    outputs.resize_with(plugin.output_channels(), Vec::new);
+    for output in outputs.iter_mut() {
+        output.clear();
+        output.resize(frames, 0.0);
+    }
+
+    let input_refs: Vec<&[f32]> = inputs.iter().map(|b| b.as_slice()).collect();
+    let mut output_refs: Vec<&mut [f32]> = outputs.iter_mut().map(|b| b.as_mut_slice()).collect();
+    process(
+        plugin,
+        &input_refs,
+        &mut output_refs,
+        frames,
+        context.as_ref(),
+        changes,
+        midi,
+    )?;
+
+    let output_refs: Vec<&[f32]> = output_refs.iter().map(|b| &**b).collect();
+    ring.write_output(seq, &output_refs, frames);
+    Ok(())
+}
+
+/// Process a block, with `context` or the changes and MIDI if the host
+/// passed them
+fn process<P: PluginInstance>(
+    plugin: &mut P,
+    inputs: &[&[f32]],
+    outputs: &mut [&mut [f32]],
+    frames: usize,
+    context: Option<&BlockContext>,
+    changes: &[ParameterChange],
+    midi: &[MidiEvent],
+) -> Result<()> {
+    match context {
+        Some(context) => plugin.process_with_context(inputs, outputs, context),
+        None if changes.is_empty() && midi.is_empty() => plugin.process(inputs, outputs, frames),
+        None => plugin.process_with_events(inputs, outputs, frames, changes, midi),
+    }
+}
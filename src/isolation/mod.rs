@@ -0,0 +1,1361 @@
+//! Running plugins in helper processes
+//!
+//! A plugin that crashes in the host's process takes the host down with it.
+//! Giving every plugin its own process isolates crashes completely, but costs
+//! a process and its memory per plugin plus an IPC round trip per call.
+//! Isolation groups sit in between: each plugin gets an [`Isolation`], and all
+//! plugins in the same [group](Isolation::Group) share one helper process. A
+//! crash then only takes down the plugins in that group; putting each
+//! vendor's plugins in a group of their own, for example, keeps one vendor's
+//! bug from silencing everyone else's plugins.
+//!
+//! # Choosing isolation
+//!
+//! [`isolation_for()`] decides where a plugin runs:
+//! [`PluginInfo::isolation`] if the host set it, else an isolation chosen by
+//! the [quirks table](crate::quirks), else in-process.
+//!
+//! ```
+//! use rack::isolation::Isolation;
+//! use rack::quirks::{self, QuirkEntry};
+//!
+//! // All of Acme's plugins share one helper
+//! quirks::register(QuirkEntry::new("Acme*", "*").isolation(Isolation::Group("acme".into())));
+//! ```
+//!
+//! # Helper processes
+//!
+//! Helpers are the host's own executable, started again with [`HELPER_ENV`]
+//! set. Hosts call [`run_helper_if_requested()`] first thing in `main()`: in
+//! a helper it loads plugins and serves requests until the host disconnects,
+//! then exits; in the host it returns immediately. A different executable can
+//! be used with [`IsolationHost::with_program()`]. On many-core machines,
+//! [`IsolationHost::affinity()`] pins helpers' threads to chosen cores or a
+//! NUMA node.
+//!
+//! Calls to isolated plugins are forwarded to the helper and block until it
+//! answers; calls to plugins sharing a helper are serialized.
+//!
+//! # Crashes
+//!
+//! When a helper dies, the next call to one of its plugins fails, and a
+//! [`CrashReport`] naming the plugin and call in flight is emitted as
+//! [`HostEvent::PluginCrashed`](crate::events::HostEvent::PluginCrashed) and
+//! kept for [`IsolatedPlugin::crash_report()`]. Every plugin in the helper
+//! fails from then on; load them again to start a new helper.
+//!
+//! # Audio
+//!
+//! On Unix, an initialized plugin exchanges audio with its helper through
+//! shared memory, and `process()` never waits longer than the block's
+//! deadline (by default, the block's duration at the plugin's sample rate;
+//! see [`IsolatedPlugin::set_process_deadline()`]). If the helper misses the
+//! deadline, the block's output is silence and
+//! [`HostEvent::Xrun`](crate::events::HostEvent::Xrun) is emitted; the audio
+//! thread doesn't stall behind a slow or hung plugin. Elsewhere, and if the
+//! shared memory can't be set up, audio goes through the helper's pipe and
+//! `process()` blocks until the helper answers.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::isolation::{self, IsolationHost};
+//! # use rack::scan::ScannerConfig;
+//! # fn example<S: PluginScanner>() -> Result<()> where S::Plugin: 'static {
+//! // First thing in main(); never returns in a helper process
+//! isolation::run_helper_if_requested(|| S::with_config(ScannerConfig::new()));
+//!
+//! let scanner = S::with_config(ScannerConfig::new())?;
+//! let plugins = scanner.scan()?;
+//! let host = IsolationHost::new()?;
+//!
+//! let mut plugin = host.load(&plugins[0])?;
+//! plugin.initialize(48000.0, 512)?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod crash;
+mod helper;
+pub(crate) mod protocol;
+mod shm;
+
+pub use crash::{CrashReport, MINIDUMP_DIR_ENV};
+pub use helper::serve;
+
+use crate::affinity::{self, CpuSet};
+use crate::events::{self, HostEvent};
+use crate::meter::MeterReading;
+use crate::node::BlockContext;
+use crate::quirks::{self, Quirks};
+use crate::session::Reader;
+use crate::{
+    CurrentPreset, Error, MidiEvent, ParameterChange, ParameterInfo, PluginInfo, PluginInstance,
+    PluginScanner, PresetInfo, Result,
+};
+use protocol::Op;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io::{BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+/// Environment variable marking a helper process
+///
+/// Set to the name of the helper's group.
+pub const HELPER_ENV: &str = "RACK_ISOLATION_HELPER";
+
+/// Environment variable listing the cores a helper's threads are pinned to
+///
+/// In the [`CpuSet`] list format, e.g. `0-3,8`. Set from
+/// [`IsolationHost::affinity()`] and [`IsolationHost::group_affinity()`].
+pub const AFFINITY_ENV: &str = "RACK_ISOLATION_AFFINITY";
+
+/// How long a helper may take to start by default
+const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How much output a helper may print before its greeting
+const MAX_GREETING_SKIP: usize = 64 * 1024;
+
+/// How long a helper gets to exit after the host disconnects
+const EXIT_GRACE: Duration = Duration::from_secs(1);
+
+/// Where a plugin runs
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Isolation {
+    /// In the host's process (no isolation)
+    #[default]
+    InProcess,
+
+    /// In the helper process shared by every plugin in the named group
+    Group(String),
+
+    /// In a helper process of its own
+    Dedicated,
+}
+
+impl Isolation {
+    /// A group shared by all plugins of the plugin's manufacturer
+    pub fn by_manufacturer(info: &PluginInfo) -> Self {
+        Isolation::Group(info.manufacturer.clone())
+    }
+
+    /// Whether the plugin runs outside the host's process
+    pub fn is_isolated(&self) -> bool {
+        !matches!(self, Isolation::InProcess)
+    }
+}
+
+/// Decide where a plugin runs
+///
+/// [`PluginInfo::isolation`] if set, else the isolation of the last matching
+/// [quirks table](crate::quirks) entry that sets one, else
+/// [`Isolation::InProcess`].
+pub fn isolation_for(info: &PluginInfo) -> Isolation {
+    if let Some(isolation) = &info.isolation {
+        return isolation.clone();
+    }
+    quirks::lookup(info)
+        .isolation()
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Serve as a helper if this process was started as one
+///
+/// Call first thing in `main()`. If [`HELPER_ENV`] is set, creates a scanner
+/// with `make_scanner`, serves the host over standard input and output with
+/// [`serve()`] and exits the process when the host disconnects. Otherwise
+/// returns immediately.
+///
+/// Standard output carries the protocol, so on Unix anything the plugins
+/// print to it is redirected to standard error.
+pub fn run_helper_if_requested<S, F>(make_scanner: F)
+where
+    S: PluginScanner,
+    S::Plugin: 'static,
+    F: FnOnce() -> Result<S>,
+{
+    if std::env::var_os(HELPER_ENV).is_none() {
+        return;
+    }
+
+    pin_to_requested_cores();
+    let mut output = std::io::BufWriter::new(stdio::protocol_output());
+    let stdin = std::io::stdin();
+    let result = make_scanner().and_then(|scanner| serve(&scanner, &mut stdin.lock(), &mut output));
+    if let Err(error) = &result {
+        eprintln!("rack isolation helper: {}", error);
+    }
+    std::process::exit(if result.is_ok() { 0 } else { 1 });
+}
+
+/// Pin the calling thread to the cores in [`AFFINITY_ENV`], if set
+///
+/// Failing to pin is reported on standard error; the helper runs unpinned.
+pub(crate) fn pin_to_requested_cores() {
+    let Some(list) = std::env::var_os(AFFINITY_ENV) else {
+        return;
+    };
+    let result = list
+        .to_str()
+        .ok_or_else(|| Error::InvalidFormat(format!("Invalid CPU list: {:?}", list)))
+        .and_then(str::parse::<CpuSet>)
+        .and_then(|cores| affinity::pin_current_thread(&cores));
+    if let Err(error) = result {
+        eprintln!("rack isolation helper: can't pin to cores: {}", error);
+    }
+}
+
+/// Starts helper processes and loads plugins into them
+///
+/// A group's helper is started when the first plugin of the group is loaded
+/// and exits once every plugin loaded into it has been dropped. If a helper
+/// dies, its plugins fail from then on; the next load into the group starts
+/// a fresh helper.
+pub struct IsolationHost {
+    program: PathBuf,
+    args: Vec<OsString>,
+    startup_timeout: Duration,
+    minidump_dir: Option<PathBuf>,
+    affinity: Option<CpuSet>,
+    group_affinity: HashMap<String, CpuSet>,
+    helpers: Mutex<HashMap<String, Weak<Helper>>>,
+    next_dedicated: AtomicU64,
+}
+
+impl IsolationHost {
+    /// Start helpers from the current executable
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path of the current executable can't be found
+    pub fn new() -> Result<Self> {
+        Ok(Self::with_program(std::env::current_exe()?))
+    }
+
+    /// Start helpers from a different executable
+    ///
+    /// It must call [`run_helper_if_requested()`] (or [`serve()`] over its
+    /// standard input and output) when [`HELPER_ENV`] is set.
+    pub fn with_program(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            startup_timeout: DEFAULT_STARTUP_TIMEOUT,
+            minidump_dir: None,
+            affinity: None,
+            group_affinity: HashMap::new(),
+            helpers: Mutex::new(HashMap::new()),
+            next_dedicated: AtomicU64::new(1),
+        }
+    }
+
+    /// Pass an extra command-line argument to helpers
+    pub fn arg(mut self, arg: impl Into<OsString>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// How long a helper may take to start before loading fails
+    pub fn startup_timeout(mut self, timeout: Duration) -> Self {
+        self.startup_timeout = timeout;
+        self
+    }
+
+    /// Directory for crash dumps of helpers
+    ///
+    /// Passed to helpers in [`MINIDUMP_DIR_ENV`]; dumps found there are linked
+    /// from [`CrashReport::minidump`].
+    pub fn minidump_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.minidump_dir = Some(dir.into());
+        self
+    }
+
+    /// Pin every helper's threads to `cores`
+    ///
+    /// Passed to helpers in [`AFFINITY_ENV`]. Keeps heavy instruments on the
+    /// cores (or [NUMA node](CpuSet::numa_node)) they were given instead of
+    /// competing with the host's audio thread. Only helpers started
+    /// afterwards are pinned.
+    pub fn affinity(mut self, cores: CpuSet) -> Self {
+        self.affinity = Some(cores);
+        self
+    }
+
+    /// Pin the threads of one group's helper to `cores`, instead of the cores
+    /// given to [`affinity()`](Self::affinity)
+    pub fn group_affinity(mut self, group: impl Into<String>, cores: CpuSet) -> Self {
+        self.group_affinity.insert(group.into(), cores);
+        self
+    }
+
+    /// Load a plugin into the helper chosen by [`isolation_for()`]
+    ///
+    /// Plugins that would run in-process get a dedicated helper, since the
+    /// caller asked for isolation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the helper can't be started or fails to load the
+    /// plugin
+    pub fn load(&self, info: &PluginInfo) -> Result<IsolatedPlugin> {
+        self.load_with(info, &isolation_for(info))
+    }
+
+    /// Load a plugin with an explicit isolation
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the helper can't be started or fails to load the
+    /// plugin
+    pub fn load_with(&self, info: &PluginInfo, isolation: &Isolation) -> Result<IsolatedPlugin> {
+        let helper = match isolation {
+            Isolation::Group(group) => self.group_helper(group)?,
+            Isolation::InProcess | Isolation::Dedicated => {
+                let n = self.next_dedicated.fetch_add(1, Ordering::Relaxed);
+                Arc::new(self.spawn(&format!("dedicated-{}", n))?)
+            }
+        };
+        IsolatedPlugin::load(helper, info)
+    }
+
+    /// Names of the groups whose helpers are running
+    pub fn running_groups(&self) -> Vec<String> {
+        let helpers = self.helpers.lock().unwrap_or_else(|e| e.into_inner());
+        let mut groups: Vec<String> = helpers
+            .iter()
+            .filter(|(_, helper)| helper.upgrade().is_some_and(|h| h.is_alive()))
+            .map(|(group, _)| group.clone())
+            .collect();
+        groups.sort();
+        groups
+    }
+
+    fn group_helper(&self, group: &str) -> Result<Arc<Helper>> {
+        let mut helpers = self.helpers.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(helper) = helpers.get(group).and_then(Weak::upgrade) {
+            if helper.is_alive() {
+                return Ok(helper);
+            }
+        }
+        let helper = Arc::new(self.spawn(group)?);
+        helpers.insert(group.to_string(), Arc::downgrade(&helper));
+        Ok(helper)
+    }
+
+    fn spawn(&self, group: &str) -> Result<Helper> {
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .env(HELPER_ENV, group)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit());
+        if let Some(dir) = &self.minidump_dir {
+            command.env(MINIDUMP_DIR_ENV, dir);
+        }
+        if let Some(cores) = self.group_affinity.get(group).or(self.affinity.as_ref()) {
+            command.env(AFFINITY_ENV, cores.to_string());
+        }
+        let mut child = command.spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+
+        // Wait for the greeting on another thread, so a program that never
+        // greets can't hang the host
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+            let result = protocol::read_hello(&mut reader, MAX_GREETING_SKIP);
+            let _ = sender.send(result.map(|()| reader));
+        });
+        let reader = match receiver.recv_timeout(self.startup_timeout) {
+            Ok(Ok(reader)) => reader,
+            failure => {
+                let _ = child.kill();
+                let _ = child.wait();
+                let reason = match failure {
+                    Ok(Err(e)) => e.to_string(),
+                    _ => format!("no answer within {:?}", self.startup_timeout),
+                };
+                return Err(Error::Other(format!(
+                    "Isolation helper '{}' failed to start: {}",
+                    group, reason
+                )));
+            }
+        };
+
+        let mut helper = Helper::new(group, Box::new(reader), Box::new(stdin));
+        helper.pid = Some(child.id());
+        helper.child = Mutex::new(Some(child));
+        helper.minidump_dir = self.minidump_dir.clone();
+        Ok(helper)
+    }
+}
+
+/// Connection to one helper process
+struct Helper {
+    group: String,
+    channel: Mutex<Channel>,
+    child: Mutex<Option<Child>>,
+    pid: Option<u32>,
+    alive: AtomicBool,
+    minidump_dir: Option<PathBuf>,
+    /// Plugins loaded in the helper, by instance ID
+    plugins: Mutex<HashMap<u32, PluginInfo>>,
+    crash: Mutex<Option<Arc<CrashReport>>>,
+}
+
+struct Channel {
+    reader: Box<dyn Read + Send>,
+    writer: Box<dyn Write + Send>,
+}
+
+impl Helper {
+    fn new(group: &str, reader: Box<dyn Read + Send>, writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            group: group.to_string(),
+            channel: Mutex::new(Channel { reader, writer }),
+            child: Mutex::new(None),
+            pid: None,
+            alive: AtomicBool::new(true),
+            minidump_dir: None,
+            plugins: Mutex::new(HashMap::new()),
+            crash: Mutex::new(None),
+        }
+    }
+
+    fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::Acquire)
+    }
+
+    fn gone(&self) -> Error {
+        match self.crash_report() {
+            Some(report) => Error::Other(report.to_string()),
+            None => Error::Other(format!("Isolation helper '{}' is gone", self.group)),
+        }
+    }
+
+    fn crash_report(&self) -> Option<Arc<CrashReport>> {
+        self.crash.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Send a request and read the response
+    ///
+    /// A broken connection marks the helper dead and reports a crash; later
+    /// calls fail at once.
+    fn call(&self, request: &[u8], response: &mut Vec<u8>) -> Result<()> {
+        if !self.is_alive() {
+            return Err(self.gone());
+        }
+        let mut channel = self.channel.lock().unwrap_or_else(|e| e.into_inner());
+        let result = protocol::write_message(&mut *channel.writer, request)
+            .and_then(|()| protocol::read_message(&mut *channel.reader, response));
+        drop(channel);
+        match result {
+            Ok(true) => Ok(()),
+            Ok(false) | Err(_) => {
+                self.died(request, None);
+                Err(self.gone())
+            }
+        }
+    }
+
+    /// Check whether the helper process exited, without blocking
+    ///
+    /// For the audio thread, which doesn't see the pipe close.
+    fn poll_exit(&self, request: &[u8]) -> bool {
+        let status = match self.child.try_lock() {
+            Ok(mut child) => child
+                .as_mut()
+                .and_then(|child| child.try_wait().ok().flatten()),
+            Err(_) => None,
+        };
+        match status {
+            Some(status) => {
+                self.died(request, Some(status));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Record the helper's death and report it, once
+    ///
+    /// `request` is the call in flight (possibly just an opcode and instance).
+    fn died(&self, request: &[u8], status: Option<ExitStatus>) {
+        if !self.alive.swap(false, Ordering::AcqRel) {
+            return;
+        }
+        let status = status.or_else(|| self.wait_exit());
+
+        let mut report = CrashReport::new(&self.group, self.pid, status);
+        let plugins = self.plugins.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(op) = request.first().copied().and_then(Op::from_u8) {
+            report.operation = Some(op.name());
+            report.plugin = match op {
+                Op::Load => {
+                    let mut reader = Reader::new(request.get(5..).unwrap_or_default());
+                    protocol::read_plugin_info(&mut reader).ok()
+                }
+                _ => request
+                    .get(1..5)
+                    .map(|id| u32::from_le_bytes([id[0], id[1], id[2], id[3]]))
+                    .and_then(|id| plugins.get(&id).cloned()),
+            };
+        }
+        report.plugins = plugins.values().cloned().collect();
+        drop(plugins);
+        if let Some(dir) = &self.minidump_dir {
+            report.find_minidump(dir);
+        }
+
+        events::emit(HostEvent::PluginCrashed { report: &report });
+        *self.crash.lock().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(report));
+    }
+
+    /// Reap the helper after its pipe closed
+    fn wait_exit(&self) -> Option<ExitStatus> {
+        let mut child = self.child.lock().unwrap_or_else(|e| e.into_inner());
+        let child = child.as_mut()?;
+        let deadline = Instant::now() + EXIT_GRACE;
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) => return Some(status),
+                Ok(None) if Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(5))
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+impl Drop for Helper {
+    fn drop(&mut self) {
+        // Closing the pipe tells the helper to exit
+        let channel = self.channel.get_mut().unwrap_or_else(|e| e.into_inner());
+        channel.writer = Box::new(std::io::sink());
+
+        let child = self.child.get_mut().unwrap_or_else(|e| e.into_inner());
+        if let Some(mut child) = child.take() {
+            let deadline = Instant::now() + EXIT_GRACE;
+            while matches!(child.try_wait(), Ok(None)) && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// A plugin running in a helper process
+///
+/// Implements [`PluginInstance`] by forwarding every call to the helper.
+/// Once the helper is gone, calls fail with an error instead of blocking.
+pub struct IsolatedPlugin {
+    helper: Arc<Helper>,
+    id: u32,
+    info: PluginInfo,
+    quirks: Quirks,
+    initialized: bool,
+    input_channels: usize,
+    output_channels: usize,
+    max_block_size: usize,
+    parameter_count: usize,
+    sample_position: u64,
+    sample_rate: f64,
+    /// Shared-memory audio transport, if set up
+    ring: Option<shm::Ring>,
+    /// Sequence number of the last block published to the ring
+    sent: u64,
+    deadline: Option<Duration>,
+    missed_deadlines: u64,
+    /// What the block being processed comes with besides its audio, as
+    /// written by `protocol::write_block()`
+    block: Vec<u8>,
+    /// Reused by process() without a ring
+    request: Vec<u8>,
+    response: Vec<u8>,
+}
+
+impl IsolatedPlugin {
+    fn load(helper: Arc<Helper>, info: &PluginInfo) -> Result<Self> {
+        let mut request = Vec::new();
+        protocol::request(&mut request, Op::Load, 0);
+        protocol::write_plugin_info(&mut request, info);
+        let mut response = Vec::new();
+        helper.call(&request, &mut response)?;
+        let mut reader = protocol::response(&response)?;
+        let id = reader.u32()?;
+        let parameter_count = reader.u32()? as usize;
+        helper
+            .plugins
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id, info.clone());
+
+        Ok(Self {
+            helper,
+            id,
+            info: info.clone(),
+            quirks: quirks::lookup(info),
+            initialized: false,
+            input_channels: 0,
+            output_channels: 0,
+            max_block_size: 0,
+            parameter_count,
+            sample_position: 0,
+            sample_rate: 0.0,
+            ring: None,
+            sent: 0,
+            deadline: None,
+            missed_deadlines: 0,
+            block: Vec::new(),
+            request,
+            response,
+        })
+    }
+
+    /// Name of the group whose helper runs this plugin
+    pub fn group(&self) -> &str {
+        &self.helper.group
+    }
+
+    /// Process ID of the helper running this plugin
+    pub fn helper_pid(&self) -> Option<u32> {
+        self.helper.pid
+    }
+
+    /// Whether the helper is still running
+    pub fn is_helper_alive(&self) -> bool {
+        self.helper.is_alive()
+    }
+
+    /// What is known about the helper's death, if it died
+    pub fn crash_report(&self) -> Option<Arc<CrashReport>> {
+        self.helper.crash_report()
+    }
+
+    /// How long `process()` waits for the helper before outputting silence
+    ///
+    /// `None` (the default) allows each block its own duration at the
+    /// plugin's sample rate. Only applies to the shared-memory transport.
+    pub fn set_process_deadline(&mut self, deadline: Option<Duration>) {
+        self.deadline = deadline;
+    }
+
+    /// The deadline set with [`set_process_deadline()`](Self::set_process_deadline)
+    pub fn process_deadline(&self) -> Option<Duration> {
+        self.deadline
+    }
+
+    /// Number of blocks replaced by silence because the helper missed their
+    /// deadline
+    pub fn missed_deadlines(&self) -> u64 {
+        self.missed_deadlines
+    }
+
+    /// Whether audio goes through shared memory rather than the helper's pipe
+    pub fn uses_shared_memory(&self) -> bool {
+        self.ring.is_some()
+    }
+
+    /// Set up a shared audio ring for the current block size
+    ///
+    /// Failure isn't an error: audio then goes through the pipe.
+    fn attach_ring(&mut self) {
+        self.ring = None;
+        self.sent = 0;
+        let channels = self.input_channels.max(self.output_channels).max(1);
+        let Ok(mut ring) = shm::Ring::create(shm::DEFAULT_SLOTS, self.max_block_size, channels)
+        else {
+            return;
+        };
+        let path = ring.path().to_string_lossy().into_owned();
+        if self
+            .call(Op::AttachRing, |out| protocol::write_str(out, &path))
+            .is_ok()
+        {
+            ring.unlink();
+            self.ring = Some(ring);
+        }
+    }
+
+    /// Exchange a block through the ring, or output silence if the helper
+    /// doesn't make the deadline
+    fn process_shared(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        let Some(ring) = &self.ring else {
+            return Ok(());
+        };
+        if !self.helper.is_alive() {
+            return Err(self.helper.gone());
+        }
+
+        let deadline = Instant::now()
+            + self
+                .deadline
+                .unwrap_or_else(|| Duration::from_secs_f64(num_frames as f64 / self.sample_rate));
+        let seq = self.sent + 1;
+        let completed = ring.completed().load(Ordering::Acquire);
+        // The slot is still taken if the helper is a whole ring behind
+        let made_it = seq <= completed + ring.slots() as u64 && {
+            let inputs = &inputs[..inputs.len().min(self.input_channels)];
+            ring.write_input(seq, inputs, num_frames, &self.block);
+            ring.requested().store(seq, Ordering::Release);
+            self.sent = seq;
+            shm::wait_for(ring.completed(), seq, Some(deadline), || false)
+        };
+
+        let id = self.id.to_le_bytes();
+        if made_it {
+            ring.read_output(seq, outputs, num_frames)
+        } else if self
+            .helper
+            .poll_exit(&[Op::Process as u8, id[0], id[1], id[2], id[3]])
+        {
+            Err(self.helper.gone())
+        } else {
+            for output in outputs.iter_mut() {
+                let len = num_frames.min(output.len());
+                output[..len].fill(0.0);
+            }
+            self.missed_deadlines += 1;
+            events::emit(HostEvent::Xrun { frames: num_frames });
+            Ok(())
+        }
+    }
+
+    /// Have the helper process a block, with `context` if given
+    fn process_block(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        context: Option<&BlockContext>,
+        changes: &[ParameterChange],
+        midi: &[MidiEvent],
+    ) -> Result<()> {
+        if !self.initialized {
+            return Err(Error::NotInitialized);
+        }
+        if num_frames > self.max_block_size {
+            return Err(Error::BlockTooLarge {
+                max: self.max_block_size,
+                got: num_frames,
+            });
+        }
+
+        if self.ring.is_some() {
+            self.block.clear();
+            protocol::write_block(&mut self.block, context, changes, midi, shm::BLOCK_SIZE);
+            self.process_shared(inputs, outputs, num_frames)?;
+        } else {
+            protocol::request(&mut self.request, Op::Process, self.id);
+            let inputs = &inputs[..inputs.len().min(self.input_channels)];
+            protocol::write_audio(&mut self.request, inputs, num_frames);
+            protocol::write_block(&mut self.request, context, changes, midi, usize::MAX);
+            self.helper.call(&self.request, &mut self.response)?;
+            let mut reader = protocol::response(&self.response)?;
+            protocol::read_audio(&mut reader, outputs)?;
+        }
+
+        if let Some(context) = context {
+            self.sample_position = context.sample_position;
+        }
+        self.sample_position += num_frames as u64;
+        Ok(())
+    }
+
+    /// Send a request built by `build` and return the response
+    fn call(&self, op: Op, build: impl FnOnce(&mut Vec<u8>)) -> Result<Vec<u8>> {
+        let mut request = Vec::new();
+        protocol::request(&mut request, op, self.id);
+        build(&mut request);
+        let mut response = Vec::new();
+        self.helper.call(&request, &mut response)?;
+        protocol::response(&response)?;
+        Ok(response)
+    }
+
+    /// The plugin's tail in samples and seconds, if the helper answers
+    fn tail(&self) -> Option<(usize, f64)> {
+        let response = self.call(Op::Tail, |_| {}).ok()?;
+        let mut reader = result(&response);
+        Some((reader.u64().ok()? as usize, reader.f64().ok()?))
+    }
+}
+
+/// The result part of a response already checked by [`IsolatedPlugin::call()`]
+fn result(response: &[u8]) -> Reader<'_> {
+    let mut reader = Reader::new(response);
+    let _ = reader.u8();
+    reader
+}
+
+impl PluginInstance for IsolatedPlugin {
+    fn initialize(&mut self, sample_rate: f64, max_block_size: usize) -> Result<()> {
+        let response = self.call(Op::Initialize, |out| {
+            protocol::write_f64(out, sample_rate);
+            protocol::write_u32(out, max_block_size as u32);
+        })?;
+        let mut reader = result(&response);
+        self.input_channels = reader.u32()? as usize;
+        self.output_channels = reader.u32()? as usize;
+        self.parameter_count = reader.u32()? as usize;
+        self.max_block_size = max_block_size;
+        self.sample_rate = sample_rate;
+        self.initialized = true;
+
+        self.attach_ring();
+        self.block.reserve(shm::BLOCK_SIZE);
+        if self.ring.is_none() {
+            // Room for a full block of audio either way
+            let audio = (self.input_channels.max(self.output_channels) * max_block_size + 4) * 4;
+            self.request.reserve(audio);
+            self.response.reserve(audio);
+        }
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.call(Op::Reset, |_| {}).map(drop)
+    }
+
+    fn process(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        self.process_block(inputs, outputs, num_frames, None, &[], &[])
+    }
+
+    /// Process with `context` passed on to the plugin in the helper
+    fn process_with_context(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        context: &BlockContext,
+    ) -> Result<()> {
+        self.process_block(inputs, outputs, context.num_frames, Some(context), &[], &[])
+    }
+
+    /// Send the changes and MIDI along with the block's audio
+    ///
+    /// Over shared memory, a block carries at most about 680 changes or 1160
+    /// MIDI events; the rest are dropped.
+    fn process_with_events(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        changes: &[ParameterChange],
+        midi: &[MidiEvent],
+    ) -> Result<()> {
+        self.process_block(inputs, outputs, num_frames, None, changes, midi)
+    }
+
+    fn sample_position(&self) -> u64 {
+        self.sample_position
+    }
+
+    fn set_sample_position(&mut self, position: u64) -> Result<()> {
+        self.call(Op::SetSamplePosition, |out| {
+            protocol::write_u64(out, position)
+        })?;
+        self.sample_position = position;
+        Ok(())
+    }
+
+    fn flush_events(&mut self) -> Result<()> {
+        self.call(Op::FlushEvents, |_| {}).map(drop)
+    }
+
+    fn parameter_count(&self) -> usize {
+        self.parameter_count
+    }
+
+    fn parameter_info(&self, index: usize) -> Result<ParameterInfo> {
+        let response = self.call(Op::ParameterInfo, |out| {
+            protocol::write_u32(out, index as u32)
+        })?;
+        protocol::read_parameter_info(&mut result(&response))
+    }
+
+    fn get_parameter(&self, index: usize) -> Result<f32> {
+        let response = self.call(Op::GetParameter, |out| {
+            protocol::write_u32(out, index as u32)
+        })?;
+        result(&response).f32()
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) -> Result<()> {
+        self.call(Op::SetParameter, |out| {
+            protocol::write_u32(out, index as u32);
+            protocol::write_f32(out, value);
+        })
+        .map(drop)
+    }
+
+    fn send_midi(&mut self, events: &[MidiEvent]) -> Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        self.call(Op::SendMidi, |out| protocol::write_midi(out, events))
+            .map(drop)
+    }
+
+    fn preset_count(&self) -> Result<usize> {
+        let response = self.call(Op::PresetCount, |_| {})?;
+        Ok(result(&response).u32()? as usize)
+    }
+
+    fn preset_info(&self, index: usize) -> Result<PresetInfo> {
+        let response = self.call(Op::PresetInfo, |out| protocol::write_u32(out, index as u32))?;
+        protocol::read_preset_info(&mut result(&response))
+    }
+
+    fn load_preset(&mut self, preset_number: i32) -> Result<()> {
+        self.call(Op::LoadPreset, |out| {
+            protocol::write_u32(out, preset_number as u32)
+        })
+        .map(drop)
+    }
+
+    fn current_preset(&self) -> Result<Option<CurrentPreset>> {
+        let response = self.call(Op::CurrentPreset, |_| {})?;
+        protocol::read_current_preset(&mut result(&response))
+    }
+
+    fn get_state(&self) -> Result<Vec<u8>> {
+        let response = self.call(Op::GetState, |_| {})?;
+        Ok(result(&response).bytes()?.to_vec())
+    }
+
+    fn set_state(&mut self, data: &[u8]) -> Result<()> {
+        self.call(Op::SetState, |out| protocol::write_field(out, data))
+            .map(drop)
+    }
+
+    fn take_state_dirty(&self) -> bool {
+        self.call(Op::StateDirty, |_| {})
+            .and_then(|response| result(&response).u8())
+            .is_ok_and(|dirty| dirty != 0)
+    }
+
+    fn info(&self) -> &PluginInfo {
+        &self.info
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    fn input_channels(&self) -> usize {
+        self.input_channels
+    }
+
+    fn output_channels(&self) -> usize {
+        self.output_channels
+    }
+
+    /// Asks the helper, so runtime changes are seen; 0 if the call fails
+    fn latency_samples(&self) -> usize {
+        self.call(Op::Latency, |_| {})
+            .and_then(|response| result(&response).u32())
+            .map_or(0, |latency| latency as usize)
+    }
+
+    fn tail_samples(&self) -> usize {
+        self.tail().map_or(0, |(samples, _)| samples)
+    }
+
+    fn tail_seconds(&self) -> f64 {
+        self.tail().map_or(0.0, |(_, seconds)| seconds)
+    }
+
+    fn set_bypass(&mut self, bypassed: bool) -> Result<()> {
+        self.call(Op::SetBypass, |out| out.push(bypassed as u8))?;
+        Ok(())
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.call(Op::Bypassed, |_| {})
+            .and_then(|response| result(&response).u8())
+            .is_ok_and(|bypassed| bypassed != 0)
+    }
+
+    fn quirks(&self) -> Quirks {
+        self.quirks.clone()
+    }
+
+    fn plugin_meter(&self) -> Option<Vec<MeterReading>> {
+        let response = self.call(Op::PluginMeter, |_| {}).ok()?;
+        protocol::read_meter(&mut result(&response)).ok()?
+    }
+}
+
+impl Drop for IsolatedPlugin {
+    fn drop(&mut self) {
+        if self.helper.is_alive() {
+            let _ = self.call(Op::Unload, |_| {});
+        }
+        self.helper
+            .plugins
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.id);
+    }
+}
+
+#[cfg(unix)]
+mod stdio {
+    use std::ffi::c_int;
+    use std::io::Write;
+    use std::os::unix::io::FromRawFd;
+
+    extern "C" {
+        fn dup(fd: c_int) -> c_int;
+        fn dup2(fd: c_int, fd2: c_int) -> c_int;
+    }
+
+    /// Take over standard output for the protocol
+    ///
+    /// Returns a handle to the original standard output and points file
+    /// descriptor 1 at standard error, so stray prints can't corrupt messages.
+    pub(super) fn protocol_output() -> Box<dyn Write> {
+        // Safety: dup() returns a new descriptor we own; dup2() only
+        // redirects descriptor 1, which Rust's stdout keeps using
+        unsafe {
+            let fd = dup(1);
+            if fd >= 0 {
+                dup2(2, 1);
+                return Box::new(std::fs::File::from_raw_fd(fd));
+            }
+        }
+        Box::new(std::io::stdout())
+    }
+}
+
+#[cfg(not(unix))]
+mod stdio {
+    use std::io::Write;
+
+    /// Standard output, which carries the protocol
+    pub(super) fn protocol_output() -> Box<dyn Write> {
+        Box::new(std::io::stdout())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::QuirkEntry;
+    use crate::scan::ScannerConfig;
+    use crate::test_util::MockPlugin;
+    use crate::PluginType;
+    use std::path::Path;
+
+    /// Scanner loading mock plugins; "panics" panics on its second block
+    struct TestScanner(ScannerConfig);
+
+    impl PluginScanner for TestScanner {
+        type Plugin = MockPlugin;
+
+        fn with_config(config: ScannerConfig) -> Result<Self> {
+            Ok(Self(config))
+        }
+
+        fn config(&self) -> &ScannerConfig {
+            &self.0
+        }
+
+        fn add_path(&mut self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        fn scan(&self) -> Result<Vec<PluginInfo>> {
+            Ok(Vec::new())
+        }
+
+        fn scan_path(&self, _path: &Path) -> Result<Vec<PluginInfo>> {
+            Ok(Vec::new())
+        }
+
+        fn load(&self, info: &PluginInfo) -> Result<MockPlugin> {
+            let mut plugin = MockPlugin::new();
+            match info.unique_id.as_str() {
+                "missing" => return Err(Error::PluginNotFound(info.name.clone())),
+                "panics" => plugin.panic_blocks = vec![1],
+                "slow" => plugin.slow_blocks = vec![1],
+                "aborts" => plugin.abort_blocks = vec![1],
+                _ => {}
+            }
+            Ok(plugin)
+        }
+    }
+
+    fn plugin(id: &str, manufacturer: &str) -> PluginInfo {
+        PluginInfo::new(
+            id.to_string(),
+            manufacturer.to_string(),
+            1,
+            PluginType::Effect,
+            PathBuf::new(),
+            id.to_string(),
+        )
+    }
+
+    fn process_ones(plugin: &mut impl PluginInstance, frames: usize) -> Result<Vec<f32>> {
+        let input = vec![1.0f32; frames];
+        let mut left = vec![0.0f32; frames];
+        let mut right = vec![0.0f32; frames];
+        plugin.process(
+            &[&input, &input],
+            &mut [left.as_mut_slice(), right.as_mut_slice()],
+            frames,
+        )?;
+        Ok(left)
+    }
+
+    /// Entry point for the helper processes started by the tests below
+    ///
+    /// Does nothing in a normal test run.
+    #[test]
+    fn helper_process_entry() {
+        run_helper_if_requested(|| TestScanner::with_config(ScannerConfig::new()));
+    }
+
+    fn test_host() -> IsolationHost {
+        IsolationHost::new()
+            .unwrap()
+            .arg("--exact")
+            .arg("isolation::tests::helper_process_entry")
+            .arg("--nocapture")
+    }
+
+    #[test]
+    fn test_isolation_resolution() {
+        let info = plugin("resolution", "Isolation Test Co");
+        assert_eq!(isolation_for(&info), Isolation::InProcess);
+
+        quirks::register(
+            QuirkEntry::new("Isolation Test Co", "*").isolation(Isolation::by_manufacturer(&info)),
+        );
+        assert_eq!(
+            isolation_for(&info),
+            Isolation::Group("Isolation Test Co".to_string())
+        );
+
+        let info = info.with_isolation(Isolation::Dedicated);
+        assert_eq!(isolation_for(&info), Isolation::Dedicated);
+        assert!(isolation_for(&info).is_isolated());
+    }
+
+    /// A helper serving on a thread of this process
+    #[cfg(unix)]
+    fn thread_helper() -> (Arc<Helper>, std::thread::JoinHandle<Result<()>>) {
+        use std::os::unix::net::UnixStream;
+
+        let (host_end, helper_end) = UnixStream::pair().unwrap();
+        let server = std::thread::spawn(move || {
+            let scanner = TestScanner::with_config(ScannerConfig::new()).unwrap();
+            let mut reader = helper_end.try_clone().unwrap();
+            let mut writer = helper_end;
+            serve(&scanner, &mut reader, &mut writer)
+        });
+
+        let mut reader = host_end.try_clone().unwrap();
+        protocol::read_hello(&mut reader, 0).unwrap();
+        let helper = Helper::new("streams", Box::new(reader), Box::new(host_end));
+        (Arc::new(helper), server)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_isolated_plugin_over_streams() {
+        let (helper, server) = thread_helper();
+
+        let mut gain = IsolatedPlugin::load(Arc::clone(&helper), &plugin("gain", "Acme")).unwrap();
+        assert_eq!(gain.parameter_count(), 3);
+        assert_eq!(gain.parameter_info(0).unwrap().unit, "dB");
+        assert!(matches!(
+            gain.process(&[], &mut [], 16),
+            Err(Error::NotInitialized)
+        ));
+
+        gain.initialize(48000.0, 64).unwrap();
+        gain.set_process_deadline(Some(Duration::from_secs(5)));
+        assert!(gain.uses_shared_memory());
+        assert_eq!(gain.output_channels(), 2);
+        gain.set_parameter(1, 0.5).unwrap();
+        assert_eq!(gain.get_parameter(1).unwrap(), 0.5);
+        assert_eq!(process_ones(&mut gain, 64).unwrap(), vec![1.0; 64]);
+        assert_eq!(gain.sample_position(), 64);
+        let input = vec![1.0f32; 64];
+        let mut left = vec![0.0f32; 64];
+        let mut right = vec![0.0f32; 64];
+        let context = BlockContext::new(48000.0, 1000, 32).offline(true);
+        gain.process_with_context(&[&input, &input], &mut [&mut left, &mut right], &context)
+            .unwrap();
+        assert_eq!(left[..32], [1.0; 32]);
+        assert_eq!(gain.sample_position(), 1032);
+        let changes = [ParameterChange::new(2, 0.25, 8)];
+        let midi = [MidiEvent::note_on(60, 100, 0, 4)];
+        gain.process_with_events(
+            &[&input, &input],
+            &mut [&mut left, &mut right],
+            32,
+            &changes,
+            &midi,
+        )
+        .unwrap();
+        assert_eq!(left[..32], [1.0; 32]);
+        assert_eq!(gain.get_parameter(2).unwrap(), 0.25);
+        assert_eq!(gain.sample_position(), 1064);
+        assert!(matches!(
+            process_ones(&mut gain, 128),
+            Err(Error::BlockTooLarge { max: 64, got: 128 })
+        ));
+
+        let state = gain.get_state().unwrap();
+        gain.set_parameter(1, 1.0).unwrap();
+        gain.set_state(&state).unwrap();
+        assert_eq!(gain.get_parameter(1).unwrap(), 0.5);
+        gain.send_midi(&[MidiEvent::note_on(60, 100, 0, 0)])
+            .unwrap();
+
+        // Errors and panics in the helper come back as errors
+        assert!(matches!(
+            IsolatedPlugin::load(Arc::clone(&helper), &plugin("missing", "Acme")),
+            Err(Error::PluginNotFound(_))
+        ));
+        let mut panics =
+            IsolatedPlugin::load(Arc::clone(&helper), &plugin("panics", "Acme")).unwrap();
+        panics.initialize(48000.0, 64).unwrap();
+        panics.set_process_deadline(Some(Duration::from_secs(5)));
+        process_ones(&mut panics, 64).unwrap();
+        assert!(matches!(
+            process_ones(&mut panics, 64),
+            Err(Error::Panic(_))
+        ));
+        assert!(helper.is_alive());
+
+        drop((gain, panics, helper));
+        server.join().unwrap().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_missed_deadline_outputs_silence() {
+        let (helper, server) = thread_helper();
+        let mut slow = IsolatedPlugin::load(Arc::clone(&helper), &plugin("slow", "Acme")).unwrap();
+        slow.initialize(48000.0, 64).unwrap();
+        slow.set_process_deadline(Some(crate::test_util::SLOW_BLOCK / 4));
+
+        assert_eq!(process_ones(&mut slow, 64).unwrap(), vec![1.0; 64]);
+        // The second block takes too long: silence instead of a stall
+        let start = Instant::now();
+        assert_eq!(process_ones(&mut slow, 64).unwrap(), vec![0.0; 64]);
+        assert!(start.elapsed() < crate::test_util::SLOW_BLOCK);
+        assert_eq!(slow.missed_deadlines(), 1);
+        assert_eq!(slow.sample_position(), 128);
+
+        // Once the helper catches up, audio flows again
+        std::thread::sleep(crate::test_util::SLOW_BLOCK);
+        assert_eq!(process_ones(&mut slow, 64).unwrap(), vec![1.0; 64]);
+        assert_eq!(slow.missed_deadlines(), 1);
+
+        drop((slow, helper));
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_groups_share_helper_processes() {
+        let host = test_host();
+        let mut first = host
+            .load_with(&plugin("a", "Acme"), &Isolation::Group("acme".to_string()))
+            .unwrap();
+        let second = host
+            .load_with(&plugin("b", "Acme"), &Isolation::Group("acme".to_string()))
+            .unwrap();
+        let dedicated = host
+            .load_with(&plugin("c", "Other"), &Isolation::Dedicated)
+            .unwrap();
+
+        assert_eq!(first.helper_pid(), second.helper_pid());
+        assert_ne!(first.helper_pid(), dedicated.helper_pid());
+        assert_eq!(host.running_groups(), vec!["acme".to_string()]);
+
+        first.initialize(48000.0, 32).unwrap();
+        first.set_process_deadline(Some(Duration::from_secs(5)));
+        assert_eq!(process_ones(&mut first, 32).unwrap(), vec![1.0; 32]);
+
+        drop((first, second));
+        assert!(host.running_groups().is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_helper_affinity() {
+        let allowed = CpuSet::current().unwrap();
+        let core = CpuSet::new([allowed.cores()[allowed.cores().len() - 1]]);
+        let host = test_host().group_affinity("pinned", core.clone());
+        let plugin = host
+            .load_with(&plugin("p", "Acme"), &Isolation::Group("pinned".to_string()))
+            .unwrap();
+
+        // The helper's serving thread is pinned, whichever thread that is
+        let tasks = format!("/proc/{}/task", plugin.helper_pid().unwrap());
+        let expected = format!("Cpus_allowed_list:\t{}", core);
+        let pinned = std::fs::read_dir(tasks).unwrap().any(|task| {
+            let status = std::fs::read_to_string(task.unwrap().path().join("status"));
+            status.is_ok_and(|s| s.lines().any(|line| line == expected))
+        });
+        assert!(pinned);
+    }
+
+    #[test]
+    fn test_crash_report() {
+        let host = test_host();
+        let group = Isolation::Group("crashy".to_string());
+        let mut aborts = host.load_with(&plugin("aborts", "Acme"), &group).unwrap();
+        let neighbor = host.load_with(&plugin("neighbor", "Acme"), &group).unwrap();
+        aborts.initialize(48000.0, 32).unwrap();
+        aborts.set_process_deadline(Some(Duration::from_millis(500)));
+        process_ones(&mut aborts, 32).unwrap();
+
+        // The helper aborts on the second block; both plugins are gone after
+        let mut error = None;
+        for _ in 0..20 {
+            if let Err(e) = process_ones(&mut aborts, 32) {
+                error = Some(e);
+                break;
+            }
+        }
+        assert!(error
+            .unwrap()
+            .to_string()
+            .contains("aborts crashed during process"));
+        assert!(neighbor.get_parameter(0).is_err());
+
+        let report = neighbor.crash_report().unwrap();
+        assert_eq!(report.plugin.as_ref().unwrap().unique_id, "aborts");
+        assert_eq!(report.operation, Some("process"));
+        assert_eq!(report.plugins.len(), 2);
+        assert_eq!(report.pid, aborts.helper_pid());
+        #[cfg(unix)]
+        assert_eq!(report.signal_name(), Some("SIGABRT"));
+    }
+}
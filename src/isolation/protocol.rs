@@ -0,0 +1,887 @@
+//! Wire format between the host and its helper processes
+//!
+//! Every message is a little-endian `u32` length followed by that many bytes.
+//! Requests start with an opcode and the instance they address; responses
+//! start with a status byte, followed by the result or an encoded [`Error`].
+//!
+//! A helper announces itself with [`HELLO`] and [`PROTOCOL_VERSION`] before
+//! the first request, so the host can skip anything written to the pipe
+//! before (static initializers that print, test harness banners).
+
+use crate::cache::parse_format;
+use crate::meter::MeterReading;
+use crate::node::BlockContext;
+use crate::session::Reader;
+use crate::tempo::{FrameRate, LoopRange, TimeSignature, Timecode};
+use crate::{
+    AudioUnitFlags, CurrentPreset, Error, MidiEvent, MidiEventKind, ParameterChange,
+    ParameterCurve, ParameterInfo, ParameterVisibility, PluginInfo, PluginType, PresetInfo, Result,
+    Vst3FactoryInfo,
+};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+/// Written by a helper once it is ready for requests
+pub(crate) const HELLO: &[u8; 8] = b"RACKHELP";
+
+/// Version of this protocol, sent after [`HELLO`]
+pub(crate) const PROTOCOL_VERSION: u32 = 9;
+
+/// Largest message either side accepts
+const MAX_MESSAGE: usize = 1 << 30;
+
+/// Request opcodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub(crate) enum Op {
+    Load = 1,
+    Unload,
+    Initialize,
+    Reset,
+    Process,
+    SetSamplePosition,
+    FlushEvents,
+    ParameterInfo,
+    GetParameter,
+    SetParameter,
+    SendMidi,
+    PresetCount,
+    PresetInfo,
+    LoadPreset,
+    CurrentPreset,
+    GetState,
+    SetState,
+    AttachRing,
+    PluginMeter,
+    Latency,
+    Tail,
+    SetBypass,
+    Bypassed,
+    StateDirty,
+}
+
+impl Op {
+    pub(crate) fn from_u8(value: u8) -> Option<Self> {
+        const OPS: [Op; 24] = [
+            Op::Load,
+            Op::Unload,
+            Op::Initialize,
+            Op::Reset,
+            Op::Process,
+            Op::SetSamplePosition,
+            Op::FlushEvents,
+            Op::ParameterInfo,
+            Op::GetParameter,
+            Op::SetParameter,
+            Op::SendMidi,
+            Op::PresetCount,
+            Op::PresetInfo,
+            Op::LoadPreset,
+            Op::CurrentPreset,
+            Op::GetState,
+            Op::SetState,
+            Op::AttachRing,
+            Op::PluginMeter,
+            Op::Latency,
+            Op::Tail,
+            Op::SetBypass,
+            Op::Bypassed,
+            Op::StateDirty,
+        ];
+        OPS.iter().copied().find(|op| *op as u8 == value)
+    }
+
+    /// The call the request stands for, for crash reports
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Op::Load => "load",
+            Op::Unload => "unload",
+            Op::Initialize => "initialize",
+            Op::Reset => "reset",
+            Op::Process => "process",
+            Op::SetSamplePosition => "set_sample_position",
+            Op::FlushEvents => "flush_events",
+            Op::ParameterInfo => "parameter_info",
+            Op::GetParameter => "get_parameter",
+            Op::SetParameter => "set_parameter",
+            Op::SendMidi => "send_midi",
+            Op::PresetCount => "preset_count",
+            Op::PresetInfo => "preset_info",
+            Op::LoadPreset => "load_preset",
+            Op::CurrentPreset => "current_preset",
+            Op::GetState => "get_state",
+            Op::SetState => "set_state",
+            Op::AttachRing => "attach_ring",
+            Op::PluginMeter => "plugin_meter",
+            Op::Latency => "latency_samples",
+            Op::Tail => "tail_samples",
+            Op::SetBypass => "set_bypass",
+            Op::Bypassed => "is_bypassed",
+            Op::StateDirty => "take_state_dirty",
+        }
+    }
+}
+
+/// Start a request for `instance`
+pub(crate) fn request(out: &mut Vec<u8>, op: Op, instance: u32) {
+    out.clear();
+    out.push(op as u8);
+    out.extend_from_slice(&instance.to_le_bytes());
+}
+
+/// Start a successful response
+pub(crate) fn ok(out: &mut Vec<u8>) {
+    out.clear();
+    out.push(0);
+}
+
+/// Write a failed response
+pub(crate) fn err(out: &mut Vec<u8>, error: &Error) {
+    out.clear();
+    out.push(1);
+    write_error(out, error);
+}
+
+/// Split a response into its result, or the error it carries
+pub(crate) fn response(message: &[u8]) -> Result<Reader<'_>> {
+    let mut reader = Reader::new(message);
+    match reader.u8()? {
+        0 => Ok(reader),
+        _ => Err(read_error(&mut reader)?),
+    }
+}
+
+/// Write one length-prefixed message
+pub(crate) fn write_message(writer: &mut dyn Write, message: &[u8]) -> std::io::Result<()> {
+    let len = u32::try_from(message.len())
+        .ok()
+        .filter(|len| *len as usize <= MAX_MESSAGE)
+        .ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "message too large")
+        })?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(message)?;
+    writer.flush()
+}
+
+/// Read one length-prefixed message into `message`
+///
+/// Returns `Ok(false)` if the stream ended cleanly before a message.
+pub(crate) fn read_message(reader: &mut dyn Read, message: &mut Vec<u8>) -> std::io::Result<bool> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(false),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_MESSAGE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "message too large",
+        ));
+    }
+    message.clear();
+    message.resize(len, 0);
+    reader.read_exact(message)?;
+    Ok(true)
+}
+
+/// Skip output until [`HELLO`], then check the protocol version
+///
+/// Gives up after `max_skip` bytes that aren't the greeting.
+pub(crate) fn read_hello(reader: &mut dyn Read, max_skip: usize) -> std::io::Result<()> {
+    let mut window = [0u8; 8];
+    let mut seen = 0usize;
+    let mut byte = [0u8; 1];
+    while window != *HELLO {
+        if seen >= max_skip + HELLO.len() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "helper didn't greet",
+            ));
+        }
+        reader.read_exact(&mut byte)?;
+        window.copy_within(1.., 0);
+        window[7] = byte[0];
+        seen += 1;
+    }
+
+    let mut version = [0u8; 4];
+    reader.read_exact(&mut version)?;
+    let version = u32::from_le_bytes(version);
+    if version != PROTOCOL_VERSION {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "helper speaks protocol version {}, expected {}",
+                version, PROTOCOL_VERSION
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Write the greeting
+pub(crate) fn write_hello(writer: &mut dyn Write) -> std::io::Result<()> {
+    writer.write_all(HELLO)?;
+    writer.write_all(&PROTOCOL_VERSION.to_le_bytes())?;
+    writer.flush()
+}
+
+pub(crate) fn write_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+pub(crate) fn write_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+pub(crate) fn write_f32(out: &mut Vec<u8>, value: f32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+pub(crate) fn write_f64(out: &mut Vec<u8>, value: f64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Write a length-prefixed field
+///
+/// Fields of 4 GiB or more can't be framed: the length saturates, and the
+/// message is then too large for [`write_message()`], which refuses to send it.
+pub(crate) fn write_field(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_u32(out, u32::try_from(bytes.len()).unwrap_or(u32::MAX));
+    out.extend_from_slice(bytes);
+}
+
+pub(crate) fn write_str(out: &mut Vec<u8>, value: &str) {
+    write_field(out, value.as_bytes());
+}
+
+/// Write planar audio: channel count, frame count, then the samples
+pub(crate) fn write_audio(out: &mut Vec<u8>, channels: &[&[f32]], frames: usize) {
+    write_u32(out, channels.len() as u32);
+    write_u32(out, frames as u32);
+    for channel in channels {
+        for sample in &channel[..frames] {
+            write_f32(out, *sample);
+        }
+    }
+}
+
+/// Read planar audio written by [`write_audio()`] into `channels`
+///
+/// Channels beyond those in the message are zeroed; extra channels in the
+/// message are skipped. Returns the frame count.
+pub(crate) fn read_audio(reader: &mut Reader<'_>, channels: &mut [&mut [f32]]) -> Result<usize> {
+    let count = reader.u32()? as usize;
+    let frames = reader.u32()? as usize;
+    for ch in 0..count {
+        let samples = reader.take(frames * 4)?;
+        if let Some(channel) = channels.get_mut(ch) {
+            if channel.len() < frames {
+                return Err(Error::InvalidFormat(
+                    "Audio message larger than the buffer".to_string(),
+                ));
+            }
+            for (sample, bytes) in channel.iter_mut().zip(samples.chunks_exact(4)) {
+                *sample = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            }
+        }
+    }
+    for channel in channels.iter_mut().skip(count) {
+        let len = frames.min(channel.len());
+        channel[..len].fill(0.0);
+    }
+    Ok(frames)
+}
+
+pub(crate) fn write_plugin_info(out: &mut Vec<u8>, info: &PluginInfo) {
+    write_str(out, &info.name);
+    write_str(out, &info.manufacturer);
+    write_u32(out, info.version);
+    out.push(plugin_type_to_u8(info.plugin_type));
+    write_str(out, &info.path.to_string_lossy());
+    write_str(out, &info.canonical_path.to_string_lossy());
+    write_str(out, &info.unique_id);
+    write_str(out, &info.format.to_string());
+    match info.au_flags {
+        Some(flags) => {
+            out.push(1);
+            out.push(
+                flags.sandbox_safe as u8
+                    | (flags.is_v3 as u8) << 1
+                    | (flags.requires_async_instantiation as u8) << 2
+                    | (flags.can_load_in_process as u8) << 3,
+            );
+        }
+        None => out.push(0),
+    }
+    match &info.vst3_factory {
+        Some(factory) => {
+            out.push(1);
+            write_str(out, &factory.url);
+            write_str(out, &factory.email);
+            write_str(out, &factory.sdk_version);
+            out.push(
+                factory.distributable as u8
+                    | (factory.simple_mode_supported as u8) << 1
+                    | (factory.classes_discardable as u8) << 2
+                    | (factory.license_check as u8) << 3
+                    | (factory.component_non_discardable as u8) << 4
+                    | (factory.unicode as u8) << 5,
+            );
+        }
+        None => out.push(0),
+    }
+}
+
+pub(crate) fn read_plugin_info(reader: &mut Reader<'_>) -> Result<PluginInfo> {
+    let name = reader.string()?;
+    let manufacturer = reader.string()?;
+    let version = reader.u32()?;
+    let plugin_type = plugin_type_from_u8(reader.u8()?);
+    let path = PathBuf::from(reader.string()?);
+    let canonical_path = PathBuf::from(reader.string()?);
+    let unique_id = reader.string()?;
+    let format = reader.string()?;
+    let format = parse_format(&format)
+        .ok_or_else(|| Error::InvalidFormat(format!("Unknown plugin format '{}'", format)))?;
+
+    let mut info = PluginInfo::new(name, manufacturer, version, plugin_type, path, unique_id)
+        .with_canonical_path(canonical_path)
+        .with_format(format);
+    if reader.u8()? != 0 {
+        let bits = reader.u8()?;
+        info = info.with_au_flags(AudioUnitFlags {
+            sandbox_safe: bits & 1 != 0,
+            is_v3: bits & 2 != 0,
+            requires_async_instantiation: bits & 4 != 0,
+            can_load_in_process: bits & 8 != 0,
+        });
+    }
+    if reader.u8()? != 0 {
+        let url = reader.string()?;
+        let email = reader.string()?;
+        let sdk_version = reader.string()?;
+        let bits = reader.u8()?;
+        info = info.with_vst3_factory(Vst3FactoryInfo {
+            url,
+            email,
+            sdk_version,
+            distributable: bits & 1 != 0,
+            simple_mode_supported: bits & 2 != 0,
+            classes_discardable: bits & 4 != 0,
+            license_check: bits & 8 != 0,
+            component_non_discardable: bits & 16 != 0,
+            unicode: bits & 32 != 0,
+        });
+    }
+    Ok(info)
+}
+
+fn plugin_type_to_u8(plugin_type: PluginType) -> u8 {
+    match plugin_type {
+        PluginType::Effect => 0,
+        PluginType::Instrument => 1,
+        PluginType::Mixer => 2,
+        PluginType::FormatConverter => 3,
+        PluginType::Analyzer => 4,
+        PluginType::Spatial => 5,
+        PluginType::Other => 6,
+    }
+}
+
+fn plugin_type_from_u8(value: u8) -> PluginType {
+    match value {
+        0 => PluginType::Effect,
+        1 => PluginType::Instrument,
+        2 => PluginType::Mixer,
+        3 => PluginType::FormatConverter,
+        4 => PluginType::Analyzer,
+        5 => PluginType::Spatial,
+        _ => PluginType::Other,
+    }
+}
+
+pub(crate) fn write_parameter_info(out: &mut Vec<u8>, info: &ParameterInfo) {
+    write_u32(out, info.index as u32);
+    write_str(out, &info.name);
+    write_f32(out, info.min);
+    write_f32(out, info.max);
+    write_f32(out, info.default);
+    write_str(out, &info.unit);
+    out.push(match info.curve {
+        ParameterCurve::Linear => 0,
+        ParameterCurve::Logarithmic => 1,
+        ParameterCurve::Exponential => 2,
+        ParameterCurve::Squared => 3,
+        ParameterCurve::SquareRoot => 4,
+        ParameterCurve::Cubed => 5,
+        ParameterCurve::CubeRoot => 6,
+    });
+    out.push(match info.visibility {
+        ParameterVisibility::Visible => 0,
+        ParameterVisibility::Expert => 1,
+        ParameterVisibility::Hidden => 2,
+    });
+}
+
+pub(crate) fn read_parameter_info(reader: &mut Reader<'_>) -> Result<ParameterInfo> {
+    let index = reader.u32()? as usize;
+    let name = reader.string()?;
+    let min = reader.f32()?;
+    let max = reader.f32()?;
+    let default = reader.f32()?;
+    let unit = reader.string()?;
+    let mut info = ParameterInfo::new(index, name, min, max, default, unit);
+    info.curve = match reader.u8()? {
+        1 => ParameterCurve::Logarithmic,
+        2 => ParameterCurve::Exponential,
+        3 => ParameterCurve::Squared,
+        4 => ParameterCurve::SquareRoot,
+        5 => ParameterCurve::Cubed,
+        6 => ParameterCurve::CubeRoot,
+        _ => ParameterCurve::Linear,
+    };
+    info.visibility = match reader.u8()? {
+        1 => ParameterVisibility::Expert,
+        2 => ParameterVisibility::Hidden,
+        _ => ParameterVisibility::Visible,
+    };
+    Ok(info)
+}
+
+pub(crate) fn write_preset_info(out: &mut Vec<u8>, info: &PresetInfo) {
+    write_u32(out, info.index as u32);
+    write_str(out, &info.name);
+    write_u32(out, info.preset_number as u32);
+}
+
+pub(crate) fn read_preset_info(reader: &mut Reader<'_>) -> Result<PresetInfo> {
+    Ok(PresetInfo::new(
+        reader.u32()? as usize,
+        reader.string()?,
+        reader.u32()? as i32,
+    ))
+}
+
+pub(crate) fn write_current_preset(out: &mut Vec<u8>, preset: Option<&CurrentPreset>) {
+    match preset {
+        Some(preset) => {
+            out.push(1);
+            write_u32(out, preset.preset_number as u32);
+            write_str(out, &preset.name);
+        }
+        None => out.push(0),
+    }
+}
+
+pub(crate) fn read_current_preset(reader: &mut Reader<'_>) -> Result<Option<CurrentPreset>> {
+    if reader.u8()? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(CurrentPreset {
+        preset_number: reader.u32()? as i32,
+        name: reader.string()?,
+    }))
+}
+
+pub(crate) fn write_meter(out: &mut Vec<u8>, meter: Option<&[MeterReading]>) {
+    match meter {
+        Some(readings) => {
+            out.push(1);
+            write_u32(out, readings.len() as u32);
+            for reading in readings {
+                write_u32(out, reading.index as u32);
+                write_str(out, &reading.name);
+                write_f32(out, reading.value);
+                write_f32(out, reading.min);
+                write_f32(out, reading.max);
+                write_str(out, &reading.unit);
+            }
+        }
+        None => out.push(0),
+    }
+}
+
+pub(crate) fn read_meter(reader: &mut Reader<'_>) -> Result<Option<Vec<MeterReading>>> {
+    if reader.u8()? == 0 {
+        return Ok(None);
+    }
+    let count = reader.u32()? as usize;
+    let mut readings = Vec::with_capacity(count.min(1024));
+    for _ in 0..count {
+        readings.push(MeterReading {
+            index: reader.u32()? as usize,
+            name: reader.string()?,
+            value: reader.f32()?,
+            min: reader.f32()?,
+            max: reader.f32()?,
+            unit: reader.string()?,
+        });
+    }
+    Ok(Some(readings))
+}
+
+/// Write MIDI events as offset plus raw status and data bytes
+pub(crate) fn write_midi(out: &mut Vec<u8>, events: &[MidiEvent]) {
+    write_u32(out, events.len() as u32);
+    for event in events {
+        write_u32(out, event.sample_offset);
+        out.extend_from_slice(&event.to_bytes());
+    }
+}
+
+/// Read MIDI events written by [`write_midi()`] into `events`
+pub(crate) fn read_midi(reader: &mut Reader<'_>, events: &mut Vec<MidiEvent>) -> Result<()> {
+    events.clear();
+    for _ in 0..reader.u32()? {
+        let sample_offset = reader.u32()?;
+        let bytes = reader.take(3)?;
+        let (status, data1, data2) = (bytes[0], bytes[1], bytes[2]);
+        let channel = status & 0x0F;
+        let kind = match status {
+            0x80..=0x8F => MidiEventKind::NoteOff {
+                note: data1,
+                velocity: data2,
+                channel,
+            },
+            0x90..=0x9F => MidiEventKind::NoteOn {
+                note: data1,
+                velocity: data2,
+                channel,
+            },
+            0xA0..=0xAF => MidiEventKind::PolyphonicAftertouch {
+                note: data1,
+                pressure: data2,
+                channel,
+            },
+            0xB0..=0xBF => MidiEventKind::ControlChange {
+                controller: data1,
+                value: data2,
+                channel,
+            },
+            0xC0..=0xCF => MidiEventKind::ProgramChange {
+                program: data1,
+                channel,
+            },
+            0xD0..=0xDF => MidiEventKind::ChannelAftertouch {
+                pressure: data1,
+                channel,
+            },
+            0xE0..=0xEF => MidiEventKind::PitchBend {
+                value: u16::from(data1) | u16::from(data2) << 7,
+                channel,
+            },
+            0xF8 => MidiEventKind::TimingClock,
+            0xFA => MidiEventKind::Start,
+            0xFB => MidiEventKind::Continue,
+            0xFC => MidiEventKind::Stop,
+            0xFE => MidiEventKind::ActiveSensing,
+            0xFF => MidiEventKind::SystemReset,
+            _ => {
+                return Err(Error::InvalidFormat(format!(
+                    "Unknown MIDI status {:#04x}",
+                    status
+                )))
+            }
+        };
+        events.push(MidiEvent {
+            sample_offset,
+            kind,
+        });
+    }
+    Ok(())
+}
+
+/// Write what a block is processed with besides its audio: the
+/// [`BlockContext`] passed to `process_with_context()`, if any, and the
+/// parameter changes and MIDI passed to `process_with_events()`
+///
+/// At most `limit` bytes are written: changes take precedence over MIDI, and
+/// whatever doesn't fit is dropped.
+pub(crate) fn write_block(
+    out: &mut Vec<u8>,
+    context: Option<&BlockContext>,
+    changes: &[ParameterChange],
+    midi: &[MidiEvent],
+    limit: usize,
+) {
+    let start = out.len();
+    match context {
+        Some(context) => {
+            out.push(1);
+            write_f64(out, context.sample_rate);
+            write_u64(out, context.sample_position);
+            out.push(context.playing as u8);
+            write_f64(out, context.tempo);
+            write_u32(out, context.time_signature.numerator);
+            write_u32(out, context.time_signature.denominator);
+            write_f64(out, context.beat);
+            write_f64(out, context.bar_start);
+            write_u64(out, context.bar as u64);
+            match context.loop_range {
+                Some(range) => {
+                    out.push(1);
+                    write_f64(out, range.start);
+                    write_f64(out, range.end);
+                }
+                None => out.push(0),
+            }
+            out.push(frame_rate_code(context.frame_rate));
+            let offset = context.smpte_offset;
+            for field in [
+                offset.hours,
+                offset.minutes,
+                offset.seconds,
+                offset.frames,
+                offset.subframes,
+            ] {
+                write_u32(out, field);
+            }
+            out.push(context.is_offline as u8);
+        }
+        None => out.push(0),
+    }
+
+    // Two counts, then 12 bytes per change and 7 per MIDI event
+    let mut room = limit.saturating_sub(out.len() - start + 8);
+    let changes = &changes[..changes.len().min(room / 12)];
+    room -= changes.len() * 12;
+    let midi = &midi[..midi.len().min(room / 7)];
+
+    write_u32(out, changes.len() as u32);
+    for change in changes {
+        write_u32(out, change.index as u32);
+        write_f32(out, change.value);
+        write_u32(out, change.sample_offset);
+    }
+    write_midi(out, midi);
+}
+
+/// Frame rates in the order of their codes on the wire
+const FRAME_RATES: [FrameRate; 9] = [
+    FrameRate::Fps23976,
+    FrameRate::Fps24,
+    FrameRate::Fps25,
+    FrameRate::Fps2997,
+    FrameRate::Fps2997Drop,
+    FrameRate::Fps30,
+    FrameRate::Fps50,
+    FrameRate::Fps5994,
+    FrameRate::Fps60,
+];
+
+fn frame_rate_code(rate: FrameRate) -> u8 {
+    FRAME_RATES
+        .iter()
+        .position(|&r| r == rate)
+        .expect("every frame rate has a code") as u8
+}
+
+fn frame_rate_from_code(code: u8) -> Result<FrameRate> {
+    FRAME_RATES
+        .get(code as usize)
+        .copied()
+        .ok_or_else(|| Error::InvalidFormat(format!("Unknown frame rate {}", code)))
+}
+
+/// Read what [`write_block()`] wrote for a block of `num_frames` frames,
+/// filling `changes` and `midi`
+pub(crate) fn read_block(
+    reader: &mut Reader<'_>,
+    num_frames: usize,
+    changes: &mut Vec<ParameterChange>,
+    midi: &mut Vec<MidiEvent>,
+) -> Result<Option<BlockContext>> {
+    let context = match reader.u8()? {
+        0 => None,
+        _ => Some(BlockContext {
+            sample_rate: reader.f64()?,
+            sample_position: reader.u64()?,
+            num_frames,
+            playing: reader.u8()? != 0,
+            tempo: reader.f64()?,
+            time_signature: TimeSignature {
+                numerator: reader.u32()?,
+                denominator: reader.u32()?,
+            },
+            beat: reader.f64()?,
+            bar_start: reader.f64()?,
+            bar: reader.u64()? as i64,
+            loop_range: match reader.u8()? {
+                0 => None,
+                _ => Some(LoopRange {
+                    start: reader.f64()?,
+                    end: reader.f64()?,
+                }),
+            },
+            frame_rate: frame_rate_from_code(reader.u8()?)?,
+            smpte_offset: Timecode {
+                hours: reader.u32()?,
+                minutes: reader.u32()?,
+                seconds: reader.u32()?,
+                frames: reader.u32()?,
+                subframes: reader.u32()?,
+            },
+            is_offline: reader.u8()? != 0,
+        }),
+    };
+
+    changes.clear();
+    for _ in 0..reader.u32()? {
+        changes.push(ParameterChange {
+            index: reader.u32()? as usize,
+            value: reader.f32()?,
+            sample_offset: reader.u32()?,
+        });
+    }
+    read_midi(reader, midi)?;
+    Ok(context)
+}
+
+/// Write an error for [`read_error()`]
+pub(crate) fn write_error(out: &mut Vec<u8>, error: &Error) {
+    match error {
+        Error::AudioUnit(status) => {
+            out.push(1);
+            write_u32(out, *status as u32);
+        }
+        Error::PluginNotFound(message) => {
+            out.push(2);
+            write_str(out, message);
+        }
+        Error::InvalidParameter(index) => {
+            out.push(3);
+            write_u64(out, *index as u64);
+        }
+        Error::NotInitialized => out.push(4),
+        Error::InvalidFormat(message) => {
+            out.push(5);
+            write_str(out, message);
+        }
+        Error::BlockTooLarge { max, got } => {
+            out.push(6);
+            write_u64(out, *max as u64);
+            write_u64(out, *got as u64);
+        }
+        Error::Panic(message) => {
+            out.push(7);
+            write_str(out, message);
+        }
+        // I/O errors can't cross the process boundary as they are
+        Error::Io(_) | Error::Other(_) => {
+            out.push(0);
+            write_str(out, &error.to_string());
+        }
+    }
+}
+
+/// Read an error written by [`write_error()`]
+pub(crate) fn read_error(reader: &mut Reader<'_>) -> Result<Error> {
+    Ok(match reader.u8()? {
+        1 => Error::AudioUnit(reader.u32()? as i32),
+        2 => Error::PluginNotFound(reader.string()?),
+        3 => Error::InvalidParameter(reader.u64()? as usize),
+        4 => Error::NotInitialized,
+        5 => Error::InvalidFormat(reader.string()?),
+        6 => Error::BlockTooLarge {
+            max: reader.u64()? as usize,
+            got: reader.u64()? as usize,
+        },
+        7 => Error::Panic(reader.string()?),
+        _ => Error::Other(reader.string()?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips() {
+        let info = PluginInfo::new(
+            "Verb".to_string(),
+            "Acme".to_string(),
+            3,
+            PluginType::Instrument,
+            PathBuf::from("/plugins/Verb.vst3"),
+            "acme-verb".to_string(),
+        )
+        .with_format(crate::PluginFormat::Vst3)
+        .with_vst3_factory(Vst3FactoryInfo {
+            url: "https://acme.example".to_string(),
+            sdk_version: "VST 3.7.9".to_string(),
+            distributable: true,
+            unicode: true,
+            ..Vst3FactoryInfo::default()
+        });
+        let events = [
+            MidiEvent::note_on(60, 100, 3, 12),
+            MidiEvent::pitch_bend(12345, 15, 0),
+            MidiEvent::system_reset(7),
+        ];
+
+        let mut out = Vec::new();
+        ok(&mut out);
+        write_plugin_info(&mut out, &info);
+        write_midi(&mut out, &events);
+
+        let mut reader = response(&out).unwrap();
+        let decoded = read_plugin_info(&mut reader).unwrap();
+        assert_eq!(decoded.unique_id, "acme-verb");
+        assert_eq!(decoded.plugin_type, PluginType::Instrument);
+        assert_eq!(decoded.format, crate::PluginFormat::Vst3);
+        assert_eq!(decoded.vst3_factory, info.vst3_factory);
+        let mut decoded_events = Vec::new();
+        read_midi(&mut reader, &mut decoded_events).unwrap();
+        assert_eq!(decoded_events, events);
+        assert!(reader.is_empty());
+
+        let mut context = BlockContext::new(48000.0, 96000, 64).offline(true);
+        context.frame_rate = FrameRate::Fps2997Drop;
+        context.smpte_offset = Timecode::new(1, 0, 0, 0);
+        out.clear();
+        let changes = [
+            ParameterChange::new(2, 0.25, 16),
+            ParameterChange::new(0, 1.0, 63),
+        ];
+        write_block(&mut out, Some(&context), &[], &[], usize::MAX);
+        write_block(&mut out, None, &changes, &events, usize::MAX);
+        let (mut decoded_changes, mut decoded_midi) = (Vec::new(), Vec::new());
+        let mut reader = Reader::new(&out);
+        let mut read = |reader: &mut Reader<'_>| {
+            read_block(reader, 64, &mut decoded_changes, &mut decoded_midi).unwrap()
+        };
+        assert_eq!(read(&mut reader), Some(context));
+        assert_eq!(read(&mut reader), None);
+        assert!(reader.is_empty());
+        assert_eq!(decoded_changes, changes);
+        assert_eq!(decoded_midi, events);
+
+        // What doesn't fit the limit is dropped, MIDI before changes
+        out.clear();
+        write_block(&mut out, None, &changes, &events, 1 + 8 + 12);
+        assert_eq!(out.len(), 1 + 8 + 12);
+        let mut reader = Reader::new(&out);
+        read_block(&mut reader, 64, &mut decoded_changes, &mut decoded_midi).unwrap();
+        assert_eq!(decoded_changes, changes[..1]);
+        assert!(decoded_midi.is_empty());
+
+        err(&mut out, &Error::BlockTooLarge { max: 64, got: 128 });
+        assert!(matches!(
+            response(&out),
+            Err(Error::BlockTooLarge { max: 64, got: 128 })
+        ));
+    }
+
+    #[test]
+    fn test_hello_skips_leading_output() {
+        let mut stream = b"running 1 test\n".to_vec();
+        write_hello(&mut stream).unwrap();
+        read_hello(&mut stream.as_slice(), 1024).unwrap();
+
+        assert!(read_hello(&mut &b"no greeting here"[..], 4).is_err());
+    }
+}
@@ -0,0 +1,564 @@
+//! Shared-memory audio transport
+//!
+//! Sending audio through a helper's pipe copies every block through the
+//! kernel twice and blocks the audio thread until the helper answers. On Unix,
+//! an initialized [`IsolatedPlugin`](super::IsolatedPlugin) instead exchanges
+//! audio through a ring of block slots in memory shared with its helper:
+//!
+//! - The host writes a block's input, and the context it is processed with,
+//!   into the next slot and publishes the block's sequence number. A worker thread in the helper processes slots in
+//!   order, writes the output over the input and publishes the sequence number
+//!   it finished.
+//! - The host waits for its block until the block's deadline. If the helper
+//!   misses it, the host outputs silence for the block and emits
+//!   [`HostEvent::Xrun`](crate::events::HostEvent::Xrun) rather than stalling
+//!   the audio thread. The late output is discarded.
+//! - If the helper falls so far behind that every slot is taken, blocks are
+//!   dropped without being sent (again silence and an xrun) until it catches
+//!   up.
+//!
+//! Both sides wait by polling (spinning briefly, then yielding, then
+//! sleeping), so the fast path makes no system calls.
+
+use super::protocol;
+use crate::session::Reader;
+use crate::{Error, Result};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Number of block slots in a ring
+pub(crate) const DEFAULT_SLOTS: usize = 4;
+
+/// Longest a waiting side sleeps between polls
+const MAX_SLEEP: Duration = Duration::from_micros(100);
+
+const MAGIC: u32 = u32::from_le_bytes(*b"RKSH");
+const VERSION: u32 = 2;
+
+// Ring header layout
+const HEADER_SIZE: usize = 64;
+const H_MAGIC: usize = 0;
+const H_VERSION: usize = 4;
+const H_SLOTS: usize = 8;
+const H_CAPACITY: usize = 12;
+const H_CHANNELS: usize = 16;
+const H_REQUESTED: usize = 24;
+const H_COMPLETED: usize = 32;
+const H_SHUTDOWN: usize = 40;
+
+// Slot layout: header, error area, block area, then planar audio
+const SLOT_HEADER_SIZE: usize = 64;
+const S_SEQ: usize = 0;
+const S_FRAMES: usize = 8;
+const S_INPUTS: usize = 12;
+const S_OUTPUTS: usize = 16;
+const S_STATUS: usize = 20;
+const S_ERROR_LEN: usize = 24;
+const S_BLOCK_LEN: usize = 28;
+const ERROR_SIZE: usize = 448;
+const BLOCK_OFFSET: usize = SLOT_HEADER_SIZE + ERROR_SIZE;
+/// Room for what a block is processed with besides its audio
+pub(crate) const BLOCK_SIZE: usize = 8192;
+const AUDIO_OFFSET: usize = BLOCK_OFFSET + BLOCK_SIZE;
+
+/// A ring of audio block slots in shared memory
+///
+/// The sequence numbers give one side at a time access to a slot: the host
+/// owns slots whose block it hasn't published, the helper owns published
+/// slots until it completes them.
+pub(crate) struct Ring {
+    ptr: *mut u8,
+    len: usize,
+    path: PathBuf,
+    slots: usize,
+    capacity: usize,
+    channels: usize,
+    /// Whether this side created the file (and removes it if still there)
+    owner: bool,
+}
+
+// Safety: the mapping is shared memory that's only accessed through the
+// atomics in its header or under the slot protocol above
+unsafe impl Send for Ring {}
+unsafe impl Sync for Ring {}
+
+impl Ring {
+    /// Create a ring for blocks of up to `capacity` frames of `channels`
+    /// channels
+    pub(crate) fn create(slots: usize, capacity: usize, channels: usize) -> Result<Self> {
+        let slots = slots.max(2);
+        let len = HEADER_SIZE + slots * slot_size(capacity, channels);
+        let (path, ptr) = sys::create(len)?;
+        let ring = Self {
+            ptr,
+            len,
+            path,
+            slots,
+            capacity,
+            channels,
+            owner: true,
+        };
+        ring.set_u32(H_MAGIC, MAGIC);
+        ring.set_u32(H_VERSION, VERSION);
+        ring.set_u32(H_SLOTS, slots as u32);
+        ring.set_u32(H_CAPACITY, capacity as u32);
+        ring.set_u32(H_CHANNELS, channels as u32);
+        Ok(ring)
+    }
+
+    /// Map a ring created by the other side
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        let (ptr, len) = sys::open(path)?;
+        let mut ring = Self {
+            ptr,
+            len,
+            path: path.to_path_buf(),
+            slots: 0,
+            capacity: 0,
+            channels: 0,
+            owner: false,
+        };
+        if len < HEADER_SIZE || ring.u32_at(H_MAGIC) != MAGIC || ring.u32_at(H_VERSION) != VERSION {
+            return Err(Error::InvalidFormat(
+                "Not an audio ring or unsupported version".to_string(),
+            ));
+        }
+        ring.slots = ring.u32_at(H_SLOTS) as usize;
+        ring.capacity = ring.u32_at(H_CAPACITY) as usize;
+        ring.channels = ring.u32_at(H_CHANNELS) as usize;
+        if ring.slots < 2
+            || HEADER_SIZE + ring.slots * slot_size(ring.capacity, ring.channels) != len
+        {
+            return Err(Error::InvalidFormat("Audio ring size mismatch".to_string()));
+        }
+        Ok(ring)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Remove the ring's file once both sides have mapped it
+    pub(crate) fn unlink(&mut self) {
+        if self.owner {
+            let _ = std::fs::remove_file(&self.path);
+            self.owner = false;
+        }
+    }
+
+    pub(crate) fn slots(&self) -> usize {
+        self.slots
+    }
+
+    /// Sequence number of the last block the host published
+    pub(crate) fn requested(&self) -> &AtomicU64 {
+        self.atomic_u64(H_REQUESTED)
+    }
+
+    /// Sequence number of the last block the helper completed
+    pub(crate) fn completed(&self) -> &AtomicU64 {
+        self.atomic_u64(H_COMPLETED)
+    }
+
+    /// Ask the helper's worker to stop
+    pub(crate) fn shut_down(&self) {
+        self.atomic_u32(H_SHUTDOWN).store(1, Ordering::Release);
+    }
+
+    pub(crate) fn is_shut_down(&self) -> bool {
+        self.atomic_u32(H_SHUTDOWN).load(Ordering::Acquire) != 0
+    }
+
+    /// Write a block's input into its slot (host side, before publishing)
+    ///
+    /// `block` is what [`protocol::write_block()`] wrote for the block, at
+    /// most [`BLOCK_SIZE`] bytes.
+    pub(crate) fn write_input(&self, seq: u64, inputs: &[&[f32]], frames: usize, block: &[u8]) {
+        debug_assert!(block.len() <= BLOCK_SIZE);
+        let slot = self.slot(seq);
+        let inputs = &inputs[..inputs.len().min(self.channels)];
+        let block = &block[..block.len().min(BLOCK_SIZE)];
+        self.set_u64(slot + S_SEQ, seq);
+        self.set_u32(slot + S_FRAMES, frames as u32);
+        self.set_u32(slot + S_INPUTS, inputs.len() as u32);
+        self.set_u32(slot + S_BLOCK_LEN, block.len() as u32);
+        // Safety: the block area is BLOCK_SIZE bytes inside the slot
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                block.as_ptr(),
+                self.ptr.add(slot + BLOCK_OFFSET),
+                block.len(),
+            );
+        }
+        for (ch, input) in inputs.iter().enumerate() {
+            self.copy_in(slot, ch, &input[..frames]);
+        }
+    }
+
+    /// Read a published block's input and block area (helper side)
+    ///
+    /// Returns the frame count, or `None` if the slot doesn't hold the block.
+    pub(crate) fn read_input(
+        &self,
+        seq: u64,
+        inputs: &mut Vec<Vec<f32>>,
+        block: &mut Vec<u8>,
+    ) -> Option<usize> {
+        let slot = self.slot(seq);
+        let frames = self.u32_at(slot + S_FRAMES) as usize;
+        let count = self.u32_at(slot + S_INPUTS) as usize;
+        let block_len = self.u32_at(slot + S_BLOCK_LEN) as usize;
+        if self.u64_at(slot + S_SEQ) != seq
+            || frames > self.capacity
+            || count > self.channels
+            || block_len > BLOCK_SIZE
+        {
+            return None;
+        }
+        block.clear();
+        // Safety: the block area is BLOCK_SIZE bytes inside the slot
+        block.extend_from_slice(unsafe {
+            std::slice::from_raw_parts(self.ptr.add(slot + BLOCK_OFFSET), block_len)
+        });
+        inputs.resize_with(count, Vec::new);
+        for (ch, input) in inputs.iter_mut().enumerate() {
+            input.clear();
+            input.resize(frames, 0.0);
+            self.copy_out(slot, ch, input);
+        }
+        Some(frames)
+    }
+
+    /// Write a block's output into its slot (helper side, before completing)
+    pub(crate) fn write_output(&self, seq: u64, outputs: &[&[f32]], frames: usize) {
+        let slot = self.slot(seq);
+        let outputs = &outputs[..outputs.len().min(self.channels)];
+        self.set_u32(slot + S_STATUS, 0);
+        self.set_u32(slot + S_OUTPUTS, outputs.len() as u32);
+        for (ch, output) in outputs.iter().enumerate() {
+            self.copy_in(slot, ch, &output[..frames]);
+        }
+    }
+
+    /// Record that processing a block failed (helper side)
+    pub(crate) fn write_error(&self, seq: u64, error: &Error) {
+        let slot = self.slot(seq);
+        let mut encoded = Vec::new();
+        protocol::write_error(&mut encoded, error);
+        if encoded.len() > ERROR_SIZE {
+            // Keep the kind of error, lose the details
+            encoded.clear();
+            protocol::write_error(
+                &mut encoded,
+                &Error::Other("Plugin failed to process a block".to_string()),
+            );
+        }
+        // Safety: the error area is ERROR_SIZE bytes inside the slot
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                encoded.as_ptr(),
+                self.ptr.add(slot + SLOT_HEADER_SIZE),
+                encoded.len(),
+            );
+        }
+        self.set_u32(slot + S_ERROR_LEN, encoded.len() as u32);
+        self.set_u32(slot + S_STATUS, 1);
+    }
+
+    /// Read a completed block's output (host side)
+    ///
+    /// Output channels the helper didn't write are zeroed.
+    pub(crate) fn read_output(
+        &self,
+        seq: u64,
+        outputs: &mut [&mut [f32]],
+        frames: usize,
+    ) -> Result<()> {
+        let slot = self.slot(seq);
+        if self.u32_at(slot + S_STATUS) != 0 {
+            let len = (self.u32_at(slot + S_ERROR_LEN) as usize).min(ERROR_SIZE);
+            // Safety: the error area is ERROR_SIZE bytes inside the slot
+            let encoded =
+                unsafe { std::slice::from_raw_parts(self.ptr.add(slot + SLOT_HEADER_SIZE), len) };
+            return Err(protocol::read_error(&mut Reader::new(encoded))
+                .unwrap_or_else(|_| Error::Other("Plugin failed to process a block".to_string())));
+        }
+        let count = self.u32_at(slot + S_OUTPUTS) as usize;
+        for (ch, output) in outputs.iter_mut().enumerate() {
+            let output = &mut output[..frames];
+            if ch < count {
+                self.copy_out(slot, ch, output);
+            } else {
+                output.fill(0.0);
+            }
+        }
+        Ok(())
+    }
+
+    /// Byte offset of the slot holding block `seq`
+    fn slot(&self, seq: u64) -> usize {
+        HEADER_SIZE + (seq % self.slots as u64) as usize * slot_size(self.capacity, self.channels)
+    }
+
+    fn channel_ptr(&self, slot: usize, ch: usize) -> *mut f32 {
+        debug_assert!(ch < self.channels);
+        // Safety: channel ch of a slot lies within the mapping
+        unsafe { self.ptr.add(slot + AUDIO_OFFSET + ch * self.capacity * 4) as *mut f32 }
+    }
+
+    fn copy_in(&self, slot: usize, ch: usize, samples: &[f32]) {
+        let len = samples.len().min(self.capacity);
+        // Safety: the channel holds capacity samples
+        unsafe { std::ptr::copy_nonoverlapping(samples.as_ptr(), self.channel_ptr(slot, ch), len) }
+    }
+
+    fn copy_out(&self, slot: usize, ch: usize, samples: &mut [f32]) {
+        let len = samples.len().min(self.capacity);
+        // Safety: the channel holds capacity samples
+        unsafe {
+            std::ptr::copy_nonoverlapping(self.channel_ptr(slot, ch), samples.as_mut_ptr(), len)
+        }
+    }
+
+    fn u32_at(&self, offset: usize) -> u32 {
+        self.atomic_u32(offset).load(Ordering::Relaxed)
+    }
+
+    fn set_u32(&self, offset: usize, value: u32) {
+        self.atomic_u32(offset).store(value, Ordering::Relaxed);
+    }
+
+    fn u64_at(&self, offset: usize) -> u64 {
+        self.atomic_u64(offset).load(Ordering::Relaxed)
+    }
+
+    fn set_u64(&self, offset: usize, value: u64) {
+        self.atomic_u64(offset).store(value, Ordering::Relaxed);
+    }
+
+    fn atomic_u32(&self, offset: usize) -> &AtomicU32 {
+        debug_assert!(offset + 4 <= self.len);
+        // Safety: offsets are aligned and inside the page-aligned mapping
+        unsafe { &*(self.ptr.add(offset) as *const AtomicU32) }
+    }
+
+    fn atomic_u64(&self, offset: usize) -> &AtomicU64 {
+        debug_assert!(offset + 8 <= self.len);
+        // Safety: offsets are aligned and inside the page-aligned mapping
+        unsafe { &*(self.ptr.add(offset) as *const AtomicU64) }
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        self.unlink();
+        sys::unmap(self.ptr, self.len);
+    }
+}
+
+/// Size of one slot, rounded up to keep slots cache-line aligned
+fn slot_size(capacity: usize, channels: usize) -> usize {
+    (AUDIO_OFFSET + capacity * channels * 4 + 63) & !63
+}
+
+/// Wait until `value` reaches `target`
+///
+/// Returns `false` if `deadline` passes or `stop` returns `true` first.
+pub(crate) fn wait_for(
+    value: &AtomicU64,
+    target: u64,
+    deadline: Option<Instant>,
+    stop: impl Fn() -> bool,
+) -> bool {
+    let mut sleep = Duration::from_micros(20);
+    let mut round = 0u32;
+    loop {
+        if value.load(Ordering::Acquire) >= target {
+            return true;
+        }
+        if stop() {
+            return false;
+        }
+        let remaining = match deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(remaining) if !remaining.is_zero() => Some(remaining),
+                _ => return false,
+            },
+            None => None,
+        };
+        if round < 64 {
+            std::hint::spin_loop();
+        } else if round < 128 {
+            std::thread::yield_now();
+        } else {
+            std::thread::sleep(remaining.map_or(sleep, |remaining| remaining.min(sleep)));
+            sleep = (sleep * 2).min(MAX_SLEEP);
+        }
+        round = round.saturating_add(1);
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use crate::Result;
+    use std::ffi::{c_int, c_long, c_void};
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    const PROT_READ: c_int = 0x1;
+    const PROT_WRITE: c_int = 0x2;
+    const MAP_SHARED: c_int = 0x1;
+    const MAP_FAILED: *mut c_void = !0usize as *mut c_void;
+
+    extern "C" {
+        fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: c_long,
+        ) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    }
+
+    /// Create a zeroed shared file of `len` bytes and map it
+    pub(super) fn create(len: usize) -> Result<(PathBuf, *mut u8)> {
+        static NEXT: AtomicU32 = AtomicU32::new(0);
+
+        // Memory-backed where available
+        let dir = Path::new("/dev/shm");
+        let dir = if dir.is_dir() {
+            dir.to_path_buf()
+        } else {
+            std::env::temp_dir()
+        };
+        let path = dir.join(format!(
+            "rack-audio-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)?;
+        let mapped = file
+            .set_len(len as u64)
+            .map_err(Into::into)
+            .and_then(|()| map(&file, len));
+        match mapped {
+            Ok(ptr) => Ok((path, ptr)),
+            Err(e) => {
+                let _ = std::fs::remove_file(&path);
+                Err(e)
+            }
+        }
+    }
+
+    /// Map an existing shared file
+    pub(super) fn open(path: &Path) -> Result<(*mut u8, usize)> {
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)?;
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            return Err(crate::Error::InvalidFormat("Empty audio ring".to_string()));
+        }
+        Ok((map(&file, len)?, len))
+    }
+
+    fn map(file: &std::fs::File, len: usize) -> Result<*mut u8> {
+        // Safety: fd is open for reading and writing and len is non-zero
+        let ptr = unsafe {
+            mmap(
+                std::ptr::null_mut(),
+                len,
+                PROT_READ | PROT_WRITE,
+                MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == MAP_FAILED {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(ptr as *mut u8)
+    }
+
+    pub(super) fn unmap(ptr: *mut u8, len: usize) {
+        // Safety: ptr and len come from a successful map()
+        unsafe {
+            munmap(ptr as *mut c_void, len);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    use crate::{Error, Result};
+    use std::path::{Path, PathBuf};
+
+    fn unsupported() -> Error {
+        Error::Other("Shared audio rings are not supported on this platform".to_string())
+    }
+
+    pub(super) fn create(_len: usize) -> Result<(PathBuf, *mut u8)> {
+        Err(unsupported())
+    }
+
+    pub(super) fn open(_path: &Path) -> Result<(*mut u8, usize)> {
+        Err(unsupported())
+    }
+
+    pub(super) fn unmap(_ptr: *mut u8, _len: usize) {}
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_round_trip() {
+        let mut host = Ring::create(DEFAULT_SLOTS, 64, 2).unwrap();
+        let helper = Ring::open(host.path()).unwrap();
+        host.unlink();
+        assert!(!host.path().exists());
+
+        let input = vec![0.5f32; 32];
+        host.write_input(1, &[&input, &input], 32, b"context");
+        host.requested().store(1, Ordering::Release);
+
+        assert!(wait_for(helper.requested(), 1, None, || false));
+        let mut inputs = Vec::new();
+        let mut block = Vec::new();
+        assert_eq!(helper.read_input(1, &mut inputs, &mut block), Some(32));
+        assert_eq!(inputs[1], input);
+        assert_eq!(block, b"context");
+        let doubled: Vec<f32> = inputs[0].iter().map(|s| s * 2.0).collect();
+        helper.write_output(1, &[&doubled], 32);
+        helper.completed().store(1, Ordering::Release);
+
+        // Nothing completes block 2 before its deadline
+        let deadline = Instant::now() + Duration::from_millis(5);
+        assert!(!wait_for(host.completed(), 2, Some(deadline), || false));
+
+        let mut left = vec![0.0f32; 32];
+        let mut right = vec![9.0f32; 32];
+        host.read_output(1, &mut [&mut left, &mut right], 32)
+            .unwrap();
+        assert_eq!(left, vec![1.0; 32]);
+        assert_eq!(right, vec![0.0; 32]);
+
+        helper.write_error(2, &Error::BlockTooLarge { max: 64, got: 65 });
+        assert!(matches!(
+            host.read_output(2, &mut [&mut left], 32),
+            Err(Error::BlockTooLarge { max: 64, got: 65 })
+        ));
+    }
+}
@@ -39,28 +39,109 @@
 //!
 //! - **AudioUnit support** (macOS, iOS) - built-in
 //! - **VST3 support** (Windows, macOS, Linux) - built-in
-//! - **CLAP support** - coming soon
+//! - **CLAP support** (Windows, macOS, Linux) - built-in
 //! - **cpal integration** - optional, enable with `cpal` feature
+//! - **MIDI clock to hardware** - optional, enable with `midir` feature
 //!
 //! ## Platform Support
 //!
-//! - **macOS**: AudioUnit (default) and VST3
+//! - **macOS**: AudioUnit (default), VST3 and CLAP
 //! - **iOS**: AudioUnit only (VST3 not available on mobile)
-//! - **Windows**: VST3 (default)
-//! - **Linux**: VST3 (default)
+//! - **Windows**: VST3 (default) and CLAP
+//! - **Linux**: VST3 (default) and CLAP
+//! - **WebAssembly**: simulated plugins only (see [`mock`]), for developing
+//!   frontends in the browser
 //!
 //! AudioUnit provides the best integration on Apple platforms (native GUI support).
 //! VST3 is the default on Windows and Linux, and also available on macOS.
 
+// Helpers shared by the native backends go unused on WebAssembly
+#![cfg_attr(target_family = "wasm", allow(dead_code))]
+
+pub mod affinity;
+pub mod aggregate;
+pub mod analysis;
+#[cfg(feature = "async")]
+pub mod async_scan;
+pub mod audition;
+pub mod autosave;
+pub mod bypass;
+pub mod cache;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod clipboard;
+pub mod clock;
+pub mod crossfade;
+pub mod deferred;
+pub mod denormal;
+pub mod dirty;
 pub mod error;
+pub mod events;
+pub mod flight;
+pub mod gain;
+pub mod generator;
+pub mod graph;
+pub mod guard;
+pub mod host;
+pub mod humanize;
+pub mod identity;
+pub mod isolation;
+pub mod listen;
+pub mod metadata;
+pub mod meter;
 pub mod midi;
+pub mod mmap;
+pub mod mock;
+pub mod mute;
+pub mod node;
+#[cfg(feature = "cpal")]
+pub mod output;
+pub mod param;
+pub mod param_cache;
+pub mod paths;
 pub mod plugin_info;
+pub mod port;
+pub mod preflight;
+pub mod preview;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod quirks;
+pub mod render;
+pub mod resample;
+pub mod sandbox;
+pub mod scan;
+pub mod session;
+pub mod split;
+pub mod storage;
+pub mod tempo;
+pub mod text;
+pub mod throttle;
 pub mod traits;
+pub mod unified;
+pub mod voices;
+pub mod volume;
+
+// Runtime library loading for the CLAP and LV2 backends (not on WebAssembly,
+// which can't load native libraries)
+#[cfg(not(any(
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "watchos",
+    target_os = "visionos",
+    target_family = "wasm"
+)))]
+mod dylib;
+#[cfg(test)]
+mod test_util;
 
 pub use error::{Error, Result};
 pub use midi::{MidiEvent, MidiEventKind};
-pub use plugin_info::{ParameterInfo, PluginInfo, PluginType, PresetInfo};
-pub use traits::{PluginInstance, PluginScanner};
+pub use param::{Normalized, ParameterChange, ParameterCurve, ParameterEdit, Plain};
+pub use plugin_info::{
+    AudioUnitFlags, CurrentPreset, ParameterInfo, ParameterVisibility, PluginFormat, PluginInfo,
+    PluginType, PresetInfo, Vst3FactoryInfo,
+};
+pub use traits::{BoxedPlugin, PluginInstance, PluginScanner};
 
 // Platform-specific implementations
 // AudioUnit is available on both macOS and iOS
@@ -79,6 +160,47 @@ pub mod au;
 ))]
 pub mod vst3;
 
+// CLAP is a plain C ABI loaded at runtime, so it needs no SDK; not available
+// on mobile platforms or WebAssembly, where plugins can't be loaded from
+// libraries
+#[cfg(not(any(
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "watchos",
+    target_os = "visionos",
+    target_family = "wasm"
+)))]
+pub mod clap;
+
+// LV2 is read from Turtle data and a plain C ABI loaded at runtime, so it
+// needs no SDK or lilv; desktop platforms only, like CLAP
+#[cfg(not(any(
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "watchos",
+    target_os = "visionos",
+    target_family = "wasm"
+)))]
+pub mod lv2;
+
+// VST2 is opt-in (the "vst2" feature) and built against the user's own SDK
+// headers, as Steinberg no longer licenses it; desktop platforms only
+// The "vst2_sdk" cfg is set by build.rs when VST2_SDK_PATH points to them
+#[cfg(all(
+    vst2_sdk,
+    not(target_os = "ios"),
+    not(target_os = "tvos"),
+    not(target_os = "watchos"),
+    not(target_os = "visionos")
+))]
+pub mod vst2;
+
+// Ableton Link is opt-in (the "link" feature) and built from the user's own
+// checkout of the SDK; desktop platforms only
+// The "link_sdk" cfg is set by build.rs when LINK_SDK_PATH points to it
+#[cfg(link_sdk)]
+pub mod link;
+
 // Re-export the default scanner and plugin types for the platform
 // On Apple platforms, default to AudioUnit (better integration, GUI support)
 #[cfg(target_vendor = "apple")]
@@ -95,11 +217,17 @@ pub use au::{AudioUnitGui, AudioUnitPlugin as Plugin, AudioUnitScanner as Scanne
 ))]
 pub use vst3::{Vst3Plugin as Plugin, Vst3Scanner as Scanner};
 
+// On WebAssembly, where native plugins can't be loaded, simulated ones stand
+// in so frontends can be developed in the browser
+#[cfg(target_family = "wasm")]
+pub use mock::{MockPlugin as Plugin, MockScanner as Scanner};
+
 /// Prelude module for convenient imports
 pub mod prelude {
     pub use crate::{
-        Error, MidiEvent, MidiEventKind, ParameterInfo, PluginInfo, PluginInstance,
-        PluginScanner, PluginType, PresetInfo, Result,
+        BoxedPlugin, Error, MidiEvent, MidiEventKind, Normalized, ParameterChange, ParameterCurve, ParameterEdit, ParameterInfo,
+        ParameterVisibility, Plain, PluginFormat, PluginInfo, PluginInstance, PluginScanner,
+        PluginType, PresetInfo, Result,
     };
 
     // Platform-specific exports
@@ -116,4 +244,8 @@ pub mod prelude {
         not(target_os = "visionos")
     ))]
     pub use crate::{Plugin, Scanner};
+
+    // Simulated plugins on WebAssembly
+    #[cfg(target_family = "wasm")]
+    pub use crate::{Plugin, Scanner};
 }
@@ -0,0 +1,68 @@
+//! Raw FFI bindings to abl_link, the C API of the Ableton Link SDK
+//!
+//! This module contains unsafe FFI declarations. All safe wrappers
+//! should be in mod.rs.
+
+#![allow(dead_code)]
+
+use std::os::raw::c_void;
+
+// Handles are passed by value and wrap a pointer to the C++ object
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct AblLink {
+    pub impl_: *mut c_void,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct AblLinkSessionState {
+    pub impl_: *mut c_void,
+}
+
+extern "C" {
+    // Link instance
+    pub fn abl_link_create(bpm: f64) -> AblLink;
+    pub fn abl_link_destroy(link: AblLink);
+    pub fn abl_link_is_enabled(link: AblLink) -> bool;
+    pub fn abl_link_enable(link: AblLink, enable: bool);
+    pub fn abl_link_is_start_stop_sync_enabled(link: AblLink) -> bool;
+    pub fn abl_link_enable_start_stop_sync(link: AblLink, enabled: bool);
+    pub fn abl_link_num_peers(link: AblLink) -> u64;
+    pub fn abl_link_clock_micros(link: AblLink) -> i64;
+
+    // Session state snapshots
+    pub fn abl_link_create_session_state() -> AblLinkSessionState;
+    pub fn abl_link_destroy_session_state(session_state: AblLinkSessionState);
+    pub fn abl_link_capture_app_session_state(link: AblLink, session_state: AblLinkSessionState);
+    pub fn abl_link_commit_app_session_state(link: AblLink, session_state: AblLinkSessionState);
+    pub fn abl_link_capture_audio_session_state(link: AblLink, session_state: AblLinkSessionState);
+    pub fn abl_link_commit_audio_session_state(link: AblLink, session_state: AblLinkSessionState);
+
+    // Timeline of a session state
+    pub fn abl_link_tempo(session_state: AblLinkSessionState) -> f64;
+    pub fn abl_link_set_tempo(session_state: AblLinkSessionState, bpm: f64, at_time: i64);
+    pub fn abl_link_beat_at_time(
+        session_state: AblLinkSessionState,
+        time: i64,
+        quantum: f64,
+    ) -> f64;
+    pub fn abl_link_phase_at_time(
+        session_state: AblLinkSessionState,
+        time: i64,
+        quantum: f64,
+    ) -> f64;
+    pub fn abl_link_time_at_beat(
+        session_state: AblLinkSessionState,
+        beat: f64,
+        quantum: f64,
+    ) -> i64;
+    pub fn abl_link_is_playing(session_state: AblLinkSessionState) -> bool;
+    pub fn abl_link_set_is_playing_and_request_beat_at_time(
+        session_state: AblLinkSessionState,
+        is_playing: bool,
+        time: u64,
+        beat: f64,
+        quantum: f64,
+    );
+}
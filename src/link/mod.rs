@@ -0,0 +1,226 @@
+//! Ableton Link tempo sync
+//!
+//! [`Link`] joins a Link session on the local network, so the
+//! [`Transport`]'s tempo and phase follow other Link-enabled apps (Live,
+//! Bitwig, phones, hardware). Call [`Link::sync()`] at the start of every
+//! block: it writes the session's tempo into the transport's tempo map and
+//! moves the position so bars line up with the session's. Everything that
+//! reads the transport follows along, including the tempo plugins see and a
+//! [`MidiClock`](crate::clock::MidiClock) driving hardware or hosted
+//! sequencers.
+//!
+//! Tempo and start/stop changes made by the host are sent to the session
+//! with [`Link::set_tempo()`] and [`Link::set_playing()`] rather than by
+//! editing the transport, which the next `sync()` would undo.
+//!
+//! Link is opt-in (the `link` feature) and built from the user's own
+//! checkout of the Link SDK, pointed to by `LINK_SDK_PATH`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::clock::MidiClock;
+//! # use rack::link::Link;
+//! # use rack::port::EventPort;
+//! # use rack::tempo::Transport;
+//! # use std::time::Duration;
+//! # fn example(mut arp: impl PluginInstance, mut transport: Transport) -> Result<()> {
+//! let mut link = Link::new(120.0).with_start_stop_sync(true);
+//! link.enable(true);
+//!
+//! let mut clock = MidiClock::new();
+//! let mut port = EventPort::new();
+//! let mut left = vec![0.0f32; 512];
+//! let mut right = vec![0.0f32; 512];
+//! loop {
+//!     // The block is heard after the device's output latency
+//!     link.sync(&mut transport, Duration::from_millis(10));
+//!     clock.process(&transport, 512, &mut port);
+//!     port.process(&mut arp, &[], &mut [&mut left, &mut right], 512)?;
+//!     transport.advance(512);
+//! }
+//! # }
+//! ```
+
+mod ffi;
+
+use crate::tempo::Transport;
+use std::time::Duration;
+
+/// Membership of a Link session
+///
+/// A new `Link` is disabled; it joins the session when
+/// [`enable()`](Self::enable)d.
+pub struct Link {
+    link: ffi::AblLink,
+    /// Session state captured and committed by the audio thread
+    audio_state: ffi::AblLinkSessionState,
+    /// Beats per bar (or phrase) that are kept in phase with the session
+    quantum: f64,
+    /// Whether the session was playing at the last sync
+    session_playing: bool,
+}
+
+// Safety: abl_link is thread-safe, and the audio thread's session state is
+// only used through `&mut self`
+unsafe impl Send for Link {}
+unsafe impl Sync for Link {}
+
+impl Link {
+    /// Create a disabled Link with the tempo to start a session at
+    pub fn new(bpm: f64) -> Self {
+        unsafe {
+            Self {
+                link: ffi::abl_link_create(bpm),
+                audio_state: ffi::abl_link_create_session_state(),
+                quantum: 4.0,
+                session_playing: false,
+            }
+        }
+    }
+
+    /// Keep phase over `quantum` beats instead of 4
+    ///
+    /// Peers with the same quantum have their bars line up; use the length
+    /// of a bar in quarter notes, or a multiple for longer phrases.
+    pub fn with_quantum(mut self, quantum: f64) -> Self {
+        if quantum > 0.0 {
+            self.quantum = quantum;
+        }
+        self
+    }
+
+    /// Share starting and stopping with peers that also enable it
+    pub fn with_start_stop_sync(self, enabled: bool) -> Self {
+        unsafe { ffi::abl_link_enable_start_stop_sync(self.link, enabled) };
+        self
+    }
+
+    /// Join (or leave) the session
+    pub fn enable(&self, enable: bool) {
+        unsafe { ffi::abl_link_enable(self.link, enable) };
+    }
+
+    /// Whether Link is enabled
+    pub fn is_enabled(&self) -> bool {
+        unsafe { ffi::abl_link_is_enabled(self.link) }
+    }
+
+    /// Whether starting and stopping are shared with peers
+    pub fn is_start_stop_sync_enabled(&self) -> bool {
+        unsafe { ffi::abl_link_is_start_stop_sync_enabled(self.link) }
+    }
+
+    /// Number of other apps in the session
+    pub fn num_peers(&self) -> u64 {
+        unsafe { ffi::abl_link_num_peers(self.link) }
+    }
+
+    /// Beats kept in phase with the session
+    pub fn quantum(&self) -> f64 {
+        self.quantum
+    }
+
+    /// Session tempo in BPM, as seen from the application thread
+    pub fn tempo(&self) -> f64 {
+        self.with_app_state(false, |state, _| unsafe { ffi::abl_link_tempo(state) })
+    }
+
+    /// Change the session's tempo, for every peer
+    ///
+    /// Call from the application thread; the transport follows on the next
+    /// [`sync()`](Self::sync).
+    pub fn set_tempo(&self, bpm: f64) {
+        self.with_app_state(true, |state, now| unsafe {
+            ffi::abl_link_set_tempo(state, bpm, now)
+        });
+    }
+
+    /// Start or stop the session's transport
+    ///
+    /// Starting requests the beginning of a bar (the quantum) at the current
+    /// time, so the song starts in phase. Peers only follow with start/stop
+    /// sync enabled. Call from the application thread.
+    pub fn set_playing(&self, playing: bool) {
+        let quantum = self.quantum;
+        self.with_app_state(true, |state, now| unsafe {
+            ffi::abl_link_set_is_playing_and_request_beat_at_time(
+                state,
+                playing,
+                now.max(0) as u64,
+                0.0,
+                quantum,
+            )
+        });
+    }
+
+    /// Bring the transport in line with the session before processing a
+    /// block
+    ///
+    /// `latency` is the time from now until the block's first sample is
+    /// heard (the audio device's output latency plus any buffering), so the
+    /// sound lines up with the other peers rather than the callback. With
+    /// start/stop sync, the transport starts and stops with the session.
+    /// Real-time safe.
+    pub fn sync(&mut self, transport: &mut Transport, latency: Duration) {
+        let time = unsafe { ffi::abl_link_clock_micros(self.link) }
+            .saturating_add(latency.as_micros().min(i64::MAX as u128) as i64);
+        let (bpm, beat, playing) = unsafe {
+            ffi::abl_link_capture_audio_session_state(self.link, self.audio_state);
+            (
+                ffi::abl_link_tempo(self.audio_state),
+                ffi::abl_link_beat_at_time(self.audio_state, time, self.quantum),
+                ffi::abl_link_is_playing(self.audio_state),
+            )
+        };
+
+        // Only the session's starts and stops are followed, so the host can
+        // still play on its own
+        if playing != self.session_playing {
+            self.session_playing = playing;
+            if self.is_start_stop_sync_enabled() {
+                transport.playing = playing;
+            }
+        }
+        transport.follow(bpm, beat, self.quantum);
+    }
+
+    /// Capture the application thread's session state and pass it with the
+    /// Link clock's current time to `f`, committing the changes if `commit`
+    fn with_app_state<T>(
+        &self,
+        commit: bool,
+        f: impl FnOnce(ffi::AblLinkSessionState, i64) -> T,
+    ) -> T {
+        unsafe {
+            let state = ffi::abl_link_create_session_state();
+            ffi::abl_link_capture_app_session_state(self.link, state);
+            let result = f(state, ffi::abl_link_clock_micros(self.link));
+            if commit {
+                ffi::abl_link_commit_app_session_state(self.link, state);
+            }
+            ffi::abl_link_destroy_session_state(state);
+            result
+        }
+    }
+}
+
+impl Drop for Link {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::abl_link_destroy_session_state(self.audio_state);
+            ffi::abl_link_destroy(self.link);
+        }
+    }
+}
+
+impl std::fmt::Debug for Link {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Link")
+            .field("enabled", &self.is_enabled())
+            .field("num_peers", &self.num_peers())
+            .field("quantum", &self.quantum)
+            .finish()
+    }
+}
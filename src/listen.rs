@@ -0,0 +1,149 @@
+//! Sidechain listen
+//!
+//! When a compressor or gate pumps in unexpected places, the first question is
+//! what it is keying from. [`SidechainListen`] temporarily replaces the monitor
+//! output with the signal feeding a plugin's sidechain, so the user can hear
+//! it, and switches back when they are done. The switch can be flipped from
+//! any thread (a "listen" button in the UI); the audio thread applies it
+//! through a [`ListenTap`], crossfading over one block so it doesn't click.
+//!
+//! The tap works on the buffers the host routes into the sidechain, so it
+//! hears exactly what the plugin is keyed from. A [`Graph`](crate::graph::Graph)
+//! sets one up with [`Graph::listen()`](crate::graph::Graph::listen), tapping
+//! the sum of a node's incoming connections; hosts with their own routing
+//! pass the key buffers to [`ListenTap::process()`] themselves.
+//!
+//! # Examples
+//!
+//! ```
+//! use rack::listen::SidechainListen;
+//!
+//! let listen = SidechainListen::new();
+//! let mut tap = listen.tap();
+//!
+//! let kick = vec![0.8f32; 64];
+//! let mut left = vec![0.1f32; 64];
+//! let mut right = vec![0.1f32; 64];
+//!
+//! listen.set_listening(true); // From the UI thread
+//! tap.process(&[&kick], &mut [&mut left, &mut right], 64);
+//! tap.process(&[&kick], &mut [&mut left, &mut right], 64);
+//! assert_eq!(left[0], 0.8);
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Switch between the monitor mix and a sidechain signal
+///
+/// Cloning gives another handle to the same switch.
+#[derive(Debug, Clone, Default)]
+pub struct SidechainListen {
+    listening: Arc<AtomicBool>,
+}
+
+impl SidechainListen {
+    /// Create a switch that isn't listening
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the monitor output plays the sidechain
+    pub fn is_listening(&self) -> bool {
+        self.listening.load(Ordering::Relaxed)
+    }
+
+    /// Play the sidechain on the monitor output, or go back to the mix
+    pub fn set_listening(&self, listening: bool) {
+        self.listening.store(listening, Ordering::Relaxed);
+    }
+
+    /// A tap applying the switch, for the audio thread
+    pub fn tap(&self) -> ListenTap {
+        ListenTap {
+            mix: if self.is_listening() { 1.0 } else { 0.0 },
+            listen: self.clone(),
+        }
+    }
+}
+
+/// Applies a [`SidechainListen`] to the monitor output
+#[derive(Debug)]
+pub struct ListenTap {
+    listen: SidechainListen,
+    /// Share of the sidechain in the output at the end of the last block
+    mix: f32,
+}
+
+impl ListenTap {
+    /// Replace the monitor output with the sidechain while listening, in place
+    ///
+    /// Sidechain channels are mapped onto the monitor channels in turn (a mono
+    /// key plays on every channel); with no sidechain channels, listening
+    /// plays silence, as do frames past the end of a short sidechain buffer.
+    /// Switching crossfades over this block.
+    pub fn process(&mut self, sidechain: &[&[f32]], monitor: &mut [&mut [f32]], num_frames: usize) {
+        let start = self.mix;
+        let target = if self.listen.is_listening() { 1.0 } else { 0.0 };
+        self.mix = target;
+        if start == 0.0 && target == 0.0 {
+            return;
+        }
+
+        let step = (target - start) / num_frames.max(1) as f32;
+        for (ch, output) in monitor.iter_mut().enumerate() {
+            let key = (!sidechain.is_empty()).then(|| sidechain[ch % sidechain.len()]);
+            for (i, sample) in output.iter_mut().take(num_frames).enumerate() {
+                let mix = if start == target {
+                    target
+                } else {
+                    start + step * (i + 1) as f32
+                };
+                let key = key.and_then(|key| key.get(i)).copied().unwrap_or(0.0);
+                *sample += (key - *sample) * mix;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listen_crossfades() {
+        let listen = SidechainListen::new();
+        let mut tap = listen.tap();
+        let key_left = [1.0f32; 4];
+        let key_right = [-1.0f32; 4];
+
+        let mut out = [0.5f32; 4];
+        tap.process(&[&key_left, &key_right], &mut [&mut out], 4);
+        assert_eq!(out, [0.5; 4]);
+
+        listen.set_listening(true);
+        let mut left = [0.0f32; 4];
+        let mut right = [0.0f32; 4];
+        tap.process(&[&key_left, &key_right], &mut [&mut left, &mut right], 4);
+        assert_eq!(left, [0.25, 0.5, 0.75, 1.0]);
+        assert_eq!(right[3], -1.0);
+
+        listen.set_listening(false);
+        let mut out = [0.5f32; 4];
+        tap.process(&[], &mut [&mut out], 4);
+        assert_eq!(out[0], 0.125);
+        assert_eq!(out[3], 0.5);
+    }
+
+    #[test]
+    fn test_short_sidechain_plays_silence() {
+        let listen = SidechainListen::new();
+        listen.set_listening(true);
+        let mut tap = listen.tap();
+
+        let key = [1.0f32; 2];
+        let mut out = [0.5f32; 4];
+        tap.process(&[&key], &mut [&mut out], 4);
+        assert_eq!(out, [1.0, 1.0, 0.0, 0.0]);
+    }
+}
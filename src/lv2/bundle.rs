@@ -0,0 +1,471 @@
+//! Reading plugin descriptions from LV2 bundles
+//!
+//! An LV2 bundle is a folder holding `manifest.ttl`, the data files it
+//! points to with `rdfs:seeAlso`, and the plugin binaries. Everything the
+//! scanner needs (names, ports, presets) comes from the data files, so no
+//! binary is loaded until a plugin is instantiated.
+
+use crate::{Error, PluginType, Result};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use super::ffi;
+use super::turtle::{file_uri_to_path, Graph, Term, RDF_TYPE};
+
+const LV2_PLUGIN: &str = "http://lv2plug.in/ns/lv2core#Plugin";
+const LV2_BINARY: &str = "http://lv2plug.in/ns/lv2core#binary";
+const LV2_PORT: &str = "http://lv2plug.in/ns/lv2core#port";
+const LV2_INDEX: &str = "http://lv2plug.in/ns/lv2core#index";
+const LV2_SYMBOL: &str = "http://lv2plug.in/ns/lv2core#symbol";
+const LV2_NAME: &str = "http://lv2plug.in/ns/lv2core#name";
+const LV2_INPUT_PORT: &str = "http://lv2plug.in/ns/lv2core#InputPort";
+const LV2_OUTPUT_PORT: &str = "http://lv2plug.in/ns/lv2core#OutputPort";
+const LV2_AUDIO_PORT: &str = "http://lv2plug.in/ns/lv2core#AudioPort";
+const LV2_CONTROL_PORT: &str = "http://lv2plug.in/ns/lv2core#ControlPort";
+const LV2_CV_PORT: &str = "http://lv2plug.in/ns/lv2core#CVPort";
+const LV2_DEFAULT: &str = "http://lv2plug.in/ns/lv2core#default";
+const LV2_MINIMUM: &str = "http://lv2plug.in/ns/lv2core#minimum";
+const LV2_MAXIMUM: &str = "http://lv2plug.in/ns/lv2core#maximum";
+const LV2_PORT_PROPERTY: &str = "http://lv2plug.in/ns/lv2core#portProperty";
+const LV2_TOGGLED: &str = "http://lv2plug.in/ns/lv2core#toggled";
+const LV2_INTEGER: &str = "http://lv2plug.in/ns/lv2core#integer";
+const LV2_ENUMERATION: &str = "http://lv2plug.in/ns/lv2core#enumeration";
+const LV2_CONNECTION_OPTIONAL: &str = "http://lv2plug.in/ns/lv2core#connectionOptional";
+const LV2_DESIGNATION: &str = "http://lv2plug.in/ns/lv2core#designation";
+const LV2_LATENCY: &str = "http://lv2plug.in/ns/lv2core#latency";
+const LV2_ENABLED: &str = "http://lv2plug.in/ns/lv2core#enabled";
+const LV2_REPORTS_LATENCY: &str = "http://lv2plug.in/ns/lv2core#reportsLatency";
+const LV2_REQUIRED_FEATURE: &str = "http://lv2plug.in/ns/lv2core#requiredFeature";
+const LV2_INSTRUMENT_PLUGIN: &str = "http://lv2plug.in/ns/lv2core#InstrumentPlugin";
+const LV2_ANALYSER_PLUGIN: &str = "http://lv2plug.in/ns/lv2core#AnalyserPlugin";
+const LV2_SPATIAL_PLUGIN: &str = "http://lv2plug.in/ns/lv2core#SpatialPlugin";
+const LV2_MINOR_VERSION: &str = "http://lv2plug.in/ns/lv2core#minorVersion";
+const LV2_MICRO_VERSION: &str = "http://lv2plug.in/ns/lv2core#microVersion";
+const LV2_PROJECT: &str = "http://lv2plug.in/ns/lv2core#project";
+const LV2_APPLIES_TO: &str = "http://lv2plug.in/ns/lv2core#appliesTo";
+const RDFS_SEE_ALSO: &str = "http://www.w3.org/2000/01/rdf-schema#seeAlso";
+const RDFS_LABEL: &str = "http://www.w3.org/2000/01/rdf-schema#label";
+const DOAP_NAME: &str = "http://usefulinc.com/ns/doap#name";
+const DOAP_MAINTAINER: &str = "http://usefulinc.com/ns/doap#maintainer";
+const DOAP_DEVELOPER: &str = "http://usefulinc.com/ns/doap#developer";
+const FOAF_NAME: &str = "http://xmlns.com/foaf/0.1/name";
+const PSET_PRESET: &str = "http://lv2plug.in/ns/ext/presets#Preset";
+const PSET_VALUE: &str = "http://lv2plug.in/ns/ext/presets#value";
+const PPROP_LOGARITHMIC: &str = "http://lv2plug.in/ns/ext/port-props#logarithmic";
+const PPROP_NOT_ON_GUI: &str = "http://lv2plug.in/ns/ext/port-props#notOnGUI";
+const UNITS_UNIT: &str = "http://lv2plug.in/ns/extensions/units#unit";
+const UNITS_SYMBOL: &str = "http://lv2plug.in/ns/extensions/units#symbol";
+const UNITS_PREFIX: &str = "http://lv2plug.in/ns/extensions/units#";
+
+/// Host features a plugin may require
+///
+/// See [`super::instance`] for how each is provided.
+pub(crate) const SUPPORTED_FEATURES: &[&str] = &[
+    "http://lv2plug.in/ns/ext/urid#map",
+    "http://lv2plug.in/ns/ext/urid#unmap",
+    "http://lv2plug.in/ns/ext/options#options",
+    "http://lv2plug.in/ns/ext/buf-size#boundedBlockLength",
+    ffi::LV2_CORE__IN_PLACE_BROKEN,
+    ffi::LV2_CORE__IS_LIVE,
+    ffi::LV2_CORE__HARD_RT_CAPABLE,
+];
+
+/// Which way data flows through a port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PortDirection {
+    Input,
+    Output,
+}
+
+/// What a port carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PortKind {
+    Audio,
+    Control,
+    Cv,
+    /// Atom sequence; `midi` if it takes MIDI events, `position` if it
+    /// takes `time:Position` objects
+    Atom {
+        midi: bool,
+        position: bool,
+    },
+    /// Anything else; connected to nothing if the plugin allows it
+    Unknown,
+}
+
+/// A port from the plugin's data
+#[derive(Debug, Clone)]
+pub(crate) struct PortDescription {
+    pub index: u32,
+    pub symbol: String,
+    pub name: String,
+    pub direction: PortDirection,
+    pub kind: PortKind,
+    pub default: Option<f32>,
+    pub minimum: Option<f32>,
+    pub maximum: Option<f32>,
+    pub unit: String,
+    pub toggled: bool,
+    pub integer: bool,
+    pub logarithmic: bool,
+    pub hidden: bool,
+    /// May be left unconnected
+    pub optional: bool,
+    /// Reports the plugin's latency in samples (a control output)
+    pub latency: bool,
+    /// Switches the plugin's processing on (> 0) or bypasses it (a control
+    /// input)
+    pub enabled: bool,
+}
+
+impl PortDescription {
+    /// The value a control port starts at
+    pub fn initial_value(&self) -> f32 {
+        self.default
+            .or(self.minimum)
+            .unwrap_or(0.0)
+            .clamp(self.range().0, self.range().1)
+    }
+
+    /// The port's range, as ordered bounds
+    pub fn range(&self) -> (f32, f32) {
+        let min = self.minimum.unwrap_or(0.0);
+        let max = self
+            .maximum
+            .unwrap_or(if self.toggled { 1.0 } else { min.max(1.0) });
+        if max < min {
+            (max, min)
+        } else {
+            (min, max)
+        }
+    }
+}
+
+/// A preset from the plugin's bundle
+#[derive(Debug, Clone)]
+pub(crate) struct PresetDescription {
+    pub label: String,
+    /// Control port values by symbol
+    pub values: Vec<(String, f32)>,
+}
+
+/// Everything the bundle says about one plugin
+#[derive(Debug, Clone)]
+pub(crate) struct PluginDescription {
+    pub uri: String,
+    pub bundle: PathBuf,
+    pub binary: Option<PathBuf>,
+    pub name: String,
+    pub manufacturer: String,
+    pub version: u32,
+    pub plugin_type: PluginType,
+    pub ports: Vec<PortDescription>,
+    pub required_features: Vec<String>,
+    pub presets: Vec<PresetDescription>,
+}
+
+impl PluginDescription {
+    /// The ports of one kind and direction, in index order
+    pub fn ports(
+        &self,
+        kind: PortKind,
+        direction: PortDirection,
+    ) -> impl Iterator<Item = &PortDescription> + '_ {
+        self.ports
+            .iter()
+            .filter(move |port| port.kind == kind && port.direction == direction)
+    }
+
+    /// Required host features this host doesn't provide
+    pub fn missing_features(&self) -> Vec<&str> {
+        self.required_features
+            .iter()
+            .map(String::as_str)
+            .filter(|feature| !SUPPORTED_FEATURES.contains(feature))
+            .collect()
+    }
+}
+
+/// Describe every plugin in the bundle at `bundle`
+///
+/// # Errors
+///
+/// Returns an error if the manifest or a data file it points to can't be
+/// read or parsed
+pub(crate) fn read_bundle(bundle: &Path) -> Result<Vec<PluginDescription>> {
+    let mut graph = Graph::new();
+    graph.parse_file(&bundle.join("manifest.ttl"))?;
+
+    // The manifest points to the files holding the full descriptions
+    let mut loaded = HashSet::new();
+    let files: Vec<PathBuf> = graph
+        .triples()
+        .iter()
+        .filter(|t| t.predicate == RDFS_SEE_ALSO)
+        .filter_map(|t| t.object.as_iri().and_then(file_uri_to_path))
+        .collect();
+    for file in files {
+        if loaded.insert(file.clone()) {
+            graph.parse_file(&file)?;
+        }
+    }
+
+    let plugin_class = Term::Iri(LV2_PLUGIN.to_string());
+    let mut uris: Vec<&str> = graph
+        .subjects(RDF_TYPE, &plugin_class)
+        .filter_map(Term::as_iri)
+        .collect();
+    uris.sort_unstable();
+    uris.dedup();
+
+    uris.into_iter()
+        .map(|uri| describe_plugin(&graph, bundle, uri))
+        .collect()
+}
+
+fn describe_plugin(graph: &Graph, bundle: &Path, uri: &str) -> Result<PluginDescription> {
+    let plugin = Term::Iri(uri.to_string());
+
+    let binary = graph
+        .object(&plugin, LV2_BINARY)
+        .and_then(Term::as_iri)
+        .and_then(file_uri_to_path);
+    let name = text(graph, &plugin, DOAP_NAME)
+        .or_else(|| text(graph, &plugin, RDFS_LABEL))
+        .unwrap_or_else(|| uri.to_string());
+
+    // The maintainer may be given on the plugin or its project
+    let project = graph.object(&plugin, LV2_PROJECT);
+    let manufacturer = [Some(&plugin), project]
+        .into_iter()
+        .flatten()
+        .flat_map(|subject| {
+            graph
+                .objects(subject, DOAP_MAINTAINER)
+                .chain(graph.objects(subject, DOAP_DEVELOPER))
+        })
+        .find_map(|person| {
+            text(graph, person, FOAF_NAME).or_else(|| text(graph, person, DOAP_NAME))
+        })
+        .unwrap_or_default();
+
+    let number = |predicate| {
+        graph
+            .object(&plugin, predicate)
+            .and_then(Term::as_f64)
+            .map_or(0, |value| value.max(0.0) as u32)
+    };
+    let version = (number(LV2_MINOR_VERSION).min(0xFF) << 8) | number(LV2_MICRO_VERSION).min(0xFF);
+
+    let mut ports = graph
+        .objects(&plugin, LV2_PORT)
+        .map(|port| describe_port(graph, port))
+        .collect::<Result<Vec<_>>>()?;
+    ports.sort_by_key(|port| port.index);
+    for (expected, port) in ports.iter().enumerate() {
+        if port.index as usize != expected {
+            return Err(Error::InvalidFormat(format!(
+                "{} has no port {}",
+                uri, expected
+            )));
+        }
+    }
+
+    let plugin_type = if graph.has_type(&plugin, LV2_INSTRUMENT_PLUGIN) {
+        PluginType::Instrument
+    } else if graph.has_type(&plugin, LV2_ANALYSER_PLUGIN) {
+        PluginType::Analyzer
+    } else if graph.has_type(&plugin, LV2_SPATIAL_PLUGIN) {
+        PluginType::Spatial
+    } else if ports
+        .iter()
+        .any(|p| p.kind == PortKind::Audio && p.direction == PortDirection::Input)
+    {
+        PluginType::Effect
+    } else {
+        PluginType::Other
+    };
+
+    let required_features = graph
+        .objects(&plugin, LV2_REQUIRED_FEATURE)
+        .filter_map(Term::as_iri)
+        .map(str::to_string)
+        .collect();
+
+    Ok(PluginDescription {
+        uri: uri.to_string(),
+        bundle: bundle.to_path_buf(),
+        binary,
+        name,
+        manufacturer,
+        version,
+        plugin_type,
+        ports,
+        required_features,
+        presets: describe_presets(graph, &plugin),
+    })
+}
+
+fn describe_port(graph: &Graph, port: &Term) -> Result<PortDescription> {
+    let index = graph
+        .object(port, LV2_INDEX)
+        .and_then(Term::as_f64)
+        .filter(|index| *index >= 0.0 && index.fract() == 0.0)
+        .ok_or_else(|| Error::InvalidFormat("LV2 port without a valid index".to_string()))?
+        as u32;
+    let symbol = text(graph, port, LV2_SYMBOL).unwrap_or_else(|| format!("port{}", index));
+    let name = text(graph, port, LV2_NAME).unwrap_or_else(|| symbol.clone());
+
+    let direction = if graph.has_type(port, LV2_OUTPUT_PORT) {
+        PortDirection::Output
+    } else if graph.has_type(port, LV2_INPUT_PORT) {
+        PortDirection::Input
+    } else {
+        return Err(Error::InvalidFormat(format!(
+            "LV2 port {} is neither an input nor an output",
+            symbol
+        )));
+    };
+
+    let kind = if graph.has_type(port, LV2_AUDIO_PORT) {
+        PortKind::Audio
+    } else if graph.has_type(port, LV2_CONTROL_PORT) {
+        PortKind::Control
+    } else if graph.has_type(port, LV2_CV_PORT) {
+        PortKind::Cv
+    } else if graph.has_type(port, ffi::LV2_ATOM__ATOM_PORT) {
+        let supports = |uri| {
+            graph
+                .objects(port, ffi::LV2_ATOM__SUPPORTS)
+                .any(|t| t.as_iri() == Some(uri))
+        };
+        PortKind::Atom {
+            midi: supports(ffi::LV2_MIDI__MIDI_EVENT),
+            position: supports(ffi::LV2_TIME__POSITION),
+        }
+    } else {
+        PortKind::Unknown
+    };
+
+    let value = |predicate| {
+        graph
+            .object(port, predicate)
+            .and_then(Term::as_f64)
+            .map(|v| v as f32)
+    };
+    let has_property = |property: &str| {
+        graph
+            .objects(port, LV2_PORT_PROPERTY)
+            .any(|t| t.as_iri() == Some(property))
+    };
+    let latency = graph
+        .objects(port, LV2_DESIGNATION)
+        .any(|t| t.as_iri() == Some(LV2_LATENCY))
+        || has_property(LV2_REPORTS_LATENCY);
+    let enabled = graph
+        .objects(port, LV2_DESIGNATION)
+        .any(|t| t.as_iri() == Some(LV2_ENABLED));
+
+    Ok(PortDescription {
+        index,
+        symbol,
+        name,
+        direction,
+        kind,
+        default: value(LV2_DEFAULT),
+        minimum: value(LV2_MINIMUM),
+        maximum: value(LV2_MAXIMUM),
+        unit: graph
+            .object(port, UNITS_UNIT)
+            .map(|unit| unit_label(graph, unit))
+            .unwrap_or_default(),
+        toggled: has_property(LV2_TOGGLED),
+        integer: has_property(LV2_INTEGER) || has_property(LV2_ENUMERATION),
+        logarithmic: has_property(PPROP_LOGARITHMIC),
+        hidden: has_property(PPROP_NOT_ON_GUI),
+        optional: has_property(LV2_CONNECTION_OPTIONAL),
+        latency,
+        enabled,
+    })
+}
+
+/// The presets that apply to `plugin`, sorted by label
+fn describe_presets(graph: &Graph, plugin: &Term) -> Vec<PresetDescription> {
+    // The manifest and the preset's own file both say what it applies to
+    let mut seen = HashSet::new();
+    let mut presets: Vec<PresetDescription> = graph
+        .subjects(LV2_APPLIES_TO, plugin)
+        .filter(|preset| seen.insert(*preset) && graph.has_type(preset, PSET_PRESET))
+        .map(|preset| {
+            let label = text(graph, preset, RDFS_LABEL)
+                .or_else(|| preset.as_iri().map(str::to_string))
+                .unwrap_or_default();
+            let values = graph
+                .objects(preset, LV2_PORT)
+                .filter_map(|port| {
+                    let symbol = text(graph, port, LV2_SYMBOL)?;
+                    let value = graph.object(port, PSET_VALUE)?.as_f64()?;
+                    Some((symbol, value as f32))
+                })
+                .collect();
+            PresetDescription { label, values }
+        })
+        .collect();
+    presets.sort_by(|a, b| a.label.cmp(&b.label));
+    presets
+}
+
+/// A literal's text, preferring one without a language tag or in English
+fn text(graph: &Graph, subject: &Term, predicate: &str) -> Option<String> {
+    let mut fallback = None;
+    for object in graph.objects(subject, predicate) {
+        if let Term::Literal {
+            value, language, ..
+        } = object
+        {
+            match language.as_deref() {
+                None | Some("en") => return Some(value.clone()),
+                Some(_) => {
+                    fallback.get_or_insert_with(|| value.clone());
+                }
+            }
+        }
+    }
+    fallback
+}
+
+/// The label for a `units:unit`: a known unit's symbol, or the symbol a
+/// custom unit declares
+fn unit_label(graph: &Graph, unit: &Term) -> String {
+    if let Some(symbol) = text(graph, unit, UNITS_SYMBOL) {
+        return symbol;
+    }
+    let Some(name) = unit.as_iri().and_then(|iri| iri.strip_prefix(UNITS_PREFIX)) else {
+        return String::new();
+    };
+    match name {
+        "bar" => "bars",
+        "beat" => "beats",
+        "bpm" => "BPM",
+        "cent" => "ct",
+        "cm" => "cm",
+        "db" => "dB",
+        "degree" => "°",
+        "frame" => "frames",
+        "hz" => "Hz",
+        "khz" => "kHz",
+        "km" => "km",
+        "m" => "m",
+        "mhz" => "MHz",
+        "midiNote" => "note",
+        "min" => "min",
+        "mm" => "mm",
+        "ms" => "ms",
+        "oct" => "oct",
+        "pc" => "%",
+        "s" => "s",
+        "semitone12TET" => "semi",
+        _ => "",
+    }
+    .to_string()
+}
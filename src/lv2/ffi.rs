@@ -0,0 +1,212 @@
+//! LV2 ABI declarations
+//!
+//! The subset of the LV2 C headers the host uses, transcribed from
+//! `lv2/core/lv2.h`, `lv2/urid/urid.h`, `lv2/options/options.h`,
+//! `lv2/atom/atom.h` and `lv2/state/state.h`. LV2 is a plain C ABI, so no SDK
+//! is needed to build against it.
+
+#![allow(dead_code, non_camel_case_types)]
+
+use std::ffi::{c_char, c_void};
+
+pub type LV2_Handle = *mut c_void;
+pub type LV2_URID = u32;
+
+// core/lv2.h
+
+/// Name of the symbol every LV2 library exports
+pub const LV2_DESCRIPTOR_SYMBOL: &[u8] = b"lv2_descriptor\0";
+
+pub type LV2_Descriptor_Function = unsafe extern "C" fn(index: u32) -> *const LV2_Descriptor;
+
+#[repr(C)]
+pub struct LV2_Feature {
+    pub uri: *const c_char,
+    pub data: *mut c_void,
+}
+
+#[repr(C)]
+pub struct LV2_Descriptor {
+    pub uri: *const c_char,
+    pub instantiate: Option<
+        unsafe extern "C" fn(
+            descriptor: *const LV2_Descriptor,
+            sample_rate: f64,
+            bundle_path: *const c_char,
+            features: *const *const LV2_Feature,
+        ) -> LV2_Handle,
+    >,
+    pub connect_port:
+        Option<unsafe extern "C" fn(instance: LV2_Handle, port: u32, data: *mut c_void)>,
+    pub activate: Option<unsafe extern "C" fn(instance: LV2_Handle)>,
+    pub run: Option<unsafe extern "C" fn(instance: LV2_Handle, sample_count: u32)>,
+    pub deactivate: Option<unsafe extern "C" fn(instance: LV2_Handle)>,
+    pub cleanup: Option<unsafe extern "C" fn(instance: LV2_Handle)>,
+    pub extension_data: Option<unsafe extern "C" fn(uri: *const c_char) -> *const c_void>,
+}
+
+pub const LV2_CORE_PREFIX: &str = "http://lv2plug.in/ns/lv2core#";
+pub const LV2_CORE__IN_PLACE_BROKEN: &str = "http://lv2plug.in/ns/lv2core#inPlaceBroken";
+pub const LV2_CORE__IS_LIVE: &str = "http://lv2plug.in/ns/lv2core#isLive";
+pub const LV2_CORE__HARD_RT_CAPABLE: &str = "http://lv2plug.in/ns/lv2core#hardRTCapable";
+
+// urid/urid.h
+
+pub const LV2_URID__MAP: &[u8] = b"http://lv2plug.in/ns/ext/urid#map\0";
+pub const LV2_URID__UNMAP: &[u8] = b"http://lv2plug.in/ns/ext/urid#unmap\0";
+
+#[repr(C)]
+pub struct LV2_URID_Map {
+    pub handle: *mut c_void,
+    pub map: Option<unsafe extern "C" fn(handle: *mut c_void, uri: *const c_char) -> LV2_URID>,
+}
+
+#[repr(C)]
+pub struct LV2_URID_Unmap {
+    pub handle: *mut c_void,
+    pub unmap: Option<unsafe extern "C" fn(handle: *mut c_void, urid: LV2_URID) -> *const c_char>,
+}
+
+// options/options.h and buf-size/buf-size.h
+
+pub const LV2_OPTIONS__OPTIONS: &[u8] = b"http://lv2plug.in/ns/ext/options#options\0";
+pub const LV2_BUF_SIZE__BOUNDED_BLOCK_LENGTH: &[u8] =
+    b"http://lv2plug.in/ns/ext/buf-size#boundedBlockLength\0";
+pub const LV2_BUF_SIZE__MIN_BLOCK_LENGTH: &str = "http://lv2plug.in/ns/ext/buf-size#minBlockLength";
+pub const LV2_BUF_SIZE__MAX_BLOCK_LENGTH: &str = "http://lv2plug.in/ns/ext/buf-size#maxBlockLength";
+pub const LV2_BUF_SIZE__NOMINAL_BLOCK_LENGTH: &str =
+    "http://lv2plug.in/ns/ext/buf-size#nominalBlockLength";
+pub const LV2_PARAMETERS__SAMPLE_RATE: &str = "http://lv2plug.in/ns/ext/parameters#sampleRate";
+
+pub const LV2_OPTIONS_INSTANCE: u32 = 0;
+
+#[repr(C)]
+pub struct LV2_Options_Option {
+    pub context: u32,
+    pub subject: u32,
+    pub key: LV2_URID,
+    pub size: u32,
+    pub type_: LV2_URID,
+    pub value: *const c_void,
+}
+
+// atom/atom.h, midi/midi.h and time/time.h
+
+pub const LV2_ATOM__ATOM_PORT: &str = "http://lv2plug.in/ns/ext/atom#AtomPort";
+pub const LV2_ATOM__CHUNK: &str = "http://lv2plug.in/ns/ext/atom#Chunk";
+pub const LV2_ATOM__FLOAT: &str = "http://lv2plug.in/ns/ext/atom#Float";
+pub const LV2_ATOM__INT: &str = "http://lv2plug.in/ns/ext/atom#Int";
+pub const LV2_ATOM__LONG: &str = "http://lv2plug.in/ns/ext/atom#Long";
+pub const LV2_ATOM__OBJECT: &str = "http://lv2plug.in/ns/ext/atom#Object";
+pub const LV2_ATOM__SEQUENCE: &str = "http://lv2plug.in/ns/ext/atom#Sequence";
+pub const LV2_ATOM__SUPPORTS: &str = "http://lv2plug.in/ns/ext/atom#supports";
+pub const LV2_MIDI__MIDI_EVENT: &str = "http://lv2plug.in/ns/ext/midi#MidiEvent";
+pub const LV2_TIME__POSITION: &str = "http://lv2plug.in/ns/ext/time#Position";
+pub const LV2_TIME__BAR: &str = "http://lv2plug.in/ns/ext/time#bar";
+pub const LV2_TIME__BAR_BEAT: &str = "http://lv2plug.in/ns/ext/time#barBeat";
+pub const LV2_TIME__BEAT_UNIT: &str = "http://lv2plug.in/ns/ext/time#beatUnit";
+pub const LV2_TIME__BEATS_PER_BAR: &str = "http://lv2plug.in/ns/ext/time#beatsPerBar";
+pub const LV2_TIME__BEATS_PER_MINUTE: &str = "http://lv2plug.in/ns/ext/time#beatsPerMinute";
+pub const LV2_TIME__FRAME: &str = "http://lv2plug.in/ns/ext/time#frame";
+pub const LV2_TIME__SPEED: &str = "http://lv2plug.in/ns/ext/time#speed";
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LV2_Atom {
+    pub size: u32,
+    pub type_: LV2_URID,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LV2_Atom_Sequence_Body {
+    pub unit: u32,
+    pub pad: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LV2_Atom_Sequence {
+    pub atom: LV2_Atom,
+    pub body: LV2_Atom_Sequence_Body,
+}
+
+/// The start of an object's body, followed by its properties
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LV2_Atom_Object_Body {
+    pub id: u32,
+    pub otype: LV2_URID,
+}
+
+/// A property of an object, followed by `value.size` bytes of value padded
+/// to 8 bytes
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LV2_Atom_Property_Body {
+    pub key: LV2_URID,
+    pub context: LV2_URID,
+    pub value: LV2_Atom,
+}
+
+/// An event in a sequence, followed by `body.size` bytes of data padded to
+/// 8 bytes
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LV2_Atom_Event {
+    pub time_frames: i64,
+    pub body: LV2_Atom,
+}
+
+// state/state.h
+
+pub const LV2_STATE__INTERFACE: &[u8] = b"http://lv2plug.in/ns/ext/state#interface\0";
+
+pub const LV2_STATE_IS_POD: u32 = 1;
+pub const LV2_STATE_IS_PORTABLE: u32 = 1 << 1;
+
+pub const LV2_STATE_SUCCESS: u32 = 0;
+pub const LV2_STATE_ERR_UNKNOWN: u32 = 1;
+pub const LV2_STATE_ERR_BAD_FLAGS: u32 = 3;
+pub const LV2_STATE_ERR_NO_PROPERTY: u32 = 5;
+
+pub type LV2_State_Handle = *mut c_void;
+
+pub type LV2_State_Store_Function = unsafe extern "C" fn(
+    handle: LV2_State_Handle,
+    key: LV2_URID,
+    value: *const c_void,
+    size: usize,
+    type_: LV2_URID,
+    flags: u32,
+) -> u32;
+
+pub type LV2_State_Retrieve_Function = unsafe extern "C" fn(
+    handle: LV2_State_Handle,
+    key: LV2_URID,
+    size: *mut usize,
+    type_: *mut LV2_URID,
+    flags: *mut u32,
+) -> *const c_void;
+
+#[repr(C)]
+pub struct LV2_State_Interface {
+    pub save: Option<
+        unsafe extern "C" fn(
+            instance: LV2_Handle,
+            store: LV2_State_Store_Function,
+            handle: LV2_State_Handle,
+            flags: u32,
+            features: *const *const LV2_Feature,
+        ) -> u32,
+    >,
+    pub restore: Option<
+        unsafe extern "C" fn(
+            instance: LV2_Handle,
+            retrieve: LV2_State_Retrieve_Function,
+            handle: LV2_State_Handle,
+            flags: u32,
+            features: *const *const LV2_Feature,
+        ) -> u32,
+    >,
+}
@@ -0,0 +1,1577 @@
+use crate::bypass::HostBypass;
+use crate::events::{self, HostEvent};
+use crate::host;
+use crate::node::BlockContext;
+use crate::paths;
+use crate::quirks::{self, Quirk, Quirks};
+use crate::traits;
+use crate::{
+    Error, MidiEvent, ParameterChange, ParameterCurve, ParameterInfo, ParameterVisibility,
+    PluginInfo, PluginInstance, PresetInfo, Result,
+};
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+use super::bundle::{read_bundle, PluginDescription, PortDescription, PortDirection, PortKind};
+use super::ffi;
+use super::library::Library;
+
+/// Size of each atom port buffer in bytes
+///
+/// Fits a few hundred MIDI events per block; events past that are dropped.
+const ATOM_BUFFER_SIZE: usize = 16 * 1024;
+
+/// First bytes of a state from [`get_state()`](PluginInstance::get_state)
+const STATE_MAGIC: &[u8; 4] = b"RLV2";
+const STATE_VERSION: u32 = 1;
+
+/// An instantiated LV2 plugin
+///
+/// LV2 plugins are instantiated for a fixed sample rate, so the plugin is
+/// created by [`initialize()`](PluginInstance::initialize) and created again
+/// (keeping its state) when the sample rate or block size changes.
+///
+/// Parameters are the plugin's control input ports; changes reach the
+/// plugin with the next block. CV inputs are fed silence, and MIDI is sent
+/// to the first atom input that takes MIDI events. The context given to
+/// [`process_with_context()`](PluginInstance::process_with_context) reaches
+/// the first atom input that takes `time:Position` objects at the start of
+/// the block; plain `process()` sends no transport information.
+///
+/// Presets are the ones the plugin's bundle lists. The state from
+/// [`get_state()`](PluginInstance::get_state) holds the control port values
+/// and whatever the plugin saves through the LV2 state interface.
+///
+/// # Thread Safety
+///
+/// This type is `Send` but not `Sync`:
+/// - `Send`: The plugin can be moved between threads safely
+/// - NOT `Sync`: Multiple threads should not access the plugin simultaneously
+///   without synchronization. Wrap in `Arc<Mutex<>>` if shared access is needed.
+pub struct Lv2Plugin {
+    descriptor: *const ffi::LV2_Descriptor,
+    // Null until the plugin is instantiated
+    handle: ffi::LV2_Handle,
+    info: PluginInfo,
+    description: PluginDescription,
+    // Features passed to the plugin (boxed for a stable address)
+    host: Box<HostFeatures>,
+    urids: Urids,
+    // Value of every control port, by port index (boxed for a stable address)
+    controls: Box<[f32]>,
+    // Port indices of the parameters, audio ports, latency port and
+    // lv2:enabled port
+    parameters: Vec<usize>,
+    audio_inputs: Vec<u32>,
+    audio_outputs: Vec<u32>,
+    latency_port: Option<usize>,
+    enabled_port: Option<usize>,
+    // Bypass for plugins without an lv2:enabled port
+    bypass: HostBypass,
+    // Buffers for CV ports, by port index
+    cv_buffers: Vec<(u32, Vec<f32>)>,
+    // Buffers for atom ports, by port index; u64 for the 8-byte alignment
+    // atoms need
+    atom_inputs: Vec<(u32, Box<[u64]>)>,
+    atom_outputs: Vec<(u32, Box<[u64]>)>,
+    // Which of atom_inputs take MIDI and time:Position objects
+    midi_input: Option<usize>,
+    position_input: Option<usize>,
+    // MIDI for the next process() call, in time order
+    midi: Vec<MidiEvent>,
+    // Transport for the next process() call
+    position: Option<BlockContext>,
+    state: *const ffi::LV2_State_Interface,
+    sample_rate: f64,
+    max_block_size: usize,
+    active: bool,
+    // Timeline position of the next processed sample
+    sample_position: u64,
+    // Quirks looked up when the plugin was loaded
+    quirks: Quirks,
+    // Dropped after the plugin is cleaned up
+    _library: Arc<Library>,
+    // PhantomData<*const ()> makes this type !Sync while keeping it Send
+    _not_sync: PhantomData<*const ()>,
+}
+
+// Safety: Lv2Plugin can be sent between threads because:
+// 1. Each plugin instance owns its handle exclusively
+// 2. LV2 only requires that calls to one instance don't overlap, which
+//    &mut self guarantees
+unsafe impl Send for Lv2Plugin {}
+
+// Note: Lv2Plugin is NOT Sync due to PhantomData<*const ()>
+
+impl Lv2Plugin {
+    /// Load an LV2 plugin
+    ///
+    /// `info.path` is the plugin's bundle and `info.unique_id` its URI.
+    /// Plugins requiring host features this host doesn't provide are
+    /// rejected. Host capabilities that are feature URIs count as provided
+    /// (see [`HostInfo`](crate::host::HostInfo)).
+    pub(crate) fn new(info: &PluginInfo) -> Result<Self> {
+        let description = read_bundle(&info.path)?
+            .into_iter()
+            .find(|description| description.uri == info.unique_id)
+            .ok_or_else(|| {
+                Error::PluginNotFound(format!(
+                    "{} is not in {}",
+                    info.unique_id,
+                    info.path.display()
+                ))
+            })?;
+
+        let host_info = host::host_info();
+        let mut missing = description.missing_features();
+        missing.retain(|feature| !host_info.has_capability(feature));
+        if !missing.is_empty() {
+            return Err(Error::Other(format!(
+                "LV2 plugin {} requires unsupported features: {}",
+                info.name,
+                missing.join(", ")
+            )));
+        }
+        if let Some(port) = description
+            .ports
+            .iter()
+            .find(|port| port.kind == PortKind::Unknown && !port.optional)
+        {
+            return Err(Error::InvalidFormat(format!(
+                "LV2 plugin {} has port {} of an unsupported type",
+                info.name, port.symbol
+            )));
+        }
+
+        let binary = description.binary.clone().ok_or_else(|| {
+            Error::InvalidFormat(format!("LV2 plugin {} has no binary", description.uri))
+        })?;
+        let library = Library::open(&binary)?;
+        let descriptor = library.descriptor(&description.uri).ok_or_else(|| {
+            Error::PluginNotFound(format!(
+                "{} doesn't provide {}",
+                binary.display(),
+                description.uri
+            ))
+        })? as *const ffi::LV2_Descriptor;
+
+        let host = HostFeatures::new(&host_info.capabilities);
+        let urids = Urids {
+            chunk: host.map_str(ffi::LV2_ATOM__CHUNK),
+            sequence: host.map_str(ffi::LV2_ATOM__SEQUENCE),
+            midi_event: host.map_str(ffi::LV2_MIDI__MIDI_EVENT),
+            object: host.map_str(ffi::LV2_ATOM__OBJECT),
+            long: host.map_str(ffi::LV2_ATOM__LONG),
+            float: host.map_str(ffi::LV2_ATOM__FLOAT),
+            int: host.map_str(ffi::LV2_ATOM__INT),
+            position: host.map_str(ffi::LV2_TIME__POSITION),
+            frame: host.map_str(ffi::LV2_TIME__FRAME),
+            speed: host.map_str(ffi::LV2_TIME__SPEED),
+            bar: host.map_str(ffi::LV2_TIME__BAR),
+            bar_beat: host.map_str(ffi::LV2_TIME__BAR_BEAT),
+            beat_unit: host.map_str(ffi::LV2_TIME__BEAT_UNIT),
+            beats_per_bar: host.map_str(ffi::LV2_TIME__BEATS_PER_BAR),
+            beats_per_minute: host.map_str(ffi::LV2_TIME__BEATS_PER_MINUTE),
+        };
+
+        let port_indices = |kind, direction| {
+            description
+                .ports(kind, direction)
+                .map(|port| port.index)
+                .collect::<Vec<_>>()
+        };
+        let atom_ports = |direction| {
+            description
+                .ports
+                .iter()
+                .filter(move |port| {
+                    matches!(port.kind, PortKind::Atom { .. }) && port.direction == direction
+                })
+                .map(|port| (port.index, new_atom_buffer()))
+                .collect::<Vec<_>>()
+        };
+
+        // Index into atom_inputs of the first input port of a kind
+        let atom_input = |kind: fn(PortKind) -> bool| {
+            description
+                .ports
+                .iter()
+                .filter(|port| {
+                    matches!(port.kind, PortKind::Atom { .. })
+                        && port.direction == PortDirection::Input
+                })
+                .position(|port| kind(port.kind))
+        };
+
+        let latency_port = description
+            .ports(PortKind::Control, PortDirection::Output)
+            .find(|port| port.latency)
+            .map(|port| port.index as usize);
+        let enabled_port = description
+            .ports(PortKind::Control, PortDirection::Input)
+            .find(|port| port.enabled)
+            .map(|port| port.index as usize);
+
+        Ok(Self {
+            descriptor,
+            handle: std::ptr::null_mut(),
+            info: info.clone(),
+            controls: description
+                .ports
+                .iter()
+                .map(|port| match port.kind {
+                    PortKind::Control => port.initial_value(),
+                    _ => 0.0,
+                })
+                .collect(),
+            parameters: description
+                .ports(PortKind::Control, PortDirection::Input)
+                .map(|port| port.index as usize)
+                .collect(),
+            audio_inputs: port_indices(PortKind::Audio, PortDirection::Input),
+            audio_outputs: port_indices(PortKind::Audio, PortDirection::Output),
+            latency_port,
+            enabled_port,
+            bypass: HostBypass::new(),
+            cv_buffers: description
+                .ports
+                .iter()
+                .filter(|port| port.kind == PortKind::Cv)
+                .map(|port| (port.index, Vec::new()))
+                .collect(),
+            atom_inputs: atom_ports(PortDirection::Input),
+            atom_outputs: atom_ports(PortDirection::Output),
+            midi_input: atom_input(|port| matches!(port, PortKind::Atom { midi: true, .. })),
+            position_input: atom_input(|port| {
+                matches!(port, PortKind::Atom { position: true, .. })
+            }),
+            description,
+            host,
+            urids,
+            midi: Vec::new(),
+            position: None,
+            state: std::ptr::null(),
+            sample_rate: 0.0,
+            max_block_size: 0,
+            active: false,
+            sample_position: 0,
+            quirks: quirks::lookup(info),
+            _library: library,
+            _not_sync: PhantomData,
+        })
+    }
+
+    /// The plugin's processing latency in samples, or 0 if it reports none
+    ///
+    /// Read from the plugin's latency port, which it updates while
+    /// processing.
+    pub fn latency(&self) -> usize {
+        match self.latency_port {
+            Some(port) if self.active => self.controls[port].max(0.0) as usize,
+            _ => 0,
+        }
+    }
+
+    /// The control input port behind a parameter
+    fn parameter_port(&self, index: usize) -> Result<&PortDescription> {
+        let port = *self
+            .parameters
+            .get(index)
+            .ok_or(Error::InvalidParameter(index))?;
+        Ok(&self.description.ports[port])
+    }
+
+    /// Create the plugin for `sample_rate` and connect its fixed ports
+    fn instantiate(&mut self, sample_rate: f64, max_block_size: usize) -> Result<()> {
+        // Safety: descriptors live as long as their library
+        let descriptor = unsafe { &*self.descriptor };
+        let instantiate = descriptor.instantiate.ok_or_else(|| {
+            Error::InvalidFormat(format!("LV2 plugin {} can't be created", self.info.name))
+        })?;
+
+        // The bundle path is passed with a trailing separator
+        let bundle = paths::to_ffi_bytes(&self.description.bundle.join(""))?;
+        self.host.prepare(sample_rate, max_block_size);
+        let handle = unsafe {
+            instantiate(
+                self.descriptor,
+                sample_rate,
+                bundle.as_ptr(),
+                self.host.feature_list.as_ptr(),
+            )
+        };
+        if handle.is_null() {
+            return Err(Error::Other(format!(
+                "LV2 plugin {} failed to instantiate",
+                self.info.name
+            )));
+        }
+        self.handle = handle;
+
+        self.state = match descriptor.extension_data {
+            Some(extension_data) => unsafe {
+                extension_data(ffi::LV2_STATE__INTERFACE.as_ptr() as *const c_char)
+                    as *const ffi::LV2_State_Interface
+            },
+            None => std::ptr::null(),
+        };
+        Ok(())
+    }
+
+    /// Connect every port except the audio ports, which are connected to
+    /// the caller's buffers on each block
+    fn connect_fixed_ports(&mut self) {
+        let connect = |port: u32, data: *mut c_void| unsafe {
+            if let Some(connect_port) = (*self.descriptor).connect_port {
+                connect_port(self.handle, port, data);
+            }
+        };
+
+        for port in &self.description.ports {
+            match port.kind {
+                PortKind::Control => connect(
+                    port.index,
+                    &mut self.controls[port.index as usize] as *mut f32 as *mut c_void,
+                ),
+                PortKind::Unknown => connect(port.index, std::ptr::null_mut()),
+                _ => {}
+            }
+        }
+        for (port, buffer) in &mut self.cv_buffers {
+            connect(*port, buffer.as_mut_ptr() as *mut c_void);
+        }
+        for (port, buffer) in self.atom_inputs.iter_mut().chain(&mut self.atom_outputs) {
+            connect(*port, buffer.as_mut_ptr() as *mut c_void);
+        }
+    }
+
+    /// Deactivate, if active
+    fn deactivate(&mut self) {
+        if self.active {
+            unsafe {
+                if let Some(deactivate) = (*self.descriptor).deactivate {
+                    deactivate(self.handle);
+                }
+            }
+            self.active = false;
+        }
+    }
+
+    /// Activate an instantiated plugin
+    fn activate(&mut self) {
+        unsafe {
+            if let Some(activate) = (*self.descriptor).activate {
+                activate(self.handle);
+            }
+        }
+        self.active = true;
+    }
+
+    /// Destroy the instance, if there is one
+    fn cleanup(&mut self) {
+        self.deactivate();
+        if !self.handle.is_null() {
+            unsafe {
+                if let Some(cleanup) = (*self.descriptor).cleanup {
+                    cleanup(self.handle);
+                }
+            }
+            self.handle = std::ptr::null_mut();
+            self.state = std::ptr::null();
+        }
+    }
+
+    /// Collect the properties the plugin saves through the state interface
+    ///
+    /// Empty if the plugin has no state interface.
+    fn save_properties(&self) -> Result<Vec<Property>> {
+        let Some(save) = (unsafe { self.state.as_ref() }).and_then(|state| state.save) else {
+            return Ok(Vec::new());
+        };
+
+        let mut store = StateStore {
+            host: &self.host,
+            properties: Vec::new(),
+        };
+        let status = unsafe {
+            save(
+                self.handle,
+                store_property,
+                &mut store as *mut StateStore as *mut c_void,
+                ffi::LV2_STATE_IS_POD | ffi::LV2_STATE_IS_PORTABLE,
+                self.host.feature_list.as_ptr(),
+            )
+        };
+        if status != ffi::LV2_STATE_SUCCESS {
+            return Err(Error::Other(format!(
+                "LV2 plugin {} failed to save its state (status {})",
+                self.info.name, status
+            )));
+        }
+        Ok(store.properties)
+    }
+
+    /// Hand saved properties back to the plugin through the state interface
+    fn restore_properties(&mut self, properties: &[Property]) -> Result<()> {
+        let Some(restore) = (unsafe { self.state.as_ref() }).and_then(|state| state.restore) else {
+            return Ok(());
+        };
+
+        let retrieve = StateRetrieve {
+            properties: properties
+                .iter()
+                .map(|property| {
+                    (
+                        self.host.map_str(&property.key),
+                        self.host.map_str(&property.type_),
+                        property,
+                    )
+                })
+                .collect(),
+        };
+        let status = unsafe {
+            restore(
+                self.handle,
+                retrieve_property,
+                &retrieve as *const StateRetrieve as *mut c_void,
+                ffi::LV2_STATE_IS_POD | ffi::LV2_STATE_IS_PORTABLE,
+                self.host.feature_list.as_ptr(),
+            )
+        };
+        if status != ffi::LV2_STATE_SUCCESS {
+            return Err(Error::Other(format!(
+                "LV2 plugin {} failed to restore its state (status {})",
+                self.info.name, status
+            )));
+        }
+        Ok(())
+    }
+
+    /// Fill the atom input buffers for a block of `num_frames`
+    ///
+    /// The position input gets the pending transport and the MIDI input the
+    /// queued events, the others empty sequences.
+    fn write_sequences(&mut self, num_frames: usize) {
+        let last_frame = num_frames.saturating_sub(1) as i64;
+        for (index, (_, buffer)) in self.atom_inputs.iter_mut().enumerate() {
+            let mut sequence = SequenceWriter::new(buffer, self.urids.sequence);
+            if let Some(context) = self.position.filter(|_| Some(index) == self.position_input) {
+                let object = position_object(&self.urids, &context);
+                sequence.push(0, self.urids.object, &object);
+            }
+            if Some(index) == self.midi_input {
+                for event in &self.midi {
+                    let bytes = event.to_bytes();
+                    let data = &bytes[..midi_message_len(bytes[0])];
+                    // MIDI sent for later blocks lands at the end of this one
+                    let time = (event.sample_offset as i64).min(last_frame);
+                    if !sequence.push(time, self.urids.midi_event, data) {
+                        break;
+                    }
+                }
+            }
+        }
+        self.midi.clear();
+    }
+
+    /// Bookkeeping after the plugin accepted a restored state of `bytes` bytes
+    fn state_restored(&mut self, bytes: usize) -> Result<()> {
+        if self.quirks.contains(Quirk::ResetAfterStateRestore) {
+            self.reset()?;
+        }
+
+        events::emit(HostEvent::StateRestored {
+            info: &self.info,
+            bytes,
+        });
+
+        Ok(())
+    }
+}
+
+impl Drop for Lv2Plugin {
+    fn drop(&mut self) {
+        self.cleanup();
+    }
+}
+
+/// URIDs the host uses while processing
+struct Urids {
+    chunk: ffi::LV2_URID,
+    sequence: ffi::LV2_URID,
+    midi_event: ffi::LV2_URID,
+    object: ffi::LV2_URID,
+    long: ffi::LV2_URID,
+    float: ffi::LV2_URID,
+    int: ffi::LV2_URID,
+    position: ffi::LV2_URID,
+    frame: ffi::LV2_URID,
+    speed: ffi::LV2_URID,
+    bar: ffi::LV2_URID,
+    bar_beat: ffi::LV2_URID,
+    beat_unit: ffi::LV2_URID,
+    beats_per_bar: ffi::LV2_URID,
+    beats_per_minute: ffi::LV2_URID,
+}
+
+/// Size of a `time:Position` object's body: the object header and seven
+/// properties with values of up to 8 bytes
+const POSITION_SIZE: usize = 8 + 7 * 24;
+
+/// The body of a `time:Position` object describing `context`
+///
+/// Beats are counted in the time signature's beat unit, as LV2 expects.
+fn position_object(urids: &Urids, context: &BlockContext) -> [u8; POSITION_SIZE] {
+    let mut object = [0u8; POSITION_SIZE];
+    let mut used = 0;
+    // Write 32-bit words, then a value padded to 8 bytes
+    let mut put = |words: &[u32], value: &[u8]| {
+        for word in words {
+            object[used..used + 4].copy_from_slice(&word.to_ne_bytes());
+            used += 4;
+        }
+        object[used..used + value.len()].copy_from_slice(value);
+        used += (value.len() + 7) & !7;
+    };
+    put(&[0, urids.position], &[]);
+    // Each property's key, context, value size and type, then the value
+    let mut property = |key: ffi::LV2_URID, type_: ffi::LV2_URID, value: &[u8]| {
+        put(&[key, 0, value.len() as u32, type_], value);
+    };
+
+    let signature = context.time_signature;
+    let quarters_per_beat = 4.0 / signature.denominator.max(1) as f64;
+    let bar_beat = (context.beat - context.bar_start).max(0.0) / quarters_per_beat;
+    let speed = if context.playing { 1.0f32 } else { 0.0 };
+    property(
+        urids.frame,
+        urids.long,
+        &(context.sample_position as i64).to_ne_bytes(),
+    );
+    property(urids.speed, urids.float, &speed.to_ne_bytes());
+    property(urids.bar, urids.long, &context.bar.to_ne_bytes());
+    property(
+        urids.bar_beat,
+        urids.float,
+        &(bar_beat as f32).to_ne_bytes(),
+    );
+    property(
+        urids.beat_unit,
+        urids.int,
+        &(signature.denominator as i32).to_ne_bytes(),
+    );
+    property(
+        urids.beats_per_bar,
+        urids.float,
+        &(signature.numerator as f32).to_ne_bytes(),
+    );
+    property(
+        urids.beats_per_minute,
+        urids.float,
+        &((context.tempo / quarters_per_beat) as f32).to_ne_bytes(),
+    );
+    object
+}
+
+/// The features the host passes to the plugin
+///
+/// The option list and feature array point into the struct itself, so it
+/// is boxed and [`prepare()`](Self::prepare) is only called while no
+/// instance holds them.
+struct HostFeatures {
+    // Mapped URIs; URID n is entry n - 1
+    uris: Mutex<Vec<CString>>,
+    map: ffi::LV2_URID_Map,
+    unmap: ffi::LV2_URID_Unmap,
+    sample_rate: f32,
+    min_block_length: i32,
+    max_block_length: i32,
+    options: Vec<ffi::LV2_Options_Option>,
+    // Host capabilities passed as features without data
+    capabilities: Vec<CString>,
+    features: Vec<ffi::LV2_Feature>,
+    // Null-terminated, as passed to instantiate()
+    feature_list: Vec<*const ffi::LV2_Feature>,
+}
+
+impl HostFeatures {
+    fn new(capabilities: &[String]) -> Box<Self> {
+        // Only URIs can be features
+        let capabilities = capabilities
+            .iter()
+            .filter(|capability| capability.contains(':'))
+            .filter_map(|capability| CString::new(capability.as_str()).ok())
+            .collect();
+
+        let mut host = Box::new(Self {
+            uris: Mutex::new(Vec::new()),
+            map: ffi::LV2_URID_Map {
+                handle: std::ptr::null_mut(),
+                map: Some(urid_map),
+            },
+            unmap: ffi::LV2_URID_Unmap {
+                handle: std::ptr::null_mut(),
+                unmap: Some(urid_unmap),
+            },
+            sample_rate: 0.0,
+            min_block_length: 0,
+            max_block_length: 0,
+            options: Vec::new(),
+            capabilities,
+            features: Vec::new(),
+            feature_list: Vec::new(),
+        });
+        let handle = &mut *host as *mut Self as *mut c_void;
+        host.map.handle = handle;
+        host.unmap.handle = handle;
+        host
+    }
+
+    /// Fill in the options and feature list for an instance
+    fn prepare(&mut self, sample_rate: f64, max_block_size: usize) {
+        self.sample_rate = sample_rate as f32;
+        self.min_block_length = 1;
+        self.max_block_length = max_block_size.min(i32::MAX as usize) as i32;
+
+        let float = self.map_str(ffi::LV2_ATOM__FLOAT);
+        let int = self.map_str(ffi::LV2_ATOM__INT);
+        let option =
+            |key: &str, size: usize, type_, value: *const c_void| ffi::LV2_Options_Option {
+                context: ffi::LV2_OPTIONS_INSTANCE,
+                subject: 0,
+                key: self.map_str(key),
+                size: size as u32,
+                type_,
+                value,
+            };
+        let options = vec![
+            option(
+                ffi::LV2_PARAMETERS__SAMPLE_RATE,
+                std::mem::size_of::<f32>(),
+                float,
+                &self.sample_rate as *const f32 as *const c_void,
+            ),
+            option(
+                ffi::LV2_BUF_SIZE__MIN_BLOCK_LENGTH,
+                std::mem::size_of::<i32>(),
+                int,
+                &self.min_block_length as *const i32 as *const c_void,
+            ),
+            option(
+                ffi::LV2_BUF_SIZE__MAX_BLOCK_LENGTH,
+                std::mem::size_of::<i32>(),
+                int,
+                &self.max_block_length as *const i32 as *const c_void,
+            ),
+            option(
+                ffi::LV2_BUF_SIZE__NOMINAL_BLOCK_LENGTH,
+                std::mem::size_of::<i32>(),
+                int,
+                &self.max_block_length as *const i32 as *const c_void,
+            ),
+            // Terminates the list
+            ffi::LV2_Options_Option {
+                context: ffi::LV2_OPTIONS_INSTANCE,
+                subject: 0,
+                key: 0,
+                size: 0,
+                type_: 0,
+                value: std::ptr::null(),
+            },
+        ];
+        self.options = options;
+
+        let feature = |uri: &'static [u8], data: *mut c_void| ffi::LV2_Feature {
+            uri: uri.as_ptr() as *const c_char,
+            data,
+        };
+        self.features = vec![
+            feature(ffi::LV2_URID__MAP, &mut self.map as *mut _ as *mut c_void),
+            feature(
+                ffi::LV2_URID__UNMAP,
+                &mut self.unmap as *mut _ as *mut c_void,
+            ),
+            feature(
+                ffi::LV2_OPTIONS__OPTIONS,
+                self.options.as_mut_ptr() as *mut c_void,
+            ),
+            feature(
+                ffi::LV2_BUF_SIZE__BOUNDED_BLOCK_LENGTH,
+                std::ptr::null_mut(),
+            ),
+        ];
+        let provided: Vec<&CStr> = self
+            .features
+            .iter()
+            // Safety: the standard feature URIs are null-terminated constants
+            .map(|feature| unsafe { CStr::from_ptr(feature.uri) })
+            .collect();
+        let declared: Vec<ffi::LV2_Feature> = self
+            .capabilities
+            .iter()
+            .filter(|capability| !provided.contains(&capability.as_c_str()))
+            .map(|capability| ffi::LV2_Feature {
+                uri: capability.as_ptr(),
+                data: std::ptr::null_mut(),
+            })
+            .collect();
+        self.features.extend(declared);
+        self.feature_list = self
+            .features
+            .iter()
+            .map(|feature| feature as *const ffi::LV2_Feature)
+            .chain(std::iter::once(std::ptr::null()))
+            .collect();
+    }
+
+    /// The URID for `uri`, mapping it if it's new
+    fn map(&self, uri: &CStr) -> ffi::LV2_URID {
+        let mut uris = self.uris.lock().unwrap_or_else(|e| e.into_inner());
+        let index = match uris.iter().position(|known| known.as_c_str() == uri) {
+            Some(index) => index,
+            None => {
+                uris.push(uri.to_owned());
+                uris.len() - 1
+            }
+        };
+        index as ffi::LV2_URID + 1
+    }
+
+    /// The URID for `uri`, or 0 if it contains a null byte
+    fn map_str(&self, uri: &str) -> ffi::LV2_URID {
+        CString::new(uri).map_or(0, |uri| self.map(&uri))
+    }
+
+    /// The URI for a URID, if it was mapped
+    ///
+    /// The string stays valid as long as the features do.
+    fn unmap(&self, urid: ffi::LV2_URID) -> Option<&CStr> {
+        let uris = self.uris.lock().unwrap_or_else(|e| e.into_inner());
+        let uri = uris.get((urid as usize).checked_sub(1)?)?;
+        // Safety: mapped strings are never removed or changed, and moving a
+        // CString doesn't move its contents
+        Some(unsafe { CStr::from_ptr(uri.as_ptr()) })
+    }
+}
+
+unsafe extern "C" fn urid_map(handle: *mut c_void, uri: *const c_char) -> ffi::LV2_URID {
+    if handle.is_null() || uri.is_null() {
+        return 0;
+    }
+    (*(handle as *const HostFeatures)).map(CStr::from_ptr(uri))
+}
+
+unsafe extern "C" fn urid_unmap(handle: *mut c_void, urid: ffi::LV2_URID) -> *const c_char {
+    if handle.is_null() {
+        return std::ptr::null();
+    }
+    match (*(handle as *const HostFeatures)).unmap(urid) {
+        Some(uri) => uri.as_ptr(),
+        None => std::ptr::null(),
+    }
+}
+
+/// A property saved through the LV2 state interface
+struct Property {
+    key: String,
+    type_: String,
+    flags: u32,
+    value: Vec<u8>,
+}
+
+/// A state save in progress, shared with [`store_property()`]
+struct StateStore<'a> {
+    host: &'a HostFeatures,
+    properties: Vec<Property>,
+}
+
+/// A state restore in progress, shared with [`retrieve_property()`]
+///
+/// Properties are listed with their key and type mapped for this instance.
+struct StateRetrieve<'a> {
+    properties: Vec<(ffi::LV2_URID, ffi::LV2_URID, &'a Property)>,
+}
+
+/// `LV2_State_Store_Function` over a [`StateStore`]
+///
+/// Only plain-data values can be kept, since they outlive the instance.
+unsafe extern "C" fn store_property(
+    handle: ffi::LV2_State_Handle,
+    key: ffi::LV2_URID,
+    value: *const c_void,
+    size: usize,
+    type_: ffi::LV2_URID,
+    flags: u32,
+) -> u32 {
+    let store = &mut *(handle as *mut StateStore);
+    if flags & ffi::LV2_STATE_IS_POD == 0 {
+        return ffi::LV2_STATE_ERR_BAD_FLAGS;
+    }
+    let (Some(key), Some(type_)) = (store.host.unmap(key), store.host.unmap(type_)) else {
+        return ffi::LV2_STATE_ERR_UNKNOWN;
+    };
+    let value = if size == 0 || value.is_null() {
+        Vec::new()
+    } else {
+        std::slice::from_raw_parts(value as *const u8, size).to_vec()
+    };
+
+    let property = Property {
+        key: key.to_string_lossy().into_owned(),
+        type_: type_.to_string_lossy().into_owned(),
+        flags,
+        value,
+    };
+    // A key stored twice keeps its last value
+    store.properties.retain(|p| p.key != property.key);
+    store.properties.push(property);
+    ffi::LV2_STATE_SUCCESS
+}
+
+/// `LV2_State_Retrieve_Function` over a [`StateRetrieve`]
+unsafe extern "C" fn retrieve_property(
+    handle: ffi::LV2_State_Handle,
+    key: ffi::LV2_URID,
+    size: *mut usize,
+    type_: *mut ffi::LV2_URID,
+    flags: *mut u32,
+) -> *const c_void {
+    let retrieve = &*(handle as *const StateRetrieve);
+    let Some((_, property_type, property)) =
+        retrieve.properties.iter().find(|(urid, _, _)| *urid == key)
+    else {
+        return std::ptr::null();
+    };
+    if !size.is_null() {
+        *size = property.value.len();
+    }
+    if !type_.is_null() {
+        *type_ = *property_type;
+    }
+    if !flags.is_null() {
+        *flags = property.flags;
+    }
+    property.value.as_ptr() as *const c_void
+}
+
+/// An atom buffer, zeroed
+fn new_atom_buffer() -> Box<[u64]> {
+    vec![0u64; ATOM_BUFFER_SIZE / 8].into_boxed_slice()
+}
+
+/// Writes an `LV2_Atom_Sequence` of frame-timed events into a buffer
+struct SequenceWriter<'a> {
+    buffer: &'a mut [u64],
+    // Bytes used, including the sequence header
+    used: usize,
+}
+
+impl<'a> SequenceWriter<'a> {
+    /// Start an empty sequence at the start of `buffer`
+    fn new(buffer: &'a mut [u64], sequence: ffi::LV2_URID) -> Self {
+        let mut writer = Self { buffer, used: 0 };
+        writer.write(
+            0,
+            &ffi::LV2_Atom_Sequence {
+                atom: ffi::LV2_Atom {
+                    size: std::mem::size_of::<ffi::LV2_Atom_Sequence_Body>() as u32,
+                    type_: sequence,
+                },
+                body: ffi::LV2_Atom_Sequence_Body { unit: 0, pad: 0 },
+            },
+        );
+        writer.used = std::mem::size_of::<ffi::LV2_Atom_Sequence>();
+        writer
+    }
+
+    /// Append an event, returning false if it doesn't fit
+    fn push(&mut self, time: i64, type_: ffi::LV2_URID, data: &[u8]) -> bool {
+        let header = std::mem::size_of::<ffi::LV2_Atom_Event>();
+        let size = header + ((data.len() + 7) & !7);
+        if self.used + size > self.buffer.len() * 8 {
+            return false;
+        }
+
+        self.write(
+            self.used,
+            &ffi::LV2_Atom_Event {
+                time_frames: time,
+                body: ffi::LV2_Atom {
+                    size: data.len() as u32,
+                    type_,
+                },
+            },
+        );
+        let start = self.used + header;
+        let bytes = self.bytes();
+        bytes[start..start + data.len()].copy_from_slice(data);
+        self.used += size;
+
+        // The sequence's size counts everything after its atom header
+        let atom_size = (self.used - std::mem::size_of::<ffi::LV2_Atom>()) as u32;
+        self.bytes()[..4].copy_from_slice(&atom_size.to_ne_bytes());
+        true
+    }
+
+    fn bytes(&mut self) -> &mut [u8] {
+        // Safety: u64 has no invalid bit patterns as bytes and u8 has no
+        // alignment requirement
+        unsafe {
+            std::slice::from_raw_parts_mut(
+                self.buffer.as_mut_ptr() as *mut u8,
+                self.buffer.len() * 8,
+            )
+        }
+    }
+
+    /// Copy `value` to `offset`, which is a multiple of 8
+    fn write<T: Copy>(&mut self, offset: usize, value: &T) {
+        let size = std::mem::size_of::<T>();
+        // Safety: T is a plain repr(C) struct
+        let value = unsafe { std::slice::from_raw_parts(value as *const T as *const u8, size) };
+        self.bytes()[offset..offset + size].copy_from_slice(value);
+    }
+}
+
+/// Mark an atom output buffer as free space for the plugin to write into
+fn clear_atom_output(buffer: &mut [u64], chunk: ffi::LV2_URID) {
+    let size = (buffer.len() * 8 - std::mem::size_of::<ffi::LV2_Atom>()) as u64;
+    // The atom header is the first 8 bytes: size, then type
+    let header = if cfg!(target_endian = "little") {
+        size | (chunk as u64) << 32
+    } else {
+        size << 32 | chunk as u64
+    };
+    buffer[0] = header;
+}
+
+/// Length of a MIDI message by its status byte
+fn midi_message_len(status: u8) -> usize {
+    match status & 0xF0 {
+        0xC0 | 0xD0 => 2,
+        0xF0 => 1,
+        _ => 3,
+    }
+}
+
+impl PluginInstance for Lv2Plugin {
+    fn initialize(&mut self, sample_rate: f64, max_block_size: usize) -> Result<()> {
+        if max_block_size == 0 || max_block_size > i32::MAX as usize {
+            return Err(Error::Other(format!(
+                "Block size {} out of range",
+                max_block_size
+            )));
+        }
+        self.deactivate();
+
+        // Options are fixed when the plugin is created, so a new rate or
+        // block size means a new instance, carrying the state across
+        if !self.handle.is_null()
+            && (self.sample_rate != sample_rate || self.max_block_size != max_block_size)
+        {
+            let properties = self.save_properties()?;
+            self.cleanup();
+            self.instantiate(sample_rate, max_block_size)?;
+            self.restore_properties(&properties)?;
+        } else if self.handle.is_null() {
+            self.instantiate(sample_rate, max_block_size)?;
+        }
+
+        for (_, buffer) in &mut self.cv_buffers {
+            *buffer = vec![0.0; max_block_size];
+        }
+        self.connect_fixed_ports();
+        self.activate();
+
+        self.sample_rate = sample_rate;
+        self.max_block_size = max_block_size;
+        self.bypass
+            .prepare(sample_rate, self.audio_outputs.len(), self.latency());
+        self.midi.reserve(256);
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        // LV2 has no reset; reactivating clears the plugin's internal state
+        self.deactivate();
+        self.activate();
+        Ok(())
+    }
+
+    fn process(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        // Reject oversized blocks here rather than passing them to the plugin
+        if num_frames > self.max_block_size {
+            return Err(Error::BlockTooLarge {
+                max: self.max_block_size,
+                got: num_frames,
+            });
+        }
+
+        // Validate channel counts match plugin configuration
+        if inputs.len() != self.audio_inputs.len() {
+            return Err(Error::Other(format!(
+                "Input channel count mismatch: plugin expects {}, got {}",
+                self.audio_inputs.len(),
+                inputs.len()
+            )));
+        }
+        if outputs.len() != self.audio_outputs.len() {
+            return Err(Error::Other(format!(
+                "Output channel count mismatch: plugin expects {}, got {}",
+                self.audio_outputs.len(),
+                outputs.len()
+            )));
+        }
+
+        for (i, input) in inputs.iter().enumerate() {
+            if input.len() < num_frames {
+                return Err(Error::Other(format!(
+                    "Input channel {} has {} samples, need at least {}",
+                    i,
+                    input.len(),
+                    num_frames
+                )));
+            }
+        }
+        for (i, output) in outputs.iter().enumerate() {
+            if output.len() < num_frames {
+                return Err(Error::Other(format!(
+                    "Output channel {} has {} samples, need at least {}",
+                    i,
+                    output.len(),
+                    num_frames
+                )));
+            }
+        }
+
+        // The plugin is never run for empty blocks; queued MIDI waits for
+        // the next one
+        if num_frames == 0 {
+            return Ok(());
+        }
+
+        let descriptor = unsafe { &*self.descriptor };
+        if let Some(connect_port) = descriptor.connect_port {
+            // The plugin only reads the inputs
+            for (&port, input) in self.audio_inputs.iter().zip(inputs) {
+                unsafe { connect_port(self.handle, port, input.as_ptr() as *mut c_void) };
+            }
+            for (&port, output) in self.audio_outputs.iter().zip(outputs.iter_mut()) {
+                if self.quirks.contains(Quirk::ClearOutputsBeforeProcess) {
+                    output[..num_frames].fill(0.0);
+                }
+                unsafe { connect_port(self.handle, port, output.as_mut_ptr() as *mut c_void) };
+            }
+        }
+
+        self.write_sequences(num_frames);
+        for (_, buffer) in &mut self.atom_outputs {
+            clear_atom_output(buffer, self.urids.chunk);
+        }
+
+        let run = descriptor.run.ok_or_else(|| {
+            Error::InvalidFormat(format!("LV2 plugin {} can't process", self.info.name))
+        })?;
+        unsafe { run(self.handle, num_frames as u32) };
+        self.bypass.process(inputs, outputs, num_frames);
+
+        self.sample_position += num_frames as u64;
+        Ok(())
+    }
+
+    /// Process with `context` sent to the plugin as a `time:Position`
+    fn process_with_context(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        context: &BlockContext,
+    ) -> Result<()> {
+        self.position = Some(*context);
+        let result = self.process(inputs, outputs, context.num_frames);
+        self.position = None;
+        result?;
+        self.sample_position = context.sample_position + context.num_frames as u64;
+        Ok(())
+    }
+
+    /// Split the block at each change offset
+    ///
+    /// A control port holds one value per `run()`, so each piece is run with
+    /// the values set for it; MIDI keeps its offset within the piece.
+    fn process_with_events(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        changes: &[ParameterChange],
+        midi: &[MidiEvent],
+    ) -> Result<()> {
+        traits::split_events(self, inputs, outputs, num_frames, changes, midi)
+    }
+
+    fn sample_position(&self) -> u64 {
+        self.sample_position
+    }
+
+    fn set_sample_position(&mut self, position: u64) -> Result<()> {
+        if position > i64::MAX as u64 {
+            return Err(Error::Other(format!(
+                "Sample position {} out of range",
+                position
+            )));
+        }
+        // Plugins don't see the timeline
+        self.sample_position = position;
+        Ok(())
+    }
+
+    fn flush_events(&mut self) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        // Control ports are read directly by the plugin, so parameter
+        // changes are already in place; MIDI needs a block to arrive in
+        Ok(())
+    }
+
+    fn parameter_count(&self) -> usize {
+        self.parameters.len()
+    }
+
+    fn parameter_info(&self, index: usize) -> Result<ParameterInfo> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        let port = self.parameter_port(index)?;
+        let (min, max) = port.range();
+        let visibility = if port.hidden {
+            ParameterVisibility::Hidden
+        } else {
+            ParameterVisibility::Visible
+        };
+        let curve = if port.logarithmic {
+            ParameterCurve::Logarithmic
+        } else {
+            ParameterCurve::Linear
+        };
+
+        Ok(ParameterInfo::new(
+            index,
+            port.name.clone(),
+            min,
+            max,
+            port.initial_value(),
+            port.unit.clone(),
+        )
+        .with_visibility(visibility)
+        .with_curve(curve))
+    }
+
+    fn get_parameter(&self, index: usize) -> Result<f32> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        let port = self.parameter_port(index)?;
+        let (min, max) = port.range();
+        if max <= min {
+            return Ok(0.0);
+        }
+        let value = self.controls[port.index as usize];
+        Ok(((value - min) / (max - min)).clamp(0.0, 1.0))
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        // Control ports take plain values
+        let port = self.parameter_port(index)?;
+        let (min, max) = port.range();
+        let mut plain = min + value.clamp(0.0, 1.0) * (max - min);
+        if port.toggled || port.integer {
+            plain = plain.round();
+        }
+        let port = port.index as usize;
+        self.controls[port] = plain;
+        Ok(())
+    }
+
+    fn send_midi(&mut self, events: &[MidiEvent]) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        // Plugins without a MIDI input ignore MIDI
+        if self.midi_input.is_none() {
+            return Ok(());
+        }
+        for event in events {
+            let at = self
+                .midi
+                .partition_point(|e| e.sample_offset <= event.sample_offset);
+            self.midi.insert(at, *event);
+        }
+        Ok(())
+    }
+
+    fn preset_count(&self) -> Result<usize> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        Ok(self.description.presets.len())
+    }
+
+    fn preset_info(&self, index: usize) -> Result<PresetInfo> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        let preset = self
+            .description
+            .presets
+            .get(index)
+            .ok_or_else(|| Error::Other(format!("Preset index {} out of range", index)))?;
+        Ok(PresetInfo::new(index, preset.label.clone(), index as i32))
+    }
+
+    fn load_preset(&mut self, preset_number: i32) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        let preset = usize::try_from(preset_number)
+            .ok()
+            .and_then(|index| self.description.presets.get(index))
+            .ok_or_else(|| Error::Other(format!("Preset {} out of range", preset_number)))?;
+        for (symbol, value) in &preset.values {
+            if let Some(port) = self
+                .description
+                .ports(PortKind::Control, PortDirection::Input)
+                .find(|port| &port.symbol == symbol)
+            {
+                self.controls[port.index as usize] = *value;
+            }
+        }
+        Ok(())
+    }
+
+    fn get_state(&self) -> Result<Vec<u8>> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        let properties = self.save_properties()?;
+        let mut data = Vec::new();
+        data.extend_from_slice(STATE_MAGIC);
+        data.extend_from_slice(&STATE_VERSION.to_le_bytes());
+
+        let ports: Vec<_> = self
+            .description
+            .ports(PortKind::Control, PortDirection::Input)
+            .collect();
+        write_len(&mut data, ports.len());
+        for port in ports {
+            write_bytes(&mut data, port.symbol.as_bytes());
+            data.extend_from_slice(&self.controls[port.index as usize].to_le_bytes());
+        }
+
+        write_len(&mut data, properties.len());
+        for property in &properties {
+            write_bytes(&mut data, property.key.as_bytes());
+            write_bytes(&mut data, property.type_.as_bytes());
+            data.extend_from_slice(&property.flags.to_le_bytes());
+            write_bytes(&mut data, &property.value);
+        }
+        Ok(data)
+    }
+
+    fn set_state(&mut self, data: &[u8]) -> Result<()> {
+        if data.is_empty() {
+            return Err(Error::Other("State data is empty".to_string()));
+        }
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        let mut reader = StateReader { data };
+        if reader.take(4)? != STATE_MAGIC {
+            return Err(Error::InvalidFormat("Not an LV2 state".to_string()));
+        }
+        let version = reader.u32()?;
+        if version != STATE_VERSION {
+            return Err(Error::InvalidFormat(format!(
+                "Unsupported LV2 state version {}",
+                version
+            )));
+        }
+
+        let mut values = Vec::new();
+        for _ in 0..reader.u32()? {
+            let symbol = reader.string()?;
+            let value = f32::from_le_bytes(reader.take(4)?.try_into().unwrap_or_default());
+            values.push((symbol, value));
+        }
+        let mut properties = Vec::new();
+        for _ in 0..reader.u32()? {
+            properties.push(Property {
+                key: reader.string()?,
+                type_: reader.string()?,
+                flags: reader.u32()?,
+                value: reader.bytes()?.to_vec(),
+            });
+        }
+
+        // Ports the plugin no longer has are skipped
+        for (symbol, value) in values {
+            if let Some(port) = self
+                .description
+                .ports(PortKind::Control, PortDirection::Input)
+                .find(|port| port.symbol == symbol)
+            {
+                self.controls[port.index as usize] = value;
+            }
+        }
+        if !properties.is_empty() {
+            self.restore_properties(&properties)?;
+        }
+
+        self.state_restored(data.len())
+    }
+
+    fn info(&self) -> &PluginInfo {
+        &self.info
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.active
+    }
+
+    fn input_channels(&self) -> usize {
+        self.audio_inputs.len()
+    }
+
+    fn output_channels(&self) -> usize {
+        self.audio_outputs.len()
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.latency()
+    }
+
+    fn set_bypass(&mut self, bypassed: bool) -> Result<()> {
+        match self.enabled_port {
+            Some(port) => self.controls[port] = if bypassed { 0.0 } else { 1.0 },
+            None => self.bypass.set_bypassed(bypassed),
+        }
+        Ok(())
+    }
+
+    fn is_bypassed(&self) -> bool {
+        match self.enabled_port {
+            Some(port) => self.controls[port] <= 0.0,
+            None => self.bypass.is_bypassed(),
+        }
+    }
+
+    fn quirks(&self) -> Quirks {
+        self.quirks.clone()
+    }
+}
+
+fn write_len(data: &mut Vec<u8>, len: usize) {
+    data.extend_from_slice(&(len as u32).to_le_bytes());
+}
+
+fn write_bytes(data: &mut Vec<u8>, bytes: &[u8]) {
+    write_len(data, bytes.len());
+    data.extend_from_slice(bytes);
+}
+
+/// Reads the fields of a saved state in order
+struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(Error::InvalidFormat("LV2 state is truncated".to_string()));
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        String::from_utf8(self.bytes()?.to_vec())
+            .map_err(|_| Error::InvalidFormat("LV2 state has an invalid string".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lv2::test_plugin;
+    use crate::lv2::Lv2Scanner;
+    use crate::scan::ScannerConfig;
+    use crate::PluginScanner;
+
+    #[test]
+    fn test_capabilities_become_features() {
+        let capabilities = [
+            "http://example.org/ns#flag".to_string(),
+            "offline-render".to_string(),
+            "http://lv2plug.in/ns/ext/urid#map".to_string(),
+        ];
+        let mut host = HostFeatures::new(&capabilities);
+        host.prepare(48000.0, 64);
+
+        let features: Vec<(&str, bool)> = host.feature_list[..host.feature_list.len() - 1]
+            .iter()
+            .map(|&feature| unsafe {
+                (
+                    CStr::from_ptr((*feature).uri).to_str().unwrap(),
+                    (*feature).data.is_null(),
+                )
+            })
+            .collect();
+        assert!(features.contains(&("http://example.org/ns#flag", true)));
+        assert!(!features.iter().any(|(uri, _)| *uri == "offline-render"));
+        let maps = features
+            .iter()
+            .filter(|(uri, _)| *uri == "http://lv2plug.in/ns/ext/urid#map");
+        assert_eq!(maps.count(), 1);
+        assert!(host.feature_list.last().unwrap().is_null());
+    }
+
+    #[test]
+    fn test_gain_plugin() {
+        let dir = std::env::temp_dir().join(format!("rack-lv2-instance-{}", std::process::id()));
+        let _bundle = test_plugin::install(&dir.join("gain.lv2"));
+
+        let scanner =
+            Lv2Scanner::with_config(ScannerConfig::new().path(&dir).skip_default_paths(true))
+                .unwrap();
+        let info = scanner.scan().unwrap().remove(0);
+        let mut plugin = scanner.load(&info).unwrap();
+        assert!(
+            plugin.process(&[], &mut [], 0).is_err(),
+            "Not initialized yet"
+        );
+
+        plugin.initialize(48000.0, 64).unwrap();
+        assert_eq!((plugin.input_channels(), plugin.output_channels()), (2, 2));
+        assert_eq!(plugin.parameter_count(), 1);
+        let param = plugin.parameter_info(0).unwrap();
+        assert_eq!(param.name, "Gain");
+        assert_eq!(param.unit, "dB");
+        assert_eq!((param.min, param.max, param.default), (0.0, 2.0, 1.0));
+        assert_eq!(plugin.get_parameter(0).unwrap(), 0.5);
+        plugin.set_parameter(0, 1.0).unwrap();
+
+        let input = vec![0.25f32; 64];
+        let mut left = vec![0.0f32; 64];
+        let mut right = vec![0.0f32; 64];
+        plugin
+            .send_midi(&[
+                MidiEvent::note_on(60, 100, 0, 200),
+                MidiEvent::note_on(64, 100, 0, 10),
+                MidiEvent::control_change(7, 100, 0, 0),
+            ])
+            .unwrap();
+        plugin
+            .process(&[&input, &input], &mut [&mut left, &mut right], 64)
+            .unwrap();
+        assert!(left.iter().chain(&right).all(|&s| s == 0.5));
+        assert_eq!(plugin.sample_position(), 64);
+
+        // The transport goes ahead of the MIDI as a time:Position object
+        let context = BlockContext::new(48000.0, 96000, 64);
+        plugin
+            .process_with_context(&[&input, &input], &mut [&mut left, &mut right], &context)
+            .unwrap();
+        assert_eq!(plugin.sample_position(), 96064);
+        let sequence = &plugin.atom_inputs[0].1;
+        // Sequence header, then the event's time and atom header
+        let event = sequence[2..4].iter().flat_map(|word| word.to_ne_bytes());
+        let event: Vec<u8> = event.collect();
+        let word =
+            |index: usize| u32::from_ne_bytes(event[index * 4..index * 4 + 4].try_into().unwrap());
+        assert_eq!(word(2), POSITION_SIZE as u32);
+        assert_eq!(word(3), plugin.urids.object);
+        let body = sequence[4..].iter().flat_map(|word| word.to_ne_bytes());
+        let body: Vec<u8> = body.take(POSITION_SIZE).collect();
+        assert_eq!(body, position_object(&plugin.urids, &context));
+        assert_eq!(
+            u32::from_ne_bytes(body[4..8].try_into().unwrap()),
+            plugin.urids.position
+        );
+        // time:frame comes first
+        assert_eq!(i64::from_ne_bytes(body[24..32].try_into().unwrap()), 96000);
+        assert_eq!(plugin.latency(), test_plugin::LATENCY);
+        assert_eq!(plugin.latency_samples(), test_plugin::LATENCY);
+
+        // The gain port value and the plugin's note count
+        let state = plugin.get_state().unwrap();
+        plugin.set_parameter(0, 0.0).unwrap();
+        plugin.initialize(44100.0, 128).unwrap();
+        plugin.set_state(&state).unwrap();
+        assert_eq!(plugin.get_parameter(0).unwrap(), 1.0);
+        assert_eq!(
+            plugin.get_state().unwrap(),
+            state,
+            "Both notes survive a save and restore"
+        );
+
+        // No lv2:enabled port, so the host bypasses it
+        plugin.set_bypass(true).unwrap();
+        assert!(plugin.is_bypassed());
+        let input = vec![0.25f32; 128];
+        let mut left = vec![0.0f32; 128];
+        let mut right = vec![0.0f32; 128];
+        for _ in 0..5 {
+            plugin
+                .process(&[&input, &input], &mut [&mut left, &mut right], 128)
+                .unwrap();
+        }
+        assert!(left.iter().chain(&right).all(|&s| s == 0.25));
+
+        assert_eq!(plugin.preset_count().unwrap(), 1);
+        assert_eq!(plugin.preset_info(0).unwrap().name, "Quiet");
+        plugin.load_preset(0).unwrap();
+        assert_eq!(plugin.get_parameter(0).unwrap(), 0.25);
+        assert!(plugin.set_state(b"nope").is_err());
+
+        drop(plugin);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+//! Loading LV2 plugin binaries
+//!
+//! A binary may hold several plugins, listed by its `lv2_descriptor()`
+//! function. Open binaries are kept in a process-wide table so instances of
+//! plugins from the same binary share one loaded copy; it is unloaded when
+//! the last instance is dropped.
+
+use crate::{Error, Result};
+use std::ffi::{c_void, CStr};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+
+use super::ffi;
+use crate::dylib as sys;
+
+/// Libraries currently open, by the path they were opened from
+static LIBRARIES: Mutex<Vec<(PathBuf, Weak<Library>)>> = Mutex::new(Vec::new());
+
+/// A loaded LV2 binary
+pub(super) struct Library {
+    // Null for descriptor functions compiled into the process (tests)
+    handle: *mut c_void,
+    descriptor_fn: ffi::LV2_Descriptor_Function,
+}
+
+// Safety: lv2_descriptor() and the descriptors it returns may be used from
+// any thread
+unsafe impl Send for Library {}
+unsafe impl Sync for Library {}
+
+impl Library {
+    /// Load the binary at `path`, or share the copy that is already loaded
+    pub fn open(path: &Path) -> Result<Arc<Self>> {
+        let mut libraries = LIBRARIES.lock().unwrap_or_else(|e| e.into_inner());
+        libraries.retain(|(_, library)| library.strong_count() > 0);
+        if let Some(library) = libraries
+            .iter()
+            .find(|(open, _)| open == path)
+            .and_then(|(_, library)| library.upgrade())
+        {
+            return Ok(library);
+        }
+
+        let handle = sys::open(path)?;
+        // Safety: handle is a library that was just opened
+        let symbol = unsafe { sys::symbol(handle, ffi::LV2_DESCRIPTOR_SYMBOL) };
+        if symbol.is_null() {
+            unsafe { sys::close(handle) };
+            return Err(Error::InvalidFormat(format!(
+                "{} doesn't export lv2_descriptor",
+                path.display()
+            )));
+        }
+
+        let library = Arc::new(Self {
+            handle,
+            // Safety: lv2_descriptor has this signature in every LV2 binary
+            descriptor_fn: unsafe {
+                std::mem::transmute::<*mut c_void, ffi::LV2_Descriptor_Function>(symbol)
+            },
+        });
+        libraries.push((path.to_path_buf(), Arc::downgrade(&library)));
+        Ok(library)
+    }
+
+    /// Register a descriptor function compiled into the process as the
+    /// binary at `path`
+    #[cfg(test)]
+    pub fn from_function(path: &Path, descriptor_fn: ffi::LV2_Descriptor_Function) -> Arc<Self> {
+        let library = Arc::new(Self {
+            handle: std::ptr::null_mut(),
+            descriptor_fn,
+        });
+        let mut libraries = LIBRARIES.lock().unwrap_or_else(|e| e.into_inner());
+        libraries.push((path.to_path_buf(), Arc::downgrade(&library)));
+        library
+    }
+
+    /// The descriptor of the plugin with this URI, if the binary has it
+    ///
+    /// The descriptor lives as long as the library.
+    pub fn descriptor(&self, uri: &str) -> Option<&ffi::LV2_Descriptor> {
+        for index in 0.. {
+            // Safety: lv2_descriptor returns null past the last plugin, and
+            // descriptors stay valid while the binary is loaded
+            let descriptor = unsafe { (self.descriptor_fn)(index).as_ref() }?;
+            if descriptor.uri.is_null() {
+                continue;
+            }
+            if unsafe { CStr::from_ptr(descriptor.uri) }.to_bytes() == uri.as_bytes() {
+                return Some(descriptor);
+            }
+        }
+        None
+    }
+}
+
+impl Drop for Library {
+    fn drop(&mut self) {
+        if !self.handle.is_null() {
+            unsafe { sys::close(self.handle) };
+        }
+    }
+}
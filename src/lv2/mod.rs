@@ -0,0 +1,11 @@
+mod bundle;
+mod ffi;
+mod instance;
+mod library;
+mod scanner;
+#[cfg(test)]
+pub(crate) mod test_plugin;
+mod turtle;
+
+pub use instance::Lv2Plugin;
+pub use scanner::{Lv2Scanner, LV2_PATH_ENV};
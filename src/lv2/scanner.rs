@@ -0,0 +1,244 @@
+use crate::events::{self, HostEvent};
+use crate::metadata::{self, SharedMetadataStore};
+use crate::scan::{find_bundles, BundleKind, ScanFilter, ScannerConfig};
+use crate::{PluginFormat, PluginInfo, PluginScanner, Result};
+use std::path::{Path, PathBuf};
+
+use super::bundle::{read_bundle, PluginDescription};
+use super::instance::Lv2Plugin;
+
+/// Environment variable listing the LV2 search paths, defined by the LV2
+/// specification
+///
+/// Uses the platform's path list separator (`:` on Unix, `;` on Windows).
+/// When set, it replaces the default locations.
+pub const LV2_PATH_ENV: &str = "LV2_PATH";
+
+/// How deep [`scan()`](PluginScanner::scan) looks below each search path
+///
+/// LV2 bundles sit directly in the search folders.
+const SEARCH_DEPTH: usize = 1;
+
+/// Scanner for LV2 plugins
+///
+/// Plugins are described from the Turtle data in each `.lv2` bundle, so
+/// scanning never loads plugin binaries. Bundles whose data can't be read
+/// are skipped and reported as [`HostEvent::Error`].
+pub struct Lv2Scanner {
+    usage: Option<SharedMetadataStore>,
+    config: ScannerConfig,
+}
+
+impl Lv2Scanner {
+    /// Create a new LV2 scanner
+    ///
+    /// Searches the default system paths plus any listed in `RACK_PLUGIN_PATH`
+    /// (see [`ScannerConfig::from_env()`]).
+    ///
+    /// # Errors
+    ///
+    /// Never fails; returns a `Result` like the other scanners
+    pub fn new() -> Result<Self> {
+        Self::with_config(ScannerConfig::from_env())
+    }
+
+    /// Record every successful [`load()`](PluginScanner::load) in `store`
+    ///
+    /// Enables [`most_used()`](PluginScanner::most_used). Usage tracking is off
+    /// until this is called.
+    pub fn track_usage(&mut self, store: SharedMetadataStore) {
+        self.usage = Some(store);
+    }
+
+    /// Describe the plugins in every LV2 bundle under `roots`
+    fn scan_roots(
+        &self,
+        roots: &[PathBuf],
+        depth: usize,
+        filter: Option<&ScanFilter>,
+    ) -> Result<Vec<PluginInfo>> {
+        let mut bundles: Vec<PathBuf> = roots
+            .iter()
+            .flat_map(|root| {
+                find_bundles(
+                    root,
+                    depth,
+                    self.config.follow_symlinks,
+                    self.config.io_timeout,
+                )
+            })
+            .filter(|bundle| bundle.kind == BundleKind::Lv2 && bundle.path.is_dir())
+            .map(|bundle| bundle.path)
+            .collect();
+        bundles.sort();
+        bundles.dedup();
+
+        let mut plugins = Vec::new();
+        for path in bundles {
+            let canonical_path = std::fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+            let found = match read_bundle(&path) {
+                Ok(found) => found,
+                Err(error) => {
+                    events::emit(HostEvent::Error {
+                        info: None,
+                        error: &error,
+                    });
+                    continue;
+                }
+            };
+            for description in &found {
+                let info = convert_description(description);
+                if filter.is_some_and(|filter| !filter.matches(&info)) {
+                    continue;
+                }
+                plugins.push(info.with_canonical_path(canonical_path.clone()));
+            }
+        }
+        Ok(plugins)
+    }
+
+    /// Every path [`scan()`](PluginScanner::scan) searches
+    fn search_paths(&self) -> Vec<PathBuf> {
+        let mut paths = if self.config.skip_default_paths {
+            Vec::new()
+        } else {
+            Self::default_paths()
+        };
+        paths.extend(self.config.extra_paths.iter().cloned());
+        paths
+    }
+}
+
+/// Convert a plugin description to a PluginInfo
+fn convert_description(description: &PluginDescription) -> PluginInfo {
+    PluginInfo::new(
+        description.name.clone(),
+        description.manufacturer.clone(),
+        description.version,
+        description.plugin_type,
+        description.bundle.clone(),
+        description.uri.clone(),
+    )
+    .with_format(PluginFormat::Lv2)
+}
+
+impl PluginScanner for Lv2Scanner {
+    type Plugin = Lv2Plugin;
+
+    fn with_config(config: ScannerConfig) -> Result<Self> {
+        Ok(Self {
+            usage: None,
+            config,
+        })
+    }
+
+    fn config(&self) -> &ScannerConfig {
+        &self.config
+    }
+
+    /// The folders listed in [`LV2_PATH_ENV`] if set, otherwise the default
+    /// locations from the LV2 specification
+    fn default_paths() -> Vec<PathBuf> {
+        if let Some(list) = std::env::var_os(LV2_PATH_ENV).filter(|v| !v.is_empty()) {
+            return std::env::split_paths(&list)
+                .filter(|p| p.is_absolute())
+                .collect();
+        }
+
+        let var = |name: &str| {
+            std::env::var_os(name)
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from)
+        };
+
+        let mut paths = Vec::new();
+        if cfg!(windows) {
+            paths.extend(var("APPDATA").map(|dir| dir.join("LV2")));
+            paths.extend(var("COMMONPROGRAMFILES").map(|dir| dir.join("LV2")));
+        } else if cfg!(target_vendor = "apple") {
+            paths.extend(var("HOME").map(|home| home.join("Library/Audio/Plug-Ins/LV2")));
+            paths.extend(var("HOME").map(|home| home.join(".lv2")));
+            paths.push(PathBuf::from("/Library/Audio/Plug-Ins/LV2"));
+        } else {
+            paths.extend(var("HOME").map(|home| home.join(".lv2")));
+            paths.push(PathBuf::from("/usr/local/lib/lv2"));
+            paths.push(PathBuf::from("/usr/lib/lv2"));
+        }
+        paths
+    }
+
+    fn add_path(&mut self, path: &Path) -> Result<()> {
+        self.config.extra_paths.push(path.to_path_buf());
+        Ok(())
+    }
+
+    fn scan(&self) -> Result<Vec<PluginInfo>> {
+        let depth = self.config.scan_depth.max(SEARCH_DEPTH);
+        self.scan_roots(&self.search_paths(), depth, None)
+    }
+
+    fn scan_filtered(&self, filter: &ScanFilter) -> Result<Vec<PluginInfo>> {
+        let depth = self.config.scan_depth.max(SEARCH_DEPTH);
+        self.scan_roots(&self.search_paths(), depth, Some(filter))
+    }
+
+    fn scan_path(&self, path: &Path) -> Result<Vec<PluginInfo>> {
+        self.scan_roots(&[path.to_path_buf()], self.config.scan_depth, None)
+    }
+
+    fn scan_path_filtered(&self, path: &Path, filter: &ScanFilter) -> Result<Vec<PluginInfo>> {
+        self.scan_roots(&[path.to_path_buf()], self.config.scan_depth, Some(filter))
+    }
+
+    fn load(&self, info: &PluginInfo) -> Result<Self::Plugin> {
+        let plugin = Lv2Plugin::new(info)?;
+        metadata::record_load(self.usage.as_ref(), &info.unique_id);
+        events::emit(HostEvent::PluginLoaded { info });
+        Ok(plugin)
+    }
+
+    fn usage_store(&self) -> Option<&SharedMetadataStore> {
+        self.usage.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lv2::test_plugin;
+    use crate::PluginType;
+
+    #[test]
+    fn test_default_paths() {
+        let paths = Lv2Scanner::default_paths();
+        assert!(
+            !paths.is_empty(),
+            "Every desktop platform has default LV2 paths"
+        );
+        assert!(paths.iter().all(|p| p.is_absolute()));
+    }
+
+    #[test]
+    fn test_scan_describes_plugins() {
+        let dir = std::env::temp_dir().join(format!("rack-lv2-scan-{}", std::process::id()));
+        let _bundle = test_plugin::install(&dir.join("gain.lv2"));
+
+        let config = ScannerConfig::new().path(&dir).skip_default_paths(true);
+        let scanner = Lv2Scanner::with_config(config).unwrap();
+        let plugins = scanner.scan().unwrap();
+        assert_eq!(plugins.len(), 1);
+        let plugin = &plugins[0];
+        assert_eq!(plugin.name, "Test Gain");
+        assert_eq!(plugin.manufacturer, "Example");
+        assert_eq!(plugin.unique_id, test_plugin::URI);
+        assert_eq!(plugin.version, 0x0203);
+        assert_eq!(plugin.plugin_type, PluginType::Effect);
+        assert_eq!(plugin.format, PluginFormat::Lv2);
+        assert_eq!(plugin.path, dir.join("gain.lv2"));
+
+        let filter = ScanFilter::new().plugin_type(PluginType::Instrument);
+        assert!(scanner.scan_filtered(&filter).unwrap().is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,278 @@
+//! An LV2 gain plugin compiled into the test binary
+//!
+//! Stereo in and out, a "Gain" control (0 to 2, default 1), a MIDI input
+//! that counts note-ons (and accepts, but ignores, transport positions), and
+//! a latency output. The note count is its state.
+//! [`install()`] writes the bundle data and registers the descriptor
+//! function under the bundle's binary path, so scanners and instances find
+//! it like a real bundle.
+
+use std::ffi::{c_char, c_void, CStr};
+use std::path::Path;
+use std::sync::Arc;
+
+use super::ffi;
+use super::library::Library;
+
+pub const URI: &str = "http://example.org/plugins/gain";
+
+/// What the plugin reports on its latency port
+pub const LATENCY: usize = 32;
+
+const URI_C: &[u8] = b"http://example.org/plugins/gain\0";
+
+const NOTES_KEY: &str = "http://example.org/plugins/gain#notes";
+
+const MANIFEST: &str = r#"
+@prefix lv2:  <http://lv2plug.in/ns/lv2core#> .
+@prefix pset: <http://lv2plug.in/ns/ext/presets#> .
+@prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .
+
+<http://example.org/plugins/gain>
+    a lv2:Plugin ;
+    lv2:binary <gain.so> ;
+    rdfs:seeAlso <gain.ttl> .
+
+<http://example.org/plugins/gain#quiet>
+    a pset:Preset ;
+    lv2:appliesTo <http://example.org/plugins/gain> ;
+    rdfs:seeAlso <presets.ttl> .
+"#;
+
+const PLUGIN: &str = r#"
+@prefix atom:  <http://lv2plug.in/ns/ext/atom#> .
+@prefix doap:  <http://usefulinc.com/ns/doap#> .
+@prefix foaf:  <http://xmlns.com/foaf/0.1/> .
+@prefix lv2:   <http://lv2plug.in/ns/lv2core#> .
+@prefix midi:  <http://lv2plug.in/ns/ext/midi#> .
+@prefix time:  <http://lv2plug.in/ns/ext/time#> .
+@prefix units: <http://lv2plug.in/ns/extensions/units#> .
+@prefix urid:  <http://lv2plug.in/ns/ext/urid#> .
+
+<http://example.org/plugins/gain>
+    a lv2:Plugin , lv2:AmplifierPlugin ;
+    doap:name "Test Gain" ;
+    doap:maintainer [ foaf:name "Example" ] ;
+    lv2:minorVersion 2 ;
+    lv2:microVersion 3 ;
+    lv2:requiredFeature urid:map ;
+    lv2:optionalFeature lv2:hardRTCapable ;
+    lv2:port [
+        a lv2:InputPort , lv2:AudioPort ; lv2:index 0 ; lv2:symbol "in_l" ; lv2:name "In L"
+    ] , [
+        a lv2:InputPort , lv2:AudioPort ; lv2:index 1 ; lv2:symbol "in_r" ; lv2:name "In R"
+    ] , [
+        a lv2:OutputPort , lv2:AudioPort ; lv2:index 2 ; lv2:symbol "out_l" ; lv2:name "Out L"
+    ] , [
+        a lv2:OutputPort , lv2:AudioPort ; lv2:index 3 ; lv2:symbol "out_r" ; lv2:name "Out R"
+    ] , [
+        a lv2:InputPort , lv2:ControlPort ;
+        lv2:index 4 ;
+        lv2:symbol "gain" ;
+        lv2:name "Gain" ;
+        lv2:default 1.0 ;
+        lv2:minimum 0.0 ;
+        lv2:maximum 2.0 ;
+        units:unit units:db
+    ] , [
+        a lv2:InputPort , atom:AtomPort ;
+        atom:bufferType atom:Sequence ;
+        atom:supports midi:MidiEvent , time:Position ;
+        lv2:index 5 ;
+        lv2:symbol "midi" ;
+        lv2:name "MIDI In"
+    ] , [
+        a lv2:OutputPort , lv2:ControlPort ;
+        lv2:index 6 ;
+        lv2:symbol "latency" ;
+        lv2:name "Latency" ;
+        lv2:designation lv2:latency ;
+        lv2:portProperty lv2:reportsLatency
+    ] .
+"#;
+
+const PRESETS: &str = r#"
+@prefix lv2:  <http://lv2plug.in/ns/lv2core#> .
+@prefix pset: <http://lv2plug.in/ns/ext/presets#> .
+@prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .
+
+<http://example.org/plugins/gain#quiet>
+    a pset:Preset ;
+    lv2:appliesTo <http://example.org/plugins/gain> ;
+    rdfs:label "Quiet" ;
+    lv2:port [ lv2:symbol "gain" ; pset:value 0.5 ] .
+"#;
+
+/// Keeps the gain plugin registered while alive
+pub(crate) struct Installed {
+    _library: Arc<Library>,
+}
+
+/// Write the gain plugin's bundle at `bundle` and register its binary
+///
+/// The registration lasts as long as the returned value is kept.
+pub(crate) fn install(bundle: &Path) -> Installed {
+    std::fs::create_dir_all(bundle).unwrap();
+    std::fs::write(bundle.join("manifest.ttl"), MANIFEST).unwrap();
+    std::fs::write(bundle.join("gain.ttl"), PLUGIN).unwrap();
+    std::fs::write(bundle.join("presets.ttl"), PRESETS).unwrap();
+    std::fs::write(bundle.join("gain.so"), b"").unwrap();
+    Installed {
+        _library: Library::from_function(&bundle.join("gain.so"), lv2_descriptor),
+    }
+}
+
+struct Gain {
+    ports: [*mut c_void; 7],
+    midi_event: ffi::LV2_URID,
+    int: ffi::LV2_URID,
+    notes_key: ffi::LV2_URID,
+    notes: i32,
+}
+
+struct Static<T>(T);
+
+// Safety: only holds pointers to static strings
+unsafe impl<T> Sync for Static<T> {}
+
+static DESCRIPTOR: Static<ffi::LV2_Descriptor> = Static(ffi::LV2_Descriptor {
+    uri: URI_C.as_ptr() as *const c_char,
+    instantiate: Some(instantiate),
+    connect_port: Some(connect_port),
+    activate: None,
+    run: Some(run),
+    deactivate: None,
+    cleanup: Some(cleanup),
+    extension_data: Some(extension_data),
+});
+
+static STATE: ffi::LV2_State_Interface = ffi::LV2_State_Interface {
+    save: Some(save),
+    restore: Some(restore),
+};
+
+unsafe extern "C" fn lv2_descriptor(index: u32) -> *const ffi::LV2_Descriptor {
+    if index == 0 {
+        &DESCRIPTOR.0
+    } else {
+        std::ptr::null()
+    }
+}
+
+unsafe extern "C" fn instantiate(
+    _descriptor: *const ffi::LV2_Descriptor,
+    _sample_rate: f64,
+    _bundle_path: *const c_char,
+    features: *const *const ffi::LV2_Feature,
+) -> ffi::LV2_Handle {
+    let mut map = None;
+    for index in 0.. {
+        let feature = *features.add(index);
+        if feature.is_null() {
+            break;
+        }
+        if CStr::from_ptr((*feature).uri).to_bytes_with_nul() == ffi::LV2_URID__MAP {
+            map = ((*feature).data as *const ffi::LV2_URID_Map).as_ref();
+        }
+    }
+    let Some(map) = map else {
+        return std::ptr::null_mut();
+    };
+    let urid = |uri: &str| {
+        let uri = std::ffi::CString::new(uri).unwrap();
+        map.map.unwrap()(map.handle, uri.as_ptr())
+    };
+
+    Box::into_raw(Box::new(Gain {
+        ports: [std::ptr::null_mut(); 7],
+        midi_event: urid(ffi::LV2_MIDI__MIDI_EVENT),
+        int: urid(ffi::LV2_ATOM__INT),
+        notes_key: urid(NOTES_KEY),
+        notes: 0,
+    })) as ffi::LV2_Handle
+}
+
+unsafe fn gain<'a>(instance: ffi::LV2_Handle) -> &'a mut Gain {
+    &mut *(instance as *mut Gain)
+}
+
+unsafe extern "C" fn connect_port(instance: ffi::LV2_Handle, port: u32, data: *mut c_void) {
+    gain(instance).ports[port as usize] = data;
+}
+
+unsafe extern "C" fn run(instance: ffi::LV2_Handle, sample_count: u32) {
+    let gain = gain(instance);
+    let frames = sample_count as usize;
+    let level = *(gain.ports[4] as *const f32);
+    for channel in 0..2 {
+        let from = std::slice::from_raw_parts(gain.ports[channel] as *const f32, frames);
+        let to = std::slice::from_raw_parts_mut(gain.ports[channel + 2] as *mut f32, frames);
+        for (out, sample) in to.iter_mut().zip(from) {
+            *out = sample * level;
+        }
+    }
+
+    // Count note-ons in the MIDI sequence
+    let sequence = gain.ports[5] as *const u8;
+    let atom = *(sequence as *const ffi::LV2_Atom);
+    let end = std::mem::size_of::<ffi::LV2_Atom>() + atom.size as usize;
+    let mut offset = std::mem::size_of::<ffi::LV2_Atom_Sequence>();
+    while offset < end {
+        let event = *(sequence.add(offset) as *const ffi::LV2_Atom_Event);
+        let data = sequence.add(offset + std::mem::size_of::<ffi::LV2_Atom_Event>());
+        if event.body.type_ == gain.midi_event && *data & 0xF0 == 0x90 {
+            gain.notes += 1;
+        }
+        offset +=
+            std::mem::size_of::<ffi::LV2_Atom_Event>() + ((event.body.size as usize + 7) & !7);
+    }
+
+    *(gain.ports[6] as *mut f32) = LATENCY as f32;
+}
+
+unsafe extern "C" fn cleanup(instance: ffi::LV2_Handle) {
+    drop(Box::from_raw(instance as *mut Gain));
+}
+
+unsafe extern "C" fn extension_data(uri: *const c_char) -> *const c_void {
+    if CStr::from_ptr(uri).to_bytes_with_nul() == ffi::LV2_STATE__INTERFACE {
+        &STATE as *const ffi::LV2_State_Interface as *const c_void
+    } else {
+        std::ptr::null()
+    }
+}
+
+unsafe extern "C" fn save(
+    instance: ffi::LV2_Handle,
+    store: ffi::LV2_State_Store_Function,
+    handle: ffi::LV2_State_Handle,
+    _flags: u32,
+    _features: *const *const ffi::LV2_Feature,
+) -> u32 {
+    let gain = gain(instance);
+    store(
+        handle,
+        gain.notes_key,
+        &gain.notes as *const i32 as *const c_void,
+        std::mem::size_of::<i32>(),
+        gain.int,
+        ffi::LV2_STATE_IS_POD | ffi::LV2_STATE_IS_PORTABLE,
+    )
+}
+
+unsafe extern "C" fn restore(
+    instance: ffi::LV2_Handle,
+    retrieve: ffi::LV2_State_Retrieve_Function,
+    handle: ffi::LV2_State_Handle,
+    _flags: u32,
+    _features: *const *const ffi::LV2_Feature,
+) -> u32 {
+    let gain = gain(instance);
+    let (mut size, mut type_, mut flags) = (0, 0, 0);
+    let value = retrieve(handle, gain.notes_key, &mut size, &mut type_, &mut flags);
+    if value.is_null() || size != std::mem::size_of::<i32>() || type_ != gain.int {
+        return ffi::LV2_STATE_ERR_NO_PROPERTY;
+    }
+    gain.notes = *(value as *const i32);
+    ffi::LV2_STATE_SUCCESS
+}
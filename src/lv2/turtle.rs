@@ -0,0 +1,886 @@
+//! A small Turtle (RDF) reader for LV2 bundle data
+//!
+//! LV2 plugins describe themselves in Turtle files (`manifest.ttl` and the
+//! files it points to). This covers the Turtle 1.1 syntax those files use:
+//! prefixes and base IRIs, prefixed names, blank nodes (labelled and `[ ]`),
+//! collections, and string, numeric and boolean literals. The result is a
+//! flat list of triples with every IRI resolved to its absolute form.
+
+use crate::{Error, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub const RDF_TYPE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+pub const RDF_FIRST: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#first";
+pub const RDF_REST: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#rest";
+pub const RDF_NIL: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#nil";
+const XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+
+/// An RDF node
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Term {
+    /// An absolute IRI
+    Iri(String),
+
+    /// A blank node, by its label within the graph
+    Blank(String),
+
+    /// A literal; numbers and booleans keep their lexical form and get an
+    /// XSD datatype
+    Literal {
+        value: String,
+        datatype: Option<String>,
+        language: Option<String>,
+    },
+}
+
+impl Term {
+    /// The IRI, if this is one
+    pub fn as_iri(&self) -> Option<&str> {
+        match self {
+            Term::Iri(iri) => Some(iri),
+            _ => None,
+        }
+    }
+
+    /// The literal's text, if this is a literal
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Term::Literal { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The literal's value as a number, if it is one
+    pub fn as_f64(&self) -> Option<f64> {
+        self.as_str()?.trim().parse().ok()
+    }
+}
+
+/// A subject, predicate and object
+#[derive(Debug, Clone, PartialEq)]
+pub struct Triple {
+    pub subject: Term,
+    pub predicate: String,
+    pub object: Term,
+}
+
+/// Triples from one or more Turtle documents
+#[derive(Debug, Default)]
+pub struct Graph {
+    triples: Vec<Triple>,
+    // Blank node labels are scoped to their document
+    documents: usize,
+}
+
+impl Graph {
+    /// Create an empty graph
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a Turtle document into the graph
+    ///
+    /// Relative IRIs are resolved against `base`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidFormat`] with the line of the first syntax
+    /// error; nothing is added in that case
+    pub fn parse(&mut self, text: &str, base: &str) -> Result<()> {
+        let mut parser = Parser::new(text, base, self.documents);
+        parser.document()?;
+        self.documents += 1;
+        self.triples.append(&mut parser.triples);
+        Ok(())
+    }
+
+    /// Parse the Turtle file at `path` into the graph
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or parsed
+    pub fn parse_file(&mut self, path: &Path) -> Result<()> {
+        let text = std::fs::read_to_string(path)?;
+        self.parse(&text, &file_uri(path))
+            .map_err(|e| Error::InvalidFormat(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Every triple in the graph
+    pub fn triples(&self) -> &[Triple] {
+        &self.triples
+    }
+
+    /// The objects of every triple with this subject and predicate
+    pub fn objects<'a>(
+        &'a self,
+        subject: &'a Term,
+        predicate: &'a str,
+    ) -> impl Iterator<Item = &'a Term> + 'a {
+        self.triples
+            .iter()
+            .filter(move |t| t.subject == *subject && t.predicate == predicate)
+            .map(|t| &t.object)
+    }
+
+    /// The first object with this subject and predicate
+    pub fn object(&self, subject: &Term, predicate: &str) -> Option<&Term> {
+        self.triples
+            .iter()
+            .find(|t| t.subject == *subject && t.predicate == predicate)
+            .map(|t| &t.object)
+    }
+
+    /// The subjects of every triple with this predicate and object
+    pub fn subjects<'a>(
+        &'a self,
+        predicate: &'a str,
+        object: &'a Term,
+    ) -> impl Iterator<Item = &'a Term> + 'a {
+        self.triples
+            .iter()
+            .filter(move |t| t.predicate == predicate && t.object == *object)
+            .map(|t| &t.subject)
+    }
+
+    /// Whether the subject has the given `rdf:type`
+    pub fn has_type(&self, subject: &Term, class: &str) -> bool {
+        self.objects(subject, RDF_TYPE)
+            .any(|object| object.as_iri() == Some(class))
+    }
+}
+
+/// The `file:` URI for an absolute path, percent-encoding anything that
+/// isn't safe in a URI path
+pub fn file_uri(path: &Path) -> String {
+    let text = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !text.starts_with('/') {
+        // Windows drive paths
+        uri.push('/');
+    }
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// The path a `file:` URI points to
+///
+/// Returns `None` for other schemes or remote hosts.
+pub fn file_uri_to_path(uri: &str) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("file://")?;
+    let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+    if !rest.starts_with('/') {
+        return None;
+    }
+
+    let bytes = rest.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = |b: u8| (b as char).to_digit(16);
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(high), Some(low)) = (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                decoded.push((high * 16 + low) as u8);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    #[cfg(windows)]
+    {
+        // file:///C:/... names a drive path
+        let text = String::from_utf8(decoded).ok()?;
+        let text = match text.as_bytes().get(2) {
+            Some(b':') => &text[1..],
+            _ => &text,
+        };
+        Some(PathBuf::from(text.replace('/', "\\")))
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        Some(PathBuf::from(std::ffi::OsString::from_vec(decoded)))
+    }
+}
+
+/// Resolve a possibly relative IRI reference against a base IRI
+fn resolve(base: &str, reference: &str) -> String {
+    if has_scheme(reference) {
+        return reference.to_string();
+    }
+    let without_fragment = base.split('#').next().unwrap_or(base);
+    if reference.is_empty() {
+        return without_fragment.to_string();
+    }
+    if reference.starts_with('#') {
+        return format!("{}{}", without_fragment, reference);
+    }
+
+    // Split the base into scheme + authority and path
+    let (prefix, base_path) = match without_fragment.find("://") {
+        Some(start) => {
+            let after = start + 3;
+            match without_fragment[after..].find('/') {
+                Some(slash) => without_fragment.split_at(after + slash),
+                None => (without_fragment, ""),
+            }
+        }
+        None => match without_fragment.find(':') {
+            Some(colon) => without_fragment.split_at(colon + 1),
+            None => ("", without_fragment),
+        },
+    };
+    if reference.starts_with("//") {
+        let scheme = prefix.split(':').next().unwrap_or("");
+        return format!("{}:{}", scheme, reference);
+    }
+
+    let base_path = base_path.split('?').next().unwrap_or(base_path);
+    let path = if reference.starts_with('/') {
+        reference.to_string()
+    } else {
+        let directory = match base_path.rfind('/') {
+            Some(slash) => &base_path[..=slash],
+            None => "/",
+        };
+        format!("{}{}", directory, reference)
+    };
+    format!("{}{}", prefix, remove_dot_segments(&path))
+}
+
+fn has_scheme(reference: &str) -> bool {
+    match reference.find(':') {
+        Some(colon) => {
+            let scheme = &reference[..colon];
+            !scheme.is_empty()
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }
+        None => false,
+    }
+}
+
+/// Remove `.` and `..` segments from a path, keeping any query or fragment
+fn remove_dot_segments(path: &str) -> String {
+    let (path, suffix) = match path.find(['?', '#']) {
+        Some(at) => path.split_at(at),
+        None => (path, ""),
+    };
+    let mut segments: Vec<&str> = Vec::new();
+    let mut parts = path.split('/').peekable();
+    while let Some(part) = parts.next() {
+        let last = parts.peek().is_none();
+        match part {
+            "." => {
+                if last {
+                    segments.push("");
+                }
+            }
+            ".." => {
+                if segments.len() > 1 {
+                    segments.pop();
+                }
+                if last {
+                    segments.push("");
+                }
+            }
+            _ => segments.push(part),
+        }
+    }
+    format!("{}{}", segments.join("/"), suffix)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    base: String,
+    prefixes: HashMap<String, String>,
+    triples: Vec<Triple>,
+    document: usize,
+    next_blank: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str, base: &str, document: usize) -> Self {
+        Self {
+            text,
+            pos: 0,
+            base: base.to_string(),
+            prefixes: HashMap::new(),
+            triples: Vec::new(),
+            document,
+            next_blank: 0,
+        }
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> Error {
+        let line = self.text[..self.pos].matches('\n').count() + 1;
+        Error::InvalidFormat(format!("Turtle syntax error on line {}: {}", line, message))
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    /// Skip whitespace and comments
+    fn skip_space(&mut self) {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.bump();
+                }
+                Some('#') => while !matches!(self.bump(), Some('\n') | None) {},
+                _ => return,
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        self.skip_space();
+        match self.bump() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(self.error(format!("expected '{}', found '{}'", expected, c))),
+            None => Err(self.error(format!("expected '{}', found end of file", expected))),
+        }
+    }
+
+    /// Whether the input continues with `keyword` (ASCII case-insensitive)
+    /// followed by a non-name character
+    fn at_keyword(&self, keyword: &str) -> bool {
+        let rest = self.rest();
+        rest.len() >= keyword.len()
+            && rest.is_char_boundary(keyword.len())
+            && rest[..keyword.len()].eq_ignore_ascii_case(keyword)
+            && !rest[keyword.len()..].starts_with(|c: char| is_name_char(c) || c == ':')
+    }
+
+    fn blank_node(&mut self) -> Term {
+        self.next_blank += 1;
+        Term::Blank(format!("d{}b{}", self.document, self.next_blank))
+    }
+
+    fn document(&mut self) -> Result<()> {
+        loop {
+            self.skip_space();
+            if self.peek().is_none() {
+                return Ok(());
+            }
+            self.statement()?;
+        }
+    }
+
+    fn statement(&mut self) -> Result<()> {
+        if self.rest().starts_with("@prefix") {
+            self.pos += "@prefix".len();
+            self.prefix_declaration()?;
+            return self.expect('.');
+        }
+        if self.rest().starts_with("@base") {
+            self.pos += "@base".len();
+            self.base_declaration()?;
+            return self.expect('.');
+        }
+        if self.at_keyword("PREFIX") {
+            self.pos += "PREFIX".len();
+            return self.prefix_declaration();
+        }
+        if self.at_keyword("BASE") {
+            self.pos += "BASE".len();
+            return self.base_declaration();
+        }
+
+        // A bare blank node property list may stand alone
+        if self.peek() == Some('[') {
+            let subject = self.blank_node_property_list()?;
+            self.skip_space();
+            if self.peek() != Some('.') {
+                self.predicate_object_list(&subject)?;
+            }
+            return self.expect('.');
+        }
+
+        let subject = self.subject()?;
+        self.predicate_object_list(&subject)?;
+        self.expect('.')
+    }
+
+    fn prefix_declaration(&mut self) -> Result<()> {
+        self.skip_space();
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c == ':' {
+                break;
+            }
+            if !is_name_char(c) {
+                return Err(self.error(format!("invalid prefix name character '{}'", c)));
+            }
+            self.bump();
+        }
+        let name = self.text[start..self.pos].to_string();
+        self.expect(':')?;
+        self.skip_space();
+        let iri = self.iri_ref()?;
+        self.prefixes.insert(name, iri);
+        Ok(())
+    }
+
+    fn base_declaration(&mut self) -> Result<()> {
+        self.skip_space();
+        self.base = self.iri_ref()?;
+        Ok(())
+    }
+
+    fn subject(&mut self) -> Result<Term> {
+        self.skip_space();
+        match self.peek() {
+            Some('<') => Ok(Term::Iri(self.iri_ref()?)),
+            Some('_') if self.rest().starts_with("_:") => Ok(self.blank_node_label()),
+            Some('(') => self.collection(),
+            Some('[') => self.blank_node_property_list(),
+            Some(_) => Ok(Term::Iri(self.prefixed_name()?)),
+            None => Err(self.error("expected a subject, found end of file")),
+        }
+    }
+
+    fn predicate_object_list(&mut self, subject: &Term) -> Result<()> {
+        loop {
+            self.skip_space();
+            let predicate = self.predicate()?;
+            loop {
+                let object = self.object()?;
+                self.triples.push(Triple {
+                    subject: subject.clone(),
+                    predicate: predicate.clone(),
+                    object,
+                });
+                self.skip_space();
+                if self.peek() == Some(',') {
+                    self.bump();
+                } else {
+                    break;
+                }
+            }
+
+            // Any number of ';' may follow, and a trailing one is allowed
+            self.skip_space();
+            if self.peek() != Some(';') {
+                return Ok(());
+            }
+            while self.peek() == Some(';') {
+                self.bump();
+                self.skip_space();
+            }
+            if matches!(self.peek(), Some('.') | Some(']') | None) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn predicate(&mut self) -> Result<String> {
+        self.skip_space();
+        if self.peek() == Some('a')
+            && !self.rest()[1..].starts_with(|c: char| is_name_char(c) || c == ':')
+        {
+            self.bump();
+            return Ok(RDF_TYPE.to_string());
+        }
+        match self.peek() {
+            Some('<') => self.iri_ref(),
+            Some(_) => self.prefixed_name(),
+            None => Err(self.error("expected a predicate, found end of file")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Term> {
+        self.skip_space();
+        match self.peek() {
+            Some('<') => Ok(Term::Iri(self.iri_ref()?)),
+            Some('_') if self.rest().starts_with("_:") => Ok(self.blank_node_label()),
+            Some('(') => self.collection(),
+            Some('[') => self.blank_node_property_list(),
+            Some('"') | Some('\'') => self.string_literal(),
+            Some(c) if c.is_ascii_digit() || matches!(c, '+' | '-' | '.') => self.numeric_literal(),
+            Some(_) if self.at_keyword("true") || self.at_keyword("false") => {
+                let value = if self.at_keyword("true") {
+                    "true"
+                } else {
+                    "false"
+                };
+                self.pos += value.len();
+                Ok(Term::Literal {
+                    value: value.to_string(),
+                    datatype: Some(format!("{}boolean", XSD)),
+                    language: None,
+                })
+            }
+            Some(_) => Ok(Term::Iri(self.prefixed_name()?)),
+            None => Err(self.error("expected an object, found end of file")),
+        }
+    }
+
+    fn blank_node_label(&mut self) -> Term {
+        self.pos += 2;
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if is_name_char(c) || (c == '.' && self.rest()[1..].starts_with(is_name_char)) {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+        Term::Blank(format!(
+            "d{}l{}",
+            self.document,
+            &self.text[start..self.pos]
+        ))
+    }
+
+    fn blank_node_property_list(&mut self) -> Result<Term> {
+        self.expect('[')?;
+        let node = self.blank_node();
+        self.skip_space();
+        if self.peek() != Some(']') {
+            self.predicate_object_list(&node)?;
+        }
+        self.expect(']')?;
+        Ok(node)
+    }
+
+    fn collection(&mut self) -> Result<Term> {
+        self.expect('(')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_space();
+            match self.peek() {
+                Some(')') => {
+                    self.bump();
+                    break;
+                }
+                Some(_) => items.push(self.object()?),
+                None => return Err(self.error("unterminated collection")),
+            }
+        }
+
+        let mut list = Term::Iri(RDF_NIL.to_string());
+        for item in items.into_iter().rev() {
+            let node = self.blank_node();
+            self.triples.push(Triple {
+                subject: node.clone(),
+                predicate: RDF_FIRST.to_string(),
+                object: item,
+            });
+            self.triples.push(Triple {
+                subject: node.clone(),
+                predicate: RDF_REST.to_string(),
+                object: list,
+            });
+            list = node;
+        }
+        Ok(list)
+    }
+
+    /// `<...>`, resolved against the base
+    fn iri_ref(&mut self) -> Result<String> {
+        self.expect('<')?;
+        let mut iri = String::new();
+        loop {
+            match self.bump() {
+                Some('>') => break,
+                Some('\\') => iri.push(self.unicode_escape()?),
+                Some(c) if c.is_whitespace() => {
+                    return Err(self.error("whitespace in IRI"));
+                }
+                Some(c) => iri.push(c),
+                None => return Err(self.error("unterminated IRI")),
+            }
+        }
+        Ok(resolve(&self.base, &iri))
+    }
+
+    fn prefixed_name(&mut self) -> Result<String> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c == ':' {
+                break;
+            }
+            if !is_name_char(c) && c != '.' {
+                return Err(self.error(format!("unexpected '{}'", c)));
+            }
+            self.bump();
+        }
+        let prefix = self.text[start..self.pos].to_string();
+        if self.bump() != Some(':') {
+            return Err(self.error(format!("expected a prefixed name, found '{}'", prefix)));
+        }
+        let namespace = self
+            .prefixes
+            .get(&prefix)
+            .cloned()
+            .ok_or_else(|| self.error(format!("undefined prefix '{}:'", prefix)))?;
+
+        let mut local = String::new();
+        while let Some(c) = self.peek() {
+            if is_name_char(c) || c == ':' {
+                local.push(c);
+                self.bump();
+            } else if c == '.'
+                && self.rest()[1..].starts_with(|c: char| is_name_char(c) || c == ':')
+            {
+                // A dot inside a name, not the statement terminator
+                local.push(c);
+                self.bump();
+            } else if c == '%' {
+                let escape = self.rest().get(..3).unwrap_or("").to_string();
+                local.push_str(&escape);
+                self.pos += escape.len();
+            } else if c == '\\' {
+                self.bump();
+                match self.bump() {
+                    Some(escaped) => local.push(escaped),
+                    None => return Err(self.error("unterminated escape")),
+                }
+            } else {
+                break;
+            }
+        }
+        Ok(format!("{}{}", namespace, local))
+    }
+
+    fn string_literal(&mut self) -> Result<Term> {
+        let quote = self.bump().expect("caller saw a quote");
+        let long = self.rest().starts_with(&format!("{0}{0}", quote));
+        if long {
+            self.pos += 2;
+        }
+
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some('\\') => value.push(self.string_escape()?),
+                Some(c) if c == quote => {
+                    if !long {
+                        break;
+                    }
+                    if self.rest().starts_with(&format!("{0}{0}", quote)) {
+                        self.pos += 2;
+                        // Quotes right before the closing ones belong to the string
+                        while self.peek() == Some(quote) {
+                            value.push(quote);
+                            self.bump();
+                        }
+                        break;
+                    }
+                    value.push(c);
+                }
+                Some('\n') | Some('\r') if !long => {
+                    return Err(self.error("line break in string"));
+                }
+                Some(c) => value.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+
+        let mut datatype = None;
+        let mut language = None;
+        if self.peek() == Some('@') {
+            self.bump();
+            let start = self.pos;
+            while self
+                .peek()
+                .is_some_and(|c| c.is_ascii_alphanumeric() || c == '-')
+            {
+                self.bump();
+            }
+            language = Some(self.text[start..self.pos].to_string());
+        } else if self.rest().starts_with("^^") {
+            self.pos += 2;
+            datatype = Some(match self.peek() {
+                Some('<') => self.iri_ref()?,
+                _ => self.prefixed_name()?,
+            });
+        }
+        Ok(Term::Literal {
+            value,
+            datatype,
+            language,
+        })
+    }
+
+    fn string_escape(&mut self) -> Result<char> {
+        match self.bump() {
+            Some('t') => Ok('\t'),
+            Some('b') => Ok('\u{8}'),
+            Some('n') => Ok('\n'),
+            Some('r') => Ok('\r'),
+            Some('f') => Ok('\u{c}'),
+            Some('"') => Ok('"'),
+            Some('\'') => Ok('\''),
+            Some('\\') => Ok('\\'),
+            Some('u') | Some('U') => {
+                self.pos -= 1;
+                self.unicode_escape()
+            }
+            Some(c) => Err(self.error(format!("invalid escape '\\{}'", c))),
+            None => Err(self.error("unterminated escape")),
+        }
+    }
+
+    /// `uXXXX` or `UXXXXXXXX`, after the backslash
+    fn unicode_escape(&mut self) -> Result<char> {
+        let digits = match self.bump() {
+            Some('u') => 4,
+            Some('U') => 8,
+            _ => return Err(self.error("invalid escape")),
+        };
+        let hex = self.rest().get(..digits).unwrap_or("");
+        let code = u32::from_str_radix(hex, 16)
+            .ok()
+            .filter(|_| hex.len() == digits)
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.pos += digits;
+        Ok(code)
+    }
+
+    fn numeric_literal(&mut self) -> Result<Term> {
+        let start = self.pos;
+        if matches!(self.peek(), Some('+') | Some('-')) {
+            self.bump();
+        }
+        let mut kind = "integer";
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() {
+                self.bump();
+            } else if c == '.' && self.rest()[1..].starts_with(|c: char| c.is_ascii_digit()) {
+                kind = "decimal";
+                self.bump();
+            } else if matches!(c, 'e' | 'E') {
+                kind = "double";
+                self.bump();
+                if matches!(self.peek(), Some('+') | Some('-')) {
+                    self.bump();
+                }
+            } else {
+                break;
+            }
+        }
+        let value = &self.text[start..self.pos];
+        if value.parse::<f64>().is_err() {
+            return Err(self.error(format!("invalid number '{}'", value)));
+        }
+        Ok(Term::Literal {
+            value: value.to_string(),
+            datatype: Some(format!("{}{}", XSD, kind)),
+            language: None,
+        })
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '\u{B7}') || (c as u32) > 0x7F
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lv2_style_document() {
+        let text = r#"
+            @prefix lv2:  <http://lv2plug.in/ns/lv2core#> .
+            @prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .
+            PREFIX doap: <http://usefulinc.com/ns/doap#>
+
+            # A comment
+            <http://example.org/gain>
+                a lv2:Plugin , lv2:AmplifierPlugin ;
+                lv2:binary <gain.so> ;
+                doap:name "Gain"@en , """Long "quoted" name""" ;
+                doap:maintainer [ doap:name 'Someone' ] ;
+                lv2:port [
+                    a lv2:InputPort , lv2:ControlPort ;
+                    lv2:index 0 ;
+                    lv2:default -1.5e0 ;
+                    lv2:maximum 2.0 ;
+                    lv2:scalePoint ( 1 2 ) ;
+                    lv2:toggled true ;
+                ] .
+        "#;
+        let mut graph = Graph::new();
+        graph
+            .parse(text, "file:///usr/lib/lv2/gain.lv2/manifest.ttl")
+            .unwrap();
+
+        let plugin = Term::Iri("http://example.org/gain".to_string());
+        assert!(graph.has_type(&plugin, "http://lv2plug.in/ns/lv2core#AmplifierPlugin"));
+        let binary = graph
+            .object(&plugin, "http://lv2plug.in/ns/lv2core#binary")
+            .unwrap();
+        assert_eq!(
+            binary.as_iri(),
+            Some("file:///usr/lib/lv2/gain.lv2/gain.so")
+        );
+        assert_eq!(
+            file_uri_to_path(binary.as_iri().unwrap()),
+            Some(PathBuf::from("/usr/lib/lv2/gain.lv2/gain.so"))
+        );
+
+        let names: Vec<_> = graph
+            .objects(&plugin, "http://usefulinc.com/ns/doap#name")
+            .filter_map(Term::as_str)
+            .collect();
+        assert_eq!(names, vec!["Gain", "Long \"quoted\" name"]);
+
+        let port = graph
+            .object(&plugin, "http://lv2plug.in/ns/lv2core#port")
+            .unwrap();
+        let default = graph.object(port, "http://lv2plug.in/ns/lv2core#default");
+        assert_eq!(default.and_then(Term::as_f64), Some(-1.5));
+        let toggled = graph.object(port, "http://lv2plug.in/ns/lv2core#toggled");
+        assert_eq!(toggled.and_then(Term::as_str), Some("true"));
+        let list = graph
+            .object(port, "http://lv2plug.in/ns/lv2core#scalePoint")
+            .unwrap();
+        assert_eq!(
+            graph.object(list, RDF_FIRST).and_then(Term::as_f64),
+            Some(1.0)
+        );
+
+        let error = Graph::new().parse("<a> <b> \"open .", "file:///x.ttl");
+        assert!(matches!(error, Err(Error::InvalidFormat(_))));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let base = "file:///a/b/c.ttl";
+        assert_eq!(resolve(base, "d.so"), "file:///a/b/d.so");
+        assert_eq!(resolve(base, "../d.ttl"), "file:///a/d.ttl");
+        assert_eq!(resolve(base, "#frag"), "file:///a/b/c.ttl#frag");
+        assert_eq!(resolve(base, "/x"), "file:///x");
+        assert_eq!(resolve(base, "http://e.org/p"), "http://e.org/p");
+        assert_eq!(resolve("http://e.org/p/q", "r"), "http://e.org/p/r");
+        assert_eq!(file_uri(Path::new("/a b/%.lv2")), "file:///a%20b/%25.lv2");
+    }
+}
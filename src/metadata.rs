@@ -0,0 +1,587 @@
+//! User metadata for the plugin browser
+//!
+//! [`MetadataStore`] keeps per-plugin favorites, ratings, tags, color labels,
+//! last-used timestamps and load counts, keyed by [`PluginInfo::unique_id`](crate::PluginInfo::unique_id).
+//! It is a small in-memory map that can be saved to and loaded from a plain text
+//! file, so hosts don't each have to build their own database.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::metadata::{ColorLabel, MetadataStore};
+//! # fn example() -> rack::Result<()> {
+//! let mut store = MetadataStore::open("plugin-metadata.txt")?;
+//!
+//! store.set_favorite("aufx:dely:appl", true);
+//! store.set_rating("aufx:dely:appl", Some(4));
+//! store.add_tag("aufx:dely:appl", "delay");
+//! store.set_color("aufx:dely:appl", Some(ColorLabel::Blue));
+//! store.touch("aufx:dely:appl");
+//!
+//! for id in store.with_tag("delay") {
+//!     println!("{}", id);
+//! }
+//!
+//! store.save()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Usage Tracking
+//!
+//! Scanners record every successful [`load()`](crate::PluginScanner::load) into
+//! a shared store once tracking is enabled with the backend's `track_usage()`
+//! (e.g. `Scanner::track_usage()`). Tracking is opt-in.
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # fn example(scanner: &impl PluginScanner) {
+//! // Sort the browser or pre-warm instance pools with the most-loaded plugins
+//! for id in scanner.most_used(8) {
+//!     println!("{}", id);
+//! }
+//! # }
+//! ```
+
+use crate::{Error, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Header written at the top of saved metadata files
+const FILE_HEADER: &str = "# rack plugin metadata v1";
+
+/// Highest rating accepted by [`MetadataStore::set_rating()`]
+pub const MAX_RATING: u8 = 5;
+
+/// A metadata store shared between scanners and the host
+pub type SharedMetadataStore = Arc<Mutex<MetadataStore>>;
+
+/// Color label for organizing plugins
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ColorLabel {
+    /// Red
+    Red,
+    /// Orange
+    Orange,
+    /// Yellow
+    Yellow,
+    /// Green
+    Green,
+    /// Blue
+    Blue,
+    /// Purple
+    Purple,
+    /// Gray
+    Gray,
+}
+
+impl ColorLabel {
+    fn as_str(self) -> &'static str {
+        match self {
+            ColorLabel::Red => "red",
+            ColorLabel::Orange => "orange",
+            ColorLabel::Yellow => "yellow",
+            ColorLabel::Green => "green",
+            ColorLabel::Blue => "blue",
+            ColorLabel::Purple => "purple",
+            ColorLabel::Gray => "gray",
+        }
+    }
+
+    fn from_str(s: &str) -> Option<Self> {
+        Some(match s {
+            "red" => ColorLabel::Red,
+            "orange" => ColorLabel::Orange,
+            "yellow" => ColorLabel::Yellow,
+            "green" => ColorLabel::Green,
+            "blue" => ColorLabel::Blue,
+            "purple" => ColorLabel::Purple,
+            "gray" => ColorLabel::Gray,
+            _ => return None,
+        })
+    }
+}
+
+/// User metadata for a single plugin
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PluginMetadata {
+    /// Whether the user marked the plugin as a favorite
+    pub favorite: bool,
+
+    /// User rating from 1 to [`MAX_RATING`], if rated
+    pub rating: Option<u8>,
+
+    /// User tags (stored lowercase)
+    pub tags: BTreeSet<String>,
+
+    /// Color label
+    pub color: Option<ColorLabel>,
+
+    /// When the plugin was last used
+    pub last_used: Option<SystemTime>,
+
+    /// How many times the plugin has been loaded
+    pub load_count: u64,
+}
+
+impl PluginMetadata {
+    fn is_empty(&self) -> bool {
+        *self == PluginMetadata::default()
+    }
+}
+
+/// Persistent store of per-plugin user metadata
+#[derive(Debug, Clone, Default)]
+pub struct MetadataStore {
+    entries: BTreeMap<String, PluginMetadata>,
+    path: Option<PathBuf>,
+}
+
+impl MetadataStore {
+    /// Create an empty in-memory store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open a store backed by a file
+    ///
+    /// Loads the file if it exists; otherwise starts empty. [`save()`](Self::save)
+    /// writes back to the same file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or parsed
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut store = match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::new(),
+            Err(e) => return Err(e.into()),
+        };
+        store.path = Some(path.to_path_buf());
+        Ok(store)
+    }
+
+    /// Save the store to the file it was opened from
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store was not opened from a file, or writing fails
+    pub fn save(&self) -> Result<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| Error::Other("Metadata store has no backing file".to_string()))?;
+        self.save_to(path)
+    }
+
+    /// Save the store to a specific file
+    ///
+    /// The file is written to a temporary sibling first and then renamed into
+    /// place, so a crash mid-write never leaves a truncated store behind.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails
+    pub fn save_to(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, self.serialize())?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Get the metadata for a plugin, if any has been recorded
+    pub fn get(&self, unique_id: &str) -> Option<&PluginMetadata> {
+        self.entries.get(unique_id)
+    }
+
+    /// Get mutable metadata for a plugin, creating an empty entry if needed
+    pub fn entry(&mut self, unique_id: &str) -> &mut PluginMetadata {
+        self.entries.entry(unique_id.to_string()).or_default()
+    }
+
+    /// Remove all metadata for a plugin
+    pub fn remove(&mut self, unique_id: &str) -> Option<PluginMetadata> {
+        self.entries.remove(unique_id)
+    }
+
+    /// Number of plugins with metadata
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check whether the store is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Mark or unmark a plugin as a favorite
+    pub fn set_favorite(&mut self, unique_id: &str, favorite: bool) {
+        self.entry(unique_id).favorite = favorite;
+    }
+
+    /// Set a plugin's rating (clamped to 1..=[`MAX_RATING`]; `None` or 0 clears it)
+    pub fn set_rating(&mut self, unique_id: &str, rating: Option<u8>) {
+        self.entry(unique_id).rating = rating.filter(|&r| r > 0).map(|r| r.min(MAX_RATING));
+    }
+
+    /// Add a tag to a plugin (tags are case-insensitive)
+    pub fn add_tag(&mut self, unique_id: &str, tag: &str) {
+        let tag = normalize_tag(tag);
+        if !tag.is_empty() {
+            self.entry(unique_id).tags.insert(tag);
+        }
+    }
+
+    /// Remove a tag from a plugin
+    pub fn remove_tag(&mut self, unique_id: &str, tag: &str) {
+        if let Some(meta) = self.entries.get_mut(unique_id) {
+            meta.tags.remove(&normalize_tag(tag));
+        }
+    }
+
+    /// Set or clear a plugin's color label
+    pub fn set_color(&mut self, unique_id: &str, color: Option<ColorLabel>) {
+        self.entry(unique_id).color = color;
+    }
+
+    /// Record that a plugin was used just now
+    pub fn touch(&mut self, unique_id: &str) {
+        self.entry(unique_id).last_used = Some(SystemTime::now());
+    }
+
+    /// Record that a plugin was loaded: bumps its load count and last-used time
+    pub fn record_load(&mut self, unique_id: &str) {
+        let meta = self.entry(unique_id);
+        meta.load_count = meta.load_count.saturating_add(1);
+        meta.last_used = Some(SystemTime::now());
+    }
+
+    /// IDs of all favorite plugins
+    pub fn favorites(&self) -> Vec<&str> {
+        self.ids_where(|m| m.favorite)
+    }
+
+    /// IDs of all plugins with a tag
+    pub fn with_tag(&self, tag: &str) -> Vec<&str> {
+        let tag = normalize_tag(tag);
+        self.ids_where(|m| m.tags.contains(&tag))
+    }
+
+    /// IDs of all plugins with a color label
+    pub fn with_color(&self, color: ColorLabel) -> Vec<&str> {
+        self.ids_where(|m| m.color == Some(color))
+    }
+
+    /// IDs of all plugins rated at least `rating`
+    pub fn rated_at_least(&self, rating: u8) -> Vec<&str> {
+        self.ids_where(|m| m.rating.is_some_and(|r| r >= rating))
+    }
+
+    /// IDs of the `n` most recently used plugins, most recent first
+    pub fn recently_used(&self, n: usize) -> Vec<&str> {
+        let mut used: Vec<(&str, SystemTime)> = self
+            .entries
+            .iter()
+            .filter_map(|(id, m)| m.last_used.map(|t| (id.as_str(), t)))
+            .collect();
+        used.sort_by_key(|&(_, t)| std::cmp::Reverse(t));
+        used.into_iter().take(n).map(|(id, _)| id).collect()
+    }
+
+    /// IDs of the `n` most loaded plugins, most loaded first
+    ///
+    /// Ties are broken by last-used time, most recent first.
+    pub fn most_used(&self, n: usize) -> Vec<&str> {
+        let mut used: Vec<(&str, &PluginMetadata)> = self
+            .entries
+            .iter()
+            .filter(|(_, m)| m.load_count > 0)
+            .map(|(id, m)| (id.as_str(), m))
+            .collect();
+        used.sort_by(|a, b| {
+            b.1.load_count
+                .cmp(&a.1.load_count)
+                .then(b.1.last_used.cmp(&a.1.last_used))
+        });
+        used.into_iter().take(n).map(|(id, _)| id).collect()
+    }
+
+    /// All tags in use, sorted
+    pub fn all_tags(&self) -> BTreeSet<&str> {
+        self.entries
+            .values()
+            .flat_map(|m| m.tags.iter().map(String::as_str))
+            .collect()
+    }
+
+    fn ids_where(&self, predicate: impl Fn(&PluginMetadata) -> bool) -> Vec<&str> {
+        self.entries
+            .iter()
+            .filter(|(_, m)| predicate(m))
+            .map(|(id, _)| id.as_str())
+            .collect()
+    }
+
+    /// Serialize to the text file format
+    ///
+    /// One section per plugin:
+    ///
+    /// ```text
+    /// [aufx:dely:appl]
+    /// favorite = true
+    /// rating = 4
+    /// tags = delay,tape
+    /// color = blue
+    /// last_used = 1700000000
+    /// load_count = 12
+    /// ```
+    fn serialize(&self) -> String {
+        let mut out = String::from(FILE_HEADER);
+        out.push('\n');
+
+        for (id, meta) in &self.entries {
+            if meta.is_empty() {
+                continue;
+            }
+            out.push_str(&format!("\n[{}]\n", escape(id)));
+            if meta.favorite {
+                out.push_str("favorite = true\n");
+            }
+            if let Some(rating) = meta.rating {
+                out.push_str(&format!("rating = {}\n", rating));
+            }
+            if !meta.tags.is_empty() {
+                let tags: Vec<String> = meta.tags.iter().map(|t| escape(t)).collect();
+                out.push_str(&format!("tags = {}\n", tags.join(",")));
+            }
+            if let Some(color) = meta.color {
+                out.push_str(&format!("color = {}\n", color.as_str()));
+            }
+            if let Some(last_used) = meta.last_used {
+                let secs = last_used
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                out.push_str(&format!("last_used = {}\n", secs));
+            }
+            if meta.load_count > 0 {
+                out.push_str(&format!("load_count = {}\n", meta.load_count));
+            }
+        }
+
+        out
+    }
+
+    /// Parse the text file format
+    fn parse(text: &str) -> Result<Self> {
+        let mut store = Self::new();
+        let mut current: Option<String> = None;
+
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || {
+                Error::InvalidFormat(format!(
+                    "Metadata line {}: unexpected '{}'",
+                    line_number + 1,
+                    line
+                ))
+            };
+
+            if let Some(id) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let id = unescape(id);
+                store.entry(&id);
+                current = Some(id);
+                continue;
+            }
+
+            let id = current.as_deref().ok_or_else(invalid)?;
+            let (key, value) = line.split_once('=').ok_or_else(invalid)?;
+            let value = value.trim();
+            let meta = store.entry(id);
+
+            match key.trim() {
+                "favorite" => meta.favorite = value == "true",
+                "rating" => {
+                    meta.rating = Some(value.parse::<u8>().map_err(|_| invalid())?.min(MAX_RATING))
+                }
+                "tags" => {
+                    meta.tags = value
+                        .split(',')
+                        .map(unescape)
+                        .filter(|t| !t.is_empty())
+                        .collect()
+                }
+                "color" => meta.color = Some(ColorLabel::from_str(value).ok_or_else(invalid)?),
+                "last_used" => {
+                    let secs = value.parse::<u64>().map_err(|_| invalid())?;
+                    meta.last_used = Some(UNIX_EPOCH + Duration::from_secs(secs));
+                }
+                "load_count" => meta.load_count = value.parse().map_err(|_| invalid())?,
+                // Unknown keys come from newer versions; keep going
+                _ => {}
+            }
+        }
+
+        Ok(store)
+    }
+}
+
+/// Record a load in a scanner's usage store, if tracking is enabled
+pub(crate) fn record_load(store: Option<&SharedMetadataStore>, unique_id: &str) {
+    if let Some(store) = store {
+        // A panic elsewhere while holding the lock doesn't make the counts invalid
+        let mut store = store.lock().unwrap_or_else(|e| e.into_inner());
+        store.record_load(unique_id);
+    }
+}
+
+fn normalize_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+/// Percent-encode characters that are significant in the file format
+pub(crate) fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '%' | ',' | '[' | ']' | '=' | '\n' | '\r' => {
+                let mut buf = [0u8; 4];
+                for byte in c.encode_utf8(&mut buf).bytes() {
+                    out.push_str(&format!("%{:02X}", byte));
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Reverse [`escape()`]
+pub(crate) fn unescape(s: &str) -> String {
+    let bytes = s.trim().as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_favorites_and_ratings() {
+        let mut store = MetadataStore::new();
+        store.set_favorite("a", true);
+        store.set_favorite("b", false);
+        store.set_rating("b", Some(9));
+        store.set_rating("c", Some(3));
+
+        assert_eq!(store.favorites(), vec!["a"]);
+        assert_eq!(store.get("b").unwrap().rating, Some(MAX_RATING));
+        assert_eq!(store.rated_at_least(4), vec!["b"]);
+
+        store.set_rating("b", Some(0));
+        assert_eq!(store.get("b").unwrap().rating, None);
+    }
+
+    #[test]
+    fn test_tags_are_case_insensitive() {
+        let mut store = MetadataStore::new();
+        store.add_tag("a", "Delay");
+        store.add_tag("b", "delay ");
+        store.add_tag("b", "Tape");
+
+        assert_eq!(store.with_tag("DELAY"), vec!["a", "b"]);
+        assert_eq!(
+            store.all_tags().into_iter().collect::<Vec<_>>(),
+            vec!["delay", "tape"]
+        );
+
+        store.remove_tag("a", "delay");
+        assert_eq!(store.with_tag("delay"), vec!["b"]);
+    }
+
+    #[test]
+    fn test_recently_used() {
+        let mut store = MetadataStore::new();
+        store.entry("old").last_used = Some(UNIX_EPOCH + Duration::from_secs(100));
+        store.entry("new").last_used = Some(UNIX_EPOCH + Duration::from_secs(300));
+        store.entry("mid").last_used = Some(UNIX_EPOCH + Duration::from_secs(200));
+        store.set_favorite("never", true);
+
+        assert_eq!(store.recently_used(2), vec!["new", "mid"]);
+    }
+
+    #[test]
+    fn test_most_used() {
+        let mut store = MetadataStore::new();
+        for _ in 0..3 {
+            store.record_load("often");
+        }
+        store.record_load("once");
+        store.touch("touched");
+
+        assert_eq!(store.get("often").unwrap().load_count, 3);
+        assert_eq!(store.most_used(5), vec!["often", "once"]);
+        assert_eq!(store.most_used(1), vec!["often"]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut store = MetadataStore::new();
+        store.set_favorite("aufx:dely:appl", true);
+        store.set_rating("aufx:dely:appl", Some(4));
+        store.add_tag("aufx:dely:appl", "delay");
+        store.add_tag("aufx:dely:appl", "weird, tag=[x]%");
+        store.set_color("aufx:dely:appl", Some(ColorLabel::Blue));
+        store.entry("aufx:dely:appl").load_count = 7;
+        store.entry("vst3[id]").last_used = Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+
+        let parsed = MetadataStore::parse(&store.serialize()).unwrap();
+        assert_eq!(parsed.entries, store.entries);
+    }
+
+    #[test]
+    fn test_parse_rejects_garbage() {
+        assert!(MetadataStore::parse("favorite = true").is_err());
+        assert!(MetadataStore::parse("[a]\nrating = lots").is_err());
+        assert!(MetadataStore::parse("[a]\nfuture_key = 1").is_ok());
+    }
+
+    #[test]
+    fn test_open_save() {
+        let path =
+            std::env::temp_dir().join(format!("rack-metadata-test-{}.txt", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut store = MetadataStore::open(&path).unwrap();
+        assert!(store.is_empty());
+        store.set_favorite("a", true);
+        store.save().unwrap();
+
+        let reopened = MetadataStore::open(&path).unwrap();
+        assert_eq!(reopened.favorites(), vec!["a"]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,108 @@
+//! Metering published by plugins themselves
+//!
+//! Some plugins compute levels as part of their processing (gain reduction of
+//! a compressor, input and output levels of a channel strip) and publish them.
+//! [`PluginInstance::plugin_meter()`] returns the current values where the
+//! backend supports it, so a host can show the plugin's own meters instead of
+//! measuring the audio itself; when it returns `None`, fall back to a host
+//! tap on the plugin's output.
+//!
+//! | Backend | Source |
+//! |---------|--------|
+//! | AudioUnit | Parameters flagged `kAudioUnitParameterFlag_MeterReadOnly` (metering is switched on with `kAudioUnitProperty_MeteringMode` when the plugin has any) |
+//! | VST3 | None (no standard mechanism) |
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # fn example(plugin: &impl PluginInstance) {
+//! match plugin.plugin_meter() {
+//!     Some(meters) => {
+//!         for meter in meters {
+//!             println!("{}: {} {}", meter.name, meter.value, meter.unit);
+//!         }
+//!     }
+//!     None => {
+//!         // Meter the plugin's output in the host instead
+//!     }
+//! }
+//! # }
+//! ```
+//!
+//! [`PluginInstance::plugin_meter()`]: crate::PluginInstance::plugin_meter
+
+use crate::ParameterInfo;
+
+/// Current value of one meter a plugin publishes
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeterReading {
+    /// Index of the parameter carrying the meter
+    pub index: usize,
+
+    /// Meter name as reported by the plugin (e.g. "Gain Reduction")
+    pub name: String,
+
+    /// Current value, in `unit`
+    pub value: f32,
+
+    /// Lowest value the meter shows
+    pub min: f32,
+
+    /// Highest value the meter shows
+    pub max: f32,
+
+    /// Unit label (e.g. "dB")
+    pub unit: String,
+}
+
+impl MeterReading {
+    /// Create a reading of a meter parameter from its plain value
+    pub fn new(info: &ParameterInfo, value: f32) -> Self {
+        Self {
+            index: info.index,
+            name: info.name.clone(),
+            value,
+            min: info.min,
+            max: info.max,
+            unit: info.unit.clone(),
+        }
+    }
+
+    /// The value's position between `min` and `max`, from 0.0 to 1.0
+    ///
+    /// For drawing the meter.
+    pub fn fraction(&self) -> f32 {
+        if self.max > self.min {
+            ((self.value - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockPlugin;
+    use crate::PluginInstance;
+
+    #[test]
+    fn test_meter_reading() {
+        let info = ParameterInfo::new(
+            4,
+            "Gain Reduction".to_string(),
+            -24.0,
+            0.0,
+            0.0,
+            "dB".to_string(),
+        );
+        let reading = MeterReading::new(&info, -6.0);
+        assert_eq!(reading.index, 4);
+        assert_eq!(reading.fraction(), 0.75);
+        assert_eq!(MeterReading::new(&info, 3.0).fraction(), 1.0);
+
+        // Plugins without meters leave it to the host
+        assert!(MockPlugin::new().plugin_meter().is_none());
+    }
+}
@@ -173,6 +173,44 @@ impl MidiEvent {
         }
     }
 
+    /// Create a Note On and its matching Note Off, `duration` samples apart
+    ///
+    /// The Note Off is usually beyond the current block; queue both events in
+    /// an [`EventPort`](crate::port::EventPort), which holds it back until the
+    /// block it falls in. The Note Off uses release velocity 64.
+    ///
+    /// # Arguments
+    ///
+    /// * `note` - MIDI note number (0-127, clamped if out of range)
+    /// * `velocity` - Note velocity (0-127, clamped if out of range)
+    /// * `channel` - MIDI channel (0-15, clamped if out of range)
+    /// * `start_offset` - Sample offset of the Note On
+    /// * `duration` - Length of the note in samples
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rack::midi::MidiEvent;
+    /// use rack::port::EventPort;
+    ///
+    /// // Middle C for half a second at 48 kHz
+    /// let mut port = EventPort::new();
+    /// port.extend(MidiEvent::note(60, 100, 0, 0, 24000));
+    /// assert_eq!(port.len(), 2);
+    /// ```
+    pub fn note(
+        note: u8,
+        velocity: u8,
+        channel: u8,
+        start_offset: u32,
+        duration: u32,
+    ) -> [Self; 2] {
+        [
+            Self::note_on(note, velocity, channel, start_offset),
+            Self::note_off(note, 64, channel, start_offset.saturating_add(duration)),
+        ]
+    }
+
     /// Create a new Control Change event
     ///
     /// # Arguments
@@ -385,6 +423,123 @@ impl MidiEvent {
             kind: MidiEventKind::SystemReset,
         }
     }
+
+    /// The event as a raw MIDI 1.0 message: status byte and two data bytes
+    ///
+    /// Unused data bytes are 0. Channels above 15 are masked to 4 bits.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rack::midi::MidiEvent;
+    ///
+    /// assert_eq!(MidiEvent::note_on(60, 100, 1, 0).to_bytes(), [0x91, 60, 100]);
+    /// ```
+    pub fn to_bytes(&self) -> [u8; 3] {
+        let channel = |ch: u8| ch & 0x0F;
+        match self.kind {
+            MidiEventKind::NoteOn {
+                note,
+                velocity,
+                channel: ch,
+            } => [0x90 | channel(ch), note, velocity],
+            MidiEventKind::NoteOff {
+                note,
+                velocity,
+                channel: ch,
+            } => [0x80 | channel(ch), note, velocity],
+            MidiEventKind::PolyphonicAftertouch {
+                note,
+                pressure,
+                channel: ch,
+            } => [0xA0 | channel(ch), note, pressure],
+            MidiEventKind::ControlChange {
+                controller,
+                value,
+                channel: ch,
+            } => [0xB0 | channel(ch), controller, value],
+            MidiEventKind::ProgramChange {
+                program,
+                channel: ch,
+            } => [0xC0 | channel(ch), program, 0],
+            MidiEventKind::ChannelAftertouch {
+                pressure,
+                channel: ch,
+            } => [0xD0 | channel(ch), pressure, 0],
+            MidiEventKind::PitchBend { value, channel: ch } => [
+                0xE0 | channel(ch),
+                (value & 0x7F) as u8,
+                ((value >> 7) & 0x7F) as u8,
+            ],
+            MidiEventKind::TimingClock => [0xF8, 0, 0],
+            MidiEventKind::Start => [0xFA, 0, 0],
+            MidiEventKind::Continue => [0xFB, 0, 0],
+            MidiEventKind::Stop => [0xFC, 0, 0],
+            MidiEventKind::ActiveSensing => [0xFE, 0, 0],
+            MidiEventKind::SystemReset => [0xFF, 0, 0],
+        }
+    }
+
+    /// Parse a MIDI message from its bytes, the inverse of [`to_bytes()`](Self::to_bytes)
+    ///
+    /// Returns `None` for messages that aren't supported (system exclusive,
+    /// song position, ...) or are too short. Data bytes are masked to 7 bits.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rack::midi::MidiEvent;
+    ///
+    /// let event = MidiEvent::from_bytes(&[0x91, 60, 100], 32);
+    /// assert_eq!(event, Some(MidiEvent::note_on(60, 100, 1, 32)));
+    /// ```
+    pub fn from_bytes(bytes: &[u8], sample_offset: u32) -> Option<Self> {
+        let status = *bytes.first()?;
+        let data = |index: usize| bytes.get(index).map(|byte| byte & 0x7F);
+        let channel = status & 0x0F;
+        let kind = match status {
+            0x80..=0x8F => MidiEventKind::NoteOff {
+                note: data(1)?,
+                velocity: data(2)?,
+                channel,
+            },
+            0x90..=0x9F => MidiEventKind::NoteOn {
+                note: data(1)?,
+                velocity: data(2)?,
+                channel,
+            },
+            0xA0..=0xAF => MidiEventKind::PolyphonicAftertouch {
+                note: data(1)?,
+                pressure: data(2)?,
+                channel,
+            },
+            0xB0..=0xBF => MidiEventKind::ControlChange {
+                controller: data(1)?,
+                value: data(2)?,
+                channel,
+            },
+            0xC0..=0xCF => MidiEventKind::ProgramChange {
+                program: data(1)?,
+                channel,
+            },
+            0xD0..=0xDF => MidiEventKind::ChannelAftertouch {
+                pressure: data(1)?,
+                channel,
+            },
+            0xE0..=0xEF => MidiEventKind::PitchBend {
+                value: data(1)? as u16 | (data(2)? as u16) << 7,
+                channel,
+            },
+            0xF8 => MidiEventKind::TimingClock,
+            0xFA => MidiEventKind::Start,
+            0xFB => MidiEventKind::Continue,
+            0xFC => MidiEventKind::Stop,
+            0xFE => MidiEventKind::ActiveSensing,
+            0xFF => MidiEventKind::SystemReset,
+            _ => return None,
+        };
+        Some(Self { sample_offset, kind })
+    }
 }
 
 #[cfg(test)]
@@ -419,6 +574,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_note_with_duration() {
+        let [on, off] = MidiEvent::note(60, 100, 2, 10, 500);
+        assert_eq!(on, MidiEvent::note_on(60, 100, 2, 10));
+        assert_eq!(off, MidiEvent::note_off(60, 64, 2, 510));
+
+        let [_, off] = MidiEvent::note(60, 100, 0, u32::MAX - 1, 10);
+        assert_eq!(off.sample_offset, u32::MAX);
+    }
+
     #[test]
     fn test_control_change_creation() {
         let event = MidiEvent::control_change(1, 64, 0, 0);
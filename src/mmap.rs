@@ -0,0 +1,229 @@
+//! Read-only memory-mapped files
+//!
+//! Sampler states can be hundreds of megabytes. Reading one into a `Vec<u8>`
+//! before handing it to [`set_state()`](crate::PluginInstance::set_state)
+//! holds the whole file in memory on top of whatever the plugin builds from
+//! it. A [`MappedFile`] lets the OS page the file in on demand instead, and
+//! drops those pages again under memory pressure.
+//!
+//! [`PluginInstance::set_state_from_file()`](crate::PluginInstance::set_state_from_file)
+//! uses this.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # fn example() -> rack::Result<()> {
+//! use rack::mmap::MappedFile;
+//!
+//! let state = MappedFile::open("kontakt-session.state")?;
+//! println!("{} bytes, starting with {:?}", state.len(), &state[..4]);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{Error, Result};
+use std::path::Path;
+
+/// A file mapped read-only into memory
+///
+/// Dereferences to the file's contents. The file must not be truncated by
+/// another process while it is mapped; on Unix that raises `SIGBUS`.
+pub struct MappedFile {
+    ptr: *const u8,
+    len: usize,
+}
+
+// Safety: the mapping is read-only and owned exclusively by this value
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl MappedFile {
+    /// Map a whole file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be opened or mapped
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = std::fs::File::open(path.as_ref())?;
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| Error::Other("File too large to map".to_string()))?;
+        if len == 0 {
+            // Zero-length mappings aren't allowed; nothing to map anyway
+            return Ok(Self {
+                ptr: std::ptr::NonNull::<u8>::dangling().as_ptr(),
+                len: 0,
+            });
+        }
+        let ptr = sys::map(&file, len)?;
+        Ok(Self { ptr, len })
+    }
+}
+
+impl std::ops::Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // Safety: ptr points to len readable bytes for the lifetime of self
+        // (or is dangling with len 0)
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl std::fmt::Debug for MappedFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MappedFile")
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            sys::unmap(self.ptr, self.len);
+        }
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::ffi::{c_int, c_long, c_void};
+    use std::os::unix::io::AsRawFd;
+
+    const PROT_READ: c_int = 0x1;
+    const MAP_PRIVATE: c_int = 0x2;
+    const MAP_FAILED: *mut c_void = !0usize as *mut c_void;
+
+    extern "C" {
+        fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: c_long,
+        ) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    }
+
+    pub(super) fn map(file: &std::fs::File, len: usize) -> crate::Result<*const u8> {
+        // Safety: fd is open for reading and len is the file's non-zero length
+        let ptr = unsafe {
+            mmap(
+                std::ptr::null_mut(),
+                len,
+                PROT_READ,
+                MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == MAP_FAILED {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(ptr as *const u8)
+    }
+
+    pub(super) fn unmap(ptr: *const u8, len: usize) {
+        // Safety: ptr and len come from a successful map()
+        unsafe {
+            munmap(ptr as *mut c_void, len);
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::ffi::c_void;
+    use std::os::windows::io::AsRawHandle;
+
+    const PAGE_READONLY: u32 = 0x02;
+    const FILE_MAP_READ: u32 = 0x0004;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn CreateFileMappingW(
+            file: *mut c_void,
+            attributes: *mut c_void,
+            protect: u32,
+            max_size_high: u32,
+            max_size_low: u32,
+            name: *const u16,
+        ) -> *mut c_void;
+        fn MapViewOfFile(
+            mapping: *mut c_void,
+            access: u32,
+            offset_high: u32,
+            offset_low: u32,
+            len: usize,
+        ) -> *mut c_void;
+        fn UnmapViewOfFile(base: *const c_void) -> i32;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+
+    pub(super) fn map(file: &std::fs::File, len: usize) -> crate::Result<*const u8> {
+        // Safety: the handle is an open file; the mapping handle can be closed
+        // once the view exists (the view keeps the mapping alive)
+        unsafe {
+            let mapping = CreateFileMappingW(
+                file.as_raw_handle() as *mut c_void,
+                std::ptr::null_mut(),
+                PAGE_READONLY,
+                0,
+                0,
+                std::ptr::null(),
+            );
+            if mapping.is_null() {
+                return Err(std::io::Error::last_os_error().into());
+            }
+            let view = MapViewOfFile(mapping, FILE_MAP_READ, 0, 0, len);
+            let error = std::io::Error::last_os_error();
+            CloseHandle(mapping);
+            if view.is_null() {
+                return Err(error.into());
+            }
+            Ok(view as *const u8)
+        }
+    }
+
+    pub(super) fn unmap(ptr: *const u8, _len: usize) {
+        // Safety: ptr comes from a successful map()
+        unsafe {
+            UnmapViewOfFile(ptr as *const c_void);
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    pub(super) fn map(_file: &std::fs::File, _len: usize) -> crate::Result<*const u8> {
+        Err(crate::Error::Other(
+            "Memory-mapped files aren't supported on this platform".to_string(),
+        ))
+    }
+
+    pub(super) fn unmap(_ptr: *const u8, _len: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_file() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("rack-mmap-{}.bin", std::process::id()));
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+
+        let mapped = MappedFile::open(&path).unwrap();
+        assert_eq!(&mapped[..], &data[..]);
+        drop(mapped);
+
+        std::fs::write(&path, b"").unwrap();
+        assert!(MappedFile::open(&path).unwrap().is_empty());
+        assert!(MappedFile::open(dir.join("rack-mmap-missing.bin")).is_err());
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,698 @@
+//! Simulated plugins for developing hosts without real ones
+//!
+//! [`MockScanner`] "finds" a fixed catalog of plugins — a gain, a delay and a
+//! sine synth, one per format — and [`MockPlugin`] processes audio the way
+//! they would: parameters, presets, state, MIDI, tails and bypass all behave
+//! like a real plugin's. Use them to build and test a host's UI on machines without
+//! plugins installed.
+//!
+//! On WebAssembly, where native plugins can't be loaded, they are the
+//! platform's default [`Scanner`](crate::Scanner) and
+//! [`Plugin`](crate::Plugin), and [`UnifiedScanner`](crate::unified::UnifiedScanner)
+//! returns the catalog, so a frontend built on rack's types runs unchanged in
+//! the browser.
+//!
+//! # Examples
+//!
+//! ```
+//! use rack::mock::MockScanner;
+//! use rack::prelude::*;
+//!
+//! # fn main() -> rack::Result<()> {
+//! let scanner = MockScanner::new()?;
+//! let plugins = scanner.scan()?;
+//! let synth = plugins
+//!     .iter()
+//!     .find(|p| p.plugin_type == PluginType::Instrument)
+//!     .unwrap();
+//!
+//! let mut plugin = scanner.load(synth)?;
+//! plugin.initialize(48000.0, 512)?;
+//! plugin.send_midi(&[MidiEvent::note_on(69, 100, 0, 0)])?;
+//!
+//! let mut left = vec![0.0f32; 512];
+//! let mut right = vec![0.0f32; 512];
+//! plugin.process(&[], &mut [&mut left, &mut right], 512)?;
+//! assert!(left.iter().any(|&s| s != 0.0));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::bypass::HostBypass;
+use crate::scan::ScannerConfig;
+use crate::{
+    CurrentPreset, Error, MidiEvent, MidiEventKind, Normalized, ParameterInfo, Plain, PluginFormat,
+    PluginInfo, PluginInstance, PluginScanner, PluginType, PresetInfo, Result,
+};
+use std::f64::consts::PI;
+use std::path::{Path, PathBuf};
+
+/// Where the catalog's plugins "live"
+const MOCK_PATH: &str = "/mock";
+
+/// Most notes the synth plays at once; further notes steal the oldest
+const MAX_VOICES: usize = 16;
+
+/// Longest delay time, in seconds
+const MAX_DELAY: f64 = 2.0;
+
+/// The simulated plugins
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Gain,
+    Delay,
+    Synth,
+}
+
+impl Kind {
+    const ALL: [Kind; 3] = [Kind::Gain, Kind::Delay, Kind::Synth];
+
+    fn unique_id(self) -> &'static str {
+        match self {
+            Kind::Gain => "rack.mock.gain",
+            Kind::Delay => "rack.mock.delay",
+            Kind::Synth => "rack.mock.synth",
+        }
+    }
+
+    fn from_unique_id(unique_id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.unique_id() == unique_id)
+    }
+
+    fn info(self) -> PluginInfo {
+        let (name, plugin_type, file, format) = match self {
+            Kind::Gain => (
+                "Mock Gain",
+                PluginType::Effect,
+                "Mock Gain.vst3",
+                PluginFormat::Vst3,
+            ),
+            Kind::Delay => (
+                "Mock Delay",
+                PluginType::Effect,
+                "Mock Delay.clap",
+                PluginFormat::Clap,
+            ),
+            Kind::Synth => (
+                "Mock Synth",
+                PluginType::Instrument,
+                "Mock Synth.lv2",
+                PluginFormat::Lv2,
+            ),
+        };
+        PluginInfo::new(
+            name.to_string(),
+            "Rack".to_string(),
+            0x0001_0000,
+            plugin_type,
+            Path::new(MOCK_PATH).join(file),
+            self.unique_id().to_string(),
+        )
+        .with_format(format)
+    }
+
+    fn parameters(self) -> Vec<ParameterInfo> {
+        match self {
+            Kind::Gain => vec![ParameterInfo::new(
+                0,
+                "Gain".to_string(),
+                -60.0,
+                12.0,
+                0.0,
+                "dB".to_string(),
+            )],
+            Kind::Delay => vec![
+                ParameterInfo::new(
+                    0,
+                    "Time".to_string(),
+                    1.0,
+                    (MAX_DELAY * 1000.0) as f32,
+                    250.0,
+                    "ms".to_string(),
+                ),
+                ParameterInfo::new(1, "Feedback".to_string(), 0.0, 0.95, 0.4, "".to_string()),
+                ParameterInfo::new(2, "Mix".to_string(), 0.0, 1.0, 0.3, "".to_string()),
+            ],
+            Kind::Synth => vec![
+                ParameterInfo::new(0, "Volume".to_string(), -60.0, 0.0, -12.0, "dB".to_string()),
+                ParameterInfo::new(
+                    1,
+                    "Release".to_string(),
+                    1.0,
+                    2000.0,
+                    200.0,
+                    "ms".to_string(),
+                ),
+            ],
+        }
+    }
+
+    /// Factory presets, as names and plain parameter values
+    fn presets(self) -> &'static [(&'static str, &'static [f32])] {
+        match self {
+            Kind::Gain => &[("Unity", &[0.0]), ("Boost", &[6.0]), ("Quiet", &[-20.0])],
+            Kind::Delay => &[
+                ("Slapback", &[90.0, 0.1, 0.3]),
+                ("Quarter Note", &[500.0, 0.4, 0.3]),
+                ("Ambient", &[750.0, 0.8, 0.5]),
+            ],
+            Kind::Synth => &[("Pluck", &[-12.0, 50.0]), ("Pad", &[-18.0, 1500.0])],
+        }
+    }
+}
+
+/// Scanner returning a fixed catalog of simulated plugins
+///
+/// The catalog holds "Mock Gain" (a VST3 effect), "Mock Delay" (a CLAP
+/// effect) and "Mock Synth" (an LV2 instrument), all under `/mock`. Nothing
+/// is read from disk: [`scan_path()`](PluginScanner::scan_path) returns the
+/// catalog entries below the path, and the catalog is empty when default
+/// paths are skipped.
+pub struct MockScanner {
+    config: ScannerConfig,
+    formats: Option<Vec<PluginFormat>>,
+}
+
+impl MockScanner {
+    /// Create a scanner for the catalog
+    ///
+    /// # Errors
+    ///
+    /// Never fails; returns a `Result` like the other scanners
+    pub fn new() -> Result<Self> {
+        Self::with_config(ScannerConfig::from_env())
+    }
+
+    /// Only return the catalog's plugins of `format`
+    #[cfg(target_family = "wasm")]
+    pub(crate) fn only(mut self, format: PluginFormat) -> Self {
+        self.formats = Some(vec![format]);
+        self
+    }
+
+    /// The formats of the catalog's plugins
+    #[cfg(target_family = "wasm")]
+    pub(crate) fn catalog_formats() -> Vec<PluginFormat> {
+        Kind::ALL.iter().map(|k| k.info().format).collect()
+    }
+
+    fn catalog(&self) -> impl Iterator<Item = PluginInfo> + '_ {
+        Kind::ALL
+            .into_iter()
+            .map(Kind::info)
+            .filter(|info| match &self.formats {
+                Some(formats) => formats.contains(&info.format),
+                None => true,
+            })
+    }
+}
+
+impl PluginScanner for MockScanner {
+    type Plugin = MockPlugin;
+
+    fn with_config(config: ScannerConfig) -> Result<Self> {
+        Ok(Self {
+            config,
+            formats: None,
+        })
+    }
+
+    fn config(&self) -> &ScannerConfig {
+        &self.config
+    }
+
+    fn default_paths() -> Vec<PathBuf> {
+        vec![PathBuf::from(MOCK_PATH)]
+    }
+
+    fn add_path(&mut self, path: &Path) -> Result<()> {
+        self.config.extra_paths.push(path.to_path_buf());
+        Ok(())
+    }
+
+    fn scan(&self) -> Result<Vec<PluginInfo>> {
+        let config = &self.config;
+        Ok(self
+            .catalog()
+            .filter(|info| {
+                !config.skip_default_paths
+                    || config.extra_paths.iter().any(|p| info.path.starts_with(p))
+            })
+            .collect())
+    }
+
+    fn scan_path(&self, path: &Path) -> Result<Vec<PluginInfo>> {
+        Ok(self
+            .catalog()
+            .filter(|info| info.path.starts_with(path))
+            .collect())
+    }
+
+    fn load(&self, info: &PluginInfo) -> Result<MockPlugin> {
+        MockPlugin::new(&info.unique_id)
+            .ok_or_else(|| Error::PluginNotFound(info.unique_id.clone()))
+    }
+}
+
+/// A sounding voice of the synth
+#[derive(Debug, Clone, Copy)]
+struct Voice {
+    note: u8,
+    velocity: f32,
+    phase: f64,
+    /// Envelope level, falling once released
+    level: f32,
+    released: bool,
+}
+
+/// A simulated plugin from [`MockScanner`]'s catalog
+///
+/// - "Mock Gain" scales its stereo input.
+/// - "Mock Delay" is a stereo feedback delay, with a tail that follows its
+///   time and feedback.
+/// - "Mock Synth" plays a sine per MIDI note, with a release tail.
+///
+/// MIDI events apply at their sample offset in the next processed block.
+pub struct MockPlugin {
+    kind: Kind,
+    info: PluginInfo,
+    params: Vec<ParameterInfo>,
+    values: Vec<f32>,
+    current_preset: Option<usize>,
+    initialized: bool,
+    sample_rate: f64,
+    max_block_size: usize,
+    sample_position: u64,
+    /// Per-channel delay lines (the delay)
+    lines: Vec<Vec<f32>>,
+    write: usize,
+    /// Sounding notes (the synth)
+    voices: Vec<Voice>,
+    /// MIDI for the next block, sorted by offset
+    pending: Vec<MidiEvent>,
+    bypass: HostBypass,
+}
+
+impl MockPlugin {
+    /// Create the catalog plugin with `unique_id`, or `None` if there is none
+    pub fn new(unique_id: &str) -> Option<Self> {
+        let kind = Kind::from_unique_id(unique_id)?;
+        let params = kind.parameters();
+        let values = params
+            .iter()
+            .map(|p| p.default_normalized().value())
+            .collect();
+        Some(Self {
+            kind,
+            info: kind.info(),
+            params,
+            values,
+            current_preset: None,
+            initialized: false,
+            sample_rate: 0.0,
+            max_block_size: 0,
+            sample_position: 0,
+            lines: Vec::new(),
+            write: 0,
+            voices: Vec::with_capacity(MAX_VOICES),
+            pending: Vec::new(),
+            bypass: HostBypass::new(),
+        })
+    }
+
+    /// Plain value of parameter `index`
+    fn plain(&self, index: usize) -> f32 {
+        self.params[index]
+            .to_plain(Normalized::new(self.values[index]))
+            .value()
+    }
+
+    fn handle_midi(&mut self, event: &MidiEvent) {
+        match event.kind {
+            MidiEventKind::NoteOn { note, velocity, .. } if velocity > 0 => {
+                if self.voices.len() == MAX_VOICES {
+                    self.voices.remove(0);
+                }
+                self.voices.push(Voice {
+                    note,
+                    velocity: velocity as f32 / 127.0,
+                    phase: 0.0,
+                    level: 1.0,
+                    released: false,
+                });
+            }
+            MidiEventKind::NoteOn { note, .. } | MidiEventKind::NoteOff { note, .. } => {
+                for voice in self.voices.iter_mut().filter(|v| v.note == note) {
+                    voice.released = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn process_gain(&self, inputs: &[&[f32]], outputs: &mut [&mut [f32]], num_frames: usize) {
+        let gain = 10f32.powf(self.plain(0) / 20.0);
+        for (ch, output) in outputs.iter_mut().enumerate() {
+            match inputs.get(ch) {
+                Some(input) => {
+                    for (o, i) in output[..num_frames].iter_mut().zip(&input[..num_frames]) {
+                        *o = i * gain;
+                    }
+                }
+                None => output[..num_frames].fill(0.0),
+            }
+        }
+    }
+
+    fn process_delay(&mut self, inputs: &[&[f32]], outputs: &mut [&mut [f32]], num_frames: usize) {
+        let delay = ((self.plain(0) as f64 / 1000.0 * self.sample_rate) as usize)
+            .clamp(1, self.lines[0].len() - 1);
+        let feedback = self.plain(1);
+        let mix = self.plain(2);
+        let len = self.lines[0].len();
+        for (ch, output) in outputs.iter_mut().enumerate().take(self.lines.len()) {
+            let line = &mut self.lines[ch];
+            let mut write = self.write;
+            for i in 0..num_frames {
+                let dry = inputs.get(ch).map_or(0.0, |input| input[i]);
+                let wet = line[(write + len - delay) % len];
+                line[write] = dry + wet * feedback;
+                output[i] = dry * (1.0 - mix) + wet * mix;
+                write = (write + 1) % len;
+            }
+        }
+        self.write = (self.write + num_frames) % len;
+    }
+
+    fn render_synth(&mut self, outputs: &mut [&mut [f32]], start: usize, end: usize) {
+        let volume = 10f32.powf(self.plain(0) / 20.0);
+        let release = (self.plain(1) / 1000.0 * self.sample_rate as f32).max(1.0);
+        let step = 1.0 / release;
+        for i in start..end {
+            let mut sample = 0.0;
+            for voice in self.voices.iter_mut() {
+                let frequency = 440.0 * 2f64.powf((voice.note as f64 - 69.0) / 12.0);
+                sample += (voice.phase * 2.0 * PI).sin() as f32 * voice.velocity * voice.level;
+                voice.phase = (voice.phase + frequency / self.sample_rate).fract();
+                if voice.released {
+                    voice.level = (voice.level - step).max(0.0);
+                }
+            }
+            for output in outputs.iter_mut() {
+                output[i] = sample * volume;
+            }
+        }
+        self.voices.retain(|v| v.level > 0.0);
+    }
+}
+
+impl PluginInstance for MockPlugin {
+    fn initialize(&mut self, sample_rate: f64, max_block_size: usize) -> Result<()> {
+        if !(sample_rate.is_finite() && sample_rate > 0.0) {
+            return Err(Error::Other(format!(
+                "Invalid sample rate: {}",
+                sample_rate
+            )));
+        }
+        self.sample_rate = sample_rate;
+        self.max_block_size = max_block_size;
+        if self.kind == Kind::Delay {
+            let len = (MAX_DELAY * sample_rate) as usize + 1;
+            self.lines = vec![vec![0.0; len]; 2];
+            self.write = 0;
+        }
+        self.bypass.prepare(sample_rate, 2, 0);
+        self.initialized = true;
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        for line in self.lines.iter_mut() {
+            line.fill(0.0);
+        }
+        self.voices.clear();
+        self.pending.clear();
+        Ok(())
+    }
+
+    fn process(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        if !self.initialized {
+            return Err(Error::NotInitialized);
+        }
+        if num_frames > self.max_block_size {
+            return Err(Error::BlockTooLarge {
+                max: self.max_block_size,
+                got: num_frames,
+            });
+        }
+        match self.kind {
+            Kind::Gain => self.process_gain(inputs, outputs, num_frames),
+            Kind::Delay => self.process_delay(inputs, outputs, num_frames),
+            Kind::Synth => {
+                let pending = std::mem::take(&mut self.pending);
+                let mut start = 0;
+                for event in &pending {
+                    let offset = (event.sample_offset as usize).min(num_frames);
+                    self.render_synth(outputs, start, offset);
+                    self.handle_midi(event);
+                    start = offset;
+                }
+                self.render_synth(outputs, start, num_frames);
+                self.pending = pending;
+                self.pending.clear();
+            }
+        }
+        self.bypass.process(inputs, outputs, num_frames);
+        self.sample_position += num_frames as u64;
+        Ok(())
+    }
+
+    fn sample_position(&self) -> u64 {
+        self.sample_position
+    }
+
+    fn set_sample_position(&mut self, position: u64) -> Result<()> {
+        self.sample_position = position;
+        Ok(())
+    }
+
+    fn parameter_count(&self) -> usize {
+        self.params.len()
+    }
+
+    fn parameter_info(&self, index: usize) -> Result<ParameterInfo> {
+        self.params
+            .get(index)
+            .cloned()
+            .ok_or(Error::InvalidParameter(index))
+    }
+
+    fn get_parameter(&self, index: usize) -> Result<f32> {
+        self.values
+            .get(index)
+            .copied()
+            .ok_or(Error::InvalidParameter(index))
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) -> Result<()> {
+        let slot = self
+            .values
+            .get_mut(index)
+            .ok_or(Error::InvalidParameter(index))?;
+        *slot = value.clamp(0.0, 1.0);
+        Ok(())
+    }
+
+    fn send_midi(&mut self, events: &[MidiEvent]) -> Result<()> {
+        if self.kind == Kind::Synth {
+            self.pending.extend_from_slice(events);
+            self.pending.sort_by_key(|e| e.sample_offset);
+        }
+        Ok(())
+    }
+
+    fn preset_count(&self) -> Result<usize> {
+        Ok(self.kind.presets().len())
+    }
+
+    fn preset_info(&self, index: usize) -> Result<PresetInfo> {
+        let (name, _) = self
+            .kind
+            .presets()
+            .get(index)
+            .ok_or_else(|| Error::Other(format!("Invalid preset index: {}", index)))?;
+        Ok(PresetInfo::new(index, name.to_string(), index as i32))
+    }
+
+    fn load_preset(&mut self, preset_number: i32) -> Result<()> {
+        let index = usize::try_from(preset_number)
+            .ok()
+            .filter(|&i| i < self.kind.presets().len())
+            .ok_or_else(|| Error::Other(format!("Invalid preset number: {}", preset_number)))?;
+        let (_, values) = self.kind.presets()[index];
+        for (slot, (param, &value)) in self.values.iter_mut().zip(self.params.iter().zip(values)) {
+            *slot = param.to_normalized(Plain(value)).value();
+        }
+        self.current_preset = Some(index);
+        Ok(())
+    }
+
+    fn current_preset(&self) -> Result<Option<CurrentPreset>> {
+        Ok(self.current_preset.map(|index| CurrentPreset {
+            preset_number: index as i32,
+            name: self.kind.presets()[index].0.to_string(),
+        }))
+    }
+
+    fn get_state(&self) -> Result<Vec<u8>> {
+        Ok(self.values.iter().flat_map(|v| v.to_le_bytes()).collect())
+    }
+
+    fn set_state(&mut self, data: &[u8]) -> Result<()> {
+        if data.len() != self.values.len() * 4 {
+            return Err(Error::Other(format!(
+                "Invalid {} state: {} bytes",
+                self.info.name,
+                data.len()
+            )));
+        }
+        for (value, bytes) in self.values.iter_mut().zip(data.chunks_exact(4)) {
+            *value = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]).clamp(0.0, 1.0);
+        }
+        Ok(())
+    }
+
+    fn info(&self) -> &PluginInfo {
+        &self.info
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    fn input_channels(&self) -> usize {
+        match (self.initialized, self.kind) {
+            (false, _) | (true, Kind::Synth) => 0,
+            (true, _) => 2,
+        }
+    }
+
+    fn output_channels(&self) -> usize {
+        if self.initialized {
+            2
+        } else {
+            0
+        }
+    }
+
+    fn tail_samples(&self) -> usize {
+        if self.initialized {
+            crate::traits::tail_samples(self.tail_seconds(), self.sample_rate)
+        } else {
+            0
+        }
+    }
+
+    fn tail_seconds(&self) -> f64 {
+        match self.kind {
+            Kind::Gain => 0.0,
+            // Until the echoes fall below -60 dB
+            Kind::Delay => {
+                let time = self.plain(0) as f64 / 1000.0;
+                let feedback = self.plain(1) as f64;
+                if feedback <= 0.001 {
+                    time
+                } else {
+                    time * (1.0 + (0.001f64).ln() / feedback.ln()).ceil()
+                }
+            }
+            Kind::Synth => self.plain(1) as f64 / 1000.0,
+        }
+    }
+
+    fn set_bypass(&mut self, bypassed: bool) -> Result<()> {
+        self.bypass.set_bypassed(bypassed);
+        Ok(())
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.bypass.is_bypassed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_catalog() {
+        let scanner = MockScanner::new().unwrap();
+        let plugins = scanner.scan().unwrap();
+        assert_eq!(plugins.len(), 3);
+        assert!(scanner
+            .scan_path(Path::new("/elsewhere"))
+            .unwrap()
+            .is_empty());
+
+        let skipped = MockScanner::with_config(ScannerConfig::new().skip_default_paths(true));
+        assert!(skipped.unwrap().scan().unwrap().is_empty());
+
+        let delay = plugins.iter().find(|p| p.name == "Mock Delay").unwrap();
+        assert_eq!(delay.format, PluginFormat::Clap);
+        let mut plugin = scanner.load(delay).unwrap();
+        assert_eq!(plugin.preset_count().unwrap(), 3);
+        plugin.load_preset(0).unwrap();
+        assert_eq!(plugin.current_preset().unwrap().unwrap().name, "Slapback");
+        assert!((plugin.get_parameter_plain(0).unwrap().value() - 90.0).abs() < 0.01);
+
+        let state = plugin.get_state().unwrap();
+        plugin.load_preset(2).unwrap();
+        plugin.set_state(&state).unwrap();
+        assert_eq!(plugin.get_state().unwrap(), state);
+
+        let mut unknown = delay.clone();
+        unknown.unique_id = "nope".to_string();
+        assert!(matches!(
+            scanner.load(&unknown),
+            Err(Error::PluginNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_mock_processing() {
+        let mut delay = MockPlugin::new("rack.mock.delay").unwrap();
+        delay.load_preset(0).unwrap(); // 90 ms
+        delay.initialize(1000.0, 128).unwrap();
+        let mut impulse = vec![0.0f32; 128];
+        impulse[0] = 1.0;
+        let mut left = vec![0.0f32; 128];
+        let mut right = vec![0.0f32; 128];
+        delay
+            .process(&[&impulse, &impulse], &mut [&mut left, &mut right], 128)
+            .unwrap();
+        assert!((left[0] - 0.7).abs() < 1e-6);
+        assert!((left[90] - 0.3).abs() < 1e-6);
+        assert!(delay.tail_seconds() > 0.09);
+
+        let mut synth = MockPlugin::new("rack.mock.synth").unwrap();
+        synth.initialize(48000.0, 64).unwrap();
+        assert_eq!(synth.input_channels(), 0);
+        synth
+            .send_midi(&[
+                MidiEvent::note_on(69, 127, 0, 32),
+                MidiEvent::note_off(69, 0, 0, 48),
+            ])
+            .unwrap();
+        synth
+            .process(&[], &mut [&mut left[..64], &mut right[..64]], 64)
+            .unwrap();
+        assert!(left[..33].iter().all(|&s| s == 0.0));
+        assert!(left[33..48].iter().all(|&s| s != 0.0));
+        assert_eq!(left[..64], right[..64]);
+    }
+}
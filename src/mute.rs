@@ -0,0 +1,492 @@
+//! Per-node mute and solo
+//!
+//! [`MuteSolo`] wraps a plugin and silences its output when the node is muted,
+//! or when another node in its [`SoloGroup`] is soloed. How it silences
+//! depends on the [`MuteMode`]:
+//!
+//! - [`MuteMode::Soft`] (default) keeps calling `process()` and replaces the
+//!   output with silence. The plugin's timeline, delay lines and reverb tails
+//!   keep running, so unmuting brings back exactly what it would be playing
+//!   had it never been muted, in time with the other nodes.
+//! - [`MuteMode::Hard`] stops calling `process()` to save the CPU. The sample
+//!   position still advances by the suspended frames, but whatever was ringing
+//!   in the plugin when it was suspended plays on when it resumes.
+//!
+//! Switching fades the output over one block to avoid clicks; in hard mode,
+//! processing is suspended once the fade-out has finished.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::mute::{MuteSolo, SoloGroup};
+//! # fn example(drums: impl PluginInstance, bass: impl PluginInstance) {
+//! let solo = SoloGroup::new();
+//! let mut drums = MuteSolo::new(drums).with_solo_group(&solo);
+//! let mut bass = MuteSolo::new(bass).with_solo_group(&solo);
+//!
+//! drums.set_soloed(true);
+//! assert!(bass.is_silenced()); // Only the drums play
+//! # }
+//! ```
+
+use crate::meter::MeterReading;
+use crate::node::BlockContext;
+use crate::quirks::Quirks;
+use crate::{
+    CurrentPreset, MidiEvent, ParameterChange, ParameterInfo, PluginInfo, PluginInstance,
+    PresetInfo, Result,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// How a silenced node is silenced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MuteMode {
+    /// Keep processing, output silence
+    #[default]
+    Soft,
+
+    /// Stop processing
+    Hard,
+}
+
+/// Nodes that solo together
+///
+/// While any node in the group is soloed, the nodes that aren't are silenced.
+/// Cloning gives another handle to the same group.
+#[derive(Debug, Clone, Default)]
+pub struct SoloGroup {
+    soloed: Arc<AtomicUsize>,
+}
+
+impl SoloGroup {
+    /// Create a group with nothing soloed
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether any node in the group is soloed
+    pub fn any_soloed(&self) -> bool {
+        self.soloed.load(Ordering::Acquire) > 0
+    }
+
+    /// Number of soloed nodes
+    pub fn soloed_count(&self) -> usize {
+        self.soloed.load(Ordering::Acquire)
+    }
+}
+
+/// A node's place in a solo group; withdraws its solo when dropped
+#[derive(Debug)]
+struct Membership {
+    group: SoloGroup,
+    /// Whether the node's solo is counted in the group
+    counted: bool,
+}
+
+impl Membership {
+    fn count(&mut self, soloed: bool) {
+        if soloed != self.counted {
+            if soloed {
+                self.group.soloed.fetch_add(1, Ordering::AcqRel);
+            } else {
+                self.group.soloed.fetch_sub(1, Ordering::AcqRel);
+            }
+            self.counted = soloed;
+        }
+    }
+}
+
+impl Drop for Membership {
+    fn drop(&mut self) {
+        self.count(false);
+    }
+}
+
+/// Plugin wrapper that mutes and solos the plugin's output
+///
+/// All methods are forwarded to the wrapped plugin; `process()` silences the
+/// output (and in [`MuteMode::Hard`] skips the plugin) while
+/// [`is_silenced()`](Self::is_silenced). MIDI is forwarded either way.
+pub struct MuteSolo<P> {
+    plugin: P,
+    mode: MuteMode,
+    muted: bool,
+    soloed: bool,
+    membership: Option<Membership>,
+    /// Output gain at the end of the last block (0.0 or 1.0)
+    gain: f32,
+    /// Frames skipped since processing was suspended
+    suspended: Option<u64>,
+}
+
+impl<P: PluginInstance> MuteSolo<P> {
+    /// Wrap a plugin, unmuted and outside any solo group
+    pub fn new(plugin: P) -> Self {
+        Self {
+            plugin,
+            mode: MuteMode::Soft,
+            muted: false,
+            soloed: false,
+            membership: None,
+            gain: 1.0,
+            suspended: None,
+        }
+    }
+
+    /// Silence the node with `mode`
+    pub fn with_mode(mut self, mode: MuteMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Solo together with the other nodes of `group`
+    pub fn with_solo_group(mut self, group: &SoloGroup) -> Self {
+        self.set_solo_group(Some(group));
+        self
+    }
+
+    /// How the node is silenced
+    pub fn mode(&self) -> MuteMode {
+        self.mode
+    }
+
+    /// Change how the node is silenced
+    pub fn set_mode(&mut self, mode: MuteMode) {
+        self.mode = mode;
+    }
+
+    /// Whether the node is muted
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    /// Mute or unmute the node
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    /// Whether the node is soloed
+    pub fn is_soloed(&self) -> bool {
+        self.soloed
+    }
+
+    /// Solo or unsolo the node
+    ///
+    /// Soloing a node outside any solo group has no effect on what is heard.
+    pub fn set_soloed(&mut self, soloed: bool) {
+        self.soloed = soloed;
+        if let Some(membership) = &mut self.membership {
+            membership.count(soloed);
+        }
+    }
+
+    /// The solo group the node belongs to, if any
+    pub fn solo_group(&self) -> Option<&SoloGroup> {
+        self.membership.as_ref().map(|membership| &membership.group)
+    }
+
+    /// Move the node to another solo group (or out of any)
+    ///
+    /// The node keeps its solo state.
+    pub fn set_solo_group(&mut self, group: Option<&SoloGroup>) {
+        self.membership = group.map(|group| Membership {
+            group: group.clone(),
+            counted: false,
+        });
+        self.set_soloed(self.soloed);
+    }
+
+    /// Whether the output is silenced, by a mute or another node's solo
+    pub fn is_silenced(&self) -> bool {
+        self.muted || (!self.soloed && self.solo_group().is_some_and(SoloGroup::any_soloed))
+    }
+
+    /// Whether processing is suspended by a hard mute
+    pub fn is_suspended(&self) -> bool {
+        self.suspended.is_some()
+    }
+
+    /// The wrapped plugin
+    pub fn inner(&self) -> &P {
+        &self.plugin
+    }
+
+    /// The wrapped plugin, mutably
+    pub fn inner_mut(&mut self) -> &mut P {
+        &mut self.plugin
+    }
+
+    /// Unwrap the plugin, withdrawing its solo
+    pub fn into_inner(self) -> P {
+        self.plugin
+    }
+
+    /// Render a block with `process`, applying the mute
+    fn render(
+        &mut self,
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        process: impl FnOnce(&mut P, &mut [&mut [f32]]) -> Result<()>,
+    ) -> Result<()> {
+        let target = if self.is_silenced() { 0.0 } else { 1.0 };
+
+        if self.mode == MuteMode::Hard && target == 0.0 && self.gain == 0.0 {
+            if let Some(skipped) = &mut self.suspended {
+                *skipped += num_frames as u64;
+            } else {
+                self.suspended = Some(num_frames as u64);
+            }
+            for output in outputs.iter_mut() {
+                output[..num_frames].fill(0.0);
+            }
+            return Ok(());
+        }
+        self.resume();
+
+        process(&mut self.plugin, outputs)?;
+
+        let start = self.gain;
+        self.gain = target;
+        if start == 1.0 && target == 1.0 {
+            return Ok(());
+        }
+        let step = (target - start) / num_frames.max(1) as f32;
+        for output in outputs.iter_mut() {
+            for (i, sample) in output[..num_frames].iter_mut().enumerate() {
+                *sample *= start + step * (i + 1) as f32;
+            }
+        }
+        Ok(())
+    }
+
+    /// Resume processing after a hard mute, keeping the timeline in place
+    fn resume(&mut self) {
+        if let Some(skipped) = self.suspended.take() {
+            let position = self.plugin.sample_position() + skipped;
+            let _ = self.plugin.set_sample_position(position);
+        }
+    }
+}
+
+impl<P: PluginInstance> PluginInstance for MuteSolo<P> {
+    fn initialize(&mut self, sample_rate: f64, max_block_size: usize) -> Result<()> {
+        self.plugin.initialize(sample_rate, max_block_size)
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.plugin.reset()
+    }
+
+    fn process(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        self.render(outputs, num_frames, |plugin, outputs| {
+            plugin.process(inputs, outputs, num_frames)
+        })
+    }
+
+    fn process_with_context(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        context: &BlockContext,
+    ) -> Result<()> {
+        self.render(outputs, context.num_frames, |plugin, outputs| {
+            plugin.process_with_context(inputs, outputs, context)
+        })
+    }
+
+    fn process_with_events(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        changes: &[ParameterChange],
+        midi: &[MidiEvent],
+    ) -> Result<()> {
+        self.render(outputs, num_frames, |plugin, outputs| {
+            plugin.process_with_events(inputs, outputs, num_frames, changes, midi)
+        })
+    }
+
+    fn sample_position(&self) -> u64 {
+        self.plugin.sample_position() + self.suspended.unwrap_or(0)
+    }
+
+    fn set_sample_position(&mut self, position: u64) -> Result<()> {
+        self.plugin.set_sample_position(position)?;
+        if self.suspended.is_some() {
+            self.suspended = Some(0);
+        }
+        Ok(())
+    }
+
+    fn flush_events(&mut self) -> Result<()> {
+        self.plugin.flush_events()
+    }
+
+    fn parameter_count(&self) -> usize {
+        self.plugin.parameter_count()
+    }
+
+    fn parameter_info(&self, index: usize) -> Result<ParameterInfo> {
+        self.plugin.parameter_info(index)
+    }
+
+    fn get_parameter(&self, index: usize) -> Result<f32> {
+        self.plugin.get_parameter(index)
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) -> Result<()> {
+        self.plugin.set_parameter(index, value)
+    }
+
+    fn send_midi(&mut self, events: &[MidiEvent]) -> Result<()> {
+        self.plugin.send_midi(events)
+    }
+
+    fn preset_count(&self) -> Result<usize> {
+        self.plugin.preset_count()
+    }
+
+    fn preset_info(&self, index: usize) -> Result<PresetInfo> {
+        self.plugin.preset_info(index)
+    }
+
+    fn load_preset(&mut self, preset_number: i32) -> Result<()> {
+        self.plugin.load_preset(preset_number)
+    }
+
+    fn current_preset(&self) -> Result<Option<CurrentPreset>> {
+        self.plugin.current_preset()
+    }
+
+    fn get_state(&self) -> Result<Vec<u8>> {
+        self.plugin.get_state()
+    }
+
+    fn set_state(&mut self, data: &[u8]) -> Result<()> {
+        self.plugin.set_state(data)
+    }
+
+    fn get_state_to_writer(&self, writer: &mut dyn std::io::Write) -> Result<()> {
+        self.plugin.get_state_to_writer(writer)
+    }
+
+    fn set_state_from_reader(&mut self, reader: &mut dyn std::io::Read) -> Result<()> {
+        self.plugin.set_state_from_reader(reader)
+    }
+
+    fn set_state_from_file(&mut self, path: &std::path::Path) -> Result<()> {
+        self.plugin.set_state_from_file(path)
+    }
+
+    fn take_state_dirty(&self) -> bool {
+        self.plugin.take_state_dirty()
+    }
+
+    fn info(&self) -> &PluginInfo {
+        self.plugin.info()
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.plugin.is_initialized()
+    }
+
+    fn input_channels(&self) -> usize {
+        self.plugin.input_channels()
+    }
+
+    fn output_channels(&self) -> usize {
+        self.plugin.output_channels()
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.plugin.latency_samples()
+    }
+
+    fn tail_samples(&self) -> usize {
+        self.plugin.tail_samples()
+    }
+
+    fn tail_seconds(&self) -> f64 {
+        self.plugin.tail_seconds()
+    }
+
+    fn set_bypass(&mut self, bypassed: bool) -> Result<()> {
+        self.plugin.set_bypass(bypassed)
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.plugin.is_bypassed()
+    }
+
+    fn quirks(&self) -> Quirks {
+        self.plugin.quirks()
+    }
+
+    fn plugin_meter(&self) -> Option<Vec<MeterReading>> {
+        self.plugin.plugin_meter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockPlugin;
+
+    fn run(node: &mut MuteSolo<MockPlugin>) -> Vec<f32> {
+        let input = [1.0f32; 4];
+        let mut left = [0.0f32; 4];
+        let mut right = [0.0f32; 4];
+        node.process(&[&input, &input], &mut [&mut left, &mut right], 4)
+            .unwrap();
+        left.to_vec()
+    }
+
+    #[test]
+    fn test_soft_mute_fades_and_keeps_processing() {
+        let mut node = MuteSolo::new(MockPlugin::new().with_latency(2));
+        node.initialize(48000.0, 4).unwrap();
+
+        node.set_muted(true);
+        assert_eq!(run(&mut node), vec![0.0, 0.0, 0.25, 0.0]);
+        assert_eq!(run(&mut node), vec![0.0; 4]);
+        assert_eq!(node.inner().sample_position(), 8);
+
+        // The delay line kept running, so there's no stale audio to flush
+        node.set_muted(false);
+        assert_eq!(run(&mut node), vec![0.25, 0.5, 0.75, 1.0]);
+    }
+
+    #[test]
+    fn test_solo_and_hard_mute() {
+        let group = SoloGroup::new();
+        let mut lead = MuteSolo::new(MockPlugin::new()).with_solo_group(&group);
+        let mut pad = MuteSolo::new(MockPlugin::new())
+            .with_mode(MuteMode::Hard)
+            .with_solo_group(&group);
+        lead.initialize(48000.0, 4).unwrap();
+        pad.initialize(48000.0, 4).unwrap();
+
+        lead.set_soloed(true);
+        assert!(pad.is_silenced() && !lead.is_silenced());
+        run(&mut pad); // Fade-out block
+        assert!(!pad.is_suspended());
+        assert_eq!(run(&mut pad), vec![0.0; 4]);
+        assert!(pad.is_suspended());
+        assert_eq!(pad.inner().sample_position(), 4);
+        assert_eq!(pad.sample_position(), 8);
+
+        // Dropping the soloed node ends the solo; the timeline picks up
+        drop(lead);
+        assert_eq!(group.soloed_count(), 0);
+        assert_eq!(run(&mut pad), vec![0.25, 0.5, 0.75, 1.0]);
+        assert_eq!(pad.inner().sample_position(), 12);
+    }
+}
@@ -0,0 +1,507 @@
+//! Custom DSP as a node
+//!
+//! [`FnNode`] wraps a closure as a [`PluginInstance`], so a few lines of
+//! host-side DSP (a meter tap, a channel swap, a gain ramp) can sit anywhere a
+//! plugin does without implementing the full trait. The closure receives the
+//! block's inputs and outputs, trimmed to the block length, and a
+//! [`BlockContext`] describing the block.
+//!
+//! # Examples
+//!
+//! ```
+//! use rack::node::FnNode;
+//! use rack::prelude::*;
+//!
+//! # fn main() -> rack::Result<()> {
+//! // Swap left and right
+//! let mut swap = FnNode::new("Swap", 2, 2, |inputs, outputs, _context| {
+//!     outputs[0].copy_from_slice(inputs[1]);
+//!     outputs[1].copy_from_slice(inputs[0]);
+//! });
+//! swap.initialize(48000.0, 512)?;
+//!
+//! let left = vec![1.0f32; 512];
+//! let right = vec![0.0f32; 512];
+//! let mut out_left = vec![0.0f32; 512];
+//! let mut out_right = vec![0.0f32; 512];
+//! swap.process(&[&left, &right], &mut [&mut out_left, &mut out_right], 512)?;
+//! assert_eq!(out_right[0], 1.0);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::tempo::{FrameRate, LoopRange, TimeSignature, Timecode, Transport};
+use crate::{
+    Error, MidiEvent, ParameterChange, ParameterInfo, PluginInfo, PluginInstance, PluginType,
+    PresetInfo, Result,
+};
+use smallvec::SmallVec;
+use std::path::PathBuf;
+
+/// Tempo reported by [`BlockContext::new()`], in BPM
+pub const DEFAULT_TEMPO: f64 = 120.0;
+
+/// What a node knows about the block it is processing
+///
+/// Passed to [`PluginInstance::process_with_context()`]. Build one per block
+/// from the host's [`Transport`] with [`from_transport()`](Self::from_transport),
+/// or with [`new()`](Self::new) when there is no song timeline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct BlockContext {
+    /// Sample rate in Hz
+    pub sample_rate: f64,
+
+    /// Timeline position of the block's first sample
+    pub sample_position: u64,
+
+    /// Number of frames in the block
+    pub num_frames: usize,
+
+    /// Whether the transport is playing
+    pub playing: bool,
+
+    /// Tempo at the block's first sample, in BPM
+    pub tempo: f64,
+
+    /// Time signature at the block's first sample
+    pub time_signature: TimeSignature,
+
+    /// Musical position of the block's first sample, in quarter notes
+    pub beat: f64,
+
+    /// Musical position of the first beat of the bar the block starts in,
+    /// in quarter notes
+    pub bar_start: f64,
+
+    /// Index of the bar the block starts in, counting from 0
+    pub bar: i64,
+
+    /// Loop the host is playing, in quarter notes
+    pub loop_range: Option<LoopRange>,
+
+    /// Frame rate of the picture, for timecode
+    pub frame_rate: FrameRate,
+
+    /// Timecode at the start of the song (sample position 0)
+    pub smpte_offset: Timecode,
+
+    /// Whether the block is rendered offline rather than in real time
+    ///
+    /// Offline renders may take longer than real time, so plugins can use
+    /// higher-quality processing.
+    pub is_offline: bool,
+}
+
+impl BlockContext {
+    /// Context for a block of `num_frames` frames starting at `sample_position`
+    ///
+    /// Describes a stopped transport at [`DEFAULT_TEMPO`] in 4/4, rendering in
+    /// real time, with the song starting at timecode 00:00:00:00 at the
+    /// default frame rate.
+    pub fn new(sample_rate: f64, sample_position: u64, num_frames: usize) -> Self {
+        // Quarter notes per bar of 4/4
+        const BEATS_PER_BAR: f64 = 4.0;
+        let beat = if sample_rate > 0.0 {
+            sample_position as f64 / sample_rate * DEFAULT_TEMPO / 60.0
+        } else {
+            0.0
+        };
+        let bar = (beat / BEATS_PER_BAR).floor();
+        Self {
+            sample_rate,
+            sample_position,
+            num_frames,
+            playing: false,
+            tempo: DEFAULT_TEMPO,
+            time_signature: TimeSignature::default(),
+            beat,
+            bar_start: bar * BEATS_PER_BAR,
+            bar: bar as i64,
+            loop_range: None,
+            frame_rate: FrameRate::default(),
+            smpte_offset: Timecode::default(),
+            is_offline: false,
+        }
+    }
+
+    /// Context for the block of `num_frames` frames at the transport's
+    /// current position
+    pub fn from_transport(transport: &Transport, num_frames: usize) -> Self {
+        let beat = transport.beat();
+        Self {
+            sample_rate: transport.sample_rate,
+            sample_position: transport.position,
+            num_frames,
+            playing: transport.playing,
+            tempo: transport.tempo_map.tempo_at(beat),
+            time_signature: transport.tempo_map.time_signature_at(beat),
+            beat,
+            bar_start: transport.bar_start(),
+            // Tolerate rounding when the position is on a downbeat
+            bar: (transport.bar() + 1e-9).floor() as i64,
+            loop_range: transport.loop_range,
+            frame_rate: transport.frame_rate,
+            smpte_offset: transport.smpte_offset,
+            is_offline: false,
+        }
+    }
+
+    /// Timecode of the block's first sample
+    pub fn timecode(&self) -> Timecode {
+        let seconds = if self.sample_rate > 0.0 {
+            self.sample_position as f64 / self.sample_rate
+        } else {
+            0.0
+        };
+        Timecode::from_seconds(
+            self.smpte_offset.to_seconds(self.frame_rate) + seconds,
+            self.frame_rate,
+        )
+    }
+
+    /// Mark the block as rendered offline, or in real time
+    pub fn offline(mut self, is_offline: bool) -> Self {
+        self.is_offline = is_offline;
+        self
+    }
+}
+
+/// A closure processing audio as a [`PluginInstance`]
+///
+/// The node has a fixed number of input and output channels, no parameters
+/// and no presets, ignores MIDI, and has no state. The closure is only called
+/// once the channel counts and buffer lengths have been checked, and
+/// only sees the first `num_frames` samples of each buffer. Nodes without
+/// inputs report themselves as instruments, like other sources.
+pub struct FnNode<F> {
+    process: F,
+    info: PluginInfo,
+    inputs: usize,
+    outputs: usize,
+    sample_rate: f64,
+    initialized: bool,
+    max_block_size: usize,
+    sample_position: u64,
+}
+
+impl<F> FnNode<F>
+where
+    F: FnMut(&[&[f32]], &mut [&mut [f32]], &BlockContext) + Send,
+{
+    /// Create a node named `name` with `inputs` input and `outputs` output
+    /// channels, processing with `process`
+    pub fn new(name: impl Into<String>, inputs: usize, outputs: usize, process: F) -> Self {
+        let name = name.into();
+        let plugin_type = if inputs == 0 {
+            PluginType::Instrument
+        } else {
+            PluginType::Effect
+        };
+        let info = PluginInfo::new(
+            name.clone(),
+            "Rack".to_string(),
+            1,
+            plugin_type,
+            PathBuf::new(),
+            format!("rack.fn.{}", name),
+        );
+        Self {
+            process,
+            info,
+            inputs,
+            outputs,
+            sample_rate: 0.0,
+            initialized: false,
+            max_block_size: 0,
+            sample_position: 0,
+        }
+    }
+}
+
+impl<F> PluginInstance for FnNode<F>
+where
+    F: FnMut(&[&[f32]], &mut [&mut [f32]], &BlockContext) + Send,
+{
+    fn initialize(&mut self, sample_rate: f64, max_block_size: usize) -> Result<()> {
+        if !(sample_rate.is_finite() && sample_rate > 0.0) {
+            return Err(Error::Other(format!(
+                "Invalid sample rate: {}",
+                sample_rate
+            )));
+        }
+        self.sample_rate = sample_rate;
+        self.max_block_size = max_block_size;
+        self.initialized = true;
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn process(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        let context = BlockContext::new(self.sample_rate, self.sample_position, num_frames);
+        self.process_with_context(inputs, outputs, &context)
+    }
+
+    /// Run the closure with `context` as given
+    ///
+    /// The node's sample position moves to the end of the block.
+    fn process_with_context(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        context: &BlockContext,
+    ) -> Result<()> {
+        let num_frames = context.num_frames;
+        if !self.initialized {
+            return Err(Error::NotInitialized);
+        }
+        if num_frames > self.max_block_size {
+            return Err(Error::BlockTooLarge {
+                max: self.max_block_size,
+                got: num_frames,
+            });
+        }
+        if inputs.len() != self.inputs {
+            return Err(Error::Other(format!(
+                "Input channel count mismatch: plugin expects {}, got {}",
+                self.inputs,
+                inputs.len()
+            )));
+        }
+        if outputs.len() != self.outputs {
+            return Err(Error::Other(format!(
+                "Output channel count mismatch: plugin expects {}, got {}",
+                self.outputs,
+                outputs.len()
+            )));
+        }
+        if inputs.iter().any(|input| input.len() < num_frames)
+            || outputs.iter().any(|output| output.len() < num_frames)
+        {
+            return Err(Error::Other(format!(
+                "Buffers must hold at least {} samples",
+                num_frames
+            )));
+        }
+
+        let inputs: SmallVec<[&[f32]; 8]> =
+            inputs.iter().map(|input| &input[..num_frames]).collect();
+        let mut outputs: SmallVec<[&mut [f32]; 8]> = outputs
+            .iter_mut()
+            .map(|output| &mut output[..num_frames])
+            .collect();
+        (self.process)(&inputs, &mut outputs, context);
+
+        self.sample_position = context.sample_position + num_frames as u64;
+        Ok(())
+    }
+
+    /// Run the closure once over the whole block
+    ///
+    /// The node has no parameters, so any change is rejected; MIDI is ignored.
+    fn process_with_events(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        changes: &[ParameterChange],
+        _midi: &[MidiEvent],
+    ) -> Result<()> {
+        if let Some(change) = changes.first() {
+            return Err(Error::InvalidParameter(change.index));
+        }
+        self.process(inputs, outputs, num_frames)
+    }
+
+    fn sample_position(&self) -> u64 {
+        self.sample_position
+    }
+
+    fn set_sample_position(&mut self, position: u64) -> Result<()> {
+        self.sample_position = position;
+        Ok(())
+    }
+
+    /// Check the node is initialized; there is nothing to deliver, and the
+    /// closure isn't called
+    fn flush_events(&mut self) -> Result<()> {
+        if !self.initialized {
+            return Err(Error::NotInitialized);
+        }
+        Ok(())
+    }
+
+    fn parameter_count(&self) -> usize {
+        0
+    }
+
+    fn parameter_info(&self, index: usize) -> Result<ParameterInfo> {
+        Err(Error::InvalidParameter(index))
+    }
+
+    fn get_parameter(&self, index: usize) -> Result<f32> {
+        Err(Error::InvalidParameter(index))
+    }
+
+    fn set_parameter(&mut self, index: usize, _value: f32) -> Result<()> {
+        Err(Error::InvalidParameter(index))
+    }
+
+    fn send_midi(&mut self, _events: &[MidiEvent]) -> Result<()> {
+        Ok(())
+    }
+
+    fn preset_count(&self) -> Result<usize> {
+        Ok(0)
+    }
+
+    fn preset_info(&self, index: usize) -> Result<PresetInfo> {
+        Err(Error::Other(format!("Invalid preset index: {}", index)))
+    }
+
+    fn load_preset(&mut self, preset_number: i32) -> Result<()> {
+        Err(Error::Other(format!(
+            "Invalid preset number: {}",
+            preset_number
+        )))
+    }
+
+    fn get_state(&self) -> Result<Vec<u8>> {
+        Ok(Vec::new())
+    }
+
+    fn set_state(&mut self, _data: &[u8]) -> Result<()> {
+        Ok(())
+    }
+
+    fn info(&self) -> &PluginInfo {
+        &self.info
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    fn input_channels(&self) -> usize {
+        if self.initialized {
+            self.inputs
+        } else {
+            0
+        }
+    }
+
+    fn output_channels(&self) -> usize {
+        if self.initialized {
+            self.outputs
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tempo::TempoMap;
+
+    #[test]
+    fn test_fn_node_sees_block_context() {
+        let mut seen = Vec::new();
+        let mut node = FnNode::new("Half", 1, 1, |inputs, outputs, context| {
+            for (out, sample) in outputs[0].iter_mut().zip(inputs[0]) {
+                *out = sample * 0.5;
+            }
+            seen.push((
+                context.sample_position,
+                context.num_frames,
+                outputs[0].len(),
+            ));
+        });
+        assert!(matches!(
+            node.process(&[], &mut [], 0),
+            Err(Error::NotInitialized)
+        ));
+        assert!(matches!(node.flush_events(), Err(Error::NotInitialized)));
+        node.initialize(48000.0, 64).unwrap();
+        assert_eq!(node.info().plugin_type, PluginType::Effect);
+
+        let input = vec![1.0f32; 64];
+        let mut output = vec![0.0f32; 64];
+        node.process(&[&input], &mut [&mut output], 64).unwrap();
+        node.process(&[&input], &mut [&mut output], 16).unwrap();
+        assert!(node
+            .process(&[&input, &input], &mut [&mut output], 16)
+            .is_err());
+        assert!(matches!(
+            node.process(&[&input], &mut [&mut output], 65),
+            Err(Error::BlockTooLarge { max: 64, got: 65 })
+        ));
+        assert_eq!(output[0], 0.5);
+
+        // MIDI is ignored and the block isn't split; changes are rejected
+        let midi = [MidiEvent::note_on(60, 100, 0, 8)];
+        node.process_with_events(&[&input], &mut [&mut output], 32, &[], &midi)
+            .unwrap();
+        let changes = [ParameterChange::new(0, 1.0, 8)];
+        assert!(matches!(
+            node.process_with_events(&[&input], &mut [&mut output], 32, &changes, &[]),
+            Err(Error::InvalidParameter(0))
+        ));
+        node.flush_events().unwrap();
+        drop(node);
+        assert_eq!(seen, vec![(0, 64, 64), (64, 16, 16), (80, 32, 32)]);
+    }
+
+    #[test]
+    fn test_block_context_from_transport() {
+        let mut map = TempoMap::new(90.0);
+        map.set_time_signature(0.0, TimeSignature::new(6, 8));
+        let mut transport = Transport::new(48000.0, map);
+        transport.playing = true;
+        transport.seek(64000); // 2 beats at 90 BPM
+        transport.loop_range = Some(LoopRange::new(0.0, 12.0));
+        transport.frame_rate = FrameRate::Fps24;
+        transport.smpte_offset = Timecode::new(1, 0, 0, 0);
+
+        let context = BlockContext::from_transport(&transport, 32).offline(true);
+        assert_eq!(context.sample_position, 64000);
+        assert_eq!(context.tempo, 90.0);
+        assert_eq!(context.time_signature, TimeSignature::new(6, 8));
+        assert!((context.beat - 2.0).abs() < 1e-9);
+        assert!(context.playing && context.is_offline);
+        assert_eq!(context.bar_start, 0.0);
+        assert_eq!(context.loop_range, Some(LoopRange::new(0.0, 12.0)));
+        assert_eq!(context.frame_rate, FrameRate::Fps24);
+        assert_eq!(context.timecode(), transport.timecode());
+        assert_eq!(context.timecode().hours, 1);
+        transport.seek(128000); // 6/8 bars are 3 quarter notes long
+        let next_bar = BlockContext::from_transport(&transport, 32);
+        assert!((next_bar.bar_start - 3.0).abs() < 1e-9);
+        assert_eq!((context.bar, next_bar.bar), (0, 1));
+
+        let mut seen = None;
+        let mut node = FnNode::new("Tap", 0, 1, |_, _, context| seen = Some(*context));
+        node.initialize(48000.0, 32).unwrap();
+        let mut output = vec![0.0f32; 32];
+        node.process_with_context(&[], &mut [&mut output], &context)
+            .unwrap();
+        assert_eq!(node.sample_position(), 64032);
+        drop(node);
+        assert_eq!(seen, Some(context));
+
+        let default = BlockContext::new(48000.0, 24000, 32);
+        assert_eq!(default.beat, 1.0);
+        assert_eq!(default.bar_start, 0.0);
+        let second_bar = BlockContext::new(48000.0, 120000, 32);
+        assert_eq!((second_bar.bar_start, second_bar.bar), (4.0, 1));
+        assert!(!default.playing && !default.is_offline);
+    }
+}
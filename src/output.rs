@@ -0,0 +1,412 @@
+//! Audio output that survives device changes
+//!
+//! A cpal stream built on one device errors out when that device is unplugged
+//! and keeps playing on it when the user picks another default output. An
+//! [`OutputStream`] watches for both: when its device goes away or the default
+//! device changes, it stops the stream, opens the new device, calls
+//! [`OutputRenderer::configure()`] if the sample rate or channel count
+//! changed (so plugins can be re-initialized), and starts again. Each switch
+//! is reported as [`HostEvent::DeviceLost`] and [`HostEvent::DeviceChanged`].
+//!
+//! The renderer is never called while no device is open.
+//!
+//! Requires the `cpal` feature.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::output::{OutputConfig, OutputRenderer, OutputStream};
+//! struct Synth<P: PluginInstance> {
+//!     plugin: P,
+//! }
+//!
+//! impl<P: PluginInstance + Send + 'static> OutputRenderer for Synth<P> {
+//!     fn configure(&mut self, sample_rate: f64, max_block_size: usize, _channels: usize) -> Result<()> {
+//!         self.plugin.initialize(sample_rate, max_block_size)
+//!     }
+//!
+//!     fn render(&mut self, outputs: &mut [&mut [f32]], num_frames: usize) {
+//!         if self.plugin.process(&[], outputs, num_frames).is_err() {
+//!             outputs.iter_mut().for_each(|out| out.fill(0.0));
+//!         }
+//!     }
+//! }
+//!
+//! # fn example<P: PluginInstance + Send + 'static>(plugin: P) -> Result<()> {
+//! let stream = OutputStream::start(OutputConfig::default(), Synth { plugin })?;
+//! if let Some(device) = stream.device() {
+//!     println!("playing on {} at {} Hz", device.name, device.sample_rate);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::events::{self, HostEvent};
+use crate::guard::{catch_audio_panic, PanicPolicy};
+use crate::split::BlockSplitting;
+use crate::{Error, Result};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use smallvec::SmallVec;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Audio source played by an [`OutputStream`]
+pub trait OutputRenderer: Send + 'static {
+    /// Prepare for a device
+    ///
+    /// Called on the stream's device thread before the first device is opened
+    /// and again whenever a device with a different sample rate or channel
+    /// count replaces it, never while audio is running. If it fails, the
+    /// device isn't started and is retried at the next poll.
+    fn configure(&mut self, sample_rate: f64, max_block_size: usize, channels: usize)
+        -> Result<()>;
+
+    /// Fill one planar buffer per device channel with `num_frames` samples
+    ///
+    /// Called on the audio thread, with `num_frames` at most the configured
+    /// `max_block_size`.
+    fn render(&mut self, outputs: &mut [&mut [f32]], num_frames: usize);
+}
+
+/// Device selection and timing for an [`OutputStream`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputConfig {
+    /// Name of the preferred device
+    ///
+    /// `None` follows the system default output.
+    pub device: Option<String>,
+
+    /// Play on the default output while the named device is missing
+    ///
+    /// If false, the stream stays silent until the named device comes back.
+    pub fallback_to_default: bool,
+
+    /// Largest block passed to [`OutputRenderer::render()`]; device buffers
+    /// are split into blocks of at most this size
+    pub block_size: usize,
+
+    /// How device buffers are split into blocks
+    ///
+    /// [`BlockSplitting::Adaptive`] renders smaller, equal blocks for more
+    /// even CPU load and faster parameter changes, at some cost in
+    /// throughput.
+    pub splitting: BlockSplitting,
+
+    /// How often to check whether the default (or preferred) device changed
+    pub poll_interval: Duration,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            device: None,
+            fallback_to_default: true,
+            block_size: 512,
+            splitting: BlockSplitting::Fixed,
+            poll_interval: Duration::from_millis(500),
+        }
+    }
+}
+
+/// The device an [`OutputStream`] is playing on
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputDevice {
+    /// Device name
+    pub name: String,
+
+    /// Sample rate in Hz
+    pub sample_rate: f64,
+
+    /// Number of output channels
+    pub channels: usize,
+}
+
+/// Messages to the device thread
+enum Signal {
+    /// A stream error, from the stream's error callback
+    StreamError(cpal::StreamError),
+    Stop,
+}
+
+/// A cpal output stream that follows device changes
+///
+/// Dropping it stops the stream like [`stop()`](Self::stop).
+pub struct OutputStream {
+    device: Arc<Mutex<Option<OutputDevice>>>,
+    signal: mpsc::Sender<Signal>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl OutputStream {
+    /// Open the configured device and start playing `renderer`
+    ///
+    /// The stream lives on a "rack-output" thread, which also watches for
+    /// device changes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no output device can be opened, `configure()`
+    /// fails for it, or the thread can't be started
+    pub fn start<R: OutputRenderer>(config: OutputConfig, renderer: R) -> Result<Self> {
+        let device = Arc::new(Mutex::new(None));
+        let (signal_tx, signal_rx) = mpsc::channel();
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        let thread_device = Arc::clone(&device);
+        let thread_signal = signal_tx.clone();
+        let thread = std::thread::Builder::new()
+            .name("rack-output".to_string())
+            .spawn(move || {
+                let mut watcher = Watcher {
+                    host: cpal::default_host(),
+                    config,
+                    renderer: Arc::new(Mutex::new(renderer)),
+                    configured: None,
+                    active: None,
+                    failed: None,
+                    device: thread_device,
+                    signal: thread_signal,
+                };
+                match watcher.open() {
+                    Ok(()) => {
+                        let _ = ready_tx.send(Ok(()));
+                    }
+                    Err(error) => {
+                        let _ = ready_tx.send(Err(error));
+                        return;
+                    }
+                }
+                watcher.run(signal_rx);
+            })?;
+
+        let opened = ready_rx
+            .recv()
+            .unwrap_or_else(|_| Err(Error::Other("Output thread exited".to_string())));
+        // On failure the thread has already exited
+        opened.map(|()| Self {
+            device,
+            signal: signal_tx,
+            thread: Some(thread),
+        })
+    }
+
+    /// The device currently playing, or `None` while switching or waiting
+    /// for a device to appear
+    pub fn device(&self) -> Option<OutputDevice> {
+        self.device
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Stop playing and close the device
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        let _ = self.signal.send(Signal::Stop);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for OutputStream {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// A running stream and the device it plays on
+struct Active {
+    // Streams aren't `Send`, so this lives and dies on the device thread
+    _stream: cpal::Stream,
+    device: OutputDevice,
+}
+
+/// State of the device thread
+struct Watcher<R: OutputRenderer> {
+    host: cpal::Host,
+    config: OutputConfig,
+    renderer: Arc<Mutex<R>>,
+    /// Sample rate and channel count the renderer was configured for
+    configured: Option<(f64, usize)>,
+    active: Option<Active>,
+    /// Device that last failed to open, so the failure is reported once
+    failed: Option<String>,
+    device: Arc<Mutex<Option<OutputDevice>>>,
+    signal: mpsc::Sender<Signal>,
+}
+
+impl<R: OutputRenderer> Watcher<R> {
+    /// Handle stream errors and poll for device changes until stopped
+    fn run(&mut self, signal: mpsc::Receiver<Signal>) {
+        loop {
+            match signal.recv_timeout(self.config.poll_interval) {
+                Ok(Signal::Stop) | Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                Ok(Signal::StreamError(cpal::StreamError::DeviceNotAvailable)) => {
+                    self.close();
+                }
+                Ok(Signal::StreamError(error)) => {
+                    let error = Error::Other(format!("Output stream error: {}", error));
+                    events::emit(HostEvent::Error {
+                        info: None,
+                        error: &error,
+                    });
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+            }
+
+            let wanted = self.wanted_name();
+            let current = self.active.as_ref().map(|a| a.device.name.as_str());
+            if wanted.is_some() && wanted.as_deref() != current {
+                self.close();
+                if let Err(error) = self.open() {
+                    self.report_failure(wanted, &error);
+                }
+            }
+        }
+        // Stopping isn't a device change, so report nothing
+        self.active = None;
+        *self.device.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Name of the device that should be playing: the preferred one if
+    /// present, the default otherwise (if falling back)
+    fn wanted_name(&self) -> Option<String> {
+        self.pick().and_then(|device| device.name().ok())
+    }
+
+    fn pick(&self) -> Option<cpal::Device> {
+        if let Some(preferred) = &self.config.device {
+            let found = self.host.output_devices().ok().and_then(|mut devices| {
+                devices.find(|d| d.name().ok().as_deref() == Some(preferred.as_str()))
+            });
+            if found.is_some() || !self.config.fallback_to_default {
+                return found;
+            }
+        }
+        self.host.default_output_device()
+    }
+
+    /// Stop the current stream, if any, reporting the device as lost
+    fn close(&mut self) {
+        if let Some(active) = self.active.take() {
+            *self.device.lock().unwrap_or_else(|e| e.into_inner()) = None;
+            // Stop the stream before reporting, so the renderer is idle
+            let name = active.device.name.clone();
+            drop(active);
+            events::emit(HostEvent::DeviceLost { name: &name });
+        }
+    }
+
+    /// Open the device that should be playing and start it
+    fn open(&mut self) -> Result<()> {
+        let device = self
+            .pick()
+            .ok_or_else(|| Error::Other("No output device available".to_string()))?;
+        let name = device.name().unwrap_or_default();
+        let supported = device.default_output_config().map_err(|e| {
+            Error::Other(format!("Failed to get output config for {}: {}", name, e))
+        })?;
+        let sample_rate = supported.sample_rate().0 as f64;
+        let channels = supported.channels() as usize;
+
+        if self.configured != Some((sample_rate, channels)) {
+            self.renderer
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .configure(sample_rate, self.config.block_size, channels)?;
+            self.configured = Some((sample_rate, channels));
+        }
+
+        let config: cpal::StreamConfig = supported.config();
+        let stream = match supported.sample_format() {
+            cpal::SampleFormat::F32 => self.build::<f32>(&device, &config),
+            cpal::SampleFormat::F64 => self.build::<f64>(&device, &config),
+            cpal::SampleFormat::I16 => self.build::<i16>(&device, &config),
+            cpal::SampleFormat::I32 => self.build::<i32>(&device, &config),
+            cpal::SampleFormat::U16 => self.build::<u16>(&device, &config),
+            format => Err(Error::Other(format!(
+                "Unsupported sample format {:?} on {}",
+                format, name
+            ))),
+        }?;
+        stream
+            .play()
+            .map_err(|e| Error::Other(format!("Failed to play stream on {}: {}", name, e)))?;
+
+        let info = OutputDevice {
+            name,
+            sample_rate,
+            channels,
+        };
+        *self.device.lock().unwrap_or_else(|e| e.into_inner()) = Some(info.clone());
+        events::emit(HostEvent::DeviceChanged {
+            name: &info.name,
+            sample_rate,
+            channels,
+        });
+        self.failed = None;
+        self.active = Some(Active {
+            _stream: stream,
+            device: info,
+        });
+        Ok(())
+    }
+
+    fn build<T>(&self, device: &cpal::Device, config: &cpal::StreamConfig) -> Result<cpal::Stream>
+    where
+        T: cpal::SizedSample + cpal::FromSample<f32>,
+    {
+        let renderer = Arc::clone(&self.renderer);
+        let channels = (config.channels as usize).max(1);
+        let block_size = self.config.block_size.max(1);
+        let splitting = self.config.splitting;
+        let mut buffers = vec![vec![0.0f32; block_size]; channels];
+        let signal = self.signal.clone();
+
+        device
+            .build_output_stream(
+                config,
+                move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                    // A panic unwinding into cpal would abort the process
+                    let rendered = catch_audio_panic(PanicPolicy::Silence, None, || {
+                        let mut renderer = renderer.lock().unwrap_or_else(|e| e.into_inner());
+                        for block in splitting.blocks(data.len() / channels, block_size) {
+                            let frames = block.len();
+                            let chunk = &mut data[block.start * channels..block.end * channels];
+                            let mut outputs: SmallVec<[&mut [f32]; 8]> =
+                                buffers.iter_mut().map(|b| &mut b[..frames]).collect();
+                            renderer.render(&mut outputs, frames);
+                            drop(outputs);
+                            for (frame, samples) in chunk.chunks_mut(channels).enumerate() {
+                                for (sample, buffer) in samples.iter_mut().zip(&buffers) {
+                                    *sample = cpal::Sample::from_sample(buffer[frame]);
+                                }
+                            }
+                        }
+                    });
+                    if rendered.is_none() {
+                        data.fill(T::EQUILIBRIUM);
+                    }
+                },
+                move |error| {
+                    let _ = signal.send(Signal::StreamError(error));
+                },
+                None,
+            )
+            .map_err(|e| Error::Other(format!("Failed to build output stream: {}", e)))
+    }
+
+    /// Report that `name` couldn't be opened, once until something opens
+    fn report_failure(&mut self, name: Option<String>, error: &Error) {
+        if self.failed != name {
+            events::emit(HostEvent::Error { info: None, error });
+            self.failed = name;
+        }
+    }
+}
@@ -0,0 +1,399 @@
+//! Typed parameter values
+//!
+//! Plugin parameters travel through the API as normalized values (0.0 to 1.0),
+//! while hosts usually want to display and edit them in the plugin's own units
+//! ("plain" values such as Hz or dB). Mixing the two up is an easy mistake to
+//! make when everything is an `f32`, so [`Normalized`] and [`Plain`] make the
+//! distinction explicit. Conversions between them go through the parameter's
+//! [`ParameterInfo`], which knows the range and the display curve.
+//!
+//! # Examples
+//!
+//! ```
+//! use rack::{Normalized, ParameterInfo, Plain};
+//!
+//! let cutoff = ParameterInfo::new(0, "Cutoff".to_string(), 20.0, 20000.0, 1000.0, "Hz".to_string());
+//!
+//! let plain = cutoff.to_plain(Normalized::new(0.5));
+//! assert_eq!(plain, Plain(10010.0));
+//!
+//! let normalized = cutoff.to_normalized(Plain(20.0));
+//! assert_eq!(normalized.value(), 0.0);
+//! ```
+
+use crate::ParameterInfo;
+use std::f32::consts::E;
+
+/// A parameter value in the normalized 0.0 to 1.0 range
+///
+/// This is the representation used by [`PluginInstance::get_parameter`] and
+/// [`PluginInstance::set_parameter`](crate::PluginInstance::set_parameter).
+///
+/// [`PluginInstance::get_parameter`]: crate::PluginInstance::get_parameter
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Normalized(f32);
+
+impl Normalized {
+    /// The minimum normalized value
+    pub const MIN: Normalized = Normalized(0.0);
+
+    /// The maximum normalized value
+    pub const MAX: Normalized = Normalized(1.0);
+
+    /// Create a normalized value, clamping it to 0.0 to 1.0
+    ///
+    /// NaN is mapped to 0.0.
+    pub fn new(value: f32) -> Self {
+        if value.is_nan() {
+            Self(0.0)
+        } else {
+            Self(value.clamp(0.0, 1.0))
+        }
+    }
+
+    /// Get the raw normalized value
+    pub fn value(self) -> f32 {
+        self.0
+    }
+}
+
+impl From<Normalized> for f32 {
+    fn from(value: Normalized) -> Self {
+        value.0
+    }
+}
+
+/// A parameter value in the plugin's own units (e.g., Hz, dB, %)
+///
+/// Plain values are what [`ParameterInfo::min`], [`ParameterInfo::max`] and
+/// [`ParameterInfo::default`] are expressed in.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Plain(pub f32);
+
+impl Plain {
+    /// Get the raw plain value
+    pub fn value(self) -> f32 {
+        self.0
+    }
+}
+
+impl From<Plain> for f32 {
+    fn from(value: Plain) -> Self {
+        value.0
+    }
+}
+
+/// A parameter edit made in a plugin's own editor
+///
+/// Editors report each gesture as a `Begin`, any number of `Change`s and an
+/// `End`, so a host can record the whole gesture as one automation pass or
+/// undo step. Parameters are identified by the same index as
+/// [`PluginInstance::set_parameter`](crate::PluginInstance::set_parameter).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParameterEdit {
+    /// The user grabbed the parameter's control
+    Begin {
+        /// Parameter index
+        index: usize,
+    },
+
+    /// The parameter changed during the gesture
+    Change {
+        /// Parameter index
+        index: usize,
+        /// New value
+        value: Normalized,
+    },
+
+    /// The user released the parameter's control
+    End {
+        /// Parameter index
+        index: usize,
+    },
+}
+
+impl ParameterEdit {
+    /// Index of the edited parameter
+    pub fn index(&self) -> usize {
+        match *self {
+            ParameterEdit::Begin { index }
+            | ParameterEdit::Change { index, .. }
+            | ParameterEdit::End { index } => index,
+        }
+    }
+}
+
+/// A parameter change at a sample offset within a block, for
+/// [`PluginInstance::process_with_events()`](crate::PluginInstance::process_with_events)
+///
+/// Unlike [`set_parameter()`](crate::PluginInstance::set_parameter) between
+/// blocks, which moves the parameter in steps of a whole block (audible as
+/// zipper noise on gain or filter sweeps), a change takes effect at the given
+/// frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParameterChange {
+    /// Parameter index
+    pub index: usize,
+    /// New value (normalized 0.0 to 1.0)
+    pub value: f32,
+    /// Frame of the block at which the value takes effect
+    pub sample_offset: u32,
+}
+
+impl ParameterChange {
+    /// Create a change of parameter `index` to `value` at `sample_offset`
+    pub fn new(index: usize, value: f32, sample_offset: u32) -> Self {
+        Self {
+            index,
+            value,
+            sample_offset,
+        }
+    }
+}
+
+/// Display curve of a parameter
+///
+/// Describes how a linear control (slider, knob) should map onto the
+/// parameter's range so that e.g. frequency parameters feel right. The curve
+/// only affects how hosts present the parameter; the values sent to the plugin
+/// are always [`Normalized`] or [`Plain`].
+///
+/// AudioUnits report this through their `kAudioUnitParameterFlag_Display*`
+/// flags. VST3 plugins apply any skew internally to their normalized values,
+/// so their parameters are reported as [`ParameterCurve::Linear`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParameterCurve {
+    /// Linear mapping
+    #[default]
+    Linear,
+
+    /// Logarithmic mapping (e.g., frequency). Falls back to linear when the
+    /// range does not lie entirely above zero.
+    Logarithmic,
+
+    /// Exponential mapping (inverse of logarithmic)
+    Exponential,
+
+    /// Control position is the square of the value
+    Squared,
+
+    /// Control position is the square root of the value
+    SquareRoot,
+
+    /// Control position is the cube of the value
+    Cubed,
+
+    /// Control position is the cube root of the value
+    CubeRoot,
+}
+
+impl ParameterInfo {
+    /// Convert a normalized value to a plain value in this parameter's range
+    pub fn to_plain(&self, value: Normalized) -> Plain {
+        Plain(self.min + value.0 * (self.max - self.min))
+    }
+
+    /// Convert a plain value to a normalized value
+    ///
+    /// Values outside the parameter's range are clamped. A parameter with an
+    /// empty range (min == max) always maps to 0.0.
+    pub fn to_normalized(&self, value: Plain) -> Normalized {
+        let range = self.max - self.min;
+        if range == 0.0 {
+            return Normalized::MIN;
+        }
+        Normalized::new((value.0 - self.min) / range)
+    }
+
+    /// Get the default value as a plain value
+    pub fn default_plain(&self) -> Plain {
+        Plain(self.default)
+    }
+
+    /// Get the default value as a normalized value
+    pub fn default_normalized(&self) -> Normalized {
+        self.to_normalized(self.default_plain())
+    }
+
+    /// Set the display curve
+    pub fn with_curve(mut self, curve: ParameterCurve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    /// Convert a control position (0.0 to 1.0) to a normalized value using the
+    /// parameter's display curve
+    pub fn position_to_normalized(&self, position: f32) -> Normalized {
+        let p = Normalized::new(position).value();
+        let value = match self.curve {
+            ParameterCurve::Linear => p,
+            ParameterCurve::Logarithmic => match self.log_range() {
+                Some((min, max)) => {
+                    let plain = min * (max / min).powf(p);
+                    return self.to_normalized(Plain(plain));
+                }
+                None => p,
+            },
+            ParameterCurve::Exponential => (1.0 + p * (E - 1.0)).ln(),
+            ParameterCurve::Squared => p.sqrt(),
+            ParameterCurve::SquareRoot => p * p,
+            ParameterCurve::Cubed => p.cbrt(),
+            ParameterCurve::CubeRoot => p * p * p,
+        };
+        Normalized::new(value)
+    }
+
+    /// Convert a normalized value to a control position (0.0 to 1.0) using the
+    /// parameter's display curve
+    ///
+    /// This is the inverse of [`position_to_normalized()`](Self::position_to_normalized).
+    pub fn normalized_to_position(&self, value: Normalized) -> f32 {
+        let n = value.value();
+        let position = match self.curve {
+            ParameterCurve::Linear => n,
+            ParameterCurve::Logarithmic => match self.log_range() {
+                Some((min, max)) => {
+                    let plain = self.to_plain(value).value().max(min);
+                    (plain / min).ln() / (max / min).ln()
+                }
+                None => n,
+            },
+            ParameterCurve::Exponential => (n.exp() - 1.0) / (E - 1.0),
+            ParameterCurve::Squared => n * n,
+            ParameterCurve::SquareRoot => n.sqrt(),
+            ParameterCurve::Cubed => n * n * n,
+            ParameterCurve::CubeRoot => n.cbrt(),
+        };
+        position.clamp(0.0, 1.0)
+    }
+
+    /// Convert a control position (0.0 to 1.0) to a plain value
+    pub fn position_to_plain(&self, position: f32) -> Plain {
+        self.to_plain(self.position_to_normalized(position))
+    }
+
+    /// Convert a plain value to a control position (0.0 to 1.0)
+    pub fn plain_to_position(&self, value: Plain) -> f32 {
+        self.normalized_to_position(self.to_normalized(value))
+    }
+
+    /// Range usable for logarithmic mapping (strictly positive and non-empty)
+    fn log_range(&self) -> Option<(f32, f32)> {
+        if self.min > 0.0 && self.max > self.min {
+            Some((self.min, self.max))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gain_param() -> ParameterInfo {
+        ParameterInfo::new(0, "Gain".to_string(), -60.0, 12.0, 0.0, "dB".to_string())
+    }
+
+    #[test]
+    fn test_normalized_clamps() {
+        assert_eq!(Normalized::new(-0.5).value(), 0.0);
+        assert_eq!(Normalized::new(1.5).value(), 1.0);
+        assert_eq!(Normalized::new(0.25).value(), 0.25);
+        assert_eq!(Normalized::new(f32::NAN).value(), 0.0);
+    }
+
+    #[test]
+    fn test_to_plain() {
+        let param = gain_param();
+        assert_eq!(param.to_plain(Normalized::MIN), Plain(-60.0));
+        assert_eq!(param.to_plain(Normalized::MAX), Plain(12.0));
+        assert_eq!(param.to_plain(Normalized::new(0.5)), Plain(-24.0));
+    }
+
+    #[test]
+    fn test_to_normalized() {
+        let param = gain_param();
+        assert_eq!(param.to_normalized(Plain(-60.0)), Normalized::MIN);
+        assert_eq!(param.to_normalized(Plain(12.0)), Normalized::MAX);
+        assert_eq!(param.to_normalized(Plain(100.0)), Normalized::MAX);
+        assert_eq!(param.default_normalized().value(), 60.0 / 72.0);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let param = gain_param();
+        for i in 0..=10 {
+            let normalized = Normalized::new(i as f32 / 10.0);
+            let back = param.to_normalized(param.to_plain(normalized));
+            assert!((back.value() - normalized.value()).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_linear_curve_is_identity() {
+        let param = gain_param();
+        assert_eq!(param.curve, ParameterCurve::Linear);
+        assert_eq!(param.position_to_normalized(0.3).value(), 0.3);
+        assert_eq!(param.normalized_to_position(Normalized::new(0.3)), 0.3);
+    }
+
+    #[test]
+    fn test_logarithmic_curve() {
+        let param = ParameterInfo::new(
+            0,
+            "Cutoff".to_string(),
+            20.0,
+            20000.0,
+            1000.0,
+            "Hz".to_string(),
+        )
+        .with_curve(ParameterCurve::Logarithmic);
+
+        assert!((param.position_to_plain(0.0).value() - 20.0).abs() < 1e-3);
+        assert!((param.position_to_plain(1.0).value() - 20000.0).abs() < 1e-1);
+        // Halfway along a log slider is the geometric mean
+        assert!((param.position_to_plain(0.5).value() - 632.456).abs() < 0.1);
+        assert!((param.plain_to_position(Plain(632.456)) - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_logarithmic_curve_falls_back_to_linear() {
+        let param = gain_param().with_curve(ParameterCurve::Logarithmic);
+        assert_eq!(param.position_to_normalized(0.25).value(), 0.25);
+    }
+
+    #[test]
+    fn test_curves_round_trip() {
+        let curves = [
+            ParameterCurve::Linear,
+            ParameterCurve::Logarithmic,
+            ParameterCurve::Exponential,
+            ParameterCurve::Squared,
+            ParameterCurve::SquareRoot,
+            ParameterCurve::Cubed,
+            ParameterCurve::CubeRoot,
+        ];
+        for curve in curves {
+            let param = ParameterInfo::new(0, "P".to_string(), 1.0, 100.0, 1.0, String::new())
+                .with_curve(curve);
+            for i in 0..=10 {
+                let position = i as f32 / 10.0;
+                let back = param.normalized_to_position(param.position_to_normalized(position));
+                assert!(
+                    (back - position).abs() < 1e-4,
+                    "{:?} at {}",
+                    curve,
+                    position
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_empty_range() {
+        let param = ParameterInfo::new(0, "Fixed".to_string(), 1.0, 1.0, 1.0, String::new());
+        assert_eq!(param.to_normalized(Plain(1.0)), Normalized::MIN);
+        assert_eq!(param.to_plain(Normalized::MAX), Plain(1.0));
+    }
+}
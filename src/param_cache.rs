@@ -0,0 +1,517 @@
+//! Lock-free mirror of parameter values for UIs
+//!
+//! Reading a parameter with [`get_parameter()`](PluginInstance::get_parameter)
+//! calls into the plugin, which needs `&` access to the instance (so a lock
+//! shared with the audio thread) and may be slow. UIs that redraw knobs and
+//! meters at 60 fps would rather not do either. A [`ParameterCache`] keeps the
+//! last known normalized value of every parameter in atomics instead: any
+//! thread can read it at any time without locks or calls into the plugin.
+//!
+//! The cache is updated by whoever learns of a change:
+//! - [`CachedParameters`] wraps a plugin and records every `set_parameter()`
+//!   that passes through it (including the ones a
+//!   [`ParameterThrottle`](crate::throttle::ParameterThrottle) sends on the
+//!   audio thread), and re-reads all values after state or preset loads
+//! - [`ParameterCache::listener()`] records the edits made in a plugin's own
+//!   editor (see `Vst3Plugin::set_parameter_listener()` and
+//!   `AudioUnitPlugin::set_parameter_listener()`)
+//!
+//! Changes the plugin makes on its own (e.g. a macro moving other
+//! parameters) only show up after [`CachedParameters::refresh()`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::param_cache::CachedParameters;
+//! # fn example(plugin: impl PluginInstance + 'static) -> Result<()> {
+//! let mut plugin = CachedParameters::new(plugin);
+//! let cache = plugin.cache();
+//!
+//! std::thread::spawn(move || loop {
+//!     // UI thread: no locks, no plugin calls
+//!     if let Some(gain) = cache.get(0) {
+//!         println!("gain knob at {}", gain.value());
+//!     }
+//!     std::thread::sleep(std::time::Duration::from_millis(16));
+//! });
+//!
+//! plugin.set_parameter(0, 0.5)?; // Shows up in the cache
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::meter::MeterReading;
+use crate::node::BlockContext;
+use crate::quirks::Quirks;
+use crate::{
+    CurrentPreset, MidiEvent, Normalized, ParameterChange, ParameterEdit, ParameterInfo,
+    PluginInfo, PluginInstance, PresetInfo, Result,
+};
+use arc_swap::ArcSwap;
+use smallvec::SmallVec;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+
+struct Inner {
+    /// f32 bit patterns, swapped out whole by a resize
+    values: ArcSwap<Box<[AtomicU32]>>,
+    /// Bumped on every change
+    generation: AtomicU64,
+}
+
+/// Shared, lock-free store of normalized parameter values
+///
+/// Cloning is cheap and gives another handle to the same values, including
+/// after a [`resize()`](Self::resize). Changes to parameters beyond the
+/// current number are ignored.
+#[derive(Clone)]
+pub struct ParameterCache {
+    inner: Arc<Inner>,
+}
+
+impl ParameterCache {
+    /// Create a cache of `count` parameters, all at 0.0
+    pub fn new(count: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                values: ArcSwap::from_pointee((0..count).map(|_| AtomicU32::new(0)).collect()),
+                generation: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Create a cache holding a plugin's current values
+    ///
+    /// Parameters that can't be read start at 0.0.
+    pub fn from_plugin<P: PluginInstance + ?Sized>(plugin: &P) -> Self {
+        let cache = Self::new(plugin.parameter_count());
+        cache.read_from(plugin);
+        cache
+    }
+
+    /// Number of parameters
+    pub fn len(&self) -> usize {
+        self.inner.values.load().len()
+    }
+
+    /// Whether the cache holds no parameters
+    pub fn is_empty(&self) -> bool {
+        self.inner.values.load().is_empty()
+    }
+
+    /// Last known value of a parameter
+    pub fn get(&self, index: usize) -> Option<Normalized> {
+        self.inner
+            .values
+            .load()
+            .get(index)
+            .map(|value| Normalized::new(f32::from_bits(value.load(Ordering::Relaxed))))
+    }
+
+    /// Record a parameter's new value
+    pub fn set(&self, index: usize, value: Normalized) {
+        if let Some(slot) = self.inner.values.load().get(index) {
+            let bits = value.value().to_bits();
+            if slot.swap(bits, Ordering::Relaxed) != bits {
+                self.inner.generation.fetch_add(1, Ordering::Release);
+            }
+        }
+    }
+
+    /// Change the number of parameters
+    ///
+    /// Values of the remaining parameters are kept and new ones start at 0.0.
+    /// All handles see the new size and reads stay lock-free. A `set()`
+    /// racing with the resize may be lost, so resize from the thread that
+    /// refreshes the values.
+    pub fn resize(&self, count: usize) {
+        let old = self.inner.values.load();
+        if old.len() == count {
+            return;
+        }
+        let values: Box<[AtomicU32]> = (0..count)
+            .map(|index| {
+                AtomicU32::new(
+                    old.get(index)
+                        .map_or(0, |value| value.load(Ordering::Relaxed)),
+                )
+            })
+            .collect();
+        self.inner.values.store(Arc::new(values));
+        self.inner.generation.fetch_add(1, Ordering::Release);
+    }
+
+    /// Record an edit made in a plugin's editor
+    pub fn apply_edit(&self, edit: &ParameterEdit) {
+        if let ParameterEdit::Change { index, value } = *edit {
+            self.set(index, value);
+        }
+    }
+
+    /// A parameter listener that records editor edits in this cache
+    ///
+    /// For `Vst3Plugin::set_parameter_listener()` and
+    /// `AudioUnitPlugin::set_parameter_listener()`.
+    pub fn listener(&self) -> impl Fn(ParameterEdit) + Send + Sync + 'static {
+        let cache = self.clone();
+        move |edit| cache.apply_edit(&edit)
+    }
+
+    /// Counter bumped by every change
+    ///
+    /// UIs can skip redrawing while it stays the same.
+    pub fn generation(&self) -> u64 {
+        self.inner.generation.load(Ordering::Acquire)
+    }
+
+    /// All values, by parameter index
+    pub fn snapshot(&self) -> Vec<Normalized> {
+        (0..self.len())
+            .filter_map(|index| self.get(index))
+            .collect()
+    }
+
+    /// Re-read every value from the plugin
+    fn read_from<P: PluginInstance + ?Sized>(&self, plugin: &P) {
+        for index in 0..self.len() {
+            if let Ok(value) = plugin.get_parameter(index) {
+                self.set(index, Normalized::new(value));
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for ParameterCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParameterCache")
+            .field("values", &self.snapshot())
+            .field("generation", &self.generation())
+            .finish()
+    }
+}
+
+/// Plugin wrapper that keeps a [`ParameterCache`] up to date
+///
+/// All methods are forwarded to the wrapped plugin. Successful
+/// `set_parameter()` calls are recorded in the cache, as are the values read
+/// by `get_parameter()`; `set_state()`, `load_preset()` and
+/// `set_state_from_*()` re-read every value.
+pub struct CachedParameters<P> {
+    plugin: P,
+    cache: ParameterCache,
+}
+
+impl<P: PluginInstance> CachedParameters<P> {
+    /// Wrap a plugin, filling the cache with its current values
+    pub fn new(plugin: P) -> Self {
+        let cache = ParameterCache::from_plugin(&plugin);
+        Self { plugin, cache }
+    }
+
+    /// A handle to the cache, for other threads
+    pub fn cache(&self) -> ParameterCache {
+        self.cache.clone()
+    }
+
+    /// Re-read every value from the plugin
+    ///
+    /// If the number of parameters changed (after
+    /// [`HostEvent::ParametersChanged`](crate::events::HostEvent::ParametersChanged)),
+    /// the cache is [resized](ParameterCache::resize) first; existing handles
+    /// keep working.
+    pub fn refresh(&mut self) {
+        self.cache.resize(self.plugin.parameter_count());
+        self.cache.read_from(&self.plugin);
+    }
+
+    /// The wrapped plugin
+    pub fn inner(&self) -> &P {
+        &self.plugin
+    }
+
+    /// The wrapped plugin, mutably
+    ///
+    /// Changes made directly on the inner plugin aren't cached until
+    /// [`refresh()`](Self::refresh).
+    pub fn inner_mut(&mut self) -> &mut P {
+        &mut self.plugin
+    }
+
+    /// Unwrap the plugin
+    pub fn into_inner(self) -> P {
+        self.plugin
+    }
+
+    fn refreshed(&mut self, result: Result<()>) -> Result<()> {
+        if result.is_ok() {
+            self.refresh();
+        }
+        result
+    }
+}
+
+impl<P: PluginInstance> PluginInstance for CachedParameters<P> {
+    fn initialize(&mut self, sample_rate: f64, max_block_size: usize) -> Result<()> {
+        let result = self.plugin.initialize(sample_rate, max_block_size);
+        self.refreshed(result)
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.plugin.reset()
+    }
+
+    fn process(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        self.plugin.process(inputs, outputs, num_frames)
+    }
+
+    fn process_with_context(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        context: &BlockContext,
+    ) -> Result<()> {
+        self.plugin.process_with_context(inputs, outputs, context)
+    }
+
+    fn process_with_events(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        changes: &[ParameterChange],
+        midi: &[MidiEvent],
+    ) -> Result<()> {
+        self.plugin
+            .process_with_events(inputs, outputs, num_frames, changes, midi)?;
+        // Changes at later offsets win, as in the plugin
+        let last = num_frames.saturating_sub(1) as u32;
+        let mut order: SmallVec<[(u32, usize); 16]> = changes
+            .iter()
+            .enumerate()
+            .map(|(i, change)| (change.sample_offset.min(last), i))
+            .collect();
+        order.sort_unstable();
+        for (_, i) in order {
+            let change = &changes[i];
+            self.cache.set(change.index, Normalized::new(change.value));
+        }
+        Ok(())
+    }
+
+    fn sample_position(&self) -> u64 {
+        self.plugin.sample_position()
+    }
+
+    fn set_sample_position(&mut self, position: u64) -> Result<()> {
+        self.plugin.set_sample_position(position)
+    }
+
+    fn flush_events(&mut self) -> Result<()> {
+        self.plugin.flush_events()
+    }
+
+    fn parameter_count(&self) -> usize {
+        self.plugin.parameter_count()
+    }
+
+    fn parameter_info(&self, index: usize) -> Result<ParameterInfo> {
+        self.plugin.parameter_info(index)
+    }
+
+    fn get_parameter(&self, index: usize) -> Result<f32> {
+        let value = self.plugin.get_parameter(index)?;
+        self.cache.set(index, Normalized::new(value));
+        Ok(value)
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) -> Result<()> {
+        self.plugin.set_parameter(index, value)?;
+        self.cache.set(index, Normalized::new(value));
+        Ok(())
+    }
+
+    fn send_midi(&mut self, events: &[MidiEvent]) -> Result<()> {
+        self.plugin.send_midi(events)
+    }
+
+    fn preset_count(&self) -> Result<usize> {
+        self.plugin.preset_count()
+    }
+
+    fn preset_info(&self, index: usize) -> Result<PresetInfo> {
+        self.plugin.preset_info(index)
+    }
+
+    fn load_preset(&mut self, preset_number: i32) -> Result<()> {
+        let result = self.plugin.load_preset(preset_number);
+        self.refreshed(result)
+    }
+
+    fn current_preset(&self) -> Result<Option<CurrentPreset>> {
+        self.plugin.current_preset()
+    }
+
+    fn get_state(&self) -> Result<Vec<u8>> {
+        self.plugin.get_state()
+    }
+
+    fn set_state(&mut self, data: &[u8]) -> Result<()> {
+        let result = self.plugin.set_state(data);
+        self.refreshed(result)
+    }
+
+    fn get_state_to_writer(&self, writer: &mut dyn std::io::Write) -> Result<()> {
+        self.plugin.get_state_to_writer(writer)
+    }
+
+    fn set_state_from_reader(&mut self, reader: &mut dyn std::io::Read) -> Result<()> {
+        let result = self.plugin.set_state_from_reader(reader);
+        self.refreshed(result)
+    }
+
+    fn set_state_from_file(&mut self, path: &std::path::Path) -> Result<()> {
+        let result = self.plugin.set_state_from_file(path);
+        self.refreshed(result)
+    }
+
+    fn take_state_dirty(&self) -> bool {
+        self.plugin.take_state_dirty()
+    }
+
+    fn info(&self) -> &PluginInfo {
+        self.plugin.info()
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.plugin.is_initialized()
+    }
+
+    fn input_channels(&self) -> usize {
+        self.plugin.input_channels()
+    }
+
+    fn output_channels(&self) -> usize {
+        self.plugin.output_channels()
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.plugin.latency_samples()
+    }
+
+    fn tail_samples(&self) -> usize {
+        self.plugin.tail_samples()
+    }
+
+    fn tail_seconds(&self) -> f64 {
+        self.plugin.tail_seconds()
+    }
+
+    fn set_bypass(&mut self, bypassed: bool) -> Result<()> {
+        self.plugin.set_bypass(bypassed)
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.plugin.is_bypassed()
+    }
+
+    fn quirks(&self) -> Quirks {
+        self.plugin.quirks()
+    }
+
+    fn plugin_meter(&self) -> Option<Vec<MeterReading>> {
+        self.plugin.plugin_meter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockPlugin;
+
+    #[test]
+    fn test_cache_follows_the_plugin() {
+        let mut plugin = CachedParameters::new(MockPlugin::new());
+        let cache = plugin.cache();
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.get(1), Some(Normalized::new(1.0)));
+
+        let state = plugin.get_state().unwrap();
+        let generation = cache.generation();
+        plugin.set_parameter(1, 0.25).unwrap();
+        assert_eq!(cache.get(1), Some(Normalized::new(0.25)));
+        assert!(cache.generation() > generation);
+
+        // Failed sets aren't recorded; restored states are
+        assert!(plugin.set_parameter(7, 0.5).is_err());
+        plugin.set_state(&state).unwrap();
+        assert_eq!(cache.get(1), Some(Normalized::new(1.0)));
+        assert_eq!(cache.get(7), None);
+
+        // The change at the latest offset is what the plugin ends up with
+        plugin.initialize(48000.0, 64).unwrap();
+        let input = vec![0.0f32; 64];
+        let (mut left, mut right) = (vec![0.0f32; 64], vec![0.0f32; 64]);
+        let changes = [
+            ParameterChange::new(1, 0.75, 48),
+            ParameterChange::new(1, 0.5, 16),
+        ];
+        plugin
+            .process_with_events(
+                &[&input, &input],
+                &mut [&mut left, &mut right],
+                64,
+                &changes,
+                &[],
+            )
+            .unwrap();
+        assert_eq!(plugin.inner().get_parameter(1).unwrap(), 0.75);
+        assert_eq!(cache.get(1), Some(Normalized::new(0.75)));
+    }
+
+    #[test]
+    fn test_editor_edits() {
+        let cache = ParameterCache::new(2);
+        let listener = cache.listener();
+        listener(ParameterEdit::Begin { index: 0 });
+        listener(ParameterEdit::Change {
+            index: 0,
+            value: Normalized::new(0.75),
+        });
+        listener(ParameterEdit::End { index: 0 });
+
+        let reader = std::thread::spawn(move || cache.snapshot());
+        assert_eq!(
+            reader.join().unwrap(),
+            vec![Normalized::new(0.75), Normalized::new(0.0)]
+        );
+    }
+
+    #[test]
+    fn test_resize_keeps_handles() {
+        let cache = ParameterCache::new(2);
+        let handle = cache.clone();
+        cache.set(1, Normalized::new(0.5));
+
+        cache.resize(3);
+        assert_eq!(
+            handle.snapshot(),
+            vec![
+                Normalized::new(0.0),
+                Normalized::new(0.5),
+                Normalized::new(0.0)
+            ]
+        );
+        handle.set(2, Normalized::new(0.25));
+        assert_eq!(cache.get(2), Some(Normalized::new(0.25)));
+
+        cache.resize(1);
+        assert_eq!(handle.len(), 1);
+        assert_eq!(handle.get(1), None);
+    }
+}
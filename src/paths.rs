@@ -0,0 +1,297 @@
+//! Path handling for plugin bundles
+//!
+//! Paths cross the FFI in the platform's native form: raw bytes on Unix and
+//! UTF-16 on Windows, so bundles installed under non-ASCII (or, on Unix,
+//! non-UTF-8) folders load like any other.
+//!
+//! # Long paths on Windows
+//!
+//! Win32 file APIs reject paths of [`MAX_PATH`] (260) characters or more unless
+//! they carry the `\\?\` extended-length prefix. Plugins installed under deeply
+//! nested user folders easily exceed that, so paths handed to the plugin
+//! backends are converted with [`to_extended_length()`] and paths coming back
+//! are converted with [`strip_extended_length()`]. `PluginInfo::path` therefore
+//! always holds the ordinary form, which round-trips through scanning and
+//! loading unchanged.
+//!
+//! Both functions work on Windows path syntax on every platform, so they can be
+//! used (and tested) anywhere; paths that aren't absolute Windows paths are
+//! returned unchanged.
+//!
+//! # Examples
+//!
+//! ```
+//! use rack::paths;
+//! use std::path::Path;
+//!
+//! let path = Path::new(r"C:\Users\me\Plugins\Synth.vst3");
+//! let extended = paths::to_extended_length(path);
+//! assert_eq!(extended, Path::new(r"\\?\C:\Users\me\Plugins\Synth.vst3"));
+//! assert_eq!(paths::strip_extended_length(&extended), path);
+//! ```
+
+use crate::{Error, Result};
+use std::ffi::CString;
+use std::path::{Path, PathBuf};
+
+/// Longest path (in UTF-16 units, including the terminator) Win32 accepts
+/// without the extended-length prefix
+pub const MAX_PATH: usize = 260;
+
+/// Prefix of extended-length paths
+const VERBATIM_PREFIX: &str = r"\\?\";
+
+/// Prefix of extended-length UNC paths (`\\server\share`)
+const VERBATIM_UNC_PREFIX: &str = r"\\?\UNC\";
+
+/// Whether a path is too long for Win32 APIs without the `\\?\` prefix
+pub fn is_long(path: &Path) -> bool {
+    // MAX_PATH counts UTF-16 units, not bytes of the OS string
+    path.as_os_str().to_string_lossy().encode_utf16().count() >= MAX_PATH
+}
+
+/// Convert an absolute Windows path to its `\\?\` extended-length form
+///
+/// Forward slashes become backslashes and `.`/`..` components are resolved,
+/// since Windows doesn't normalize extended-length paths. Paths that are
+/// relative, already extended or not valid UTF-8 are returned unchanged.
+pub fn to_extended_length(path: &Path) -> PathBuf {
+    let Some(s) = path.to_str() else {
+        return path.to_path_buf();
+    };
+    if s.starts_with(VERBATIM_PREFIX) {
+        return path.to_path_buf();
+    }
+
+    let s = s.replace('/', "\\");
+    let bytes = s.as_bytes();
+    let (prefix, root, rest) = if let Some(unc) = s.strip_prefix(r"\\") {
+        // \\server\share\rest: server and share form the root
+        let mut parts = unc.splitn(3, '\\');
+        let (Some(server), Some(share)) = (parts.next(), parts.next()) else {
+            return path.to_path_buf();
+        };
+        if server.is_empty() || share.is_empty() {
+            return path.to_path_buf();
+        }
+        (
+            VERBATIM_UNC_PREFIX,
+            format!(r"{}\{}", server, share),
+            parts.next().unwrap_or(""),
+        )
+    } else if bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && bytes[2] == b'\\'
+    {
+        (VERBATIM_PREFIX, s[..2].to_string(), &s[3..])
+    } else {
+        return path.to_path_buf();
+    };
+
+    let mut components: Vec<&str> = Vec::new();
+    for component in rest.split('\\') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            name => components.push(name),
+        }
+    }
+
+    let mut extended = format!("{}{}", prefix, root);
+    if components.is_empty() {
+        // The root itself, e.g. "C:\"
+        extended.push('\\');
+    }
+    for component in components {
+        extended.push('\\');
+        extended.push_str(component);
+    }
+    PathBuf::from(extended)
+}
+
+/// Convert an extended-length path back to its ordinary form
+///
+/// `\\?\C:\dir` becomes `C:\dir` and `\\?\UNC\server\share` becomes
+/// `\\server\share`. Other paths are returned unchanged.
+pub fn strip_extended_length(path: &Path) -> PathBuf {
+    let Some(s) = path.to_str() else {
+        return path.to_path_buf();
+    };
+    if let Some(unc) = s.strip_prefix(VERBATIM_UNC_PREFIX) {
+        PathBuf::from(format!(r"\\{}", unc))
+    } else if let Some(local) = s.strip_prefix(VERBATIM_PREFIX) {
+        PathBuf::from(local)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Convert a path to the byte string passed to the narrow (`char*`) FFI calls
+///
+/// On Unix the path's raw bytes are passed as they are, so paths that aren't
+/// valid UTF-8 still reach the filesystem intact. On Windows the narrow calls
+/// take UTF-8; prefer [`to_ffi_wide()`] there.
+///
+/// # Errors
+///
+/// Returns an error if the path contains a null byte, or on Windows if it
+/// isn't valid Unicode
+#[cfg_attr(windows, allow(dead_code))]
+pub(crate) fn to_ffi_bytes(path: &Path) -> Result<CString> {
+    #[cfg(unix)]
+    let bytes = {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes().to_vec()
+    };
+    #[cfg(not(unix))]
+    let bytes = path
+        .to_str()
+        .ok_or_else(|| Error::Other(format!("Path is not valid Unicode: {}", path.display())))?
+        .as_bytes()
+        .to_vec();
+
+    CString::new(bytes)
+        .map_err(|_| Error::Other(format!("Path contains null byte: {}", path.display())))
+}
+
+/// Convert a path to the null-terminated UTF-16 string passed to the wide
+/// (`wchar_t*`) FFI calls on Windows
+///
+/// Absolute paths are converted to the extended-length form so the backends
+/// can address bundles of any depth. The UTF-16 is taken straight from the
+/// `OsStr`, so no path is lost to a UTF-8 round trip.
+///
+/// # Errors
+///
+/// Returns an error if the path contains a null character
+#[cfg(windows)]
+pub(crate) fn to_ffi_wide(path: &Path) -> Result<Vec<u16>> {
+    use std::os::windows::ffi::OsStrExt;
+
+    let mut wide: Vec<u16> = to_extended_length(path).as_os_str().encode_wide().collect();
+    if wide.contains(&0) {
+        return Err(Error::Other(format!(
+            "Path contains null character: {}",
+            path.display()
+        )));
+    }
+    wide.push(0);
+    Ok(wide)
+}
+
+/// Convert a path received from the FFI (without its terminator) to the form
+/// stored in `PluginInfo`
+///
+/// The inverse of [`to_ffi_bytes()`]/[`to_ffi_wide()`]: raw bytes on Unix,
+/// UTF-8 with any extended-length prefix removed on Windows.
+///
+/// # Errors
+///
+/// Returns an error on Windows if the bytes aren't valid UTF-8
+#[cfg(any(vst3_sdk, target_vendor = "apple", test))]
+pub(crate) fn from_ffi_bytes(bytes: Vec<u8>) -> Result<PathBuf> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        Ok(PathBuf::from(std::ffi::OsString::from_vec(bytes)))
+    }
+    #[cfg(not(unix))]
+    {
+        let path = String::from_utf8(bytes)
+            .map_err(|e| Error::Other(format!("Invalid UTF-8 in path: {}", e)))?;
+        Ok(strip_extended_length(Path::new(&path)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A bundle path well past MAX_PATH
+    fn deep_bundle() -> String {
+        let nested = "Very Long Vendor Folder Name\\".repeat(12);
+        format!(r"C:\Users\me\Documents\{}Synth.vst3", nested)
+    }
+
+    #[test]
+    fn test_long_paths_round_trip() {
+        let original = deep_bundle();
+        let path = Path::new(&original);
+        assert!(original.len() > 300);
+        assert!(is_long(path));
+
+        let extended = to_extended_length(path);
+        assert_eq!(extended.to_str().unwrap(), format!(r"\\?\{}", original));
+        assert_eq!(strip_extended_length(&extended), path);
+
+        // Already extended and non-Windows paths are left alone
+        assert_eq!(to_extended_length(&extended), extended);
+        assert_eq!(
+            to_extended_length(Path::new("/usr/lib/vst3/Synth.vst3")),
+            Path::new("/usr/lib/vst3/Synth.vst3")
+        );
+        assert_eq!(
+            to_extended_length(Path::new(r"Plugins\Synth.vst3")),
+            Path::new(r"Plugins\Synth.vst3")
+        );
+        assert!(!is_long(Path::new(r"C:\Program Files\Common Files\VST3")));
+
+        // Non-ASCII names take more bytes than UTF-16 units
+        let accented = format!(r"C:\Plugins\{}.vst3", "é".repeat(200));
+        assert!(accented.len() > MAX_PATH);
+        assert!(!is_long(Path::new(&accented)));
+    }
+
+    #[test]
+    fn test_extended_length_normalization() {
+        assert_eq!(
+            to_extended_length(Path::new("C:/VST3/./Old/../Synth.vst3")),
+            Path::new(r"\\?\C:\VST3\Synth.vst3")
+        );
+        assert_eq!(to_extended_length(Path::new(r"D:\")), Path::new(r"\\?\D:\"));
+
+        let unc = Path::new(r"\\studio-nas\plugins\VST3\Synth.vst3");
+        let extended = to_extended_length(unc);
+        assert_eq!(
+            extended,
+            Path::new(r"\\?\UNC\studio-nas\plugins\VST3\Synth.vst3")
+        );
+        assert_eq!(strip_extended_length(&extended), unc);
+    }
+
+    #[test]
+    fn test_non_ascii_paths_round_trip() {
+        let path = Path::new("/home/山田太郎/.vst3/Sýnth 音.vst3");
+        let bytes = to_ffi_bytes(path).unwrap().into_bytes();
+        assert_eq!(from_ffi_bytes(bytes).unwrap(), path);
+
+        assert!(to_ffi_bytes(Path::new("bad\0path")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_paths_round_trip() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        // "café" in Latin-1, not valid UTF-8
+        let path = Path::new(OsStr::from_bytes(b"/opt/plugins/caf\xe9/Synth.vst3"));
+        let bytes = to_ffi_bytes(path).unwrap().into_bytes();
+        assert_eq!(from_ffi_bytes(bytes).unwrap(), path);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_wide_paths() {
+        let path = Path::new(r"C:\Users\山田太郎\VST3\Synth.vst3");
+        let wide = to_ffi_wide(path).unwrap();
+        assert_eq!(wide.last(), Some(&0));
+        let expected: Vec<u16> = r"\\?\C:\Users\山田太郎\VST3\Synth.vst3"
+            .encode_utf16()
+            .collect();
+        assert_eq!(&wide[..wide.len() - 1], &expected[..]);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::isolation::Isolation;
+use crate::param::ParameterCurve;
 use std::path::PathBuf;
 
 /// Information about a discovered audio plugin
@@ -16,10 +18,124 @@ pub struct PluginInfo {
     pub plugin_type: PluginType,
 
     /// Path to the plugin bundle (for AudioUnits, this is the .component path)
+    ///
+    /// Where the scanner found the bundle, which may be a symlink or (on macOS)
+    /// a Finder alias.
     pub path: PathBuf,
 
+    /// Where the bundle actually lives, with symlinks and aliases resolved
+    ///
+    /// Same as `path` unless the bundle was found through a link. Plugins are
+    /// loaded from here.
+    pub canonical_path: PathBuf,
+
     /// Unique identifier for the plugin
     pub unique_id: String,
+
+    /// Plugin format (AudioUnit, VST3, ...)
+    pub format: PluginFormat,
+
+    /// AudioComponent flags (AudioUnits only)
+    pub au_flags: Option<AudioUnitFlags>,
+
+    /// Factory and class information (VST3 only)
+    pub vst3_factory: Option<Vst3FactoryInfo>,
+
+    /// Process isolation chosen for this plugin, overriding the quirks table
+    ///
+    /// See [`isolation_for()`](crate::isolation::isolation_for).
+    pub isolation: Option<Isolation>,
+}
+
+/// Flags an AudioUnit component declares about how it can be hosted
+///
+/// Hosts use these to decide between in-process and out-of-process hosting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AudioUnitFlags {
+    /// The component is safe to open in a sandboxed process
+    pub sandbox_safe: bool,
+
+    /// The component is an AUv3 app extension
+    pub is_v3: bool,
+
+    /// The component must be instantiated asynchronously
+    pub requires_async_instantiation: bool,
+
+    /// The AUv3 extension can be loaded into the host's process
+    pub can_load_in_process: bool,
+}
+
+/// What a VST3 module's factory says about its vendor and a plugin class
+///
+/// From `PFactoryInfo` and `PClassInfo2`, or the bundle's `moduleinfo.json`.
+/// Vendors often leave the strings empty.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Vst3FactoryInfo {
+    /// Vendor website (e.g. for a "Visit website" link)
+    pub url: String,
+
+    /// Vendor support email address
+    pub email: String,
+
+    /// SDK version the class was built with (e.g. "VST 3.7.9")
+    pub sdk_version: String,
+
+    /// The class can run on a different computer than its controller
+    /// (`kDistributable`)
+    pub distributable: bool,
+
+    /// The class supports simple IO mode (`kSimpleModeSupported`)
+    pub simple_mode_supported: bool,
+
+    /// Classes can be created and destroyed at any time
+    /// (`kClassesDiscardable`)
+    pub classes_discardable: bool,
+
+    /// The module checks a license when it is loaded (`kLicenseCheck`)
+    pub license_check: bool,
+
+    /// Components are never unloaded until the process exits
+    /// (`kComponentNonDiscardable`)
+    pub component_non_discardable: bool,
+
+    /// Factory strings are UTF-16 (`kUnicode`)
+    pub unicode: bool,
+}
+
+/// Plugin format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PluginFormat {
+    /// Apple AudioUnit
+    AudioUnit,
+
+    /// Steinberg VST3
+    Vst3,
+
+    /// CLever Audio Plugin
+    Clap,
+
+    /// LV2 (LADSPA Version 2)
+    Lv2,
+
+    /// Steinberg VST 2.x
+    Vst2,
+
+    /// Unknown format (e.g., a PluginInfo built by hand)
+    Unknown,
+}
+
+impl std::fmt::Display for PluginFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            PluginFormat::AudioUnit => "AU",
+            PluginFormat::Vst3 => "VST3",
+            PluginFormat::Clap => "CLAP",
+            PluginFormat::Lv2 => "LV2",
+            PluginFormat::Vst2 => "VST2",
+            PluginFormat::Unknown => "Unknown",
+        };
+        f.write_str(name)
+    }
 }
 
 /// Type of audio plugin
@@ -62,10 +178,50 @@ impl PluginInfo {
             manufacturer,
             version,
             plugin_type,
+            canonical_path: path.clone(),
             path,
             unique_id,
+            format: PluginFormat::Unknown,
+            au_flags: None,
+            vst3_factory: None,
+            isolation: None,
         }
     }
+
+    /// Set the plugin format
+    pub fn with_format(mut self, format: PluginFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set where the bundle actually lives, if `path` is a link to it
+    pub fn with_canonical_path(mut self, canonical_path: PathBuf) -> Self {
+        self.canonical_path = canonical_path;
+        self
+    }
+
+    /// Check whether the bundle was found through a symlink or alias
+    pub fn is_linked(&self) -> bool {
+        self.path != self.canonical_path
+    }
+
+    /// Set the AudioComponent flags
+    pub fn with_au_flags(mut self, flags: AudioUnitFlags) -> Self {
+        self.au_flags = Some(flags);
+        self
+    }
+
+    /// Set the VST3 factory information
+    pub fn with_vst3_factory(mut self, factory: Vst3FactoryInfo) -> Self {
+        self.vst3_factory = Some(factory);
+        self
+    }
+
+    /// Choose how the plugin is isolated from the host process
+    pub fn with_isolation(mut self, isolation: Isolation) -> Self {
+        self.isolation = Some(isolation);
+        self
+    }
 }
 
 impl std::fmt::Display for PluginInfo {
@@ -98,6 +254,12 @@ pub struct ParameterInfo {
 
     /// Unit label (e.g., "dB", "Hz", "%")
     pub unit: String,
+
+    /// Display curve for host controls (linear unless the plugin says otherwise)
+    pub curve: ParameterCurve,
+
+    /// Whether the plugin wants the parameter shown in normal parameter lists
+    pub visibility: ParameterVisibility,
 }
 
 impl ParameterInfo {
@@ -117,8 +279,40 @@ impl ParameterInfo {
             max,
             default,
             unit,
+            curve: ParameterCurve::Linear,
+            visibility: ParameterVisibility::Visible,
         }
     }
+
+    /// Set the visibility
+    pub fn with_visibility(mut self, visibility: ParameterVisibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    /// Check whether the parameter belongs in normal parameter lists
+    pub fn is_visible(&self) -> bool {
+        self.visibility == ParameterVisibility::Visible
+    }
+}
+
+/// How prominently a parameter should be shown to users
+///
+/// Plugins mark some parameters as not meant for everyday editing.
+/// [`PluginInstance::parameters()`](crate::PluginInstance::parameters) leaves
+/// these out; [`all_parameters()`](crate::PluginInstance::all_parameters)
+/// includes them for power-user hosts and validators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ParameterVisibility {
+    /// A regular parameter
+    #[default]
+    Visible,
+
+    /// Only for expert users (AudioUnit `kAudioUnitParameterFlag_ExpertMode`)
+    Expert,
+
+    /// Not meant to be shown at all (VST3 `kIsHidden`)
+    Hidden,
 }
 
 /// Information about a plugin preset
@@ -144,3 +338,15 @@ impl PresetInfo {
         }
     }
 }
+
+/// The preset a plugin reports as currently loaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CurrentPreset {
+    /// Preset number, as passed to `load_preset()`
+    ///
+    /// Negative for AudioUnit user presets, which have no factory number.
+    pub preset_number: i32,
+
+    /// Preset name as reported by the plugin
+    pub name: String,
+}
@@ -0,0 +1,524 @@
+//! Event input ports
+//!
+//! [`PluginInstance::send_midi()`] hands events to the plugin as soon as it is
+//! called, and each event's `sample_offset` is relative to whichever block the
+//! plugin happens to render next. When events come from several places, or are
+//! sent while a block is being processed, the result depends on call order.
+//!
+//! An [`EventPort`] works the way a host's event input does instead: events are
+//! queued with offsets relative to the next `process()` call, kept in time
+//! order, and delivered by [`EventPort::process()`] together with the audio
+//! block they belong to. Events scheduled past the end of a block stay queued
+//! and are carried into later blocks with their offsets adjusted, so an event
+//! can be scheduled any distance ahead. Queued notes can also be reshaped with
+//! a velocity curve and humanization (see [`humanize`](crate::humanize)).
+//!
+//! Events can also be scheduled at musical positions ("next bar", "beat 3.5")
+//! with [`EventPort::schedule()`]. They are resolved against the
+//! [`Transport`] by [`EventPort::process_with_transport()`] in the block they
+//! fall in, so they stay sample-accurate through tempo changes.
+//!
+//! Other threads (a MIDI input callback, a UI) queue events through an
+//! [`EventSender`]. The audio thread never blocks on a sender: if one is busy
+//! at the start of a block, its events wait for the next block.
+//!
+//! Every queue has a fixed capacity, allocated when the port is created, so
+//! the audio thread never allocates or frees memory. Events queued while a
+//! queue is full are dropped.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::port::EventPort;
+//! # fn example(mut synth: impl PluginInstance) -> Result<()> {
+//! synth.initialize(48000.0, 512)?;
+//!
+//! let mut port = EventPort::new();
+//! port.push(MidiEvent::note_on(60, 100, 0, 0));
+//! port.push(MidiEvent::note_off(60, 0, 0, 24000)); // Half a second later
+//!
+//! let mut left = vec![0.0f32; 512];
+//! let mut right = vec![0.0f32; 512];
+//! for _ in 0..100 {
+//!     port.process(&mut synth, &[], &mut [&mut left, &mut right], 512)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::humanize::{self, Humanize, VelocityCurve};
+use crate::tempo::{MusicalTime, Transport};
+use crate::{MidiEvent, PluginInstance, Result};
+use std::sync::{Arc, Mutex};
+
+/// Capacity of each queue of a port created with [`EventPort::new()`]
+const DEFAULT_CAPACITY: usize = 256;
+
+/// Queue of events waiting for upcoming `process()` calls
+///
+/// Offsets of queued events are relative to the start of the next block.
+pub struct EventPort {
+    /// Queued events, sorted by offset (events with equal offsets keep their order)
+    pending: Vec<MidiEvent>,
+
+    /// Events waiting for a musical position, in the order they were scheduled
+    scheduled: Vec<(MusicalTime, MidiEvent)>,
+
+    /// Events queued by senders since the last block
+    inbox: Arc<Mutex<Vec<MidiEvent>>>,
+
+    /// Empty buffer swapped with the inbox to collect its events
+    spare: Vec<MidiEvent>,
+
+    /// Curve applied to the velocity of every queued Note On
+    pub velocity_curve: VelocityCurve,
+
+    /// Random variation applied to every queued Note On, after the curve
+    pub humanize: Option<Humanize>,
+}
+
+impl EventPort {
+    /// Create an empty port
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// Create an empty port with room for `capacity` queued events
+    ///
+    /// The capacity applies separately to events queued for upcoming blocks,
+    /// events scheduled at musical positions and events waiting from senders.
+    /// Events pushed or sent while the queue is full are dropped.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            pending: Vec::with_capacity(capacity),
+            scheduled: Vec::with_capacity(capacity),
+            inbox: Arc::new(Mutex::new(Vec::with_capacity(capacity))),
+            spare: Vec::with_capacity(capacity),
+            velocity_curve: VelocityCurve::default(),
+            humanize: None,
+        }
+    }
+
+    /// Create a handle for queueing events from another thread
+    pub fn sender(&self) -> EventSender {
+        EventSender {
+            inbox: Arc::clone(&self.inbox),
+        }
+    }
+
+    /// Queue an event for the next block
+    ///
+    /// `sample_offset` is counted from the start of the next `process()` call
+    /// and may be larger than the block size. The port's velocity curve and
+    /// humanization are applied as the event is queued.
+    ///
+    /// Returns `false` if the queue is full and the event was dropped.
+    pub fn push(&mut self, mut event: MidiEvent) -> bool {
+        self.shape(&mut event);
+        self.insert(event)
+    }
+
+    /// Queue an event for a musical position
+    ///
+    /// The event is delivered in the block containing `at`, with its
+    /// `sample_offset` added to the resolved position (so a Note Off can be
+    /// scheduled a fixed time after a bar line). Relative positions such as
+    /// [`MusicalTime::NextBar`] are anchored in the first block processed
+    /// while the transport plays; positions already passed by then are
+    /// delivered at the start of that block.
+    ///
+    /// Scheduled events are only delivered by
+    /// [`process_with_transport()`](Self::process_with_transport).
+    ///
+    /// Returns `false` if the queue is full and the event was dropped.
+    pub fn schedule(&mut self, mut event: MidiEvent, at: MusicalTime) -> bool {
+        if self.scheduled.len() == self.scheduled.capacity() {
+            return false;
+        }
+        self.shape(&mut event);
+        self.scheduled.push((at, event));
+        true
+    }
+
+    /// Apply the velocity curve and humanization
+    fn shape(&mut self, event: &mut MidiEvent) {
+        humanize::apply_curve(&self.velocity_curve, event);
+        if let Some(humanize) = self.humanize.as_mut() {
+            humanize.apply(event);
+        }
+    }
+
+    /// Add an event to the queue, keeping it sorted, unless the queue is full
+    fn insert(&mut self, event: MidiEvent) -> bool {
+        if self.pending.len() == self.pending.capacity() {
+            return false;
+        }
+        // After existing events at the same offset, so equal-time events keep
+        // the order they were queued in
+        let pos = self
+            .pending
+            .partition_point(|e| e.sample_offset <= event.sample_offset);
+        self.pending.insert(pos, event);
+        true
+    }
+
+    /// Number of queued events, including those scheduled at musical
+    /// positions
+    pub fn len(&self) -> usize {
+        self.pending.len() + self.scheduled.len()
+    }
+
+    /// Check whether no events are queued
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty() && self.scheduled.is_empty()
+    }
+
+    /// Drop all queued events, including those queued by senders and those
+    /// scheduled at musical positions
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.scheduled.clear();
+        self.inbox.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+
+    /// Deliver the events due in this block, then process it
+    ///
+    /// Events with an offset inside the block are sent to the plugin with
+    /// [`send_midi()`](PluginInstance::send_midi) immediately before
+    /// [`process()`](PluginInstance::process). The rest stay queued, with their
+    /// offsets moved back by `num_frames`. A zero-frame block delivers only
+    /// events at offset 0.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error from `send_midi()` or `process()`. Events due in the
+    /// block are removed from the queue either way.
+    pub fn process<P: PluginInstance + ?Sized>(
+        &mut self,
+        plugin: &mut P,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        self.collect_inbox();
+
+        let block_end = num_frames.max(1).min(u32::MAX as usize) as u32;
+        let due = self
+            .pending
+            .partition_point(|e| e.sample_offset < block_end);
+
+        let sent = if due > 0 {
+            plugin.send_midi(&self.pending[..due])
+        } else {
+            Ok(())
+        };
+        self.pending.drain(..due);
+        // Everything left starts at or after the end of this block
+        for event in self.pending.iter_mut() {
+            event.sample_offset -= num_frames as u32;
+        }
+        sent?;
+
+        plugin.process(inputs, outputs, num_frames)
+    }
+
+    /// Deliver the events due in this block, including scheduled events that
+    /// fall in it, then process it
+    ///
+    /// `transport` describes this block: its position is the block's first
+    /// sample. While the transport is stopped, scheduled events wait. The
+    /// port doesn't advance the transport; the host does that once every
+    /// node has processed the block.
+    ///
+    /// # Errors
+    ///
+    /// As [`process()`](Self::process)
+    pub fn process_with_transport<P: PluginInstance + ?Sized>(
+        &mut self,
+        plugin: &mut P,
+        transport: &Transport,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        if transport.playing {
+            let start = transport.position;
+            let end = start + num_frames.max(1) as u64;
+            let mut i = 0;
+            while i < self.scheduled.len() {
+                let (at, mut event) = self.scheduled[i];
+                if at.is_relative() {
+                    self.scheduled[i].0 = MusicalTime::Beat(transport.anchor(at));
+                }
+                let target = transport.resolve(self.scheduled[i].0);
+                if target < end {
+                    self.scheduled.remove(i);
+                    let offset = target.saturating_sub(start).min(u32::MAX as u64) as u32;
+                    event.sample_offset = event.sample_offset.saturating_add(offset);
+                    self.insert(event);
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        self.process(plugin, inputs, outputs, num_frames)
+    }
+
+    /// Move events queued by senders into the queue without blocking
+    fn collect_inbox(&mut self) {
+        // Trade the empty spare buffer for the inbox's, so neither side ever
+        // allocates or frees
+        match self.inbox.try_lock() {
+            Ok(mut inbox) => std::mem::swap(&mut *inbox, &mut self.spare),
+            Err(_) => return,
+        }
+
+        let mut events = std::mem::take(&mut self.spare);
+        for event in events.drain(..) {
+            self.push(event);
+        }
+        self.spare = events;
+    }
+}
+
+impl Extend<MidiEvent> for EventPort {
+    fn extend<I: IntoIterator<Item = MidiEvent>>(&mut self, events: I) {
+        for event in events {
+            self.push(event);
+        }
+    }
+}
+
+impl Default for EventPort {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Handle for queueing events into an [`EventPort`] from another thread
+///
+/// Cheap to clone. Sending may block briefly, so don't send from the audio
+/// thread; use [`EventPort::push()`] there. Events sent while the port's
+/// inbox is full are dropped.
+#[derive(Clone)]
+pub struct EventSender {
+    inbox: Arc<Mutex<Vec<MidiEvent>>>,
+}
+
+impl EventSender {
+    /// Queue an event for the next block the port processes
+    ///
+    /// `sample_offset` is counted from the start of that block. Returns
+    /// `false` if the inbox is full and the event was dropped.
+    pub fn send(&self, event: MidiEvent) -> bool {
+        self.send_all([event]) == 1
+    }
+
+    /// Queue several events at once, e.g. the pair from [`MidiEvent::note()`]
+    ///
+    /// Returns how many were queued; the rest are dropped once the inbox is
+    /// full.
+    pub fn send_all(&self, events: impl IntoIterator<Item = MidiEvent>) -> usize {
+        let mut inbox = self.inbox.lock().unwrap_or_else(|e| e.into_inner());
+        let room = inbox.capacity() - inbox.len();
+        let before = inbox.len();
+        inbox.extend(events.into_iter().take(room));
+        inbox.len() - before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockPlugin;
+
+    fn offsets(events: &[MidiEvent]) -> Vec<u32> {
+        events.iter().map(|e| e.sample_offset).collect()
+    }
+
+    #[test]
+    fn test_events_carried_across_blocks() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+
+        let mut port = EventPort::new();
+        port.push(MidiEvent::note_on(60, 100, 0, 600));
+        port.push(MidiEvent::note_on(62, 100, 0, 10));
+        port.push(MidiEvent::note_off(62, 0, 0, 100));
+
+        let input = vec![0.0f32; 512];
+        let mut left = vec![0.0f32; 512];
+        let mut right = vec![0.0f32; 512];
+
+        port.process(
+            &mut plugin,
+            &[&input, &input],
+            &mut [&mut left, &mut right],
+            512,
+        )
+        .unwrap();
+        assert_eq!(offsets(&plugin.midi_received), vec![10, 100]);
+        assert_eq!(port.len(), 1);
+
+        plugin.midi_received.clear();
+        port.process(
+            &mut plugin,
+            &[&input, &input],
+            &mut [&mut left, &mut right],
+            512,
+        )
+        .unwrap();
+        assert_eq!(offsets(&plugin.midi_received), vec![88]);
+        assert!(port.is_empty());
+    }
+
+    #[test]
+    fn test_equal_offsets_keep_order() {
+        let mut port = EventPort::new();
+        port.push(MidiEvent::note_off(60, 0, 0, 0));
+        port.push(MidiEvent::note_on(60, 100, 0, 0));
+
+        assert!(matches!(
+            port.pending[0].kind,
+            crate::MidiEventKind::NoteOff { .. }
+        ));
+    }
+
+    #[test]
+    fn test_note_off_lands_in_later_block() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 256).unwrap();
+
+        let mut port = EventPort::new();
+        port.extend(MidiEvent::note(60, 100, 0, 100, 1000));
+
+        let input = vec![0.0f32; 256];
+        let mut left = vec![0.0f32; 256];
+        let mut right = vec![0.0f32; 256];
+        let mut received = Vec::new();
+        for block in 0..5 {
+            port.process(
+                &mut plugin,
+                &[&input, &input],
+                &mut [&mut left, &mut right],
+                256,
+            )
+            .unwrap();
+            for event in plugin.midi_received.drain(..) {
+                received.push((block, event.sample_offset));
+            }
+        }
+
+        // Note Off at 1100 = block 4, offset 76
+        assert_eq!(received, vec![(0, 100), (4, 76)]);
+    }
+
+    #[test]
+    fn test_velocity_curve_applied_on_push() {
+        let mut port = EventPort::new();
+        port.velocity_curve = VelocityCurve::Fixed(90);
+        port.extend(MidiEvent::note(60, 20, 0, 0, 100));
+
+        assert_eq!(port.pending[0], MidiEvent::note_on(60, 90, 0, 0));
+        assert_eq!(port.pending[1], MidiEvent::note_off(60, 64, 0, 100));
+    }
+
+    #[test]
+    fn test_sender() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 64).unwrap();
+
+        let mut port = EventPort::new();
+        let sender = port.sender();
+        std::thread::spawn(move || sender.send(MidiEvent::note_on(60, 100, 0, 5)))
+            .join()
+            .unwrap();
+
+        let input = vec![0.0f32; 64];
+        let mut left = vec![0.0f32; 64];
+        let mut right = vec![0.0f32; 64];
+        port.process(
+            &mut plugin,
+            &[&input, &input],
+            &mut [&mut left, &mut right],
+            64,
+        )
+        .unwrap();
+        assert_eq!(offsets(&plugin.midi_received), vec![5]);
+    }
+
+    #[test]
+    fn test_full_queues_drop_events() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 64).unwrap();
+
+        let mut port = EventPort::with_capacity(2);
+        assert!(port.push(MidiEvent::note_on(60, 100, 0, 0)));
+        assert!(port.push(MidiEvent::note_on(62, 100, 0, 1)));
+        assert!(!port.push(MidiEvent::note_on(64, 100, 0, 2)));
+        assert_eq!(offsets(&port.pending), vec![0, 1]);
+        assert_eq!(port.pending.capacity(), 2);
+
+        let input = vec![0.0f32; 64];
+        let mut left = vec![0.0f32; 64];
+        let mut right = vec![0.0f32; 64];
+        let sender = port.sender();
+        for _ in 0..2 {
+            port.process(
+                &mut plugin,
+                &[&input, &input],
+                &mut [&mut left, &mut right],
+                64,
+            )
+            .unwrap();
+
+            let notes = (0..3).map(|i| MidiEvent::note_on(70 + i, 100, 0, i as u32 + 2));
+            assert_eq!(sender.send_all(notes), 2);
+            assert!(!sender.send(MidiEvent::note_on(80, 100, 0, 0)));
+        }
+        assert_eq!(offsets(&plugin.midi_received), vec![0, 1, 2, 3]);
+
+        // The inbox keeps its capacity after being collected
+        assert_eq!(port.inbox.lock().unwrap().capacity(), 2);
+    }
+
+    #[test]
+    fn test_scheduled_at_musical_positions() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(1000.0, 300).unwrap();
+
+        // 60 BPM at 1 kHz: a beat is 1000 samples, a bar 4000
+        let mut transport = Transport::new(1000.0, crate::tempo::TempoMap::new(60.0));
+        let mut port = EventPort::new();
+        port.schedule(MidiEvent::note_on(60, 100, 0, 0), MusicalTime::NextBar);
+        port.schedule(MidiEvent::note_on(62, 100, 0, 10), MusicalTime::Beat(1.5));
+
+        let input = vec![0.0f32; 300];
+        let mut left = vec![0.0f32; 300];
+        let mut right = vec![0.0f32; 300];
+        let mut received = Vec::new();
+        for block in 0..20 {
+            if block == 2 {
+                transport.playing = true;
+            }
+            port.process_with_transport(
+                &mut plugin,
+                &transport,
+                &[&input, &input],
+                &mut [&mut left, &mut right],
+                300,
+            )
+            .unwrap();
+            transport.advance(300);
+            for event in plugin.midi_received.drain(..) {
+                received.push((transport.position - 300, event.sample_offset));
+            }
+        }
+
+        // Nothing while stopped; the next bar was anchored once playing
+        // started at 0 (the bar line itself), beat 1.5 + 10 lands at 1510
+        assert_eq!(received, vec![(0, 0), (1500, 10)]);
+        assert!(port.is_empty());
+    }
+}
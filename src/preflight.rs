@@ -0,0 +1,385 @@
+//! Dry-run checks for loading a session
+//!
+//! Loading a [`SessionDocument`] instantiates every plugin in it, which is
+//! slow and, when something is missing, fails halfway through with a single
+//! error. [`preflight()`] checks the document against the installed plugins
+//! instead, without loading any of them, and reports every problem at once so
+//! the host can show the user what to expect (or what to install) first.
+//!
+//! Checks:
+//! - Every node's plugin is installed ([`IssueKind::PluginMissing`])
+//! - Installed versions match the saved ones ([`IssueKind::VersionMismatch`])
+//! - The session's sample rate matches the one it will run at
+//!   ([`IssueKind::SampleRateMismatch`])
+//! - Connected nodes agree on channel counts ([`IssueKind::ChannelMismatch`])
+//! - Connections refer to existing nodes, and sidechain sources will produce
+//!   audio ([`IssueKind::UnknownNode`], [`IssueKind::SidechainSourceMissing`])
+//!
+//! Version and channel checks are skipped for nodes that record them as 0
+//! (unknown), e.g. nodes built by hand rather than with
+//! [`add_node()`](crate::session::SessionDocument::add_node).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::session::SessionDocument;
+//! # fn example(scanner: &impl PluginScanner) -> Result<()> {
+//! let session = SessionDocument::from_bytes(&std::fs::read("song.rack")?)?;
+//! let report = rack::preflight::preflight(&session, &scanner.scan()?, 48000.0);
+//!
+//! for issue in &report.issues {
+//!     println!("{:?}: {}", issue.severity(), issue);
+//! }
+//! if report.can_load() {
+//!     // Load with the plugins preflight picked
+//!     for node in &session.nodes {
+//!         let plugin = scanner.load(report.plugin_for(&node.id).unwrap())?;
+//!         // ...
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::session::{NodeState, SessionDocument};
+use crate::PluginInfo;
+use std::fmt;
+
+/// How serious an [`Issue`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The session loads, but may not sound as it did when saved
+    Warning,
+
+    /// Part of the session can't be loaded
+    Error,
+}
+
+/// What a preflight check found
+#[derive(Debug, Clone, PartialEq)]
+pub enum IssueKind {
+    /// No installed plugin matches the node
+    PluginMissing {
+        /// Saved plugin name
+        name: String,
+        /// Saved unique ID
+        unique_id: String,
+    },
+
+    /// The installed plugin's version differs from the one the state was
+    /// saved with
+    ///
+    /// A newer plugin usually reads older states; an older one may not read
+    /// a newer state at all.
+    VersionMismatch {
+        /// Version the state was saved with
+        saved: u32,
+        /// Installed version
+        installed: u32,
+    },
+
+    /// The session was saved at a different sample rate
+    ///
+    /// Plugins that store times in samples will play back faster or slower.
+    SampleRateMismatch {
+        /// Sample rate the session was saved at, in Hz
+        saved: u32,
+        /// Sample rate it will run at, in Hz
+        target: f64,
+    },
+
+    /// A connection's source has a different number of output channels than
+    /// its destination has inputs
+    ChannelMismatch {
+        /// Source node ID
+        from: String,
+        /// Destination node ID
+        to: String,
+        /// Source output channels
+        outputs: usize,
+        /// Destination input channels
+        inputs: usize,
+    },
+
+    /// A connection refers to a node that isn't in the document
+    UnknownNode {
+        /// The missing node ID
+        id: String,
+    },
+
+    /// A sidechain input is fed by a node whose plugin is missing, so it will
+    /// be silent
+    SidechainSourceMissing {
+        /// Source node ID
+        from: String,
+        /// Destination node ID
+        to: String,
+    },
+}
+
+/// One problem found by [`preflight()`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    /// The node concerned, if the issue is about a single node
+    pub node: Option<String>,
+
+    /// What was found
+    pub kind: IssueKind,
+}
+
+impl Issue {
+    /// How serious the issue is
+    pub fn severity(&self) -> Severity {
+        match self.kind {
+            IssueKind::PluginMissing { .. } | IssueKind::UnknownNode { .. } => Severity::Error,
+            IssueKind::VersionMismatch { saved, installed } if installed < saved => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(node) = &self.node {
+            write!(f, "{}: ", node)?;
+        }
+        match &self.kind {
+            IssueKind::PluginMissing { name, unique_id } => {
+                write!(f, "plugin {} ({}) is not installed", name, unique_id)
+            }
+            IssueKind::VersionMismatch { saved, installed } => write!(
+                f,
+                "saved with plugin version {}, installed version is {}",
+                saved, installed
+            ),
+            IssueKind::SampleRateMismatch { saved, target } => write!(
+                f,
+                "session was saved at {} Hz and will run at {} Hz",
+                saved, target
+            ),
+            IssueKind::ChannelMismatch {
+                from,
+                to,
+                outputs,
+                inputs,
+            } => write!(
+                f,
+                "{} has {} output channels but {} has {} inputs",
+                from, outputs, to, inputs
+            ),
+            IssueKind::UnknownNode { id } => write!(f, "connection to unknown node '{}'", id),
+            IssueKind::SidechainSourceMissing { from, to } => write!(
+                f,
+                "sidechain of {} will be silent: source {} is missing",
+                to, from
+            ),
+        }
+    }
+}
+
+/// Result of [`preflight()`]
+#[derive(Debug, Clone, Default)]
+pub struct PreflightReport {
+    /// Problems found, in document order
+    pub issues: Vec<Issue>,
+
+    /// The installed plugin chosen for each node that has one, by node ID
+    pub plugins: Vec<(String, PluginInfo)>,
+}
+
+impl PreflightReport {
+    /// Whether nothing at all was found
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Whether the whole session can be loaded (there are at most warnings)
+    pub fn can_load(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    /// Issues that keep part of the session from loading
+    pub fn errors(&self) -> impl Iterator<Item = &Issue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity() == Severity::Error)
+    }
+
+    /// Issues the session loads despite
+    pub fn warnings(&self) -> impl Iterator<Item = &Issue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity() == Severity::Warning)
+    }
+
+    /// The installed plugin chosen for a node
+    pub fn plugin_for(&self, node_id: &str) -> Option<&PluginInfo> {
+        self.plugins
+            .iter()
+            .find(|(id, _)| id == node_id)
+            .map(|(_, info)| info)
+    }
+}
+
+/// Check a session against the installed `plugins` without loading anything
+///
+/// `sample_rate` is the rate the session is about to run at. When several
+/// installed plugins match a node, the one with the saved version is chosen,
+/// else the newest.
+pub fn preflight(
+    document: &SessionDocument,
+    plugins: &[PluginInfo],
+    sample_rate: f64,
+) -> PreflightReport {
+    let mut report = PreflightReport::default();
+
+    if let Some(saved) = document.sample_rate {
+        if saved as f64 != sample_rate {
+            report.issues.push(Issue {
+                node: None,
+                kind: IssueKind::SampleRateMismatch {
+                    saved,
+                    target: sample_rate,
+                },
+            });
+        }
+    }
+
+    for node in &document.nodes {
+        let Some(info) = choose_plugin(node, plugins) else {
+            report.issues.push(Issue {
+                node: Some(node.id.clone()),
+                kind: IssueKind::PluginMissing {
+                    name: node.name.clone(),
+                    unique_id: node.unique_id.clone(),
+                },
+            });
+            continue;
+        };
+        if node.version != 0 && info.version != node.version {
+            report.issues.push(Issue {
+                node: Some(node.id.clone()),
+                kind: IssueKind::VersionMismatch {
+                    saved: node.version,
+                    installed: info.version,
+                },
+            });
+        }
+        report.plugins.push((node.id.clone(), info.clone()));
+    }
+
+    for connection in &document.connections {
+        let from = document.node(&connection.from);
+        let to = document.node(&connection.to);
+        for id in [&connection.from, &connection.to] {
+            if !document.contains(id) {
+                report.issues.push(Issue {
+                    node: None,
+                    kind: IssueKind::UnknownNode { id: id.clone() },
+                });
+            }
+        }
+        let (Some(from), Some(to)) = (from, to) else {
+            continue;
+        };
+
+        if connection.sidechain {
+            if report.plugin_for(&from.id).is_none() {
+                report.issues.push(Issue {
+                    node: Some(to.id.clone()),
+                    kind: IssueKind::SidechainSourceMissing {
+                        from: from.id.clone(),
+                        to: to.id.clone(),
+                    },
+                });
+            }
+        } else if from.output_channels != 0
+            && to.input_channels != 0
+            && from.output_channels != to.input_channels
+        {
+            report.issues.push(Issue {
+                node: None,
+                kind: IssueKind::ChannelMismatch {
+                    from: from.id.clone(),
+                    to: to.id.clone(),
+                    outputs: from.output_channels,
+                    inputs: to.input_channels,
+                },
+            });
+        }
+    }
+
+    report
+}
+
+/// The installed plugin to load for a node
+fn choose_plugin<'a>(node: &NodeState, plugins: &'a [PluginInfo]) -> Option<&'a PluginInfo> {
+    let mut candidates = plugins.iter().filter(|info| node.matches(info));
+    let newest = candidates.clone().max_by_key(|info| info.version);
+    candidates
+        .find(|info| info.version == node.version)
+        .or(newest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockPlugin;
+    use crate::PluginInstance;
+
+    #[test]
+    fn test_preflight_reports_every_problem() {
+        let mut synth = MockPlugin::new();
+        synth.initialize(48000.0, 64).unwrap();
+        let mut document = SessionDocument::new();
+        document.sample_rate = Some(44100);
+        document.add_node("synth", &synth).unwrap();
+        document.add_node("gone", &synth).unwrap();
+        document.nodes[1].unique_id = "uninstalled".to_string();
+        document.nodes[1].name = "Gone".to_string();
+        document.add_node("mono", &synth).unwrap();
+        document.nodes[2].input_channels = 1;
+        document.connect("synth", "mono");
+        document.connect_sidechain("gone", "synth");
+        document.connect("synth", "nowhere");
+
+        let mut installed = synth.info().clone();
+        installed.version = 2;
+        let report = preflight(&document, &[installed], 48000.0);
+
+        let kinds: Vec<_> = report.issues.iter().map(|issue| &issue.kind).collect();
+        assert!(matches!(
+            kinds[0],
+            IssueKind::SampleRateMismatch { saved: 44100, .. }
+        ));
+        assert!(matches!(
+            kinds[1],
+            IssueKind::VersionMismatch {
+                saved: 1,
+                installed: 2
+            }
+        ));
+        assert!(matches!(kinds[2], IssueKind::PluginMissing { .. }));
+        assert!(matches!(
+            kinds[4],
+            IssueKind::ChannelMismatch {
+                outputs: 2,
+                inputs: 1,
+                ..
+            }
+        ));
+        assert!(matches!(kinds[5], IssueKind::SidechainSourceMissing { .. }));
+        assert!(matches!(kinds[6], IssueKind::UnknownNode { .. }));
+        assert_eq!(report.issues.len(), 7);
+
+        assert!(!report.can_load());
+        assert_eq!(report.errors().count(), 2);
+        assert_eq!(report.plugin_for("synth").unwrap().version, 2);
+        assert!(report.plugin_for("gone").is_none());
+        assert_eq!(
+            report.issues[2].to_string(),
+            "gone: plugin Gone (uninstalled) is not installed"
+        );
+    }
+}
@@ -0,0 +1,494 @@
+//! Background rendering of instrument previews
+//!
+//! "Hover to hear" plugin browsers need a short sample of every instrument
+//! preset, available instantly. A [`PreviewService`] renders a standardized
+//! phrase (an [`Audition`] of a chord) through each requested instrument and
+//! preset on a background thread and caches the result as a small 16-bit WAV
+//! file, so each preview is rendered once and then played from disk.
+//!
+//! Renders run one at a time with a pause between them, so a browser
+//! requesting hundreds of previews doesn't starve the rest of the host.
+//! The most recent request renders first (the preset under the cursor), and
+//! requests can be cancelled when the user moves on.
+//!
+//! Preview files are named after the plugin's format, unique ID, version,
+//! preset and the phrase settings, so an updated plugin or a different
+//! phrase renders fresh previews.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::preview::{PreviewConfig, PreviewService};
+//! # fn example<S>(scanner: S, synth: PluginInfo) -> Result<()>
+//! # where
+//! #     S: PluginScanner + Send + 'static,
+//! #     S::Plugin: 'static,
+//! # {
+//! let previews = PreviewService::start(PreviewConfig::new("/tmp/my-host/previews"), move |info| {
+//!     scanner.load(info)
+//! })?;
+//!
+//! // On hover
+//! if let Some(path) = previews.request(&synth, Some(3)) {
+//!     println!("play {}", path.display());
+//! }
+//!
+//! // Each UI frame
+//! while let Some(rendered) = previews.poll() {
+//!     if let Ok(path) = rendered.result {
+//!         println!("preview ready: {}", path.display());
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::audition::Audition;
+use crate::cache::Fnv1a;
+use crate::{Error, PluginInfo, PluginInstance, Result};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// What to render and where to cache it
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewConfig {
+    /// Directory for preview files (created if missing)
+    pub dir: PathBuf,
+
+    /// Sample rate of the previews, in Hz
+    pub sample_rate: f64,
+
+    /// MIDI notes of the phrase
+    pub notes: Vec<u8>,
+
+    /// Frames from the first Note On to the Note Offs
+    pub duration: usize,
+
+    /// Velocity, strum, release tail and block size of the phrase
+    pub audition: Audition,
+
+    /// Pause between renders
+    pub interval: Duration,
+}
+
+impl PreviewConfig {
+    /// Preview a C major triad held for one second, at 48 kHz, into `dir`
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            sample_rate: 48000.0,
+            notes: vec![60, 64, 67],
+            duration: 48000,
+            audition: Audition::default(),
+            interval: Duration::from_millis(50),
+        }
+    }
+
+    /// Hash of everything that affects the rendered audio
+    fn phrase_hash(&self, hash: &mut Fnv1a) {
+        let audition = &self.audition;
+        hash.write(&self.sample_rate.to_le_bytes());
+        hash.write(&self.notes);
+        hash.write(&(self.duration as u64).to_le_bytes());
+        hash.write(&[audition.velocity, audition.channel]);
+        hash.write(&(audition.strum as u64).to_le_bytes());
+        hash.write(&(audition.tail as u64).to_le_bytes());
+    }
+}
+
+/// An instrument preset to preview
+#[derive(Debug, Clone)]
+pub struct PreviewRequest {
+    /// The instrument
+    pub plugin: PluginInfo,
+
+    /// Preset to load before rendering, or `None` for the default sound
+    pub preset: Option<i32>,
+}
+
+impl PreviewRequest {
+    fn is(&self, plugin: &PluginInfo, preset: Option<i32>) -> bool {
+        self.plugin.format == plugin.format
+            && self.plugin.unique_id == plugin.unique_id
+            && self.preset == preset
+    }
+}
+
+/// A finished render, successful or not
+#[derive(Debug)]
+pub struct RenderedPreview {
+    /// What was rendered
+    pub request: PreviewRequest,
+
+    /// The preview file, or why it couldn't be rendered
+    pub result: Result<PathBuf>,
+}
+
+/// Requests waiting for the render thread
+#[derive(Default)]
+struct Queue {
+    /// Newest first
+    pending: VecDeque<PreviewRequest>,
+
+    /// Set when the request being rendered is cancelled
+    cancelled: bool,
+
+    /// The request being rendered
+    current: Option<PreviewRequest>,
+
+    stopping: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    wake: Condvar,
+}
+
+/// A running preview render thread
+///
+/// Dropping it stops the thread like [`stop()`](Self::stop).
+pub struct PreviewService {
+    config: PreviewConfig,
+    shared: Arc<Shared>,
+    rendered: mpsc::Receiver<RenderedPreview>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PreviewService {
+    /// Start the render thread
+    ///
+    /// `load` is called on the render thread to instantiate a plugin (e.g.
+    /// with [`PluginScanner::load()`](crate::PluginScanner::load)). The last
+    /// plugin loaded is kept and reset between presets, so previewing the
+    /// presets of one instrument in a row loads it once.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory can't be created or the thread can't
+    /// be started
+    pub fn start<P, F>(config: PreviewConfig, load: F) -> Result<Self>
+    where
+        P: PluginInstance + 'static,
+        F: FnMut(&PluginInfo) -> Result<P> + Send + 'static,
+    {
+        std::fs::create_dir_all(&config.dir)?;
+
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue::default()),
+            wake: Condvar::new(),
+        });
+        let (rendered_tx, rendered) = mpsc::channel();
+        let thread = {
+            let config = config.clone();
+            let shared = Arc::clone(&shared);
+            std::thread::Builder::new()
+                .name("rack-preview".to_string())
+                .spawn(move || render_loop(&config, &shared, load, rendered_tx))?
+        };
+
+        Ok(Self {
+            config,
+            shared,
+            rendered,
+            thread: Some(thread),
+        })
+    }
+
+    /// Where the preview of `plugin` with `preset` is cached
+    pub fn preview_path(&self, plugin: &PluginInfo, preset: Option<i32>) -> PathBuf {
+        preview_path(&self.config, plugin, preset)
+    }
+
+    /// The cached preview of `plugin` with `preset`, if it has been rendered
+    pub fn cached(&self, plugin: &PluginInfo, preset: Option<i32>) -> Option<PathBuf> {
+        let path = self.preview_path(plugin, preset);
+        path.is_file().then_some(path)
+    }
+
+    /// Get a preview, queueing a render if it isn't cached
+    ///
+    /// Returns the cached file, or `None` if a render was queued; the result
+    /// arrives through [`poll()`](Self::poll). Queued requests render newest
+    /// first; requesting an already queued preview moves it to the front.
+    pub fn request(&self, plugin: &PluginInfo, preset: Option<i32>) -> Option<PathBuf> {
+        if let Some(path) = self.cached(plugin, preset) {
+            return Some(path);
+        }
+
+        let mut queue = self.shared.queue.lock().unwrap();
+        let rendering = queue.current.as_ref().is_some_and(|r| r.is(plugin, preset));
+        if rendering {
+            queue.cancelled = false;
+        } else {
+            queue.pending.retain(|r| !r.is(plugin, preset));
+            queue.pending.push_front(PreviewRequest {
+                plugin: plugin.clone(),
+                preset,
+            });
+        }
+        self.shared.wake.notify_one();
+        None
+    }
+
+    /// Cancel a queued or rendering preview
+    ///
+    /// A render in progress runs to the end, but its file isn't written and
+    /// no result is reported.
+    pub fn cancel(&self, plugin: &PluginInfo, preset: Option<i32>) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        queue.pending.retain(|r| !r.is(plugin, preset));
+        if queue.current.as_ref().is_some_and(|r| r.is(plugin, preset)) {
+            queue.cancelled = true;
+        }
+    }
+
+    /// Cancel every queued and rendering preview
+    pub fn cancel_all(&self) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        queue.pending.clear();
+        queue.cancelled = queue.current.is_some();
+    }
+
+    /// Number of previews waiting to render, including one in progress
+    pub fn pending(&self) -> usize {
+        let queue = self.shared.queue.lock().unwrap();
+        queue.pending.len() + usize::from(queue.current.is_some() && !queue.cancelled)
+    }
+
+    /// Take the next finished render, without waiting
+    pub fn poll(&self) -> Option<RenderedPreview> {
+        self.rendered.try_recv().ok()
+    }
+
+    /// Stop rendering
+    ///
+    /// Waits for a render in progress to finish. Queued requests are dropped;
+    /// cached previews are kept.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        {
+            let mut queue = self.shared.queue.lock().unwrap();
+            queue.stopping = true;
+            queue.pending.clear();
+        }
+        self.shared.wake.notify_one();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for PreviewService {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn render_loop<P, F>(
+    config: &PreviewConfig,
+    shared: &Shared,
+    mut load: F,
+    rendered: mpsc::Sender<RenderedPreview>,
+) where
+    P: PluginInstance,
+    F: FnMut(&PluginInfo) -> Result<P>,
+{
+    let mut loaded: Option<(PluginInfo, P)> = None;
+    loop {
+        let request = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                if queue.stopping {
+                    return;
+                }
+                if let Some(request) = queue.pending.pop_front() {
+                    queue.current = Some(request.clone());
+                    queue.cancelled = false;
+                    break request;
+                }
+                queue = shared.wake.wait(queue).unwrap();
+            }
+        };
+
+        let path = preview_path(config, &request.plugin, request.preset);
+        let result = render(config, &request, &mut loaded, &mut load);
+
+        let cancelled = {
+            let mut queue = shared.queue.lock().unwrap();
+            queue.current = None;
+            queue.cancelled
+        };
+        if !cancelled {
+            let result = result.and_then(|channels| {
+                write_wav(&path, config.sample_rate, &channels)?;
+                Ok(path)
+            });
+            let _ = rendered.send(RenderedPreview { request, result });
+        }
+
+        // Throttle; new requests wait, stopping doesn't
+        let deadline = Instant::now() + config.interval;
+        let mut queue = shared.queue.lock().unwrap();
+        while !queue.stopping {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            queue = shared.wake.wait_timeout(queue, deadline - now).unwrap().0;
+        }
+    }
+}
+
+/// Render the phrase, reusing the loaded plugin when it is the requested one
+fn render<P, F>(
+    config: &PreviewConfig,
+    request: &PreviewRequest,
+    loaded: &mut Option<(PluginInfo, P)>,
+    load: &mut F,
+) -> Result<Vec<Vec<f32>>>
+where
+    P: PluginInstance,
+    F: FnMut(&PluginInfo) -> Result<P>,
+{
+    let reuse = loaded.as_ref().is_some_and(|(info, _)| {
+        info.format == request.plugin.format && info.unique_id == request.plugin.unique_id
+    });
+    if reuse {
+        let (_, plugin) = loaded.as_mut().unwrap();
+        plugin.reset()?;
+    } else {
+        *loaded = None;
+        let mut plugin = load(&request.plugin)?;
+        plugin.initialize(config.sample_rate, config.audition.block_size)?;
+        *loaded = Some((request.plugin.clone(), plugin));
+    }
+
+    let (_, plugin) = loaded.as_mut().unwrap();
+    if let Some(preset) = request.preset {
+        plugin.load_preset(preset)?;
+    }
+    let output = config
+        .audition
+        .run(plugin, &config.notes, config.duration)?;
+    Ok(output.channels)
+}
+
+fn preview_path(config: &PreviewConfig, plugin: &PluginInfo, preset: Option<i32>) -> PathBuf {
+    let mut hash = Fnv1a::new();
+    hash.write(plugin.format.to_string().as_bytes());
+    hash.write(plugin.unique_id.as_bytes());
+    hash.write(&plugin.version.to_le_bytes());
+    match preset {
+        Some(preset) => hash.write(&preset.to_le_bytes()),
+        None => hash.write(b"default"),
+    }
+    config.phrase_hash(&mut hash);
+    config.dir.join(format!("{:016x}.wav", hash.finish()))
+}
+
+/// Write planar audio as an interleaved 16-bit PCM WAV file
+///
+/// Written to a temporary file first, so readers never see a partial preview.
+fn write_wav(path: &Path, sample_rate: f64, channels: &[Vec<f32>]) -> Result<()> {
+    let num_channels = u16::try_from(channels.len())
+        .ok()
+        .filter(|&n| n > 0)
+        .ok_or_else(|| Error::Other("Preview has no output channels".to_string()))?;
+    let frames = channels[0].len();
+    let data_len = (frames * channels.len() * 2) as u32;
+    let sample_rate = sample_rate.round() as u32;
+    let block_align = num_channels * 2;
+
+    let mut out = Vec::with_capacity(44 + data_len as usize);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    out.extend_from_slice(&num_channels.to_le_bytes());
+    out.extend_from_slice(&sample_rate.to_le_bytes());
+    out.extend_from_slice(&(sample_rate * u32::from(block_align)).to_le_bytes());
+    out.extend_from_slice(&block_align.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for frame in 0..frames {
+        for channel in channels {
+            let sample = (channel[frame].clamp(-1.0, 1.0) * 32767.0).round() as i16;
+            out.extend_from_slice(&sample.to_le_bytes());
+        }
+    }
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, out)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockPlugin;
+
+    fn wait_for(service: &PreviewService) -> RenderedPreview {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            if let Some(rendered) = service.poll() {
+                return rendered;
+            }
+            assert!(Instant::now() < deadline, "preview never rendered");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_renders_and_caches_previews() {
+        let dir = std::env::temp_dir().join(format!("rack-preview-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut config = PreviewConfig::new(&dir);
+        config.duration = 1000;
+        config.audition.tail = 0;
+        // Long enough that the second request waits for it
+        config.interval = Duration::from_secs(60);
+
+        let loads = Arc::new(Mutex::new(0));
+        let counter = Arc::clone(&loads);
+        let service = PreviewService::start(config, move |_| {
+            *counter.lock().unwrap() += 1;
+            Ok(MockPlugin::new())
+        })
+        .unwrap();
+        let info = MockPlugin::new().info().clone();
+
+        assert_eq!(service.request(&info, None), None);
+        let rendered = wait_for(&service);
+        let path = rendered.result.unwrap();
+        let wav = std::fs::read(&path).unwrap();
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(wav.len(), 44 + 1000 * 2 * 2);
+        assert_eq!(service.request(&info, None), Some(path));
+
+        // Queued behind the throttle, then cancelled
+        assert_eq!(service.request(&info, Some(1)), None);
+        assert_eq!(service.pending(), 1);
+        service.cancel(&info, Some(1));
+        assert_eq!(service.pending(), 0);
+
+        // Stopping doesn't wait out the throttle
+        let start = Instant::now();
+        service.stop();
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(dir.read_dir().unwrap().count() == 1);
+        assert_eq!(*loads.lock().unwrap(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
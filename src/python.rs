@@ -0,0 +1,438 @@
+//! Python bindings (the `python` feature)
+//!
+//! Exposes scanning, loading, parameters, MIDI, state and offline rendering
+//! to Python, for scripting and batch work such as rendering datasets through
+//! plugins. Build the extension module with [maturin](https://www.maturin.rs)
+//! (`maturin develop --release` installs it into the current virtualenv);
+//! `pyproject.toml` enables the feature.
+//!
+//! Audio is passed as lists of channels, each a sequence of floats (lists,
+//! `array.array`s or 1-D NumPy arrays). Processing and rendering release the
+//! GIL, so plugins can render on several Python threads at once. Errors raise
+//! `rack.RackError`.
+//!
+//! # Example
+//!
+//! ```python
+//! import rack
+//!
+//! scanner = rack.Scanner()
+//! plugins = scanner.scan()
+//! reverb = scanner.load(next(p for p in plugins if p.name == "Reverb"))
+//! reverb.initialize(48000.0, 512)
+//! reverb.set_parameter(0, 0.8)
+//!
+//! # One second of a click, rendered with two seconds of tail
+//! click = [1.0] + [0.0] * 47999
+//! left, right = reverb.render([click, click], length=3 * 48000)
+//!
+//! # Instruments take MIDI as (frame, bytes) pairs
+//! synth = scanner.load(next(p for p in plugins if p.plugin_type == "instrument"))
+//! synth.initialize(48000.0, 512)
+//! audio = synth.render([], length=48000, midi=[(0, b"\x90\x3c\x64"), (24000, b"\x80\x3c\x00")])
+//! ```
+
+use crate::port::EventPort;
+use crate::render::RenderJob;
+use crate::unified::UnifiedScanner;
+use crate::{BoxedPlugin, Error, MidiEvent, ParameterInfo, PluginInfo, PluginType};
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+create_exception!(
+    rack,
+    RackError,
+    PyException,
+    "Error raised by rack or a hosted plugin"
+);
+
+impl From<Error> for PyErr {
+    fn from(error: Error) -> Self {
+        RackError::new_err(error.to_string())
+    }
+}
+
+/// Finds and loads plugins of every available format
+///
+/// Bound to the thread that created it, as some formats' scanners are.
+#[pyclass(name = "Scanner", module = "rack", unsendable)]
+pub struct PyScanner {
+    scanner: UnifiedScanner,
+}
+
+#[pymethods]
+impl PyScanner {
+    #[new]
+    fn new() -> PyResult<Self> {
+        Ok(Self {
+            scanner: UnifiedScanner::new()?,
+        })
+    }
+
+    /// Add a folder for every format to search
+    fn add_path(&mut self, path: PathBuf) -> PyResult<()> {
+        Ok(self.scanner.add_path(&path)?)
+    }
+
+    /// Scan for plugins
+    fn scan(&self) -> PyResult<Vec<PyPluginInfo>> {
+        let plugins = self.scanner.scan()?;
+        Ok(plugins
+            .into_iter()
+            .map(|info| PyPluginInfo { info })
+            .collect())
+    }
+
+    /// Scan a single file or folder
+    fn scan_path(&self, path: PathBuf) -> PyResult<Vec<PyPluginInfo>> {
+        let plugins = self.scanner.scan_path(&path)?;
+        Ok(plugins
+            .into_iter()
+            .map(|info| PyPluginInfo { info })
+            .collect())
+    }
+
+    /// Load a plugin found by a scan
+    fn load(&self, info: PyRef<'_, PyPluginInfo>) -> PyResult<PyPlugin> {
+        Ok(PyPlugin {
+            plugin: Mutex::new(self.scanner.load(&info.info)?),
+            max_block_size: 0,
+        })
+    }
+}
+
+/// A plugin found by a scan
+#[pyclass(name = "PluginInfo", module = "rack", frozen)]
+pub struct PyPluginInfo {
+    info: PluginInfo,
+}
+
+#[pymethods]
+impl PyPluginInfo {
+    #[getter]
+    fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    #[getter]
+    fn manufacturer(&self) -> &str {
+        &self.info.manufacturer
+    }
+
+    #[getter]
+    fn version(&self) -> u32 {
+        self.info.version
+    }
+
+    /// "effect", "instrument", "mixer", "format_converter", "analyzer",
+    /// "spatial" or "other"
+    #[getter]
+    fn plugin_type(&self) -> &'static str {
+        match self.info.plugin_type {
+            PluginType::Effect => "effect",
+            PluginType::Instrument => "instrument",
+            PluginType::Mixer => "mixer",
+            PluginType::FormatConverter => "format_converter",
+            PluginType::Analyzer => "analyzer",
+            PluginType::Spatial => "spatial",
+            PluginType::Other => "other",
+        }
+    }
+
+    /// "AU", "VST3", "CLAP", "LV2" or "VST2"
+    #[getter]
+    fn format(&self) -> String {
+        self.info.format.to_string()
+    }
+
+    #[getter]
+    fn path(&self) -> PathBuf {
+        self.info.path.clone()
+    }
+
+    #[getter]
+    fn unique_id(&self) -> &str {
+        &self.info.unique_id
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<PluginInfo {}>", self.info)
+    }
+}
+
+/// A parameter of a loaded plugin
+#[pyclass(name = "ParameterInfo", module = "rack", frozen, get_all)]
+pub struct PyParameterInfo {
+    index: usize,
+    name: String,
+    min: f32,
+    max: f32,
+    default: f32,
+    unit: String,
+}
+
+impl From<ParameterInfo> for PyParameterInfo {
+    fn from(info: ParameterInfo) -> Self {
+        Self {
+            index: info.index,
+            name: info.name,
+            min: info.min,
+            max: info.max,
+            default: info.default,
+            unit: info.unit,
+        }
+    }
+}
+
+#[pymethods]
+impl PyParameterInfo {
+    fn __repr__(&self) -> String {
+        format!("<ParameterInfo {} {:?}>", self.index, self.name)
+    }
+}
+
+/// A loaded plugin
+#[pyclass(name = "Plugin", module = "rack")]
+pub struct PyPlugin {
+    /// Python already serializes access; the mutex makes the class `Sync`
+    plugin: Mutex<BoxedPlugin>,
+    max_block_size: usize,
+}
+
+impl PyPlugin {
+    fn plugin(&self) -> MutexGuard<'_, BoxedPlugin> {
+        self.plugin.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[pymethods]
+impl PyPlugin {
+    #[getter]
+    fn name(&self) -> String {
+        self.plugin().info().name.clone()
+    }
+
+    #[getter]
+    fn info(&self) -> PyPluginInfo {
+        PyPluginInfo {
+            info: self.plugin().info().clone(),
+        }
+    }
+
+    #[getter]
+    fn is_initialized(&self) -> bool {
+        self.plugin().is_initialized()
+    }
+
+    #[getter]
+    fn input_channels(&self) -> usize {
+        self.plugin().input_channels()
+    }
+
+    #[getter]
+    fn output_channels(&self) -> usize {
+        self.plugin().output_channels()
+    }
+
+    #[getter]
+    fn latency_samples(&self) -> usize {
+        self.plugin().latency_samples()
+    }
+
+    /// How long the output rings out, `math.inf` if it never stops
+    #[getter]
+    fn tail_seconds(&self) -> f64 {
+        self.plugin().tail_seconds()
+    }
+
+    /// Prepare for processing at `sample_rate`, in blocks of up to `max_block_size`
+    fn initialize(&mut self, sample_rate: f64, max_block_size: usize) -> PyResult<()> {
+        self.plugin().initialize(sample_rate, max_block_size)?;
+        self.max_block_size = max_block_size;
+        Ok(())
+    }
+
+    /// Clear delay lines, reverb tails and held notes
+    fn reset(&mut self) -> PyResult<()> {
+        Ok(self.plugin().reset()?)
+    }
+
+    fn parameters(&self) -> PyResult<Vec<PyParameterInfo>> {
+        Ok(self
+            .plugin()
+            .parameters()?
+            .into_iter()
+            .map(PyParameterInfo::from)
+            .collect())
+    }
+
+    fn get_parameter(&self, index: usize) -> PyResult<f32> {
+        Ok(self.plugin().get_parameter(index)?)
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) -> PyResult<()> {
+        Ok(self.plugin().set_parameter(index, value)?)
+    }
+
+    /// Queue MIDI for the next `process()` call, as `(frame, bytes)` pairs
+    fn send_midi(&mut self, events: Vec<(u32, Vec<u8>)>) -> PyResult<()> {
+        let events = midi_events(&events)?;
+        Ok(self.plugin().send_midi(&events)?)
+    }
+
+    /// Process one block and return the output channels
+    ///
+    /// `num_frames` defaults to the length of the input channels, and must
+    /// be given for plugins without inputs.
+    #[pyo3(signature = (inputs, num_frames = None))]
+    fn process(
+        &mut self,
+        py: Python<'_>,
+        inputs: Vec<Vec<f32>>,
+        num_frames: Option<usize>,
+    ) -> PyResult<Vec<Vec<f32>>> {
+        let num_frames = frames(&inputs, num_frames)?;
+        let mut outputs = vec![vec![0.0f32; num_frames]; self.plugin().output_channels()];
+        let plugin = self.plugin.get_mut().unwrap_or_else(|e| e.into_inner());
+        py.allow_threads(|| {
+            let inputs: Vec<&[f32]> = inputs.iter().map(Vec::as_slice).collect();
+            let mut output_slices: Vec<&mut [f32]> =
+                outputs.iter_mut().map(Vec::as_mut_slice).collect();
+            plugin.process(&inputs, &mut output_slices, num_frames)
+        })?;
+        Ok(outputs)
+    }
+
+    /// Render a whole signal offline and return the output channels
+    ///
+    /// The input is zero-padded up to `length` frames (its own length by
+    /// default) so tails ring out. `midi` is a list of `(frame, bytes)` pairs
+    /// counted from the start of the render. Blocks default to the size the
+    /// plugin was initialized with.
+    #[pyo3(signature = (inputs, length = None, midi = None, block_size = None))]
+    fn render(
+        &mut self,
+        py: Python<'_>,
+        inputs: Vec<Vec<f32>>,
+        length: Option<usize>,
+        midi: Option<Vec<(u32, Vec<u8>)>>,
+        block_size: Option<usize>,
+    ) -> PyResult<Vec<Vec<f32>>> {
+        let length = frames(&inputs, length)?;
+        let events = midi_events(&midi.unwrap_or_default())?;
+        let mut port = EventPort::with_capacity(events.len());
+        port.extend(events);
+        let job = RenderJob::new(block_size.unwrap_or(self.max_block_size));
+        let plugin = self.plugin.get_mut().unwrap_or_else(|e| e.into_inner());
+        let output = py.allow_threads(|| {
+            let inputs: Vec<&[f32]> = inputs.iter().map(Vec::as_slice).collect();
+            job.run_with_events(plugin, &inputs, length, &mut port)
+        })?;
+        Ok(output.channels)
+    }
+
+    /// The plugin's complete state
+    fn get_state<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        Ok(PyBytes::new(py, &self.plugin().get_state()?))
+    }
+
+    /// Restore state from `get_state()`
+    fn set_state(&mut self, data: Vec<u8>) -> PyResult<()> {
+        Ok(self.plugin().set_state(&data)?)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Plugin {}>", self.plugin().info())
+    }
+}
+
+/// Frame count of a call: `given`, or else the length of the inputs
+fn frames(inputs: &[Vec<f32>], given: Option<usize>) -> PyResult<usize> {
+    match (given, inputs.first()) {
+        (Some(frames), _) => Ok(frames),
+        (None, Some(channel)) => Ok(channel.len()),
+        (None, None) => Err(RackError::new_err(
+            "the number of frames is needed when there are no inputs",
+        )),
+    }
+}
+
+fn midi_events(events: &[(u32, Vec<u8>)]) -> PyResult<Vec<MidiEvent>> {
+    events
+        .iter()
+        .map(|(frame, bytes)| {
+            MidiEvent::from_bytes(bytes, *frame)
+                .ok_or_else(|| RackError::new_err(format!("not a MIDI message: {:02x?}", bytes)))
+        })
+        .collect()
+}
+
+/// The `rack` Python module
+#[pymodule]
+fn rack(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("RackError", m.py().get_type::<RackError>())?;
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    m.add_class::<PyScanner>()?;
+    m.add_class::<PyPluginInfo>()?;
+    m.add_class::<PyParameterInfo>()?;
+    m.add_class::<PyPlugin>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clap::test_plugin;
+    use pyo3::types::PyDict;
+
+    #[test]
+    fn test_scan_load_and_render_from_python() {
+        let dir = std::env::temp_dir().join(format!("rack-python-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let _gain = test_plugin::install(&dir.join("gain.clap"));
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| -> PyResult<()> {
+            let module = PyModule::new(py, "rack")?;
+            rack(&module)?;
+            let locals = PyDict::new(py);
+            locals.set_item("rack", module)?;
+            locals.set_item("path", &dir)?;
+            py.run(
+                cr#"
+scanner = rack.Scanner()
+plugins = scanner.scan_path(path)
+assert [(p.name, p.format) for p in plugins] == [("Test Gain", "CLAP")]
+
+plugin = scanner.load(plugins[0])
+plugin.initialize(48000.0, 64)
+assert plugin.output_channels == 2
+plugin.set_parameter(0, 0.25)
+state = plugin.get_state()
+
+left, right = plugin.render([[1.0] * 100, [1.0] * 100], length=150)
+assert len(left) == 150 and left == right
+assert left[0] == 0.5 and left[149] == 0.0
+
+plugin.set_parameter(0, 1.0)
+plugin.set_state(state)
+assert plugin.get_parameter(0) == 0.25
+try:
+    plugin.get_parameter(99)
+    raise AssertionError("no error")
+except rack.RackError:
+    pass
+"#,
+                None,
+                Some(&locals),
+            )
+        })
+        .unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,275 @@
+//! Compatibility shims for plugins with known-broken behavior
+//!
+//! Some plugins misbehave in ways a spec-compliant host doesn't expect: they leave
+//! garbage in their outputs or ignore restored state until something else
+//! happens first. Rather than scattering special cases through the backends,
+//! those behaviors are described as [`Quirk`]s in a table matched against
+//! [`PluginInfo`]. Backends look up a plugin's quirks when it is loaded and work
+//! around them; they are also reported to the host through
+//! [`PluginInstance::quirks()`](crate::PluginInstance::quirks).
+//!
+//! Entries can also choose a plugin's process [`Isolation`], e.g. to run all
+//! plugins of a crash-prone vendor in one helper process.
+//!
+//! Hosts can add their own entries with [`register()`]:
+//!
+//! ```
+//! use rack::quirks::{self, Quirk, QuirkEntry};
+//!
+//! quirks::register(
+//!     QuirkEntry::new("Acme*", "Reverb ?")
+//!         .quirk(Quirk::ClearOutputsBeforeProcess)
+//!         .quirk(Quirk::ResetAfterStateRestore),
+//! );
+//! ```
+
+use crate::isolation::Isolation;
+use crate::scan::wildcard_match;
+use crate::{PluginFormat, PluginInfo};
+use std::sync::RwLock;
+
+/// A known-broken plugin behavior and how to work around it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Quirk {
+    /// The plugin doesn't write every output sample, leaving whatever was in the
+    /// buffer before. Handled by the backends: outputs are zeroed before each
+    /// `process()` call.
+    ClearOutputsBeforeProcess,
+
+    /// The plugin only applies restored state after being reset. Handled by the
+    /// backends: `set_state()` resets the plugin after a successful restore.
+    ResetAfterStateRestore,
+
+    /// The plugin ignores state restored before its GUI has been created.
+    /// Handled by the backends with a GUI (AudioUnit): state restored before
+    /// the first `create_gui()` is applied again once the GUI exists.
+    GuiBeforeStateRestore,
+}
+
+/// The set of quirks that apply to a plugin
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Quirks {
+    quirks: Vec<Quirk>,
+    isolation: Option<Isolation>,
+}
+
+impl Quirks {
+    /// An empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check whether a quirk applies
+    pub fn contains(&self, quirk: Quirk) -> bool {
+        self.quirks.contains(&quirk)
+    }
+
+    /// Check whether no quirks apply
+    pub fn is_empty(&self) -> bool {
+        self.quirks.is_empty()
+    }
+
+    /// Iterate over the quirks, in a stable order
+    pub fn iter(&self) -> impl Iterator<Item = Quirk> + '_ {
+        self.quirks.iter().copied()
+    }
+
+    /// Process isolation chosen by the matching entries, if any
+    pub fn isolation(&self) -> Option<&Isolation> {
+        self.isolation.as_ref()
+    }
+
+    fn insert(&mut self, quirk: Quirk) {
+        if let Err(pos) = self.quirks.binary_search(&quirk) {
+            self.quirks.insert(pos, quirk);
+        }
+    }
+}
+
+/// A row in the quirks table
+///
+/// Manufacturer and name are case-insensitive wildcard patterns (`*` matches
+/// any run of characters, `?` matches one).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuirkEntry {
+    /// Manufacturer pattern
+    pub manufacturer: String,
+
+    /// Plugin name pattern
+    pub name: String,
+
+    /// Only match plugins in this format (`None` matches every format)
+    pub format: Option<PluginFormat>,
+
+    /// Quirks that apply to matching plugins
+    pub quirks: Vec<Quirk>,
+
+    /// Process isolation for matching plugins
+    pub isolation: Option<Isolation>,
+}
+
+impl QuirkEntry {
+    /// Create an entry matching plugins by manufacturer and name pattern
+    pub fn new(manufacturer: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            manufacturer: manufacturer.into(),
+            name: name.into(),
+            format: None,
+            quirks: Vec::new(),
+            isolation: None,
+        }
+    }
+
+    /// Only match plugins in `format`
+    pub fn format(mut self, format: PluginFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Add a quirk to the entry
+    pub fn quirk(mut self, quirk: Quirk) -> Self {
+        self.quirks.push(quirk);
+        self
+    }
+
+    /// Run matching plugins with this process isolation
+    pub fn isolation(mut self, isolation: Isolation) -> Self {
+        self.isolation = Some(isolation);
+        self
+    }
+
+    /// Check whether the entry applies to a plugin
+    pub fn matches(&self, info: &PluginInfo) -> bool {
+        matches_entry(&self.manufacturer, &self.name, self.format, info)
+    }
+}
+
+/// Built-in entries: (manufacturer, name, format, quirks)
+///
+/// Add a row here when a plugin is confirmed to need a workaround.
+const BUILTIN_QUIRKS: &[(&str, &str, Option<PluginFormat>, &[Quirk])] = &[];
+
+/// Entries added at runtime with [`register()`]
+static REGISTERED_QUIRKS: RwLock<Vec<QuirkEntry>> = RwLock::new(Vec::new());
+
+/// Add an entry to the quirks table
+///
+/// Applies to plugins loaded after the call; already loaded instances keep the
+/// quirks they were loaded with.
+pub fn register(entry: QuirkEntry) {
+    REGISTERED_QUIRKS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(entry);
+}
+
+/// Remove all entries added with [`register()`]
+///
+/// The built-in entries are unaffected.
+pub fn clear_registered() {
+    REGISTERED_QUIRKS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .clear();
+}
+
+/// Look up the quirks that apply to a plugin
+///
+/// Combines every matching built-in and registered entry. If several entries
+/// choose an isolation, the one registered last wins.
+pub fn lookup(info: &PluginInfo) -> Quirks {
+    let mut quirks = Quirks::new();
+
+    for &(manufacturer, name, format, entry_quirks) in BUILTIN_QUIRKS {
+        if matches_entry(manufacturer, name, format, info) {
+            entry_quirks.iter().for_each(|&q| quirks.insert(q));
+        }
+    }
+
+    let registered = REGISTERED_QUIRKS.read().unwrap_or_else(|e| e.into_inner());
+    for entry in registered.iter().filter(|e| e.matches(info)) {
+        entry.quirks.iter().for_each(|&q| quirks.insert(q));
+        if let Some(isolation) = &entry.isolation {
+            quirks.isolation = Some(isolation.clone());
+        }
+    }
+
+    quirks
+}
+
+fn matches_entry(
+    manufacturer: &str,
+    name: &str,
+    format: Option<PluginFormat>,
+    info: &PluginInfo,
+) -> bool {
+    format.is_none_or(|f| f == info.format)
+        && wildcard_match(
+            &manufacturer.to_lowercase(),
+            &info.manufacturer.to_lowercase(),
+        )
+        && wildcard_match(&name.to_lowercase(), &info.name.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PluginType;
+    use std::path::PathBuf;
+
+    fn plugin(name: &str, manufacturer: &str, format: PluginFormat) -> PluginInfo {
+        PluginInfo::new(
+            name.to_string(),
+            manufacturer.to_string(),
+            1,
+            PluginType::Effect,
+            PathBuf::new(),
+            format!("{}:{}", manufacturer, name),
+        )
+        .with_format(format)
+    }
+
+    #[test]
+    fn test_quirk_entry_matching() {
+        let entry = QuirkEntry::new("acme*", "Verb ?")
+            .format(PluginFormat::Vst3)
+            .quirk(Quirk::ResetAfterStateRestore);
+
+        assert!(entry.matches(&plugin("Verb 2", "Acme Audio", PluginFormat::Vst3)));
+        assert!(!entry.matches(&plugin("Verb 2", "Acme Audio", PluginFormat::AudioUnit)));
+        assert!(!entry.matches(&plugin("Verb 10", "Acme Audio", PluginFormat::Vst3)));
+    }
+
+    #[test]
+    fn test_quirks_set_is_sorted_and_deduplicated() {
+        let mut quirks = Quirks::new();
+        quirks.insert(Quirk::GuiBeforeStateRestore);
+        quirks.insert(Quirk::ClearOutputsBeforeProcess);
+        quirks.insert(Quirk::GuiBeforeStateRestore);
+
+        assert_eq!(
+            quirks.iter().collect::<Vec<_>>(),
+            vec![
+                Quirk::ClearOutputsBeforeProcess,
+                Quirk::GuiBeforeStateRestore
+            ]
+        );
+        assert!(quirks.contains(Quirk::ClearOutputsBeforeProcess));
+        assert!(!quirks.contains(Quirk::ResetAfterStateRestore));
+    }
+
+    #[test]
+    fn test_register_and_lookup() {
+        // Use a manufacturer no other test registers, since the table is global
+        let info = plugin("Broken", "Quirks Test Co", PluginFormat::AudioUnit);
+        assert!(lookup(&info).is_empty());
+
+        register(QuirkEntry::new("Quirks Test Co", "*").quirk(Quirk::ClearOutputsBeforeProcess));
+        register(QuirkEntry::new("quirks test co", "Broken").quirk(Quirk::GuiBeforeStateRestore));
+
+        let quirks = lookup(&info);
+        assert!(quirks.contains(Quirk::ClearOutputsBeforeProcess));
+        assert!(quirks.contains(Quirk::GuiBeforeStateRestore));
+        assert!(lookup(&plugin("Broken", "Other", PluginFormat::AudioUnit)).is_empty());
+    }
+}
@@ -0,0 +1,424 @@
+//! Offline rendering
+//!
+//! [`RenderJob`] runs a signal through a plugin faster than realtime, block by
+//! block, and collects the output. Long renders can be made tolerant of the
+//! occasional transient plugin error with an [`ErrorPolicy`], so one bad block
+//! doesn't throw away a render that was 90% done.
+//!
+//! Renders always run on the calling thread in blocks of the job's fixed
+//! size. Setting [`deterministic`](RenderJob::deterministic) also resets the
+//! plugin first and fixes denormal handling, so repeated renders of the same
+//! input through the same plugin state are bit-identical (provided the plugin
+//! itself is deterministic) and can be compared by
+//! [`checksum()`](RenderOutput::checksum).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::render::{ErrorPolicy, RenderJob};
+//! # fn example(mut plugin: impl PluginInstance, left: Vec<f32>, right: Vec<f32>) -> Result<()> {
+//! plugin.initialize(48000.0, 512)?;
+//!
+//! let mut job = RenderJob::new(512);
+//! job.error_policy = ErrorPolicy::RetryAfterReset;
+//!
+//! let output = job.run(&mut plugin, &[&left, &right], left.len())?;
+//! for dropout in &output.dropouts {
+//!     eprintln!("block at frame {} failed: {}", dropout.frame, dropout.error);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::cache::Fnv1a;
+use crate::denormal::DenormalGuard;
+use crate::events::{self, HostEvent};
+use crate::port::EventPort;
+use crate::{Error, PluginInstance, Result};
+
+/// What to do when the plugin returns an error for a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Stop rendering and return the error
+    #[default]
+    Abort,
+
+    /// Output silence for the failed block, record it and keep going
+    SubstituteSilence,
+
+    /// Reset the plugin and process the block again; abort if it fails twice
+    RetryAfterReset,
+}
+
+/// How a failed block was dealt with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropoutAction {
+    /// The block was replaced with silence
+    Silenced,
+
+    /// The block succeeded after resetting the plugin
+    Retried,
+}
+
+/// A block that failed during rendering
+#[derive(Debug)]
+pub struct Dropout {
+    /// First frame of the failed block
+    pub frame: usize,
+
+    /// Number of frames in the block
+    pub frames: usize,
+
+    /// The error the plugin returned
+    pub error: Error,
+
+    /// What the render job did about it
+    pub action: DropoutAction,
+}
+
+/// Output of a [`RenderJob`]
+#[derive(Debug)]
+pub struct RenderOutput {
+    /// Rendered audio, one buffer per output channel
+    pub channels: Vec<Vec<f32>>,
+
+    /// Blocks that failed and were recovered according to the error policy
+    pub dropouts: Vec<Dropout>,
+}
+
+impl RenderOutput {
+    /// Hash of the rendered samples' exact bit patterns
+    ///
+    /// Equal for bit-identical renders, so exports can be verified by
+    /// comparing checksums.
+    pub fn checksum(&self) -> u64 {
+        let mut hash = Fnv1a::new();
+        for channel in &self.channels {
+            let bytes: Vec<u8> = channel.iter().flat_map(|s| s.to_le_bytes()).collect();
+            hash.write(&bytes);
+        }
+        hash.finish()
+    }
+}
+
+/// Offline render of an input signal through a plugin
+#[derive(Debug, Clone)]
+pub struct RenderJob {
+    /// Frames per `process()` call (must be ≤ the plugin's max_block_size)
+    pub block_size: usize,
+
+    /// How to handle blocks the plugin fails to process
+    pub error_policy: ErrorPolicy,
+
+    /// Reset the plugin before rendering and flush denormals to zero while
+    /// rendering, so the result doesn't depend on earlier processing or on
+    /// the calling thread's floating-point settings
+    pub deterministic: bool,
+}
+
+impl RenderJob {
+    /// Create a render job with the given block size and the default
+    /// [`ErrorPolicy::Abort`] policy
+    pub fn new(block_size: usize) -> Self {
+        Self {
+            block_size,
+            error_policy: ErrorPolicy::default(),
+            deterministic: false,
+        }
+    }
+
+    /// Render `input` through `plugin`
+    ///
+    /// The input is zero-padded up to `length` frames, which lets reverb and
+    /// delay tails ring out past the end of the input.
+    ///
+    /// # Arguments
+    ///
+    /// * `plugin` - An initialized plugin
+    /// * `input` - Planar input signal, one slice per plugin input channel
+    /// * `length` - Number of frames to render
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The plugin is not initialized
+    /// - `input` doesn't match the plugin's input channel count
+    /// - The block size is zero
+    /// - A block fails and the error policy doesn't recover from it
+    pub fn run<P: PluginInstance + ?Sized>(
+        &self,
+        plugin: &mut P,
+        input: &[&[f32]],
+        length: usize,
+    ) -> Result<RenderOutput> {
+        self.render(plugin, input, length, None)
+    }
+
+    /// Render `input` through `plugin`, delivering MIDI from `events`
+    ///
+    /// Events queued in the port are delivered in the block they fall in, with
+    /// offsets counted from the start of the render. Events beyond `length`
+    /// stay queued.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`run()`](Self::run).
+    pub fn run_with_events<P: PluginInstance + ?Sized>(
+        &self,
+        plugin: &mut P,
+        input: &[&[f32]],
+        length: usize,
+        events: &mut EventPort,
+    ) -> Result<RenderOutput> {
+        self.render(plugin, input, length, Some(events))
+    }
+
+    fn render<P: PluginInstance + ?Sized>(
+        &self,
+        plugin: &mut P,
+        input: &[&[f32]],
+        length: usize,
+        mut events: Option<&mut EventPort>,
+    ) -> Result<RenderOutput> {
+        if !plugin.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+        if self.block_size == 0 {
+            return Err(Error::Other(
+                "Render block size must be non-zero".to_string(),
+            ));
+        }
+
+        let num_inputs = plugin.input_channels();
+        let num_outputs = plugin.output_channels();
+        if input.len() != num_inputs {
+            return Err(Error::Other(format!(
+                "Input has {} channels, plugin expects {}",
+                input.len(),
+                num_inputs
+            )));
+        }
+
+        let _denormals = self.deterministic.then(DenormalGuard::flush_to_zero);
+        if self.deterministic {
+            plugin.reset()?;
+        }
+
+        let mut inputs = vec![vec![0.0f32; self.block_size]; num_inputs];
+        let mut outputs = vec![vec![0.0f32; self.block_size]; num_outputs];
+        let mut channels = vec![Vec::with_capacity(length); num_outputs];
+        let mut dropouts = Vec::new();
+
+        let mut position = 0;
+        while position < length {
+            let frames = self.block_size.min(length - position);
+
+            for (buffer, source) in inputs.iter_mut().zip(input) {
+                buffer.fill(0.0);
+                if position < source.len() {
+                    let available = frames.min(source.len() - position);
+                    buffer[..available].copy_from_slice(&source[position..position + available]);
+                }
+            }
+
+            let result =
+                process_block(plugin, &inputs, &mut outputs, frames, events.as_deref_mut());
+            if let Err(error) = result {
+                let action = match self.error_policy {
+                    ErrorPolicy::Abort => return Err(error),
+                    ErrorPolicy::SubstituteSilence => {
+                        outputs.iter_mut().for_each(|b| b[..frames].fill(0.0));
+                        DropoutAction::Silenced
+                    }
+                    ErrorPolicy::RetryAfterReset => {
+                        plugin.reset()?;
+                        // The block's events were already delivered
+                        process_block(plugin, &inputs, &mut outputs, frames, None)?;
+                        DropoutAction::Retried
+                    }
+                };
+                events::emit(HostEvent::Error {
+                    info: Some(plugin.info()),
+                    error: &error,
+                });
+                dropouts.push(Dropout {
+                    frame: position,
+                    frames,
+                    error,
+                    action,
+                });
+            }
+
+            for (channel, output) in channels.iter_mut().zip(outputs.iter()) {
+                channel.extend_from_slice(&output[..frames]);
+            }
+            position += frames;
+        }
+
+        Ok(RenderOutput { channels, dropouts })
+    }
+}
+
+fn process_block<P: PluginInstance + ?Sized>(
+    plugin: &mut P,
+    inputs: &[Vec<f32>],
+    outputs: &mut [Vec<f32>],
+    frames: usize,
+    events: Option<&mut EventPort>,
+) -> Result<()> {
+    let input_refs: Vec<&[f32]> = inputs.iter().map(|b| &b[..frames]).collect();
+    let mut output_refs: Vec<&mut [f32]> = outputs.iter_mut().map(|b| &mut b[..frames]).collect();
+    match events {
+        Some(port) => port.process(plugin, &input_refs, &mut output_refs, frames),
+        None => plugin.process(&input_refs, &mut output_refs, frames),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockPlugin;
+
+    fn ramp(frames: usize) -> Vec<f32> {
+        (0..frames).map(|i| i as f32 / frames as f32).collect()
+    }
+
+    #[test]
+    fn test_render_pads_to_length() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+
+        let input = ramp(100);
+        let output = RenderJob::new(64)
+            .run(&mut plugin, &[&input, &input], 300)
+            .unwrap();
+
+        assert_eq!(output.channels.len(), 2);
+        assert_eq!(output.channels[0].len(), 300);
+        assert_eq!(&output.channels[0][..100], &input[..]);
+        assert!(output.channels[0][100..].iter().all(|&s| s == 0.0));
+        assert!(output.dropouts.is_empty());
+    }
+
+    #[test]
+    fn test_abort_policy() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+        plugin.fail_blocks = vec![2];
+
+        let input = ramp(256);
+        let result = RenderJob::new(64).run(&mut plugin, &[&input, &input], 256);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_substitute_silence_policy() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+        plugin.fail_blocks = vec![2];
+
+        let mut job = RenderJob::new(64);
+        job.error_policy = ErrorPolicy::SubstituteSilence;
+
+        let input = vec![1.0f32; 256];
+        let output = job.run(&mut plugin, &[&input, &input], 256).unwrap();
+
+        assert_eq!(output.dropouts.len(), 1);
+        assert_eq!(output.dropouts[0].frame, 128);
+        assert_eq!(output.dropouts[0].action, DropoutAction::Silenced);
+        assert!(output.channels[0][128..192].iter().all(|&s| s == 0.0));
+        assert!(output.channels[0][192..].iter().all(|&s| s == 1.0));
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+        plugin.fail_blocks = vec![1];
+
+        let mut job = RenderJob::new(64);
+        job.error_policy = ErrorPolicy::RetryAfterReset;
+
+        let input = vec![1.0f32; 256];
+        let output = job.run(&mut plugin, &[&input, &input], 256).unwrap();
+
+        assert_eq!(output.dropouts.len(), 1);
+        assert_eq!(output.dropouts[0].action, DropoutAction::Retried);
+        assert!(output.channels[0].iter().all(|&s| s == 1.0));
+    }
+
+    #[test]
+    fn test_retry_policy_aborts_on_repeated_failure() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+        plugin.fail_blocks = vec![1, 2];
+
+        let mut job = RenderJob::new(64);
+        job.error_policy = ErrorPolicy::RetryAfterReset;
+
+        let input = vec![1.0f32; 256];
+        assert!(job.run(&mut plugin, &[&input, &input], 256).is_err());
+    }
+
+    #[test]
+    fn test_block_size_above_max() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 32).unwrap();
+
+        let input = vec![1.0f32; 256];
+        let result = RenderJob::new(64).run(&mut plugin, &[&input, &input], 256);
+        assert!(matches!(
+            result,
+            Err(Error::BlockTooLarge { max: 32, got: 64 })
+        ));
+    }
+
+    #[test]
+    fn test_zero_block_size() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+        assert!(RenderJob::new(0).run(&mut plugin, &[&[], &[]], 10).is_err());
+    }
+
+    #[test]
+    fn test_run_with_events() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+
+        let mut port = EventPort::new();
+        port.extend(crate::MidiEvent::note(60, 100, 0, 10, 100));
+        port.push(crate::MidiEvent::note_on(62, 100, 0, 1000));
+
+        RenderJob::new(64)
+            .run_with_events(&mut plugin, &[&[], &[]], 256, &mut port)
+            .unwrap();
+
+        let offsets: Vec<u32> = plugin
+            .midi_received
+            .iter()
+            .map(|e| e.sample_offset)
+            .collect();
+        assert_eq!(offsets, vec![10, 46]);
+        assert_eq!(port.len(), 1);
+    }
+
+    #[test]
+    fn test_deterministic_renders_match() {
+        // The delay line carries state from one render into the next
+        let mut plugin = MockPlugin::new().with_latency(32);
+        plugin.initialize(48000.0, 512).unwrap();
+        let input = ramp(200);
+
+        let mut job = RenderJob::new(64);
+        let first = job.run(&mut plugin, &[&input, &input], 200).unwrap();
+        let second = job.run(&mut plugin, &[&input, &input], 200).unwrap();
+        assert_ne!(first.checksum(), second.checksum());
+
+        job.deterministic = true;
+        let first = job.run(&mut plugin, &[&input, &input], 200).unwrap();
+        let second = job.run(&mut plugin, &[&input, &input], 200).unwrap();
+        assert_eq!(first.checksum(), second.checksum());
+        assert_eq!(first.channels, second.channels);
+    }
+}
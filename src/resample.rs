@@ -0,0 +1,713 @@
+//! Falling back to another sample rate when a plugin refuses the host's
+//!
+//! Some plugins refuse to initialize at high sample rates (192 kHz is the
+//! usual casualty). Rather than making every host catch that error and
+//! re-initialize by hand, [`SampleRateFallback`] retries at a list of fallback
+//! rates and, if one of them works, runs the plugin at that rate behind a
+//! resampler. The host keeps processing at its own rate and can ask what was
+//! [negotiated](SampleRateFallback::negotiated).
+//!
+//! The resampler is a shim to keep audio flowing, not a mastering-grade
+//! converter: it interpolates linearly (content near the lower Nyquist
+//! frequency aliases), and adds a few samples of latency, reported by
+//! [`added_latency()`](SampleRateFallback::added_latency).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::resample::SampleRateFallback;
+//! # fn example(plugin: impl PluginInstance) -> Result<()> {
+//! let mut plugin = SampleRateFallback::new(plugin);
+//! plugin.initialize(192000.0, 512)?;
+//!
+//! if let Some(negotiated) = plugin.negotiated() {
+//!     if negotiated.is_resampled() {
+//!         println!("running at {} Hz behind a resampler", negotiated.plugin_rate);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::meter::MeterReading;
+use crate::node::BlockContext;
+use crate::quirks::Quirks;
+use crate::{
+    CurrentPreset, Error, MidiEvent, ParameterChange, ParameterInfo, PluginInfo, PluginInstance,
+    PresetInfo, Result,
+};
+use smallvec::SmallVec;
+use std::collections::VecDeque;
+
+/// Rates tried when none are configured
+pub const DEFAULT_FALLBACK_RATES: [f64; 4] = [96000.0, 88200.0, 48000.0, 44100.0];
+
+/// Silence queued ahead of the resampled output so it never runs dry
+///
+/// The two conversions deliver a frame more or less than asked for from one
+/// block to the next; this absorbs the difference.
+const OUTPUT_PRIMING: usize = 4;
+
+/// The sample rates a [`SampleRateFallback`] settled on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Negotiated {
+    /// Rate the host asked for (and processes at)
+    pub requested: f64,
+
+    /// Rate the plugin was initialized at
+    pub plugin_rate: f64,
+}
+
+impl Negotiated {
+    /// Whether audio is resampled between the host and the plugin
+    pub fn is_resampled(&self) -> bool {
+        self.requested != self.plugin_rate
+    }
+}
+
+/// Streaming linear-interpolation rate converter for any number of channels
+#[derive(Debug, Clone)]
+struct Converter {
+    /// Input frames per output frame
+    step: f64,
+    /// Position of the next output frame, relative to the start of the next
+    /// input block (-1 is the last frame of the previous block)
+    phase: f64,
+    /// Last frame of the previous block, per channel
+    last: Vec<f32>,
+}
+
+impl Converter {
+    fn new(from_rate: f64, to_rate: f64, channels: usize) -> Self {
+        Self {
+            step: from_rate / to_rate,
+            phase: 0.0,
+            last: vec![0.0; channels],
+        }
+    }
+
+    fn reset(&mut self) {
+        self.phase = 0.0;
+        self.last.fill(0.0);
+    }
+
+    /// Most output frames `frames` input frames can produce
+    fn max_output(&self, frames: usize) -> usize {
+        (frames as f64 / self.step).ceil() as usize + 2
+    }
+
+    /// Convert one block, handing each output sample to `emit(channel, sample)`
+    ///
+    /// Returns the number of frames produced. Channels beyond those given at
+    /// construction are ignored.
+    fn run(&mut self, input: &[&[f32]], frames: usize, mut emit: impl FnMut(usize, f32)) -> usize {
+        let channels = input.len().min(self.last.len());
+        let end = frames as f64 - 1.0;
+        let mut position = self.phase;
+        let mut produced = 0;
+
+        while position <= end {
+            let index = position.floor();
+            let t = (position - index) as f32;
+            let index = index as isize;
+            for (ch, samples) in input[..channels].iter().enumerate() {
+                let a = if index < 0 {
+                    self.last[ch]
+                } else {
+                    samples[index as usize]
+                };
+                let sample = if t > 0.0 {
+                    a + (samples[(index + 1) as usize] - a) * t
+                } else {
+                    a
+                };
+                emit(ch, sample);
+            }
+            produced += 1;
+            position += self.step;
+        }
+
+        self.phase = position - frames as f64;
+        if frames > 0 {
+            for (ch, samples) in input[..channels].iter().enumerate() {
+                self.last[ch] = samples[frames - 1];
+            }
+        }
+        produced
+    }
+}
+
+/// Resampling state while the plugin runs at a different rate than the host
+struct Resampler {
+    input: Converter,
+    output: Converter,
+    /// Host-rate input converted to the plugin's rate, per input channel
+    plugin_inputs: Vec<Vec<f32>>,
+    /// Plugin output at the plugin's rate, per output channel
+    plugin_outputs: Vec<Vec<f32>>,
+    /// Plugin output converted back to the host's rate, per output channel
+    queued: Vec<VecDeque<f32>>,
+    /// Largest block the plugin is given
+    plugin_block: usize,
+}
+
+impl Resampler {
+    fn new(
+        host_rate: f64,
+        plugin_rate: f64,
+        max_block_size: usize,
+        inputs: usize,
+        outputs: usize,
+    ) -> Self {
+        let input = Converter::new(host_rate, plugin_rate, inputs);
+        let output = Converter::new(plugin_rate, host_rate, outputs);
+        let plugin_block = input.max_output(max_block_size);
+        let queue = output.max_output(plugin_block) + max_block_size + OUTPUT_PRIMING;
+        let mut resampler = Self {
+            input,
+            output,
+            plugin_inputs: vec![Vec::with_capacity(plugin_block); inputs],
+            plugin_outputs: vec![vec![0.0; plugin_block]; outputs],
+            queued: vec![VecDeque::with_capacity(queue); outputs],
+            plugin_block,
+        };
+        resampler.reset();
+        resampler
+    }
+
+    fn reset(&mut self) {
+        self.input.reset();
+        self.output.reset();
+        for queue in &mut self.queued {
+            queue.clear();
+            queue.resize(OUTPUT_PRIMING, 0.0);
+        }
+    }
+}
+
+/// Plugin wrapper that falls back to other sample rates, resampling if needed
+///
+/// Until a fallback rate is in use, everything is forwarded to the wrapped
+/// plugin unchanged. Sample offsets of MIDI events and the timeline position
+/// are converted between the host's and the plugin's rate.
+pub struct SampleRateFallback<P> {
+    plugin: P,
+    fallback_rates: Vec<f64>,
+    negotiated: Option<Negotiated>,
+    max_block_size: usize,
+    resampler: Option<Resampler>,
+    /// Timeline position at the host's rate
+    position: u64,
+}
+
+impl<P: PluginInstance> SampleRateFallback<P> {
+    /// Wrap a plugin, falling back to [`DEFAULT_FALLBACK_RATES`]
+    pub fn new(plugin: P) -> Self {
+        Self::with_fallback_rates(plugin, &DEFAULT_FALLBACK_RATES)
+    }
+
+    /// Wrap a plugin with a custom list of fallback rates
+    ///
+    /// An empty list disables the fallback.
+    pub fn with_fallback_rates(plugin: P, rates: &[f64]) -> Self {
+        Self {
+            plugin,
+            fallback_rates: rates.iter().copied().filter(|r| *r > 0.0).collect(),
+            negotiated: None,
+            max_block_size: 0,
+            resampler: None,
+            position: 0,
+        }
+    }
+
+    /// The sample rates settled on by the last successful `initialize()`
+    pub fn negotiated(&self) -> Option<Negotiated> {
+        self.negotiated
+    }
+
+    /// Latency added by resampling, in samples at the host's rate
+    ///
+    /// 0 when the plugin runs at the host's rate.
+    pub fn added_latency(&self) -> usize {
+        if self.resampler.is_some() {
+            // One frame per interpolation stage plus the priming
+            OUTPUT_PRIMING + 2
+        } else {
+            0
+        }
+    }
+
+    /// The wrapped plugin
+    pub fn inner(&self) -> &P {
+        &self.plugin
+    }
+
+    /// The wrapped plugin, mutably
+    pub fn inner_mut(&mut self) -> &mut P {
+        &mut self.plugin
+    }
+
+    /// Unwrap the plugin
+    pub fn into_inner(self) -> P {
+        self.plugin
+    }
+
+    /// Ratio of the plugin's rate to the host's (1 without resampling)
+    fn ratio(&self) -> f64 {
+        match (self.negotiated, &self.resampler) {
+            (Some(negotiated), Some(_)) => negotiated.plugin_rate / negotiated.requested,
+            _ => 1.0,
+        }
+    }
+
+    /// Process `num_frames` host frames, with `process` running the plugin on
+    /// the block at its own rate
+    fn resampled(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        process: impl FnOnce(&mut P, &[&[f32]], &mut [&mut [f32]], usize) -> Result<()>,
+    ) -> Result<()> {
+        let Some(resampler) = &mut self.resampler else {
+            process(&mut self.plugin, inputs, outputs, num_frames)?;
+            self.position = self.plugin.sample_position();
+            return Ok(());
+        };
+        if num_frames > self.max_block_size {
+            return Err(Error::BlockTooLarge {
+                max: self.max_block_size,
+                got: num_frames,
+            });
+        }
+
+        for buffer in &mut resampler.plugin_inputs {
+            buffer.clear();
+        }
+        let plugin_inputs = &mut resampler.plugin_inputs;
+        let inputs = &inputs[..inputs.len().min(plugin_inputs.len())];
+        let mut plugin_frames = resampler.input.run(inputs, num_frames, |ch, sample| {
+            plugin_inputs[ch].push(sample)
+        });
+        plugin_frames = plugin_frames.min(resampler.plugin_block);
+        // Channels the host didn't provide are silent
+        for buffer in &mut plugin_inputs[inputs.len()..] {
+            buffer.resize(plugin_frames, 0.0);
+        }
+
+        {
+            let plugin_input_refs: SmallVec<[&[f32]; 8]> = resampler
+                .plugin_inputs
+                .iter()
+                .map(|b| &b[..plugin_frames])
+                .collect();
+            let mut plugin_output_refs: SmallVec<[&mut [f32]; 8]> = resampler
+                .plugin_outputs
+                .iter_mut()
+                .map(|b| &mut b[..plugin_frames])
+                .collect();
+            process(
+                &mut self.plugin,
+                &plugin_input_refs,
+                &mut plugin_output_refs,
+                plugin_frames,
+            )?;
+        }
+
+        let plugin_outputs: SmallVec<[&[f32]; 8]> = resampler
+            .plugin_outputs
+            .iter()
+            .map(|b| &b[..plugin_frames])
+            .collect();
+        let queued = &mut resampler.queued;
+        resampler
+            .output
+            .run(&plugin_outputs, plugin_frames, |ch, sample| {
+                queued[ch].push_back(sample)
+            });
+
+        for (ch, output) in outputs.iter_mut().enumerate() {
+            let output = &mut output[..num_frames];
+            match queued.get_mut(ch) {
+                Some(queue) => {
+                    let available = queue.len().min(num_frames);
+                    for (sample, queued) in output.iter_mut().zip(queue.drain(..available)) {
+                        *sample = queued;
+                    }
+                    output[available..].fill(0.0);
+                }
+                None => output.fill(0.0),
+            }
+        }
+
+        self.position += num_frames as u64;
+        Ok(())
+    }
+}
+
+impl<P: PluginInstance> PluginInstance for SampleRateFallback<P> {
+    /// Initialize at `sample_rate`, or else at the closest fallback rate that works
+    ///
+    /// Fallback rates are tried closest to `sample_rate` first.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error for `sample_rate` if no fallback rate works either
+    fn initialize(&mut self, sample_rate: f64, max_block_size: usize) -> Result<()> {
+        self.negotiated = None;
+        self.resampler = None;
+        self.max_block_size = max_block_size;
+
+        let error = match self.plugin.initialize(sample_rate, max_block_size) {
+            Ok(()) => {
+                self.negotiated = Some(Negotiated {
+                    requested: sample_rate,
+                    plugin_rate: sample_rate,
+                });
+                return Ok(());
+            }
+            Err(error) => error,
+        };
+        if sample_rate.is_nan() || sample_rate <= 0.0 || max_block_size == 0 {
+            return Err(error);
+        }
+
+        let mut candidates: Vec<f64> = self
+            .fallback_rates
+            .iter()
+            .copied()
+            .filter(|rate| *rate != sample_rate)
+            .collect();
+        candidates.sort_by(|a, b| {
+            let distance = |rate: f64| (rate / sample_rate).ln().abs();
+            distance(*a).total_cmp(&distance(*b))
+        });
+
+        for plugin_rate in candidates {
+            let plugin_block =
+                Converter::new(sample_rate, plugin_rate, 0).max_output(max_block_size);
+            if self.plugin.initialize(plugin_rate, plugin_block).is_err() {
+                continue;
+            }
+            self.resampler = Some(Resampler::new(
+                sample_rate,
+                plugin_rate,
+                max_block_size,
+                self.plugin.input_channels(),
+                self.plugin.output_channels(),
+            ));
+            self.negotiated = Some(Negotiated {
+                requested: sample_rate,
+                plugin_rate,
+            });
+            return Ok(());
+        }
+
+        Err(error)
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        if let Some(resampler) = &mut self.resampler {
+            resampler.reset();
+        }
+        self.plugin.reset()
+    }
+
+    fn process(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        self.resampled(
+            inputs,
+            outputs,
+            num_frames,
+            |plugin, inputs, outputs, frames| plugin.process(inputs, outputs, frames),
+        )
+    }
+
+    /// Process with `context` converted to the plugin's rate
+    fn process_with_context(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        context: &BlockContext,
+    ) -> Result<()> {
+        let ratio = self.ratio();
+        let sample_rate = context.sample_rate * ratio;
+        let sample_position = (context.sample_position as f64 * ratio).round() as u64;
+        self.position = context.sample_position;
+        self.resampled(
+            inputs,
+            outputs,
+            context.num_frames,
+            |plugin, inputs, outputs, frames| {
+                let context = BlockContext {
+                    sample_rate,
+                    sample_position,
+                    num_frames: frames,
+                    ..*context
+                };
+                plugin.process_with_context(inputs, outputs, &context)
+            },
+        )
+    }
+
+    /// Process with the change and MIDI offsets converted to the plugin's rate
+    fn process_with_events(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        changes: &[ParameterChange],
+        midi: &[MidiEvent],
+    ) -> Result<()> {
+        let ratio = self.ratio();
+        let scale = |sample_offset: u32| (sample_offset as f64 * ratio) as u32;
+        self.resampled(
+            inputs,
+            outputs,
+            num_frames,
+            |plugin, inputs, outputs, frames| {
+                if ratio == 1.0 {
+                    return plugin.process_with_events(inputs, outputs, frames, changes, midi);
+                }
+                let changes: SmallVec<[ParameterChange; 16]> = changes
+                    .iter()
+                    .map(|change| ParameterChange {
+                        sample_offset: scale(change.sample_offset),
+                        ..*change
+                    })
+                    .collect();
+                let midi: SmallVec<[MidiEvent; 16]> = midi
+                    .iter()
+                    .map(|event| MidiEvent {
+                        sample_offset: scale(event.sample_offset),
+                        ..*event
+                    })
+                    .collect();
+                plugin.process_with_events(inputs, outputs, frames, &changes, &midi)
+            },
+        )
+    }
+
+    fn sample_position(&self) -> u64 {
+        if self.resampler.is_some() {
+            self.position
+        } else {
+            self.plugin.sample_position()
+        }
+    }
+
+    fn set_sample_position(&mut self, position: u64) -> Result<()> {
+        let plugin_position = (position as f64 * self.ratio()).round() as u64;
+        self.plugin.set_sample_position(plugin_position)?;
+        self.position = position;
+        Ok(())
+    }
+
+    fn flush_events(&mut self) -> Result<()> {
+        self.plugin.flush_events()
+    }
+
+    fn parameter_count(&self) -> usize {
+        self.plugin.parameter_count()
+    }
+
+    fn parameter_info(&self, index: usize) -> Result<ParameterInfo> {
+        self.plugin.parameter_info(index)
+    }
+
+    fn get_parameter(&self, index: usize) -> Result<f32> {
+        self.plugin.get_parameter(index)
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) -> Result<()> {
+        self.plugin.set_parameter(index, value)
+    }
+
+    fn send_midi(&mut self, events: &[MidiEvent]) -> Result<()> {
+        if self.resampler.is_none() {
+            return self.plugin.send_midi(events);
+        }
+        let ratio = self.ratio();
+        let events: SmallVec<[MidiEvent; 64]> = events
+            .iter()
+            .map(|event| MidiEvent {
+                sample_offset: (event.sample_offset as f64 * ratio) as u32,
+                ..*event
+            })
+            .collect();
+        self.plugin.send_midi(&events)
+    }
+
+    fn preset_count(&self) -> Result<usize> {
+        self.plugin.preset_count()
+    }
+
+    fn preset_info(&self, index: usize) -> Result<PresetInfo> {
+        self.plugin.preset_info(index)
+    }
+
+    fn load_preset(&mut self, preset_number: i32) -> Result<()> {
+        self.plugin.load_preset(preset_number)
+    }
+
+    fn current_preset(&self) -> Result<Option<CurrentPreset>> {
+        self.plugin.current_preset()
+    }
+
+    fn get_state(&self) -> Result<Vec<u8>> {
+        self.plugin.get_state()
+    }
+
+    fn set_state(&mut self, data: &[u8]) -> Result<()> {
+        self.plugin.set_state(data)
+    }
+
+    fn get_state_to_writer(&self, writer: &mut dyn std::io::Write) -> Result<()> {
+        self.plugin.get_state_to_writer(writer)
+    }
+
+    fn set_state_from_reader(&mut self, reader: &mut dyn std::io::Read) -> Result<()> {
+        self.plugin.set_state_from_reader(reader)
+    }
+
+    fn set_state_from_file(&mut self, path: &std::path::Path) -> Result<()> {
+        self.plugin.set_state_from_file(path)
+    }
+
+    fn take_state_dirty(&self) -> bool {
+        self.plugin.take_state_dirty()
+    }
+
+    fn info(&self) -> &PluginInfo {
+        self.plugin.info()
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.plugin.is_initialized()
+    }
+
+    fn input_channels(&self) -> usize {
+        self.plugin.input_channels()
+    }
+
+    fn output_channels(&self) -> usize {
+        self.plugin.output_channels()
+    }
+
+    /// The plugin's latency converted to the host's rate, plus
+    /// [`added_latency()`](Self::added_latency)
+    fn latency_samples(&self) -> usize {
+        let latency = (self.plugin.latency_samples() as f64 / self.ratio()).round() as usize;
+        latency + self.added_latency()
+    }
+
+    /// The plugin's tail converted to the host's rate
+    fn tail_samples(&self) -> usize {
+        match self.plugin.tail_samples() {
+            usize::MAX => usize::MAX,
+            tail => (tail as f64 / self.ratio()).round() as usize,
+        }
+    }
+
+    fn tail_seconds(&self) -> f64 {
+        self.plugin.tail_seconds()
+    }
+
+    fn set_bypass(&mut self, bypassed: bool) -> Result<()> {
+        self.plugin.set_bypass(bypassed)
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.plugin.is_bypassed()
+    }
+
+    fn quirks(&self) -> Quirks {
+        self.plugin.quirks()
+    }
+
+    fn plugin_meter(&self) -> Option<Vec<MeterReading>> {
+        self.plugin.plugin_meter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockPlugin;
+
+    #[test]
+    fn test_converter_frame_counts() {
+        let mut down = Converter::new(192000.0, 96000.0, 1);
+        let mut up = Converter::new(44100.0, 48000.0, 1);
+        let block = [1.0f32; 512];
+        let (mut total_down, mut total_up) = (0, 0);
+        for _ in 0..100 {
+            total_down += down.run(&[&block], 512, |_, _| {});
+            total_up += up.run(&[&block], 512, |_, _| {});
+        }
+        assert_eq!(total_down, 25600);
+        assert!((total_up as f64 - 51200.0 * 48000.0 / 44100.0).abs() <= 1.0);
+    }
+
+    #[test]
+    fn test_falls_back_and_resamples() {
+        let mut mock = MockPlugin::new().with_latency(16).with_tail(9600);
+        mock.max_sample_rate = Some(96000.0);
+        let mut plugin = SampleRateFallback::new(mock);
+        plugin.initialize(192000.0, 256).unwrap();
+        assert_eq!(
+            plugin.negotiated(),
+            Some(Negotiated {
+                requested: 192000.0,
+                plugin_rate: 96000.0
+            })
+        );
+
+        // Latency and tail double at the host's rate; the tail lasts as long
+        assert_eq!(plugin.latency_samples(), 32 + plugin.added_latency());
+        assert_eq!(plugin.tail_samples(), 19200);
+        assert_eq!(plugin.tail_seconds(), 0.1);
+
+        // A steady signal comes out steady once the latency has passed
+        let input = vec![0.5f32; 256];
+        let mut left = vec![0.0f32; 256];
+        let mut right = vec![0.0f32; 256];
+        for block in 0..20 {
+            let mut outputs = [left.as_mut_slice(), right.as_mut_slice()];
+            plugin
+                .process(&[&input, &input], &mut outputs, 256)
+                .unwrap();
+            let start = if block == 0 {
+                plugin.latency_samples()
+            } else {
+                0
+            };
+            assert!(left[start..].iter().all(|s| (s - 0.5).abs() < 1e-6));
+        }
+        assert_eq!(plugin.sample_position(), 20 * 256);
+
+        plugin
+            .send_midi(&[MidiEvent::note_on(60, 100, 0, 100)])
+            .unwrap();
+        assert_eq!(plugin.inner().midi_received[0].sample_offset, 50);
+    }
+
+    #[test]
+    fn test_reports_original_error() {
+        let mut mock = MockPlugin::new();
+        mock.max_sample_rate = Some(32000.0);
+        let mut plugin = SampleRateFallback::new(mock);
+        assert!(plugin.initialize(192000.0, 256).is_err());
+        assert_eq!(plugin.negotiated(), None);
+
+        let mut plugin = SampleRateFallback::new(MockPlugin::new());
+        plugin.initialize(192000.0, 256).unwrap();
+        assert!(!plugin.negotiated().unwrap().is_resampled());
+        assert_eq!(plugin.added_latency(), 0);
+    }
+}
@@ -0,0 +1,486 @@
+//! Time and memory guards for in-process scanning
+//!
+//! Scanning a plugin means running its code, and a pathological bundle can hang
+//! in its static initializers or allocate until the host runs out of memory.
+//! [`scan_guarded()`] scans bundles one at a time on a watchdog-supervised
+//! worker thread and gives up on any bundle that exceeds its [`ScanLimits`], so
+//! the rest of the scan carries on.
+//!
+//! In-process guards can only stop *waiting*: a bundle that is abandoned keeps
+//! its worker thread until it returns (if ever), and memory it already
+//! allocated is not reclaimed. They keep the scan responsive and stop piling
+//! more work onto a misbehaving process; they are not a substitute for
+//! out-of-process scanning when full isolation is needed.
+//!
+//! Bundles on network shares and removable drives can stall before the scanner
+//! even gets to run them. [`ScanLimits::io_timeout`] bounds how long the volume
+//! of each bundle may take to answer, and [`ScanLimits::skip_non_local`] skips
+//! such volumes entirely. Both are reported in [`GuardedScanReport::skipped`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::sandbox::{scan_guarded, ScanLimits};
+//! # use std::path::PathBuf;
+//! # use std::time::Duration;
+//! # fn example<S: PluginScanner + 'static>(
+//! #     make_scanner: fn() -> Result<S>,
+//! #     bundles: Vec<PathBuf>,
+//! # ) {
+//! let limits = ScanLimits {
+//!     timeout: Some(Duration::from_secs(10)),
+//!     max_memory: Some(512 * 1024 * 1024),
+//!     io_timeout: Some(Duration::from_secs(2)),
+//!     skip_non_local: false,
+//! };
+//!
+//! let report = scan_guarded(&bundles, &limits, make_scanner);
+//! for failure in &report.failures {
+//!     eprintln!("failed {}: {}", failure.path.display(), failure.reason);
+//! }
+//! for skipped in &report.skipped {
+//!     eprintln!("skipped {}: {}", skipped.path.display(), skipped.reason);
+//! }
+//! # }
+//! ```
+
+use crate::volume::{self, IoProbe, VolumeKind};
+use crate::{Error, PluginInfo, PluginScanner, Result};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How often the watchdog checks the worker's elapsed time and memory use
+const WATCHDOG_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Per-bundle limits for [`scan_guarded()`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ScanLimits {
+    /// Give up on a bundle that takes longer than this to scan
+    pub timeout: Option<Duration>,
+
+    /// Give up on a bundle if the process's resident memory grows by more than
+    /// this many bytes while scanning it
+    ///
+    /// Only enforced where resident memory can be measured (Linux and macOS).
+    pub max_memory: Option<u64>,
+
+    /// Skip a bundle whose volume takes longer than this to answer
+    ///
+    /// Checked before the bundle is scanned, by reading its metadata and
+    /// classifying its volume on a separate thread. Folders searched by
+    /// scanners with a [`scan_depth`](crate::scan::ScannerConfig::scan_depth)
+    /// are bounded by [`ScannerConfig::io_timeout`](crate::scan::ScannerConfig::io_timeout)
+    /// instead.
+    pub io_timeout: Option<Duration>,
+
+    /// Skip bundles on network shares and removable drives
+    pub skip_non_local: bool,
+}
+
+/// Why a bundle was skipped
+#[derive(Debug)]
+pub enum ScanFailureReason {
+    /// The scan took longer than [`ScanLimits::timeout`]
+    TimedOut(Duration),
+
+    /// Resident memory grew by more than [`ScanLimits::max_memory`]
+    MemoryExceeded(u64),
+
+    /// The scanner panicked while scanning the bundle
+    Panicked,
+
+    /// The scanner returned an error
+    Error(Error),
+}
+
+impl std::fmt::Display for ScanFailureReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScanFailureReason::TimedOut(elapsed) => {
+                write!(f, "timed out after {:.1}s", elapsed.as_secs_f64())
+            }
+            ScanFailureReason::MemoryExceeded(grown) => {
+                write!(f, "memory grew by {} MiB", grown / (1024 * 1024))
+            }
+            ScanFailureReason::Panicked => f.write_str("scanner panicked"),
+            ScanFailureReason::Error(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// A bundle that could not be scanned
+#[derive(Debug)]
+pub struct ScanFailure {
+    /// Path of the bundle
+    pub path: PathBuf,
+
+    /// Why it was skipped
+    pub reason: ScanFailureReason,
+}
+
+/// Why a bundle was skipped without being scanned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The bundle is on a non-local volume and
+    /// [`ScanLimits::skip_non_local`] is set
+    NonLocal(VolumeKind),
+
+    /// The bundle's volume didn't answer within [`ScanLimits::io_timeout`]
+    IoTimedOut(Duration),
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::NonLocal(VolumeKind::Network) => f.write_str("on a network volume"),
+            SkipReason::NonLocal(VolumeKind::Removable) => f.write_str("on a removable volume"),
+            SkipReason::NonLocal(kind) => write!(f, "on a {:?} volume", kind),
+            SkipReason::IoTimedOut(timeout) => {
+                write!(f, "volume unresponsive for {:.1}s", timeout.as_secs_f64())
+            }
+        }
+    }
+}
+
+/// A bundle that was not scanned
+#[derive(Debug, Clone)]
+pub struct SkippedPath {
+    /// Path of the bundle
+    pub path: PathBuf,
+
+    /// Why it was skipped
+    pub reason: SkipReason,
+}
+
+/// Result of [`scan_guarded()`]
+#[derive(Debug, Default)]
+pub struct GuardedScanReport {
+    /// Plugins found in bundles that scanned successfully
+    pub plugins: Vec<PluginInfo>,
+
+    /// Bundles that failed or were abandoned
+    pub failures: Vec<ScanFailure>,
+
+    /// Bundles that were skipped because of their volume
+    pub skipped: Vec<SkippedPath>,
+}
+
+/// Scan bundles one at a time, abandoning any that exceed `limits`
+///
+/// Each bundle is scanned with [`PluginScanner::scan_path()`] on a fresh worker
+/// thread, using a scanner created by `make_scanner`. The calling thread acts
+/// as the watchdog. Bundles whose volume is unresponsive or, if requested,
+/// not local are skipped first.
+///
+/// # Arguments
+///
+/// * `bundles` - Bundle paths to scan
+/// * `limits` - Per-bundle time and memory limits
+/// * `make_scanner` - Creates a scanner on the worker thread
+pub fn scan_guarded<S, F>(
+    bundles: &[PathBuf],
+    limits: &ScanLimits,
+    make_scanner: F,
+) -> GuardedScanReport
+where
+    S: PluginScanner,
+    F: Fn() -> Result<S> + Send + Sync + 'static,
+{
+    let make_scanner = Arc::new(make_scanner);
+    let mut report = GuardedScanReport::default();
+    let mut probe = limits.io_timeout.map(IoProbe::new);
+
+    for path in bundles {
+        let skip = check_volume(path, limits, probe.as_mut(), volume::volume_kind);
+        if let Some(reason) = skip {
+            report.skipped.push(SkippedPath {
+                path: path.clone(),
+                reason,
+            });
+            continue;
+        }
+
+        match scan_one(path, limits, Arc::clone(&make_scanner)) {
+            Ok(plugins) => report.plugins.extend(plugins),
+            Err(reason) => report.failures.push(ScanFailure {
+                path: path.clone(),
+                reason,
+            }),
+        }
+    }
+
+    report
+}
+
+/// Decide whether a bundle's volume rules it out, probing with `classify`
+///
+/// `probe` runs the checks when [`ScanLimits::io_timeout`] is set.
+fn check_volume(
+    path: &Path,
+    limits: &ScanLimits,
+    probe: Option<&mut IoProbe>,
+    classify: fn(&Path) -> VolumeKind,
+) -> Option<SkipReason> {
+    if limits.io_timeout.is_none() && !limits.skip_non_local {
+        return None;
+    }
+
+    let kind = match (probe, limits.io_timeout) {
+        (Some(probe), Some(timeout)) => {
+            // A stalled share blocks the probe thread, not the scan
+            let probe_path = path.to_path_buf();
+            let kind = probe.run(move || {
+                let _ = std::fs::metadata(&probe_path);
+                classify(&probe_path)
+            });
+            match kind {
+                Some(kind) => kind,
+                None => return Some(SkipReason::IoTimedOut(timeout)),
+            }
+        }
+        _ => classify(path),
+    };
+
+    (limits.skip_non_local && !kind.is_local()).then_some(SkipReason::NonLocal(kind))
+}
+
+fn scan_one<S, F>(
+    path: &Path,
+    limits: &ScanLimits,
+    make_scanner: Arc<F>,
+) -> std::result::Result<Vec<PluginInfo>, ScanFailureReason>
+where
+    S: PluginScanner,
+    F: Fn() -> Result<S> + Send + Sync + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    let worker_path = path.to_path_buf();
+
+    let spawned = std::thread::Builder::new()
+        .name("rack-scan".to_string())
+        .spawn(move || {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                make_scanner()?.scan_path(&worker_path)
+            }));
+            // The watchdog may have given up on us already
+            let _ = tx.send(result);
+        });
+    if let Err(e) = spawned {
+        return Err(ScanFailureReason::Error(Error::Io(e)));
+    }
+
+    let start = Instant::now();
+    let baseline = resident_memory();
+
+    loop {
+        match rx.recv_timeout(WATCHDOG_INTERVAL) {
+            Ok(Ok(Ok(plugins))) => return Ok(plugins),
+            Ok(Ok(Err(e))) => return Err(ScanFailureReason::Error(e)),
+            Ok(Err(_)) | Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err(ScanFailureReason::Panicked)
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+        }
+
+        let elapsed = start.elapsed();
+        if limits.timeout.is_some_and(|timeout| elapsed > timeout) {
+            return Err(ScanFailureReason::TimedOut(elapsed));
+        }
+
+        if let (Some(max), Some(baseline), Some(current)) =
+            (limits.max_memory, baseline, resident_memory())
+        {
+            let grown = current.saturating_sub(baseline);
+            if grown > max {
+                return Err(ScanFailureReason::MemoryExceeded(grown));
+            }
+        }
+    }
+}
+
+/// Current resident memory of the process in bytes, if it can be measured
+#[cfg(target_os = "linux")]
+fn resident_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Current resident memory of the process in bytes, if it can be measured
+#[cfg(target_vendor = "apple")]
+fn resident_memory() -> Option<u64> {
+    // mach_task_basic_info from <mach/task_info.h>
+    #[repr(C)]
+    #[derive(Default)]
+    struct MachTaskBasicInfo {
+        virtual_size: u64,
+        resident_size: u64,
+        resident_size_max: u64,
+        user_time: [i32; 2],
+        system_time: [i32; 2],
+        policy: i32,
+        suspend_count: i32,
+    }
+
+    const MACH_TASK_BASIC_INFO: i32 = 20;
+
+    extern "C" {
+        static mach_task_self_: u32;
+        fn task_info(task: u32, flavor: i32, info: *mut i32, count: *mut u32) -> i32;
+    }
+
+    let mut info = MachTaskBasicInfo::default();
+    let mut count = (std::mem::size_of::<MachTaskBasicInfo>() / std::mem::size_of::<i32>()) as u32;
+    // Safety: info is a correctly sized mach_task_basic_info and count matches it
+    let result = unsafe {
+        task_info(
+            mach_task_self_,
+            MACH_TASK_BASIC_INFO,
+            &mut info as *mut MachTaskBasicInfo as *mut i32,
+            &mut count,
+        )
+    };
+    (result == 0).then_some(info.resident_size)
+}
+
+/// Current resident memory of the process in bytes, if it can be measured
+#[cfg(not(any(target_os = "linux", target_vendor = "apple")))]
+fn resident_memory() -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockPlugin;
+    use crate::scan::ScannerConfig;
+    use crate::PluginType;
+
+    /// Scanner whose behavior depends on the bundle name
+    struct TestScanner(ScannerConfig);
+
+    impl PluginScanner for TestScanner {
+        type Plugin = MockPlugin;
+
+        fn with_config(config: ScannerConfig) -> Result<Self> {
+            Ok(Self(config))
+        }
+
+        fn config(&self) -> &ScannerConfig {
+            &self.0
+        }
+
+        fn add_path(&mut self, path: &Path) -> Result<()> {
+            self.0.extra_paths.push(path.to_path_buf());
+            Ok(())
+        }
+
+        fn scan(&self) -> Result<Vec<PluginInfo>> {
+            Ok(Vec::new())
+        }
+
+        fn scan_path(&self, path: &Path) -> Result<Vec<PluginInfo>> {
+            match path.to_str().unwrap() {
+                "hang" => {
+                    std::thread::sleep(Duration::from_secs(5));
+                    Ok(Vec::new())
+                }
+                "panic" => panic!("bad bundle"),
+                "error" => Err(Error::Other("unreadable".to_string())),
+                name => Ok(vec![PluginInfo::new(
+                    name.to_string(),
+                    "Test".to_string(),
+                    1,
+                    PluginType::Effect,
+                    path.to_path_buf(),
+                    name.to_string(),
+                )]),
+            }
+        }
+
+        fn load(&self, _info: &PluginInfo) -> Result<MockPlugin> {
+            Ok(MockPlugin::new())
+        }
+    }
+
+    #[test]
+    fn test_scan_guarded() {
+        let bundles: Vec<PathBuf> = ["good", "hang", "panic", "error", "also-good"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        let limits = ScanLimits {
+            timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
+
+        let report = scan_guarded(&bundles, &limits, || {
+            TestScanner::with_config(ScannerConfig::new())
+        });
+
+        let names: Vec<&str> = report.plugins.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["good", "also-good"]);
+
+        assert_eq!(report.failures.len(), 3);
+        assert!(matches!(
+            report.failures[0].reason,
+            ScanFailureReason::TimedOut(_)
+        ));
+        assert!(matches!(
+            report.failures[1].reason,
+            ScanFailureReason::Panicked
+        ));
+        assert!(matches!(
+            report.failures[2].reason,
+            ScanFailureReason::Error(_)
+        ));
+    }
+
+    #[test]
+    fn test_check_volume() {
+        let path = Path::new("/plugins/Synth.vst3");
+        let mut limits = ScanLimits::default();
+        assert_eq!(
+            check_volume(path, &limits, None, |_| VolumeKind::Network),
+            None
+        );
+
+        limits.skip_non_local = true;
+        assert_eq!(
+            check_volume(path, &limits, None, |_| VolumeKind::Network),
+            Some(SkipReason::NonLocal(VolumeKind::Network))
+        );
+        assert_eq!(
+            check_volume(path, &limits, None, |_| VolumeKind::Unknown),
+            None
+        );
+
+        // A share that never answers
+        limits.io_timeout = Some(Duration::from_millis(20));
+        let mut probe = IoProbe::new(Duration::from_millis(20));
+        let stalled = |_: &Path| {
+            std::thread::sleep(Duration::from_secs(5));
+            VolumeKind::Local
+        };
+        assert_eq!(
+            check_volume(path, &limits, Some(&mut probe), stalled),
+            Some(SkipReason::IoTimedOut(Duration::from_millis(20)))
+        );
+        limits.io_timeout = Some(Duration::from_secs(5));
+        let mut probe = IoProbe::new(Duration::from_secs(5));
+        assert_eq!(
+            check_volume(path, &limits, Some(&mut probe), |_| VolumeKind::Local),
+            None
+        );
+    }
+
+    #[cfg(any(target_os = "linux", target_vendor = "apple"))]
+    #[test]
+    fn test_resident_memory() {
+        assert!(resident_memory().is_some_and(|bytes| bytes > 0));
+    }
+}
@@ -0,0 +1,983 @@
+//! Format-independent scanning helpers
+//!
+//! [`ScanFilter`] restricts a scan to the plugins a host actually cares about,
+//! e.g. only instruments, or only one vendor's products.
+//!
+//! [`ScannerConfig`] controls where scanners look: extra search paths, whether
+//! the platform's default locations are included, and whether symlinked
+//! bundles are followed. Scanners created with `new()` also pick up paths from
+//! the `RACK_PLUGIN_PATH` environment variable.
+//!
+//! [`group_by_product()`] folds the same product installed in several formats
+//! (e.g. AU and VST3) into one [`PluginGroup`], so plugin browsers don't show
+//! confusing duplicates.
+//!
+//! [`dedup_by_location()`] drops entries that are the same bundle reached
+//! through different symlinks, when combining the results of several scans.
+//!
+//! [`load_best()`] loads a [`PluginIdentity`] from whichever installed format
+//! is most preferred, falling back to the next one if it fails to load.
+//!
+//! [`find_bundles()`] walks a folder tree looking for plugin bundles at any
+//! depth, including inside application bundles. Scanners use it for
+//! `scan_path()` when [`ScannerConfig::scan_depth`] is set.
+
+use crate::identity::PluginIdentity;
+use crate::volume::IoProbe;
+use crate::{Error, PluginFormat, PluginInfo, PluginType, Result};
+use regex::{Regex, RegexBuilder};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Environment variable with extra plugin search paths
+///
+/// Uses the platform's path list separator (`:` on Unix, `;` on Windows).
+pub const PLUGIN_PATH_ENV: &str = "RACK_PLUGIN_PATH";
+
+/// Where a scanner looks for plugins
+///
+/// # Examples
+///
+/// ```
+/// use rack::scan::ScannerConfig;
+///
+/// // Only a project-local folder, ignoring system-wide plugins
+/// let config = ScannerConfig::new()
+///     .path("./plugins")
+///     .skip_default_paths(true);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScannerConfig {
+    /// Searched in addition to (or, with `skip_default_paths`, instead of) the
+    /// platform's default locations
+    pub extra_paths: Vec<PathBuf>,
+
+    /// Don't search the platform's default locations
+    ///
+    /// AudioUnits are only found through the system registry, so with this set
+    /// the AudioUnit scanner only finds registered components installed under
+    /// `extra_paths`.
+    pub skip_default_paths: bool,
+
+    /// Follow symbolic links to bundles and directories
+    pub follow_symlinks: bool,
+
+    /// Order in which formats are tried by
+    /// [`load_best()`](crate::PluginScanner::load_best)
+    pub format_preference: Vec<PluginFormat>,
+
+    /// How many folder levels below the given path
+    /// [`scan_path()`](crate::PluginScanner::scan_path) searches for bundles
+    ///
+    /// 0 (the default) only looks at the path itself. Deeper scans find
+    /// plugins that installers nest in application bundles or vendor folders
+    /// (see [`find_bundles()`]).
+    pub scan_depth: usize,
+
+    /// Skip folders whose volume takes longer than this to list while
+    /// searching for bundles
+    ///
+    /// Guards against network shares and removable drives that stall. `None`
+    /// (the default) waits as long as listings take.
+    pub io_timeout: Option<Duration>,
+}
+
+impl ScannerConfig {
+    /// Default locations only, following symlinks
+    pub fn new() -> Self {
+        Self {
+            extra_paths: Vec::new(),
+            skip_default_paths: false,
+            follow_symlinks: true,
+            format_preference: DEFAULT_FORMAT_PREFERENCE.to_vec(),
+            scan_depth: 0,
+            io_timeout: None,
+        }
+    }
+
+    /// Default locations plus the paths listed in [`PLUGIN_PATH_ENV`]
+    pub fn from_env() -> Self {
+        match std::env::var_os(PLUGIN_PATH_ENV) {
+            Some(paths) => Self::new().path_list(&paths),
+            None => Self::new(),
+        }
+    }
+
+    /// Add a search path
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.extra_paths.push(path.into());
+        self
+    }
+
+    /// Add the search paths of a list in the platform's `PATH` format
+    /// (separated by `:`, or `;` on Windows), skipping empty entries
+    pub fn path_list(mut self, paths: &std::ffi::OsStr) -> Self {
+        self.extra_paths
+            .extend(std::env::split_paths(paths).filter(|p| !p.as_os_str().is_empty()));
+        self
+    }
+
+    /// Set whether the platform's default locations are skipped
+    pub fn skip_default_paths(mut self, skip: bool) -> Self {
+        self.skip_default_paths = skip;
+        self
+    }
+
+    /// Set whether symbolic links are followed
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+        self
+    }
+
+    /// Set the order in which formats are tried when loading by identity
+    pub fn format_preference(mut self, preference: &[PluginFormat]) -> Self {
+        self.format_preference = preference.to_vec();
+        self
+    }
+
+    /// Set how many folder levels `scan_path()` searches
+    pub fn scan_depth(mut self, depth: usize) -> Self {
+        self.scan_depth = depth;
+        self
+    }
+
+    /// Set how long a folder listing may take before the folder is skipped
+    pub fn io_timeout(mut self, timeout: Duration) -> Self {
+        self.io_timeout = Some(timeout);
+        self
+    }
+}
+
+impl Default for ScannerConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Criteria for restricting which plugins a scan returns
+///
+/// An empty filter matches everything. Each criterion that is set must match
+/// (criteria are combined with AND); within a list, any entry may match (OR).
+///
+/// Backends check the filter as soon as a plugin's name, manufacturer and
+/// type are known, before its paths, ID and vendor details are read, so
+/// rejected plugins cost little beyond that. Finding out those three still
+/// means opening the plugin's library (CLAP, VST2 and VST3) or reading its
+/// bundle's manifest (LV2); the filter doesn't save that part.
+///
+/// # Examples
+///
+/// ```
+/// use rack::{scan::ScanFilter, PluginType};
+///
+/// // Only instruments by Native Instruments or Arturia whose name contains "Piano"
+/// let filter = ScanFilter::new()
+///     .plugin_type(PluginType::Instrument)
+///     .manufacturer("Native Instruments")
+///     .manufacturer("Arturia")
+///     .name_pattern("piano")?;
+/// # Ok::<(), rack::Error>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ScanFilter {
+    /// Manufacturer names to accept (case-insensitive exact match)
+    pub manufacturers: Vec<String>,
+
+    /// Regular expression the plugin name must match (case-insensitive)
+    ///
+    /// It may match anywhere in the name; anchor it with `^` and `$` to match
+    /// the whole name.
+    pub name_pattern: Option<Regex>,
+
+    /// Plugin types to accept
+    pub plugin_types: Vec<PluginType>,
+}
+
+impl ScanFilter {
+    /// Create an empty filter that matches every plugin
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept plugins from this manufacturer
+    pub fn manufacturer(mut self, manufacturer: impl Into<String>) -> Self {
+        self.manufacturers.push(manufacturer.into());
+        self
+    }
+
+    /// Only accept plugins whose name matches a regular expression
+    ///
+    /// Matching ignores case; see [`name_pattern`](Self::name_pattern).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidFormat`] if `pattern` isn't a valid regular
+    /// expression
+    pub fn name_pattern(mut self, pattern: &str) -> Result<Self> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| Error::InvalidFormat(format!("Invalid name pattern: {}", e)))?;
+        self.name_pattern = Some(regex);
+        Ok(self)
+    }
+
+    /// Accept plugins of this type
+    pub fn plugin_type(mut self, plugin_type: PluginType) -> Self {
+        self.plugin_types.push(plugin_type);
+        self
+    }
+
+    /// Check whether the filter has no criteria
+    pub fn is_empty(&self) -> bool {
+        self.manufacturers.is_empty() && self.name_pattern.is_none() && self.plugin_types.is_empty()
+    }
+
+    /// Check whether a plugin passes the filter
+    pub fn matches(&self, info: &PluginInfo) -> bool {
+        self.matches_fields(&info.name, &info.manufacturer, info.plugin_type)
+    }
+
+    /// Check the filter against individual fields
+    ///
+    /// Lets backends reject plugins before building a full [`PluginInfo`].
+    pub fn matches_fields(&self, name: &str, manufacturer: &str, plugin_type: PluginType) -> bool {
+        if !self.plugin_types.is_empty() && !self.plugin_types.contains(&plugin_type) {
+            return false;
+        }
+
+        if !self.manufacturers.is_empty() {
+            let manufacturer = manufacturer.to_lowercase();
+            if !self
+                .manufacturers
+                .iter()
+                .any(|m| m.to_lowercase() == manufacturer)
+            {
+                return false;
+            }
+        }
+
+        match &self.name_pattern {
+            Some(pattern) => pattern.is_match(name),
+            None => true,
+        }
+    }
+}
+
+/// Default format preference used by [`group_by_product()`] callers that don't
+/// have their own: native AudioUnits first on Apple platforms, then VST3, then
+/// CLAP, then LV2, with legacy VST2 last
+pub const DEFAULT_FORMAT_PREFERENCE: &[PluginFormat] = &[
+    PluginFormat::AudioUnit,
+    PluginFormat::Vst3,
+    PluginFormat::Clap,
+    PluginFormat::Lv2,
+    PluginFormat::Vst2,
+];
+
+/// One logical plugin product, possibly installed in several formats
+#[derive(Debug, Clone)]
+pub struct PluginGroup {
+    /// Display name of the product (from the preferred entry)
+    pub name: String,
+
+    /// Manufacturer of the product (from the preferred entry)
+    pub manufacturer: String,
+
+    /// Per-format entries, ordered by the preference passed to [`group_by_product()`]
+    pub entries: Vec<PluginInfo>,
+}
+
+impl PluginGroup {
+    /// The entry in the most preferred format
+    pub fn preferred(&self) -> &PluginInfo {
+        &self.entries[0]
+    }
+
+    /// The entry for a specific format, if installed
+    pub fn get(&self, format: PluginFormat) -> Option<&PluginInfo> {
+        self.entries.iter().find(|p| p.format == format)
+    }
+
+    /// Formats this product is installed in, in preference order
+    pub fn formats(&self) -> Vec<PluginFormat> {
+        self.entries.iter().map(|p| p.format).collect()
+    }
+}
+
+/// Group plugins that are the same product installed in different formats
+///
+/// Plugins are considered the same product when their manufacturer and name
+/// match after normalization (case, punctuation and company suffixes such as
+/// "Inc." or "GmbH" are ignored) and they agree on being an instrument or not.
+///
+/// Entries within a group are sorted by their format's position in
+/// `preference`; formats not listed come last. Groups keep the order in which
+/// their first entry appeared in `plugins`.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use rack::prelude::*;
+/// # use rack::scan::{group_by_product, DEFAULT_FORMAT_PREFERENCE};
+/// # fn example(plugins: Vec<PluginInfo>) {
+/// for group in group_by_product(plugins, DEFAULT_FORMAT_PREFERENCE) {
+///     println!("{} by {} {:?}", group.name, group.manufacturer, group.formats());
+/// }
+/// # }
+/// ```
+pub fn group_by_product(plugins: Vec<PluginInfo>, preference: &[PluginFormat]) -> Vec<PluginGroup> {
+    let mut index: HashMap<(String, String, bool), usize> = HashMap::new();
+    let mut groups: Vec<Vec<PluginInfo>> = Vec::new();
+
+    for plugin in plugins {
+        let key = (
+            normalize_manufacturer(&plugin.manufacturer),
+            normalize_name(&plugin.name),
+            plugin.plugin_type == PluginType::Instrument,
+        );
+        match index.get(&key) {
+            Some(&i) => groups[i].push(plugin),
+            None => {
+                index.insert(key, groups.len());
+                groups.push(vec![plugin]);
+            }
+        }
+    }
+
+    let rank = |format: PluginFormat| {
+        preference
+            .iter()
+            .position(|&f| f == format)
+            .unwrap_or(preference.len())
+    };
+
+    groups
+        .into_iter()
+        .map(|mut entries| {
+            entries.sort_by_key(|p| rank(p.format));
+            PluginGroup {
+                name: entries[0].name.clone(),
+                manufacturer: entries[0].manufacturer.clone(),
+                entries,
+            }
+        })
+        .collect()
+}
+
+/// Remove plugins that are the same bundle found through different paths
+///
+/// Each scanner already lists a bundle once however many links point at it;
+/// this does the same across the results of several scans (e.g.
+/// [`scan_path()`](crate::PluginScanner::scan_path) on two folders that both
+/// link into a plugin manager's central store). Entries are the same when
+/// their format, [`canonical_path`](PluginInfo::canonical_path) and unique ID
+/// match. The first entry is kept.
+pub fn dedup_by_location(plugins: Vec<PluginInfo>) -> Vec<PluginInfo> {
+    let mut seen = HashSet::new();
+    plugins
+        .into_iter()
+        .filter(|p| seen.insert((p.format, p.canonical_path.clone(), p.unique_id.clone())))
+        .collect()
+}
+
+/// Installed entries matching `identity`, best first
+///
+/// Entries are ordered by their format's position in `preference` (formats not
+/// listed come last), then entries with the identity's unique ID before those
+/// matching by name only. Otherwise scan order is kept.
+pub fn candidates<'a>(
+    identity: &PluginIdentity,
+    plugins: &'a [PluginInfo],
+    preference: &[PluginFormat],
+) -> Vec<&'a PluginInfo> {
+    let mut matches: Vec<&PluginInfo> = plugins.iter().filter(|p| identity.matches(p)).collect();
+    matches.sort_by_key(|p| {
+        let rank = preference
+            .iter()
+            .position(|&f| f == p.format)
+            .unwrap_or(preference.len());
+        (rank, !identity.matches_id(p))
+    });
+    matches
+}
+
+/// Load the best installed entry for `identity`
+///
+/// Tries the [`candidates()`] in order with `load`, returning the first plugin
+/// that loads along with the entry it was loaded from. `load` decides how each
+/// entry is instantiated, so entries from several scanners (say AudioUnit and
+/// VST3 on macOS) can be combined by boxing the plugins.
+/// [`UnifiedScanner::load_best()`](crate::unified::UnifiedScanner::load_best)
+/// does this for every format available on the platform.
+///
+/// # Errors
+///
+/// Returns [`Error::PluginNotFound`] if no entry matches, or the error of the
+/// last candidate if none loads
+///
+/// # Examples
+///
+/// ```no_run
+/// # use rack::prelude::*;
+/// # use rack::identity::PluginIdentity;
+/// # use rack::scan::{load_best, DEFAULT_FORMAT_PREFERENCE};
+/// # fn example(
+/// #     au: &impl PluginScanner<Plugin = impl PluginInstance + 'static>,
+/// #     vst3: &impl PluginScanner<Plugin = impl PluginInstance + 'static>,
+/// #     identity: &PluginIdentity,
+/// # ) -> Result<()> {
+/// let mut plugins = au.scan()?;
+/// plugins.extend(vst3.scan()?);
+///
+/// let (info, plugin) = load_best(identity, &plugins, DEFAULT_FORMAT_PREFERENCE, |info| {
+///     Ok(match info.format {
+///         PluginFormat::AudioUnit => au.load_boxed(info)?,
+///         _ => vst3.load_boxed(info)?,
+///     })
+/// })?;
+/// println!("Loaded {} as {}", info.name, info.format);
+/// # Ok(())
+/// # }
+/// ```
+pub fn load_best<P>(
+    identity: &PluginIdentity,
+    plugins: &[PluginInfo],
+    preference: &[PluginFormat],
+    mut load: impl FnMut(&PluginInfo) -> Result<P>,
+) -> Result<(PluginInfo, P)> {
+    let mut last_error = None;
+    for info in candidates(identity, plugins, preference) {
+        match load(info) {
+            Ok(plugin) => return Ok((info.clone(), plugin)),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        Error::PluginNotFound(format!("{} by {}", identity.product, identity.vendor))
+    }))
+}
+
+/// Kind of plugin bundle, recognized by its extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BundleKind {
+    /// VST3 bundle (`.vst3`)
+    Vst3,
+
+    /// AudioUnit v2 component (`.component`)
+    Component,
+
+    /// App extension (`.appex`), which may hold an AUv3
+    AppExtension,
+
+    /// CLAP plugin (`.clap`), a bundle on macOS and a library elsewhere
+    Clap,
+
+    /// LV2 bundle (`.lv2`), a folder of Turtle data and binaries
+    Lv2,
+
+    /// VST2 plugin: a `.vst` bundle on macOS, otherwise a plain shared
+    /// library (`.dll`, `.so`), so any library may be one
+    Vst2,
+}
+
+impl BundleKind {
+    /// Recognize a bundle by its file extension, ignoring case
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "vst3" => Some(Self::Vst3),
+            "component" => Some(Self::Component),
+            "appex" => Some(Self::AppExtension),
+            "clap" => Some(Self::Clap),
+            "lv2" => Some(Self::Lv2),
+            "vst" | "dll" | "so" => Some(Self::Vst2),
+            _ => None,
+        }
+    }
+
+    /// The format of the plugins in the bundle
+    pub fn format(self) -> PluginFormat {
+        match self {
+            Self::Vst3 => PluginFormat::Vst3,
+            Self::Component | Self::AppExtension => PluginFormat::AudioUnit,
+            Self::Clap => PluginFormat::Clap,
+            Self::Lv2 => PluginFormat::Lv2,
+            Self::Vst2 => PluginFormat::Vst2,
+        }
+    }
+}
+
+/// A bundle found by [`find_bundles()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoundBundle {
+    /// Where the bundle was found
+    pub path: PathBuf,
+
+    /// What kind of bundle it is
+    pub kind: BundleKind,
+}
+
+/// Find plugin bundles in `root` and up to `max_depth` levels of subfolders
+///
+/// With a depth of 0 only the bundles directly in `root` are found, like a
+/// regular scan of the folder; `root` itself is returned if it is a bundle.
+/// Plugin bundles aren't searched further, but any other folder is,
+/// including application bundles: AUv3 extensions live three levels down, in
+/// `Vendor.app/Contents/PlugIns`. With `follow_symlinks`, linked folders are
+/// searched, each real folder once. Unreadable folders are skipped, as are
+/// folders that take longer than `io_timeout` to list. Results are sorted by
+/// path.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rack::scan::{find_bundles, BundleKind};
+/// use std::path::Path;
+///
+/// for bundle in find_bundles(Path::new("/Applications/Vendor Suite"), 4, true, None) {
+///     if bundle.kind == BundleKind::Vst3 {
+///         println!("{}", bundle.path.display());
+///     }
+/// }
+/// ```
+pub fn find_bundles(
+    root: &Path,
+    max_depth: usize,
+    follow_symlinks: bool,
+    io_timeout: Option<Duration>,
+) -> Vec<FoundBundle> {
+    let mut walk = BundleWalk {
+        follow_symlinks,
+        probe: io_timeout.map(IoProbe::new),
+        visited: HashSet::new(),
+        found: Vec::new(),
+    };
+
+    let root_path = root.to_path_buf();
+    let Some((exists, canonical)) =
+        walk.io(move || (root_path.exists(), std::fs::canonicalize(&root_path).ok()))
+    else {
+        return Vec::new();
+    };
+    match BundleKind::from_path(root).filter(|_| exists) {
+        Some(kind) => walk.found.push(FoundBundle {
+            path: root.to_path_buf(),
+            kind,
+        }),
+        None => {
+            walk.visited.extend(canonical);
+            walk.search(root, max_depth);
+        }
+    }
+
+    let mut found = walk.found;
+    found.sort_by(|a, b| a.path.cmp(&b.path));
+    found
+}
+
+/// What the search needs to know about a folder entry
+struct FolderEntry {
+    path: PathBuf,
+    is_link: bool,
+    exists: bool,
+    /// Canonical path of a folder, if the entry is one
+    folder: Option<PathBuf>,
+}
+
+/// State of a [`find_bundles()`] search
+struct BundleWalk {
+    follow_symlinks: bool,
+    /// Runs the filesystem calls when there is a timeout
+    probe: Option<IoProbe>,
+    visited: HashSet<PathBuf>,
+    found: Vec<FoundBundle>,
+}
+
+impl BundleWalk {
+    /// Run a filesystem call, giving up on it after the timeout if there is one
+    fn io<T, F>(&mut self, call: F) -> Option<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        match self.probe.as_mut() {
+            Some(probe) => probe.run(call),
+            None => Some(call()),
+        }
+    }
+
+    fn search(&mut self, folder: &Path, depth_left: usize) {
+        let folder = folder.to_path_buf();
+        let Some(entries) = self.io(move || list_folder(&folder)) else {
+            return;
+        };
+        for entry in entries {
+            if entry.is_link && !self.follow_symlinks {
+                continue;
+            }
+            if let Some(kind) = BundleKind::from_path(&entry.path) {
+                // Broken links aren't bundles; VST3 bundles are single files
+                // on Windows
+                if entry.exists {
+                    self.found.push(FoundBundle {
+                        path: entry.path,
+                        kind,
+                    });
+                }
+                continue;
+            }
+            if depth_left == 0 {
+                continue;
+            }
+            if let Some(canonical) = entry.folder {
+                if self.visited.insert(canonical) {
+                    self.search(&entry.path, depth_left - 1);
+                }
+            }
+        }
+    }
+}
+
+/// List a folder, reading everything the search needs in one go
+///
+/// Unreadable folders are empty.
+fn list_folder(folder: &Path) -> Vec<FolderEntry> {
+    let Ok(entries) = std::fs::read_dir(folder) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .map(|entry| {
+            let path = entry.path();
+            let is_link = entry.file_type().is_ok_and(|t| t.is_symlink());
+            let exists = path.exists();
+            let folder = if path.is_dir() {
+                std::fs::canonicalize(&path).ok()
+            } else {
+                None
+            };
+            FolderEntry {
+                path,
+                is_link,
+                exists,
+                folder,
+            }
+        })
+        .collect()
+}
+
+/// Lowercase and keep only alphanumeric characters
+pub(crate) fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Normalize a manufacturer name, dropping trailing company suffixes
+pub(crate) fn normalize_manufacturer(manufacturer: &str) -> String {
+    const SUFFIXES: &[&str] = &["inc", "llc", "ltd", "gmbh", "ag", "sa", "srl", "co", "corp"];
+
+    let mut words: Vec<String> = manufacturer
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .map(normalize_name)
+        .filter(|w| !w.is_empty())
+        .collect();
+    while words.len() > 1 && SUFFIXES.contains(&words[words.len() - 1].as_str()) {
+        words.pop();
+    }
+    words.concat()
+}
+
+/// Match `text` against a pattern where `*` matches any run and `?` one character
+pub(crate) fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` seen and the text position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, star_t)) = backtrack {
+            // Let the last `*` swallow one more character
+            p = star + 1;
+            t = star_t + 1;
+            backtrack = Some((star, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugin(name: &str, manufacturer: &str, plugin_type: PluginType) -> PluginInfo {
+        PluginInfo::new(
+            name.to_string(),
+            manufacturer.to_string(),
+            1,
+            plugin_type,
+            PathBuf::new(),
+            format!("{}-{}", manufacturer, name),
+        )
+    }
+
+    #[test]
+    fn test_empty_filter_matches_everything() {
+        let filter = ScanFilter::new();
+        assert!(filter.is_empty());
+        assert!(filter.matches(&plugin("AUDelay", "Apple", PluginType::Effect)));
+    }
+
+    #[test]
+    fn test_plugin_type_filter() {
+        let filter = ScanFilter::new().plugin_type(PluginType::Instrument);
+        assert!(filter.matches(&plugin("DLSMusicDevice", "Apple", PluginType::Instrument)));
+        assert!(!filter.matches(&plugin("AUDelay", "Apple", PluginType::Effect)));
+    }
+
+    #[test]
+    fn test_manufacturer_filter_is_case_insensitive() {
+        let filter = ScanFilter::new()
+            .manufacturer("apple")
+            .manufacturer("Arturia");
+        assert!(filter.matches(&plugin("AUDelay", "Apple", PluginType::Effect)));
+        assert!(filter.matches(&plugin("Pigments", "Arturia", PluginType::Instrument)));
+        assert!(!filter.matches(&plugin("Serum", "Xfer Records", PluginType::Instrument)));
+    }
+
+    #[test]
+    fn test_name_pattern() {
+        let filter = ScanFilter::new().name_pattern("delay").unwrap();
+        assert!(filter.matches(&plugin("AUDelay", "Apple", PluginType::Effect)));
+        assert!(filter.matches(&plugin("Delay", "Apple", PluginType::Effect)));
+        assert!(!filter.matches(&plugin("AUReverb", "Apple", PluginType::Effect)));
+
+        let exact = ScanFilter::new().name_pattern("^AU.elay$").unwrap();
+        assert!(exact.matches(&plugin("AUDelay", "Apple", PluginType::Effect)));
+        assert!(!exact.matches(&plugin("AUDelay2", "Apple", PluginType::Effect)));
+
+        let either = ScanFilter::new().name_pattern("^(pigments|serum)").unwrap();
+        assert!(either.matches(&plugin("Pigments", "Arturia", PluginType::Instrument)));
+        assert!(!either.matches(&plugin("Analog Lab", "Arturia", PluginType::Instrument)));
+
+        assert!(matches!(
+            ScanFilter::new().name_pattern("(unclosed"),
+            Err(Error::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_criteria_combine() {
+        let filter = ScanFilter::new()
+            .plugin_type(PluginType::Effect)
+            .manufacturer("Apple")
+            .name_pattern("^AU")
+            .unwrap();
+        assert!(filter.matches(&plugin("AUDelay", "Apple", PluginType::Effect)));
+        assert!(!filter.matches(&plugin("AUDelay", "Apple", PluginType::Instrument)));
+        assert!(!filter.matches(&plugin("Delay", "Apple", PluginType::Effect)));
+    }
+
+    #[test]
+    fn test_group_by_product() {
+        let plugins = vec![
+            plugin("Pigments", "Arturia", PluginType::Instrument).with_format(PluginFormat::Vst3),
+            plugin("AUDelay", "Apple", PluginType::Effect).with_format(PluginFormat::AudioUnit),
+            plugin("Pigments", "Arturia", PluginType::Instrument)
+                .with_format(PluginFormat::AudioUnit),
+        ];
+
+        let groups = group_by_product(plugins, DEFAULT_FORMAT_PREFERENCE);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].name, "Pigments");
+        assert_eq!(
+            groups[0].formats(),
+            vec![PluginFormat::AudioUnit, PluginFormat::Vst3]
+        );
+        assert_eq!(groups[0].preferred().format, PluginFormat::AudioUnit);
+        assert!(groups[0].get(PluginFormat::Vst3).is_some());
+        assert_eq!(groups[1].entries.len(), 1);
+    }
+
+    #[test]
+    fn test_load_best_falls_back() {
+        let plugins = vec![
+            plugin("Pro-Q 3", "FabFilter", PluginType::Effect).with_format(PluginFormat::Vst3),
+            plugin("Pro Q 3", "FabFilter, Inc.", PluginType::Effect)
+                .with_format(PluginFormat::AudioUnit),
+            plugin("Pro-C 2", "FabFilter", PluginType::Effect).with_format(PluginFormat::AudioUnit),
+        ];
+        let identity = PluginIdentity::new("fabfilter", "Pro-Q 3");
+
+        let (info, format) = load_best(&identity, &plugins, DEFAULT_FORMAT_PREFERENCE, |info| {
+            Ok(info.format)
+        })
+        .unwrap();
+        assert_eq!(info.format, PluginFormat::AudioUnit);
+        assert_eq!(format, PluginFormat::AudioUnit);
+
+        // The AudioUnit fails to instantiate: fall back to the VST3
+        let mut tried = Vec::new();
+        let (info, _) = load_best(&identity, &plugins, DEFAULT_FORMAT_PREFERENCE, |info| {
+            tried.push(info.format);
+            match info.format {
+                PluginFormat::AudioUnit => Err(Error::Other("refused".to_string())),
+                _ => Ok(()),
+            }
+        })
+        .unwrap();
+        assert_eq!(info.format, PluginFormat::Vst3);
+        assert_eq!(tried, vec![PluginFormat::AudioUnit, PluginFormat::Vst3]);
+
+        let missing = PluginIdentity::new("FabFilter", "Saturn 2");
+        assert!(matches!(
+            load_best(&missing, &plugins, DEFAULT_FORMAT_PREFERENCE, |_| Ok(())),
+            Err(Error::PluginNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_dedup_by_location() {
+        let store = PathBuf::from("/opt/plugin-store/Synth.vst3");
+        let found = |path: &str| {
+            let mut info = plugin("Synth", "Acme", PluginType::Instrument)
+                .with_format(PluginFormat::Vst3)
+                .with_canonical_path(store.clone());
+            info.path = PathBuf::from(path);
+            info
+        };
+        let plugins = vec![
+            found("/home/me/.vst3/Synth.vst3"),
+            found("/usr/lib/vst3/Synth.vst3"),
+            plugin("Synth", "Acme", PluginType::Instrument).with_format(PluginFormat::AudioUnit),
+        ];
+        assert!(plugins[0].is_linked());
+
+        let unique = dedup_by_location(plugins);
+        assert_eq!(unique.len(), 2);
+        assert_eq!(unique[0].path, PathBuf::from("/home/me/.vst3/Synth.vst3"));
+        assert_eq!(unique[0].canonical_path, store);
+        assert!(!unique[1].is_linked());
+    }
+
+    #[test]
+    fn test_group_respects_preference() {
+        let plugins = vec![
+            plugin("Pigments", "Arturia", PluginType::Instrument)
+                .with_format(PluginFormat::AudioUnit),
+            plugin("Pigments", "Arturia", PluginType::Instrument).with_format(PluginFormat::Vst3),
+        ];
+
+        let groups = group_by_product(plugins, &[PluginFormat::Vst3]);
+        assert_eq!(groups[0].preferred().format, PluginFormat::Vst3);
+    }
+
+    #[test]
+    fn test_group_normalizes_names() {
+        let plugins = vec![
+            plugin("Pro-Q 3", "FabFilter", PluginType::Effect).with_format(PluginFormat::Vst3),
+            plugin("Pro Q 3", "FabFilter Inc.", PluginType::Effect)
+                .with_format(PluginFormat::AudioUnit),
+            plugin("Pro-Q 3", "FabFilter", PluginType::Instrument).with_format(PluginFormat::Vst3),
+        ];
+
+        let groups = group_by_product(plugins, DEFAULT_FORMAT_PREFERENCE);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].entries.len(), 2);
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("", ""));
+        assert!(wildcard_match("*", "anything"));
+        assert!(wildcard_match("a*b*c", "axxbyyc"));
+        assert!(!wildcard_match("a*b*c", "axxbyy"));
+        assert!(wildcard_match("*é*", "café"));
+    }
+
+    #[test]
+    fn test_scanner_config_path_list() {
+        let first = std::env::temp_dir().join("rack-env-a");
+        let second = std::env::temp_dir().join("rack-env-b");
+        let joined =
+            std::env::join_paths([first.as_path(), Path::new(""), second.as_path()]).unwrap();
+
+        let config = ScannerConfig::new().path_list(&joined);
+        assert_eq!(config.extra_paths, vec![first, second]);
+        assert!(!config.skip_default_paths);
+        assert!(config.follow_symlinks);
+        assert!(ScannerConfig::new()
+            .path_list(std::ffi::OsStr::new(""))
+            .extra_paths
+            .is_empty());
+    }
+
+    #[test]
+    fn test_find_bundles() {
+        let root = std::env::temp_dir().join(format!("rack-deep-scan-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for dir in [
+            "Loose.vst3",
+            "Vendor/Suite.app/Contents/PlugIns/Synth.appex",
+            "Vendor/Fx.COMPONENT/Contents/Nested.vst3",
+            "deep/a/b/c/Far.vst3",
+        ] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&root, root.join("deep/loop")).unwrap();
+
+        let names = |depth| -> Vec<String> {
+            find_bundles(&root, depth, true, None)
+                .iter()
+                .map(|b| {
+                    b.path
+                        .strip_prefix(&root)
+                        .unwrap()
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect()
+        };
+        assert_eq!(names(0), vec!["Loose.vst3"]);
+        assert_eq!(names(1).len(), 2);
+        assert_eq!(names(3).len(), 2);
+        let all = names(8);
+        assert_eq!(all.len(), 4);
+        assert!(all.iter().any(|n| n.ends_with("Synth.appex")));
+
+        // Listing through the probe finds the same bundles
+        let probed = find_bundles(&root, 8, true, Some(Duration::from_secs(5)));
+        assert_eq!(probed, find_bundles(&root, 8, true, None));
+
+        let appex = find_bundles(&root.join("Vendor"), 3, true, None);
+        assert_eq!(appex[1].kind, BundleKind::AppExtension);
+        assert_eq!(appex[1].kind.format(), PluginFormat::AudioUnit);
+        let bundle = find_bundles(&root.join("Loose.vst3"), 0, true, None);
+        assert_eq!(bundle[0].kind, BundleKind::Vst3);
+        assert_eq!(
+            BundleKind::from_path(Path::new("Old Synth.DLL")),
+            Some(BundleKind::Vst2)
+        );
+        assert_eq!(
+            BundleKind::from_path(Path::new("Gain.lv2")),
+            Some(BundleKind::Lv2)
+        );
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,681 @@
+//! Whole-rack state in one document
+//!
+//! A [`SessionDocument`] holds every node of a rack (which plugin it is and
+//! its state blob from [`get_state()`](crate::PluginInstance::get_state)), its
+//! summing buses and the connections between them, and serializes to a single
+//! versioned byte string. Hosts persist a full rack with one write instead of
+//! inventing their own container around per-plugin states;
+//! [`Graph::get_state()`](crate::graph::Graph::get_state) builds one for a
+//! whole graph.
+//!
+//! Nodes and buses are identified by host-chosen IDs, unique within the
+//! document; connections refer to those IDs, or to [`INPUT_NODE`] and
+//! [`OUTPUT_NODE`] for the rack's own input and output.
+//!
+//! Before loading a document, [`preflight()`](crate::preflight::preflight)
+//! can check it against the installed plugins and report what would fail.
+//!
+//! # Format
+//!
+//! Little-endian binary: the magic `RACKSESS`, a `u32` format version, the
+//! name of the [`StateTransform`] applied to node states (empty for none), the
+//! sample rate (0 if unset), then the nodes, buses and connections as
+//! length-prefixed (`u32`) fields. Documents from newer versions are rejected rather than
+//! misread.
+//!
+//! # State Transforms
+//!
+//! Hosts with proprietary session formats can pass a [`StateTransform`] to
+//! [`to_bytes_with()`](SessionDocument::to_bytes_with) and
+//! [`from_bytes_with()`](SessionDocument::from_bytes_with) to encrypt or
+//! obfuscate the embedded plugin states. Only the state blobs are transformed;
+//! node identities and connections stay readable, so a session can be
+//! inspected (e.g. to list missing plugins) without the key.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::session::SessionDocument;
+//! # fn example(synth: &mut impl PluginInstance, reverb: &mut impl PluginInstance) -> Result<()> {
+//! let mut session = SessionDocument::new();
+//! session.add_node("synth", &*synth)?;
+//! session.add_node("reverb", &*reverb)?;
+//! session.connect("synth", "reverb");
+//! std::fs::write("song.rack", session.to_bytes()?)?;
+//!
+//! // Later
+//! let session = SessionDocument::from_bytes(&std::fs::read("song.rack")?)?;
+//! session.restore_node("synth", synth)?;
+//! session.restore_node("reverb", reverb)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::cache::parse_format;
+use crate::identity::PluginIdentity;
+use crate::{Error, PluginFormat, PluginInfo, PluginInstance, Result};
+use std::path::PathBuf;
+
+/// Magic bytes at the start of every session document
+const MAGIC: &[u8; 8] = b"RACKSESS";
+
+/// Current document format version
+pub const SESSION_VERSION: u32 = 1;
+
+/// Connection endpoint standing for the audio going into the rack
+pub const INPUT_NODE: &str = "input";
+
+/// Connection endpoint standing for the audio coming out of the rack
+pub const OUTPUT_NODE: &str = "output";
+
+/// Transforms node state blobs as session documents are written and read
+///
+/// Typically encryption: `encode()` encrypts, `decode()` decrypts. The node ID
+/// is passed along so implementations can derive per-node nonces or keys.
+///
+/// # Examples
+///
+/// ```
+/// use rack::session::StateTransform;
+///
+/// /// Not encryption, but keeps states from being read at a glance
+/// struct Xor(u8);
+///
+/// impl StateTransform for Xor {
+///     fn name(&self) -> &str {
+///         "xor"
+///     }
+///
+///     fn encode(&self, _node_id: &str, state: &[u8]) -> rack::Result<Vec<u8>> {
+///         Ok(state.iter().map(|b| b ^ self.0).collect())
+///     }
+///
+///     fn decode(&self, node_id: &str, data: &[u8]) -> rack::Result<Vec<u8>> {
+///         self.encode(node_id, data)
+///     }
+/// }
+/// ```
+pub trait StateTransform {
+    /// Identifies the transform in saved documents
+    ///
+    /// Must not be empty. Documents can only be read with a transform of the
+    /// same name; include a version (e.g. "acme-aes256-v1") if the scheme may
+    /// change.
+    fn name(&self) -> &str;
+
+    /// Transform a node's state for writing
+    ///
+    /// # Errors
+    ///
+    /// Errors abort serialization
+    fn encode(&self, node_id: &str, state: &[u8]) -> Result<Vec<u8>>;
+
+    /// Reverse [`encode()`](Self::encode) when reading
+    ///
+    /// # Errors
+    ///
+    /// Errors (e.g. a wrong key) abort parsing
+    fn decode(&self, node_id: &str, data: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// One plugin in a session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeState {
+    /// Host-chosen node ID, unique within the document
+    pub id: String,
+
+    /// Plugin name, for display when the plugin is missing
+    pub name: String,
+
+    /// Plugin format
+    pub format: PluginFormat,
+
+    /// Unique ID of the plugin, for finding it in a scan
+    pub unique_id: String,
+
+    /// The plugin product, for finding it in another format or after a
+    /// reinstall when its unique ID doesn't match
+    pub identity: PluginIdentity,
+
+    /// Path to the plugin bundle when the state was captured
+    pub path: PathBuf,
+
+    /// Plugin version when the state was captured (0 if unknown)
+    pub version: u32,
+
+    /// Input channels when the state was captured (0 if unknown)
+    pub input_channels: usize,
+
+    /// Output channels when the state was captured (0 if unknown)
+    pub output_channels: usize,
+
+    /// The plugin's state blob
+    pub state: Vec<u8>,
+}
+
+impl NodeState {
+    /// Whether this node's plugin is the one described by `info`
+    pub fn matches(&self, info: &PluginInfo) -> bool {
+        self.format == info.format && self.unique_id == info.unique_id
+    }
+}
+
+/// A bus summing its connections, without a plugin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BusState {
+    /// Host-chosen bus ID, unique among the document's nodes and buses
+    pub id: String,
+
+    /// Number of channels
+    pub channels: usize,
+}
+
+/// A connection from one node's output to another's input
+#[derive(Debug, Clone, PartialEq)]
+pub struct Connection {
+    /// Source node ID
+    pub from: String,
+
+    /// Destination node ID
+    pub to: String,
+
+    /// Whether the connection feeds the destination's sidechain input rather
+    /// than its main input
+    pub sidechain: bool,
+
+    /// Linear gain applied to the signal
+    pub gain: f32,
+}
+
+/// Nodes, their states and the connections between them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionDocument {
+    /// Nodes in the order they were added
+    pub nodes: Vec<NodeState>,
+
+    /// Buses in the order they were added
+    pub buses: Vec<BusState>,
+
+    /// Connections in the order they were added
+    pub connections: Vec<Connection>,
+
+    /// Sample rate the session was saved at, in Hz
+    pub sample_rate: Option<u32>,
+}
+
+impl SessionDocument {
+    /// Create an empty document
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Capture a plugin's identity and state as node `id`
+    ///
+    /// Replaces an existing node with the same ID, keeping its position.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin's state can't be read
+    pub fn add_node<P: PluginInstance + ?Sized>(&mut self, id: &str, plugin: &P) -> Result<()> {
+        let info = plugin.info();
+        let node = NodeState {
+            id: id.to_string(),
+            name: info.name.clone(),
+            format: info.format,
+            unique_id: info.unique_id.clone(),
+            identity: PluginIdentity::from_info(info),
+            path: info.path.clone(),
+            version: info.version,
+            input_channels: plugin.input_channels(),
+            output_channels: plugin.output_channels(),
+            state: plugin.get_state()?,
+        };
+        match self.nodes.iter_mut().find(|n| n.id == id) {
+            Some(existing) => *existing = node,
+            None => self.nodes.push(node),
+        }
+        Ok(())
+    }
+
+    /// Add a bus with `channels` channels as `id`
+    ///
+    /// Replaces an existing bus with the same ID, keeping its position.
+    pub fn add_bus(&mut self, id: &str, channels: usize) {
+        let bus = BusState {
+            id: id.to_string(),
+            channels,
+        };
+        match self.buses.iter_mut().find(|b| b.id == id) {
+            Some(existing) => *existing = bus,
+            None => self.buses.push(bus),
+        }
+    }
+
+    /// Connect node `from` to node `to`
+    pub fn connect(&mut self, from: &str, to: &str) {
+        self.connect_with_gain(from, to, 1.0);
+    }
+
+    /// Connect node `from` to node `to` with `gain` (linear)
+    pub fn connect_with_gain(&mut self, from: &str, to: &str, gain: f32) {
+        self.connections.push(Connection {
+            from: from.to_string(),
+            to: to.to_string(),
+            sidechain: false,
+            gain,
+        });
+    }
+
+    /// Connect node `from` to the sidechain input of node `to`
+    pub fn connect_sidechain(&mut self, from: &str, to: &str) {
+        self.connections.push(Connection {
+            from: from.to_string(),
+            to: to.to_string(),
+            sidechain: true,
+            gain: 1.0,
+        });
+    }
+
+    /// Get a node by ID
+    pub fn node(&self, id: &str) -> Option<&NodeState> {
+        self.nodes.iter().find(|n| n.id == id)
+    }
+
+    /// Get a bus by ID
+    pub fn bus(&self, id: &str) -> Option<&BusState> {
+        self.buses.iter().find(|b| b.id == id)
+    }
+
+    /// Whether connections can refer to `id`: a node, a bus, or the rack's
+    /// [input](INPUT_NODE) or [output](OUTPUT_NODE)
+    pub fn contains(&self, id: &str) -> bool {
+        id == INPUT_NODE
+            || id == OUTPUT_NODE
+            || self.node(id).is_some()
+            || self.bus(id).is_some()
+    }
+
+    /// Apply node `id`'s saved state to a plugin
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no such node, it was saved from a
+    /// different plugin, or the plugin rejects the state
+    pub fn restore_node<P: PluginInstance + ?Sized>(&self, id: &str, plugin: &mut P) -> Result<()> {
+        let node = self
+            .node(id)
+            .ok_or_else(|| Error::Other(format!("Session has no node '{}'", id)))?;
+        if !node.matches(plugin.info()) {
+            return Err(Error::Other(format!(
+                "Session node '{}' was saved from {} ({}), not {}",
+                id,
+                node.name,
+                node.unique_id,
+                plugin.info().unique_id
+            )));
+        }
+        plugin.set_state(&node.state)
+    }
+
+    /// Serialize to a versioned byte string
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a field, typically a node's state, is 4 GiB or
+    /// larger
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        self.write(None)
+    }
+
+    /// Serialize, passing every node's state through `transform`
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error from the transform, or an error if a field is
+    /// 4 GiB or larger
+    pub fn to_bytes_with(&self, transform: &dyn StateTransform) -> Result<Vec<u8>> {
+        self.write(Some(transform))
+    }
+
+    /// Parse a document produced by [`to_bytes()`](Self::to_bytes)
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidFormat`] if the data isn't a session document,
+    /// is truncated, comes from a newer format version, or was written with a
+    /// [`StateTransform`]
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Self::read(data, None)
+    }
+
+    /// Parse a document produced by [`to_bytes_with()`](Self::to_bytes_with)
+    ///
+    /// Documents written without a transform are read as they are.
+    ///
+    /// # Errors
+    ///
+    /// As [`from_bytes()`](Self::from_bytes), plus an error if the document
+    /// was written with a transform of a different name, or the transform
+    /// fails
+    pub fn from_bytes_with(data: &[u8], transform: &dyn StateTransform) -> Result<Self> {
+        Self::read(data, Some(transform))
+    }
+
+    fn write(&self, transform: Option<&dyn StateTransform>) -> Result<Vec<u8>> {
+        if transform.is_some_and(|t| t.name().is_empty()) {
+            return Err(Error::Other("State transform has an empty name".to_string()));
+        }
+
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&SESSION_VERSION.to_le_bytes());
+        write_bytes(&mut out, transform.map_or("", |t| t.name()).as_bytes())?;
+        write_len(&mut out, self.sample_rate.unwrap_or(0) as usize)?;
+
+        write_len(&mut out, self.nodes.len())?;
+        for node in &self.nodes {
+            write_bytes(&mut out, node.id.as_bytes())?;
+            write_bytes(&mut out, node.name.as_bytes())?;
+            write_bytes(&mut out, node.format.to_string().as_bytes())?;
+            write_bytes(&mut out, node.unique_id.as_bytes())?;
+            write_bytes(&mut out, node.identity.to_string().as_bytes())?;
+            write_bytes(&mut out, node.path.to_string_lossy().as_bytes())?;
+            write_len(&mut out, node.version as usize)?;
+            write_len(&mut out, node.input_channels)?;
+            write_len(&mut out, node.output_channels)?;
+            match transform {
+                Some(transform) => {
+                    write_bytes(&mut out, &transform.encode(&node.id, &node.state)?)?
+                }
+                None => write_bytes(&mut out, &node.state)?,
+            }
+        }
+
+        write_len(&mut out, self.buses.len())?;
+        for bus in &self.buses {
+            write_bytes(&mut out, bus.id.as_bytes())?;
+            write_len(&mut out, bus.channels)?;
+        }
+
+        write_len(&mut out, self.connections.len())?;
+        for connection in &self.connections {
+            write_bytes(&mut out, connection.from.as_bytes())?;
+            write_bytes(&mut out, connection.to.as_bytes())?;
+            out.push(connection.sidechain as u8);
+            out.extend_from_slice(&connection.gain.to_le_bytes());
+        }
+
+        Ok(out)
+    }
+
+    fn read(data: &[u8], transform: Option<&dyn StateTransform>) -> Result<Self> {
+        let mut reader = Reader::new(data);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(Error::InvalidFormat(
+                "Not a rack session document".to_string(),
+            ));
+        }
+        let version = reader.u32()?;
+        if version > SESSION_VERSION {
+            return Err(Error::InvalidFormat(format!(
+                "Session document version {} is newer than supported ({})",
+                version, SESSION_VERSION
+            )));
+        }
+
+        let transform_name = reader.string()?;
+        let transform = match (transform_name.as_str(), transform) {
+            ("", _) => None,
+            (name, Some(transform)) if transform.name() == name => Some(transform),
+            (name, _) => {
+                return Err(Error::InvalidFormat(format!(
+                    "Session plugin states were written with transform '{}'",
+                    name
+                )))
+            }
+        };
+
+        let mut document = Self::new();
+        document.sample_rate = Some(reader.u32()?).filter(|rate| *rate > 0);
+        for _ in 0..reader.u32()? {
+            let id = reader.string()?;
+            let name = reader.string()?;
+            let format = reader.string()?;
+            let format = parse_format(&format).ok_or_else(|| {
+                Error::InvalidFormat(format!("Unknown plugin format '{}' in session", format))
+            })?;
+            let unique_id = reader.string()?;
+            let identity = reader.string()?.parse()?;
+            let path = PathBuf::from(reader.string()?);
+            let plugin_version = reader.u32()?;
+            let input_channels = reader.u32()? as usize;
+            let output_channels = reader.u32()? as usize;
+            let state = match transform {
+                Some(transform) => transform.decode(&id, reader.bytes()?)?,
+                None => reader.bytes()?.to_vec(),
+            };
+            document.nodes.push(NodeState {
+                id,
+                name,
+                format,
+                unique_id,
+                identity,
+                path,
+                version: plugin_version,
+                input_channels,
+                output_channels,
+                state,
+            });
+        }
+        for _ in 0..reader.u32()? {
+            document.buses.push(BusState {
+                id: reader.string()?,
+                channels: reader.u32()? as usize,
+            });
+        }
+        for _ in 0..reader.u32()? {
+            document.connections.push(Connection {
+                from: reader.string()?,
+                to: reader.string()?,
+                sidechain: reader.u8()? != 0,
+                gain: reader.f32()?,
+            });
+        }
+
+        if !reader.is_empty() {
+            return Err(Error::InvalidFormat(
+                "Trailing data after session document".to_string(),
+            ));
+        }
+        Ok(document)
+    }
+}
+
+/// Write a `u32` length or count
+///
+/// # Errors
+///
+/// Returns an error if `len` doesn't fit, i.e. the field is 4 GiB or larger
+pub(crate) fn write_len(out: &mut Vec<u8>, len: usize) -> Result<()> {
+    let len = u32::try_from(len).map_err(|_| {
+        Error::Other(format!("Document field of {} bytes is 4 GiB or larger", len))
+    })?;
+    out.extend_from_slice(&len.to_le_bytes());
+    Ok(())
+}
+
+/// Write a length-prefixed field
+///
+/// # Errors
+///
+/// Returns an error if the field is 4 GiB or larger
+pub(crate) fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) -> Result<()> {
+    write_len(out, bytes.len())?;
+    out.extend_from_slice(bytes);
+    Ok(())
+}
+
+/// Cursor over a serialized document
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(Error::InvalidFormat("Document is truncated".to_string()));
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub(crate) fn f32(&mut self) -> Result<f32> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    pub(crate) fn f64(&mut self) -> Result<f64> {
+        Ok(f64::from_bits(self.u64()?))
+    }
+
+    pub(crate) fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    pub(crate) fn string(&mut self) -> Result<String> {
+        String::from_utf8(self.bytes()?.to_vec())
+            .map_err(|e| Error::InvalidFormat(format!("Invalid UTF-8 in document: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockPlugin;
+
+    #[test]
+    fn test_round_trip_restores_states() {
+        let mut synth = MockPlugin::new();
+        synth.set_parameter(0, 0.25).unwrap();
+        let reverb = MockPlugin::new();
+
+        let mut session = SessionDocument::new();
+        session.add_node("synth", &synth).unwrap();
+        session.add_node("reverb", &reverb).unwrap();
+        session.connect("synth", "reverb");
+        session.connect_sidechain("reverb", "synth");
+        session.add_bus("send", 2);
+        session.connect_with_gain("synth", "send", 0.5);
+        session.connect("send", OUTPUT_NODE);
+        session.sample_rate = Some(48000);
+
+        let bytes = session.to_bytes().unwrap();
+        let loaded = SessionDocument::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded, session);
+        assert_eq!(loaded.connections[0].to, "reverb");
+        assert_eq!(loaded.connections[2].gain, 0.5);
+        assert!(loaded.contains("send") && loaded.contains(OUTPUT_NODE));
+        assert!(!loaded.contains("missing"));
+
+        let mut restored = MockPlugin::new();
+        loaded.restore_node("synth", &mut restored).unwrap();
+        assert_eq!(restored.get_parameter(0).unwrap(), 0.25);
+        assert!(loaded.restore_node("missing", &mut restored).is_err());
+
+        // Re-adding a node replaces it in place
+        session.add_node("synth", &restored).unwrap();
+        assert_eq!(session.nodes.len(), 2);
+        assert_eq!(session.nodes[0].id, "synth");
+    }
+
+    #[test]
+    fn test_rejects_bad_documents() {
+        let bytes = SessionDocument::new().to_bytes().unwrap();
+        assert!(SessionDocument::from_bytes(&bytes).is_ok());
+        assert!(SessionDocument::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(SessionDocument::from_bytes(b"not a session").is_err());
+
+        let mut newer = bytes.clone();
+        newer[8..12].copy_from_slice(&(SESSION_VERSION + 1).to_le_bytes());
+        assert!(SessionDocument::from_bytes(&newer).is_err());
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_oversized_field_is_an_error() {
+        let mut out = Vec::new();
+        write_len(&mut out, u32::MAX as usize).unwrap();
+        assert!(write_len(&mut out, u32::MAX as usize + 1).is_err());
+        assert_eq!(out.len(), 4);
+    }
+
+    /// Reverses each state and checks it was given the right node
+    struct Reverse;
+
+    impl StateTransform for Reverse {
+        fn name(&self) -> &str {
+            "reverse"
+        }
+
+        fn encode(&self, node_id: &str, state: &[u8]) -> Result<Vec<u8>> {
+            let mut data = node_id.as_bytes().to_vec();
+            data.extend(state.iter().rev());
+            Ok(data)
+        }
+
+        fn decode(&self, node_id: &str, data: &[u8]) -> Result<Vec<u8>> {
+            let state = data
+                .strip_prefix(node_id.as_bytes())
+                .ok_or_else(|| Error::Other("wrong node".to_string()))?;
+            Ok(state.iter().rev().copied().collect())
+        }
+    }
+
+    #[test]
+    fn test_state_transform() {
+        let mut synth = MockPlugin::new();
+        synth.set_parameter(0, 0.75).unwrap();
+        let mut session = SessionDocument::new();
+        session.add_node("synth", &synth).unwrap();
+
+        let bytes = session.to_bytes_with(&Reverse).unwrap();
+        let plain = session.to_bytes().unwrap();
+        assert_ne!(bytes, plain);
+        assert_eq!(
+            SessionDocument::from_bytes_with(&bytes, &Reverse).unwrap(),
+            session
+        );
+
+        // Reading needs the transform; plain documents don't
+        assert!(SessionDocument::from_bytes(&bytes).is_err());
+        assert_eq!(
+            SessionDocument::from_bytes_with(&plain, &Reverse).unwrap(),
+            session
+        );
+    }
+}
@@ -0,0 +1,136 @@
+//! Dividing device buffers into processing blocks
+//!
+//! Audio devices hand over buffers of whatever size they like, and plugins
+//! process blocks of at most their configured maximum. [`BlockSplitting`]
+//! decides how a large buffer is cut up:
+//!
+//! - [`Fixed`](BlockSplitting::Fixed) renders full blocks and a shorter
+//!   remainder, the fewest `process()` calls per buffer.
+//! - [`Adaptive`](BlockSplitting::Adaptive) renders equal blocks no larger
+//!   than a target, trading throughput for uniform CPU load per block (no
+//!   short remainder followed by a full block) and for parameter changes
+//!   that land within a target's worth of samples.
+//!
+//! [`OutputStream`](crate::output::OutputStream) takes the mode in its
+//! `OutputConfig`; hosts driving their own device callbacks iterate
+//! [`BlockSplitting::blocks()`].
+//!
+//! # Examples
+//!
+//! ```
+//! use rack::split::BlockSplitting;
+//!
+//! // A 1000-frame callback with plugins initialized for 512-frame blocks
+//! let fixed: Vec<_> = BlockSplitting::Fixed.blocks(1000, 512).collect();
+//! assert_eq!(fixed, [0..512, 512..1000]);
+//!
+//! let adaptive: Vec<_> = BlockSplitting::Adaptive { target: 256 }
+//!     .blocks(1000, 512)
+//!     .collect();
+//! assert_eq!(adaptive, [0..250, 250..500, 500..750, 750..1000]);
+//! ```
+
+use std::ops::Range;
+
+/// How a device buffer is divided into processing blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BlockSplitting {
+    /// Blocks of the maximum block size, then the remainder
+    #[default]
+    Fixed,
+
+    /// Equal blocks (within a frame) of at most `target` frames
+    ///
+    /// The maximum block size still caps each block when it's smaller than
+    /// `target`.
+    Adaptive {
+        /// Largest block to process, in frames
+        target: usize,
+    },
+}
+
+impl BlockSplitting {
+    /// The blocks to process for a buffer of `frames` frames, as frame
+    /// ranges in order, none longer than `max_block_size`
+    ///
+    /// Doesn't allocate, so it can be called from the audio thread.
+    pub fn blocks(self, frames: usize, max_block_size: usize) -> Blocks {
+        let max = match self {
+            BlockSplitting::Fixed => max_block_size,
+            BlockSplitting::Adaptive { target } => target.min(max_block_size),
+        }
+        .max(1);
+        let count = frames.div_ceil(max);
+        let (size, extra) = match self {
+            BlockSplitting::Fixed => (max, 0),
+            BlockSplitting::Adaptive { .. } => (frames / count.max(1), frames % count.max(1)),
+        };
+        Blocks {
+            frames,
+            position: 0,
+            size,
+            extra,
+        }
+    }
+}
+
+/// Iterator over the blocks of a buffer, from [`BlockSplitting::blocks()`]
+#[derive(Debug, Clone)]
+pub struct Blocks {
+    frames: usize,
+    position: usize,
+    /// Frames per block
+    size: usize,
+    /// Blocks still due one more frame, so the blocks add up to `frames`
+    extra: usize,
+}
+
+impl Iterator for Blocks {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Range<usize>> {
+        if self.position >= self.frames {
+            return None;
+        }
+        let mut size = self.size;
+        if self.extra > 0 {
+            size += 1;
+            self.extra -= 1;
+        }
+        let start = self.position;
+        self.position = (start + size).min(self.frames);
+        Some(start..self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_splitting() {
+        let sizes = |mode: BlockSplitting, frames, max| {
+            mode.blocks(frames, max)
+                .map(|b| b.len())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sizes(BlockSplitting::Fixed, 1100, 512), [512, 512, 76]);
+        assert_eq!(sizes(BlockSplitting::Fixed, 0, 512), [0usize; 0]);
+
+        let adaptive = BlockSplitting::Adaptive { target: 128 };
+        assert_eq!(
+            sizes(adaptive, 1100, 512),
+            [123, 123, 122, 122, 122, 122, 122, 122, 122]
+        );
+        assert_eq!(sizes(adaptive, 100, 512), [100]);
+        // The maximum block size wins over a larger target
+        assert_eq!(sizes(adaptive, 100, 32), [25, 25, 25, 25]);
+        assert_eq!(
+            sizes(BlockSplitting::Adaptive { target: 0 }, 3, 512),
+            [1, 1, 1]
+        );
+
+        let blocks: Vec<_> = adaptive.blocks(300, 512).collect();
+        assert_eq!(blocks, [0..100, 100..200, 200..300]);
+    }
+}
@@ -0,0 +1,276 @@
+//! Where a host keeps its files
+//!
+//! The crate's persistent stores (the [`ScanCache`](crate::cache::ScanCache),
+//! [`MetadataStore`](crate::metadata::MetadataStore), preview cache, autosave
+//! snapshots) all take explicit paths. [`Storage`] picks those paths in one
+//! place, either in the platform's per-user directories or, in portable mode,
+//! under a single root: everything a rig needs then lives in one folder that
+//! can be carried on a USB stick and run on any machine.
+//!
+//! Portable roots move (a stick mounts at a different drive letter or volume
+//! name), so paths recorded inside them should be stored relative to the
+//! root with [`to_stored()`](Storage::to_stored) and turned back into full
+//! paths with [`resolve()`](Storage::resolve).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::cache::ScanCache;
+//! # use rack::metadata::MetadataStore;
+//! # use rack::storage::Storage;
+//! # fn example() -> Result<()> {
+//! // Portable if RACK_PORTABLE_ROOT is set or a marker file sits next to the
+//! // executable
+//! let storage = Storage::detect("My Host")?;
+//! storage.create_dirs()?;
+//!
+//! let cache = ScanCache::open(storage.scan_cache_path())?;
+//! let metadata = MetadataStore::open(storage.metadata_path())?;
+//! let config = storage.scanner_config();
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::scan::ScannerConfig;
+use crate::{Error, Result};
+use std::path::{Path, PathBuf};
+
+/// Environment variable selecting portable mode, holding the root directory
+pub const PORTABLE_ROOT_ENV: &str = "RACK_PORTABLE_ROOT";
+
+/// File whose presence next to the executable selects portable mode, rooted
+/// at the executable's directory
+pub const PORTABLE_MARKER: &str = "rack-portable";
+
+/// Locations of a host's persistent files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Storage {
+    /// Settings, metadata, presets and quirk tables
+    data_dir: PathBuf,
+
+    /// Files that can be rebuilt: scan cache, previews
+    cache_dir: PathBuf,
+
+    /// Root directory in portable mode
+    portable_root: Option<PathBuf>,
+}
+
+impl Storage {
+    /// Store files in the platform's per-user directories for `app_name`
+    ///
+    /// Data goes to `~/Library/Application Support` on macOS, `%APPDATA%` on
+    /// Windows and `$XDG_DATA_HOME` (or `~/.local/share`) elsewhere; caches
+    /// to `~/Library/Caches`, `%LOCALAPPDATA%` and `$XDG_CACHE_HOME` (or
+    /// `~/.cache`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the home directory (or, on Windows, the app data
+    /// directories) can't be determined
+    pub fn platform(app_name: &str) -> Result<Self> {
+        let (data, cache) = platform_dirs()?;
+        Ok(Self {
+            data_dir: data.join(app_name),
+            cache_dir: cache.join(app_name),
+            portable_root: None,
+        })
+    }
+
+    /// Store everything under `root`
+    ///
+    /// Data goes directly in `root`, caches in `root/cache`.
+    pub fn portable(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        Self {
+            data_dir: root.clone(),
+            cache_dir: root.join("cache"),
+            portable_root: Some(root),
+        }
+    }
+
+    /// Portable mode if it was asked for, the platform directories otherwise
+    ///
+    /// Portable mode is selected by [`PORTABLE_ROOT_ENV`], or by a
+    /// [`PORTABLE_MARKER`] file in the executable's directory.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`platform()`](Self::platform)
+    pub fn detect(app_name: &str) -> Result<Self> {
+        if let Some(root) = std::env::var_os(PORTABLE_ROOT_ENV).filter(|r| !r.is_empty()) {
+            return Ok(Self::portable(root));
+        }
+        let exe_dir = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf));
+        match exe_dir {
+            Some(dir) if dir.join(PORTABLE_MARKER).exists() => Ok(Self::portable(dir)),
+            _ => Self::platform(app_name),
+        }
+    }
+
+    /// Whether files live under a portable root
+    pub fn is_portable(&self) -> bool {
+        self.portable_root.is_some()
+    }
+
+    /// The portable root, if in portable mode
+    pub fn portable_root(&self) -> Option<&Path> {
+        self.portable_root.as_deref()
+    }
+
+    /// Directory for settings and other files that can't be rebuilt
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    /// Directory for files that can be rebuilt
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    /// File for the [`ScanCache`](crate::cache::ScanCache)
+    pub fn scan_cache_path(&self) -> PathBuf {
+        self.cache_dir.join("scan-cache.txt")
+    }
+
+    /// File for the [`MetadataStore`](crate::metadata::MetadataStore)
+    pub fn metadata_path(&self) -> PathBuf {
+        self.data_dir.join("metadata.txt")
+    }
+
+    /// File for the host's own [`quirks`](crate::quirks) entries
+    pub fn quirks_path(&self) -> PathBuf {
+        self.data_dir.join("quirks.txt")
+    }
+
+    /// Directory for the user's preset library
+    pub fn presets_dir(&self) -> PathBuf {
+        self.data_dir.join("presets")
+    }
+
+    /// Directory for [`PreviewService`](crate::preview::PreviewService) files
+    pub fn previews_dir(&self) -> PathBuf {
+        self.cache_dir.join("previews")
+    }
+
+    /// Directory for [`Autosave`](crate::autosave::Autosave) snapshots
+    pub fn autosave_dir(&self) -> PathBuf {
+        self.data_dir.join("autosave")
+    }
+
+    /// Directory for plugins installed with a portable rig
+    ///
+    /// `None` outside portable mode, where plugins are installed system-wide.
+    pub fn plugins_dir(&self) -> Option<PathBuf> {
+        self.portable_root.as_ref().map(|root| root.join("plugins"))
+    }
+
+    /// A scanner configuration that also searches [`plugins_dir()`](Self::plugins_dir)
+    pub fn scanner_config(&self) -> ScannerConfig {
+        let config = ScannerConfig::from_env();
+        match self.plugins_dir() {
+            Some(dir) => config.path(dir),
+            None => config,
+        }
+    }
+
+    /// Create the data, cache, presets and, in portable mode, plugins
+    /// directories
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a directory can't be created
+    pub fn create_dirs(&self) -> Result<()> {
+        std::fs::create_dir_all(&self.data_dir)?;
+        std::fs::create_dir_all(&self.cache_dir)?;
+        std::fs::create_dir_all(self.presets_dir())?;
+        if let Some(dir) = self.plugins_dir() {
+            std::fs::create_dir_all(dir)?;
+        }
+        Ok(())
+    }
+
+    /// The form of `path` to record in settings and documents
+    ///
+    /// In portable mode, paths under the root become relative to it, so they
+    /// survive the root moving. Other paths are returned unchanged.
+    pub fn to_stored(&self, path: &Path) -> PathBuf {
+        match &self.portable_root {
+            Some(root) => path.strip_prefix(root).unwrap_or(path).to_path_buf(),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// Reverse [`to_stored()`](Self::to_stored)
+    ///
+    /// In portable mode, relative paths are taken from the root.
+    pub fn resolve(&self, stored: &Path) -> PathBuf {
+        match &self.portable_root {
+            Some(root) if stored.is_relative() => root.join(stored),
+            _ => stored.to_path_buf(),
+        }
+    }
+}
+
+/// Per-user data and cache directories
+fn platform_dirs() -> Result<(PathBuf, PathBuf)> {
+    let var = |name: &str| {
+        std::env::var_os(name)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+    let missing = |what: &str| Error::Other(format!("Can't determine the {} directory", what));
+
+    if cfg!(windows) {
+        let data = var("APPDATA").ok_or_else(|| missing("app data"))?;
+        let cache = var("LOCALAPPDATA").unwrap_or_else(|| data.clone());
+        return Ok((data, cache));
+    }
+
+    let home = var("HOME").ok_or_else(|| missing("home"))?;
+    if cfg!(target_vendor = "apple") {
+        let library = home.join("Library");
+        return Ok((library.join("Application Support"), library.join("Caches")));
+    }
+    let data = var("XDG_DATA_HOME").unwrap_or_else(|| home.join(".local/share"));
+    let cache = var("XDG_CACHE_HOME").unwrap_or_else(|| home.join(".cache"));
+    Ok((data, cache))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_portable_storage() {
+        let root = std::env::temp_dir().join(format!("rack-portable-{}", std::process::id()));
+        let storage = Storage::portable(&root);
+        assert!(storage.is_portable());
+        assert!(storage.scan_cache_path().starts_with(&root));
+        assert!(storage.metadata_path().starts_with(&root));
+        assert!(storage.previews_dir().starts_with(root.join("cache")));
+
+        storage.create_dirs().unwrap();
+        assert!(storage.plugins_dir().unwrap().is_dir());
+        let config = storage.scanner_config();
+        assert!(config.extra_paths.contains(&root.join("plugins")));
+
+        // Paths on the stick are stored relative to it
+        let preset = storage.presets_dir().join("Pad.preset");
+        let stored = storage.to_stored(&preset);
+        assert_eq!(stored, Path::new("presets").join("Pad.preset"));
+        let moved = Storage::portable(root.join("elsewhere"));
+        assert_eq!(
+            moved.resolve(&stored),
+            root.join("elsewhere/presets/Pad.preset")
+        );
+
+        let outside = std::env::temp_dir().join("outside.preset");
+        assert_eq!(storage.to_stored(&outside), outside);
+        assert_eq!(storage.resolve(&outside), outside);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,706 @@
+//! Tempo map, transport and musical positions
+//!
+//! A [`TempoMap`] lists the tempo and time signature changes of a song;
+//! positions within it are counted in *beats* (quarter notes, like VST3's
+//! "project time music" and AudioUnit's beat positions) and *bars* (both
+//! counted from 0). A [`Transport`] places the map on the audio timeline: it
+//! knows the sample rate, whether the song is playing, and the sample position
+//! of the next block.
+//!
+//! [`MusicalTime`] describes when something should happen in musical terms,
+//! either absolutely ("bar 8", "beat 3.5") or relative to when it is resolved
+//! ("next bar", the way live-looping hosts launch clips).
+//! [`Transport::resolve()`] turns it into a sample position. An
+//! [`EventPort`](crate::port::EventPort) resolves scheduled MIDI events at
+//! process time, so they land sample-accurately even if the tempo changes
+//! after they were scheduled; other actions (scene changes, loop launches)
+//! can be resolved the same way by the host.
+//!
+//! For post-production, the transport also carries a video [`FrameRate`] and
+//! the SMPTE [`Timecode`] at which the song starts, so plugins that display
+//! or chase timecode see the same time as the picture.
+//!
+//! # Examples
+//!
+//! ```
+//! use rack::tempo::{MusicalTime, TempoMap, TimeSignature, Transport};
+//!
+//! let mut map = TempoMap::new(120.0);
+//! map.set_time_signature(16.0, TimeSignature::new(3, 4)); // From bar 4
+//!
+//! let mut transport = Transport::new(48000.0, map);
+//! transport.playing = true;
+//! transport.advance(1000);
+//!
+//! // At 120 BPM a beat is 24000 samples
+//! assert_eq!(transport.resolve(MusicalTime::NextBeat), 24000);
+//! assert_eq!(transport.resolve(MusicalTime::NextBar), 4 * 24000);
+//! assert_eq!(transport.resolve(MusicalTime::Bar(5.0)), (16 + 3) * 24000);
+//! ```
+
+/// Musical meter of a section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeSignature {
+    /// Beats per bar (the upper number)
+    pub numerator: u32,
+
+    /// Note value of one beat (the lower number: 4 for quarter notes)
+    pub denominator: u32,
+}
+
+impl TimeSignature {
+    /// Create a time signature
+    ///
+    /// A zero numerator or denominator is treated as 1.
+    pub fn new(numerator: u32, denominator: u32) -> Self {
+        Self {
+            numerator: numerator.max(1),
+            denominator: denominator.max(1),
+        }
+    }
+
+    /// Length of one bar in quarter notes
+    pub fn bar_length(&self) -> f64 {
+        self.numerator as f64 * 4.0 / self.denominator as f64
+    }
+}
+
+impl Default for TimeSignature {
+    /// 4/4
+    fn default() -> Self {
+        Self::new(4, 4)
+    }
+}
+
+/// A tempo and time signature taking effect at a beat
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoChange {
+    /// Position of the change, in quarter notes from the start
+    pub beat: f64,
+
+    /// Tempo from this point, in quarter notes per minute
+    pub bpm: f64,
+
+    /// Time signature from this point
+    pub time_signature: TimeSignature,
+}
+
+/// Tempo and time signature changes over a song
+///
+/// Tempo is constant between changes. Time signature changes should fall on
+/// bar lines; elsewhere the bar they interrupt is counted as a fraction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempoMap {
+    /// Sorted by beat; the first change is always at beat 0
+    changes: Vec<TempoChange>,
+}
+
+impl TempoMap {
+    /// Create a map with a constant tempo in 4/4
+    ///
+    /// Tempos that aren't positive are treated as 120 BPM.
+    pub fn new(bpm: f64) -> Self {
+        Self {
+            changes: vec![TempoChange {
+                beat: 0.0,
+                bpm: sanitize_bpm(bpm),
+                time_signature: TimeSignature::default(),
+            }],
+        }
+    }
+
+    /// All changes, in order; the first is at beat 0
+    pub fn changes(&self) -> &[TempoChange] {
+        &self.changes
+    }
+
+    /// Change the tempo from `beat` on, up to the next change
+    pub fn set_tempo(&mut self, beat: f64, bpm: f64) {
+        let index = self.change_at(beat);
+        self.changes[index].bpm = sanitize_bpm(bpm);
+    }
+
+    /// Change the time signature from `beat` on, up to the next change
+    pub fn set_time_signature(&mut self, beat: f64, time_signature: TimeSignature) {
+        let index = self.change_at(beat);
+        self.changes[index].time_signature = time_signature;
+    }
+
+    /// Remove all changes after the first
+    pub fn clear_changes(&mut self) {
+        self.changes.truncate(1);
+    }
+
+    /// Tempo at `beat`, in BPM
+    pub fn tempo_at(&self, beat: f64) -> f64 {
+        self.segment(beat).bpm
+    }
+
+    /// Time signature at `beat`
+    pub fn time_signature_at(&self, beat: f64) -> TimeSignature {
+        self.segment(beat).time_signature
+    }
+
+    /// Time from the start to `beat`, in seconds
+    pub fn seconds_at_beat(&self, beat: f64) -> f64 {
+        let beat = beat.max(0.0);
+        let mut seconds = 0.0;
+        for (i, change) in self.changes.iter().enumerate() {
+            let end = self.changes.get(i + 1).map_or(f64::INFINITY, |c| c.beat);
+            seconds += (beat.min(end) - change.beat) * 60.0 / change.bpm;
+            if beat <= end {
+                break;
+            }
+        }
+        seconds
+    }
+
+    /// Beat reached `seconds` after the start
+    pub fn beat_at_seconds(&self, seconds: f64) -> f64 {
+        let mut remaining = seconds.max(0.0);
+        for (i, change) in self.changes.iter().enumerate() {
+            let beats = remaining * change.bpm / 60.0;
+            match self.changes.get(i + 1) {
+                Some(next) if change.beat + beats > next.beat => {
+                    remaining -= (next.beat - change.beat) * 60.0 / change.bpm;
+                }
+                _ => return change.beat + beats,
+            }
+        }
+        unreachable!("the last segment is unbounded")
+    }
+
+    /// Bar containing `beat`, with the position within it as the fraction
+    pub fn bar_at_beat(&self, beat: f64) -> f64 {
+        let beat = beat.max(0.0);
+        let mut bars = 0.0;
+        for (i, change) in self.changes.iter().enumerate() {
+            let end = self.changes.get(i + 1).map_or(f64::INFINITY, |c| c.beat);
+            bars += (beat.min(end) - change.beat) / change.time_signature.bar_length();
+            if beat <= end {
+                break;
+            }
+        }
+        bars
+    }
+
+    /// Beat at which `bar` (possibly fractional) starts
+    pub fn beat_at_bar(&self, bar: f64) -> f64 {
+        let mut remaining = bar.max(0.0);
+        for (i, change) in self.changes.iter().enumerate() {
+            let length = change.time_signature.bar_length();
+            match self.changes.get(i + 1) {
+                Some(next) if change.beat + remaining * length > next.beat => {
+                    remaining -= (next.beat - change.beat) / length;
+                }
+                _ => return change.beat + remaining * length,
+            }
+        }
+        unreachable!("the last segment is unbounded")
+    }
+
+    /// The change in effect at `beat`
+    fn segment(&self, beat: f64) -> &TempoChange {
+        let index = self.changes.partition_point(|c| c.beat <= beat);
+        &self.changes[index.saturating_sub(1)]
+    }
+
+    /// Index of the change at exactly `beat`, inserting one (continuing the
+    /// previous tempo and time signature) if there is none
+    fn change_at(&mut self, beat: f64) -> usize {
+        let beat = beat.max(0.0);
+        let index = self.changes.partition_point(|c| c.beat < beat);
+        if self.changes.get(index).is_some_and(|c| c.beat == beat) {
+            return index;
+        }
+        let previous = self.changes[index - 1];
+        self.changes.insert(index, TempoChange { beat, ..previous });
+        index
+    }
+}
+
+impl Default for TempoMap {
+    /// 120 BPM in 4/4
+    fn default() -> Self {
+        Self::new(120.0)
+    }
+}
+
+fn sanitize_bpm(bpm: f64) -> f64 {
+    if bpm.is_finite() && bpm > 0.0 {
+        bpm
+    } else {
+        120.0
+    }
+}
+
+/// A position in musical time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MusicalTime {
+    /// An absolute position in quarter notes from the start
+    Beat(f64),
+
+    /// An absolute position in bars from the start (2.5 is halfway through
+    /// the third bar)
+    Bar(f64),
+
+    /// The next quarter note
+    NextBeat,
+
+    /// The next bar line
+    NextBar,
+
+    /// The next multiple of this many quarter notes (0.25 for sixteenths)
+    NextGrid(f64),
+}
+
+impl MusicalTime {
+    /// Whether the position depends on when it is resolved
+    pub fn is_relative(&self) -> bool {
+        !matches!(self, MusicalTime::Beat(_) | MusicalTime::Bar(_))
+    }
+}
+
+/// Video frame rate used for timecode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrameRate {
+    /// 23.976 fps (24 pulled down, film transferred to NTSC video)
+    Fps23976,
+    /// 24 fps (film)
+    Fps24,
+    /// 25 fps (PAL video)
+    #[default]
+    Fps25,
+    /// 29.97 fps, non-drop (NTSC video)
+    Fps2997,
+    /// 29.97 fps drop-frame (NTSC video, labels kept in step with the clock)
+    Fps2997Drop,
+    /// 30 fps
+    Fps30,
+    /// 50 fps
+    Fps50,
+    /// 59.94 fps, non-drop
+    Fps5994,
+    /// 60 fps
+    Fps60,
+}
+
+impl FrameRate {
+    /// Frames per second as counted by timecode labels (30 for 29.97)
+    pub fn nominal(&self) -> u32 {
+        match self {
+            FrameRate::Fps23976 | FrameRate::Fps24 => 24,
+            FrameRate::Fps25 => 25,
+            FrameRate::Fps2997 | FrameRate::Fps2997Drop | FrameRate::Fps30 => 30,
+            FrameRate::Fps50 => 50,
+            FrameRate::Fps5994 | FrameRate::Fps60 => 60,
+        }
+    }
+
+    /// Whether frames run 0.1% slower than [`nominal()`](Self::nominal)
+    pub fn is_pull_down(&self) -> bool {
+        matches!(
+            self,
+            FrameRate::Fps23976 | FrameRate::Fps2997 | FrameRate::Fps2997Drop | FrameRate::Fps5994
+        )
+    }
+
+    /// Whether frame labels are skipped to stay in step with the clock
+    pub fn is_drop_frame(&self) -> bool {
+        matches!(self, FrameRate::Fps2997Drop)
+    }
+
+    /// Actual frames per second
+    pub fn fps(&self) -> f64 {
+        let nominal = self.nominal() as f64;
+        if self.is_pull_down() {
+            nominal * 1000.0 / 1001.0
+        } else {
+            nominal
+        }
+    }
+}
+
+/// Number of subframes in a frame (the MIDI Time Code and VST3 resolution)
+pub const SUBFRAMES_PER_FRAME: u32 = 80;
+
+/// An SMPTE timecode (hours:minutes:seconds:frames)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Timecode {
+    /// Hours
+    pub hours: u32,
+
+    /// Minutes (0-59)
+    pub minutes: u32,
+
+    /// Seconds (0-59)
+    pub seconds: u32,
+
+    /// Frames within the second
+    pub frames: u32,
+
+    /// Fraction of a frame, in 1/80ths
+    pub subframes: u32,
+}
+
+impl Timecode {
+    /// Create a timecode on a frame boundary
+    pub fn new(hours: u32, minutes: u32, seconds: u32, frames: u32) -> Self {
+        Self {
+            hours,
+            minutes,
+            seconds,
+            frames,
+            subframes: 0,
+        }
+    }
+
+    /// Number of whole frames since 00:00:00:00 at `rate`
+    ///
+    /// Drop-frame labels that don't exist (the first two frames of most
+    /// minutes) count as the next existing frame.
+    pub fn to_frames(&self, rate: FrameRate) -> u64 {
+        let nominal = rate.nominal() as u64;
+        let total_seconds =
+            self.hours as u64 * 3600 + self.minutes as u64 * 60 + self.seconds as u64;
+        let frames = total_seconds * nominal + self.frames as u64;
+        if rate.is_drop_frame() {
+            let minutes = self.hours as u64 * 60 + self.minutes as u64;
+            let dropped = 2 * (minutes - minutes / 10);
+            frames.saturating_sub(dropped)
+        } else {
+            frames
+        }
+    }
+
+    /// The timecode of the `frames`th frame at `rate`
+    pub fn from_frames(frames: u64, rate: FrameRate) -> Self {
+        let nominal = rate.nominal() as u64;
+        let mut frames = frames;
+        if rate.is_drop_frame() {
+            // 17982 frames per ten minutes; two labels dropped per minute
+            // except every tenth
+            let tens = frames / 17982;
+            let rest = frames % 17982;
+            frames += 18 * tens;
+            if rest >= 2 {
+                frames += 2 * ((rest - 2) / 1798);
+            }
+        }
+        let total_seconds = frames / nominal;
+        Self {
+            hours: (total_seconds / 3600) as u32,
+            minutes: (total_seconds / 60 % 60) as u32,
+            seconds: (total_seconds % 60) as u32,
+            frames: (frames % nominal) as u32,
+            subframes: 0,
+        }
+    }
+
+    /// The timecode `seconds` after 00:00:00:00 at `rate`
+    pub fn from_seconds(seconds: f64, rate: FrameRate) -> Self {
+        let frames = seconds.max(0.0) * rate.fps();
+        let whole = frames.floor();
+        Self {
+            subframes: (((frames - whole) * SUBFRAMES_PER_FRAME as f64) as u32)
+                .min(SUBFRAMES_PER_FRAME - 1),
+            ..Self::from_frames(whole as u64, rate)
+        }
+    }
+
+    /// Time since 00:00:00:00 at `rate`, in seconds
+    pub fn to_seconds(&self, rate: FrameRate) -> f64 {
+        let frames =
+            self.to_frames(rate) as f64 + self.subframes as f64 / SUBFRAMES_PER_FRAME as f64;
+        frames / rate.fps()
+    }
+
+    /// Time since 00:00:00:00 at `rate`, in subframes
+    pub fn to_subframes(&self, rate: FrameRate) -> u64 {
+        self.to_frames(rate) * SUBFRAMES_PER_FRAME as u64 + self.subframes as u64
+    }
+}
+
+impl std::fmt::Display for Timecode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}:{:02}:{:02}",
+            self.hours, self.minutes, self.seconds, self.frames
+        )
+    }
+}
+
+/// A section of the song the host plays repeatedly, in beats
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoopRange {
+    /// First beat of the loop
+    pub start: f64,
+
+    /// Beat the loop jumps back from (exclusive)
+    pub end: f64,
+}
+
+impl LoopRange {
+    /// Loop from beat `start` to beat `end`
+    pub fn new(start: f64, end: f64) -> Self {
+        Self { start, end }
+    }
+}
+
+/// The song's timeline as seen by the audio thread
+///
+/// The host advances the transport once per block, after every node has
+/// processed it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transport {
+    /// Sample rate in Hz
+    pub sample_rate: f64,
+
+    /// Tempo and time signature changes
+    pub tempo_map: TempoMap,
+
+    /// Whether the song is playing
+    pub playing: bool,
+
+    /// Song position of the next block's first sample
+    pub position: u64,
+
+    /// Frame rate of the picture, for timecode
+    pub frame_rate: FrameRate,
+
+    /// Timecode at the start of the song (often 01:00:00:00)
+    pub smpte_offset: Timecode,
+
+    /// Loop the host is playing, reported to plugins so tempo-synced
+    /// effects can follow it
+    ///
+    /// The transport doesn't jump back by itself; the host
+    /// [`seek()`](Self::seek)s to the start when it reaches the end.
+    pub loop_range: Option<LoopRange>,
+}
+
+impl Transport {
+    /// Create a stopped transport at the start of the song
+    pub fn new(sample_rate: f64, tempo_map: TempoMap) -> Self {
+        Self {
+            sample_rate,
+            tempo_map,
+            playing: false,
+            position: 0,
+            frame_rate: FrameRate::default(),
+            smpte_offset: Timecode::default(),
+            loop_range: None,
+        }
+    }
+
+    /// Move past a block of `num_frames`, if playing
+    pub fn advance(&mut self, num_frames: usize) {
+        if self.playing {
+            self.position += num_frames as u64;
+        }
+    }
+
+    /// Jump to a sample position
+    pub fn seek(&mut self, position: u64) {
+        self.position = position;
+    }
+
+    /// Current position in quarter notes
+    pub fn beat(&self) -> f64 {
+        self.beat_at_sample(self.position)
+    }
+
+    /// Current position in bars
+    pub fn bar(&self) -> f64 {
+        self.tempo_map.bar_at_beat(self.beat())
+    }
+
+    /// Current tempo in BPM
+    pub fn tempo(&self) -> f64 {
+        self.tempo_map.tempo_at(self.beat())
+    }
+
+    /// Current time signature
+    pub fn time_signature(&self) -> TimeSignature {
+        self.tempo_map.time_signature_at(self.beat())
+    }
+
+    /// Beat on which the current bar starts
+    pub fn bar_start(&self) -> f64 {
+        // Tolerate rounding when the position is on a downbeat
+        self.tempo_map.beat_at_bar((self.bar() + 1e-9).floor())
+    }
+
+    /// Timecode of the current position
+    pub fn timecode(&self) -> Timecode {
+        self.timecode_at_sample(self.position)
+    }
+
+    /// Timecode of a sample position
+    pub fn timecode_at_sample(&self, position: u64) -> Timecode {
+        let seconds = position as f64 / self.sample_rate;
+        Timecode::from_seconds(
+            self.smpte_offset.to_seconds(self.frame_rate) + seconds,
+            self.frame_rate,
+        )
+    }
+
+    /// Position in quarter notes of a sample position
+    pub fn beat_at_sample(&self, position: u64) -> f64 {
+        self.tempo_map
+            .beat_at_seconds(position as f64 / self.sample_rate)
+    }
+
+    /// Sample position of a beat, rounded to the nearest sample
+    pub fn sample_at_beat(&self, beat: f64) -> u64 {
+        (self.tempo_map.seconds_at_beat(beat) * self.sample_rate).round() as u64
+    }
+
+    /// Beat a musical position refers to, relative positions counting from
+    /// the current position
+    ///
+    /// A relative position that falls exactly on the current position resolves
+    /// to it rather than to the following beat or bar.
+    pub fn anchor(&self, time: MusicalTime) -> f64 {
+        let beat = self.beat();
+        let next = |grid: f64| {
+            if grid > 0.0 {
+                // Tolerate rounding when the position is on the grid
+                (beat / grid - 1e-9).ceil().max(0.0) * grid
+            } else {
+                beat
+            }
+        };
+        match time {
+            MusicalTime::Beat(beat) => beat,
+            MusicalTime::Bar(bar) => self.tempo_map.beat_at_bar(bar),
+            MusicalTime::NextBeat => next(1.0),
+            MusicalTime::NextGrid(grid) => next(grid),
+            MusicalTime::NextBar => {
+                let bar = self.tempo_map.bar_at_beat(beat);
+                self.tempo_map.beat_at_bar((bar - 1e-9).ceil().max(0.0))
+            }
+        }
+    }
+
+    /// Sample position a musical position refers to
+    ///
+    /// Relative positions count from the current position.
+    pub fn resolve(&self, time: MusicalTime) -> u64 {
+        self.sample_at_beat(self.anchor(time))
+    }
+
+    /// Follow an external timeline (such as an Ableton Link session) whose
+    /// tempo is `bpm` and which is at `beat` at the current position
+    ///
+    /// The tempo becomes `bpm` from the current position on. While playing,
+    /// the position also moves by less than half a `quantum` so that its
+    /// phase within a quantum of beats (usually a bar) matches `beat`'s;
+    /// differences of a sample or less are left alone, so following a
+    /// steady timeline every block doesn't jitter.
+    pub fn follow(&mut self, bpm: f64, beat: f64, quantum: f64) {
+        if self.playing && quantum > 0.0 && self.sample_rate > 0.0 {
+            let current = self.beat();
+            let mut offset = (beat - current).rem_euclid(quantum);
+            if offset > quantum / 2.0 {
+                offset -= quantum;
+            }
+            let sample = bpm / 60.0 / self.sample_rate;
+            if offset.abs() > sample {
+                let target = current + offset;
+                let target = if target < 0.0 {
+                    target + quantum
+                } else {
+                    target
+                };
+                self.seek(self.sample_at_beat(target));
+            }
+        }
+        if (self.tempo() - bpm).abs() > 1e-6 {
+            self.tempo_map.set_tempo(self.beat(), bpm);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tempo_changes() {
+        let mut map = TempoMap::new(120.0);
+        map.set_tempo(8.0, 60.0);
+        map.set_time_signature(8.0, TimeSignature::new(6, 8));
+
+        // 8 beats at 120 BPM, then 3 at 60
+        assert_eq!(map.seconds_at_beat(11.0), 7.0);
+        assert_eq!(map.beat_at_seconds(7.0), 11.0);
+        assert_eq!(map.tempo_at(7.9), 120.0);
+
+        // Two bars of 4/4, then bars of three quarter notes
+        assert_eq!(map.bar_at_beat(11.0), 3.0);
+        assert_eq!(map.beat_at_bar(4.0), 14.0);
+        assert_eq!(map.changes().len(), 2);
+    }
+
+    #[test]
+    fn test_resolve_relative_positions() {
+        let mut transport = Transport::new(1000.0, TempoMap::new(60.0));
+        assert_eq!(transport.resolve(MusicalTime::NextBar), 0);
+
+        transport.playing = true;
+        transport.advance(4500);
+        assert_eq!(transport.resolve(MusicalTime::NextBeat), 5000);
+        assert_eq!(transport.resolve(MusicalTime::NextGrid(0.25)), 4500);
+        assert_eq!(transport.resolve(MusicalTime::NextBar), 8000);
+        assert_eq!(transport.resolve(MusicalTime::Beat(3.5)), 3500);
+    }
+
+    #[test]
+    fn test_follow_external_timeline() {
+        let mut transport = Transport::new(1000.0, TempoMap::new(60.0));
+        transport.seek(8000);
+
+        // Stopped: only the tempo follows, from the current beat on
+        transport.follow(120.0, 2.5, 4.0);
+        assert_eq!(transport.position, 8000);
+        assert_eq!(transport.tempo(), 120.0);
+        assert_eq!(transport.tempo_map.tempo_at(7.9), 60.0);
+
+        // Playing: the position moves to the nearest beat with the same phase
+        transport.playing = true;
+        transport.follow(120.0, 2.5, 4.0);
+        assert_eq!(transport.beat(), 6.5);
+        assert_eq!(transport.tempo(), 120.0);
+        transport.follow(120.0, 3.0, 4.0);
+        assert_eq!(transport.beat(), 7.0);
+
+        // Within a sample, nothing moves
+        let position = transport.position;
+        transport.follow(120.0, 3.0001, 4.0);
+        assert_eq!(transport.position, position);
+    }
+
+    #[test]
+    fn test_timecode() {
+        let mut transport = Transport::new(48000.0, TempoMap::default());
+        transport.frame_rate = FrameRate::Fps25;
+        transport.smpte_offset = Timecode::new(1, 0, 0, 0);
+        transport.seek(48000 * 61 + 48000 / 25 * 3 + 960);
+        let timecode = transport.timecode();
+        assert_eq!(timecode.to_string(), "01:01:01:03");
+        assert_eq!(timecode.subframes, 40);
+
+        // Drop-frame skips labels ;00 and ;01 except every tenth minute
+        let rate = FrameRate::Fps2997Drop;
+        assert_eq!(Timecode::from_frames(1800, rate), Timecode::new(0, 1, 0, 2));
+        assert_eq!(
+            Timecode::from_frames(17982, rate),
+            Timecode::new(0, 10, 0, 0)
+        );
+        for frames in [0, 1799, 1800, 17981, 17982, 107892] {
+            assert_eq!(Timecode::from_frames(frames, rate).to_frames(rate), frames);
+        }
+    }
+}
@@ -0,0 +1,295 @@
+//! Test helpers shared by unit tests across modules
+
+use crate::{
+    Error, MidiEvent, ParameterInfo, ParameterVisibility, PluginInfo, PluginInstance, PluginType,
+    PresetInfo, Result,
+};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Held by tests that install an event sink, which is process-wide
+pub(crate) static SINK_LOCK: Mutex<()> = Mutex::new(());
+
+/// How long [`MockPlugin::slow_blocks`] take to process
+pub(crate) const SLOW_BLOCK: Duration = Duration::from_millis(200);
+
+/// In-memory stereo gain plugin for exercising host-side code without real plugins
+///
+/// Parameters:
+/// - 0: "Gain" in dB (-60 to 12, default 0)
+/// - 1: "Mix" (0 to 1, default 1)
+/// - 2: "Oversampling" (0 to 4, default 0), expert-only and ignored by `process()`
+pub(crate) struct MockPlugin {
+    info: PluginInfo,
+    initialized: bool,
+    max_block_size: usize,
+    sample_rate: f64,
+    params: Vec<ParameterInfo>,
+    values: Vec<f32>,
+    pub(crate) midi_received: Vec<MidiEvent>,
+    /// Per-channel delay lines implementing the configured latency
+    delay: Vec<VecDeque<f32>>,
+    /// Tail reported once initialized (nothing actually rings out)
+    tail: usize,
+    /// Indices of `process()` calls that should fail (counted from 0)
+    pub(crate) fail_blocks: Vec<usize>,
+    /// Indices of `process()` calls that should panic (counted from 0)
+    pub(crate) panic_blocks: Vec<usize>,
+    /// Indices of `process()` calls that abort the process (counted from 0);
+    /// only for plugins in helper processes
+    pub(crate) abort_blocks: Vec<usize>,
+    /// Indices of `process()` calls that take [`SLOW_BLOCK`] (counted from 0)
+    pub(crate) slow_blocks: Vec<usize>,
+    /// Highest sample rate `initialize()` accepts
+    pub(crate) max_sample_rate: Option<f64>,
+    /// Reported by `take_state_dirty()`, like a plugin announcing unsaved changes
+    pub(crate) state_dirty: AtomicBool,
+    process_calls: usize,
+    sample_position: u64,
+}
+
+impl MockPlugin {
+    pub(crate) fn new() -> Self {
+        let params = vec![
+            ParameterInfo::new(0, "Gain".to_string(), -60.0, 12.0, 0.0, "dB".to_string()),
+            ParameterInfo::new(1, "Mix".to_string(), 0.0, 1.0, 1.0, "%".to_string()),
+            ParameterInfo::new(2, "Oversampling".to_string(), 0.0, 4.0, 0.0, "x".to_string())
+                .with_visibility(ParameterVisibility::Expert),
+        ];
+        let values = params
+            .iter()
+            .map(|p| p.default_normalized().value())
+            .collect();
+        Self {
+            info: PluginInfo::new(
+                "Mock Gain".to_string(),
+                "Rack".to_string(),
+                1,
+                PluginType::Effect,
+                PathBuf::from("/dev/null"),
+                "mock-gain".to_string(),
+            ),
+            initialized: false,
+            max_block_size: 0,
+            sample_rate: 0.0,
+            params,
+            values,
+            midi_received: Vec::new(),
+            delay: Vec::new(),
+            tail: 0,
+            fail_blocks: Vec::new(),
+            panic_blocks: Vec::new(),
+            abort_blocks: Vec::new(),
+            slow_blocks: Vec::new(),
+            max_sample_rate: None,
+            state_dirty: AtomicBool::new(false),
+            process_calls: 0,
+            sample_position: 0,
+        }
+    }
+
+    /// Delay the output by `samples` frames
+    pub(crate) fn with_latency(mut self, samples: usize) -> Self {
+        self.delay = vec![VecDeque::from(vec![0.0; samples]); 2];
+        self
+    }
+
+    /// Report a tail of `samples` frames
+    pub(crate) fn with_tail(mut self, samples: usize) -> Self {
+        self.tail = samples;
+        self
+    }
+
+    fn gain(&self) -> f32 {
+        let db = self.params[0]
+            .to_plain(crate::Normalized::new(self.values[0]))
+            .value();
+        10f32.powf(db / 20.0)
+    }
+}
+
+impl PluginInstance for MockPlugin {
+    fn initialize(&mut self, sample_rate: f64, max_block_size: usize) -> Result<()> {
+        if self.max_sample_rate.is_some_and(|max| sample_rate > max) {
+            return Err(Error::Other("Mock sample rate not supported".to_string()));
+        }
+        self.initialized = true;
+        self.max_block_size = max_block_size;
+        self.sample_rate = sample_rate;
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        for line in self.delay.iter_mut() {
+            line.iter_mut().for_each(|s| *s = 0.0);
+        }
+        Ok(())
+    }
+
+    fn process(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        if !self.initialized {
+            return Err(Error::NotInitialized);
+        }
+        if num_frames > self.max_block_size {
+            return Err(Error::BlockTooLarge {
+                max: self.max_block_size,
+                got: num_frames,
+            });
+        }
+        let call = self.process_calls;
+        self.process_calls += 1;
+        if self.fail_blocks.contains(&call) {
+            return Err(Error::Other("Mock processing failure".to_string()));
+        }
+        if self.panic_blocks.contains(&call) {
+            panic!("Mock processing panic");
+        }
+        if self.abort_blocks.contains(&call) {
+            std::process::abort();
+        }
+        if self.slow_blocks.contains(&call) {
+            std::thread::sleep(SLOW_BLOCK);
+        }
+        let gain = self.gain();
+        for (ch, output) in outputs.iter_mut().enumerate() {
+            match inputs.get(ch) {
+                Some(input) => {
+                    for i in 0..num_frames {
+                        output[i] = input[i] * gain;
+                    }
+                    if let Some(line) = self.delay.get_mut(ch) {
+                        for sample in output[..num_frames].iter_mut() {
+                            line.push_back(*sample);
+                            *sample = line.pop_front().unwrap_or(0.0);
+                        }
+                    }
+                }
+                None => output[..num_frames].fill(0.0),
+            }
+        }
+        self.sample_position += num_frames as u64;
+        Ok(())
+    }
+
+    fn sample_position(&self) -> u64 {
+        self.sample_position
+    }
+
+    fn set_sample_position(&mut self, position: u64) -> Result<()> {
+        if position > i64::MAX as u64 {
+            return Err(Error::Other("Sample position out of range".to_string()));
+        }
+        self.sample_position = position;
+        Ok(())
+    }
+
+    fn parameter_count(&self) -> usize {
+        self.params.len()
+    }
+
+    fn parameter_info(&self, index: usize) -> Result<ParameterInfo> {
+        self.params
+            .get(index)
+            .cloned()
+            .ok_or(Error::InvalidParameter(index))
+    }
+
+    fn get_parameter(&self, index: usize) -> Result<f32> {
+        self.values
+            .get(index)
+            .copied()
+            .ok_or(Error::InvalidParameter(index))
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) -> Result<()> {
+        let slot = self
+            .values
+            .get_mut(index)
+            .ok_or(Error::InvalidParameter(index))?;
+        *slot = value.clamp(0.0, 1.0);
+        Ok(())
+    }
+
+    fn send_midi(&mut self, events: &[MidiEvent]) -> Result<()> {
+        self.midi_received.extend_from_slice(events);
+        Ok(())
+    }
+
+    fn preset_count(&self) -> Result<usize> {
+        Ok(0)
+    }
+
+    fn preset_info(&self, index: usize) -> Result<PresetInfo> {
+        Err(Error::InvalidParameter(index))
+    }
+
+    fn load_preset(&mut self, _preset_number: i32) -> Result<()> {
+        Err(Error::Other("Mock plugin has no presets".to_string()))
+    }
+
+    fn get_state(&self) -> Result<Vec<u8>> {
+        Ok(self.values.iter().flat_map(|v| v.to_le_bytes()).collect())
+    }
+
+    fn set_state(&mut self, data: &[u8]) -> Result<()> {
+        if data.len() != self.values.len() * 4 {
+            return Err(Error::Other("Invalid mock state".to_string()));
+        }
+        for (value, bytes) in self.values.iter_mut().zip(data.chunks_exact(4)) {
+            *value = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        Ok(())
+    }
+
+    fn take_state_dirty(&self) -> bool {
+        self.state_dirty.swap(false, Ordering::AcqRel)
+    }
+
+    fn info(&self) -> &PluginInfo {
+        &self.info
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    fn input_channels(&self) -> usize {
+        if self.initialized {
+            2
+        } else {
+            0
+        }
+    }
+
+    fn output_channels(&self) -> usize {
+        if self.initialized {
+            2
+        } else {
+            0
+        }
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.delay.first().map_or(0, VecDeque::len)
+    }
+
+    fn tail_samples(&self) -> usize {
+        if self.initialized {
+            self.tail
+        } else {
+            0
+        }
+    }
+
+    fn tail_seconds(&self) -> f64 {
+        crate::traits::tail_seconds(self.tail_samples(), self.sample_rate)
+    }
+}
@@ -0,0 +1,252 @@
+//! Decoding of names reported by plugins
+//!
+//! Plugin, manufacturer, parameter and preset names are expected to arrive as
+//! UTF-8, but legacy plugins (older AudioUnits in particular) sometimes report
+//! them in Mac OS Roman or another 8-bit encoding. Rejecting those strings would
+//! let one old plugin break a whole scan or parameter listing, so by default
+//! invalid sequences are replaced with U+FFFD. The process-wide
+//! [`StringDecoding`] mode can instead decode them as Mac OS Roman, or restore
+//! strict validation for tools that want to flag broken plugins.
+//!
+//! Identifiers and paths are always decoded strictly: a guessed unique ID or
+//! path would only fail later, and more confusingly.
+//!
+//! # Examples
+//!
+//! ```
+//! use rack::text::{self, StringDecoding};
+//!
+//! // 0x8E is "é" in Mac OS Roman
+//! assert_eq!(StringDecoding::Lossy.decode(b"Caf\x8e").unwrap(), "Caf\u{FFFD}");
+//! assert_eq!(StringDecoding::MacRoman.decode(b"Caf\x8e").unwrap(), "Café");
+//! assert!(StringDecoding::Strict.decode(b"Caf\x8e").is_err());
+//!
+//! // A validator that wants to know about every bad string
+//! text::set_string_decoding(StringDecoding::Strict);
+//! # text::set_string_decoding(StringDecoding::Lossy);
+//! ```
+
+use crate::{Error, Result};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How to handle names that aren't valid UTF-8
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum StringDecoding {
+    /// Replace invalid sequences with U+FFFD
+    #[default]
+    Lossy,
+
+    /// Decode strings that aren't valid UTF-8 as Mac OS Roman
+    MacRoman,
+
+    /// Fail with an error
+    Strict,
+}
+
+impl StringDecoding {
+    /// Decode bytes according to this mode
+    ///
+    /// Valid UTF-8 is returned unchanged in every mode.
+    ///
+    /// # Errors
+    ///
+    /// In [`Strict`](Self::Strict) mode, returns the UTF-8 error for invalid
+    /// input
+    pub fn decode(self, bytes: &[u8]) -> std::result::Result<String, std::str::Utf8Error> {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => Ok(s.to_string()),
+            Err(e) => match self {
+                StringDecoding::Lossy => Ok(String::from_utf8_lossy(bytes).into_owned()),
+                StringDecoding::MacRoman => Ok(bytes.iter().map(|&b| mac_roman_char(b)).collect()),
+                StringDecoding::Strict => Err(e),
+            },
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            StringDecoding::Lossy => 0,
+            StringDecoding::MacRoman => 1,
+            StringDecoding::Strict => 2,
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => StringDecoding::MacRoman,
+            2 => StringDecoding::Strict,
+            _ => StringDecoding::Lossy,
+        }
+    }
+}
+
+/// The process-wide decoding mode
+static DECODING: AtomicU8 = AtomicU8::new(0);
+
+/// Set how names from plugins are decoded from now on
+pub fn set_string_decoding(mode: StringDecoding) {
+    DECODING.store(mode.to_u8(), Ordering::Relaxed);
+}
+
+/// The current decoding mode
+pub fn string_decoding() -> StringDecoding {
+    StringDecoding::from_u8(DECODING.load(Ordering::Relaxed))
+}
+
+/// Decode a name with the current mode, naming the field in errors
+pub(crate) fn decode_name(bytes: &[u8], field_name: &str) -> Result<String> {
+    string_decoding()
+        .decode(bytes)
+        .map_err(|e| Error::Other(format!("Invalid UTF-8 in {}: {}", field_name, e)))
+}
+
+/// Attempts before giving up on a string that keeps changing size
+#[cfg(any(vst3_sdk, vst2_sdk, target_vendor = "apple", test))]
+const NEGOTIATE_ATTEMPTS: usize = 4;
+
+/// Read a string through a length-negotiating FFI call
+///
+/// `call(buffer, size)` follows the rack-sys contract: with a null buffer it
+/// stores the required size (including the null terminator) in `*size`; with a
+/// buffer it copies the string, or returns `too_small` and the new required size
+/// if the string grew in between, in which case the call is retried.
+///
+/// Returns the last non-negative result code and the string's bytes without
+/// the terminator, or the first negative result code.
+#[cfg(any(vst3_sdk, vst2_sdk, target_vendor = "apple", test))]
+pub(crate) fn read_negotiated<F>(
+    too_small: i32,
+    mut call: F,
+) -> std::result::Result<(i32, Vec<u8>), i32>
+where
+    F: FnMut(*mut std::ffi::c_char, *mut usize) -> i32,
+{
+    let mut size = 0usize;
+    let code = call(std::ptr::null_mut(), &mut size);
+    if code < 0 {
+        return Err(code);
+    }
+
+    for _ in 0..NEGOTIATE_ATTEMPTS {
+        let mut buffer = vec![0u8; size.max(1)];
+        let mut used = buffer.len();
+        let code = call(buffer.as_mut_ptr() as *mut std::ffi::c_char, &mut used);
+        if code == too_small {
+            size = used;
+            continue;
+        }
+        if code < 0 {
+            return Err(code);
+        }
+
+        // Everything up to the terminator (the buffer is zeroed, so one exists
+        // even if the callee reported no string)
+        let len = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+        buffer.truncate(len);
+        return Ok((code, buffer));
+    }
+    Err(too_small)
+}
+
+/// Upper half of Mac OS Roman (0x80-0xFF)
+const MAC_ROMAN_HIGH: [char; 128] = [
+    'Ä', 'Å', 'Ç', 'É', 'Ñ', 'Ö', 'Ü', 'á', 'à', 'â', 'ä', 'ã', 'å', 'ç', 'é', 'è', //
+    'ê', 'ë', 'í', 'ì', 'î', 'ï', 'ñ', 'ó', 'ò', 'ô', 'ö', 'õ', 'ú', 'ù', 'û', 'ü', //
+    '†', '°', '¢', '£', '§', '•', '¶', 'ß', '®', '©', '™', '´', '¨', '≠', 'Æ', 'Ø', //
+    '∞', '±', '≤', '≥', '¥', 'µ', '∂', '∑', '∏', 'π', '∫', 'ª', 'º', 'Ω', 'æ', 'ø', //
+    '¿', '¡', '¬', '√', 'ƒ', '≈', '∆', '«', '»', '…', '\u{A0}', 'À', 'Ã', 'Õ', 'Œ', 'œ', //
+    '–', '—', '“', '”', '‘', '’', '÷', '◊', 'ÿ', 'Ÿ', '⁄', '€', '‹', '›', 'ﬁ', 'ﬂ', //
+    '‡', '·', '‚', '„', '‰', 'Â', 'Ê', 'Á', 'Ë', 'È', 'Í', 'Î', 'Ï', 'Ì', 'Ó', 'Ô', //
+    '\u{F8FF}', 'Ò', 'Ú', 'Û', 'Ù', 'ı', 'ˆ', '˜', '¯', '˘', '˙', '˚', '¸', '˝', '˛', 'ˇ', //
+];
+
+fn mac_roman_char(byte: u8) -> char {
+    if byte < 0x80 {
+        byte as char
+    } else {
+        MAC_ROMAN_HIGH[(byte - 0x80) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modes() {
+        let valid = "Filtre passe-bas ñ".as_bytes();
+        for mode in [
+            StringDecoding::Lossy,
+            StringDecoding::MacRoman,
+            StringDecoding::Strict,
+        ] {
+            assert_eq!(mode.decode(valid).unwrap(), "Filtre passe-bas ñ");
+        }
+
+        // "Résonance" in Mac OS Roman
+        let legacy = b"R\x8esonance";
+        assert_eq!(
+            StringDecoding::Lossy.decode(legacy).unwrap(),
+            "R\u{FFFD}sonance"
+        );
+        assert_eq!(
+            StringDecoding::MacRoman.decode(legacy).unwrap(),
+            "Résonance"
+        );
+        assert!(StringDecoding::Strict.decode(legacy).is_err());
+    }
+
+    /// Stands in for a rack-sys string getter
+    fn fake_getter(value: &str, buffer: *mut std::ffi::c_char, size: *mut usize) -> i32 {
+        const TOO_SMALL: i32 = -7;
+        let required = value.len() + 1;
+        // Safety: read_negotiated passes a valid size and a buffer of *size bytes
+        unsafe {
+            if buffer.is_null() {
+                *size = required;
+                return 0;
+            }
+            if *size < required {
+                *size = required;
+                return TOO_SMALL;
+            }
+            std::ptr::copy_nonoverlapping(value.as_ptr(), buffer as *mut u8, value.len());
+            *buffer.add(value.len()) = 0;
+            *size = required;
+        }
+        0
+    }
+
+    #[test]
+    fn test_read_negotiated() {
+        // Far longer than the old fixed 1024-byte path buffers
+        let long = format!("C:\\Users\\音楽\\{}\\Synth.vst3", "Nested\\".repeat(300));
+        let (code, bytes) = read_negotiated(-7, |b, s| fake_getter(&long, b, s)).unwrap();
+        assert_eq!(code, 0);
+        assert_eq!(bytes, long.as_bytes());
+
+        // The string grows between the size query and the copy
+        let mut calls = 0;
+        let (_, bytes) = read_negotiated(-7, |b, s| {
+            calls += 1;
+            fake_getter(if calls == 1 { "short" } else { "much longer" }, b, s)
+        })
+        .unwrap();
+        assert_eq!(bytes, b"much longer");
+
+        assert_eq!(read_negotiated(-7, |_, _| -3), Err(-3));
+        assert_eq!(
+            read_negotiated(-7, |b, s| fake_getter("", b, s)).unwrap().1,
+            b""
+        );
+    }
+
+    #[test]
+    fn test_mac_roman_table() {
+        assert_eq!(mac_roman_char(b'A'), 'A');
+        assert_eq!(mac_roman_char(0x80), 'Ä');
+        assert_eq!(mac_roman_char(0xA5), '•');
+        assert_eq!(mac_roman_char(0xDB), '€');
+        assert_eq!(mac_roman_char(0xFF), 'ˇ');
+    }
+}
@@ -0,0 +1,267 @@
+//! Rate limiting of parameter changes
+//!
+//! Hardware encoders and fast automation can change a parameter many times per
+//! block, and some plugins glitch or stall when every one of those changes
+//! reaches them. A [`ParameterThrottle`] sits between the controller and the
+//! plugin: changes are queued per parameter, repeated changes to the same
+//! parameter are coalesced into the latest value, and each parameter is
+//! updated at most `max_rate` times per second. The last value sent is always
+//! delivered, just possibly a little later.
+//!
+//! Time is counted in processed frames, so throttling is deterministic and
+//! follows the audio clock rather than the wall clock. Per-parameter
+//! [`ThrottleStats`] show how much of the incoming traffic was coalesced.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::throttle::ParameterThrottle;
+//! # fn example(mut plugin: impl PluginInstance) -> Result<()> {
+//! plugin.initialize(48000.0, 512)?;
+//!
+//! // At most 30 updates per second per parameter
+//! let mut throttle = ParameterThrottle::new(48000.0, 30.0);
+//!
+//! let input = vec![0.0f32; 512];
+//! let mut output = vec![0.0f32; 512];
+//! for step in 0..100 {
+//!     throttle.set(0, step as f32 / 100.0); // Encoder spam
+//!     throttle.process(&mut plugin, &[&input], &mut [&mut output], 512)?;
+//! }
+//! println!("{} changes dropped", throttle.stats(0).coalesced());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{PluginInstance, Result};
+
+/// Counters for one parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ThrottleStats {
+    /// Changes passed to [`ParameterThrottle::set()`]
+    pub received: u64,
+
+    /// Changes sent to the plugin
+    pub sent: u64,
+}
+
+impl ThrottleStats {
+    /// Changes that were replaced by a later value before being sent
+    pub fn coalesced(&self) -> u64 {
+        self.received.saturating_sub(self.sent)
+    }
+}
+
+/// Throttling state of one parameter
+#[derive(Debug, Clone, Copy, Default)]
+struct Slot {
+    /// Latest value not yet sent
+    pending: Option<f32>,
+
+    /// Frame at which the parameter was last updated
+    last_sent: Option<u64>,
+
+    stats: ThrottleStats,
+}
+
+/// Coalesces parameter changes and limits how often each parameter is updated
+#[derive(Debug, Clone)]
+pub struct ParameterThrottle {
+    /// Maximum updates per second for each parameter (0 or less disables
+    /// throttling; changes are still coalesced per block)
+    pub max_rate: f32,
+
+    sample_rate: f64,
+
+    /// Frames processed so far
+    clock: u64,
+
+    /// Indexed by parameter index
+    slots: Vec<Slot>,
+}
+
+impl ParameterThrottle {
+    /// Create a throttle for a plugin running at `sample_rate`
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The plugin's sample rate in Hz
+    /// * `max_rate` - Maximum updates per second for each parameter
+    pub fn new(sample_rate: f64, max_rate: f32) -> Self {
+        Self {
+            max_rate,
+            sample_rate,
+            clock: 0,
+            slots: Vec::new(),
+        }
+    }
+
+    /// Queue a parameter change (normalized 0.0 to 1.0)
+    ///
+    /// Replaces any value still queued for the same parameter.
+    pub fn set(&mut self, index: usize, value: f32) {
+        if index >= self.slots.len() {
+            self.slots.resize(index + 1, Slot::default());
+        }
+        let slot = &mut self.slots[index];
+        slot.pending = Some(value);
+        slot.stats.received += 1;
+    }
+
+    /// Send every queued change that is due to the plugin
+    ///
+    /// A change is due if its parameter hasn't been updated within the last
+    /// `1 / max_rate` seconds. Changes that aren't due stay queued.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error from `set_parameter()`; the failed change is
+    /// dropped and the remaining changes are still sent.
+    pub fn apply<P: PluginInstance + ?Sized>(&mut self, plugin: &mut P) -> Result<()> {
+        let interval = self.interval();
+        let clock = self.clock;
+        let mut first_error = None;
+
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            let Some(value) = slot.pending else {
+                continue;
+            };
+            let due = slot
+                .last_sent
+                .is_none_or(|last| clock.saturating_sub(last) >= interval);
+            if !due {
+                continue;
+            }
+
+            slot.pending = None;
+            slot.last_sent = Some(clock);
+            match plugin.set_parameter(index, value) {
+                Ok(()) => slot.stats.sent += 1,
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Advance the throttle's clock by `num_frames`
+    ///
+    /// Call once per processed block if not using [`process()`](Self::process).
+    pub fn advance(&mut self, num_frames: usize) {
+        self.clock = self.clock.saturating_add(num_frames as u64);
+    }
+
+    /// Apply due changes, then process one block
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a parameter change or processing fails
+    pub fn process<P: PluginInstance + ?Sized>(
+        &mut self,
+        plugin: &mut P,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        let applied = self.apply(plugin);
+        let processed = plugin.process(inputs, outputs, num_frames);
+        self.advance(num_frames);
+        applied.and(processed)
+    }
+
+    /// Number of parameters with a change waiting to be sent
+    pub fn pending(&self) -> usize {
+        self.slots.iter().filter(|s| s.pending.is_some()).count()
+    }
+
+    /// Counters for a parameter
+    pub fn stats(&self, index: usize) -> ThrottleStats {
+        self.slots.get(index).map(|s| s.stats).unwrap_or_default()
+    }
+
+    /// Zero the counters of every parameter
+    pub fn reset_stats(&mut self) {
+        for slot in &mut self.slots {
+            slot.stats = ThrottleStats::default();
+        }
+    }
+
+    /// Minimum frames between updates of one parameter
+    fn interval(&self) -> u64 {
+        if self.max_rate <= 0.0 {
+            return 0;
+        }
+        (self.sample_rate / self.max_rate as f64).round() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockPlugin;
+
+    fn run_block(throttle: &mut ParameterThrottle, plugin: &mut MockPlugin) {
+        let input = vec![0.0f32; 100];
+        let mut left = vec![0.0f32; 100];
+        let mut right = vec![0.0f32; 100];
+        throttle
+            .process(plugin, &[&input, &input], &mut [&mut left, &mut right], 100)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_changes_are_coalesced_and_rate_limited() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(1000.0, 100).unwrap();
+
+        // One update per 250 frames, blocks of 100 frames
+        let mut throttle = ParameterThrottle::new(1000.0, 4.0);
+        let mut seen = Vec::new();
+        for block in 0..8 {
+            for step in 0..5 {
+                throttle.set(0, (block * 5 + step) as f32 / 100.0);
+            }
+            run_block(&mut throttle, &mut plugin);
+            seen.push(plugin.get_parameter(0).unwrap());
+        }
+
+        // Updates at frames 0, 300 and 600
+        let stats = throttle.stats(0);
+        assert_eq!(stats.received, 40);
+        assert_eq!(stats.sent, 3);
+        assert_eq!(stats.coalesced(), 37);
+        assert_eq!(seen[0], 0.04);
+        assert_eq!(seen[2], 0.04);
+        assert_eq!(seen[3], 0.19);
+
+        // The latest value still arrives once the interval has passed
+        assert_eq!(throttle.pending(), 1);
+        run_block(&mut throttle, &mut plugin);
+        run_block(&mut throttle, &mut plugin);
+        assert_eq!(throttle.pending(), 0);
+        assert_eq!(plugin.get_parameter(0).unwrap(), 0.39);
+    }
+
+    #[test]
+    fn test_parameters_are_throttled_independently() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(1000.0, 100).unwrap();
+
+        let mut throttle = ParameterThrottle::new(1000.0, 1.0);
+        throttle.set(0, 0.1);
+        run_block(&mut throttle, &mut plugin);
+
+        throttle.set(0, 0.2);
+        throttle.set(1, 0.3);
+        run_block(&mut throttle, &mut plugin);
+        assert_eq!(plugin.get_parameter(0).unwrap(), 0.1);
+        assert_eq!(plugin.get_parameter(1).unwrap(), 0.3);
+        assert_eq!(throttle.stats(2), ThrottleStats::default());
+    }
+}
@@ -1,22 +1,185 @@
-use crate::{MidiEvent, ParameterInfo, PluginInfo, PresetInfo, Result};
+use crate::cache::{ScanCache, ScanDiff};
+use crate::identity::PluginIdentity;
+use crate::metadata::SharedMetadataStore;
+use crate::meter::MeterReading;
+use crate::node::BlockContext;
+use crate::quirks::Quirks;
+use crate::scan::{ScanFilter, ScannerConfig};
+use crate::{
+    CurrentPreset, MidiEvent, Normalized, ParameterChange, ParameterInfo, Plain, PluginInfo,
+    PresetInfo, Result,
+};
+use smallvec::SmallVec;
+use std::path::PathBuf;
+use std::sync::OnceLock;
 
 /// Trait for scanning and discovering audio plugins
 pub trait PluginScanner {
     /// The type of plugin instance this scanner produces
     type Plugin: PluginInstance;
 
+    /// Create a scanner that searches according to `config`
+    ///
+    /// `new()` on the concrete scanners uses [`ScannerConfig::from_env()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the scanner can't be created; the default
+    /// implementation, for scanners that aren't configurable, always does
+    fn with_config(config: ScannerConfig) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let _ = config;
+        Err(crate::Error::Other(
+            "This scanner can't be created from a configuration".to_string(),
+        ))
+    }
+
+    /// The search configuration
+    ///
+    /// The default implementation returns [`ScannerConfig::new()`].
+    fn config(&self) -> &ScannerConfig {
+        static DEFAULT: OnceLock<ScannerConfig> = OnceLock::new();
+        DEFAULT.get_or_init(ScannerConfig::new)
+    }
+
+    /// The directories that make up the platform's default locations
+    ///
+    /// What [`scan()`](Self::scan) searches unless
+    /// [`skip_default_paths`](ScannerConfig::skip_default_paths) is set, for
+    /// hosts to list in their preferences. Directories are listed whether or
+    /// not they exist. To let users turn some of them off, skip the default
+    /// paths and pass the ones still enabled as
+    /// [`extra_paths`](ScannerConfig::extra_paths).
+    ///
+    /// The default implementation lists none.
+    fn default_paths() -> Vec<PathBuf>
+    where
+        Self: Sized,
+    {
+        Vec::new()
+    }
+
+    /// Add a search path for later scans
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path can't be passed to the backend; the
+    /// default implementation, for scanners without search paths, always does
+    fn add_path(&mut self, path: &std::path::Path) -> Result<()> {
+        Err(crate::Error::Other(format!(
+            "This scanner can't search {}",
+            path.display()
+        )))
+    }
+
     /// Scan for plugins in default system locations
     fn scan(&self) -> Result<Vec<PluginInfo>>;
 
     /// Scan for plugins in a specific directory
     fn scan_path(&self, path: &std::path::Path) -> Result<Vec<PluginInfo>>;
 
+    /// Scan default system locations, returning only plugins that pass `filter`
+    ///
+    /// Backends may apply the filter while enumerating, before the full
+    /// metadata of rejected plugins is extracted.
+    fn scan_filtered(&self, filter: &ScanFilter) -> Result<Vec<PluginInfo>> {
+        let mut plugins = self.scan()?;
+        plugins.retain(|p| filter.matches(p));
+        Ok(plugins)
+    }
+
+    /// Scan a specific directory, returning only plugins that pass `filter`
+    fn scan_path_filtered(
+        &self,
+        path: &std::path::Path,
+        filter: &ScanFilter,
+    ) -> Result<Vec<PluginInfo>> {
+        let mut plugins = self.scan_path(path)?;
+        plugins.retain(|p| filter.matches(p));
+        Ok(plugins)
+    }
+
+    /// Scan default system locations and compare the results with `cache`
+    ///
+    /// `cache` is then updated to the new results; save it to compare against
+    /// in the next session. See [`ScanCache::refresh()`].
+    fn diff_with_cache(&self, cache: &mut ScanCache) -> Result<ScanDiff> {
+        let plugins = self.scan()?;
+        Ok(cache.refresh(&plugins))
+    }
+
     /// Load a plugin from PluginInfo
     fn load(&self, info: &PluginInfo) -> Result<Self::Plugin>;
+
+    /// Load a plugin as a [`BoxedPlugin`]
+    ///
+    /// For collections that mix formats and built-in nodes, e.g. the plugins
+    /// of a [`UnifiedScanner`](crate::unified::UnifiedScanner) and an
+    /// [`FnNode`](crate::node::FnNode) in one chain.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`load()`](Self::load)
+    fn load_boxed(&self, info: &PluginInfo) -> Result<BoxedPlugin>
+    where
+        Self::Plugin: 'static,
+    {
+        Ok(Box::new(self.load(info)?))
+    }
+
+    /// Load the best installed match for a plugin identity
+    ///
+    /// Scans default locations and loads the matching entry whose format comes
+    /// first in the configuration's
+    /// [`format_preference`](ScannerConfig::format_preference), falling back to
+    /// the next match if loading fails. See [`scan::load_best()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::PluginNotFound`](crate::Error::PluginNotFound) if
+    /// nothing installed matches, or the last load error if no match loads
+    ///
+    /// [`scan::load_best()`]: crate::scan::load_best
+    fn load_best(&self, identity: &PluginIdentity) -> Result<Self::Plugin> {
+        let plugins = self.scan()?;
+        let preference = &self.config().format_preference;
+        crate::scan::load_best(identity, &plugins, preference, |info| self.load(info))
+            .map(|(_, plugin)| plugin)
+    }
+
+    /// The store this scanner records loads into, if usage tracking is enabled
+    fn usage_store(&self) -> Option<&SharedMetadataStore> {
+        None
+    }
+
+    /// Unique IDs of the `n` most loaded plugins, most loaded first
+    ///
+    /// Returns an empty list unless usage tracking was enabled on the scanner.
+    fn most_used(&self, n: usize) -> Vec<String> {
+        match self.usage_store() {
+            Some(store) => {
+                let store = store.lock().unwrap_or_else(|e| e.into_inner());
+                store.most_used(n).into_iter().map(String::from).collect()
+            }
+            None => Vec::new(),
+        }
+    }
 }
 
+/// A plugin of any format, or any other [`PluginInstance`], behind a box
+///
+/// `PluginInstance` is object-safe, and a box of one is itself a
+/// `PluginInstance`, so boxed plugins work with every wrapper in the crate
+/// (e.g. `PanicSafe<BoxedPlugin>`).
+pub type BoxedPlugin = Box<dyn PluginInstance>;
+
 /// Trait for an instantiated audio plugin
 ///
+/// The trait is object-safe: plugins of different formats can be kept
+/// together as [`BoxedPlugin`]s.
+///
 /// # Thread Safety
 ///
 /// - All methods except `process()` should be called from **non-realtime threads**
@@ -75,6 +238,15 @@ pub trait PluginInstance: Send {
     /// * `outputs` - Array of output channel buffers (e.g., `&mut [left, right]` for stereo)
     /// * `num_frames` - Number of audio frames to process (must be ≤ max_block_size)
     ///
+    /// # Small Blocks
+    ///
+    /// Any block size from 0 to max_block_size is valid. A zero-frame call
+    /// produces no audio but still delivers pending parameter changes and MIDI
+    /// events where the format supports it (VST3 treats it as a flush; AudioUnits
+    /// receive events as they are sent and play them in the next non-empty block).
+    /// MIDI events whose `sample_offset` falls beyond a short block are moved to
+    /// its last frame rather than dropped.
+    ///
     /// # Channel Formats
     ///
     /// * Mono: `inputs = &[&mono]`, `outputs = &mut [&mut mono]`
@@ -100,6 +272,14 @@ pub trait PluginInstance: Send {
     /// # Ok(())
     /// # }
     /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The plugin is not initialized
+    /// - `num_frames` exceeds the max block size ([`Error::BlockTooLarge`](crate::Error::BlockTooLarge))
+    /// - The channel counts don't match the plugin's configuration
+    /// - The plugin fails to process the block
     fn process(
         &mut self,
         inputs: &[&[f32]],
@@ -107,18 +287,268 @@ pub trait PluginInstance: Send {
         num_frames: usize,
     ) -> Result<()>;
 
+    /// Process a block described by `context`
+    ///
+    /// Like [`process()`](Self::process) for `context.num_frames` frames, but
+    /// also hands the plugin the block's transport state (tempo, time
+    /// signature, musical position, loop, whether playing) and whether it is
+    /// rendered offline, all in one place instead of through separate setters
+    /// between blocks. VST3 plugins receive it in their `ProcessContext`,
+    /// AudioUnits through the host callbacks, VST2 plugins through
+    /// `audioMasterGetTime`, CLAP plugins as their `clap_event_transport` and
+    /// LV2 plugins as a `time:Position` object, so tempo-synced delays and
+    /// LFOs follow the song.
+    ///
+    /// The default implementation ignores everything but
+    /// [`num_frames`](BlockContext::num_frames) and calls `process()`;
+    /// plugins that can use the context override it, and wrappers pass it on
+    /// to the plugin they wrap.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use rack::prelude::*;
+    /// # use rack::node::BlockContext;
+    /// # use rack::tempo::Transport;
+    /// # fn example(mut plugin: impl PluginInstance, mut transport: Transport) -> Result<()> {
+    /// let input = vec![0.0f32; 512];
+    /// let mut output = vec![0.0f32; 512];
+    ///
+    /// let context = BlockContext::from_transport(&transport, 512).offline(true);
+    /// plugin.process_with_context(&[&input], &mut [&mut output], &context)?;
+    /// transport.advance(512);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Same as [`process()`](Self::process).
+    fn process_with_context(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        context: &BlockContext,
+    ) -> Result<()> {
+        self.process(inputs, outputs, context.num_frames)
+    }
+
+    /// Process a block with parameter changes and MIDI events placed at
+    /// sample offsets within it
+    ///
+    /// Each [`ParameterChange`] takes effect at its `sample_offset`, so
+    /// automation follows its curve inside the block instead of jumping at
+    /// block boundaries like [`set_parameter()`](Self::set_parameter) between
+    /// calls (zipper noise). `midi` is delivered as by
+    /// [`send_midi()`](Self::send_midi). Offsets past the end of the block are
+    /// treated as its last frame; changes at the same offset apply in order.
+    ///
+    /// VST3 plugins receive the changes in their `IParameterChanges` queues,
+    /// AudioUnits as scheduled parameter events and CLAP plugins as timed
+    /// events. The default implementation splits the block at each offset
+    /// and calls `set_parameter()` and `process()` for every piece, which is
+    /// sample-accurate for any plugin at the cost of more `process()` calls.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use rack::prelude::*;
+    /// # fn example(mut plugin: impl PluginInstance) -> Result<()> {
+    /// let input = vec![0.0f32; 512];
+    /// let mut output = vec![0.0f32; 512];
+    ///
+    /// // Ramp parameter 0 across the block
+    /// let changes: Vec<_> = (0..8)
+    ///     .map(|i| ParameterChange::new(0, i as f32 / 8.0, i * 64))
+    ///     .collect();
+    /// plugin.process_with_events(&[&input], &mut [&mut output], 512, &changes, &[])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Same as [`process()`](Self::process), [`set_parameter()`](Self::set_parameter)
+    /// and [`send_midi()`](Self::send_midi).
+    fn process_with_events(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        changes: &[ParameterChange],
+        midi: &[MidiEvent],
+    ) -> Result<()> {
+        split_events(self, inputs, outputs, num_frames, changes, midi)
+    }
+
+    /// Timeline position of the next sample `process()` will produce
+    ///
+    /// Starts at 0 and advances by `num_frames` after every successful
+    /// `process()` call, giving the plugin, host-side schedulers and recorders
+    /// one shared clock. Backends report it to plugins that rely on continuity
+    /// (VST3 `ProcessContext::projectTimeSamples`, AudioUnit
+    /// `AudioTimeStamp::mSampleTime`). `reset()` leaves it unchanged.
+    ///
+    /// The default implementation, for instances that don't keep a timeline,
+    /// always returns 0.
+    fn sample_position(&self) -> u64 {
+        0
+    }
+
+    /// Move the timeline so the next `process()` call starts at `position`
+    ///
+    /// Use when the transport seeks or loops. Positions above `i64::MAX` are
+    /// rejected.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the instance doesn't keep a timeline; the default
+    /// implementation always does
+    fn set_sample_position(&mut self, position: u64) -> Result<()> {
+        Err(crate::Error::Other(format!(
+            "{} can't move to sample {}",
+            self.info().name,
+            position
+        )))
+    }
+
+    /// Deliver pending parameter changes and MIDI events without producing audio
+    ///
+    /// Use while the transport is stopped so that edits reach the processor
+    /// deterministically instead of waiting for the next audio block. VST3
+    /// plugins receive a zero-frame `process()` call (the standard parameter
+    /// flush); AudioUnits apply changes as they are made, so this only checks
+    /// that the plugin is initialized. The sample position is not advanced.
+    ///
+    /// Must not be called concurrently with `process()`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use rack::prelude::*;
+    /// # fn example(mut plugin: impl PluginInstance) -> Result<()> {
+    /// plugin.set_parameter(0, 0.75)?;
+    /// plugin.flush_events()?; // The processor sees 0.75 now, not at the next block
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn flush_events(&mut self) -> Result<()> {
+        let inputs: SmallVec<[&[f32]; 8]> = (0..self.input_channels()).map(|_| &[][..]).collect();
+        let mut outputs: SmallVec<[&mut [f32]; 8]> =
+            (0..self.output_channels()).map(|_| &mut [][..]).collect();
+        self.process(&inputs, &mut outputs, 0)
+    }
+
     /// Get the number of parameters
+    ///
+    /// This counts every parameter, including ones the plugin marks as expert
+    /// or hidden (see [`ParameterInfo::visibility`]).
     fn parameter_count(&self) -> usize;
 
     /// Get information about a parameter
     fn parameter_info(&self, index: usize) -> Result<ParameterInfo>;
 
+    /// Get information about the parameters meant for normal parameter lists
+    ///
+    /// Expert and hidden parameters are left out. Each entry keeps its
+    /// original `index`, so it can be passed straight to
+    /// [`set_parameter()`](Self::set_parameter).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not initialized or a parameter's info
+    /// can't be read
+    fn parameters(&self) -> Result<Vec<ParameterInfo>> {
+        let mut all = self.all_parameters()?;
+        all.retain(ParameterInfo::is_visible);
+        Ok(all)
+    }
+
+    /// Get information about every parameter, including expert and hidden ones
+    ///
+    /// For power-user hosts and validators that need to see everything the
+    /// plugin exposes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not initialized or a parameter's info
+    /// can't be read
+    fn all_parameters(&self) -> Result<Vec<ParameterInfo>> {
+        (0..self.parameter_count())
+            .map(|index| self.parameter_info(index))
+            .collect()
+    }
+
     /// Get the current value of a parameter (normalized 0.0 to 1.0)
     fn get_parameter(&self, index: usize) -> Result<f32>;
 
     /// Set the value of a parameter (normalized 0.0 to 1.0)
     fn set_parameter(&mut self, index: usize, value: f32) -> Result<()>;
 
+    /// Get the current value of a parameter in the plugin's own units
+    ///
+    /// Converts the normalized value using the parameter's range from
+    /// [`parameter_info()`](Self::parameter_info).
+    fn get_parameter_plain(&self, index: usize) -> Result<Plain> {
+        let info = self.parameter_info(index)?;
+        let value = self.get_parameter(index)?;
+        Ok(info.to_plain(Normalized::new(value)))
+    }
+
+    /// Set the value of a parameter in the plugin's own units
+    ///
+    /// Values outside the parameter's range are clamped.
+    fn set_parameter_plain(&mut self, index: usize, value: Plain) -> Result<()> {
+        let info = self.parameter_info(index)?;
+        self.set_parameter(index, info.to_normalized(value).value())
+    }
+
+    /// Reset a parameter to its default value
+    ///
+    /// This is the standard "double-click to reset" behavior. The default comes
+    /// from [`ParameterInfo::default`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The plugin is not initialized
+    /// - The index is out of range
+    fn reset_parameter(&mut self, index: usize) -> Result<()> {
+        let info = self.parameter_info(index)?;
+        self.set_parameter(index, info.default_normalized().value())
+    }
+
+    /// Reset all parameters to their default values
+    ///
+    /// Unlike [`reset()`](Self::reset), this changes parameter values and leaves
+    /// the plugin's internal buffers alone. A single
+    /// [`HostEvent::ParametersReset`](crate::events::HostEvent::ParametersReset)
+    /// is emitted once the batch is done, if any parameter was reset.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not initialized, or if any parameter
+    /// fails to reset (remaining parameters are still reset).
+    fn reset_all_parameters(&mut self) -> Result<()> {
+        let mut first_error = None;
+        let mut reset_any = false;
+        for index in 0..self.parameter_count() {
+            match self.reset_parameter(index) {
+                Ok(()) => reset_any = true,
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        if reset_any {
+            crate::events::emit(crate::events::HostEvent::ParametersReset { info: self.info() });
+        }
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
     /// Send MIDI events to the plugin
     ///
     /// This is primarily useful for instrument plugins (synthesizers, samplers).
@@ -127,6 +557,10 @@ pub trait PluginInstance: Send {
     /// Events are processed immediately. For sample-accurate timing,
     /// set the `sample_offset` field in each event.
     ///
+    /// To queue events for the next `process()` call, including events
+    /// scheduled beyond the current block, use an
+    /// [`EventPort`](crate::port::EventPort).
+    ///
     /// # Performance Note
     ///
     /// This method is **zero-allocation** for typical use cases (≤16 events).
@@ -205,6 +639,24 @@ pub trait PluginInstance: Send {
     /// - The preset number is invalid
     fn load_preset(&mut self, preset_number: i32) -> Result<()>;
 
+    /// Get the preset the plugin reports as currently loaded
+    ///
+    /// Returns `Ok(None)` when the plugin doesn't say. AudioUnits report their
+    /// present preset; VST3 plugins report the program selected by their
+    /// program-change parameter, falling back to the preset last loaded with
+    /// `load_preset()`. Edits made after loading are not reflected, so hosts
+    /// showing "Init — edited" should track changes themselves.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin is not initialized
+    fn current_preset(&self) -> Result<Option<CurrentPreset>> {
+        if !self.is_initialized() {
+            return Err(crate::Error::NotInitialized);
+        }
+        Ok(None)
+    }
+
     /// Get the plugin's current state as a byte array
     ///
     /// This can be saved and restored later with `set_state()`.
@@ -231,9 +683,763 @@ pub trait PluginInstance: Send {
     /// - The plugin doesn't support state serialization
     fn set_state(&mut self, data: &[u8]) -> Result<()>;
 
+    /// Write the plugin's current state to `writer`
+    ///
+    /// Writes the same bytes as [`get_state()`](Self::get_state). Saving
+    /// straight to a file avoids keeping a second copy of a large state around.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the state can't be read or writing fails
+    fn get_state_to_writer(&self, writer: &mut dyn std::io::Write) -> Result<()> {
+        writer.write_all(&self.get_state()?)?;
+        Ok(())
+    }
+
+    /// Restore the plugin's state from `reader`
+    ///
+    /// Reads to the end of `reader`; see [`set_state()`](Self::set_state).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails or the plugin rejects the state
+    fn set_state_from_reader(&mut self, reader: &mut dyn std::io::Read) -> Result<()> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        self.set_state(&data)
+    }
+
+    /// Restore the plugin's state from a file saved with
+    /// [`get_state_to_writer()`](Self::get_state_to_writer)
+    ///
+    /// The file is memory-mapped (see [`MappedFile`](crate::mmap::MappedFile))
+    /// rather than read into a buffer, so states of hundreds of megabytes
+    /// don't need that much extra memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be mapped or the plugin rejects the
+    /// state
+    fn set_state_from_file(&mut self, path: &std::path::Path) -> Result<()> {
+        let data = crate::mmap::MappedFile::open(path)?;
+        self.set_state(&data)
+    }
+
+    /// Check whether the plugin reported unsaved changes since the last call
+    ///
+    /// Plugins announce these with `setDirty()` (VST3), `mark_dirty()` on the
+    /// state extension (CLAP) or a `kAudioUnitProperty_ClassInfo` change (AU).
+    /// Reading the flag clears it. [`DirtyTracker`](crate::dirty::DirtyTracker)
+    /// checks it before comparing state; plugins that never report changes
+    /// always return `false`.
+    fn take_state_dirty(&self) -> bool {
+        false
+    }
+
     /// Get plugin info
     fn info(&self) -> &PluginInfo;
 
     /// Check if the plugin is initialized
     fn is_initialized(&self) -> bool;
+
+    /// Get the number of input channels
+    ///
+    /// Returns the actual number of input channels the plugin was configured with
+    /// after initialization. This may differ from what was requested if the plugin
+    /// doesn't support the requested configuration.
+    ///
+    /// # Returns
+    ///
+    /// - Number of input channels (e.g., 1 for mono, 2 for stereo)
+    /// - 0 if not initialized
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use rack::prelude::*;
+    /// # fn example(mut plugin: impl PluginInstance) -> Result<()> {
+    /// plugin.initialize(48000.0, 512)?;
+    /// let channels = plugin.input_channels();
+    /// println!("Plugin has {} input channels", channels);
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn input_channels(&self) -> usize;
+
+    /// Get the number of output channels
+    ///
+    /// Returns the actual number of output channels the plugin was configured with
+    /// after initialization. This may differ from what was requested if the plugin
+    /// doesn't support the requested configuration.
+    ///
+    /// # Returns
+    ///
+    /// - Number of output channels (e.g., 1 for mono, 2 for stereo)
+    /// - 0 if not initialized
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use rack::prelude::*;
+    /// # fn example(mut plugin: impl PluginInstance) -> Result<()> {
+    /// plugin.initialize(48000.0, 512)?;
+    /// let channels = plugin.output_channels();
+    /// println!("Plugin has {} output channels", channels);
+    ///
+    /// // Allocate buffers with correct channel count
+    /// let mut inputs: Vec<Vec<f32>> = (0..plugin.input_channels())
+    ///     .map(|_| vec![0.0f32; 512])
+    ///     .collect();
+    /// let mut outputs: Vec<Vec<f32>> = (0..plugin.output_channels())
+    ///     .map(|_| vec![0.0f32; 512])
+    ///     .collect();
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn output_channels(&self) -> usize;
+
+    /// Processing latency in samples
+    ///
+    /// How far the plugin's output lags its input. Hosts delay parallel
+    /// signals by this much to keep them aligned. Plugins that don't report
+    /// their latency return 0; [`analysis::detect_latency()`](crate::analysis::detect_latency)
+    /// can measure it instead.
+    ///
+    /// Read from `kAudioUnitProperty_Latency` (AU), `getLatencySamples()`
+    /// (VST3), the latency extension (CLAP), the latency port (LV2) or
+    /// `initialDelay` (VST2). It can change at runtime, which plugins announce
+    /// with [`HostEvent::LatencyChanged`](crate::events::HostEvent::LatencyChanged);
+    /// 0 before [`initialize()`](Self::initialize).
+    fn latency_samples(&self) -> usize {
+        0
+    }
+
+    /// Tail length in samples
+    ///
+    /// How long the output keeps sounding after the input goes silent or the
+    /// last note is released: reverb decays, delay repeats, release stages.
+    /// Offline bounces keep rendering this long past the end of the input.
+    /// `usize::MAX` for a tail that never ends (e.g. a reverb set to freeze),
+    /// where renders should stop on silence instead; 0 for plugins without a
+    /// tail and before [`initialize()`](Self::initialize).
+    ///
+    /// Read from `kAudioUnitProperty_TailTime` (AU), `getTailSamples()`
+    /// (VST3), the tail extension (CLAP) or `effGetTailSize` (VST2). LV2 has
+    /// no way to report it.
+    fn tail_samples(&self) -> usize {
+        0
+    }
+
+    /// Tail length in seconds
+    ///
+    /// [`tail_samples()`](Self::tail_samples) at the initialized sample rate;
+    /// `f64::INFINITY` for a tail that never ends.
+    fn tail_seconds(&self) -> f64 {
+        0.0
+    }
+
+    /// Bypass the plugin, passing its input through, or bring it back
+    ///
+    /// Uses the plugin's own bypass where it has one: the bypass parameter
+    /// (VST3 `kIsBypass`, CLAP `CLAP_PARAM_IS_BYPASS`), `kAudioUnitProperty_BypassEffect`
+    /// (AU), the `lv2:enabled` port (LV2) or `effSetBypass` (VST2). Otherwise
+    /// the host crossfades to the input with a [`HostBypass`](crate::bypass::HostBypass),
+    /// so toggling doesn't click. Bypassed plugins keep processing either way.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin can't be bypassed; the default
+    /// implementation always does
+    fn set_bypass(&mut self, bypassed: bool) -> Result<()> {
+        let _ = bypassed;
+        Err(crate::Error::Other(format!(
+            "{} can't be bypassed",
+            self.info().name
+        )))
+    }
+
+    /// Whether the plugin is bypassed
+    ///
+    /// Also reflects a native bypass switched from the plugin's own editor.
+    fn is_bypassed(&self) -> bool {
+        false
+    }
+
+    /// Known-broken behaviors of this plugin
+    ///
+    /// Backends work around some quirks themselves; the rest tell the host what
+    /// to avoid. See [`quirks`](crate::quirks) for the table and how to extend it.
+    fn quirks(&self) -> Quirks {
+        crate::quirks::lookup(self.info())
+    }
+
+    /// Current values of the meters the plugin publishes itself
+    ///
+    /// Returns `None` when the plugin or its backend has no metering to offer;
+    /// hosts then meter the output themselves. See [`meter`](crate::meter) for
+    /// which backends support it. Not meant for the audio thread.
+    fn plugin_meter(&self) -> Option<Vec<MeterReading>> {
+        None
+    }
+}
+
+/// Forwards every method, including the provided ones, so a boxed plugin
+/// keeps the behavior of the plugin inside
+impl<P: PluginInstance + ?Sized> PluginInstance for Box<P> {
+    fn initialize(&mut self, sample_rate: f64, max_block_size: usize) -> Result<()> {
+        (**self).initialize(sample_rate, max_block_size)
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        (**self).reset()
+    }
+
+    fn process(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        (**self).process(inputs, outputs, num_frames)
+    }
+
+    fn process_with_context(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        context: &BlockContext,
+    ) -> Result<()> {
+        (**self).process_with_context(inputs, outputs, context)
+    }
+
+    fn process_with_events(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        changes: &[ParameterChange],
+        midi: &[MidiEvent],
+    ) -> Result<()> {
+        (**self).process_with_events(inputs, outputs, num_frames, changes, midi)
+    }
+
+    fn sample_position(&self) -> u64 {
+        (**self).sample_position()
+    }
+
+    fn set_sample_position(&mut self, position: u64) -> Result<()> {
+        (**self).set_sample_position(position)
+    }
+
+    fn flush_events(&mut self) -> Result<()> {
+        (**self).flush_events()
+    }
+
+    fn parameter_count(&self) -> usize {
+        (**self).parameter_count()
+    }
+
+    fn parameter_info(&self, index: usize) -> Result<ParameterInfo> {
+        (**self).parameter_info(index)
+    }
+
+    fn parameters(&self) -> Result<Vec<ParameterInfo>> {
+        (**self).parameters()
+    }
+
+    fn all_parameters(&self) -> Result<Vec<ParameterInfo>> {
+        (**self).all_parameters()
+    }
+
+    fn get_parameter(&self, index: usize) -> Result<f32> {
+        (**self).get_parameter(index)
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) -> Result<()> {
+        (**self).set_parameter(index, value)
+    }
+
+    fn get_parameter_plain(&self, index: usize) -> Result<Plain> {
+        (**self).get_parameter_plain(index)
+    }
+
+    fn set_parameter_plain(&mut self, index: usize, value: Plain) -> Result<()> {
+        (**self).set_parameter_plain(index, value)
+    }
+
+    fn reset_parameter(&mut self, index: usize) -> Result<()> {
+        (**self).reset_parameter(index)
+    }
+
+    fn reset_all_parameters(&mut self) -> Result<()> {
+        (**self).reset_all_parameters()
+    }
+
+    fn send_midi(&mut self, events: &[MidiEvent]) -> Result<()> {
+        (**self).send_midi(events)
+    }
+
+    fn preset_count(&self) -> Result<usize> {
+        (**self).preset_count()
+    }
+
+    fn preset_info(&self, index: usize) -> Result<PresetInfo> {
+        (**self).preset_info(index)
+    }
+
+    fn load_preset(&mut self, preset_number: i32) -> Result<()> {
+        (**self).load_preset(preset_number)
+    }
+
+    fn current_preset(&self) -> Result<Option<CurrentPreset>> {
+        (**self).current_preset()
+    }
+
+    fn get_state(&self) -> Result<Vec<u8>> {
+        (**self).get_state()
+    }
+
+    fn set_state(&mut self, data: &[u8]) -> Result<()> {
+        (**self).set_state(data)
+    }
+
+    fn get_state_to_writer(&self, writer: &mut dyn std::io::Write) -> Result<()> {
+        (**self).get_state_to_writer(writer)
+    }
+
+    fn set_state_from_reader(&mut self, reader: &mut dyn std::io::Read) -> Result<()> {
+        (**self).set_state_from_reader(reader)
+    }
+
+    fn set_state_from_file(&mut self, path: &std::path::Path) -> Result<()> {
+        (**self).set_state_from_file(path)
+    }
+
+    fn take_state_dirty(&self) -> bool {
+        (**self).take_state_dirty()
+    }
+
+    fn info(&self) -> &PluginInfo {
+        (**self).info()
+    }
+
+    fn is_initialized(&self) -> bool {
+        (**self).is_initialized()
+    }
+
+    fn input_channels(&self) -> usize {
+        (**self).input_channels()
+    }
+
+    fn output_channels(&self) -> usize {
+        (**self).output_channels()
+    }
+
+    fn latency_samples(&self) -> usize {
+        (**self).latency_samples()
+    }
+
+    fn tail_samples(&self) -> usize {
+        (**self).tail_samples()
+    }
+
+    fn tail_seconds(&self) -> f64 {
+        (**self).tail_seconds()
+    }
+
+    fn set_bypass(&mut self, bypassed: bool) -> Result<()> {
+        (**self).set_bypass(bypassed)
+    }
+
+    fn is_bypassed(&self) -> bool {
+        (**self).is_bypassed()
+    }
+
+    fn quirks(&self) -> Quirks {
+        (**self).quirks()
+    }
+
+    fn plugin_meter(&self) -> Option<Vec<MeterReading>> {
+        (**self).plugin_meter()
+    }
+}
+
+/// Default [`PluginInstance::process_with_events()`]: split the block at each
+/// change offset and call `set_parameter()`, `send_midi()` and `process()` for
+/// every piece
+///
+/// Backends whose parameters hold one value per run (LV2 control ports, VST2
+/// `setParameter`) call this directly.
+pub(crate) fn split_events<P: PluginInstance + ?Sized>(
+    plugin: &mut P,
+    inputs: &[&[f32]],
+    outputs: &mut [&mut [f32]],
+    num_frames: usize,
+    changes: &[ParameterChange],
+    midi: &[MidiEvent],
+) -> Result<()> {
+    let last = num_frames.saturating_sub(1) as u32;
+    let offset = |sample_offset: u32| sample_offset.min(last) as usize;
+
+    let mut splits: SmallVec<[usize; 16]> = changes
+        .iter()
+        .map(|change| offset(change.sample_offset))
+        .filter(|&at| at > 0)
+        .collect();
+    if splits.is_empty() {
+        for change in changes {
+            plugin.set_parameter(change.index, change.value)?;
+        }
+        if !midi.is_empty() {
+            let events: SmallVec<[MidiEvent; 16]> = midi
+                .iter()
+                .map(|event| MidiEvent {
+                    sample_offset: offset(event.sample_offset) as u32,
+                    ..*event
+                })
+                .collect();
+            plugin.send_midi(&events)?;
+        }
+        return plugin.process(inputs, outputs, num_frames);
+    }
+    splits.sort_unstable();
+    splits.dedup();
+
+    if inputs.iter().any(|input| input.len() < num_frames)
+        || outputs.iter().any(|output| output.len() < num_frames)
+    {
+        return Err(crate::Error::Other(format!(
+            "Buffers must hold at least {} samples",
+            num_frames
+        )));
+    }
+
+    let mut start = 0;
+    for end in splits.into_iter().chain(std::iter::once(num_frames)) {
+        for change in changes {
+            if offset(change.sample_offset) == start {
+                plugin.set_parameter(change.index, change.value)?;
+            }
+        }
+
+        let events: SmallVec<[MidiEvent; 16]> = midi
+            .iter()
+            .filter(|event| (start..end).contains(&offset(event.sample_offset)))
+            .map(|event| MidiEvent {
+                sample_offset: (offset(event.sample_offset) - start) as u32,
+                ..*event
+            })
+            .collect();
+        if !events.is_empty() {
+            plugin.send_midi(&events)?;
+        }
+
+        let piece: SmallVec<[&[f32]; 8]> = inputs.iter().map(|input| &input[start..end]).collect();
+        let mut piece_out: SmallVec<[&mut [f32]; 8]> = outputs
+            .iter_mut()
+            .map(|output| &mut output[start..end])
+            .collect();
+        plugin.process(&piece, &mut piece_out, end - start)?;
+        start = end;
+    }
+    Ok(())
+}
+
+/// A tail in samples converted to seconds, keeping an endless tail endless
+pub(crate) fn tail_seconds(samples: usize, sample_rate: f64) -> f64 {
+    match samples {
+        usize::MAX => f64::INFINITY,
+        _ if sample_rate > 0.0 => samples as f64 / sample_rate,
+        _ => 0.0,
+    }
+}
+
+/// A tail in seconds converted to samples, keeping an endless tail endless
+pub(crate) fn tail_samples(seconds: f64, sample_rate: f64) -> usize {
+    if seconds.is_infinite() {
+        usize::MAX
+    } else {
+        // Saturates for absurdly long tails
+        (seconds.max(0.0) * sample_rate).round() as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockPlugin;
+    use crate::{MidiEventKind, ParameterVisibility};
+
+    #[test]
+    fn test_plain_parameter_access() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+
+        plugin.set_parameter_plain(0, Plain(-6.0)).unwrap();
+        assert!((plugin.get_parameter_plain(0).unwrap().value() + 6.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_reset_parameter() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+
+        plugin.set_parameter(0, 1.0).unwrap();
+        plugin.reset_parameter(0).unwrap();
+        let expected = plugin.parameter_info(0).unwrap().default_normalized().value();
+        assert_eq!(plugin.get_parameter(0).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parameters_hide_expert_parameters() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+
+        let visible = plugin.parameters().unwrap();
+        assert_eq!(visible.len(), 2);
+        assert!(visible.iter().all(|p| p.visibility == ParameterVisibility::Visible));
+
+        let all = plugin.all_parameters().unwrap();
+        assert_eq!(all.len(), plugin.parameter_count());
+        assert_eq!(all[2].name, "Oversampling");
+        assert_eq!(all[2].index, 2);
+        assert_eq!(all[2].visibility, ParameterVisibility::Expert);
+    }
+
+    #[test]
+    fn test_reset_all_parameters() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+
+        for i in 0..plugin.parameter_count() {
+            plugin.set_parameter(i, 1.0).unwrap();
+        }
+        plugin.reset_all_parameters().unwrap();
+
+        for i in 0..plugin.parameter_count() {
+            let expected = plugin.parameter_info(i).unwrap().default_normalized().value();
+            assert_eq!(plugin.get_parameter(i).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_reset_all_parameters_notifies_once() {
+        use crate::events::{self, HostEvent};
+        use crate::test_util::SINK_LOCK;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let _lock = SINK_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+
+        let resets = Arc::new(AtomicUsize::new(0));
+        let sink_resets = Arc::clone(&resets);
+        events::set_sink(move |event: &HostEvent<'_>| {
+            if let HostEvent::ParametersReset { info } = event {
+                if info.unique_id == "mock-gain" {
+                    sink_resets.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        });
+        let result = plugin.reset_all_parameters();
+        events::clear_sink();
+
+        result.unwrap();
+        assert!(plugin.parameter_count() > 1);
+        assert_eq!(resets.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_flush_events() {
+        let mut plugin = MockPlugin::new();
+        assert!(plugin.flush_events().is_err());
+
+        plugin.initialize(48000.0, 512).unwrap();
+        plugin.flush_events().unwrap();
+        assert_eq!(plugin.sample_position(), 0);
+    }
+
+    #[test]
+    fn test_process_with_context_default() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+
+        let input = vec![0.0f32; 512];
+        let mut left = vec![0.0f32; 512];
+        let mut right = vec![0.0f32; 512];
+        let context = BlockContext::new(48000.0, 0, 128).offline(true);
+        plugin
+            .process_with_context(&[&input, &input], &mut [&mut left, &mut right], &context)
+            .unwrap();
+        assert_eq!(plugin.sample_position(), 128);
+    }
+
+    #[test]
+    fn test_current_preset_default() {
+        let mut plugin = MockPlugin::new();
+        assert!(plugin.current_preset().is_err());
+
+        plugin.initialize(48000.0, 512).unwrap();
+        assert_eq!(plugin.current_preset().unwrap(), None);
+    }
+
+    #[test]
+    fn test_streaming_state() {
+        let mut source = MockPlugin::new();
+        source.set_parameter(0, 0.3).unwrap();
+        let path = std::env::temp_dir().join(format!("rack-state-{}.bin", std::process::id()));
+        let mut file = std::fs::File::create(&path).unwrap();
+        source.get_state_to_writer(&mut file).unwrap();
+        drop(file);
+
+        let mut from_file = MockPlugin::new();
+        from_file.set_state_from_file(&path).unwrap();
+        assert_eq!(from_file.get_parameter(0).unwrap(), 0.3);
+
+        let mut from_reader = MockPlugin::new();
+        let mut reader = std::fs::File::open(&path).unwrap();
+        from_reader.set_state_from_reader(&mut reader).unwrap();
+        assert_eq!(from_reader.get_state().unwrap(), source.get_state().unwrap());
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_boxed_plugins_mix_with_nodes() {
+        use crate::guard::PanicSafe;
+        use crate::node::FnNode;
+
+        let node = FnNode::new("swap", 2, 2, |inputs, outputs, _| {
+            outputs[0].copy_from_slice(inputs[1]);
+            outputs[1].copy_from_slice(inputs[0]);
+        });
+        let mut chain: Vec<BoxedPlugin> = vec![Box::new(MockPlugin::new()), Box::new(node)];
+        for plugin in &mut chain {
+            plugin.initialize(48000.0, 64).unwrap();
+        }
+        assert_eq!(chain[0].info().name, MockPlugin::new().info().name);
+        assert_eq!(chain[1].info().name, "swap");
+
+        // A box keeps the boxed plugin's own behavior and fits any wrapper
+        let mut wrapped = PanicSafe::new(chain.remove(1));
+        let left = vec![1.0f32; 64];
+        let right = vec![2.0f32; 64];
+        let mut out_left = vec![0.0f32; 64];
+        let mut out_right = vec![0.0f32; 64];
+        let context = BlockContext::new(48000.0, 0, 64);
+        wrapped
+            .process_with_context(
+                &[&left, &right],
+                &mut [&mut out_left, &mut out_right],
+                &context,
+            )
+            .unwrap();
+        assert_eq!((out_left[0], out_right[0]), (2.0, 1.0));
+        assert_eq!(wrapped.sample_position(), 64);
+    }
+
+    #[test]
+    fn test_reset_parameter_out_of_range() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 512).unwrap();
+        assert!(plugin.reset_parameter(99).is_err());
+    }
+
+    #[test]
+    fn test_tail_conversion() {
+        assert_eq!(tail_seconds(24000, 48000.0), 0.5);
+        assert_eq!(tail_seconds(24000, 0.0), 0.0);
+        assert_eq!(tail_seconds(usize::MAX, 48000.0), f64::INFINITY);
+        assert_eq!(tail_samples(0.5, 44100.0), 22050);
+        assert_eq!(tail_samples(f64::INFINITY, 44100.0), usize::MAX);
+
+        let mut plugin: BoxedPlugin = Box::new(MockPlugin::new().with_tail(4800));
+        assert_eq!(plugin.tail_samples(), 0);
+        plugin.initialize(48000.0, 512).unwrap();
+        assert_eq!((plugin.tail_samples(), plugin.tail_seconds()), (4800, 0.1));
+    }
+
+    #[test]
+    fn test_process_with_events_splits_block() {
+        let mut plugin = MockPlugin::new();
+        plugin.initialize(48000.0, 256).unwrap();
+
+        let input = vec![1.0f32; 256];
+        let mut left = vec![0.0f32; 256];
+        let mut right = vec![0.0f32; 256];
+        let changes = [
+            ParameterChange::new(0, 0.0, 100),
+            // Past the end of the block: lands on its last frame
+            ParameterChange::new(0, 1.0, 1000),
+        ];
+        let midi = [MidiEvent {
+            sample_offset: 150,
+            kind: MidiEventKind::NoteOn {
+                note: 60,
+                velocity: 100,
+                channel: 0,
+            },
+        }];
+        plugin
+            .process_with_events(
+                &[&input, &input],
+                &mut [&mut left, &mut right],
+                256,
+                &changes,
+                &midi,
+            )
+            .unwrap();
+
+        // 0 dB, then -60 dB from frame 100, then +12 dB on the last frame
+        assert!(left[..100].iter().all(|&s| (s - 1.0).abs() < 1e-4));
+        assert!(left[100..255].iter().all(|&s| (s - 0.001).abs() < 1e-6));
+        assert!((left[255] - 3.981).abs() < 1e-3);
+        assert_eq!(left, right);
+
+        assert_eq!(plugin.midi_received.len(), 1);
+        assert_eq!(plugin.midi_received[0].sample_offset, 50);
+        assert_eq!(plugin.sample_position(), 256);
+        assert_eq!(plugin.get_parameter(0).unwrap(), 1.0);
+
+        // MIDI past the end lands on the last frame without splits too
+        plugin.midi_received.clear();
+        let late = [MidiEvent::note_on(60, 100, 0, 1000)];
+        plugin
+            .process_with_events(
+                &[&input, &input],
+                &mut [&mut left, &mut right],
+                256,
+                &[],
+                &late,
+            )
+            .unwrap();
+        assert_eq!(plugin.midi_received.len(), 1);
+        assert_eq!(plugin.midi_received[0].sample_offset, 255);
+    }
+
+    #[test]
+    fn test_scanner_defaults() {
+        struct Fixed;
+        impl PluginScanner for Fixed {
+            type Plugin = MockPlugin;
+
+            fn scan(&self) -> Result<Vec<PluginInfo>> {
+                Ok(Vec::new())
+            }
+
+            fn scan_path(&self, _path: &std::path::Path) -> Result<Vec<PluginInfo>> {
+                Ok(Vec::new())
+            }
+
+            fn load(&self, _info: &PluginInfo) -> Result<MockPlugin> {
+                Ok(MockPlugin::new())
+            }
+        }
+
+        assert!(Fixed::with_config(ScannerConfig::new()).is_err());
+        let mut scanner = Fixed;
+        assert_eq!(scanner.config(), &ScannerConfig::new());
+        assert!(scanner.add_path(std::path::Path::new("plugins")).is_err());
+    }
 }
@@ -0,0 +1,318 @@
+//! One scanner for every plugin format
+//!
+//! [`UnifiedScanner`] wraps the scanners of all formats available on the
+//! platform (AudioUnit, VST3, CLAP, LV2 and, if enabled, VST2) behind one set of
+//! calls. Scans return every format's plugins together, each [`PluginInfo`]
+//! carrying its [`format`](PluginInfo::format), and plugins load as
+//! [`BoxedPlugin`], whichever format they are.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use rack::prelude::*;
+//! use rack::unified::UnifiedScanner;
+//!
+//! # fn main() -> rack::Result<()> {
+//! let scanner = UnifiedScanner::new()?;
+//! for info in scanner.scan()? {
+//!     println!("{} ({})", info.name, info.format);
+//! }
+//!
+//! let plugins = scanner.scan()?;
+//! let mut plugin = scanner.load(&plugins[0])?;
+//! plugin.initialize(48000.0, 512)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::events::{self, HostEvent};
+use crate::identity::PluginIdentity;
+use crate::scan::{self, ScanFilter, ScannerConfig};
+use crate::{BoxedPlugin, Error, PluginFormat, PluginInfo, PluginScanner, Result};
+use std::path::Path;
+
+/// A format's scanner, with its plugins boxed
+trait Backend {
+    fn add_path(&mut self, path: &Path) -> Result<()>;
+    fn scan(&self, filter: Option<&ScanFilter>) -> Result<Vec<PluginInfo>>;
+    fn scan_path(&self, path: &Path, filter: Option<&ScanFilter>) -> Result<Vec<PluginInfo>>;
+    fn load(&self, info: &PluginInfo) -> Result<BoxedPlugin>;
+}
+
+impl<S> Backend for S
+where
+    S: PluginScanner,
+    S::Plugin: 'static,
+{
+    fn add_path(&mut self, path: &Path) -> Result<()> {
+        PluginScanner::add_path(self, path)
+    }
+
+    fn scan(&self, filter: Option<&ScanFilter>) -> Result<Vec<PluginInfo>> {
+        match filter {
+            Some(filter) => self.scan_filtered(filter),
+            None => PluginScanner::scan(self),
+        }
+    }
+
+    fn scan_path(&self, path: &Path, filter: Option<&ScanFilter>) -> Result<Vec<PluginInfo>> {
+        match filter {
+            Some(filter) => self.scan_path_filtered(path, filter),
+            None => PluginScanner::scan_path(self, path),
+        }
+    }
+
+    fn load(&self, info: &PluginInfo) -> Result<BoxedPlugin> {
+        self.load_boxed(info)
+    }
+}
+
+/// Scanner for every plugin format available on the platform
+///
+/// AudioUnit is available on Apple platforms, VST3 on desktop platforms when
+/// built with the SDK, CLAP and LV2 on desktop platforms, and VST2 on
+/// desktop platforms when built with the `vst2` feature and its SDK. On
+/// WebAssembly, the [simulated plugins](crate::mock) are returned instead. A format whose
+/// scan fails is left out of the results and its error reported as
+/// [`HostEvent::Error`], so one broken format doesn't hide the others.
+pub struct UnifiedScanner {
+    config: ScannerConfig,
+    backends: Vec<(PluginFormat, Box<dyn Backend>)>,
+}
+
+impl UnifiedScanner {
+    /// Create a scanner for every available format
+    ///
+    /// Searches the default system paths plus any listed in `RACK_PLUGIN_PATH`
+    /// (see [`ScannerConfig::from_env()`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a format's scanner can't be created
+    pub fn new() -> Result<Self> {
+        Self::with_config(ScannerConfig::from_env())
+    }
+
+    /// Create a scanner for every available format, each searching
+    /// according to `config`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a format's scanner can't be created
+    #[allow(clippy::vec_init_then_push)]
+    pub fn with_config(config: ScannerConfig) -> Result<Self> {
+        // Which formats are pushed depends on the platform
+        #[allow(unused_mut)]
+        let mut backends: Vec<(PluginFormat, Box<dyn Backend>)> = Vec::new();
+
+        #[cfg(target_vendor = "apple")]
+        backends.push((
+            PluginFormat::AudioUnit,
+            Box::new(crate::au::AudioUnitScanner::with_config(config.clone())?),
+        ));
+
+        #[cfg(all(
+            vst3_sdk,
+            not(target_os = "ios"),
+            not(target_os = "tvos"),
+            not(target_os = "watchos"),
+            not(target_os = "visionos")
+        ))]
+        backends.push((
+            PluginFormat::Vst3,
+            Box::new(crate::vst3::Vst3Scanner::with_config(config.clone())?),
+        ));
+
+        #[cfg(not(any(
+            target_os = "ios",
+            target_os = "tvos",
+            target_os = "watchos",
+            target_os = "visionos",
+            target_family = "wasm"
+        )))]
+        backends.push((
+            PluginFormat::Clap,
+            Box::new(crate::clap::ClapScanner::with_config(config.clone())?),
+        ));
+
+        #[cfg(not(any(
+            target_os = "ios",
+            target_os = "tvos",
+            target_os = "watchos",
+            target_os = "visionos",
+            target_family = "wasm"
+        )))]
+        backends.push((
+            PluginFormat::Lv2,
+            Box::new(crate::lv2::Lv2Scanner::with_config(config.clone())?),
+        ));
+
+        #[cfg(all(
+            vst2_sdk,
+            not(target_os = "ios"),
+            not(target_os = "tvos"),
+            not(target_os = "watchos"),
+            not(target_os = "visionos")
+        ))]
+        backends.push((
+            PluginFormat::Vst2,
+            Box::new(crate::vst2::Vst2Scanner::with_config(config.clone())?),
+        ));
+
+        // Native plugins can't be loaded on WebAssembly; each format's
+        // simulated plugins stand in for its scanner
+        #[cfg(target_family = "wasm")]
+        for format in crate::mock::MockScanner::catalog_formats() {
+            let scanner = crate::mock::MockScanner::with_config(config.clone())?.only(format);
+            backends.push((format, Box::new(scanner)));
+        }
+
+        Ok(Self { config, backends })
+    }
+
+    /// The formats this scanner covers, in the order they are scanned
+    pub fn formats(&self) -> Vec<PluginFormat> {
+        self.backends.iter().map(|(format, _)| *format).collect()
+    }
+
+    /// The search configuration
+    pub fn config(&self) -> &ScannerConfig {
+        &self.config
+    }
+
+    /// Add a search path to every format's scanner
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path can't be passed to a backend
+    pub fn add_path(&mut self, path: &Path) -> Result<()> {
+        for (_, backend) in &mut self.backends {
+            backend.add_path(path)?;
+        }
+        self.config.extra_paths.push(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Scan every format's default locations
+    ///
+    /// # Errors
+    ///
+    /// Never fails as a whole; failing formats are reported as events
+    pub fn scan(&self) -> Result<Vec<PluginInfo>> {
+        Ok(self.collect(|backend| backend.scan(None)))
+    }
+
+    /// Scan every format's default locations, returning only plugins that
+    /// pass `filter`
+    ///
+    /// # Errors
+    ///
+    /// Never fails as a whole; failing formats are reported as events
+    pub fn scan_filtered(&self, filter: &ScanFilter) -> Result<Vec<PluginInfo>> {
+        Ok(self.collect(|backend| backend.scan(Some(filter))))
+    }
+
+    /// Scan a specific directory for plugins of every format
+    ///
+    /// # Errors
+    ///
+    /// Never fails as a whole; failing formats are reported as events
+    pub fn scan_path(&self, path: &Path) -> Result<Vec<PluginInfo>> {
+        Ok(self.collect(|backend| backend.scan_path(path, None)))
+    }
+
+    /// Scan a specific directory for plugins of every format, returning only
+    /// plugins that pass `filter`
+    ///
+    /// # Errors
+    ///
+    /// Never fails as a whole; failing formats are reported as events
+    pub fn scan_path_filtered(&self, path: &Path, filter: &ScanFilter) -> Result<Vec<PluginInfo>> {
+        Ok(self.collect(|backend| backend.scan_path(path, Some(filter))))
+    }
+
+    /// Load a plugin of any available format
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the plugin's format isn't available on this
+    /// platform, or if loading fails
+    pub fn load(&self, info: &PluginInfo) -> Result<BoxedPlugin> {
+        let (_, backend) = self
+            .backends
+            .iter()
+            .find(|(format, _)| *format == info.format)
+            .ok_or_else(|| {
+                Error::Other(format!(
+                    "{} plugins can't be loaded on this platform",
+                    info.format
+                ))
+            })?;
+        backend.load(info)
+    }
+
+    /// Load the best installed match for a plugin identity, whatever its
+    /// format
+    ///
+    /// Formats are tried in the order of the configuration's
+    /// [`format_preference`](ScannerConfig::format_preference). See
+    /// [`scan::load_best()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::PluginNotFound`] if nothing installed matches, or the
+    /// last load error if no match loads
+    pub fn load_best(&self, identity: &PluginIdentity) -> Result<BoxedPlugin> {
+        let plugins = self.scan()?;
+        let preference = &self.config.format_preference;
+        scan::load_best(identity, &plugins, preference, |info| self.load(info))
+            .map(|(_, plugin)| plugin)
+    }
+
+    /// Run a scan on every backend, reporting failures
+    fn collect(&self, scan: impl Fn(&dyn Backend) -> Result<Vec<PluginInfo>>) -> Vec<PluginInfo> {
+        let mut plugins = Vec::new();
+        for (_, backend) in &self.backends {
+            match scan(backend.as_ref()) {
+                Ok(found) => plugins.extend(found),
+                Err(error) => events::emit(HostEvent::Error {
+                    info: None,
+                    error: &error,
+                }),
+            }
+        }
+        scan::dedup_by_location(plugins)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_scan_and_load() {
+        let dir = std::env::temp_dir().join(format!("rack-unified-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let _gain = crate::clap::test_plugin::install(&dir.join("gain.clap"));
+
+        let config = ScannerConfig::new().path(&dir).skip_default_paths(true);
+        let scanner = UnifiedScanner::with_config(config).unwrap();
+        assert!(scanner.formats().contains(&PluginFormat::Clap));
+
+        let plugins = scanner.scan().unwrap();
+        let info = plugins
+            .iter()
+            .find(|p| p.format == PluginFormat::Clap)
+            .expect("the CLAP plugin is found");
+        let mut plugin: BoxedPlugin = scanner.load(info).unwrap();
+        plugin.initialize(48000.0, 32).unwrap();
+        assert_eq!(plugin.output_channels(), 2);
+
+        let mut unknown = info.clone();
+        unknown.format = PluginFormat::Unknown;
+        assert!(scanner.load(&unknown).is_err());
+
+        drop(plugin);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,476 @@
+//! Host-side voice counting and polyphony limiting for instruments
+//!
+//! Instruments rarely report how many voices they are playing, and neither
+//! plugin API has a standard way to cap it: AudioUnits have no voice count or
+//! limit property (the `kMusicDeviceProperty_*` set covers instrument counts
+//! and sound banks), and VST3 has nothing comparable either. [`VoiceLimiter`]
+//! therefore counts voices on the host side from the notes it sends, and can
+//! cap polyphony per instance. That is what resource-constrained hosts (mobile,
+//! embedded) need: a dense chord or a held sustain pedal can no longer push
+//! an expensive patch past the CPU budget.
+//!
+//! When a Note On would exceed the cap, the oldest voice is stolen: a Note Off
+//! for it is sent at the same sample offset, just before the new note. Voices
+//! held only by the sustain pedal are stolen before keys that are still down.
+//!
+//! The count is an estimate of what the plugin is playing. Release tails are
+//! not counted (a voice ends at its Note Off), and plugins that stack several
+//! oscillators or layers per note use more internal voices than notes.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use rack::prelude::*;
+//! # use rack::voices::VoiceLimiter;
+//! # fn example(synth: impl PluginInstance) -> Result<()> {
+//! let mut synth = VoiceLimiter::new(synth).with_max_voices(8);
+//!
+//! synth.send_midi(&[MidiEvent::note_on(60, 100, 0, 0)])?;
+//! println!("{} voices playing", synth.active_voices());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::meter::MeterReading;
+use crate::node::BlockContext;
+use crate::quirks::Quirks;
+use crate::{
+    CurrentPreset, MidiEvent, MidiEventKind, ParameterChange, ParameterInfo, PluginInfo,
+    PluginInstance, PresetInfo, Result,
+};
+use smallvec::SmallVec;
+
+/// Sustain pedal controller
+const CC_SUSTAIN: u8 = 64;
+
+/// All Sound Off controller
+const CC_ALL_SOUND_OFF: u8 = 120;
+
+/// All Notes Off controller
+const CC_ALL_NOTES_OFF: u8 = 123;
+
+/// A note the plugin is (assumed to be) playing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Voice {
+    channel: u8,
+    note: u8,
+    /// The key is up but the sustain pedal holds the note
+    released: bool,
+}
+
+/// Plugin wrapper that counts voices and optionally caps polyphony
+///
+/// All methods are forwarded to the wrapped plugin; `send_midi()` tracks the
+/// notes passing through and inserts Note Offs for stolen voices.
+pub struct VoiceLimiter<P> {
+    plugin: P,
+    max_voices: Option<usize>,
+    /// Sounding voices, oldest first
+    voices: Vec<Voice>,
+    sustain: [bool; 16],
+    stolen: u64,
+}
+
+impl<P: PluginInstance> VoiceLimiter<P> {
+    /// Wrap a plugin without a voice cap (counting only)
+    pub fn new(plugin: P) -> Self {
+        Self {
+            plugin,
+            max_voices: None,
+            voices: Vec::with_capacity(128),
+            sustain: [false; 16],
+            stolen: 0,
+        }
+    }
+
+    /// Cap polyphony at `max_voices` (0 mutes all new notes)
+    pub fn with_max_voices(mut self, max_voices: usize) -> Self {
+        self.max_voices = Some(max_voices);
+        self
+    }
+
+    /// The voice cap, if any
+    pub fn max_voices(&self) -> Option<usize> {
+        self.max_voices
+    }
+
+    /// Change the voice cap
+    ///
+    /// Lowering the cap below the number of sounding voices doesn't stop any
+    /// of them; the excess is stolen as new notes arrive.
+    pub fn set_max_voices(&mut self, max_voices: Option<usize>) {
+        self.max_voices = max_voices;
+    }
+
+    /// Number of voices currently sounding (including ones held by the
+    /// sustain pedal)
+    pub fn active_voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    /// Number of voices stolen to stay under the cap since the wrapper was
+    /// created
+    pub fn stolen_voices(&self) -> u64 {
+        self.stolen
+    }
+
+    /// The wrapped plugin
+    pub fn inner(&self) -> &P {
+        &self.plugin
+    }
+
+    /// The wrapped plugin, mutably
+    ///
+    /// MIDI sent directly to the inner plugin isn't counted.
+    pub fn inner_mut(&mut self) -> &mut P {
+        &mut self.plugin
+    }
+
+    /// Unwrap the plugin
+    pub fn into_inner(self) -> P {
+        self.plugin
+    }
+
+    fn remove_voice(&mut self, channel: u8, note: u8) {
+        self.voices
+            .retain(|voice| voice.channel != channel || voice.note != note);
+    }
+
+    fn release_voice(&mut self, channel: u8, note: u8) {
+        if self.sustain[(channel & 0x0F) as usize] {
+            for voice in &mut self.voices {
+                if voice.channel == channel && voice.note == note {
+                    voice.released = true;
+                }
+            }
+        } else {
+            self.remove_voice(channel, note);
+        }
+    }
+
+    /// Index of the voice to steal: the oldest pedal-held voice, else the oldest
+    fn victim(&self) -> usize {
+        self.voices
+            .iter()
+            .position(|voice| voice.released)
+            .unwrap_or(0)
+    }
+
+    /// Track voices for `events`, adding note-offs for stolen voices
+    fn limit(&mut self, events: &[MidiEvent]) -> SmallVec<[MidiEvent; 64]> {
+        let mut out: SmallVec<[MidiEvent; 64]> = SmallVec::new();
+
+        for event in events {
+            match event.kind {
+                MidiEventKind::NoteOn {
+                    note,
+                    velocity,
+                    channel,
+                } if velocity > 0 => {
+                    // A retriggered note keeps a single voice
+                    self.remove_voice(channel, note);
+                    if let Some(max_voices) = self.max_voices {
+                        if max_voices == 0 {
+                            continue;
+                        }
+                        while self.voices.len() >= max_voices {
+                            let stolen = self.voices.remove(self.victim());
+                            out.push(MidiEvent::note_off(
+                                stolen.note,
+                                0,
+                                stolen.channel,
+                                event.sample_offset,
+                            ));
+                            self.stolen += 1;
+                        }
+                    }
+                    self.voices.push(Voice {
+                        channel,
+                        note,
+                        released: false,
+                    });
+                }
+                MidiEventKind::NoteOn { note, channel, .. }
+                | MidiEventKind::NoteOff { note, channel, .. } => {
+                    self.release_voice(channel, note);
+                }
+                MidiEventKind::ControlChange {
+                    controller: CC_SUSTAIN,
+                    value,
+                    channel,
+                } => {
+                    let down = value >= 64;
+                    self.sustain[(channel & 0x0F) as usize] = down;
+                    if !down {
+                        self.voices
+                            .retain(|voice| voice.channel != channel || !voice.released);
+                    }
+                }
+                MidiEventKind::ControlChange {
+                    controller: CC_ALL_SOUND_OFF | CC_ALL_NOTES_OFF,
+                    channel,
+                    ..
+                } => {
+                    self.voices.retain(|voice| voice.channel != channel);
+                }
+                MidiEventKind::SystemReset => {
+                    self.voices.clear();
+                    self.sustain = [false; 16];
+                }
+                _ => {}
+            }
+            out.push(*event);
+        }
+
+        out
+    }
+}
+
+impl<P: PluginInstance> PluginInstance for VoiceLimiter<P> {
+    fn initialize(&mut self, sample_rate: f64, max_block_size: usize) -> Result<()> {
+        self.plugin.initialize(sample_rate, max_block_size)
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.voices.clear();
+        self.sustain = [false; 16];
+        self.plugin.reset()
+    }
+
+    fn process(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        self.plugin.process(inputs, outputs, num_frames)
+    }
+
+    fn process_with_context(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        context: &BlockContext,
+    ) -> Result<()> {
+        self.plugin.process_with_context(inputs, outputs, context)
+    }
+
+    fn process_with_events(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        changes: &[ParameterChange],
+        midi: &[MidiEvent],
+    ) -> Result<()> {
+        let midi = self.limit(midi);
+        self.plugin
+            .process_with_events(inputs, outputs, num_frames, changes, &midi)
+    }
+
+    fn sample_position(&self) -> u64 {
+        self.plugin.sample_position()
+    }
+
+    fn set_sample_position(&mut self, position: u64) -> Result<()> {
+        self.plugin.set_sample_position(position)
+    }
+
+    fn flush_events(&mut self) -> Result<()> {
+        self.plugin.flush_events()
+    }
+
+    fn parameter_count(&self) -> usize {
+        self.plugin.parameter_count()
+    }
+
+    fn parameter_info(&self, index: usize) -> Result<ParameterInfo> {
+        self.plugin.parameter_info(index)
+    }
+
+    fn get_parameter(&self, index: usize) -> Result<f32> {
+        self.plugin.get_parameter(index)
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) -> Result<()> {
+        self.plugin.set_parameter(index, value)
+    }
+
+    fn send_midi(&mut self, events: &[MidiEvent]) -> Result<()> {
+        let events = self.limit(events);
+        self.plugin.send_midi(&events)
+    }
+
+    fn preset_count(&self) -> Result<usize> {
+        self.plugin.preset_count()
+    }
+
+    fn preset_info(&self, index: usize) -> Result<PresetInfo> {
+        self.plugin.preset_info(index)
+    }
+
+    fn load_preset(&mut self, preset_number: i32) -> Result<()> {
+        self.plugin.load_preset(preset_number)
+    }
+
+    fn current_preset(&self) -> Result<Option<CurrentPreset>> {
+        self.plugin.current_preset()
+    }
+
+    fn get_state(&self) -> Result<Vec<u8>> {
+        self.plugin.get_state()
+    }
+
+    fn set_state(&mut self, data: &[u8]) -> Result<()> {
+        self.plugin.set_state(data)
+    }
+
+    fn get_state_to_writer(&self, writer: &mut dyn std::io::Write) -> Result<()> {
+        self.plugin.get_state_to_writer(writer)
+    }
+
+    fn set_state_from_reader(&mut self, reader: &mut dyn std::io::Read) -> Result<()> {
+        self.plugin.set_state_from_reader(reader)
+    }
+
+    fn set_state_from_file(&mut self, path: &std::path::Path) -> Result<()> {
+        self.plugin.set_state_from_file(path)
+    }
+
+    fn take_state_dirty(&self) -> bool {
+        self.plugin.take_state_dirty()
+    }
+
+    fn info(&self) -> &PluginInfo {
+        self.plugin.info()
+    }
+
+    fn is_initialized(&self) -> bool {
+        self.plugin.is_initialized()
+    }
+
+    fn input_channels(&self) -> usize {
+        self.plugin.input_channels()
+    }
+
+    fn output_channels(&self) -> usize {
+        self.plugin.output_channels()
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.plugin.latency_samples()
+    }
+
+    fn tail_samples(&self) -> usize {
+        self.plugin.tail_samples()
+    }
+
+    fn tail_seconds(&self) -> f64 {
+        self.plugin.tail_seconds()
+    }
+
+    fn set_bypass(&mut self, bypassed: bool) -> Result<()> {
+        self.plugin.set_bypass(bypassed)
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.plugin.is_bypassed()
+    }
+
+    fn quirks(&self) -> Quirks {
+        self.plugin.quirks()
+    }
+
+    fn plugin_meter(&self) -> Option<Vec<MeterReading>> {
+        self.plugin.plugin_meter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockPlugin;
+
+    #[test]
+    fn test_voice_stealing() {
+        let mut synth = VoiceLimiter::new(MockPlugin::new()).with_max_voices(2);
+        synth
+            .send_midi(&[
+                MidiEvent::note_on(60, 100, 0, 0),
+                MidiEvent::note_on(64, 100, 0, 0),
+                MidiEvent::note_on(67, 100, 0, 32),
+            ])
+            .unwrap();
+
+        assert_eq!(synth.active_voices(), 2);
+        assert_eq!(synth.stolen_voices(), 1);
+        // The oldest note is released right before the new one
+        assert_eq!(
+            synth.inner().midi_received[2..],
+            [
+                MidiEvent::note_off(60, 0, 0, 32),
+                MidiEvent::note_on(67, 100, 0, 32)
+            ]
+        );
+
+        synth
+            .send_midi(&[MidiEvent::note_off(64, 0, 0, 0)])
+            .unwrap();
+        assert_eq!(synth.active_voices(), 1);
+
+        // MIDI passed with a block is limited the same way
+        synth.initialize(48000.0, 64).unwrap();
+        let input = vec![0.0f32; 64];
+        let (mut left, mut right) = (vec![0.0f32; 64], vec![0.0f32; 64]);
+        let midi = [
+            MidiEvent::note_on(72, 100, 0, 8),
+            MidiEvent::note_on(74, 100, 0, 16),
+        ];
+        synth
+            .process_with_events(
+                &[&input, &input],
+                &mut [&mut left, &mut right],
+                64,
+                &[],
+                &midi,
+            )
+            .unwrap();
+        assert_eq!(synth.stolen_voices(), 2);
+        assert_eq!(
+            synth.inner().midi_received[5..],
+            [
+                MidiEvent::note_on(72, 100, 0, 8),
+                MidiEvent::note_off(67, 0, 0, 16),
+                MidiEvent::note_on(74, 100, 0, 16)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sustain_pedal_holds_voices() {
+        let mut synth = VoiceLimiter::new(MockPlugin::new()).with_max_voices(2);
+        synth
+            .send_midi(&[
+                MidiEvent::control_change(CC_SUSTAIN, 127, 0, 0),
+                MidiEvent::note_on(60, 100, 0, 0),
+                MidiEvent::note_off(60, 0, 0, 0),
+                MidiEvent::note_on(64, 100, 0, 0),
+            ])
+            .unwrap();
+        assert_eq!(synth.active_voices(), 2);
+
+        // The pedal-held voice goes first, though the held key is newer
+        synth
+            .send_midi(&[MidiEvent::note_on(67, 100, 0, 0)])
+            .unwrap();
+        assert!(synth
+            .inner()
+            .midi_received
+            .contains(&MidiEvent::note_off(60, 0, 0, 0)));
+
+        synth
+            .send_midi(&[MidiEvent::control_change(CC_ALL_NOTES_OFF, 0, 0, 0)])
+            .unwrap();
+        assert_eq!(synth.active_voices(), 0);
+    }
+}
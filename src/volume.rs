@@ -0,0 +1,458 @@
+//! Classification of the volumes plugins are installed on
+//!
+//! Plugins on network shares or removable drives are scanned like any other,
+//! but every directory listing and file read can stall for as long as the
+//! share takes to answer, or until the drive spins up. [`volume_kind()`] tells
+//! local volumes apart from the rest so scans can skip them or guard them with
+//! a timeout (see [`ScanLimits`](crate::sandbox::ScanLimits)).
+//!
+//! Detection uses `statfs()` on macOS, the mount table on Linux and
+//! `GetDriveTypeW()` on Windows. Anything that can't be classified is
+//! [`VolumeKind::Unknown`] and treated as local.
+//!
+//! Filesystem calls that may stall are run through a probe thread that the
+//! scan stops waiting for after a timeout. Probe threads are reused while
+//! volumes answer, and at most a few stalled ones are left behind at any time.
+//!
+//! # Examples
+//!
+//! ```
+//! use rack::volume::{self, VolumeKind};
+//!
+//! let kind = volume::volume_kind(&std::env::temp_dir());
+//! if !kind.is_local() {
+//!     println!("temp dir is on a {:?} volume", kind);
+//! }
+//! ```
+
+use std::path::Path;
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+/// Where a path physically lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VolumeKind {
+    /// A fixed local disk
+    Local,
+
+    /// A network share (NFS, SMB, AFP, WebDAV, ...)
+    Network,
+
+    /// A removable drive or optical disc
+    Removable,
+
+    /// The volume couldn't be classified
+    Unknown,
+}
+
+impl VolumeKind {
+    /// Whether the volume should be treated as local
+    ///
+    /// Unclassified volumes count as local, so an unsupported platform scans
+    /// everything.
+    pub fn is_local(self) -> bool {
+        matches!(self, VolumeKind::Local | VolumeKind::Unknown)
+    }
+}
+
+/// Classify the volume `path` is on
+///
+/// The path doesn't need to exist. On a network share this may block for as
+/// long as the share takes to respond; use
+/// [`ScanLimits::io_timeout`](crate::sandbox::ScanLimits::io_timeout) to bound
+/// it during scans.
+#[cfg(target_os = "linux")]
+pub fn volume_kind(path: &Path) -> VolumeKind {
+    let path = match std::fs::canonicalize(path) {
+        Ok(path) => path,
+        Err(_) => match std::env::current_dir() {
+            Ok(cwd) => cwd.join(path),
+            Err(_) => return VolumeKind::Unknown,
+        },
+    };
+    match std::fs::read_to_string("/proc/self/mounts") {
+        Ok(mounts) => kind_from_mounts(&mounts, &path),
+        Err(_) => VolumeKind::Unknown,
+    }
+}
+
+/// Classify the volume `path` is on
+///
+/// The path doesn't need to exist. On a network share this may block for as
+/// long as the share takes to respond; use
+/// [`ScanLimits::io_timeout`](crate::sandbox::ScanLimits::io_timeout) to bound
+/// it during scans.
+#[cfg(target_vendor = "apple")]
+pub fn volume_kind(path: &Path) -> VolumeKind {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    // struct statfs from <sys/mount.h> (64-bit inode layout)
+    #[repr(C)]
+    struct StatFs {
+        f_bsize: u32,
+        f_iosize: i32,
+        f_blocks: u64,
+        f_bfree: u64,
+        f_bavail: u64,
+        f_files: u64,
+        f_ffree: u64,
+        f_fsid: [i32; 2],
+        f_owner: u32,
+        f_type: u32,
+        f_flags: u32,
+        f_fssubtype: u32,
+        f_fstypename: [u8; 16],
+        f_mntonname: [u8; 1024],
+        f_mntfromname: [u8; 1024],
+        f_flags_ext: u32,
+        f_reserved: [u32; 7],
+    }
+
+    const MNT_REMOVABLE: u32 = 0x0000_0200;
+    const MNT_LOCAL: u32 = 0x0000_1000;
+
+    extern "C" {
+        #[cfg_attr(target_arch = "x86_64", link_name = "statfs$INODE64")]
+        fn statfs(path: *const std::ffi::c_char, buf: *mut StatFs) -> i32;
+    }
+
+    // Missing paths are classified by their nearest existing ancestor
+    let Some(existing) = path.ancestors().find(|p| p.exists()) else {
+        return VolumeKind::Unknown;
+    };
+    let Ok(c_path) = CString::new(existing.as_os_str().as_bytes()) else {
+        return VolumeKind::Unknown;
+    };
+
+    let mut info = std::mem::MaybeUninit::<StatFs>::zeroed();
+    // Safety: c_path is NUL-terminated and info is a correctly sized statfs
+    let result = unsafe { statfs(c_path.as_ptr(), info.as_mut_ptr()) };
+    if result != 0 {
+        return VolumeKind::Unknown;
+    }
+    // Safety: statfs succeeded and filled in the struct
+    let flags = unsafe { info.assume_init() }.f_flags;
+
+    if flags & MNT_LOCAL == 0 {
+        VolumeKind::Network
+    } else if flags & MNT_REMOVABLE != 0 {
+        VolumeKind::Removable
+    } else {
+        VolumeKind::Local
+    }
+}
+
+/// Classify the volume `path` is on
+///
+/// The path doesn't need to exist. On a network share this may block for as
+/// long as the share takes to respond; use
+/// [`ScanLimits::io_timeout`](crate::sandbox::ScanLimits::io_timeout) to bound
+/// it during scans.
+#[cfg(windows)]
+pub fn volume_kind(path: &Path) -> VolumeKind {
+    use std::path::{Component, Prefix};
+
+    const DRIVE_REMOVABLE: u32 = 2;
+    const DRIVE_FIXED: u32 = 3;
+    const DRIVE_REMOTE: u32 = 4;
+    const DRIVE_CDROM: u32 = 5;
+    const DRIVE_RAMDISK: u32 = 6;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetDriveTypeW(root_path_name: *const u16) -> u32;
+    }
+
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let letter = match path.components().next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::UNC(..) | Prefix::VerbatimUNC(..) => return VolumeKind::Network,
+            Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => letter,
+            _ => return VolumeKind::Unknown,
+        },
+        _ => return VolumeKind::Unknown,
+    };
+
+    let root: Vec<u16> = format!("{}:\\", letter as char)
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect();
+    // Safety: root is a NUL-terminated UTF-16 string
+    match unsafe { GetDriveTypeW(root.as_ptr()) } {
+        DRIVE_FIXED | DRIVE_RAMDISK => VolumeKind::Local,
+        DRIVE_REMOTE => VolumeKind::Network,
+        DRIVE_REMOVABLE | DRIVE_CDROM => VolumeKind::Removable,
+        _ => VolumeKind::Unknown,
+    }
+}
+
+/// Classify the volume `path` is on
+///
+/// Always [`VolumeKind::Unknown`] on this platform.
+#[cfg(not(any(target_os = "linux", target_vendor = "apple", windows)))]
+pub fn volume_kind(_path: &Path) -> VolumeKind {
+    VolumeKind::Unknown
+}
+
+/// Most probe threads left waiting on unresponsive volumes at any time
+///
+/// Once reached, probes give up at once instead of starting another thread.
+const MAX_STALLED_PROBES: usize = 8;
+
+/// Probe threads currently stuck in a call that was given up on
+static STALLED_PROBES: AtomicUsize = AtomicUsize::new(0);
+
+/// States of a probe call
+const CALL_RUNNING: u8 = 0;
+const CALL_DONE: u8 = 1;
+const CALL_ABANDONED: u8 = 2;
+
+type ProbeJob = Box<dyn FnOnce() + Send>;
+
+/// Runs filesystem calls on a reusable thread, giving up on any that take
+/// longer than a timeout
+///
+/// The thread serves every call until one stalls; it is then left to finish
+/// that call and exit, and the next call starts a new thread.
+pub(crate) struct IoProbe {
+    timeout: Duration,
+    worker: Option<mpsc::Sender<ProbeJob>>,
+}
+
+impl IoProbe {
+    pub(crate) fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            worker: None,
+        }
+    }
+
+    /// Run `call` on the probe thread
+    ///
+    /// Returns `None` if it didn't return within the timeout, or if too many
+    /// probe threads are already stalled to start one. If no thread can be
+    /// started, `call` runs on the current thread.
+    pub(crate) fn run<T, F>(&mut self, call: F) -> Option<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        if self.worker.is_none() && STALLED_PROBES.load(Ordering::Acquire) >= MAX_STALLED_PROBES {
+            return None;
+        }
+
+        let state = Arc::new(AtomicU8::new(CALL_RUNNING));
+        let call_state = Arc::clone(&state);
+        let (tx, rx) = mpsc::channel();
+        let job: ProbeJob = Box::new(move || {
+            let _finished = FinishCall(call_state);
+            let _ = tx.send(call());
+        });
+
+        // A worker that died (a panicking call) is replaced
+        let job = match self.worker.as_ref() {
+            Some(worker) => worker.send(job).err().map(|mpsc::SendError(job)| job),
+            None => Some(job),
+        };
+        if let Some(job) = job {
+            match spawn_probe() {
+                Some(worker) => {
+                    let _ = worker.send(job);
+                    self.worker = Some(worker);
+                }
+                None => {
+                    self.worker = None;
+                    job();
+                }
+            }
+        }
+
+        match rx.recv_timeout(self.timeout) {
+            Ok(value) => Some(value),
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                // Leave the thread to finish the call and exit
+                self.worker = None;
+                STALLED_PROBES.fetch_add(1, Ordering::AcqRel);
+                if state
+                    .compare_exchange(
+                        CALL_RUNNING,
+                        CALL_ABANDONED,
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                    )
+                    .is_err()
+                {
+                    // It returned just now after all
+                    STALLED_PROBES.fetch_sub(1, Ordering::AcqRel);
+                }
+                None
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                self.worker = None;
+                None
+            }
+        }
+    }
+}
+
+/// Marks a probe call as done when dropped, even if the call panicked
+struct FinishCall(Arc<AtomicU8>);
+
+impl Drop for FinishCall {
+    fn drop(&mut self) {
+        if self.0.swap(CALL_DONE, Ordering::AcqRel) == CALL_ABANDONED {
+            STALLED_PROBES.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+/// Start a probe thread, returning the sender for its calls
+fn spawn_probe() -> Option<mpsc::Sender<ProbeJob>> {
+    let (tx, rx) = mpsc::channel::<ProbeJob>();
+    std::thread::Builder::new()
+        .name("rack-io-probe".to_string())
+        .spawn(move || {
+            for job in rx {
+                job();
+            }
+        })
+        .ok()?;
+    Some(tx)
+}
+
+/// Filesystem types that live on another machine
+#[cfg(any(target_os = "linux", test))]
+const NETWORK_FILESYSTEMS: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "ncpfs",
+    "afs",
+    "9p",
+    "ceph",
+    "glusterfs",
+    "lustre",
+    "davfs",
+    "fuse.sshfs",
+    "fuse.rclone",
+    "fuse.s3fs",
+];
+
+/// Filesystem types only found on optical media
+#[cfg(any(target_os = "linux", test))]
+const REMOVABLE_FILESYSTEMS: &[&str] = &["iso9660", "udf"];
+
+/// Mount points under which desktop environments mount removable drives
+#[cfg(any(target_os = "linux", test))]
+const REMOVABLE_MOUNT_ROOTS: &[&str] = &["/media", "/run/media"];
+
+/// Classify an absolute path using the contents of `/proc/self/mounts`
+#[cfg(any(target_os = "linux", test))]
+fn kind_from_mounts(mounts: &str, path: &Path) -> VolumeKind {
+    // The mount with the longest mount point containing the path
+    let mount = mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _device = fields.next()?;
+            let mount_point = unescape_mount_field(fields.next()?);
+            let fs_type = fields.next()?;
+            Some((mount_point, fs_type))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.len());
+
+    let Some((mount_point, fs_type)) = mount else {
+        return VolumeKind::Unknown;
+    };
+
+    if NETWORK_FILESYSTEMS.contains(&fs_type) {
+        VolumeKind::Network
+    } else if REMOVABLE_FILESYSTEMS.contains(&fs_type)
+        || REMOVABLE_MOUNT_ROOTS
+            .iter()
+            .any(|root| Path::new(&mount_point).starts_with(root))
+    {
+        VolumeKind::Removable
+    } else {
+        VolumeKind::Local
+    }
+}
+
+/// Undo the octal escaping of spaces, tabs and backslashes in mount fields
+#[cfg(any(target_os = "linux", test))]
+fn unescape_mount_field(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes.get(i + 1..i + 4).and_then(|digits| {
+            std::str::from_utf8(digits)
+                .ok()
+                .and_then(|digits| u8::from_str_radix(digits, 8).ok())
+        });
+        match (bytes[i], octal) {
+            (b'\\', Some(byte)) => {
+                out.push(byte);
+                i += 4;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTS: &str = "\
+/dev/nvme0n1p2 / ext4 rw,relatime 0 0
+proc /proc proc rw,nosuid,nodev,noexec,relatime 0 0
+server:/export/audio /mnt/studio nfs4 rw,relatime 0 0
+//nas/plugins /home/me/NAS\\040Plugins cifs rw,relatime 0 0
+/dev/sdb1 /run/media/me/USB vfat rw,nosuid,nodev 0 0
+/dev/sr0 /mnt/cdrom iso9660 ro 0 0
+";
+
+    #[test]
+    fn test_io_probe() {
+        let mut probe = IoProbe::new(Duration::from_millis(500));
+        let first = probe.run(|| std::thread::current().id()).unwrap();
+        let second = probe.run(|| std::thread::current().id()).unwrap();
+        assert_eq!(first, second);
+        assert_ne!(first, std::thread::current().id());
+
+        // A stalled call is given up on; the next one gets a fresh thread
+        let stalled = probe.run(|| std::thread::sleep(Duration::from_secs(2)));
+        assert_eq!(stalled, None);
+        let third = probe.run(|| std::thread::current().id()).unwrap();
+        assert_ne!(third, first);
+    }
+
+    #[test]
+    fn test_kind_from_mounts() {
+        let kind = |path: &str| kind_from_mounts(MOUNTS, Path::new(path));
+
+        assert_eq!(kind("/usr/lib/vst3/Synth.vst3"), VolumeKind::Local);
+        assert_eq!(kind("/mnt/studio/vst3/Synth.vst3"), VolumeKind::Network);
+        assert_eq!(
+            kind("/home/me/NAS Plugins/Reverb.vst3"),
+            VolumeKind::Network
+        );
+        assert_eq!(kind("/home/me/NAS Plugins2"), VolumeKind::Local);
+        assert_eq!(kind("/run/media/me/USB/Delay.vst3"), VolumeKind::Removable);
+        assert_eq!(kind("/mnt/cdrom/Installer"), VolumeKind::Removable);
+        assert_eq!(kind_from_mounts("", Path::new("/")), VolumeKind::Unknown);
+
+        assert!(VolumeKind::Unknown.is_local());
+        assert!(!VolumeKind::Removable.is_local());
+    }
+}
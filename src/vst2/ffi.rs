@@ -0,0 +1,168 @@
+//! Raw FFI bindings to the rack-sys VST2 C API
+//!
+//! This module contains unsafe FFI declarations. All safe wrappers
+//! should be in scanner.rs and instance.rs.
+
+#![allow(dead_code)]
+
+use std::os::raw::{c_char, c_int};
+
+// Opaque types (zero-sized to prevent construction)
+#[repr(C)]
+pub struct RackVST2Plugin {
+    _private: [u8; 0],
+}
+
+// Plugin type enum
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RackVST2PluginType {
+    Effect = 0,
+    Instrument = 1,
+    Analyzer = 2,
+    Spatial = 3,
+    Other = 4,
+}
+
+// Plugin info struct (matches C layout exactly)
+#[repr(C)]
+#[derive(Clone)]
+pub struct RackVST2PluginInfo {
+    pub name: [c_char; 256],
+    pub manufacturer: [c_char; 256],
+    pub unique_id: i32,
+    pub version: i32,
+    pub plugin_type: RackVST2PluginType,
+    pub num_inputs: i32,
+    pub num_outputs: i32,
+}
+
+// MIDI event struct (matches C layout exactly)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RackVST2MidiEvent {
+    pub sample_offset: u32,
+    pub data: [u8; 3],
+}
+
+// Error codes
+pub const RACK_VST2_OK: c_int = 0;
+pub const RACK_VST2_ERROR_GENERIC: c_int = -1;
+pub const RACK_VST2_ERROR_NOT_FOUND: c_int = -2;
+pub const RACK_VST2_ERROR_INVALID_PARAM: c_int = -3;
+pub const RACK_VST2_ERROR_NOT_INITIALIZED: c_int = -4;
+pub const RACK_VST2_ERROR_LOAD_FAILED: c_int = -5;
+pub const RACK_VST2_ERROR_NOT_SUPPORTED: c_int = -6;
+pub const RACK_VST2_ERROR_BUFFER_TOO_SMALL: c_int = -7;
+
+extern "C" {
+    // Scanning
+    pub fn rack_vst2_describe(
+        path: *const c_char,
+        plugins: *mut RackVST2PluginInfo,
+        max_plugins: usize,
+    ) -> c_int;
+
+    // Plugin lifecycle
+    pub fn rack_vst2_set_host_name(name: *const c_char) -> c_int;
+    pub fn rack_vst2_set_host_capabilities(
+        capabilities: *const *const c_char,
+        count: usize,
+    ) -> c_int;
+    pub fn rack_vst2_plugin_new(path: *const c_char, unique_id: i32) -> *mut RackVST2Plugin;
+    pub fn rack_vst2_plugin_free(plugin: *mut RackVST2Plugin);
+    pub fn rack_vst2_plugin_initialize(
+        plugin: *mut RackVST2Plugin,
+        sample_rate: f64,
+        max_block_size: u32,
+    ) -> c_int;
+    pub fn rack_vst2_plugin_is_initialized(plugin: *mut RackVST2Plugin) -> c_int;
+    pub fn rack_vst2_plugin_reset(plugin: *mut RackVST2Plugin) -> c_int;
+    pub fn rack_vst2_plugin_get_input_channels(plugin: *mut RackVST2Plugin) -> c_int;
+    pub fn rack_vst2_plugin_get_output_channels(plugin: *mut RackVST2Plugin) -> c_int;
+    pub fn rack_vst2_plugin_get_latency(plugin: *mut RackVST2Plugin) -> c_int;
+    pub fn rack_vst2_plugin_get_tail_size(plugin: *mut RackVST2Plugin) -> c_int;
+    pub fn rack_vst2_plugin_set_bypass(plugin: *mut RackVST2Plugin, bypassed: c_int) -> c_int;
+
+    // Processing
+    pub fn rack_vst2_plugin_process(
+        plugin: *mut RackVST2Plugin,
+        inputs: *const *const f32,
+        num_input_channels: u32,
+        outputs: *const *mut f32,
+        num_output_channels: u32,
+        frames: u32,
+    ) -> c_int;
+    pub fn rack_vst2_plugin_set_sample_position(
+        plugin: *mut RackVST2Plugin,
+        position: i64,
+    ) -> c_int;
+    pub fn rack_vst2_plugin_set_transport(
+        plugin: *mut RackVST2Plugin,
+        playing: c_int,
+        tempo: f64,
+        numerator: i32,
+        denominator: i32,
+        ppq_position: f64,
+        bar_start_ppq: f64,
+        cycling: c_int,
+        cycle_start_ppq: f64,
+        cycle_end_ppq: f64,
+        offline: c_int,
+    ) -> c_int;
+
+    // Parameters
+    pub fn rack_vst2_plugin_parameter_count(plugin: *mut RackVST2Plugin) -> c_int;
+    pub fn rack_vst2_plugin_get_parameter(
+        plugin: *mut RackVST2Plugin,
+        index: u32,
+        value: *mut f32,
+    ) -> c_int;
+    pub fn rack_vst2_plugin_set_parameter(
+        plugin: *mut RackVST2Plugin,
+        index: u32,
+        value: f32,
+    ) -> c_int;
+    pub fn rack_vst2_plugin_parameter_name(
+        plugin: *mut RackVST2Plugin,
+        index: u32,
+        buffer: *mut c_char,
+        size: *mut usize,
+    ) -> c_int;
+    pub fn rack_vst2_plugin_parameter_unit(
+        plugin: *mut RackVST2Plugin,
+        index: u32,
+        buffer: *mut c_char,
+        size: *mut usize,
+    ) -> c_int;
+
+    // Programs
+    pub fn rack_vst2_plugin_get_preset_count(plugin: *mut RackVST2Plugin) -> c_int;
+    pub fn rack_vst2_plugin_preset_name(
+        plugin: *mut RackVST2Plugin,
+        index: u32,
+        buffer: *mut c_char,
+        size: *mut usize,
+    ) -> c_int;
+    pub fn rack_vst2_plugin_load_preset(plugin: *mut RackVST2Plugin, preset_number: i32) -> c_int;
+    pub fn rack_vst2_plugin_get_current_preset(plugin: *mut RackVST2Plugin) -> c_int;
+
+    // State
+    pub fn rack_vst2_plugin_get_state(
+        plugin: *mut RackVST2Plugin,
+        data: *mut u8,
+        size: *mut usize,
+    ) -> c_int;
+    pub fn rack_vst2_plugin_set_state(
+        plugin: *mut RackVST2Plugin,
+        data: *const u8,
+        size: usize,
+    ) -> c_int;
+
+    // MIDI
+    pub fn rack_vst2_plugin_send_midi(
+        plugin: *mut RackVST2Plugin,
+        events: *const RackVST2MidiEvent,
+        event_count: u32,
+    ) -> c_int;
+}
@@ -0,0 +1,616 @@
+use crate::bypass::HostBypass;
+use crate::host;
+use crate::node::BlockContext;
+use crate::paths;
+use crate::quirks::{self, Quirk, Quirks};
+use crate::traits;
+use crate::{
+    CurrentPreset, Error, MidiEvent, ParameterChange, ParameterCurve, ParameterInfo,
+    ParameterVisibility, PluginInfo, PluginInstance, PresetInfo, Result,
+};
+use smallvec::SmallVec;
+use std::ffi::{c_char, CString};
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+use super::ffi;
+use super::util::{map_error, negotiated_bytes, negotiated_name};
+
+/// An instantiated VST2 plugin
+///
+/// All VST2 parameters are normalized (0.0 to 1.0). State is the plugin's
+/// bank chunk for plugins that provide one, otherwise the current program
+/// and every parameter value.
+///
+/// # Thread Safety
+///
+/// This type is `Send` but not `Sync`:
+/// - `Send`: The plugin can be moved between threads safely
+/// - NOT `Sync`: Multiple threads should not access the plugin simultaneously
+///   without synchronization. Wrap in `Arc<Mutex<>>` if shared access is needed.
+pub struct Vst2Plugin {
+    inner: NonNull<ffi::RackVST2Plugin>,
+    info: PluginInfo,
+    // Pre-allocated pointer arrays for zero-allocation process() calls
+    input_ptrs: Vec<*const f32>,
+    output_ptrs: Vec<*mut f32>,
+    // Channel configuration (queried during initialize)
+    input_channels: usize,
+    output_channels: usize,
+    // Max frames per process() call (set during initialize)
+    max_block_size: usize,
+    // Sample rate (set during initialize)
+    sample_rate: f64,
+    // Timeline position of the next processed sample (mirrors the C++ side)
+    sample_position: u64,
+    // Quirks looked up when the plugin was loaded
+    quirks: Quirks,
+    // Whether the plugin bypasses itself (effSetBypass); plugins that can't
+    // are bypassed by the host instead
+    native_bypassed: bool,
+    bypass: HostBypass,
+    // PhantomData<*const ()> makes this type !Sync while keeping it Send
+    _not_sync: PhantomData<*const ()>,
+}
+
+// Safety: Vst2Plugin can be sent between threads because:
+// 1. Each plugin instance owns its C++ state exclusively
+// 2. The plugin doesn't share mutable state with other instances
+unsafe impl Send for Vst2Plugin {}
+
+// Note: Vst2Plugin is NOT Sync due to PhantomData<*const ()>
+// This is intentional - VST2 instances require synchronization for shared access
+
+impl Vst2Plugin {
+    /// Create a new VST2 plugin instance
+    pub(crate) fn new(info: &PluginInfo) -> Result<Self> {
+        // Shell libraries hold several plugins, told apart by their ID
+        let unique_id: i32 = info
+            .unique_id
+            .parse()
+            .map_err(|_| Error::Other(format!("Invalid VST2 unique ID: {}", info.unique_id)))?;
+
+        // Load from the resolved location; UTF-8 on Windows, raw bytes elsewhere
+        let path = paths::to_ffi_bytes(&info.canonical_path)?;
+
+        let host_info = host::host_info();
+        let host_name = CString::new(host_info.name)
+            .map_err(|_| Error::Other("Host name contains null byte".to_string()))?;
+        // Answered in audioMasterCanDo
+        let capabilities = host_info
+            .capabilities
+            .into_iter()
+            .map(CString::new)
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| Error::Other("Host capability contains null byte".to_string()))?;
+        let capability_ptrs: Vec<*const c_char> = capabilities.iter().map(|c| c.as_ptr()).collect();
+
+        unsafe {
+            let result = ffi::rack_vst2_set_host_name(host_name.as_ptr());
+            if result != ffi::RACK_VST2_OK {
+                return Err(map_error(result));
+            }
+            let result = ffi::rack_vst2_set_host_capabilities(
+                capability_ptrs.as_ptr(),
+                capability_ptrs.len(),
+            );
+            if result != ffi::RACK_VST2_OK {
+                return Err(map_error(result));
+            }
+
+            let ptr = ffi::rack_vst2_plugin_new(path.as_ptr(), unique_id);
+            if ptr.is_null() {
+                return Err(Error::PluginNotFound(format!(
+                    "Failed to create VST2 instance for {}",
+                    info.name
+                )));
+            }
+
+            Ok(Self {
+                inner: NonNull::new(ptr).expect("pointer is non-null after null check"),
+                info: info.clone(),
+                input_ptrs: Vec::new(),
+                output_ptrs: Vec::new(),
+                input_channels: 0,
+                output_channels: 0,
+                max_block_size: 0,
+                sample_rate: 0.0,
+                sample_position: 0,
+                quirks: quirks::lookup(info),
+                native_bypassed: false,
+                bypass: HostBypass::new(),
+                _not_sync: PhantomData,
+            })
+        }
+    }
+
+    /// The plugin's latency in samples, as it reports it
+    ///
+    /// Only meaningful once the plugin is initialized; 0 before.
+    pub fn latency(&self) -> usize {
+        let latency = unsafe { ffi::rack_vst2_plugin_get_latency(self.inner.as_ptr()) };
+        latency.max(0) as usize
+    }
+}
+
+impl Drop for Vst2Plugin {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::rack_vst2_plugin_free(self.inner.as_ptr());
+        }
+    }
+}
+
+impl PluginInstance for Vst2Plugin {
+    fn initialize(&mut self, sample_rate: f64, max_block_size: usize) -> Result<()> {
+        unsafe {
+            let result = ffi::rack_vst2_plugin_initialize(
+                self.inner.as_ptr(),
+                sample_rate,
+                max_block_size as u32,
+            );
+
+            if result != ffi::RACK_VST2_OK {
+                return Err(map_error(result));
+            }
+
+            // VST2 channel counts are fixed once the plugin is open
+            let inputs = ffi::rack_vst2_plugin_get_input_channels(self.inner.as_ptr());
+            let outputs = ffi::rack_vst2_plugin_get_output_channels(self.inner.as_ptr());
+            self.input_channels = inputs.max(0) as usize;
+            self.output_channels = outputs.max(0) as usize;
+        }
+
+        self.input_ptrs = vec![std::ptr::null(); self.input_channels];
+        self.output_ptrs = vec![std::ptr::null_mut(); self.output_channels];
+        self.max_block_size = max_block_size;
+        self.sample_rate = sample_rate;
+        self.bypass
+            .prepare(sample_rate, self.output_channels, self.latency());
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        unsafe {
+            let result = ffi::rack_vst2_plugin_reset(self.inner.as_ptr());
+
+            if result != ffi::RACK_VST2_OK {
+                return Err(map_error(result));
+            }
+
+            Ok(())
+        }
+    }
+
+    fn process(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+    ) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        // Reject oversized blocks here rather than passing them to the plugin
+        if num_frames > self.max_block_size {
+            return Err(Error::BlockTooLarge {
+                max: self.max_block_size,
+                got: num_frames,
+            });
+        }
+
+        // Validate channel counts match plugin configuration
+        if inputs.len() != self.input_channels {
+            return Err(Error::Other(format!(
+                "Input channel count mismatch: plugin expects {}, got {}",
+                self.input_channels,
+                inputs.len()
+            )));
+        }
+        if outputs.len() != self.output_channels {
+            return Err(Error::Other(format!(
+                "Output channel count mismatch: plugin expects {}, got {}",
+                self.output_channels,
+                outputs.len()
+            )));
+        }
+
+        for (i, input) in inputs.iter().enumerate() {
+            if input.len() < num_frames {
+                return Err(Error::Other(format!(
+                    "Input channel {} has {} samples, need at least {}",
+                    i,
+                    input.len(),
+                    num_frames
+                )));
+            }
+        }
+        for (i, output) in outputs.iter().enumerate() {
+            if output.len() < num_frames {
+                return Err(Error::Other(format!(
+                    "Output channel {} has {} samples, need at least {}",
+                    i,
+                    output.len(),
+                    num_frames
+                )));
+            }
+        }
+
+        // Reuse pre-allocated pointer arrays (zero-allocation hot path)
+        for (ptr, input) in self.input_ptrs.iter_mut().zip(inputs) {
+            *ptr = input.as_ptr();
+        }
+        for (ptr, output) in self.output_ptrs.iter_mut().zip(outputs.iter_mut()) {
+            if self.quirks.contains(Quirk::ClearOutputsBeforeProcess) {
+                output[..num_frames].fill(0.0);
+            }
+            *ptr = output.as_mut_ptr();
+        }
+
+        unsafe {
+            let result = ffi::rack_vst2_plugin_process(
+                self.inner.as_ptr(),
+                self.input_ptrs.as_ptr(),
+                inputs.len() as u32,
+                self.output_ptrs.as_ptr(),
+                outputs.len() as u32,
+                num_frames as u32,
+            );
+
+            if result != ffi::RACK_VST2_OK {
+                return Err(map_error(result));
+            }
+        }
+        self.bypass.process(inputs, outputs, num_frames);
+
+        self.sample_position += num_frames as u64;
+        Ok(())
+    }
+
+    /// Process a block, reporting the context's transport to the plugin
+    /// through `audioMasterGetTime`
+    fn process_with_context(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        context: &BlockContext,
+    ) -> Result<()> {
+        if context.sample_position != self.sample_position {
+            self.set_sample_position(context.sample_position)?;
+        }
+
+        let signature = context.time_signature;
+        let cycle = context.loop_range;
+        unsafe {
+            let result = ffi::rack_vst2_plugin_set_transport(
+                self.inner.as_ptr(),
+                context.playing as i32,
+                context.tempo,
+                signature.numerator as i32,
+                signature.denominator as i32,
+                context.beat,
+                context.bar_start,
+                cycle.is_some() as i32,
+                cycle.map_or(0.0, |range| range.start),
+                cycle.map_or(0.0, |range| range.end),
+                context.is_offline as i32,
+            );
+            if result != ffi::RACK_VST2_OK {
+                return Err(map_error(result));
+            }
+        }
+
+        self.process(inputs, outputs, context.num_frames)
+    }
+
+    /// Split the block at each change offset
+    ///
+    /// `setParameter` takes effect for the next `processReplacing`, so each
+    /// piece is processed with the values set for it; MIDI keeps its offset
+    /// within the piece.
+    fn process_with_events(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_frames: usize,
+        changes: &[ParameterChange],
+        midi: &[MidiEvent],
+    ) -> Result<()> {
+        traits::split_events(self, inputs, outputs, num_frames, changes, midi)
+    }
+
+    fn sample_position(&self) -> u64 {
+        self.sample_position
+    }
+
+    fn set_sample_position(&mut self, position: u64) -> Result<()> {
+        let raw = i64::try_from(position)
+            .map_err(|_| Error::Other(format!("Sample position {} out of range", position)))?;
+
+        unsafe {
+            let result = ffi::rack_vst2_plugin_set_sample_position(self.inner.as_ptr(), raw);
+            if result != ffi::RACK_VST2_OK {
+                return Err(map_error(result));
+            }
+        }
+
+        self.sample_position = position;
+        Ok(())
+    }
+
+    fn parameter_count(&self) -> usize {
+        unsafe {
+            let count = ffi::rack_vst2_plugin_parameter_count(self.inner.as_ptr());
+            count.max(0) as usize
+        }
+    }
+
+    fn parameter_info(&self, index: usize) -> Result<ParameterInfo> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+        if index >= self.parameter_count() {
+            return Err(Error::InvalidParameter(index));
+        }
+
+        let plugin = self.inner.as_ptr();
+        let name = negotiated_name("parameter name", |buffer, size| unsafe {
+            ffi::rack_vst2_plugin_parameter_name(plugin, index as u32, buffer, size)
+        })?;
+        let unit = negotiated_name("parameter unit", |buffer, size| unsafe {
+            ffi::rack_vst2_plugin_parameter_unit(plugin, index as u32, buffer, size)
+        })?;
+
+        Ok(ParameterInfo {
+            index,
+            name,
+            min: 0.0,
+            max: 1.0,
+            // VST2 has no notion of a default value
+            default: 0.0,
+            unit,
+            // VST2 plugins map their normalized values internally
+            curve: ParameterCurve::Linear,
+            visibility: ParameterVisibility::Visible,
+        })
+    }
+
+    fn get_parameter(&self, index: usize) -> Result<f32> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        unsafe {
+            let mut value = 0.0f32;
+            let result =
+                ffi::rack_vst2_plugin_get_parameter(self.inner.as_ptr(), index as u32, &mut value);
+
+            if result == ffi::RACK_VST2_ERROR_INVALID_PARAM {
+                return Err(Error::InvalidParameter(index));
+            }
+            if result != ffi::RACK_VST2_OK {
+                return Err(map_error(result));
+            }
+
+            Ok(value)
+        }
+    }
+
+    fn set_parameter(&mut self, index: usize, value: f32) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        unsafe {
+            let result = ffi::rack_vst2_plugin_set_parameter(
+                self.inner.as_ptr(),
+                index as u32,
+                value.clamp(0.0, 1.0),
+            );
+
+            if result == ffi::RACK_VST2_ERROR_INVALID_PARAM {
+                return Err(Error::InvalidParameter(index));
+            }
+            if result != ffi::RACK_VST2_OK {
+                return Err(map_error(result));
+            }
+
+            Ok(())
+        }
+    }
+
+    fn send_midi(&mut self, events: &[MidiEvent]) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        // VST2 takes raw MIDI bytes, so system messages pass through too
+        let c_events: SmallVec<[ffi::RackVST2MidiEvent; 16]> = events
+            .iter()
+            .map(|event| ffi::RackVST2MidiEvent {
+                sample_offset: event.sample_offset,
+                data: event.to_bytes(),
+            })
+            .collect();
+
+        unsafe {
+            let result = ffi::rack_vst2_plugin_send_midi(
+                self.inner.as_ptr(),
+                c_events.as_ptr(),
+                c_events.len() as u32,
+            );
+
+            if result != ffi::RACK_VST2_OK {
+                return Err(map_error(result));
+            }
+
+            Ok(())
+        }
+    }
+
+    fn preset_count(&self) -> Result<usize> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        unsafe {
+            let count = ffi::rack_vst2_plugin_get_preset_count(self.inner.as_ptr());
+            Ok(count.max(0) as usize)
+        }
+    }
+
+    fn preset_info(&self, index: usize) -> Result<PresetInfo> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        let plugin = self.inner.as_ptr();
+        let mut name = negotiated_name("preset name", |buffer, size| unsafe {
+            ffi::rack_vst2_plugin_preset_name(plugin, index as u32, buffer, size)
+        })?;
+        if name.is_empty() {
+            name = format!("Program {}", index + 1);
+        }
+
+        // VST2 programs are numbered by index
+        Ok(PresetInfo {
+            index,
+            name,
+            preset_number: index as i32,
+        })
+    }
+
+    fn load_preset(&mut self, preset_number: i32) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        unsafe {
+            let result = ffi::rack_vst2_plugin_load_preset(self.inner.as_ptr(), preset_number);
+
+            if result != ffi::RACK_VST2_OK {
+                return Err(map_error(result));
+            }
+
+            Ok(())
+        }
+    }
+
+    fn current_preset(&self) -> Result<Option<CurrentPreset>> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        let preset_number =
+            unsafe { ffi::rack_vst2_plugin_get_current_preset(self.inner.as_ptr()) };
+        if preset_number < 0 {
+            return Err(map_error(preset_number));
+        }
+        if preset_number as usize >= self.preset_count()? {
+            // Plugins without programs still report program 0
+            return Ok(None);
+        }
+
+        let name = self.preset_info(preset_number as usize)?.name;
+        Ok(Some(CurrentPreset {
+            preset_number,
+            name,
+        }))
+    }
+
+    fn get_state(&self) -> Result<Vec<u8>> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        let plugin = self.inner.as_ptr();
+        negotiated_bytes(|data, size| unsafe {
+            ffi::rack_vst2_plugin_get_state(plugin, data, size)
+        })
+    }
+
+    fn set_state(&mut self, data: &[u8]) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        if data.is_empty() {
+            return Err(Error::Other("State data is empty".to_string()));
+        }
+
+        unsafe {
+            let result =
+                ffi::rack_vst2_plugin_set_state(self.inner.as_ptr(), data.as_ptr(), data.len());
+
+            if result != ffi::RACK_VST2_OK {
+                return Err(map_error(result));
+            }
+
+            Ok(())
+        }
+    }
+
+    fn info(&self) -> &PluginInfo {
+        &self.info
+    }
+
+    fn is_initialized(&self) -> bool {
+        unsafe {
+            let result = ffi::rack_vst2_plugin_is_initialized(self.inner.as_ptr());
+            result > 0
+        }
+    }
+
+    fn input_channels(&self) -> usize {
+        self.input_channels
+    }
+
+    fn output_channels(&self) -> usize {
+        self.output_channels
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.latency()
+    }
+
+    fn tail_samples(&self) -> usize {
+        let tail = unsafe { ffi::rack_vst2_plugin_get_tail_size(self.inner.as_ptr()) };
+        tail.max(0) as usize
+    }
+
+    fn tail_seconds(&self) -> f64 {
+        crate::traits::tail_seconds(self.tail_samples(), self.sample_rate)
+    }
+
+    fn set_bypass(&mut self, bypassed: bool) -> Result<()> {
+        if !self.is_initialized() {
+            return Err(Error::NotInitialized);
+        }
+
+        let result = unsafe {
+            ffi::rack_vst2_plugin_set_bypass(self.inner.as_ptr(), bypassed as std::os::raw::c_int)
+        };
+        if result < 0 {
+            return Err(map_error(result));
+        }
+        if result > 0 {
+            self.native_bypassed = bypassed;
+        } else {
+            self.bypass.set_bypassed(bypassed);
+        }
+        Ok(())
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.native_bypassed || self.bypass.is_bypassed()
+    }
+
+    fn quirks(&self) -> Quirks {
+        self.quirks.clone()
+    }
+}
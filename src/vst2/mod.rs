@@ -0,0 +1,7 @@
+mod ffi;
+mod instance;
+mod scanner;
+mod util;
+
+pub use instance::Vst2Plugin;
+pub use scanner::{Vst2Scanner, VST_PATH_ENV};
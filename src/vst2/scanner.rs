@@ -0,0 +1,322 @@
+use crate::events::{self, HostEvent};
+use crate::metadata::{self, SharedMetadataStore};
+use crate::paths;
+use crate::scan::{find_bundles, BundleKind, ScanFilter, ScannerConfig};
+use crate::text::decode_name;
+use crate::{Error, PluginFormat, PluginInfo, PluginScanner, PluginType, Result};
+use std::ffi::c_char;
+use std::mem::MaybeUninit;
+use std::path::{Path, PathBuf};
+
+use super::ffi;
+use super::instance::Vst2Plugin;
+use super::util::map_error;
+
+/// Environment variable with extra VST2 search paths, read by most VST2 hosts
+///
+/// Uses the platform's path list separator (`:` on Unix, `;` on Windows).
+pub const VST_PATH_ENV: &str = "VST_PATH";
+
+/// How deep [`scan()`](PluginScanner::scan) looks below each search path
+///
+/// VST2 has no standard folder layout, and installers commonly create vendor
+/// subfolders.
+const SEARCH_DEPTH: usize = 8;
+
+/// Scanner for VST2 plugins
+///
+/// VST2 plugins are plain shared libraries (`.vst` bundles on macOS), so every
+/// library found is loaded and opened to describe it; libraries that turn out
+/// not to be VST2 plugins, or fail to load, are skipped and reported as
+/// [`HostEvent::Error`]. Shell libraries (e.g. WaveShell) are listed as the
+/// plugins they contain.
+pub struct Vst2Scanner {
+    usage: Option<SharedMetadataStore>,
+    config: ScannerConfig,
+}
+
+impl Vst2Scanner {
+    /// Create a new VST2 scanner
+    ///
+    /// Searches the default system paths plus any listed in `RACK_PLUGIN_PATH`
+    /// (see [`ScannerConfig::from_env()`]).
+    ///
+    /// # Errors
+    ///
+    /// Never fails; returns a `Result` like the other scanners
+    pub fn new() -> Result<Self> {
+        Self::with_config(ScannerConfig::from_env())
+    }
+
+    /// Record every successful [`load()`](PluginScanner::load) in `store`
+    ///
+    /// Enables [`most_used()`](PluginScanner::most_used). Usage tracking is off
+    /// until this is called.
+    pub fn track_usage(&mut self, store: SharedMetadataStore) {
+        self.usage = Some(store);
+    }
+
+    /// Describe the plugins in every VST2 library under `roots`
+    fn scan_roots(
+        &self,
+        roots: &[PathBuf],
+        depth: usize,
+        filter: Option<&ScanFilter>,
+    ) -> Result<Vec<PluginInfo>> {
+        let mut libraries: Vec<PathBuf> = roots
+            .iter()
+            .flat_map(|root| {
+                find_bundles(
+                    root,
+                    depth,
+                    self.config.follow_symlinks,
+                    self.config.io_timeout,
+                )
+            })
+            .filter(|bundle| bundle.kind == BundleKind::Vst2)
+            .map(|bundle| bundle.path)
+            .collect();
+        libraries.sort();
+        libraries.dedup();
+
+        let mut plugins = Vec::new();
+        for path in libraries {
+            match describe_library(&path, filter) {
+                Ok(found) => plugins.extend(found),
+                Err(error) => events::emit(HostEvent::Error {
+                    info: None,
+                    error: &error,
+                }),
+            }
+        }
+        Ok(plugins)
+    }
+
+    /// Every path [`scan()`](PluginScanner::scan) searches
+    fn search_paths(&self) -> Vec<PathBuf> {
+        let mut paths = if self.config.skip_default_paths {
+            Vec::new()
+        } else {
+            Self::default_paths()
+        };
+        paths.extend(self.config.extra_paths.iter().cloned());
+        paths
+    }
+}
+
+/// List the plugins in the library at `path`
+fn describe_library(path: &Path, filter: Option<&ScanFilter>) -> Result<Vec<PluginInfo>> {
+    // UTF-8 on Windows, raw bytes elsewhere
+    let c_path = paths::to_ffi_bytes(path)?;
+    let c_path = c_path.as_ptr();
+
+    let infos = unsafe {
+        // First pass: get count
+        let count = ffi::rack_vst2_describe(c_path, std::ptr::null_mut(), 0);
+        if count < 0 {
+            return Err(map_error(count));
+        }
+        let count = usize::try_from(count)
+            .map_err(|_| Error::Other("Plugin count exceeds usize".to_string()))?;
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+
+        // Second pass: fill array
+        let mut infos: Vec<MaybeUninit<ffi::RackVST2PluginInfo>> = Vec::with_capacity(count);
+        infos.resize_with(count, MaybeUninit::uninit);
+        let actual = ffi::rack_vst2_describe(
+            c_path,
+            infos.as_mut_ptr() as *mut ffi::RackVST2PluginInfo,
+            count,
+        );
+        if actual < 0 {
+            return Err(map_error(actual));
+        }
+
+        // A shell may list a different number of plugins the second time
+        let valid = (actual as usize).min(count);
+        infos
+            .into_iter()
+            .take(valid)
+            // Safety: C++ has written the first `valid` elements
+            .map(|info| info.assume_init())
+            .collect::<Vec<_>>()
+    };
+
+    let canonical_path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut plugins = Vec::new();
+    for info in &infos {
+        if let Some(plugin) = convert_plugin_info(path, info, filter)? {
+            plugins.push(plugin.with_canonical_path(canonical_path.clone()));
+        }
+    }
+    Ok(plugins)
+}
+
+/// Convert C plugin info to Rust PluginInfo
+///
+/// Returns `Ok(None)` if the plugin is rejected by `filter`. Plugins that
+/// report no name are named after their library.
+fn convert_plugin_info(
+    path: &Path,
+    c_info: &ffi::RackVST2PluginInfo,
+    filter: Option<&ScanFilter>,
+) -> Result<Option<PluginInfo>> {
+    let string = |field: &[c_char], name: &str| {
+        // Bounded: only look for the terminator within the array
+        let bytes: Vec<u8> = field
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| c as u8)
+            .collect();
+        decode_name(&bytes, name)
+    };
+
+    let mut name = string(&c_info.name, "plugin name")?;
+    if name.is_empty() {
+        name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+    }
+    let manufacturer = string(&c_info.manufacturer, "manufacturer")?;
+
+    let plugin_type = match c_info.plugin_type {
+        ffi::RackVST2PluginType::Effect => PluginType::Effect,
+        ffi::RackVST2PluginType::Instrument => PluginType::Instrument,
+        ffi::RackVST2PluginType::Analyzer => PluginType::Analyzer,
+        ffi::RackVST2PluginType::Spatial => PluginType::Spatial,
+        ffi::RackVST2PluginType::Other => PluginType::Other,
+    };
+
+    if let Some(filter) = filter {
+        if !filter.matches_fields(&name, &manufacturer, plugin_type) {
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(
+        PluginInfo::new(
+            name,
+            manufacturer,
+            c_info.version as u32,
+            plugin_type,
+            path.to_path_buf(),
+            c_info.unique_id.to_string(),
+        )
+        .with_format(PluginFormat::Vst2),
+    ))
+}
+
+impl PluginScanner for Vst2Scanner {
+    type Plugin = Vst2Plugin;
+
+    fn with_config(config: ScannerConfig) -> Result<Self> {
+        Ok(Self {
+            usage: None,
+            config,
+        })
+    }
+
+    fn config(&self) -> &ScannerConfig {
+        &self.config
+    }
+
+    /// The folders VST2 installers conventionally use, plus any listed in
+    /// [`VST_PATH_ENV`]
+    fn default_paths() -> Vec<PathBuf> {
+        let var = |name: &str| {
+            std::env::var_os(name)
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from)
+        };
+
+        let mut paths = Vec::new();
+        if cfg!(windows) {
+            for dir in [var("PROGRAMFILES"), var("COMMONPROGRAMFILES")]
+                .into_iter()
+                .flatten()
+            {
+                paths.push(dir.join("VSTPlugins"));
+                paths.push(dir.join(r"Steinberg\VSTPlugins"));
+                paths.push(dir.join("VST2"));
+            }
+        } else if cfg!(target_vendor = "apple") {
+            paths.push(PathBuf::from("/Library/Audio/Plug-Ins/VST"));
+            paths.extend(var("HOME").map(|home| home.join("Library/Audio/Plug-Ins/VST")));
+        } else {
+            paths.extend(var("HOME").map(|home| home.join(".vst")));
+            paths.push(PathBuf::from("/usr/lib/vst"));
+            paths.push(PathBuf::from("/usr/local/lib/vst"));
+        }
+
+        if let Some(list) = std::env::var_os(VST_PATH_ENV) {
+            paths.extend(std::env::split_paths(&list).filter(|p| p.is_absolute()));
+        }
+        paths
+    }
+
+    fn add_path(&mut self, path: &Path) -> Result<()> {
+        self.config.extra_paths.push(path.to_path_buf());
+        Ok(())
+    }
+
+    fn scan(&self) -> Result<Vec<PluginInfo>> {
+        let depth = self.config.scan_depth.max(SEARCH_DEPTH);
+        self.scan_roots(&self.search_paths(), depth, None)
+    }
+
+    fn scan_filtered(&self, filter: &ScanFilter) -> Result<Vec<PluginInfo>> {
+        let depth = self.config.scan_depth.max(SEARCH_DEPTH);
+        self.scan_roots(&self.search_paths(), depth, Some(filter))
+    }
+
+    fn scan_path(&self, path: &Path) -> Result<Vec<PluginInfo>> {
+        self.scan_roots(&[path.to_path_buf()], self.config.scan_depth, None)
+    }
+
+    fn scan_path_filtered(&self, path: &Path, filter: &ScanFilter) -> Result<Vec<PluginInfo>> {
+        self.scan_roots(&[path.to_path_buf()], self.config.scan_depth, Some(filter))
+    }
+
+    fn load(&self, info: &PluginInfo) -> Result<Self::Plugin> {
+        let plugin = Vst2Plugin::new(info)?;
+        metadata::record_load(self.usage.as_ref(), &info.unique_id);
+        events::emit(HostEvent::PluginLoaded { info });
+        Ok(plugin)
+    }
+
+    fn usage_store(&self) -> Option<&SharedMetadataStore> {
+        self.usage.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_paths() {
+        let paths = Vst2Scanner::default_paths();
+        assert!(
+            !paths.is_empty(),
+            "Every desktop platform has default VST2 paths"
+        );
+        assert!(paths.iter().all(|p| p.is_absolute()));
+    }
+
+    #[test]
+    fn test_scan_skips_non_plugins() {
+        let dir = std::env::temp_dir().join(format!("rack-vst2-scan-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("not-a-plugin.dll"), b"not a library").unwrap();
+        std::fs::write(dir.join("not-a-plugin.so"), b"not a library").unwrap();
+
+        let config = ScannerConfig::new().path(&dir).skip_default_paths(true);
+        let scanner = Vst2Scanner::with_config(config).unwrap();
+        assert!(scanner.scan().unwrap().is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,71 @@
+//! Shared utilities for VST2 FFI interop
+
+use crate::{text, Error, Result};
+use std::ffi::c_char;
+
+use super::ffi;
+
+/// Convert C API error code to Rust Error
+///
+/// The C API returns negative error codes for errors
+pub(crate) fn map_error(code: i32) -> Error {
+    match code {
+        ffi::RACK_VST2_ERROR_GENERIC => Error::Other("Generic VST2 error".to_string()),
+        ffi::RACK_VST2_ERROR_NOT_FOUND => {
+            Error::PluginNotFound("VST2 plugin not found".to_string())
+        }
+        ffi::RACK_VST2_ERROR_INVALID_PARAM => Error::Other("Invalid parameter".to_string()),
+        ffi::RACK_VST2_ERROR_NOT_INITIALIZED => Error::NotInitialized,
+        ffi::RACK_VST2_ERROR_BUFFER_TOO_SMALL => {
+            Error::Other("Data kept changing size".to_string())
+        }
+        ffi::RACK_VST2_ERROR_LOAD_FAILED => Error::Other("Failed to load VST2 plugin".to_string()),
+        ffi::RACK_VST2_ERROR_NOT_SUPPORTED => {
+            Error::Other("Feature not supported by this plugin".to_string())
+        }
+        _ => Error::Other(format!("Unknown VST2 error code: {}", code)),
+    }
+}
+
+/// Read a display name through a length-negotiating FFI call
+///
+/// Never truncates. Invalid UTF-8 (common in older VST2 plugins, which often
+/// use the system code page) is handled according to the configured
+/// [`StringDecoding`](crate::text::StringDecoding).
+pub(crate) fn negotiated_name<F>(field_name: &str, call: F) -> Result<String>
+where
+    F: FnMut(*mut c_char, *mut usize) -> i32,
+{
+    let (_, bytes) =
+        text::read_negotiated(ffi::RACK_VST2_ERROR_BUFFER_TOO_SMALL, call).map_err(map_error)?;
+    text::decode_name(&bytes, field_name)
+}
+
+/// Read binary data through a length-negotiating FFI call
+///
+/// Retries if the data grows between the size query and the copy.
+pub(crate) fn negotiated_bytes<F>(mut call: F) -> Result<Vec<u8>>
+where
+    F: FnMut(*mut u8, *mut usize) -> i32,
+{
+    const ATTEMPTS: usize = 4;
+
+    let mut size = 0usize;
+    let result = call(std::ptr::null_mut(), &mut size);
+    if result != ffi::RACK_VST2_OK {
+        return Err(map_error(result));
+    }
+    for _ in 0..ATTEMPTS {
+        let mut data = vec![0u8; size];
+        let result = call(data.as_mut_ptr(), &mut size);
+        match result {
+            ffi::RACK_VST2_OK => {
+                data.truncate(size);
+                return Ok(data);
+            }
+            ffi::RACK_VST2_ERROR_BUFFER_TOO_SMALL => continue,
+            _ => return Err(map_error(result)),
+        }
+    }
+    Err(map_error(ffi::RACK_VST2_ERROR_BUFFER_TOO_SMALL))
+}
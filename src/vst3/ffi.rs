@@ -5,6 +5,7 @@
 
 #![allow(dead_code)]
 
+use std::ffi::c_void;
 use std::os::raw::{c_char, c_int};
 
 // Opaque types (zero-sized to prevent construction)
@@ -63,6 +64,86 @@ pub const RACK_VST3_ERROR_INVALID_PARAM: c_int = -3;
 pub const RACK_VST3_ERROR_NOT_INITIALIZED: c_int = -4;
 pub const RACK_VST3_ERROR_LOAD_FAILED: c_int = -5;
 pub const RACK_VST3_ERROR_NOT_SUPPORTED: c_int = -6;
+pub const RACK_VST3_ERROR_BUFFER_TOO_SMALL: c_int = -7;
+
+// Tail length of plugins whose tail never ends
+pub const RACK_VST3_INFINITE_TAIL: u32 = u32::MAX;
+
+// String fields for rack_vst3_scanner_plugin_string
+pub const RACK_VST3_FIELD_NAME: c_int = 0;
+pub const RACK_VST3_FIELD_MANUFACTURER: c_int = 1;
+pub const RACK_VST3_FIELD_PATH: c_int = 2;
+pub const RACK_VST3_FIELD_CATEGORY: c_int = 3;
+pub const RACK_VST3_FIELD_CANONICAL_PATH: c_int = 4;
+pub const RACK_VST3_FIELD_VENDOR_URL: c_int = 5;
+pub const RACK_VST3_FIELD_VENDOR_EMAIL: c_int = 6;
+pub const RACK_VST3_FIELD_SDK_VERSION: c_int = 7;
+
+// Flags for rack_vst3_scanner_plugin_flags
+pub const RACK_VST3_FACTORY_CLASSES_DISCARDABLE: u32 = 1 << 0;
+pub const RACK_VST3_FACTORY_LICENSE_CHECK: u32 = 1 << 1;
+pub const RACK_VST3_FACTORY_COMPONENT_NON_DISCARDABLE: u32 = 1 << 3;
+pub const RACK_VST3_FACTORY_UNICODE: u32 = 1 << 4;
+pub const RACK_VST3_CLASS_DISTRIBUTABLE: u32 = 1 << 0;
+pub const RACK_VST3_CLASS_SIMPLE_MODE_SUPPORTED: u32 = 1 << 1;
+
+// Steinberg::Vst::ParameterInfo::ParameterFlags (from ivsteditcontroller.h)
+pub const VST3_PARAMETER_FLAG_IS_HIDDEN: u32 = 1 << 4;
+pub const VST3_PARAMETER_FLAG_IS_BYPASS: u32 = 1 << 16;
+
+// Component handler notifications (RackVST3ComponentEvent::kind)
+pub const RACK_VST3_COMPONENT_BEGIN_EDIT: i32 = 0;
+pub const RACK_VST3_COMPONENT_PERFORM_EDIT: i32 = 1;
+pub const RACK_VST3_COMPONENT_END_EDIT: i32 = 2;
+pub const RACK_VST3_COMPONENT_RESTART: i32 = 3;
+pub const RACK_VST3_COMPONENT_DIRTY: i32 = 4;
+
+// Steinberg::Vst::RestartFlags (RackVST3ComponentEvent::restart_flags)
+pub const RACK_VST3_RESTART_RELOAD_COMPONENT: i32 = 1;
+pub const RACK_VST3_RESTART_IO_CHANGED: i32 = 2;
+pub const RACK_VST3_RESTART_PARAM_VALUES_CHANGED: i32 = 4;
+pub const RACK_VST3_RESTART_LATENCY_CHANGED: i32 = 8;
+pub const RACK_VST3_RESTART_PARAM_TITLES_CHANGED: i32 = 16;
+
+// Steinberg::Vst::FrameRate::FrameRateFlags (rack_vst3_plugin_set_timecode)
+pub const RACK_VST3_FRAME_RATE_PULL_DOWN: u32 = 1 << 0;
+pub const RACK_VST3_FRAME_RATE_DROP: u32 = 1 << 1;
+
+/// Notification from the plugin's edit controller
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RackVST3ComponentEvent {
+    pub kind: i32,
+    pub param_index: i32,
+    pub param_id: u32,
+    pub value: f64,
+    pub restart_flags: i32,
+    pub latency: u32,
+}
+
+/// Callback receiving component handler notifications
+///
+/// Invoked on whatever thread the plugin calls its component handler from,
+/// usually the editor's UI thread.
+pub type RackVST3ComponentCallback =
+    extern "C" fn(user_data: *mut c_void, event: *const RackVST3ComponentEvent);
+
+/// Callback receiving serialized state in chunks
+///
+/// Returns 0 to continue, non-zero to abort.
+pub type RackVST3StateWriteCallback =
+    extern "C" fn(user_data: *mut c_void, data: *const u8, size: usize) -> c_int;
+
+/// Callback supplying serialized state as the plugin reads it
+///
+/// Stores the number of bytes filled in `bytes_read` (0 at the end) and
+/// returns 0, or returns non-zero on failure.
+pub type RackVST3StateReadCallback = extern "C" fn(
+    user_data: *mut c_void,
+    data: *mut u8,
+    size: usize,
+    bytes_read: *mut usize,
+) -> c_int;
 
 extern "C" {
     // ============================================================================
@@ -106,6 +187,21 @@ extern "C" {
     /// - `path` must remain valid for the duration of the call
     pub fn rack_vst3_scanner_add_path(scanner: *mut RackVST3Scanner, path: *const c_char) -> c_int;
 
+    /// Add a search path for VST3 plugins, given as UTF-16
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `scanner` must be a valid pointer returned by `rack_vst3_scanner_new`
+    /// - `path` must be a valid null-terminated UTF-16 string
+    /// - `path` must remain valid for the duration of the call
+    #[cfg(windows)]
+    pub fn rack_vst3_scanner_add_path_w(scanner: *mut RackVST3Scanner, path: *const u16) -> c_int;
+
     /// Add system default VST3 search paths
     ///
     /// # Returns
@@ -118,6 +214,44 @@ extern "C" {
     /// - `scanner` must be a valid pointer returned by `rack_vst3_scanner_new`
     pub fn rack_vst3_scanner_add_default_paths(scanner: *mut RackVST3Scanner) -> c_int;
 
+    /// Get one of the system default VST3 search paths
+    ///
+    /// Length-negotiated, like [`rack_vst3_scanner_plugin_string`]. The path is
+    /// UTF-8.
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - `RACK_VST3_ERROR_NOT_FOUND` if `index` is past the last path
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `size` must be a valid pointer to a usize
+    /// - `buffer` can be NULL to query the required size, or must point to a
+    ///   buffer of at least `*size` bytes
+    pub fn rack_vst3_default_path(index: usize, buffer: *mut c_char, size: *mut usize) -> c_int;
+
+    /// Set scanning options
+    ///
+    /// - `include_system_paths`: non-zero to also scan the platform's default
+    ///   locations and the modules the SDK discovers (the default)
+    /// - `follow_symlinks`: non-zero to follow symbolic links to bundles (the default)
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `scanner` must be a valid pointer returned by `rack_vst3_scanner_new`
+    pub fn rack_vst3_scanner_set_options(
+        scanner: *mut RackVST3Scanner,
+        include_system_paths: c_int,
+        follow_symlinks: c_int,
+    ) -> c_int;
+
     /// Scan for plugins
     ///
     /// Two-pass usage pattern:
@@ -148,10 +282,74 @@ extern "C" {
         max_plugins: usize,
     ) -> c_int;
 
+    /// Get a string field of a plugin from the last filling scan, without truncation
+    ///
+    /// Length-negotiated: with a NULL `buffer`, `*size` receives the required
+    /// size including the null terminator. If the buffer is too small, nothing
+    /// is copied and `RACK_VST3_ERROR_BUFFER_TOO_SMALL` is returned with the required
+    /// size in `*size`.
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `scanner` must be a valid pointer returned by `rack_vst3_scanner_new`
+    /// - `index` must be less than the number of plugins filled in by the last scan
+    /// - `field` must be one of the `RACK_VST3_FIELD_*` constants
+    /// - `size` must be a valid pointer to a usize
+    /// - `buffer` can be NULL to query the required size, or must point to a
+    ///   buffer of at least `*size` bytes
+    pub fn rack_vst3_scanner_plugin_string(
+        scanner: *mut RackVST3Scanner,
+        index: usize,
+        field: c_int,
+        buffer: *mut c_char,
+        size: *mut usize,
+    ) -> c_int;
+
+    /// Get the factory and class flags of a plugin from the last filling scan
+    ///
+    /// `factory_flags` receives `RACK_VST3_FACTORY_*` bits, `class_flags`
+    /// `RACK_VST3_CLASS_*` bits.
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `scanner` must be a valid pointer returned by `rack_vst3_scanner_new`
+    /// - `index` must be less than the number of plugins filled in by the last scan
+    /// - `factory_flags` and `class_flags` must be valid pointers to u32
+    pub fn rack_vst3_scanner_plugin_flags(
+        scanner: *mut RackVST3Scanner,
+        index: usize,
+        factory_flags: *mut u32,
+        class_flags: *mut u32,
+    ) -> c_int;
+
     // ============================================================================
     // Plugin Instance API
     // ============================================================================
 
+    /// Set the host name reported to plugins through `IHostApplication::getName()`
+    ///
+    /// Applies to plugin instances created afterwards.
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `name` must be a valid null-terminated UTF-8 C string
+    pub fn rack_vst3_set_host_name(name: *const c_char) -> c_int;
+
     /// Create a new plugin instance from path and UID
     ///
     /// # Safety
@@ -163,6 +361,18 @@ extern "C" {
     /// - Returned pointer must be freed with `rack_vst3_plugin_free`
     pub fn rack_vst3_plugin_new(path: *const c_char, uid: *const c_char) -> *mut RackVST3Plugin;
 
+    /// Create a new plugin instance from a UTF-16 path and UID
+    ///
+    /// # Safety
+    ///
+    /// - `path` must be a valid null-terminated UTF-16 string pointing to .vst3 bundle
+    /// - `uid` must be a valid null-terminated C string with VST3 UID (from scan)
+    /// - Both strings must remain valid for the duration of the call
+    /// - Returns NULL if plugin not found or allocation fails
+    /// - Returned pointer must be freed with `rack_vst3_plugin_free`
+    #[cfg(windows)]
+    pub fn rack_vst3_plugin_new_w(path: *const u16, uid: *const c_char) -> *mut RackVST3Plugin;
+
     /// Free plugin instance
     ///
     /// # Safety
@@ -253,6 +463,33 @@ extern "C" {
     /// - Should be called after `rack_vst3_plugin_initialize`
     pub fn rack_vst3_plugin_get_output_channels(plugin: *mut RackVST3Plugin) -> c_int;
 
+    /// Get processing latency in samples (`IAudioProcessor::getLatencySamples`)
+    ///
+    /// # Returns
+    ///
+    /// - Latency in samples (>= 0)
+    /// - 0 if not initialized
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_vst3_plugin_new`
+    /// - Should be called after `rack_vst3_plugin_initialize`
+    pub fn rack_vst3_plugin_get_latency(plugin: *mut RackVST3Plugin) -> c_int;
+
+    /// Get tail length in samples (`IAudioProcessor::getTailSamples`)
+    ///
+    /// # Returns
+    ///
+    /// - Tail in samples
+    /// - [`RACK_VST3_INFINITE_TAIL`] for a tail that never ends
+    /// - 0 if not initialized or the plugin has no tail
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_vst3_plugin_new`
+    /// - Should be called after `rack_vst3_plugin_initialize`
+    pub fn rack_vst3_plugin_get_tail_samples(plugin: *mut RackVST3Plugin) -> u32;
+
     /// Process audio through the plugin (planar format)
     ///
     /// Uses planar (non-interleaved) audio format - one buffer per channel.
@@ -287,6 +524,73 @@ extern "C" {
         frames: u32,
     ) -> c_int;
 
+    /// Set the timeline position of the next processed sample
+    ///
+    /// `process` reports the position to the plugin and advances it by the
+    /// number of frames processed.
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code if `position` is negative
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer
+    /// - Must not be called concurrently with `rack_vst3_plugin_process`
+    pub fn rack_vst3_plugin_set_sample_position(plugin: *mut RackVST3Plugin, position: i64) -> c_int;
+
+    /// Set the video frame rate and SMPTE offset reported to the plugin
+    ///
+    /// `process` reports them in the process context. `flags` is a
+    /// combination of the `RACK_VST3_FRAME_RATE_*` flags;
+    /// `smpte_offset_subframes` is the timecode of the project start in 1/80
+    /// frames.
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code if `frames_per_second` is 0
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer
+    /// - Must not be called concurrently with `rack_vst3_plugin_process`
+    pub fn rack_vst3_plugin_set_timecode(
+        plugin: *mut RackVST3Plugin,
+        frames_per_second: u32,
+        flags: u32,
+        smpte_offset_subframes: i32,
+    ) -> c_int;
+
+    /// Set the transport reported to the plugin
+    ///
+    /// `process` reports it in the process context until it is set again.
+    /// Positions are in quarter notes; the cycle is only reported if
+    /// `cycling` is non-zero.
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code if the tempo or time signature is invalid
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer
+    /// - Must not be called concurrently with `rack_vst3_plugin_process`
+    pub fn rack_vst3_plugin_set_transport(
+        plugin: *mut RackVST3Plugin,
+        playing: c_int,
+        tempo: f64,
+        numerator: i32,
+        denominator: i32,
+        beat: f64,
+        bar_start_beat: f64,
+        cycling: c_int,
+        cycle_start_beat: f64,
+        cycle_end_beat: f64,
+    ) -> c_int;
+
     /// Get parameter count
     ///
     /// # Returns
@@ -335,6 +639,28 @@ extern "C" {
         value: f32,
     ) -> c_int;
 
+    /// Schedule a parameter change at a sample offset within the next
+    /// processed block
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_vst3_plugin_new`
+    /// - `index` must be less than parameter count
+    /// - `value` should be in range 0.0-1.0 (values outside may be clamped)
+    /// - `sample_offset` should be less than the next block's frame count
+    /// - Must not be called concurrently with process()
+    pub fn rack_vst3_plugin_set_parameter_at(
+        plugin: *mut RackVST3Plugin,
+        index: u32,
+        value: f32,
+        sample_offset: u32,
+    ) -> c_int;
+
     /// Get parameter info (name, min, max, default, unit)
     ///
     /// # Returns
@@ -364,6 +690,70 @@ extern "C" {
         unit_size: usize,
     ) -> c_int;
 
+    /// Get parameter flags (raw `Steinberg::Vst::ParameterInfo::flags`)
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success (flags written to `flags` pointer)
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_vst3_plugin_new`
+    /// - `index` must be less than parameter count
+    /// - `flags` must be a valid pointer to a u32
+    pub fn rack_vst3_plugin_parameter_flags(
+        plugin: *mut RackVST3Plugin,
+        index: u32,
+        flags: *mut u32,
+    ) -> c_int;
+
+    /// Get a parameter name without truncation
+    ///
+    /// Length-negotiated, like [`rack_vst3_scanner_plugin_string`].
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_vst3_plugin_new`
+    /// - `index` must be less than parameter count
+    /// - `size` must be a valid pointer to a usize
+    /// - `buffer` can be NULL to query the required size, or must point to a
+    ///   buffer of at least `*size` bytes
+    pub fn rack_vst3_plugin_parameter_name(
+        plugin: *mut RackVST3Plugin,
+        index: u32,
+        buffer: *mut c_char,
+        size: *mut usize,
+    ) -> c_int;
+
+    /// Get a parameter's unit string without truncation
+    ///
+    /// Length-negotiated, like [`rack_vst3_scanner_plugin_string`].
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_vst3_plugin_new`
+    /// - `index` must be less than parameter count
+    /// - `size` must be a valid pointer to a usize
+    /// - `buffer` can be NULL to query the required size, or must point to a
+    ///   buffer of at least `*size` bytes
+    pub fn rack_vst3_plugin_parameter_unit(
+        plugin: *mut RackVST3Plugin,
+        index: u32,
+        buffer: *mut c_char,
+        size: *mut usize,
+    ) -> c_int;
+
     // ============================================================================
     // Preset Management API
     // ============================================================================
@@ -419,6 +809,74 @@ extern "C" {
     /// - `preset_number` should be a valid preset number from get_preset_info
     pub fn rack_vst3_plugin_load_preset(plugin: *mut RackVST3Plugin, preset_number: i32) -> c_int;
 
+    /// Get a factory preset name without truncation
+    ///
+    /// Length-negotiated, like [`rack_vst3_scanner_plugin_string`].
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_vst3_plugin_new`
+    /// - `index` must be less than preset count
+    /// - `size` must be a valid pointer to a usize
+    /// - `buffer` can be NULL to query the required size, or must point to a
+    ///   buffer of at least `*size` bytes
+    pub fn rack_vst3_plugin_preset_name(
+        plugin: *mut RackVST3Plugin,
+        index: u32,
+        buffer: *mut c_char,
+        size: *mut usize,
+    ) -> c_int;
+
+    /// Get the currently loaded preset
+    ///
+    /// # Returns
+    ///
+    /// - 1 if the plugin reports a current preset (`name` and `preset_number` are filled in)
+    /// - 0 if it doesn't
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_vst3_plugin_new`
+    /// - Plugin must be initialized
+    /// - `name` must point to a buffer of at least `name_size` bytes
+    /// - `preset_number` must be a valid pointer
+    pub fn rack_vst3_plugin_get_current_preset(
+        plugin: *mut RackVST3Plugin,
+        name: *mut c_char,
+        name_size: usize,
+        preset_number: *mut i32,
+    ) -> c_int;
+
+    /// Get the name of the current preset without truncation
+    ///
+    /// Length-negotiated, like [`rack_vst3_scanner_plugin_string`]. `*size` is
+    /// set to 0 if there is no current preset.
+    ///
+    /// # Returns
+    ///
+    /// - 1 if the plugin reports a current preset
+    /// - 0 if it doesn't
+    /// - Negative error code on failure
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_vst3_plugin_new`
+    /// - Plugin must be initialized
+    /// - `size` must be a valid pointer to a usize
+    /// - `buffer` can be NULL to query the required size, or must point to a
+    ///   buffer of at least `*size` bytes
+    pub fn rack_vst3_plugin_current_preset_name(
+        plugin: *mut RackVST3Plugin,
+        buffer: *mut c_char,
+        size: *mut usize,
+    ) -> c_int;
+
     /// Get plugin state size (for allocation)
     ///
     /// # Returns
@@ -474,6 +932,45 @@ extern "C" {
         size: usize,
     ) -> c_int;
 
+    /// Get plugin state, passing it to `callback` in chunks
+    ///
+    /// The state is serialized once, so unlike `get_state_size()` followed by
+    /// `get_state()` the size can't change in between.
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure, including when `callback` aborts
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_vst3_plugin_new`
+    /// - Plugin must be initialized
+    /// - `user_data` is passed to `callback` unchanged and must be valid for it
+    pub fn rack_vst3_plugin_get_state_stream(
+        plugin: *mut RackVST3Plugin,
+        callback: RackVST3StateWriteCallback,
+        user_data: *mut c_void,
+    ) -> c_int;
+
+    /// Set plugin state, pulling it from `callback` as the plugin reads
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - Negative error code on failure, including when `callback` fails
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_vst3_plugin_new`
+    /// - Plugin must be initialized
+    /// - `user_data` is passed to `callback` unchanged and must be valid for it
+    pub fn rack_vst3_plugin_set_state_stream(
+        plugin: *mut RackVST3Plugin,
+        callback: RackVST3StateReadCallback,
+        user_data: *mut c_void,
+    ) -> c_int;
+
     // ============================================================================
     // MIDI API
     // ============================================================================
@@ -496,6 +993,49 @@ extern "C" {
         events: *const RackVST3MidiEvent,
         event_count: u32,
     ) -> c_int;
+
+    // ============================================================================
+    // Component Handler API
+    // ============================================================================
+
+    /// Set the callback receiving component handler notifications
+    ///
+    /// Pass `None` to remove it. Once this returns, the previous callback is
+    /// no longer running or invoked.
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success
+    /// - RACK_VST3_ERROR_NOT_SUPPORTED if the plugin has no edit controller
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_vst3_plugin_new`
+    /// - `user_data` must stay valid until the callback is replaced or the
+    ///   plugin is freed
+    /// - Must not be called from inside the callback
+    pub fn rack_vst3_plugin_set_component_callback(
+        plugin: *mut RackVST3Plugin,
+        callback: Option<RackVST3ComponentCallback>,
+        user_data: *mut c_void,
+    ) -> c_int;
+
+    /// Apply the changes the plugin requested with `restartComponent()`
+    ///
+    /// Bus and latency changes reactivate the plugin and re-read its channel
+    /// counts; parameter title changes rebuild the parameter cache.
+    ///
+    /// # Returns
+    ///
+    /// - 0 on success, with the applied `RACK_VST3_RESTART_*` flags in `flags`
+    /// - Negative error code on failure (the plugin is left uninitialized)
+    ///
+    /// # Safety
+    ///
+    /// - `plugin` must be a valid pointer returned by `rack_vst3_plugin_new`
+    /// - `flags` must be a valid pointer
+    /// - Must not be called concurrently with process()
+    pub fn rack_vst3_plugin_apply_restart(plugin: *mut RackVST3Plugin, flags: *mut i32) -> c_int;
 }
 
 // MIDI event struct (matches C layout exactly)
@@ -1,5 +1,9 @@
-use crate::{Error, PluginInfo, PluginScanner, PluginType, Result};
-use std::ffi::CString;
+use crate::events::{self, HostEvent};
+use crate::metadata::{self, SharedMetadataStore};
+use crate::paths;
+use crate::scan::{find_bundles, BundleKind, ScanFilter, ScannerConfig};
+use crate::{Error, PluginFormat, PluginInfo, PluginScanner, PluginType, Result, Vst3FactoryInfo};
+use std::ffi::c_char;
 use std::marker::PhantomData;
 use std::mem::MaybeUninit;
 use std::path::{Path, PathBuf};
@@ -7,10 +11,14 @@ use std::ptr::NonNull;
 
 use super::ffi;
 use super::instance::Vst3Plugin;
-use super::util::{c_array_to_string, map_error};
+use super::util::{c_array_to_string, map_error, negotiated_name, negotiated_path};
 
 /// Scanner for VST3 plugins
 ///
+/// Bundles that ship a `moduleinfo.json` are listed from that file without
+/// loading their binary; others fall back to loading the module and
+/// enumerating its factory.
+///
 /// # Thread Safety
 ///
 /// This type is `Send` but not `Sync`:
@@ -23,6 +31,8 @@ pub struct Vst3Scanner {
     // PhantomData<*const ()> makes this type !Sync while keeping it Send
     // This prevents concurrent access without Arc<Mutex<>>
     _not_sync: PhantomData<*const ()>,
+    usage: Option<SharedMetadataStore>,
+    config: ScannerConfig,
 }
 
 // Safety: Vst3Scanner can be sent between threads because:
@@ -36,66 +46,92 @@ unsafe impl Send for Vst3Scanner {}
 impl Vst3Scanner {
     /// Create a new VST3 scanner
     ///
+    /// Searches the default system paths plus any listed in `RACK_PLUGIN_PATH`
+    /// (see [`ScannerConfig::from_env()`]).
+    ///
     /// # Errors
     ///
     /// Returns an error if scanner allocation fails
     pub fn new() -> Result<Self> {
-        unsafe {
-            let ptr = ffi::rack_vst3_scanner_new();
-            if ptr.is_null() {
-                return Err(Error::Other("Failed to allocate VST3 scanner".to_string()));
-            }
-
-            // Add default system paths
-            let result = ffi::rack_vst3_scanner_add_default_paths(ptr);
-            if result != ffi::RACK_VST3_OK {
-                // Clean up scanner before returning error
-                ffi::rack_vst3_scanner_free(ptr);
-                return Err(map_error(result));
-            }
-
-            Ok(Self {
-                inner: NonNull::new(ptr).expect("pointer is non-null after null check"),
-                _not_sync: PhantomData,
-            })
-        }
+        Self::with_config(ScannerConfig::from_env())
     }
 
-    /// Create a new VST3 scanner without adding default paths
+    /// Create a new VST3 scanner that only searches `path`
     ///
     /// This is useful for scanning specific paths without system defaults.
     ///
     /// # Errors
     ///
-    /// Returns an error if scanner allocation fails
-    fn new_empty() -> Result<Self> {
-        unsafe {
-            let ptr = ffi::rack_vst3_scanner_new();
-            if ptr.is_null() {
-                return Err(Error::Other("Failed to allocate VST3 scanner".to_string()));
-            }
+    /// Returns an error if scanner allocation fails or the path is invalid
+    fn new_for_path(path: &Path, follow_symlinks: bool) -> Result<Self> {
+        let config = ScannerConfig::new()
+            .path(path)
+            .skip_default_paths(true)
+            .follow_symlinks(follow_symlinks);
+        Self::with_config(config)
+    }
 
-            Ok(Self {
-                inner: NonNull::new(ptr).expect("pointer is non-null after null check"),
-                _not_sync: PhantomData,
-            })
+    /// Scan `path` and, with a scan depth, the folders below it
+    ///
+    /// Deep scans find the bundles first and hand the folders holding them to
+    /// the C++ scanner. A path that is itself a bundle is scanned alone.
+    fn scan_path_plugins(
+        &self,
+        path: &Path,
+        filter: Option<&ScanFilter>,
+    ) -> Result<Vec<PluginInfo>> {
+        let follow_symlinks = self.config.follow_symlinks;
+        if self.config.scan_depth == 0 && BundleKind::from_path(path).is_none() {
+            return Self::new_for_path(path, follow_symlinks)?.scan_plugins(filter);
+        }
+
+        let bundles = find_bundles(
+            path,
+            self.config.scan_depth,
+            follow_symlinks,
+            self.config.io_timeout,
+        );
+        let mut folders: Vec<&Path> = bundles
+            .iter()
+            .filter(|b| b.kind == BundleKind::Vst3)
+            .filter_map(|b| b.path.parent())
+            .collect();
+        folders.sort();
+        folders.dedup();
+        if folders.is_empty() {
+            return Ok(Vec::new());
         }
+
+        let mut config = ScannerConfig::new()
+            .skip_default_paths(true)
+            .follow_symlinks(follow_symlinks);
+        config.extra_paths = folders.iter().map(|f| f.to_path_buf()).collect();
+        let mut plugins = Self::with_config(config)?.scan_plugins(filter)?;
+
+        // Every bundle in the other folders was found; a bundle's siblings
+        // weren't asked for
+        if BundleKind::from_path(path).is_some() {
+            plugins.retain(|p| p.path.file_name() == path.file_name());
+        }
+        Ok(plugins)
     }
 
-    /// Add a custom search path for VST3 plugins
+    /// Pass a search path to the C++ scanner
     ///
-    /// # Errors
-    ///
-    /// Returns an error if the path is invalid or cannot be added
-    pub fn add_path(&mut self, path: &Path) -> Result<()> {
-        let path_str = path.to_str()
-            .ok_or_else(|| Error::Other("Path contains invalid UTF-8".to_string()))?;
-
-        let path_cstr = CString::new(path_str)
-            .map_err(|_| Error::Other("Path contains null byte".to_string()))?;
+    /// On Windows the path is passed as UTF-16 in extended-length form, so
+    /// bundles under non-ASCII folders or nested past `MAX_PATH` can be found;
+    /// elsewhere its raw bytes are passed (see [`paths`]).
+    fn add_ffi_path(&mut self, path: &Path) -> Result<()> {
+        #[cfg(windows)]
+        let path = paths::to_ffi_wide(path)?;
+        #[cfg(not(windows))]
+        let path = paths::to_ffi_bytes(path)?;
 
         unsafe {
-            let result = ffi::rack_vst3_scanner_add_path(self.inner.as_ptr(), path_cstr.as_ptr());
+            #[cfg(windows)]
+            let result = ffi::rack_vst3_scanner_add_path_w(self.inner.as_ptr(), path.as_ptr());
+            #[cfg(not(windows))]
+            let result = ffi::rack_vst3_scanner_add_path(self.inner.as_ptr(), path.as_ptr());
             if result != ffi::RACK_VST3_OK {
                 return Err(map_error(result));
             }
@@ -104,8 +140,16 @@ impl Vst3Scanner {
         Ok(())
     }
 
+    /// Record every successful [`load()`](PluginScanner::load) in `store`
+    ///
+    /// Enables [`most_used()`](PluginScanner::most_used). Usage tracking is off
+    /// until this is called.
+    pub fn track_usage(&mut self, store: SharedMetadataStore) {
+        self.usage = Some(store);
+    }
+
     /// Scan for VST3 plugins
-    fn scan_plugins(&self) -> Result<Vec<PluginInfo>> {
+    fn scan_plugins(&self, filter: Option<&ScanFilter>) -> Result<Vec<PluginInfo>> {
         unsafe {
             // First pass: get count
             let count = ffi::rack_vst3_scanner_scan(self.inner.as_ptr(), std::ptr::null_mut(), 0);
@@ -151,11 +195,13 @@ impl Vst3Scanner {
             let plugins = plugins_c
                 .into_iter()
                 .take(valid_count)
-                .map(|p| {
+                .enumerate()
+                .map(|(index, p)| {
                     // Safety: C++ has written valid data to these elements
                     let plugin_info = p.assume_init();
-                    convert_plugin_info(&plugin_info)
+                    convert_plugin_info(self.inner.as_ptr(), index, &plugin_info, filter)
                 })
+                .filter_map(|r| r.transpose())
                 .collect::<Result<Vec<_>>>()?;
 
             Ok(plugins)
@@ -164,15 +210,27 @@ impl Vst3Scanner {
 }
 
 /// Convert C plugin info to Rust PluginInfo
-fn convert_plugin_info(c_info: &ffi::RackVST3PluginInfo) -> Result<PluginInfo> {
+///
+/// Returns `Ok(None)` if the plugin is rejected by `filter`. The filter is checked
+/// before the path and unique ID are converted.
+///
+/// Name, manufacturer and path are read from the scanner at full length rather
+/// than from the fixed-size fields of `c_info`, which may be truncated.
+fn convert_plugin_info(
+    scanner: *mut ffi::RackVST3Scanner,
+    index: usize,
+    c_info: &ffi::RackVST3PluginInfo,
+    filter: Option<&ScanFilter>,
+) -> Result<Option<PluginInfo>> {
     unsafe {
-        // Use bounded string conversion for safety
-        // This protects against C++ bugs (missing null-termination)
-        // by only searching for null within the fixed array bounds
-        let name = c_array_to_string(&c_info.name, "plugin name")?;
-        let manufacturer = c_array_to_string(&c_info.manufacturer, "manufacturer")?;
-        let path_str = c_array_to_string(&c_info.path, "path")?;
-        let unique_id = c_array_to_string(&c_info.unique_id, "unique_id")?;
+        let field = |field: std::ffi::c_int| {
+            move |buffer: *mut c_char, size: *mut usize| {
+                ffi::rack_vst3_scanner_plugin_string(scanner, index, field, buffer, size)
+            }
+        };
+        let (_, name) = negotiated_name("plugin name", field(ffi::RACK_VST3_FIELD_NAME))?;
+        let (_, manufacturer) =
+            negotiated_name("manufacturer", field(ffi::RACK_VST3_FIELD_MANUFACTURER))?;
 
         // Convert plugin type
         let plugin_type = match c_info.plugin_type {
@@ -183,14 +241,60 @@ fn convert_plugin_info(c_info: &ffi::RackVST3PluginInfo) -> Result<PluginInfo> {
             ffi::RackVST3PluginType::Other => PluginType::Other,
         };
 
-        Ok(PluginInfo::new(
+        if let Some(filter) = filter {
+            if !filter.matches_fields(&name, &manufacturer, plugin_type) {
+                return Ok(None);
+            }
+        }
+
+        let path = negotiated_path(field(ffi::RACK_VST3_FIELD_PATH))?;
+        let canonical_path = negotiated_path(field(ffi::RACK_VST3_FIELD_CANONICAL_PATH))?;
+
+        // Use bounded string conversion for safety
+        // This protects against C++ bugs (missing null-termination)
+        // by only searching for null within the fixed array bounds
+        let unique_id = c_array_to_string(&c_info.unique_id, "unique_id")?;
+
+        let (_, url) = negotiated_name("vendor URL", field(ffi::RACK_VST3_FIELD_VENDOR_URL))?;
+        let (_, email) = negotiated_name("vendor email", field(ffi::RACK_VST3_FIELD_VENDOR_EMAIL))?;
+        let (_, sdk_version) =
+            negotiated_name("SDK version", field(ffi::RACK_VST3_FIELD_SDK_VERSION))?;
+        let mut factory_flags = 0u32;
+        let mut class_flags = 0u32;
+        let result = ffi::rack_vst3_scanner_plugin_flags(
+            scanner,
+            index,
+            &mut factory_flags,
+            &mut class_flags,
+        );
+        if result != ffi::RACK_VST3_OK {
+            return Err(map_error(result));
+        }
+        let factory = Vst3FactoryInfo {
+            url,
+            email,
+            sdk_version,
+            distributable: class_flags & ffi::RACK_VST3_CLASS_DISTRIBUTABLE != 0,
+            simple_mode_supported: class_flags & ffi::RACK_VST3_CLASS_SIMPLE_MODE_SUPPORTED != 0,
+            classes_discardable: factory_flags & ffi::RACK_VST3_FACTORY_CLASSES_DISCARDABLE != 0,
+            license_check: factory_flags & ffi::RACK_VST3_FACTORY_LICENSE_CHECK != 0,
+            component_non_discardable: factory_flags
+                & ffi::RACK_VST3_FACTORY_COMPONENT_NON_DISCARDABLE
+                != 0,
+            unicode: factory_flags & ffi::RACK_VST3_FACTORY_UNICODE != 0,
+        };
+
+        Ok(Some(PluginInfo::new(
             name,
             manufacturer,
             c_info.version,
             plugin_type,
-            PathBuf::from(path_str),
+            path,
             unique_id,
-        ))
+        )
+        .with_format(PluginFormat::Vst3)
+        .with_canonical_path(canonical_path)
+        .with_vst3_factory(factory)))
     }
 }
 
@@ -205,29 +309,106 @@ impl Drop for Vst3Scanner {
 impl PluginScanner for Vst3Scanner {
     type Plugin = Vst3Plugin;
 
+    fn with_config(config: ScannerConfig) -> Result<Self> {
+        let mut scanner = unsafe {
+            let ptr = ffi::rack_vst3_scanner_new();
+            if ptr.is_null() {
+                return Err(Error::Other("Failed to allocate VST3 scanner".to_string()));
+            }
+
+            // Owned from here on, so errors below free the C++ scanner
+            Self {
+                inner: NonNull::new(ptr).expect("pointer is non-null after null check"),
+                _not_sync: PhantomData,
+                usage: None,
+                config: ScannerConfig::new(),
+            }
+        };
+
+        unsafe {
+            let ptr = scanner.inner.as_ptr();
+            let result = ffi::rack_vst3_scanner_set_options(
+                ptr,
+                !config.skip_default_paths as i32,
+                config.follow_symlinks as i32,
+            );
+            if result != ffi::RACK_VST3_OK {
+                return Err(map_error(result));
+            }
+
+            if !config.skip_default_paths {
+                // Add default system paths
+                let result = ffi::rack_vst3_scanner_add_default_paths(ptr);
+                if result != ffi::RACK_VST3_OK {
+                    return Err(map_error(result));
+                }
+            }
+        }
+
+        for path in &config.extra_paths {
+            scanner.add_ffi_path(path)?;
+        }
+        scanner.config = config;
+        Ok(scanner)
+    }
+
+    fn config(&self) -> &ScannerConfig {
+        &self.config
+    }
+
+    fn default_paths() -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        for index in 0.. {
+            let call = |buffer: *mut c_char, size: *mut usize| unsafe {
+                ffi::rack_vst3_default_path(index, buffer, size)
+            };
+            match negotiated_path(call) {
+                Ok(path) => paths.push(path),
+                Err(_) => break,
+            }
+        }
+        paths
+    }
+
+    fn add_path(&mut self, path: &Path) -> Result<()> {
+        self.add_ffi_path(path)?;
+        self.config.extra_paths.push(path.to_path_buf());
+        Ok(())
+    }
+
     fn scan(&self) -> Result<Vec<PluginInfo>> {
-        self.scan_plugins()
+        self.scan_plugins(None)
     }
 
-    fn scan_path(&self, path: &Path) -> Result<Vec<PluginInfo>> {
-        // Create a scanner without default paths for path-specific scanning
-        let mut scanner = Self::new_empty()?;
+    fn scan_filtered(&self, filter: &ScanFilter) -> Result<Vec<PluginInfo>> {
+        self.scan_plugins(Some(filter))
+    }
 
-        // Add only the requested path
-        scanner.add_path(path)?;
+    fn scan_path(&self, path: &Path) -> Result<Vec<PluginInfo>> {
+        // Scanners without default paths do the path-specific scanning
+        self.scan_path_plugins(path, None)
+    }
 
-        // Scan
-        scanner.scan_plugins()
+    fn scan_path_filtered(&self, path: &Path, filter: &ScanFilter) -> Result<Vec<PluginInfo>> {
+        self.scan_path_plugins(path, Some(filter))
     }
 
     fn load(&self, info: &PluginInfo) -> Result<Self::Plugin> {
-        Vst3Plugin::new(info)
+        let plugin = Vst3Plugin::new(info)?;
+        metadata::record_load(self.usage.as_ref(), &info.unique_id);
+        events::emit(HostEvent::PluginLoaded { info });
+        Ok(plugin)
+    }
+
+    fn usage_store(&self) -> Option<&SharedMetadataStore> {
+        self.usage.as_ref()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::path::PathBuf;
 
     #[test]
     fn test_scanner_creation() {
@@ -284,6 +465,13 @@ mod tests {
         assert_eq!(count1, count2, "Multiple scans should return same count");
     }
 
+    #[test]
+    fn test_default_paths() {
+        let paths = Vst3Scanner::default_paths();
+        assert!(!paths.is_empty(), "Every desktop platform has default VST3 paths");
+        assert!(paths.iter().all(|p| p.is_absolute()));
+    }
+
     #[test]
     fn test_plugin_info_fields() {
         let scanner = Vst3Scanner::new().expect("Scanner creation should succeed");
@@ -295,9 +483,18 @@ mod tests {
             assert!(!plugin.manufacturer.is_empty(), "Manufacturer should not be empty");
             assert!(!plugin.unique_id.is_empty(), "Unique ID should not be empty");
             assert!(plugin.path.as_os_str().len() > 0, "Path should not be empty");
+            assert!(plugin.vst3_factory.is_some(), "VST3 plugins should report factory info");
         }
     }
 
+    #[test]
+    fn test_scan_filtered() {
+        let scanner = Vst3Scanner::new().expect("Scanner creation should succeed");
+        let filter = ScanFilter::new().plugin_type(PluginType::Instrument);
+        let plugins = scanner.scan_filtered(&filter).expect("Scan should succeed");
+        assert!(plugins.iter().all(|p| p.plugin_type == PluginType::Instrument));
+    }
+
     #[test]
     fn test_add_path() {
         let mut scanner = Vst3Scanner::new().expect("Scanner creation should succeed");
@@ -306,4 +503,19 @@ mod tests {
         let result = scanner.add_path(path);
         assert!(result.is_ok(), "Adding path should succeed");
     }
+
+    #[test]
+    fn test_skip_default_paths() {
+        let dir = std::env::temp_dir().join(format!("rack-vst3-empty-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let config = ScannerConfig::new().path(&dir).skip_default_paths(true);
+        let mut scanner = Vst3Scanner::with_config(config).expect("Scanner creation should succeed");
+        assert!(scanner.scan().expect("Scan should succeed").is_empty());
+
+        scanner.add_path(Path::new("/tmp")).unwrap();
+        assert_eq!(scanner.config().extra_paths, vec![dir.clone(), PathBuf::from("/tmp")]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -1,9 +1,11 @@
 //! Shared utilities for VST3 FFI interop
 
-use crate::{Error, Result};
-use std::ffi::CStr;
+use crate::{Error, ParameterVisibility, Result};
+use std::ffi::{c_char, CStr};
+use std::path::PathBuf;
 
 use super::ffi;
+use crate::{paths, text};
 
 /// Convert C API error code to Rust Error
 ///
@@ -14,12 +16,22 @@ pub(crate) fn map_error(code: i32) -> Error {
         ffi::RACK_VST3_ERROR_NOT_FOUND => Error::PluginNotFound("VST3 plugin not found".to_string()),
         ffi::RACK_VST3_ERROR_INVALID_PARAM => Error::Other("Invalid parameter".to_string()),
         ffi::RACK_VST3_ERROR_NOT_INITIALIZED => Error::NotInitialized,
+        ffi::RACK_VST3_ERROR_BUFFER_TOO_SMALL => Error::Other("String kept changing size".to_string()),
         ffi::RACK_VST3_ERROR_LOAD_FAILED => Error::Other("Failed to load VST3 plugin".to_string()),
         ffi::RACK_VST3_ERROR_NOT_SUPPORTED => Error::Other("Feature not supported by this plugin".to_string()),
         _ => Error::Other(format!("Unknown VST3 error code: {}", code)),
     }
 }
 
+/// Map VST3 parameter flags to a ParameterVisibility
+pub(crate) fn parameter_visibility_from_flags(flags: u32) -> ParameterVisibility {
+    if flags & ffi::VST3_PARAMETER_FLAG_IS_HIDDEN != 0 {
+        ParameterVisibility::Hidden
+    } else {
+        ParameterVisibility::Visible
+    }
+}
+
 /// Safely convert a fixed-size C char array to a Rust String
 ///
 /// This uses bounded string conversion to prevent UB even if the C++ code
@@ -45,3 +57,30 @@ pub(crate) unsafe fn c_array_to_string(arr: &[i8], field_name: &str) -> Result<S
         .map_err(|e| Error::Other(format!("Invalid UTF-8 in {}: {}", field_name, e)))
         .map(|s| s.to_string())
 }
+
+/// Read a display name through a length-negotiating FFI call
+///
+/// Never truncates. Invalid UTF-8 is handled according to the configured
+/// [`StringDecoding`](crate::text::StringDecoding) instead of always failing.
+/// Returns the call's non-negative result code along with the name.
+pub(crate) fn negotiated_name<F>(field_name: &str, call: F) -> Result<(i32, String)>
+where
+    F: FnMut(*mut c_char, *mut usize) -> i32,
+{
+    let (code, bytes) =
+        text::read_negotiated(ffi::RACK_VST3_ERROR_BUFFER_TOO_SMALL, call).map_err(map_error)?;
+    Ok((code, text::decode_name(&bytes, field_name)?))
+}
+
+/// Read a path through a length-negotiating FFI call
+///
+/// Never truncates. The bytes are converted with the platform's path rules
+/// (see [`paths`](crate::paths)), so non-UTF-8 paths survive on Unix.
+pub(crate) fn negotiated_path<F>(call: F) -> Result<PathBuf>
+where
+    F: FnMut(*mut c_char, *mut usize) -> i32,
+{
+    let (_, bytes) =
+        text::read_negotiated(ffi::RACK_VST3_ERROR_BUFFER_TOO_SMALL, call).map_err(map_error)?;
+    paths::from_ffi_bytes(bytes)
+}